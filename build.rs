@@ -1,4 +1,10 @@
 fn main() {
+    #[cfg(feature = "gui")]
+    compile_ui();
+}
+
+#[cfg(feature = "gui")]
+fn compile_ui() {
     // Compile Slint UI with bundled translations for runtime language switch
     let config = slint_build::CompilerConfiguration::default()
         .with_bundled_translations("i18n");
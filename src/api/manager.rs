@@ -0,0 +1,379 @@
+//! Library workflows without a window: the same services the editor calls,
+//! behind one handle that owns the database.
+
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use std::path::Path;
+
+use crate::db::repositories::{BranchCategoryRepo, BranchRepo, FormationLevelRepo, LibraryRepo, LibraryStats, UnitRepo};
+use crate::db::Database;
+use crate::export::{self, export_full_to_path, AnonymizeOptions, ExportOptions};
+use crate::import::{self, LibraryImport};
+use crate::models::{
+    units_as_of, Branch, BranchCategory, CustomFormationLevel, Equipment, Library, Personnel, Snapshot, TreeLimits, Unit,
+};
+use crate::services::{save_branches, save_editor_rows, EditorRows, LibraryService, RowChanges, UnitService};
+
+/// File formats [`LibraryManager::export_library_as`] writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Enveloped JSON export of the library and its units
+    Json,
+    /// Full JSON export, with formation levels, branch categories and branches
+    Full,
+    Yaml,
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    /// Format by name: `json`, `full`, `yaml`/`yml`, `csv` or `xlsx`
+    pub fn from_name(name: &str) -> Result<Self> {
+        Ok(match name.to_lowercase().as_str() {
+            "json" => Self::Json,
+            "full" => Self::Full,
+            "yaml" | "yml" => Self::Yaml,
+            "csv" => Self::Csv,
+            "xlsx" => Self::Xlsx,
+            other => bail!("Unsupported export format: {}", other),
+        })
+    }
+
+    /// Format for a path by its extension; `.json` (or none) is [`Self::Json`]
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension() {
+            None => Ok(Self::Json),
+            Some(ext) => Self::from_name(&ext.to_string_lossy())
+                .map_err(|_| anyhow::anyhow!("Unsupported export file extension: .{}", ext.to_string_lossy())),
+        }
+    }
+}
+
+/// Create, edit, snapshot, export and import libraries of one database.
+///
+/// ```no_run
+/// use toeditor::api::LibraryManager;
+/// use toeditor::models::Library;
+///
+/// let manager = LibraryManager::open("toeditor.db")?;
+/// let library = manager.create_library(Library::new("Army 1943".into(), "SU".into(), "1943".into(), "".into()))?;
+/// let id = library.id.unwrap();
+/// manager.add_unit(id, None, "Rifle Regiment", "Regiment")?;
+/// manager.create_snapshot(id, "First draft")?;
+/// manager.export_library(id, "army-1943.yaml".as_ref())?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct LibraryManager {
+    db: Database,
+    tree_limits: TreeLimits,
+}
+
+impl LibraryManager {
+    /// Manage the libraries of an open database
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            tree_limits: TreeLimits::default(),
+        }
+    }
+
+    /// Open (creating and migrating as needed) the database at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(Database::open(path)?))
+    }
+
+    /// Like [`Self::open`], but fails instead of creating a missing file
+    pub fn open_existing<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(Database::open_existing(path)?))
+    }
+
+    /// Manage a new database that lives only in memory
+    pub fn open_in_memory() -> Result<Self> {
+        Ok(Self::new(Database::open_in_memory()?))
+    }
+
+    /// Refuse unit trees past `limits` when adding units or importing
+    pub fn with_tree_limits(mut self, limits: TreeLimits) -> Self {
+        self.tree_limits = limits;
+        self
+    }
+
+    /// Like [`Self::with_tree_limits`], on a manager in use
+    pub fn set_tree_limits(&mut self, limits: TreeLimits) {
+        self.tree_limits = limits;
+    }
+
+    /// The managed database, for what the manager does not cover
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    fn libraries_service(&self) -> LibraryService<'_> {
+        LibraryService::new(self.db.conn()).with_tree_limits(self.tree_limits)
+    }
+
+    fn units_service(&self) -> UnitService<'_> {
+        UnitService::new(self.db.conn()).with_tree_limits(self.tree_limits)
+    }
+
+    // Libraries
+
+    /// Store a new library with its units. Fails when one with the same
+    /// name, country and era exists.
+    pub fn create_library(&self, library: Library) -> Result<Library> {
+        self.libraries_service().create_library_with_units(library)
+    }
+
+    /// The library with its unit tree
+    pub fn library(&self, id: i64) -> Result<Library> {
+        self.libraries_service().library_with_units(id)
+    }
+
+    /// The library with only the units valid on `as_of`; every unit with `None`
+    pub fn library_as_of(&self, id: i64, as_of: Option<NaiveDate>) -> Result<Library> {
+        let mut library = self.library(id)?;
+        if let Some(date) = as_of {
+            library.units = units_as_of(&library.units, date);
+        }
+        Ok(library)
+    }
+
+    /// All libraries, metadata only (units are not loaded)
+    pub fn libraries(&self) -> Result<Vec<Library>> {
+        self.libraries_service().list_libraries()
+    }
+
+    /// Counts and dates of a library, as its properties dialog shows them
+    pub fn stats(&self, library_id: i64) -> Result<LibraryStats> {
+        LibraryRepo::new(self.db.conn()).stats(library_id)
+    }
+
+    /// Libraries matching `query` the way the library search box matches them
+    pub fn search(&self, query: &str) -> Result<Vec<Library>> {
+        self.libraries_service().search_libraries(query)
+    }
+
+    /// Save the library's metadata, storing a snapshot of it when `snapshot` is set
+    pub fn save_library(&self, library: Library, snapshot: bool) -> Result<Library> {
+        self.libraries_service().save_library(library, snapshot)
+    }
+
+    /// Delete the library with its units, history and per-library lists
+    pub fn delete_library(&self, id: i64) -> Result<()> {
+        self.libraries_service().delete_library(id)
+    }
+
+    // Units
+
+    /// Add a unit as the last child of `parent_id`, or after the library's
+    /// top-level units when `None`
+    pub fn add_unit(&self, library_id: i64, parent_id: Option<i64>, name: &str, unit_type: &str) -> Result<Unit> {
+        let service = self.units_service();
+        let Some(parent_id) = parent_id else {
+            return service.add_root(library_id, name, unit_type);
+        };
+        match UnitRepo::new(self.db.conn()).get_placement(parent_id)? {
+            Some((parent_library, _)) if parent_library == library_id => service.add_child(parent_id, name, unit_type),
+            _ => bail!("Unit {} is not in library {}", parent_id, library_id),
+        }
+    }
+
+    /// The unit with its subordinates, personnel and equipment
+    pub fn unit(&self, unit_id: i64) -> Result<Unit> {
+        UnitRepo::new(self.db.conn())
+            .get_by_id(unit_id)?
+            .ok_or_else(|| anyhow::anyhow!("Unit {} not found", unit_id))
+    }
+
+    /// Save a unit's name, type, effective period and TOE number
+    pub fn update_unit(&self, unit: &Unit) -> Result<()> {
+        self.units_service().update_properties(unit)
+    }
+
+    /// Replace a unit's personnel and equipment lists. Rows keep the stored
+    /// row at the same position, so only the rows that differ are written;
+    /// returns how many were.
+    pub fn set_unit_rows(&self, unit_id: i64, personnel: &[Personnel], equipment: &[Equipment]) -> Result<RowChanges> {
+        let repo = UnitRepo::new(self.db.conn());
+        let personnel = with_stored_ids(repo.list_personnel_rows(unit_id)?, personnel);
+        let equipment = with_stored_ids(repo.list_equipment_rows(unit_id)?, equipment);
+        self.units_service().save_unit_rows(unit_id, &personnel, &equipment)
+    }
+
+    /// Delete a unit with all of its subordinates; how many units were removed
+    pub fn delete_unit(&self, unit_id: i64) -> Result<usize> {
+        self.units_service().delete_subtree(unit_id)
+    }
+
+    // History
+
+    /// Store the library's current state as its next version
+    pub fn create_snapshot(&self, library_id: i64, description: &str) -> Result<Snapshot> {
+        self.libraries_service().create_snapshot(library_id, description)
+    }
+
+    /// Store the library's current state as an autosave of its version,
+    /// keeping the newest `keep` autosaves
+    pub fn create_autosave_snapshot(&self, library_id: i64, keep: usize) -> Result<Snapshot> {
+        self.libraries_service().with_autosave_keep(keep).create_autosave_snapshot(library_id)
+    }
+
+    /// The library's versions, newest first
+    pub fn versions(&self, library_id: i64) -> Result<Vec<Snapshot>> {
+        self.libraries_service().get_library_versions(library_id)
+    }
+
+    /// Bring the library back to `version`, itself stored as a new version
+    pub fn restore_version(&self, library_id: i64, version: i64) -> Result<Snapshot> {
+        self.libraries_service().restore_version(library_id, version)
+    }
+
+    /// Like [`Self::restore_version`], describing the new version as a revert
+    pub fn revert_to_version(&self, library_id: i64, version: i64) -> Result<Snapshot> {
+        self.libraries_service().revert_to_version(library_id, version)
+    }
+
+    /// Copy `version` of the library into a new library "<name> (vN)"
+    pub fn fork_from_version(&self, library_id: i64, version: i64) -> Result<Library> {
+        self.libraries_service().fork_from_version(library_id, version)
+    }
+
+    /// Bring the library back to its autosave `snapshot_id`, stored as a new version
+    pub fn restore_autosave(&self, library_id: i64, snapshot_id: i64) -> Result<Snapshot> {
+        self.libraries_service().restore_autosave(library_id, snapshot_id)
+    }
+
+    // Export and import
+
+    /// Write the library to `path` in the format of its extension: `.json`,
+    /// `.yaml`/`.yml`, `.csv` or `.xlsx`
+    pub fn export_library(&self, library_id: i64, path: &Path) -> Result<()> {
+        self.export_library_as(library_id, path, ExportFormat::from_path(path)?)
+    }
+
+    /// Write the library to `path` in `format`, whatever the extension
+    pub fn export_library_as(&self, library_id: i64, path: &Path, format: ExportFormat) -> Result<()> {
+        self.export_library_as_of(library_id, path, format, None)
+    }
+
+    /// Like [`Self::export_library_as`], with only the units valid on `as_of`
+    /// in YAML, CSV and workbook files; JSON exports keep every unit
+    pub fn export_library_as_of(&self, library_id: i64, path: &Path, format: ExportFormat, as_of: Option<NaiveDate>) -> Result<()> {
+        match format {
+            ExportFormat::Json => self.export_json(library_id, path, &ExportOptions::default()),
+            ExportFormat::Full => self.export_full(library_id, path),
+            ExportFormat::Yaml => export::export_yaml(&self.library_as_of(library_id, as_of)?, path),
+            ExportFormat::Csv => export::export_csv(&self.library_as_of(library_id, as_of)?, path),
+            ExportFormat::Xlsx => {
+                let library = self.library_as_of(library_id, as_of)?;
+                export::export_xlsx(&library, &library.units, None, path)
+            }
+        }
+    }
+
+    /// Write the library as an enveloped JSON export
+    pub fn export_json(&self, library_id: i64, path: &Path, options: &ExportOptions) -> Result<()> {
+        self.libraries_service().export_json(library_id, path, options)
+    }
+
+    /// Write the library as a full export with the author and notes
+    /// stripped, for sharing publicly
+    pub fn export_anonymized(&self, library_id: i64, path: &Path, options: &AnonymizeOptions) -> Result<()> {
+        self.libraries_service().export_anonymized(library_id, path, options)
+    }
+
+    /// Write the library with its formation levels, branch categories and
+    /// branches as a full export
+    pub fn export_full(&self, library_id: i64, path: &Path) -> Result<()> {
+        export_full_to_path(&self.libraries_service().export_full(library_id)?, path)
+    }
+
+    /// Store the library in a JSON, YAML or Excel file as a new library.
+    /// Workbook warnings are logged; the rows they name are skipped.
+    pub fn import_library(&self, path: &Path) -> Result<Library> {
+        let is_xlsx = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("xlsx"));
+        let import = if is_xlsx {
+            let preview = import::xlsx::import_xlsx(path)?;
+            for warning in &preview.warnings {
+                log::warn!("{}: {}", path.display(), warning);
+            }
+            LibraryImport::Plain {
+                library: preview.library,
+                versions: Vec::new(),
+            }
+        } else {
+            import::read_library_file(path)?
+        };
+        self.import(import)
+    }
+
+    /// Store a library already read from a file as a new library
+    pub fn import(&self, import: LibraryImport) -> Result<Library> {
+        self.libraries_service().import(import)
+    }
+
+    // Per-library lists
+
+    /// The library's branches of service, in list order
+    pub fn branches(&self, library_id: i64) -> Result<Vec<Branch>> {
+        BranchRepo::new(self.db.conn()).list_by_library(library_id)
+    }
+
+    /// Replace the library's branches of service
+    pub fn save_branches(&self, library_id: i64, branches: Vec<Branch>) -> Result<()> {
+        save_branches(self.db.conn(), library_id, &branches)
+    }
+
+    /// The library's branch categories, in list order
+    pub fn branch_categories(&self, library_id: i64) -> Result<Vec<BranchCategory>> {
+        BranchCategoryRepo::new(self.db.conn()).list_by_library(library_id)
+    }
+
+    /// Replace the library's branch categories; branches of removed
+    /// categories are left without one
+    pub fn save_branch_categories(&self, library_id: i64, categories: Vec<BranchCategory>) -> Result<()> {
+        save_editor_rows(self.db.conn(), library_id, &EditorRows::BranchCategories(categories))
+    }
+
+    /// The library's formation levels, in list order
+    pub fn formation_levels(&self, library_id: i64) -> Result<Vec<CustomFormationLevel>> {
+        FormationLevelRepo::new(self.db.conn()).list_by_library(library_id)
+    }
+
+    /// Replace the library's formation levels
+    pub fn save_formation_levels(&self, library_id: i64, levels: Vec<CustomFormationLevel>) -> Result<()> {
+        save_editor_rows(self.db.conn(), library_id, &EditorRows::FormationLevels(levels))
+    }
+}
+
+/// Pair rows with the ids of the stored rows at the same positions; rows
+/// past the stored ones are new
+fn with_stored_ids<T: Clone>(stored: Vec<(i64, T)>, rows: &[T]) -> Vec<(Option<i64>, T)> {
+    let ids = stored.into_iter().map(|(id, _)| Some(id)).chain(std::iter::repeat(None));
+    ids.zip(rows.iter().cloned()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_format_by_name_and_extension() {
+        assert_eq!(ExportFormat::from_name("YML").unwrap(), ExportFormat::Yaml);
+        assert_eq!(ExportFormat::from_name("full").unwrap(), ExportFormat::Full);
+        assert!(ExportFormat::from_name("pdf").is_err());
+        assert_eq!(ExportFormat::from_path(Path::new("army.xlsx")).unwrap(), ExportFormat::Xlsx);
+        assert_eq!(ExportFormat::from_path(Path::new("army")).unwrap(), ExportFormat::Json);
+        assert!(ExportFormat::from_path(Path::new("army.txt")).is_err());
+    }
+
+    #[test]
+    fn test_with_stored_ids_pairs_by_position() {
+        let stored = vec![(7, "a"), (9, "b")];
+        assert_eq!(with_stored_ids(stored.clone(), &["x"]), [(Some(7), "x")]);
+        assert_eq!(
+            with_stored_ids(stored, &["x", "y", "z"]),
+            [(Some(7), "x"), (Some(9), "y"), (None, "z")]
+        );
+    }
+}
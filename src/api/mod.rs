@@ -0,0 +1,140 @@
+//! Access to a TOEditor database for other programs and build scripts:
+//! [`LibraryManager`] runs the editor's library workflows, [`read_only`]
+//! only reads.
+//!
+//! Available without the `gui` feature:
+//!
+//! ```no_run
+//! let handle = toeditor::api::read_only("toeditor.db")?;
+//! for library in handle.libraries()? {
+//!     println!("{} ({} units)", library.name, handle.summary(library.id.unwrap())?.unit_count);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+mod manager;
+
+pub use manager::{ExportFormat, LibraryManager};
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::db::repositories::{EquipmentCatalogRepo, UnitRepo};
+use crate::db::Database;
+use crate::models::{walk_units, EquipmentAliases, Library, Unit};
+use crate::services::LibraryService;
+
+/// Open the database at `path` for reading. Nothing is migrated or written.
+pub fn read_only<P: AsRef<Path>>(path: P) -> Result<ReadOnlyHandle> {
+    Ok(ReadOnlyHandle {
+        db: Mutex::new(Database::open_read_only(path)?),
+    })
+}
+
+/// Read-only handle to a database; `Send + Sync`, so it can be shared between threads
+pub struct ReadOnlyHandle {
+    db: Mutex<Database>,
+}
+
+/// Totals of one library
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibrarySummary {
+    pub id: i64,
+    pub name: String,
+    pub country: String,
+    pub era: String,
+    pub version: i64,
+    /// Units at every level of the tree
+    pub unit_count: usize,
+    /// Personnel positions across all units
+    pub personnel: usize,
+    /// Equipment quantity by name across all units; names the equipment
+    /// catalog knows as aliases count under the catalog name
+    pub equipment: BTreeMap<String, usize>,
+}
+
+impl ReadOnlyHandle {
+    fn db(&self) -> MutexGuard<'_, Database> {
+        // A panic in another reader cannot leave a read-only connection inconsistent
+        self.db.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// All libraries, metadata only (units are not loaded)
+    pub fn libraries(&self) -> Result<Vec<Library>> {
+        let db = self.db();
+        LibraryService::new(db.conn()).list_libraries()
+    }
+
+    /// The library in the full export format (same JSON as File → Export → Full)
+    pub fn library_json(&self, id: i64) -> Result<String> {
+        let db = self.db();
+        let export = LibraryService::new(db.conn()).export_full(id)?;
+        Ok(serde_json::to_string_pretty(&export)?)
+    }
+
+    /// Root units of the library with their children, personnel and equipment
+    pub fn unit_tree(&self, id: i64) -> Result<Vec<Unit>> {
+        let db = self.db();
+        Self::library(&db, id)?;
+        UnitRepo::new(db.conn()).get_by_library_id(id)
+    }
+
+    /// Unit, personnel and equipment totals of the library
+    pub fn summary(&self, id: i64) -> Result<LibrarySummary> {
+        let db = self.db();
+        let library = Self::library(&db, id)?;
+        let units = UnitRepo::new(db.conn()).get_by_library_id(id)?;
+        let catalog = EquipmentCatalogRepo::new(db.conn()).list_by_library(id)?;
+        let aliases = EquipmentAliases::new(&catalog);
+        let mut equipment = BTreeMap::new();
+        for unit in &units {
+            for (name, quantity) in unit.total_equipment_resolved(&aliases, &library.display_language) {
+                *equipment.entry(name).or_insert(0) += quantity;
+            }
+        }
+        Ok(LibrarySummary {
+            id,
+            name: library.name,
+            country: library.country,
+            era: library.era,
+            version: library.version,
+            unit_count: units.iter().map(count_units).sum(),
+            personnel: units.iter().map(Unit::total_personnel).sum(),
+            equipment,
+        })
+    }
+
+    fn library(db: &Database, id: i64) -> Result<Library> {
+        LibraryService::new(db.conn())
+            .get_library(id)?
+            .ok_or_else(|| anyhow::anyhow!("Library {} not found", id))
+    }
+}
+
+fn count_units(unit: &Unit) -> usize {
+    walk_units(std::slice::from_ref(unit)).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ReadOnlyHandle>();
+    }
+
+    #[test]
+    fn test_count_units_includes_children() {
+        let mut root = Unit::new("Company".into(), "Company".into());
+        let mut platoon = Unit::new("1st Platoon".into(), "Platoon".into());
+        platoon.add_child(Unit::new("1st Squad".into(), "Squad".into()));
+        root.add_child(platoon);
+        root.add_child(Unit::new("HQ".into(), "Section".into()));
+        assert_eq!(count_units(&root), 4);
+    }
+}
@@ -0,0 +1,149 @@
+//! Keyboard and assistive technology support: the focus a dialog opens
+//! with, and the announcements behind the status bar
+//!
+//! Every library context menu action is also in the menu bar, so it can be
+//! reached with the keyboard; the tests below keep it that way.
+
+use std::collections::VecDeque;
+use crate::models::LibraryFieldErrors;
+use super::confirm::ConfirmSpec;
+
+/// Announcements kept for assistive technology, oldest dropped first
+const ANNOUNCEMENT_HISTORY: usize = 50;
+
+/// Status messages, newest last; the newest is the main window's
+/// `announcement`, its accessible label of the status bar
+#[derive(Debug, Default)]
+pub(crate) struct Announcements {
+    messages: VecDeque<String>,
+}
+
+impl Announcements {
+    /// Record a message as the newest announcement
+    pub(crate) fn push(&mut self, message: impl Into<String>) {
+        if self.messages.len() == ANNOUNCEMENT_HISTORY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message.into());
+    }
+
+    /// Newest announcement, if any
+    pub(crate) fn latest(&self) -> Option<&str> {
+        self.messages.back().map(String::as_str)
+    }
+}
+
+/// Field the library dialog focuses when it opens: the first one with a
+/// problem, else the name
+pub(super) fn library_dialog_focus(errors: &LibraryFieldErrors) -> &'static str {
+    if errors.name.is_some() {
+        "name"
+    } else if errors.country.is_some() {
+        "country"
+    } else if errors.era.is_some() {
+        "era"
+    } else {
+        "name"
+    }
+}
+
+/// Control the confirmation dialog focuses when it opens: the text to type
+/// if there is one, Cancel for destructive actions, else the confirm button
+pub(super) fn confirm_dialog_focus(spec: &ConfirmSpec) -> &'static str {
+    if spec.require_text_match.is_some() {
+        "typed"
+    } else if spec.danger {
+        "cancel"
+    } else {
+        "confirm"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Menu bar callback running the same action as each library context
+    /// menu callback
+    const CONTEXT_MENU_EQUIVALENTS: &[(&str, &str)] = &[
+        ("properties", "library-properties"),
+        ("export-library", "library-export-library"),
+        ("export-with-preset", "file-export-with-preset"),
+        ("manage-presets", "file-manage-export-presets"),
+        ("history", "library-view-history"),
+        ("tags", "library-manage-tags"),
+        ("delete-library", "library-delete"),
+    ];
+
+    /// Names of the callbacks declared in a Slint source
+    fn declared_callbacks(source: &str) -> BTreeSet<&str> {
+        source
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("callback "))
+            .filter_map(|rest| rest.split(['(', ';', ' ']).next())
+            .collect()
+    }
+
+    /// Callbacks run by menu bar items of the main window
+    fn menu_bar_callbacks(source: &str) -> BTreeSet<&str> {
+        source
+            .split("activated => {")
+            .skip(1)
+            .filter_map(|rest| rest.trim_start().strip_prefix("root."))
+            .filter_map(|call| call.split('(').next())
+            .collect()
+    }
+
+    #[test]
+    fn test_context_menu_actions_are_in_the_menu_bar() {
+        let context_menu = declared_callbacks(include_str!("../../ui/context_menu.slint"));
+        let menu_bar = menu_bar_callbacks(include_str!("../../ui/main_window.slint"));
+        let actions: BTreeSet<&str> = context_menu.into_iter().filter(|c| *c != "cancelled").collect();
+        let mapped: BTreeSet<&str> = CONTEXT_MENU_EQUIVALENTS.iter().map(|(c, _)| *c).collect();
+        assert_eq!(actions, mapped, "every context menu action needs a menu bar equivalent");
+        for (action, equivalent) in CONTEXT_MENU_EQUIVALENTS {
+            assert!(menu_bar.contains(equivalent), "{} has no menu bar item ({})", action, equivalent);
+        }
+    }
+
+    #[test]
+    fn test_menu_bar_callbacks_are_parsed() {
+        let menu_bar = menu_bar_callbacks(include_str!("../../ui/main_window.slint"));
+        assert!(menu_bar.contains("file-new-library"));
+        assert!(menu_bar.contains("help-about"));
+    }
+
+    #[test]
+    fn test_announcements_keep_the_newest() {
+        let mut announcements = Announcements::default();
+        assert_eq!(announcements.latest(), None);
+        for i in 0..ANNOUNCEMENT_HISTORY + 5 {
+            announcements.push(format!("message {}", i));
+        }
+        assert_eq!(announcements.messages.len(), ANNOUNCEMENT_HISTORY);
+        assert_eq!(announcements.messages.front().map(String::as_str), Some("message 5"));
+        assert_eq!(announcements.latest(), Some(format!("message {}", ANNOUNCEMENT_HISTORY + 4).as_str()));
+    }
+
+    #[test]
+    fn test_library_dialog_focuses_first_problem() {
+        let mut errors = LibraryFieldErrors::default();
+        assert_eq!(library_dialog_focus(&errors), "name");
+        errors.era = Some("bad era".into());
+        assert_eq!(library_dialog_focus(&errors), "era");
+        errors.country = Some("bad country".into());
+        assert_eq!(library_dialog_focus(&errors), "country");
+        errors.name = Some("no name".into());
+        assert_eq!(library_dialog_focus(&errors), "name");
+    }
+
+    #[test]
+    fn test_confirm_dialog_focus() {
+        let spec = ConfirmSpec::new("Title", "Message", "OK");
+        assert_eq!(confirm_dialog_focus(&spec), "confirm");
+        let danger = ConfirmSpec::new("Title", "Message", "Delete").danger();
+        assert_eq!(confirm_dialog_focus(&danger), "cancel");
+        assert_eq!(confirm_dialog_focus(&danger.require_text("name")), "typed");
+    }
+}
@@ -0,0 +1,168 @@
+//! Generic confirmation dialog shared by every "are you sure?" flow
+
+use slint::ComponentHandle;
+use std::cell::Cell;
+use std::rc::Rc;
+use super::accessibility::confirm_dialog_focus;
+use super::translations::ui_tr;
+use super::ConfirmDialog;
+
+/// What to ask. `title` and `confirm_label` are translation keys; `message`
+/// is passed through the translation table too, so callers that fill in
+/// placeholders translate and format it themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConfirmSpec {
+    pub title: String,
+    pub message: String,
+    pub confirm_label: String,
+    /// Destructive action: red accent and Return does not confirm
+    pub danger: bool,
+    /// Text the user must type before the confirm button is enabled
+    pub require_text_match: Option<String>,
+}
+
+impl ConfirmSpec {
+    pub fn new(title: &str, message: impl Into<String>, confirm_label: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.into(),
+            confirm_label: confirm_label.to_string(),
+            danger: false,
+            require_text_match: None,
+        }
+    }
+
+    pub fn danger(mut self) -> Self {
+        self.danger = true;
+        self
+    }
+
+    pub fn require_text(mut self, text: impl Into<String>) -> Self {
+        self.require_text_match = Some(text.into());
+        self
+    }
+}
+
+/// Dialog properties for a spec in the given UI language
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConfirmTexts {
+    pub title: String,
+    pub message: String,
+    pub confirm: String,
+    pub cancel: String,
+    /// Empty unless type-to-confirm is required
+    pub match_prompt: String,
+}
+
+impl ConfirmTexts {
+    pub fn from_spec(lang: &str, spec: &ConfirmSpec) -> Self {
+        let match_prompt = spec
+            .require_text_match
+            .as_deref()
+            .map(|text| ui_tr(lang, "Type \"{}\" to confirm:").replacen("{}", text, 1))
+            .unwrap_or_default();
+        Self {
+            title: ui_tr(lang, &spec.title),
+            message: ui_tr(lang, &spec.message),
+            confirm: ui_tr(lang, &spec.confirm_label),
+            cancel: ui_tr(lang, "Cancel"),
+            match_prompt,
+        }
+    }
+}
+
+/// Whether the typed text unlocks the confirm button (surrounding whitespace ignored)
+pub(crate) fn confirmation_allowed(required: Option<&str>, typed: &str) -> bool {
+    match required {
+        Some(required) => typed.trim() == required.trim(),
+        None => true,
+    }
+}
+
+/// Show a confirmation dialog; `on_result` runs once with `true` when confirmed
+/// and `false` when cancelled or closed with Escape.
+pub(crate) fn confirm(lang: &str, spec: ConfirmSpec, on_result: impl Fn(bool) + 'static) {
+    let dialog = match ConfirmDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create confirm dialog: {}", e);
+            return;
+        }
+    };
+    let texts = ConfirmTexts::from_spec(lang, &spec);
+    dialog.set_dialog_title(texts.title.into());
+    dialog.set_message(texts.message.into());
+    dialog.set_confirm_text(texts.confirm.into());
+    dialog.set_cancel_text(texts.cancel.into());
+    dialog.set_danger(spec.danger);
+    dialog.set_require_match(spec.require_text_match.is_some());
+    dialog.set_match_prompt(texts.match_prompt.into());
+    dialog.set_can_confirm(confirmation_allowed(spec.require_text_match.as_deref(), ""));
+    dialog.set_initial_focus(confirm_dialog_focus(&spec).into());
+
+    let required = spec.require_text_match.clone();
+    let weak = dialog.as_weak();
+    dialog.on_typed_text_edited(move |typed| {
+        if let Some(d) = weak.upgrade() {
+            d.set_can_confirm(confirmation_allowed(required.as_deref(), &typed));
+        }
+    });
+
+    let on_result = Rc::new(on_result);
+    let answered = Rc::new(Cell::new(false));
+    let finish = move |dialog: &ConfirmDialog, result: bool| {
+        dialog.hide().unwrap_or_default();
+        if !answered.replace(true) {
+            on_result(result);
+        }
+    };
+    let finish = Rc::new(finish);
+
+    let weak = dialog.as_weak();
+    let done = finish.clone();
+    dialog.on_confirmed(move || {
+        if let Some(d) = weak.upgrade() {
+            if d.get_can_confirm() {
+                done(&d, true);
+            }
+        }
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            finish(&d, false);
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+    dialog.invoke_focus_initial();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_texts_from_spec() {
+        let spec = ConfirmSpec::new("Delete library?", "Gone for good", "Delete").danger();
+        let texts = ConfirmTexts::from_spec("en", &spec);
+        assert_eq!(texts.title, "Delete library?");
+        assert_eq!(texts.message, "Gone for good");
+        assert_eq!(texts.confirm, "Delete");
+        assert_eq!(texts.cancel, "Cancel");
+        assert_eq!(texts.match_prompt, "");
+
+        let ru = ConfirmTexts::from_spec("ru", &spec.require_text("US Army"));
+        assert_eq!(ru.cancel, ui_tr("ru", "Cancel"));
+        assert_ne!(ru.cancel, "Cancel");
+        assert!(ru.match_prompt.contains("\"US Army\""));
+    }
+
+    #[test]
+    fn test_confirmation_allowed() {
+        assert!(confirmation_allowed(None, ""));
+        assert!(!confirmation_allowed(Some("US Army"), ""));
+        assert!(!confirmation_allowed(Some("US Army"), "us army"));
+        assert!(confirmation_allowed(Some("US Army"), " US Army "));
+    }
+}
@@ -2,12 +2,46 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
-use slint::ComponentHandle;
+use std::collections::HashMap;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel, Weak};
 
-use super::{MainWindow, LibraryDialog, AppState};
+use super::{MainWindow, LibraryDialog, LevelMappingRow, NumberingLevelRow, DuplicatePairRow, TagStateRow, AppState, notify_change, track_current_library};
+use super::accessibility::library_dialog_focus;
+use super::confirm::{confirm, ConfirmSpec};
+use super::file_dialog::{choose_file, FileRequest};
+use super::translations::{ui_tr, ui_tr_args, ui_tr_plural};
+use super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::settings::{apply_settings, database_left_by_reset};
+use super::workers::run_with_manager;
+use crate::api::LibraryManager;
+use crate::i18n::{available_languages, supported_code, DISPLAY_LANGUAGES};
+use crate::db::repositories::{FormationLevelRepo, LevelMappingEntry, LibraryRepo, LibraryStats, LibrarySummary, OrderBy, UnitTreeNode};
+use crate::export::{AnonymizeOptions, ExportOptions, PageLimit, SvgOptions, SvgOrientation};
+use crate::import::xlsx::XlsxImport;
+use crate::import::LibraryImport;
+use crate::models::{display_name, DisplayLang, tree_shape, walk_units, TreeLimitIssue, parse_effective_dates, normalize_tag, normalize_toe_number, exact_toe_match, toe_number_key, ToeNumberCollision, tag_changes, CustomFormationLevel, Library, NumberingStyle, Snapshot, TagState, Unit};
+use crate::services::{diff_snapshots_staged, ChangeEvent, DiffGranularity, DuplicatePair, LibraryService, LibraryServiceError, OperationRegistry, UnitSummary};
+use crate::services::operations::describe_running;
+use crate::services::summary::UnitSummary as UnitSummaryTable;
+
+/// Library display language for a dialog dropdown index (see `DISPLAY_LANGUAGES`)
+fn display_language_code(index: i32) -> &'static str {
+    DISPLAY_LANGUAGES
+        .get(index.max(0) as usize)
+        .copied()
+        .unwrap_or(DISPLAY_LANGUAGES[0])
+}
+
+/// Dropdown index of a library display language; unknown values map to "auto"
+fn display_language_index(code: &str) -> i32 {
+    DISPLAY_LANGUAGES
+        .iter()
+        .position(|c| *c == code)
+        .unwrap_or(0) as i32
+}
 
 /// Show library dialog for creating new library
-pub(super) fn show_library_dialog(window: &MainWindow, _mode: &str, library_id: i32) {
+pub(super) fn show_library_dialog(window: &MainWindow, _mode: &str, library_id: i32, state: &Rc<RefCell<AppState>>) {
     let dialog = match LibraryDialog::new() {
         Ok(d) => d,
         Err(e) => {
@@ -15,6 +49,7 @@ pub(super) fn show_library_dialog(window: &MainWindow, _mode: &str, library_id:
             return;
         }
     };
+    state.borrow_mut().open_dialogs.track(&dialog);
 
     // New library - set defaults
     dialog.set_library_name("".into());
@@ -22,6 +57,8 @@ pub(super) fn show_library_dialog(window: &MainWindow, _mode: &str, library_id:
     dialog.set_library_era("2024".into());
     dialog.set_library_author("".into());
     dialog.set_library_tags("".into());
+    setup_country_autocomplete(&dialog, &window.get_current_language());
+    setup_live_validation(&dialog, &window.get_current_language());
 
     let weak_dialog1 = dialog.as_weak();
     let weak_dialog2 = weak_dialog1.clone();
@@ -35,9 +72,10 @@ pub(super) fn show_library_dialog(window: &MainWindow, _mode: &str, library_id:
             let era = d.get_library_era();
             let author = d.get_library_author();
             let tags = d.get_library_tags();
+            let display_language = display_language_code(d.get_display_language_index());
 
             if let Some(w) = weak_window1.upgrade() {
-                w.invoke_library_dialog_accepted(name, country, era, author, tags, library_id);
+                w.invoke_library_dialog_accepted(name, country, era, author, tags, display_language.into(), library_id);
             }
             d.hide().unwrap_or_default();
         }
@@ -52,12 +90,118 @@ pub(super) fn show_library_dialog(window: &MainWindow, _mode: &str, library_id:
         }
     });
 
+    show_focused(&dialog);
+}
+
+/// Validate the name, country and era as they are edited, showing each
+/// problem under its field (OK stays disabled until all are fixed)
+fn setup_live_validation(dialog: &LibraryDialog, lang: &str) {
+    let lang = lang.to_string();
+    let weak = dialog.as_weak();
+    dialog.on_validate(move |name, country, era| {
+        if let Some(d) = weak.upgrade() {
+            show_field_errors(&d, &lang, &crate::models::validate_library_fields(&name, &country, &era));
+        }
+    });
+    dialog.invoke_validate(dialog.get_library_name(), dialog.get_library_country(), dialog.get_library_era());
+}
+
+/// Like [`setup_live_validation`], and also list what else the edit of
+/// `before` changes under the fields
+fn setup_impact_preview(dialog: &LibraryDialog, lang: &str, before: Library, in_recent_list: bool) {
+    let lang = lang.to_string();
+    let weak = dialog.as_weak();
+    dialog.on_validate(move |name, country, era| {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        show_field_errors(&d, &lang, &crate::models::validate_library_fields(&name, &country, &era));
+        let after = Library { name: name.to_string(), country: country.to_string(), era: era.to_string(), ..before.clone() };
+        let lines: Vec<SharedString> = crate::services::identity_change_impact(&before, &after, in_recent_list)
+            .iter()
+            .map(|i| format!("{}: {} → {}", ui_tr(&lang, i.artifact.label()), i.before, i.after).into())
+            .collect();
+        d.set_impact_title(ui_tr(&lang, "Also changes ({})").replacen("{}", &lines.len().to_string(), 1).into());
+        d.set_impact_lines(ModelRc::new(VecModel::from(lines)));
+    });
+    dialog.invoke_validate(dialog.get_library_name(), dialog.get_library_country(), dialog.get_library_era());
+}
+
+/// Lines of the library dialog's statistics section
+fn library_stats_lines(lang: &str, stats: &LibraryStats) -> Vec<SharedString> {
+    let counts = [
+        ("Units: {}", stats.units),
+        ("Personnel positions: {}", stats.personnel),
+        ("Equipment items: {}", stats.equipment),
+        ("Snapshots: {}", stats.snapshots),
+        ("Branches: {}", stats.branches),
+        ("Branch categories: {}", stats.branch_categories),
+        ("Formation levels: {}", stats.formation_levels),
+    ];
+    let dates = [("Created: {}", stats.created_at), ("Last changed: {}", stats.updated_at)];
+    counts
+        .iter()
+        .map(|(key, n)| ui_tr_args(lang, key, &[n]))
+        .chain(dates.iter().map(|(key, ts)| ui_tr_args(lang, key, &[&crate::i18n::format_timestamp(*ts)])))
+        .map(SharedString::from)
+        .collect()
+}
+
+/// Show the dialog with the focus on the first field with a problem
+fn show_focused(dialog: &LibraryDialog) {
+    let errors = crate::models::validate_library_fields(&dialog.get_library_name(), &dialog.get_library_country(), &dialog.get_library_era());
+    dialog.set_initial_focus(library_dialog_focus(&errors).into());
     dialog.show().unwrap_or_default();
+    dialog.invoke_focus_initial();
+}
+
+fn show_field_errors(dialog: &LibraryDialog, lang: &str, errors: &crate::models::LibraryFieldErrors) {
+    let text = |error: &Option<String>| -> SharedString {
+        error.as_deref().map(|e| ui_tr(lang, e)).unwrap_or_default().into()
+    };
+    dialog.set_name_error(text(&errors.name));
+    dialog.set_country_error(text(&errors.country));
+    dialog.set_era_error(text(&errors.era));
+}
+
+/// Most suggestions shown under the country field
+const COUNTRY_SUGGESTION_LIMIT: usize = 6;
+
+/// Suggest countries while the country field is edited; choosing one puts its
+/// name in the field (the code is resolved from the text when saving)
+fn setup_country_autocomplete(dialog: &LibraryDialog, lang: &str) {
+    let lang = lang.to_string();
+    let weak = dialog.as_weak();
+    dialog.on_country_edited(move |text| {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let suggestions: Vec<super::CountrySuggestion> = crate::models::suggest_countries(&text, COUNTRY_SUGGESTION_LIMIT)
+            .into_iter()
+            // Nothing to suggest once the field already holds the exact name
+            .filter(|c| c.name(&lang) != text.trim())
+            .map(|c| super::CountrySuggestion {
+                code: c.code.into(),
+                name: c.name(&lang).into(),
+            })
+            .collect();
+        d.set_country_suggestions(ModelRc::new(VecModel::from(suggestions)));
+    });
+    let weak = dialog.as_weak();
+    dialog.on_country_suggestion_chosen(move |suggestion| {
+        if let Some(d) = weak.upgrade() {
+            d.set_library_country(suggestion.name);
+            d.set_country_suggestions(ModelRc::new(VecModel::from(Vec::new())));
+            d.invoke_validate(d.get_library_name(), d.get_library_country(), d.get_library_era());
+        }
+    });
 }
 
 /// Show a simple error dialog with a message and an OK button.
-#[allow(dead_code)]
-pub(crate) fn show_error_dialog(title: &str, message: &str) {
+/// `title` and `message` are translation keys; the message's `{}`
+/// placeholders are filled with `args` (see [`ui_tr_args`]).
+pub(crate) fn show_error_dialog(lang: &str, title: &str, message: &str, args: &[&dyn std::fmt::Display]) {
+    let message = ui_tr_args(lang, message, args);
     let dialog = match super::ErrorDialog::new() {
         Ok(d) => d,
         Err(e) => {
@@ -65,7 +209,7 @@ pub(crate) fn show_error_dialog(title: &str, message: &str) {
             return;
         }
     };
-    dialog.set_dialog_title(title.into());
+    dialog.set_dialog_title(ui_tr(lang, title).into());
     dialog.set_message(message.into());
 
     let weak = dialog.as_weak();
@@ -78,58 +222,2032 @@ pub(crate) fn show_error_dialog(title: &str, message: &str) {
     dialog.show().unwrap_or_default();
 }
 
-/// Show library dialog for editing existing library
-pub(super) fn show_library_dialog_for_edit(window: &MainWindow, library_id: i32, state: Rc<RefCell<AppState>>) {
-    let dialog = match LibraryDialog::new() {
+/// Show the session performance metrics (Tools > Performance Metrics).
+pub(super) fn show_metrics_dialog(lang: &str) {
+    let dialog = match super::MetricsDialog::new() {
         Ok(d) => d,
         Err(e) => {
-            log::error!("Failed to create library dialog: {}", e);
+            log::error!("Failed to create metrics dialog: {}", e);
             return;
         }
     };
+    let stats = crate::services::metrics::session_stats();
+    dialog.set_dialog_title(ui_tr(lang, "Performance Metrics").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
+    dialog.set_report(crate::services::metrics::format_stats(&stats).into());
 
-    // Load library data for editing
-    let lib_data = {
-        let state = state.borrow();
-        state.current_library.clone()
+    let weak = dialog.as_weak();
+    dialog.on_dismissed(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::MetricsDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::MetricsDialog);
+    dialog.show().unwrap_or_default();
+}
+
+/// Unit > Summary Table: totals of a formation by rank, position and
+/// equipment, and per subordinate unit, with an export to CSV
+pub(super) fn show_summary_table_dialog(lang: &str, summary: UnitSummaryTable) {
+    let dialog = match super::SummaryTableDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create summary table dialog: {}", e);
+            return;
+        }
     };
+    dialog.set_dialog_title(ui_tr_args(lang, "Summary Table: {}", &[&summary.unit_name]).into());
+    dialog.set_total(
+        ui_tr_args(
+            lang,
+            "{} personnel, {} equipment items",
+            &[&summary.personnel, &summary.equipment_total()],
+        )
+        .into(),
+    );
+    dialog.set_rows(ModelRc::new(VecModel::from(summary_table_rows(lang, &summary))));
+    dialog.set_export_text(ui_tr(lang, "Export CSV…").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
 
-    if let Some(ref lib) = lib_data {
-        dialog.set_library_name(lib.name.clone().into());
-        dialog.set_library_country(lib.country.clone().into());
-        dialog.set_library_era(lib.era.clone().into());
-        dialog.set_library_author(lib.author.clone().into());
-        dialog.set_library_tags(lib.tags.join(", ").into());
+    let lang_export = lang.to_string();
+    dialog.on_export_csv(move || {
+        let summary = summary.clone();
+        let lang = lang_export.clone();
+        choose_file(
+            FileRequest::save()
+                .filter("CSV", &["csv"])
+                .file_name(crate::export::default_file_name(&format!("{} summary", summary.unit_name), "csv")),
+            move |path| match crate::export::export_summary_csv(&summary, &path) {
+                Ok(()) => log::info!("Summary table exported to: {:?}", path),
+                Err(e) => {
+                    log::error!("Failed to export summary table: {:#}", e);
+                    show_error_dialog(&lang, "Export Error", "{}", &[&format!("{:#}", e)]);
+                }
+            },
+        );
+    });
+    let weak = dialog.as_weak();
+    dialog.on_dismissed(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::SummaryTableDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::SummaryTableDialog);
+    dialog.show().unwrap_or_default();
+}
+
+/// Rows of the summary table: a heading for each section, then its totals
+fn summary_table_rows(lang: &str, summary: &UnitSummaryTable) -> Vec<super::SummaryTableRow> {
+    let heading = |key: &str| super::SummaryTableRow { label: ui_tr(lang, key).into(), value: "".into(), heading: true };
+    let row = |label: String, value: String| super::SummaryTableRow { label: label.into(), value: value.into(), heading: false };
+    let mut rows = vec![heading("By Rank")];
+    for r in &summary.by_rank {
+        let label = if r.name.is_empty() { ui_tr(lang, "No rank") } else { r.name.clone() };
+        rows.push(row(label, r.count.to_string()));
+    }
+    rows.push(heading("By Position"));
+    rows.extend(summary.by_position.iter().map(|r| row(r.name.clone(), r.count.to_string())));
+    rows.push(heading("Equipment"));
+    rows.extend(summary.equipment.iter().map(|r| row(r.name.clone(), r.count.to_string())));
+    if !summary.children.is_empty() {
+        rows.push(heading("Subordinate Units (personnel / equipment)"));
+        rows.extend(
+            summary
+                .children
+                .iter()
+                .map(|c| row(c.name.clone(), format!("{} / {}", c.personnel, c.equipment))),
+        );
     }
+    rows
+}
 
-    let weak_dialog1 = dialog.as_weak();
-    let weak_dialog2 = weak_dialog1.clone();
-    let weak_window1 = window.as_weak();
-    let weak_window2 = window.as_weak();
+/// Preview a library read from a workbook and import it into the database on confirmation.
+pub(super) fn show_import_preview_dialog(
+    lang: &str,
+    preview: XlsxImport,
+    state: Rc<RefCell<AppState>>,
+    weak_window: Weak<MainWindow>,
+) {
+    let dialog = match super::ImportPreviewDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create import preview dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    let library = preview.library;
+    let summary = ui_tr(lang, "Library \"{}\": {} units, {} personnel.")
+        .replacen("{}", &library.name, 1)
+        .replacen("{}", &walk_units(&library.units).count().to_string(), 1)
+        .replacen("{}", &library.total_personnel().to_string(), 1);
+    let warnings: Vec<String> = preview.warnings.iter().map(|w| w.to_string()).collect();
+    dialog.set_dialog_title(ui_tr(lang, "Import Preview").into());
+    dialog.set_warnings_title(ui_tr(lang, "Warnings").into());
+    dialog.set_import_text(ui_tr(lang, "Import").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+    dialog.set_summary(summary.into());
+    dialog.set_warnings(warnings.join("\n").into());
 
+    let lang = lang.to_string();
+    let weak = dialog.as_weak();
     dialog.on_accepted(move || {
-        if let Some(d) = weak_dialog1.upgrade() {
-            let name = d.get_library_name();
-            let country = d.get_library_country();
-            let era = d.get_library_era();
-            let author = d.get_library_author();
-            let tags = d.get_library_tags();
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::ImportPreviewDialog);
+        }
+        let import = LibraryImport::Plain {
+            library: library.clone(),
+            versions: Vec::new(),
+        };
+        import_library_with_confirmation(&lang, import, state.clone(), weak_window.clone());
+    });
 
-            if let Some(w) = weak_window1.upgrade() {
-                w.invoke_library_dialog_accepted(name, country, era, author, tags, library_id);
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::ImportPreviewDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::ImportPreviewDialog);
+    dialog.show().unwrap_or_default();
+}
+
+/// A unit tree limit the user went past, in the UI language
+pub(super) fn tree_limit_text(lang: &str, issue: &TreeLimitIssue) -> String {
+    let (template, value, limit) = match *issue {
+        TreeLimitIssue::TooDeep { depth, limit } => ("The unit tree would be {} levels deep; the limit is {}.", depth, limit),
+        TreeLimitIssue::TooWide { children, limit } => {
+            ("A unit would have {} direct subordinates; the limit is {}.", children, limit)
+        }
+    };
+    ui_tr(lang, template)
+        .replacen("{}", &value.to_string(), 1)
+        .replacen("{}", &limit.to_string(), 1)
+}
+
+/// Import a library read from a file. A unit tree past the hard limits is
+/// refused; one past the soft limits, or a library with the same name,
+/// country and era as an existing one, is imported only once confirmed.
+pub(super) fn import_library_with_confirmation(
+    lang: &str,
+    import: LibraryImport,
+    state: Rc<RefCell<AppState>>,
+    weak_window: Weak<MainWindow>,
+) {
+    let limits = state.borrow().tree_limits;
+    let warnings = match limits.check(tree_shape(&import.library().units)) {
+        Ok(warnings) => warnings,
+        Err(issue) => {
+            show_error_dialog(lang, "Import Error", &tree_limit_text(lang, &issue), &[]);
+            return;
+        }
+    };
+    if warnings.is_empty() {
+        import_unless_duplicate(lang, import, state, weak_window);
+        return;
+    }
+    let details: Vec<String> = warnings.iter().map(|w| tree_limit_text(lang, w)).collect();
+    let message = ui_tr(lang, "{}\n\nLarge trees make the formations list and exports slow. Import it anyway?")
+        .replacen("{}", &details.join("\n"), 1);
+    let spec = ConfirmSpec::new("Very large unit tree", message, "Import anyway");
+    let owned_lang = lang.to_string();
+    confirm(lang, spec, move |confirmed| {
+        if confirmed {
+            import_unless_duplicate(&owned_lang, import.clone(), state.clone(), weak_window.clone());
+        }
+    });
+}
+
+/// Import a library, asking first when a library with the same name,
+/// country and era already exists
+fn import_unless_duplicate(
+    lang: &str,
+    import: LibraryImport,
+    state: Rc<RefCell<AppState>>,
+    weak_window: Weak<MainWindow>,
+) {
+    let duplicate = {
+        let st = state.borrow();
+        let Some(db) = st.database() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        LibraryService::new(db.conn()).find_duplicate(import.library())
+    };
+    match duplicate {
+        Ok(None) => complete_library_import(import, &state, &weak_window),
+        Ok(Some(_)) => {
+            let library = import.library();
+            let message = ui_tr(lang, "A library \"{}\" ({}, {}) already exists. Import it anyway?")
+                .replacen("{}", &library.name, 1)
+                .replacen("{}", &library.country, 1)
+                .replacen("{}", &library.era, 1);
+            let spec = ConfirmSpec::new("Library already exists", message, "Import anyway");
+            confirm(lang, spec, move |confirmed| {
+                if confirmed {
+                    complete_library_import(import.clone(), &state, &weak_window);
+                }
+            });
+        }
+        Err(e) => {
+            log::error!("Failed to check for duplicate libraries: {}", e);
+            show_error_dialog(lang, "Import Error", "Failed to import library: {}", &[&e]);
+        }
+    }
+}
+
+/// Store an imported library and make it the current one.
+pub(super) fn complete_library_import(
+    import: LibraryImport,
+    state: &Rc<RefCell<AppState>>,
+    weak_window: &Weak<MainWindow>,
+) {
+    let (state_done, weak_window) = (state.clone(), weak_window.clone());
+    let work = move |manager: &LibraryManager| manager.import(import);
+    run_with_manager(state, "Import Library", work, move |result| match result {
+        Ok(lib) => {
+            log::info!("Library imported: {} (ID: {:?})", lib.name, lib.id);
+            let lib_id = lib.id.unwrap_or(-1);
+            state_done.borrow_mut().current_library = Some(lib.clone());
+            notify_change(&state_done, ChangeEvent::LibraryChanged(lib_id));
+            if let Some(window) = weak_window.upgrade() {
+                window.set_current_library_name(lib.name.clone().into());
+                window.set_current_library_id(lib_id as i32);
+                track_current_library(&window, &state_done);
+            }
+        }
+        Err(e) => {
+            log::error!("Failed to import library: {}", e);
+            let lang = weak_window.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+            show_error_dialog(&lang, "Import Error", "Failed to import library: {}", &[&e]);
+        }
+    });
+}
+
+/// Create a library entered in the library dialog and make it the current one.
+/// When one with the same name, country and era exists, ask whether to open
+/// that one instead or to create the new library anyway.
+pub(super) fn create_library_with_confirmation(
+    lang: &str,
+    library: Library,
+    allow_duplicate: bool,
+    state: Rc<RefCell<AppState>>,
+    weak_window: Weak<MainWindow>,
+) {
+    let result = {
+        let st = state.borrow();
+        let Some(db) = st.database() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        LibraryService::new(db.conn()).create_library_with(library.clone(), allow_duplicate)
+    };
+    match result {
+        Ok(lib) => {
+            log::info!("Library created: {} (ID: {:?})", lib.name, lib.id);
+            let lib_id = lib.id.map(|x| x as i32).unwrap_or(-1);
+            state.borrow_mut().current_library = Some(lib.clone());
+            notify_change(&state, ChangeEvent::LibraryChanged(lib_id as i64));
+            if let Some(window) = weak_window.upgrade() {
+                window.set_current_library_name(lib.name.clone().into());
+                window.set_current_library_id(lib_id);
+                super::remember_recent_library(&window, &lib);
+                track_current_library(&window, &state);
+            }
+        }
+        Err(e) => match e.downcast_ref::<LibraryServiceError>() {
+            Some(LibraryServiceError::Duplicate { existing_id }) => {
+                let existing_id = *existing_id;
+                let message = ui_tr(lang, "A library \"{}\" ({}, {}) already exists.")
+                    .replacen("{}", &library.name, 1)
+                    .replacen("{}", &library.country, 1)
+                    .replacen("{}", &library.era, 1);
+                let weak_open = weak_window.clone();
+                let create_lang = lang.to_string();
+                show_duplicate_library_dialog(
+                    lang,
+                    &message,
+                    move || {
+                        if let Some(window) = weak_open.upgrade() {
+                            window.invoke_library_selected(existing_id as i32);
+                        }
+                    },
+                    move || {
+                        create_library_with_confirmation(&create_lang, library.clone(), true, state.clone(), weak_window.clone());
+                    },
+                );
+            }
+            None => {
+                log::error!("Failed to create library: {}", e);
+                show_error_dialog(lang, "Error", "Failed to create library: {}", &[&e]);
             }
+        },
+    }
+}
+
+/// Offer to open the existing library or create the duplicate anyway; closing
+/// the dialog does neither
+fn show_duplicate_library_dialog(lang: &str, message: &str, on_open: impl Fn() + 'static, on_create: impl Fn() + 'static) {
+    let dialog = match super::DuplicateLibraryDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create duplicate library dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_dialog_title(ui_tr(lang, "Library already exists").into());
+    dialog.set_message(message.into());
+    dialog.set_open_text(ui_tr(lang, "Open Existing").into());
+    dialog.set_create_text(ui_tr(lang, "Create Anyway").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+
+    let weak = dialog.as_weak();
+    dialog.on_open_chosen(move || {
+        if let Some(d) = weak.upgrade() {
             d.hide().unwrap_or_default();
         }
+        on_open();
     });
+    let weak = dialog.as_weak();
+    dialog.on_create_chosen(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+        on_create();
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Ask whether to save the current library's unsaved changes before exiting:
+/// save them, leave them unsaved, or stay in the app.
+pub(super) fn show_unsaved_changes_dialog(
+    state: &Rc<RefCell<AppState>>,
+    lang: &str,
+    library_name: &str,
+    on_save: impl Fn() + 'static,
+    on_discard: impl Fn() + 'static,
+) {
+    let dialog = match super::UnsavedChangesDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create unsaved changes dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    dialog.set_dialog_title(ui_tr(lang, "Unsaved changes").into());
+    dialog.set_message(ui_tr_args(lang, "Save changes to library \"{}\" before exiting?", &[&library_name]).into());
+    dialog.set_save_text(ui_tr(lang, "Save").into());
+    dialog.set_discard_text(ui_tr(lang, "Don't Save").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
 
+    let weak = dialog.as_weak();
+    dialog.on_save_chosen(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+        on_save();
+    });
+    let weak = dialog.as_weak();
+    dialog.on_discard_chosen(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+        on_discard();
+    });
+    let weak = dialog.as_weak();
     dialog.on_cancelled(move || {
-        if let Some(d) = weak_dialog2.upgrade() {
-            if let Some(w) = weak_window2.upgrade() {
-                w.invoke_library_dialog_cancelled();
-            }
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Ask what to do when the app is closed while background operations are running:
+/// wait for them (quitting once they finish), cancel them, or quit right away.
+pub(super) fn show_running_operations_dialog(lang: &str, operations: OperationRegistry) {
+    let dialog = match super::RunningOperationsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create running operations dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_dialog_title(ui_tr(lang, "Operations in progress").into());
+    dialog.set_message(ui_tr(lang, "These operations are still running. Closing now may leave incomplete files.").into());
+    dialog.set_wait_text(ui_tr(lang, "Wait").into());
+    dialog.set_cancel_operations_text(ui_tr(lang, "Cancel Operations").into());
+    dialog.set_force_quit_text(ui_tr(lang, "Force Quit").into());
+    dialog.set_operations(describe_running(&operations.running()).into());
+
+    // Polls the registry while waiting; owned by the callbacks below
+    let timer = Rc::new(slint::Timer::default());
+    let start_waiting = {
+        let weak = dialog.as_weak();
+        let operations = operations.clone();
+        let timer = timer.clone();
+        move || {
+            let Some(d) = weak.upgrade() else {
+                return;
+            };
+            d.set_waiting(true);
+            let weak = weak.clone();
+            let operations = operations.clone();
+            timer.start(slint::TimerMode::Repeated, std::time::Duration::from_millis(250), move || {
+                if operations.is_idle() {
+                    log::info!("Background operations finished; exiting");
+                    slint::quit_event_loop().unwrap_or_default();
+                } else if let Some(d) = weak.upgrade() {
+                    d.set_operations(describe_running(&operations.running()).into());
+                }
+            });
+        }
+    };
+    let start_waiting = Rc::new(start_waiting);
+
+    let wait = start_waiting.clone();
+    dialog.on_wait(move || wait());
+
+    let wait = start_waiting.clone();
+    dialog.on_cancel_operations(move || {
+        let count = operations.cancel_all();
+        log::info!("Cancellation requested for {} operation(s)", count);
+        wait();
+    });
+
+    dialog.on_force_quit(|| {
+        log::warn!("Force quit with background operations still running");
+        slint::quit_event_loop().unwrap_or_default();
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_dismissed(move || {
+        timer.stop();
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Compare two snapshots of a library at a selectable level of detail.
+/// Defaults to the latest two versions compared by structure.
+/// `on_open_version` receives the "To" version when it is opened as a new library.
+pub(super) fn show_compare_versions_dialog(lang: &str, snapshots: Vec<Snapshot>, on_open_version: impl Fn(i64) + 'static) {
+    let dialog = match super::CompareVersionsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create compare versions dialog: {}", e);
+            return;
+        }
+    };
+    let labels: Vec<SharedString> = snapshots
+        .iter()
+        .map(|s| {
+            let when = chrono::DateTime::from_timestamp(s.timestamp, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            format!("v{} — {}", s.version, when).into()
+        })
+        .collect();
+    let options: Vec<SharedString> = DiffGranularity::ALL
+        .iter()
+        .map(|g| ui_tr(lang, g.label()).into())
+        .collect();
+    dialog.set_dialog_title(ui_tr(lang, "Compare Versions").into());
+    dialog.set_old_label(ui_tr(lang, "From:").into());
+    dialog.set_new_label(ui_tr(lang, "To:").into());
+    dialog.set_granularity_label(ui_tr(lang, "Detail:").into());
+    dialog.set_compare_text(ui_tr(lang, "Compare").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
+    dialog.set_open_version_text(ui_tr(lang, "Open as New Library").into());
+    dialog.set_versions(ModelRc::new(VecModel::from(labels)));
+    dialog.set_granularity_options(ModelRc::new(VecModel::from(options)));
+    dialog.set_old_index(snapshots.len().saturating_sub(2) as i32);
+    dialog.set_new_index(snapshots.len().saturating_sub(1) as i32);
+    dialog.set_granularity_index(1);
+
+    let versions: Vec<i64> = snapshots.iter().map(|s| s.version).collect();
+    let lang = lang.to_string();
+    let weak = dialog.as_weak();
+    dialog.on_compare(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let pick = |index: i32| usize::try_from(index).ok().and_then(|i| snapshots.get(i));
+        let (Some(old), Some(new)) = (pick(d.get_old_index()), pick(d.get_new_index())) else {
+            return;
+        };
+        let granularity = DiffGranularity::from_index(d.get_granularity_index());
+        let started = std::time::Instant::now();
+        let summary = diff_snapshots_staged(old, new, granularity).summary();
+        let elapsed = started.elapsed();
+        log::debug!(
+            "Compared v{} and v{} ({:?}) in {:?}",
+            old.version,
+            new.version,
+            granularity,
+            elapsed
+        );
+        d.set_result(summary.into());
+        d.set_elapsed_text(
+            ui_tr(&lang, "Compared in {} ms")
+                .replacen("{}", &elapsed.as_millis().to_string(), 1)
+                .into(),
+        );
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_open_version(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let Some(&version) = usize::try_from(d.get_new_index()).ok().and_then(|i| versions.get(i)) else {
+            return;
+        };
+        hide_remembering(&d, WindowKind::CompareVersionsDialog);
+        on_open_version(version);
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_closed(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::CompareVersionsDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::CompareVersionsDialog);
+    dialog.show().unwrap_or_default();
+}
+
+/// Ask for a snapshot description; `on_create` gets the text as typed.
+pub(super) fn show_snapshot_dialog(lang: &str, on_create: impl Fn(String) + 'static) {
+    let dialog = match super::SnapshotDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create snapshot dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_dialog_title(ui_tr(lang, "Create Snapshot").into());
+    dialog.set_description_label(ui_tr(lang, "Description:").into());
+    dialog.set_ok_text(ui_tr(lang, "Create").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+
+    let weak = dialog.as_weak();
+    dialog.on_accepted(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+            on_create(d.get_description_text().to_string());
+        }
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
             d.hide().unwrap_or_default();
         }
     });
 
     dialog.show().unwrap_or_default();
 }
+
+/// Edit a formation's name, type, effective period and TOE number. The
+/// dialog stays open while the input is invalid or `on_save` fails.
+pub(super) fn show_unit_properties_dialog(lang: &str, unit: Unit, on_save: impl Fn(Unit) -> anyhow::Result<()> + 'static) {
+    let dialog = match super::UnitPropertiesDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create formation properties dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_dialog_title(ui_tr(lang, "Formation Properties").into());
+    dialog.set_name_label(ui_tr(lang, "Name:").into());
+    dialog.set_type_label(ui_tr(lang, "Type:").into());
+    dialog.set_toe_label(ui_tr(lang, "TOE number:").into());
+    dialog.set_from_label(ui_tr(lang, "Effective from:").into());
+    dialog.set_to_label(ui_tr(lang, "Effective to:").into());
+    dialog.set_date_hint(ui_tr(lang, "YYYY-MM-DD, empty for no limit").into());
+    dialog.set_ok_text(ui_tr(lang, "OK").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+    dialog.set_name_text(unit.name.clone().into());
+    dialog.set_type_text(unit.unit_type.clone().into());
+    dialog.set_toe_text(unit.toe_number.clone().unwrap_or_default().into());
+    dialog.set_from_text(unit.effective_from.clone().unwrap_or_default().into());
+    dialog.set_to_text(unit.effective_to.clone().unwrap_or_default().into());
+
+    let lang = lang.to_string();
+    let weak = dialog.as_weak();
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let name = d.get_name_text().trim().to_string();
+        if name.is_empty() {
+            d.set_error_text(ui_tr(&lang, "Formation name cannot be empty").into());
+            return;
+        }
+        let (effective_from, effective_to) = match parse_effective_dates(&d.get_from_text(), &d.get_to_text()) {
+            Ok(dates) => dates,
+            Err(errors) => {
+                let messages: Vec<String> = errors
+                    .iter()
+                    .map(|e| match e.field.as_str() {
+                        _ if e.message.starts_with("The end date") => ui_tr(&lang, &e.message),
+                        "effective_from" => ui_tr(&lang, "The start date must be in the form YYYY-MM-DD"),
+                        _ => ui_tr(&lang, "The end date must be in the form YYYY-MM-DD"),
+                    })
+                    .collect();
+                d.set_error_text(messages.join("\n").into());
+                return;
+            }
+        };
+        let edited = Unit {
+            name,
+            unit_type: d.get_type_text().trim().to_string(),
+            effective_from,
+            effective_to,
+            toe_number: normalize_toe_number(&d.get_toe_text()),
+            ..unit.clone()
+        };
+        match on_save(edited) {
+            Ok(()) => d.hide().unwrap_or_default(),
+            Err(e) => match e.downcast_ref::<ToeNumberCollision>() {
+                Some(collision) => d.set_error_text(
+                    ui_tr(&lang, "TOE number {} is already used by \"{}\"")
+                        .replacen("{}", &collision.number, 1)
+                        .replacen("{}", &collision.unit_name, 1)
+                        .into(),
+                ),
+                None => {
+                    log::error!("Failed to save formation properties: {}", e);
+                    d.set_error_text(format!("{}: {}", ui_tr(&lang, "Failed to save"), e).into());
+                }
+            },
+        }
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Units whose name or type contains `query` (case-insensitive), or whose TOE
+/// number does once spaces are ignored
+fn find_units<'a>(nodes: &'a [UnitTreeNode], query: &str) -> Vec<&'a UnitTreeNode> {
+    let text = query.trim().to_lowercase();
+    let key = toe_number_key(query);
+    if text.is_empty() {
+        return Vec::new();
+    }
+    nodes
+        .iter()
+        .filter(|n| {
+            n.name.to_lowercase().contains(&text)
+                || n.unit_type.to_lowercase().contains(&text)
+                || n.toe_number.as_deref().is_some_and(|t| toe_number_key(t).contains(&key))
+        })
+        .collect()
+}
+
+fn find_result(node: &UnitTreeNode) -> super::FindResult {
+    let mut label = node.name.clone();
+    for extra in [Some(node.unit_type.as_str()).filter(|t| !t.is_empty()), node.toe_number.as_deref()]
+        .into_iter()
+        .flatten()
+    {
+        label.push_str(" · ");
+        label.push_str(extra);
+    }
+    super::FindResult { id: node.id as i32, label: label.into() }
+}
+
+/// Edit > Find: units of the current library by name, type or TOE number.
+/// A TOE number that exactly one unit has goes straight to that unit;
+/// `on_found` gets the id of the unit to show.
+pub(super) fn show_find_dialog(lang: &str, state: Rc<RefCell<AppState>>, on_found: impl Fn(i64) + 'static) {
+    let nodes = {
+        let st = state.borrow();
+        let (Some(db), Some(library_id)) = (st.database(), st.current_library.as_ref().and_then(|l| l.id)) else {
+            log::warn!("No library selected");
+            return;
+        };
+        match crate::db::repositories::UnitRepo::new(db.conn()).list_tree_by_library(library_id) {
+            Ok(nodes) => nodes,
+            Err(e) => {
+                log::error!("Failed to load formations: {}", e);
+                show_error_dialog(lang, "Error", "Failed to load formations: {}", &[&e]);
+                return;
+            }
+        }
+    };
+    let dialog = match super::FindDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create find dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    dialog.set_dialog_title(ui_tr(lang, "Find").into());
+    dialog.set_find_label(ui_tr(lang, "Find:").into());
+    dialog.set_toe_label(ui_tr(lang, "Go to TOE number:").into());
+    dialog.set_go_text(ui_tr(lang, "Go").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
+
+    let nodes = Rc::new(nodes);
+    let weak = dialog.as_weak();
+    let found = Rc::clone(&nodes);
+    dialog.on_search(move |query| {
+        if let Some(d) = weak.upgrade() {
+            let results: Vec<super::FindResult> = find_units(&found, &query).into_iter().map(find_result).collect();
+            d.set_results(ModelRc::new(VecModel::from(results)));
+        }
+    });
+
+    let on_found = Rc::new(on_found);
+    let weak = dialog.as_weak();
+    let lang = lang.to_string();
+    let (found, on_go) = (Rc::clone(&nodes), Rc::clone(&on_found));
+    dialog.on_go_to_toe(move |query| {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        if let Some(id) = exact_toe_match(found.iter().map(|n| (n.id, n.toe_number.as_deref())), &query) {
+            d.set_error_text(Default::default());
+            on_go(id);
+            return;
+        }
+        // Several units share the number, or none has it: list them instead
+        let key = toe_number_key(&query);
+        let shared: Vec<super::FindResult> = found
+            .iter()
+            .filter(|n| !key.is_empty() && n.toe_number.as_deref().is_some_and(|t| toe_number_key(t) == key))
+            .map(find_result)
+            .collect();
+        let message = if shared.is_empty() { "No formation has TOE number {}" } else { "Several formations have TOE number {}" };
+        d.set_error_text(ui_tr(&lang, message).replacen("{}", query.trim(), 1).into());
+        if !shared.is_empty() {
+            d.set_results(ModelRc::new(VecModel::from(shared)));
+        }
+    });
+
+    dialog.on_result_chosen(move |id| on_found(i64::from(id)));
+
+    let weak = dialog.as_weak();
+    dialog.on_closed(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Libraries matching `query` for the Open Library dialog: search results by
+/// name, or with an empty query every library, most recently changed first
+fn open_library_rows(conn: &rusqlite::Connection, query: &str, lang: &str) -> anyhow::Result<Vec<super::OpenLibraryRow>> {
+    let libraries = if query.trim().is_empty() {
+        LibraryRepo::new(conn).list_all_ordered(OrderBy::UpdatedAtDesc)?
+    } else {
+        LibraryService::new(conn).search_libraries(query)?
+    };
+    let summaries: HashMap<i64, LibrarySummary> =
+        LibraryRepo::new(conn).list_summaries()?.into_iter().map(|s| (s.id, s)).collect();
+    let now = chrono::Utc::now().timestamp();
+    Ok(libraries
+        .into_iter()
+        .filter_map(|library| {
+            let summary = summaries.get(&library.id?)?;
+            Some(super::OpenLibraryRow {
+                id: summary.id as i32,
+                name: library.name.into(),
+                country: library.country.into(),
+                era: library.era.into(),
+                author: library.author.into(),
+                version: library.version.to_string().into(),
+                units: ui_tr_plural(lang, summary.unit_count as u64, "{} unit", "{} units").into(),
+                changed: crate::i18n::time_ago(summary.updated_at, now, lang).into(),
+                changed_at: crate::i18n::format_timestamp(summary.updated_at).into(),
+            })
+        })
+        .collect())
+}
+
+/// File > Open Library: pick a library from a searchable list. `on_open` gets
+/// the chosen library's id; `on_open_file` is run for "Open from file…".
+pub(super) fn show_open_library_dialog(
+    lang: &str,
+    state: Rc<RefCell<AppState>>,
+    on_open: impl Fn(i64) + 'static,
+    on_open_file: impl Fn() + 'static,
+) {
+    let dialog = match super::OpenLibraryDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create open library dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    dialog.set_dialog_title(ui_tr(lang, "Open Library").into());
+    dialog.set_search_label(ui_tr(lang, "Search:").into());
+    dialog.set_country_label(ui_tr(lang, "Country:").into());
+    dialog.set_era_label(ui_tr(lang, "Era:").into());
+    dialog.set_author_label(ui_tr(lang, "Author:").into());
+    dialog.set_version_label(ui_tr(lang, "Version:").into());
+    dialog.set_units_label(ui_tr(lang, "Units:").into());
+    dialog.set_changed_label(ui_tr(lang, "Changed:").into());
+    dialog.set_empty_text(ui_tr(lang, "No libraries found").into());
+    dialog.set_open_text(ui_tr(lang, "Open").into());
+    dialog.set_open_file_text(ui_tr(lang, "Open from file…").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+
+    let weak = dialog.as_weak();
+    let (state_search, lang_search) = (state.clone(), lang.to_string());
+    let search = move |query: &str| {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let rows = match state_search.borrow().database() {
+            Some(db) => open_library_rows(db.conn(), query, &lang_search),
+            None => return,
+        };
+        match rows {
+            Ok(rows) => {
+                // Keep the selection on the same library when it is still listed
+                let selected = usize::try_from(d.get_selected_index()).ok().and_then(|i| d.get_libraries().row_data(i));
+                let index = selected
+                    .and_then(|s| rows.iter().position(|r| r.id == s.id))
+                    .unwrap_or(0);
+                d.set_selected_index(if rows.is_empty() { -1 } else { index as i32 });
+                d.set_libraries(ModelRc::new(VecModel::from(rows)));
+            }
+            Err(e) => log::error!("Failed to search libraries: {}", e),
+        }
+    };
+    search("");
+    dialog.on_search(move |query| search(&query));
+
+    let weak = dialog.as_weak();
+    dialog.on_open(move |id| {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::OpenLibraryDialog);
+        }
+        on_open(i64::from(id));
+    });
+    let weak = dialog.as_weak();
+    dialog.on_open_file(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::OpenLibraryDialog);
+        }
+        on_open_file();
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::OpenLibraryDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::OpenLibraryDialog);
+    dialog.show().unwrap_or_default();
+}
+
+/// Diagram export options; `on_accept` gets the chosen options and, with
+/// `per_formation`, the depth of the formations to draw (0: top level).
+/// Pages default to 4000×3000 when splitting was never set.
+pub(super) fn show_diagram_export_dialog(
+    lang: &str,
+    initial: SvgOptions,
+    per_formation: bool,
+    on_accept: impl Fn(SvgOptions, usize) + 'static,
+) {
+    let dialog = match super::DiagramExportDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create diagram export dialog: {}", e);
+            return;
+        }
+    };
+    let limit = initial.page_limit.unwrap_or(PageLimit { width: 4000, height: 3000 });
+    let title = if per_formation { "Export Diagrams per Formation" } else { "Export Diagram" };
+    dialog.set_dialog_title(ui_tr(lang, title).into());
+    dialog.set_per_formation(per_formation);
+    dialog.set_depth_label(ui_tr(lang, "Formations at level:").into());
+    dialog.set_orientation_label(ui_tr(lang, "Layout:").into());
+    dialog.set_orientations(ModelRc::new(VecModel::from(vec![
+        SharedString::from(ui_tr(lang, "Top to bottom")),
+        SharedString::from(ui_tr(lang, "Left to right")),
+    ])));
+    dialog.set_orientation_index(match initial.orientation {
+        SvgOrientation::TopDown => 0,
+        SvgOrientation::LeftRight => 1,
+    });
+    dialog.set_split_text(ui_tr(lang, "Split into pages no larger than").into());
+    dialog.set_split_pages(initial.page_limit.is_some());
+    dialog.set_width_label(ui_tr(lang, "Width:").into());
+    dialog.set_height_label(ui_tr(lang, "Height:").into());
+    dialog.set_page_width(limit.width.to_string().into());
+    dialog.set_page_height(limit.height.to_string().into());
+    dialog.set_ok_text(ui_tr(lang, "Export").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+
+    let weak = dialog.as_weak();
+    let lang_ok = lang.to_string();
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let page_limit = if d.get_split_pages() {
+            match PageLimit::parse(&d.get_page_width(), &d.get_page_height()) {
+                Some(limit) => Some(limit),
+                None => {
+                    d.set_error_text(
+                        ui_tr(&lang_ok, "Enter a width and height of at least {} pixels.")
+                            .replacen("{}", &PageLimit::MIN_SIDE.to_string(), 1)
+                            .into(),
+                    );
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        let depth = match d.get_depth_text().trim().parse::<usize>() {
+            Ok(level) if level >= 1 => level - 1,
+            _ if !per_formation => 0,
+            _ => {
+                d.set_error_text(ui_tr(&lang_ok, "Enter a level of 1 or more; 1 is the top level.").into());
+                return;
+            }
+        };
+        let orientation = match d.get_orientation_index() {
+            1 => SvgOrientation::LeftRight,
+            _ => SvgOrientation::TopDown,
+        };
+        d.hide().unwrap_or_default();
+        on_accept(SvgOptions { orientation, page_limit }, depth);
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// JSON export options, starting from `initial`; `on_accept` gets the chosen ones
+pub(super) fn show_export_options_dialog(
+    lang: &str,
+    state: &Rc<RefCell<AppState>>,
+    title: &str,
+    json: bool,
+    initial: ExportOptions,
+    anonymize: Option<AnonymizeOptions>,
+    on_accept: impl Fn(ExportOptions, Option<AnonymizeOptions>) + 'static,
+) {
+    let dialog = match super::ExportOptionsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create export options dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    dialog.set_dialog_title(ui_tr(lang, title).into());
+    dialog.set_json_options(json);
+    dialog.set_pretty_text(ui_tr(lang, "Indent for reading (larger file)").into());
+    dialog.set_pretty(initial.pretty);
+    dialog.set_versions_text(ui_tr(lang, "Include version history").into());
+    dialog.set_include_versions(initial.include_versions);
+    dialog.set_anonymize_text(ui_tr(lang, "Anonymize author and notes").into());
+    dialog.set_anonymize(anonymize.is_some());
+    dialog.set_author_label(ui_tr(lang, "Author shown:").into());
+    let placeholder = anonymize.map_or_else(|| AnonymizeOptions::default().placeholder_author, |a| a.placeholder_author);
+    dialog.set_placeholder_author(placeholder.into());
+    dialog.set_ok_text(ui_tr(lang, "Export…").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+
+    let weak = dialog.as_weak();
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        d.hide().unwrap_or_default();
+        let options = ExportOptions { pretty: d.get_pretty(), include_versions: json && d.get_include_versions() };
+        let anonymize = d.get_anonymize().then(|| {
+            let author = d.get_placeholder_author().trim().to_string();
+            AnonymizeOptions {
+                placeholder_author: if author.is_empty() { AnonymizeOptions::default().placeholder_author } else { author },
+                include_versions: options.include_versions,
+            }
+        });
+        on_accept(options, anonymize);
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Ask for one version of a library, newest first; `on_pick` gets the version number.
+/// `snapshots` are expected newest first, as `get_library_versions` returns them.
+/// `title` and `ok_label` are translation keys.
+pub(super) fn show_version_picker_dialog(
+    lang: &str,
+    title: &str,
+    ok_label: &str,
+    snapshots: &[Snapshot],
+    on_pick: impl Fn(i64) + 'static,
+) {
+    let dialog = match super::VersionPickerDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create version picker dialog: {}", e);
+            return;
+        }
+    };
+    let labels: Vec<SharedString> = snapshots
+        .iter()
+        .map(|s| {
+            let when = chrono::DateTime::from_timestamp(s.timestamp, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            match s.description.as_deref() {
+                Some(description) => format!("v{} — {} — {}", s.version, when, description).into(),
+                None => format!("v{} — {}", s.version, when).into(),
+            }
+        })
+        .collect();
+    let versions: Vec<i64> = snapshots.iter().map(|s| s.version).collect();
+    dialog.set_dialog_title(ui_tr(lang, title).into());
+    dialog.set_version_label(ui_tr(lang, "Version:").into());
+    dialog.set_ok_text(ui_tr(lang, ok_label).into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+    dialog.set_versions(ModelRc::new(VecModel::from(labels)));
+    // The newest entry is the current state, so preselect the one before it
+    dialog.set_current_index(if versions.len() > 1 { 1 } else { 0 });
+
+    let weak = dialog.as_weak();
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let Some(&version) = usize::try_from(d.get_current_index()).ok().and_then(|i| versions.get(i)) else {
+            return;
+        };
+        d.hide().unwrap_or_default();
+        on_pick(version);
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Review queued unit types: each row starts at its suggested level and can be
+/// changed; `on_apply` gets (unit id, level id or `None` for no level) for every row.
+pub(super) fn show_level_mapping_dialog(
+    lang: &str,
+    name_pref: DisplayLang,
+    entries: Vec<LevelMappingEntry>,
+    levels: Vec<CustomFormationLevel>,
+    on_apply: impl Fn(Vec<(i64, Option<i64>)>) + 'static,
+) {
+    let dialog = match super::LevelMappingDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create level mapping dialog: {}", e);
+            return;
+        }
+    };
+    let level_name = |level: &CustomFormationLevel| display_name(&level.name_ru, &level.name_en, name_pref, lang).to_string();
+    let mut names: Vec<SharedString> = vec![ui_tr(lang, "(no level)").into()];
+    names.extend(levels.iter().map(|l| SharedString::from(level_name(l))));
+    let rows: Vec<LevelMappingRow> = entries
+        .iter()
+        .map(|e| {
+            let suggested = levels.iter().position(|l| l.id.is_some() && l.id == e.suggested_level_id);
+            LevelMappingRow {
+                unit_name: e.unit_name.clone().into(),
+                unit_type: e.unit_type.clone().into(),
+                suggestion: suggested
+                    .map(|i| format!("{} ({:.0}%)", level_name(&levels[i]), e.score * 100.0))
+                    .unwrap_or_default()
+                    .into(),
+                level_index: suggested.map_or(0, |i| i as i32 + 1),
+            }
+        })
+        .collect();
+    let model = Rc::new(VecModel::from(rows));
+    dialog.set_dialog_title(ui_tr(lang, "Review Level Mappings").into());
+    dialog.set_hint(
+        ui_tr(lang, "These unit types name no formation level exactly. Confirm or change the level of each unit.").into(),
+    );
+    dialog.set_unit_header(ui_tr(lang, "Unit").into());
+    dialog.set_type_header(ui_tr(lang, "Type").into());
+    dialog.set_suggestion_header(ui_tr(lang, "Suggestion").into());
+    dialog.set_level_header(ui_tr(lang, "Level").into());
+    dialog.set_ok_text(ui_tr(lang, "Apply").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+    dialog.set_level_names(ModelRc::new(VecModel::from(names)));
+    dialog.set_rows(ModelRc::new(model.clone()));
+
+    let model_chosen = model.clone();
+    dialog.on_level_chosen(move |row, index| {
+        let Some(row) = usize::try_from(row).ok() else {
+            return;
+        };
+        if let Some(mut data) = model_chosen.row_data(row) {
+            data.level_index = index;
+            model_chosen.set_row_data(row, data);
+        }
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_accepted(move || {
+        let decisions: Vec<(i64, Option<i64>)> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                let index = model.row_data(i).map_or(0, |r| r.level_index);
+                let level = usize::try_from(index - 1).ok().and_then(|l| levels.get(l)).and_then(|l| l.id);
+                (e.unit_id, level)
+            })
+            .collect();
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::LevelMappingDialog);
+        }
+        on_apply(decisions);
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::LevelMappingDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::LevelMappingDialog);
+    dialog.show().unwrap_or_default();
+}
+
+/// What the user chose for a pair in the duplicates dialog
+pub(super) enum DuplicateAction {
+    /// Merge the second unit into the first
+    Merge,
+    /// Give the second unit this name
+    Rename(String),
+    /// Remember that the units are not duplicates
+    Ignore,
+}
+
+/// One-line summary of a unit in the duplicates dialog
+fn unit_summary_text(lang: &str, unit: &UnitSummary) -> String {
+    ui_tr_args(
+        lang,
+        "{} · personnel: {} · equipment: {} · subunits: {}",
+        &[&unit.unit_type, &unit.personnel, &unit.equipment, &unit.subunits],
+    )
+}
+
+/// List near-duplicate unit pairs. `on_action` carries out the choice for a
+/// pair and returns whether it succeeded; the pair then leaves the list, and
+/// after a merge so do the other pairs of the merged-away unit.
+pub(super) fn show_duplicate_units_dialog(
+    lang: &str,
+    pairs: Vec<DuplicatePair>,
+    on_action: impl Fn(&DuplicatePair, DuplicateAction) -> bool + 'static,
+) {
+    let dialog = match super::DuplicateUnitsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create duplicate units dialog: {}", e);
+            return;
+        }
+    };
+    let rows: Vec<DuplicatePairRow> = pairs
+        .iter()
+        .map(|p| DuplicatePairRow {
+            first_name: p.first.name.clone().into(),
+            first_summary: unit_summary_text(lang, &p.first).into(),
+            second_name: p.second.name.clone().into(),
+            second_summary: unit_summary_text(lang, &p.second).into(),
+            score: format!("{:.0}%", p.name_score * 100.0).into(),
+        })
+        .collect();
+    let model = Rc::new(VecModel::from(rows));
+    let pairs = Rc::new(RefCell::new(pairs));
+    dialog.set_dialog_title(ui_tr(lang, "Find Duplicate Units").into());
+    dialog.set_hint(
+        ui_tr(
+            lang,
+            "These units under the same parent have similar names and strength. Merge adds the second unit's personnel, equipment and subunits to the first and deletes the second.",
+        )
+        .into(),
+    );
+    dialog.set_none_text(ui_tr(lang, "No near-duplicate units found.").into());
+    dialog.set_merge_text(ui_tr(lang, "Merge").into());
+    dialog.set_rename_text(ui_tr(lang, "Rename").into());
+    dialog.set_ignore_text(ui_tr(lang, "Not duplicates").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
+    dialog.set_rows(ModelRc::new(model.clone()));
+
+    let act = Rc::new(move |row: i32, action: DuplicateAction| {
+        let Some(i) = usize::try_from(row).ok().filter(|&i| i < pairs.borrow().len()) else {
+            return;
+        };
+        let pair = pairs.borrow()[i].clone();
+        let merged = matches!(action, DuplicateAction::Merge);
+        if !on_action(&pair, action) {
+            return;
+        }
+        let mut pairs = pairs.borrow_mut();
+        let mut i = 0;
+        while i < pairs.len() {
+            let p = &pairs[i];
+            let gone = (p.first.id, p.second.id) == (pair.first.id, pair.second.id)
+                || (merged && (p.first.id == pair.second.id || p.second.id == pair.second.id));
+            if gone {
+                pairs.remove(i);
+                model.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    });
+    let act_merge = act.clone();
+    dialog.on_merge(move |row| act_merge(row, DuplicateAction::Merge));
+    let act_rename = act.clone();
+    dialog.on_rename(move |row, name| act_rename(row, DuplicateAction::Rename(name.trim().to_string())));
+    dialog.on_ignore(move |row| act(row, DuplicateAction::Ignore));
+    let weak = dialog.as_weak();
+    dialog.on_closed(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::DuplicateUnitsDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::DuplicateUnitsDialog);
+    dialog.show().unwrap_or_default();
+}
+
+fn tag_state_rows(states: &[(String, TagState)]) -> Vec<TagStateRow> {
+    states
+        .iter()
+        .map(|(tag, state)| TagStateRow {
+            tag: tag.clone().into(),
+            state: match state {
+                TagState::None => 0,
+                TagState::Some => 1,
+                TagState::All => 2,
+            },
+        })
+        .collect()
+}
+
+/// Tri-state tag editor for `library_count` libraries. `on_apply` gets the
+/// tags to add and to remove, and only runs if something changed.
+pub(super) fn show_tags_dialog(
+    lang: &str,
+    library_count: usize,
+    initial: Vec<(String, TagState)>,
+    on_apply: impl Fn(Vec<String>, Vec<String>) + 'static,
+) {
+    let dialog = match super::TagsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create tags dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_dialog_title(ui_tr(lang, "Tags").into());
+    dialog.set_hint_text(
+        ui_tr(lang, "Tags of {} libraries. Click a tag to set it on all or none of them.")
+            .replacen("{}", &library_count.to_string(), 1)
+            .into(),
+    );
+    dialog.set_new_tag_placeholder(ui_tr(lang, "New tag").into());
+    dialog.set_add_text(ui_tr(lang, "Add").into());
+    dialog.set_ok_text(ui_tr(lang, "Apply").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+    dialog.set_rows(ModelRc::new(VecModel::from(tag_state_rows(&initial))));
+
+    let chosen = Rc::new(RefCell::new(initial.clone()));
+    let initial = Rc::new(initial);
+
+    let weak = dialog.as_weak();
+    let chosen_click = chosen.clone();
+    let initial_click = initial.clone();
+    dialog.on_row_clicked(move |index| {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let mut chosen = chosen_click.borrow_mut();
+        let Some((tag, state)) = usize::try_from(index).ok().and_then(|i| chosen.get_mut(i)) else {
+            return;
+        };
+        let started = initial_click
+            .iter()
+            .find(|(t, _)| t == &*tag)
+            .map(|(_, s)| *s)
+            .unwrap_or(TagState::None);
+        *state = state.cycle(started);
+        d.set_rows(ModelRc::new(VecModel::from(tag_state_rows(&chosen))));
+    });
+
+    let weak = dialog.as_weak();
+    let chosen_add = chosen.clone();
+    dialog.on_add_tag(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let tag = normalize_tag(&d.get_new_tag_text());
+        if tag.is_empty() {
+            return;
+        }
+        let mut chosen = chosen_add.borrow_mut();
+        match chosen.iter_mut().find(|(t, _)| t.to_lowercase() == tag.to_lowercase()) {
+            Some((_, state)) => *state = TagState::All,
+            None => chosen.push((tag, TagState::All)),
+        }
+        d.set_new_tag_text("".into());
+        d.set_rows(ModelRc::new(VecModel::from(tag_state_rows(&chosen))));
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        d.hide().unwrap_or_default();
+        let (add, remove) = tag_changes(&initial, &chosen.borrow());
+        if !add.is_empty() || !remove.is_empty() {
+            on_apply(add, remove);
+        }
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Show library dialog for editing existing library
+pub(super) fn show_library_dialog_for_edit(window: &MainWindow, library_id: i32, state: Rc<RefCell<AppState>>) {
+    let dialog = match LibraryDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create library dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+
+    // Load library data for editing
+    let lib_data = {
+        let state = state.borrow();
+        state.current_library.clone()
+    };
+
+    if let Some(ref lib) = lib_data {
+        dialog.set_library_name(lib.name.clone().into());
+        dialog.set_library_country(lib.country.clone().into());
+        dialog.set_library_era(lib.era.clone().into());
+        dialog.set_library_author(lib.author.clone().into());
+        dialog.set_library_tags(lib.tags.join(", ").into());
+        dialog.set_display_language_index(display_language_index(&lib.display_language));
+    }
+    let lang = window.get_current_language().to_string();
+    setup_country_autocomplete(&dialog, &lang);
+    match lib_data.clone() {
+        Some(lib) => {
+            let in_recent_list = crate::config::Settings::load()
+                .unwrap_or_default()
+                .recent_libraries
+                .iter()
+                .any(|e| Some(e.library_id) == lib.id);
+            setup_impact_preview(&dialog, &lang, lib, in_recent_list);
+        }
+        None => setup_live_validation(&dialog, &lang),
+    }
+    let stats = state
+        .borrow()
+        .database()
+        .map(|db| LibraryRepo::new(db.conn()).stats(library_id as i64));
+    match stats {
+        Some(Ok(stats)) => {
+            dialog.set_stats_title(ui_tr(&lang, "Statistics").into());
+            dialog.set_stats_lines(ModelRc::new(VecModel::from(library_stats_lines(&lang, &stats))));
+        }
+        Some(Err(e)) => log::error!("Failed to count library {}: {:#}", library_id, e),
+        None => {}
+    }
+    // Versions with a description are ones users come back to; they keep the
+    // old name, so a rename is confirmed
+    let described_versions = {
+        let st = state.borrow();
+        match st.database() {
+            Some(db) => LibraryService::new(db.conn())
+                .get_library_versions(library_id as i64)
+                .map(|versions| versions.iter().filter(|v| v.description.is_some()).count())
+                .unwrap_or(0),
+            None => 0,
+        }
+    };
+
+    let weak_dialog1 = dialog.as_weak();
+    let weak_dialog2 = weak_dialog1.clone();
+    let weak_window1 = window.as_weak();
+    let weak_window2 = window.as_weak();
+
+    dialog.on_accepted(move || {
+        let Some(d) = weak_dialog1.upgrade() else {
+            return;
+        };
+        let accept = {
+            let (weak_dialog, weak_window) = (weak_dialog1.clone(), weak_window1.clone());
+            move || {
+                let Some(d) = weak_dialog.upgrade() else {
+                    return;
+                };
+                let display_language = display_language_code(d.get_display_language_index());
+                if let Some(w) = weak_window.upgrade() {
+                    w.invoke_library_dialog_accepted(
+                        d.get_library_name(),
+                        d.get_library_country(),
+                        d.get_library_era(),
+                        d.get_library_author(),
+                        d.get_library_tags(),
+                        display_language.into(),
+                        library_id,
+                    );
+                }
+                d.hide().unwrap_or_default();
+            }
+        };
+        let renamed = lib_data.as_ref().is_some_and(|before| {
+            let after = Library { name: d.get_library_name().to_string(), ..before.clone() };
+            crate::services::renames(before, &after)
+        });
+        if !renamed || described_versions == 0 {
+            accept();
+            return;
+        }
+        let old_name = lib_data.as_ref().map(|l| l.name.clone()).unwrap_or_default();
+        let spec = ConfirmSpec::new(
+            &ui_tr(&lang, "Rename Library"),
+            ui_tr(
+                &lang,
+                "{} described versions in the history keep the name \"{}\"; restoring one brings that name back. Rename the library anyway?",
+            )
+            .replacen("{}", &described_versions.to_string(), 1)
+            .replacen("{}", &old_name, 1),
+            &ui_tr(&lang, "Rename"),
+        );
+        confirm(&lang, spec, move |ok| {
+            if ok {
+                accept();
+            }
+        });
+    });
+
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak_dialog2.upgrade() {
+            if let Some(w) = weak_window2.upgrade() {
+                w.invoke_library_dialog_cancelled();
+            }
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    show_focused(&dialog);
+}
+
+/// Warn that the database is in `provider`'s synced folder and apply the user's choice:
+/// move it to the local data folder, keep it there without further warnings, or
+/// keep it in safe mode
+pub(super) fn show_cloud_sync_dialog(state: Rc<RefCell<AppState>>, lang: &str, db_path: std::path::PathBuf, provider: &str) {
+    let dialog = match super::CloudSyncDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create cloud sync dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    dialog.set_dialog_title(ui_tr(lang, "Database in a Synced Folder").into());
+    dialog.set_message(
+        ui_tr(lang, "The database {path} is in a folder synced by {provider}. Syncing can corrupt it or lose recent changes while TOEditor is running.")
+            .replace("{path}", &db_path.display().to_string())
+            .replace("{provider}", provider)
+            .into(),
+    );
+    dialog.set_move_text(ui_tr(lang, "Move to Local Folder").into());
+    dialog.set_keep_text(ui_tr(lang, "Keep and Don't Warn").into());
+    dialog.set_safe_text(ui_tr(lang, "Use Safe Mode").into());
+
+    let lang = lang.to_string();
+    let weak = dialog.as_weak();
+    let path = db_path.clone();
+    let move_state = state.clone();
+    dialog.on_move_chosen(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        match move_database_to_local(&move_state, &path) {
+            Ok(()) => d.hide().unwrap_or_default(),
+            Err(e) => {
+                log::error!("Failed to move the database: {:#}", e);
+                d.set_error_text(format!("{}: {:#}", ui_tr(&lang, "Failed to move the database"), e).into());
+            }
+        }
+    });
+
+    let weak = dialog.as_weak();
+    let path = db_path.clone();
+    dialog.on_keep_chosen(move || {
+        update_settings(|s| s.cloud_sync_warning_silenced = Some(path.clone()));
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_safe_chosen(move || {
+        if let Some(db) = state.borrow().database() {
+            if let Err(e) = db.use_safe_mode() {
+                log::error!("Failed to switch the database to safe mode: {}", e);
+            }
+        }
+        update_settings(|s| s.safe_database_mode = true);
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_dismissed(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    if let Err(e) = dialog.show() {
+        log::error!("Failed to show cloud sync dialog: {}", e);
+    }
+}
+
+/// Close the database, move it to the default location and reopen it there.
+/// On failure the original is reopened.
+fn move_database_to_local(state: &Rc<RefCell<AppState>>, from: &std::path::Path) -> anyhow::Result<()> {
+    let to = crate::config::Settings::default_database_path()?;
+    if to == from {
+        anyhow::bail!("{} is already the local database", from.display());
+    }
+    state.borrow_mut().set_database(None);
+    let result = crate::db::storage::move_database(from, &to);
+    let reopen = if result.is_ok() { &to } else { from };
+    state.borrow_mut().set_database(Some(crate::db::Database::open(reopen)?));
+    result?;
+    update_settings(|s| s.database_path = None);
+    log::info!("Database moved from {} to {}", from.display(), to.display());
+    Ok(())
+}
+
+/// Color schemes offered in the settings dialog with their labels, in list order
+const SETTINGS_THEMES: [(&str, &str); 2] = [("light", "Light"), ("dark", "Dark")];
+
+/// Tools > Settings: UI language, theme and database file. Nothing changes
+/// until OK; `on_database_switched` runs once another database file is open.
+pub(super) fn show_settings_dialog(
+    window: &MainWindow,
+    state: Rc<RefCell<AppState>>,
+    on_database_switched: impl Fn() + 'static,
+) {
+    let dialog = match super::SettingsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create settings dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    let lang = window.get_current_language().to_string();
+    let settings = crate::config::Settings::load().unwrap_or_default();
+    let current_path = settings.resolved_database_path().unwrap_or_default();
+
+    dialog.set_dialog_title(ui_tr(&lang, "Settings").into());
+    dialog.set_language_label(ui_tr(&lang, "Language:").into());
+    let languages: Vec<SharedString> = available_languages().iter().map(|l| l.name.into()).collect();
+    dialog.set_languages(ModelRc::new(VecModel::from(languages)));
+    let language = supported_code(&settings.language);
+    dialog.set_language_index(available_languages().iter().position(|l| l.code == language).unwrap_or(0) as i32);
+    dialog.set_theme_label(ui_tr(&lang, "Theme:").into());
+    let themes: Vec<SharedString> = SETTINGS_THEMES.iter().map(|(_, label)| ui_tr(&lang, label).into()).collect();
+    dialog.set_themes(ModelRc::new(VecModel::from(themes)));
+    let theme = window.get_theme();
+    dialog.set_theme_index(SETTINGS_THEMES.iter().position(|(code, _)| *code == theme.as_str()).unwrap_or(0) as i32);
+    dialog.set_database_label(ui_tr(&lang, "Database file:").into());
+    let shown_path: SharedString = current_path.display().to_string().into();
+    dialog.set_database_path(shown_path.clone());
+    dialog.set_current_database_path(shown_path);
+    dialog.set_browse_text(ui_tr(&lang, "Browse…").into());
+    dialog.set_copy_text(ui_tr(&lang, "Copy the current database to the new location").into());
+    dialog.set_copy_database(true);
+    dialog.set_unique_toe_text(ui_tr(&lang, "Require unique TOE numbers within a library").into());
+    dialog.set_unique_toe_numbers(settings.unique_toe_numbers);
+    dialog.set_numbering_label(ui_tr(&lang, "Numbering by formation level:").into());
+    let styles: Vec<SharedString> = std::iter::once("As in the View menu")
+        .chain(NUMBERING_STYLES.iter().map(|(_, label)| *label))
+        .map(|label| ui_tr(&lang, label).into())
+        .collect();
+    dialog.set_numbering_styles(ModelRc::new(VecModel::from(styles)));
+    let levels = numbering_levels(&state.borrow(), &lang);
+    let numbering_model = Rc::new(VecModel::from(levels.iter().map(|(_, row)| row.clone()).collect::<Vec<_>>()));
+    dialog.set_numbering_levels(ModelRc::new(numbering_model.clone()));
+    dialog.set_ok_text(ui_tr(&lang, "OK").into());
+    dialog.set_cancel_text(ui_tr(&lang, "Cancel").into());
+
+    let model_chosen = numbering_model.clone();
+    dialog.on_numbering_style_chosen(move |row, index| {
+        let Some(row) = usize::try_from(row).ok() else {
+            return;
+        };
+        if let Some(mut data) = model_chosen.row_data(row) {
+            data.style_index = index;
+            model_chosen.set_row_data(row, data);
+        }
+    });
+
+    let weak = dialog.as_weak();
+    let database_dir = current_path.parent().map(std::path::Path::to_path_buf).unwrap_or_default();
+    dialog.on_browse(move || {
+        let weak = weak.clone();
+        let request = FileRequest::save()
+            .filter("SQLite", &["db"])
+            .file_name("toeditor.db")
+            .directory(database_dir.clone());
+        choose_file(request, move |path| {
+            if let Some(d) = weak.upgrade() {
+                d.set_database_path(path.display().to_string().into());
+                d.set_error_text(Default::default());
+            }
+        });
+    });
+
+    let weak = dialog.as_weak();
+    let weak_window = window.as_weak();
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let entered = d.get_database_path().trim().to_string();
+        if entered.is_empty() {
+            d.set_error_text(ui_tr(&lang, "Enter the database file").into());
+            return;
+        }
+        let target = std::path::PathBuf::from(entered);
+        let path_changed = target != current_path;
+        if path_changed {
+            if let Err(e) = switch_database(&state, &target, d.get_copy_database()) {
+                log::error!("Failed to switch to the database {}: {:#}", target.display(), e);
+                d.set_error_text(format!("{}: {:#}", ui_tr(&lang, "Failed to open the database"), e).into());
+                return;
+            }
+            log::info!("Database switched from {} to {}", current_path.display(), target.display());
+            on_database_switched();
+        }
+
+        let language = available_languages()
+            .get(d.get_language_index().max(0) as usize)
+            .map_or("en", |l| l.code);
+        let theme = SETTINGS_THEMES
+            .get(d.get_theme_index().max(0) as usize)
+            .map_or("light", |(code, _)| *code);
+        update_settings(|s| {
+            s.language = language.to_string();
+            s.color_scheme = theme.to_string();
+            s.unique_toe_numbers = d.get_unique_toe_numbers();
+            if path_changed {
+                s.database_path = Some(target.clone());
+            }
+        });
+        // Levels of a database left behind keep no meaning
+        if !path_changed {
+            state.borrow_mut().update_numbering(|numbering| {
+                for (i, (level_id, _)) in levels.iter().enumerate() {
+                    let index = numbering_model.row_data(i).map_or(0, |r| r.style_index);
+                    match usize::try_from(index - 1).ok().and_then(|s| NUMBERING_STYLES.get(s)) {
+                        Some((style, _)) => numbering.set_override(*level_id, *style),
+                        None => {
+                            numbering.level_overrides.remove(level_id);
+                        }
+                    }
+                }
+            });
+        }
+        if let Some(w) = weak_window.upgrade() {
+            if !path_changed {
+                super::refresh_numbered_views(&w, &state);
+            }
+            if w.get_current_language() != language {
+                w.invoke_switch_language(language.into());
+            }
+            if w.get_theme() != theme {
+                w.invoke_switch_theme(theme.into());
+            }
+        }
+        d.hide().unwrap_or_default();
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Styles a formation level can be numbered in, with their menu labels
+const NUMBERING_STYLES: [(NumberingStyle, &str); 3] = [
+    (NumberingStyle::Arabic, "Arabic (1, 2, 3)"),
+    (NumberingStyle::Roman, "Roman (I, II, III)"),
+    (NumberingStyle::Letters, "Letters (A, B, C)"),
+];
+
+/// Formation levels of the current library with the numbering style of each
+fn numbering_levels(state: &AppState, lang: &str) -> Vec<(i64, NumberingLevelRow)> {
+    let (Some(db), Some(library)) = (state.database(), state.current_library.as_ref()) else {
+        return Vec::new();
+    };
+    let Some(library_id) = library.id else {
+        return Vec::new();
+    };
+    let levels = match FormationLevelRepo::new(db.conn()).list_by_library(library_id) {
+        Ok(levels) => levels,
+        Err(e) => {
+            log::error!("Failed to load formation levels: {}", e);
+            return Vec::new();
+        }
+    };
+    let name_pref = DisplayLang::from_code(&library.display_language);
+    levels
+        .iter()
+        .filter_map(|level| {
+            let id = level.id?;
+            let style = library.numbering.level_overrides.get(&id);
+            let style_index = style.and_then(|s| NUMBERING_STYLES.iter().position(|(n, _)| n == s)).map_or(0, |i| i as i32 + 1);
+            let name = display_name(&level.name_ru, &level.name_en, name_pref, lang).into();
+            Some((id, NumberingLevelRow { name, style_index }))
+        })
+        .collect()
+}
+
+/// Size of the database file, or a note that it does not exist yet
+fn database_size_text(lang: &str, path: &std::path::Path) -> String {
+    match std::fs::metadata(path) {
+        Ok(meta) => crate::import::limits::format_size(meta.len()),
+        Err(_) => ui_tr(lang, "Not created yet"),
+    }
+}
+
+/// Show the database file and export folder in use in the data paths dialog
+fn show_data_paths(dialog: &super::DataPathsDialog, lang: &str) {
+    let settings = crate::config::Settings::load().unwrap_or_default();
+    let database_path = settings.resolved_database_path().unwrap_or_default();
+    dialog.set_database_path(database_path.display().to_string().into());
+    dialog.set_database_size(database_size_text(lang, &database_path).into());
+    dialog.set_export_dir(settings.export_dir().display().to_string().into());
+}
+
+/// Tools > Data Paths: the database file with its size and the export folder.
+/// Choosing an existing database file opens it; choosing a new file copies
+/// the current database there first. `on_database_switched` runs once
+/// another database file is open.
+pub(super) fn show_data_paths_dialog(lang: &str, state: Rc<RefCell<AppState>>, on_database_switched: impl Fn() + 'static) {
+    let dialog = match super::DataPathsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create data paths dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    dialog.set_dialog_title(ui_tr(lang, "Data Paths").into());
+    dialog.set_database_label(ui_tr(lang, "Database file:").into());
+    dialog.set_export_label(ui_tr(lang, "Export folder:").into());
+    dialog.set_open_folder_text(ui_tr(lang, "Open containing folder").into());
+    dialog.set_change_database_text(ui_tr(lang, "Change database…").into());
+    dialog.set_change_export_text(ui_tr(lang, "Change export folder…").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
+    show_data_paths(&dialog, lang);
+
+    let weak = dialog.as_weak();
+    let lang_open = lang.to_string();
+    dialog.on_open_folder(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let path = std::path::PathBuf::from(d.get_database_path().as_str());
+        let Some(folder) = path.parent() else {
+            return;
+        };
+        if let Err(e) = super::print::open_with_system(folder) {
+            log::error!("Failed to open {}: {}", folder.display(), e);
+            d.set_error_text(format!("{}: {}", ui_tr(&lang_open, "Failed to open the folder"), e).into());
+        }
+    });
+
+    let weak = dialog.as_weak();
+    let lang_db = lang.to_string();
+    let on_database_switched = Rc::new(on_database_switched);
+    dialog.on_change_database(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let current = std::path::PathBuf::from(d.get_database_path().as_str());
+        let mut request = FileRequest::save().filter("SQLite", &["db"]).file_name("toeditor.db");
+        if let Some(dir) = current.parent() {
+            request = request.directory(dir);
+        }
+        let (weak, lang, state, on_database_switched) = (weak.clone(), lang_db.clone(), state.clone(), on_database_switched.clone());
+        choose_file(request, move |target| {
+            let Some(d) = weak.upgrade() else {
+                return;
+            };
+            if target == current {
+                return;
+            }
+            let copy = !target.exists();
+            if let Err(e) = switch_database(&state, &target, copy) {
+                log::error!("Failed to switch to the database {}: {:#}", target.display(), e);
+                d.set_error_text(format!("{}: {:#}", ui_tr(&lang, "Failed to open the database"), e).into());
+                return;
+            }
+            log::info!("Database switched from {} to {}", current.display(), target.display());
+            update_settings(|s| s.database_path = Some(target.clone()));
+            d.set_error_text(Default::default());
+            show_data_paths(&d, &lang);
+            on_database_switched();
+        });
+    });
+
+    let weak = dialog.as_weak();
+    let lang_export = lang.to_string();
+    dialog.on_change_export_dir(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let request = FileRequest::folder().directory(d.get_export_dir().as_str());
+        let (weak, lang) = (weak.clone(), lang_export.clone());
+        choose_file(request, move |dir| {
+            update_settings(|s| s.default_export_dir = Some(dir.clone()));
+            log::info!("Export folder set to {}", dir.display());
+            if let Some(d) = weak.upgrade() {
+                show_data_paths(&d, &lang);
+            }
+        });
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_closed(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Tools > Reset Settings: after confirmation, write the default settings and
+/// apply them at once. A database opened from a custom path is swapped for
+/// the default one only after a second confirmation.
+pub(super) fn reset_settings(window: &MainWindow, state: Rc<RefCell<AppState>>, on_database_switched: impl Fn() + 'static) {
+    let lang = window.get_current_language().to_string();
+    let spec = ConfirmSpec::new(
+        "Reset Settings",
+        ui_tr(&lang, "Restore all settings to their defaults? Recent libraries and window positions are forgotten as well."),
+        "Reset",
+    )
+    .danger();
+    let weak_window = window.as_weak();
+    let on_database_switched = Rc::new(on_database_switched);
+    confirm(&lang, spec, move |confirmed| {
+        if !confirmed {
+            return;
+        }
+        let previous = crate::config::Settings::load().unwrap_or_default();
+        let defaults = match crate::config::Settings::reset() {
+            Ok(defaults) => defaults,
+            Err(e) => {
+                log::error!("Failed to reset settings: {}", e);
+                show_error_dialog(&previous.language, "Error", "Failed to reset settings: {}", &[&e]);
+                return;
+            }
+        };
+        let Some(window) = weak_window.upgrade() else {
+            return;
+        };
+        apply_settings(&window, &state, &defaults);
+        log::info!("Settings reset to defaults");
+
+        let default_path = match crate::config::Settings::default_database_path() {
+            Ok(path) => path,
+            Err(e) => {
+                log::warn!("No default database location: {}", e);
+                return;
+            }
+        };
+        let Some(custom_path) = database_left_by_reset(&previous, &default_path) else {
+            return;
+        };
+        let lang = defaults.language.clone();
+        let message = ui_tr(&lang, "The database {} is still open. Open the default database {} instead?")
+            .replacen("{}", &custom_path.display().to_string(), 1)
+            .replacen("{}", &default_path.display().to_string(), 1);
+        let state = state.clone();
+        let on_database_switched = on_database_switched.clone();
+        let error_lang = lang.clone();
+        confirm(&lang, ConfirmSpec::new("Reset Settings", message, "Open Default Database"), move |confirmed| {
+            if !confirmed {
+                // Keep the file open now and on the next start
+                update_settings(|s| s.database_path = Some(custom_path.clone()));
+                return;
+            }
+            match switch_database(&state, &default_path, false) {
+                Ok(()) => {
+                    log::info!("Database switched from {} to {}", custom_path.display(), default_path.display());
+                    on_database_switched();
+                }
+                Err(e) => {
+                    log::error!("Failed to open the default database {}: {:#}", default_path.display(), e);
+                    show_error_dialog(&error_lang, "Error", "Failed to open the database: {}", &[&format!("{:#}", e)]);
+                    update_settings(|s| s.database_path = Some(custom_path.clone()));
+                }
+            }
+        });
+    });
+}
+
+/// Open the database at `target` in place of the current one, with `copy`
+/// copying the current one there first. On failure the current database
+/// stays open.
+fn switch_database(state: &Rc<RefCell<AppState>>, target: &std::path::Path, copy: bool) -> anyhow::Result<()> {
+    {
+        let st = state.borrow();
+        crate::db::storage::prepare_database_switch(st.database().filter(|_| copy), target)?;
+    }
+    let database = crate::db::Database::open(target)?;
+    if crate::config::Settings::load().unwrap_or_default().safe_database_mode {
+        if let Err(e) = database.use_safe_mode() {
+            log::warn!("Failed to switch the database to safe mode: {}", e);
+        }
+    }
+    let mut st = state.borrow_mut();
+    st.set_database(Some(database));
+    st.current_library = None;
+    st.dirty.set(crate::services::DirtyState::default());
+    Ok(())
+}
+
+/// Change the saved settings
+fn update_settings(change: impl FnOnce(&mut crate::config::Settings)) {
+    let mut settings = crate::config::Settings::load().unwrap_or_default();
+    change(&mut settings);
+    if let Err(e) = settings.save() {
+        log::error!("Failed to save settings: {}", e);
+    }
+}
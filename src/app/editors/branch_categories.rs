@@ -5,13 +5,76 @@ use std::cell::RefCell;
 use slint::{ComponentHandle, Model, ModelRc, VecModel};
 
 use crate::db::repositories::BranchCategoryRepo;
+use crate::models::{display_name, CopiedData, DisplayLang};
 use crate::export::{
-    export_branch_categories_to_path, import_branch_categories_from_path,
+    branch_category_exports, export_branch_categories_to_path, import_branch_categories_from_path,
     copy_branch_categories_between_libraries,
 };
 
 use super::super::{BranchCategoriesEditor, CategoryRow, OtherLibraryItem, AppState};
+use super::super::open_windows::OpenWindow;
 use super::super::translations::ui_tr;
+use super::super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::super::file_dialog::{choose_file, FileRequest};
+use super::{
+    adopt_stored_ids, commit_row, copy_to_clipboard, focus_open_editor, library_display_language, move_row, name_language, nothing_to_paste,
+    paste_status, record_copy, register_open_editor, report_save_error, save_unless_changed, LoadedRows,
+};
+use super::super::notify_change;
+use super::super::confirm::{confirm, ConfirmSpec};
+use super::super::recovery::EditorJournal;
+use crate::services::{
+    paste_rows, referenced_categories, save_branch_categories, ChangeEvent, ClipboardContent, EditorKind, EditorRows,
+};
+
+/// The editor's rows with the name fields of the selected row applied
+fn current_rows(ed: &BranchCategoriesEditor, model: &VecModel<CategoryRow>, lib_id: i64) -> EditorRows {
+    let mut categories = row_categories(model, lib_id);
+    if let Some(c) = usize::try_from(ed.get_current_index()).ok().and_then(|i| categories.get_mut(i)) {
+        c.name_ru = ed.get_current_name_ru().to_string();
+        c.name_en = ed.get_current_name_en().to_string();
+    }
+    EditorRows::BranchCategories(categories)
+}
+
+/// Write the name fields into the selected row
+fn commit_current_row(ed: &BranchCategoriesEditor, model: &VecModel<CategoryRow>) {
+    commit_row(model, ed.get_current_index(), |r| {
+        r.name_ru = ed.get_current_name_ru();
+        r.name_en = ed.get_current_name_en();
+    });
+}
+
+fn row_categories(model: &VecModel<CategoryRow>, lib_id: i64) -> Vec<crate::models::BranchCategory> {
+    model
+        .iter()
+        .map(|r| {
+            // Stored categories keep their id so their branches stay in them
+            let mut category = crate::models::BranchCategory::new(lib_id, r.name_ru.to_string(), r.name_en.to_string());
+            category.id = (r.id >= 0).then_some(r.id as i64);
+            category
+        })
+        .collect()
+}
+
+/// Set the editor texts in `lang`
+fn translate(editor: &BranchCategoriesEditor, lang: &str) {
+    editor.set_name_language(name_language(&editor.get_display_language(), lang).into());
+    editor.set_tr_categories_title(ui_tr(lang, "Branch categories").into());
+    editor.set_tr_name_russian(ui_tr(lang, "Name (Russian)").into());
+    editor.set_tr_name_english(ui_tr(lang, "Name (English)").into());
+    editor.set_tr_add(ui_tr(lang, "Add").into());
+    editor.set_tr_delete(ui_tr(lang, "Delete").into());
+    editor.set_tr_export(ui_tr(lang, "Export…").into());
+    editor.set_tr_import(ui_tr(lang, "Import…").into());
+    editor.set_tr_move_up(ui_tr(lang, "Move Up").into());
+    editor.set_tr_move_down(ui_tr(lang, "Move Down").into());
+    editor.set_tr_copy_from_library(ui_tr(lang, "Copy from library").into());
+    editor.set_tr_copy_all(ui_tr(lang, "Copy all").into());
+    editor.set_tr_paste(ui_tr(lang, "Paste").into());
+    editor.set_tr_save(ui_tr(lang, "Save").into());
+    editor.set_tr_close(ui_tr(lang, "Close").into());
+}
 
 /// Open the Branch categories editor window for the given library.
 pub(in crate::app) fn show_branch_categories_editor(
@@ -20,9 +83,12 @@ pub(in crate::app) fn show_branch_categories_editor(
     lib_name: &str,
     lang: &str,
 ) {
-    let (categories, other_library_items, source_library_ids) = {
+    if focus_open_editor(lib_id, EditorKind::BranchCategories) {
+        return;
+    }
+    let (categories, other_library_items, source_library_ids, display_language) = {
         let st = state.borrow();
-        let db = match st.database.as_ref() {
+        let db = match st.database() {
             Some(d) => d,
             None => {
                 log::error!("Database not initialized");
@@ -38,21 +104,17 @@ pub(in crate::app) fn show_branch_categories_editor(
             categories = cat_repo.list_by_library(lib_id).unwrap_or_default();
         }
         let lib_repo = crate::db::repositories::LibraryRepo::new(db.conn());
-        let all_libs = lib_repo.list_all().unwrap_or_default();
+        let all_libs = lib_repo.list_summaries().unwrap_or_default();
         let mut other_items = Vec::new();
         let mut source_ids = Vec::new();
-        for l in all_libs {
-            if l.id != Some(lib_id) {
-                if let Some(id) = l.id {
-                    other_items.push(OtherLibraryItem {
-                        id: id as i32,
-                        name: l.name.into(),
-                    });
-                    source_ids.push(id);
-                }
-            }
+        for l in all_libs.into_iter().filter(|l| l.id != lib_id) {
+            other_items.push(OtherLibraryItem {
+                id: l.id as i32,
+                name: l.name.into(),
+            });
+            source_ids.push(l.id);
         }
-        (categories, other_items, source_ids)
+        (categories, other_items, source_ids, library_display_language(db.conn(), lib_id))
     };
     let rows: Vec<CategoryRow> = categories
         .into_iter()
@@ -71,30 +133,39 @@ pub(in crate::app) fn show_branch_categories_editor(
     };
     editor.set_library_id(lib_id as i32);
     editor.set_library_name(lib_name.into());
+    editor.set_display_language(display_language.into());
     let model = Rc::new(VecModel::from(rows));
     editor.set_categories(ModelRc::new(model.clone()));
     editor.set_current_index(-1);
     editor.set_current_name_ru(Default::default());
     editor.set_current_name_en(Default::default());
-    editor.set_tr_categories_title(ui_tr(lang, "Branch categories").into());
-    editor.set_tr_name_russian(ui_tr(lang, "Name (Russian)").into());
-    editor.set_tr_name_english(ui_tr(lang, "Name (English)").into());
-    editor.set_tr_add(ui_tr(lang, "Add").into());
-    editor.set_tr_delete(ui_tr(lang, "Delete").into());
-    editor.set_tr_export(ui_tr(lang, "Export…").into());
-    editor.set_tr_import(ui_tr(lang, "Import…").into());
-    editor.set_tr_copy_from_library(ui_tr(lang, "Copy from library").into());
-    editor.set_tr_close(ui_tr(lang, "Close").into());
+    translate(&editor, lang);
     editor.set_other_libraries(ModelRc::new(VecModel::from(other_library_items)));
     editor.set_copy_source_index(-1);
     let state_close = state.clone();
     let weak_editor = editor.as_weak();
+    let journal = EditorJournal::new(&state, lib_id, lib_name, EditorKind::BranchCategories);
+    let loaded = LoadedRows::new(&state, lib_id, EditorKind::BranchCategories);
+    let note_change = {
+        let weak = weak_editor.clone();
+        let model = model.clone();
+        let journal = journal.clone();
+        Rc::new(move || {
+            if let Some(ed) = weak.upgrade() {
+                journal.changed(current_rows(&ed, &model, lib_id));
+            }
+        })
+    };
+    let note_edit = note_change.clone();
+    editor.on_form_edited(move || note_edit());
     let weak_add = weak_editor.clone();
     let model_add = model.clone();
+    let note_add = note_change.clone();
     editor.on_add_category(move || {
         let Some(ed) = weak_add.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_add);
         let row = CategoryRow {
             id: -1,
             name_ru: Default::default(),
@@ -104,13 +175,16 @@ pub(in crate::app) fn show_branch_categories_editor(
         ed.set_current_index(model_add.row_count() as i32 - 1);
         ed.set_current_name_ru(row.name_ru.clone());
         ed.set_current_name_en(row.name_en.clone());
+        note_add();
     });
     let weak_del = weak_editor.clone();
     let model_del = model.clone();
+    let note_del = note_change.clone();
     editor.on_delete_category(move || {
         let Some(ed) = weak_del.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_del);
         let idx = ed.get_current_index();
         if idx >= 0 && (idx as usize) < model_del.row_count() {
             model_del.remove(idx as usize);
@@ -127,103 +201,213 @@ pub(in crate::app) fn show_branch_categories_editor(
                     ed.set_current_name_en(r.name_en.clone());
                 }
             }
+            note_del();
+        }
+    });
+    let weak_up = weak_editor.clone();
+    let model_up = model.clone();
+    let note_up = note_change.clone();
+    editor.on_move_up(move || {
+        let Some(ed) = weak_up.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_up);
+        if let Some(new_idx) = move_row(&model_up, ed.get_current_index(), true) {
+            ed.set_current_index(new_idx);
+            note_up();
+        }
+    });
+    let weak_down = weak_editor.clone();
+    let model_down = model.clone();
+    let note_down = note_change.clone();
+    editor.on_move_down(move || {
+        let Some(ed) = weak_down.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_down);
+        if let Some(new_idx) = move_row(&model_down, ed.get_current_index(), false) {
+            ed.set_current_index(new_idx);
+            note_down();
         }
     });
     let weak_close = weak_editor.clone();
     let model_close = model.clone();
-    editor.on_close_editor(move || {
+    let journal_close = journal.clone();
+    let loaded_close = loaded.clone();
+    let lib_name_close = lib_name.to_string();
+    let lang_close = lang.to_string();
+    // Write the rows, then close the window when `close` is set. A failed
+    // write keeps the window open with the rows.
+    let save_rows: Rc<dyn Fn(bool)> = Rc::new(move |close| {
         let Some(ed) = weak_close.upgrade() else {
             return;
         };
-        let idx = ed.get_current_index();
-        if idx >= 0 && (idx as usize) < model_close.row_count() {
-            let ru = ed.get_current_name_ru();
-            let en = ed.get_current_name_en();
-            if let Some(r) = model_close.row_data(idx as usize) {
-                model_close.set_row_data(
-                    idx as usize,
-                    CategoryRow {
-                        id: r.id,
-                        name_ru: ru,
-                        name_en: en,
-                    },
-                );
-            }
+        commit_current_row(&ed, &model_close);
+        let categories = row_categories(&model_close, lib_id);
+        if close && loaded_close.is_saved(&EditorRows::BranchCategories(categories.clone())) {
+            journal_close.finished();
+            hide_remembering(&ed, WindowKind::BranchCategoriesEditor);
+            return;
         }
-        let st = state_close.borrow();
-        if let Some(ref db) = st.database {
-            let conn = db.conn();
-            if let Err(e) = conn.execute_batch("BEGIN IMMEDIATE") {
-                log::error!("Failed to begin transaction: {}", e);
-            } else {
-                let repo = BranchCategoryRepo::new(conn);
-                let mut ok = repo.delete_by_library(lib_id).is_ok();
-                if ok {
-                    for i in 0..model_close.row_count() {
-                        if let Some(r) = model_close.row_data(i) {
-                            let mut c = crate::models::BranchCategory::new(
-                                lib_id,
-                                r.name_ru.to_string(),
-                                r.name_en.to_string(),
-                            );
-                            if repo.create(&mut c).is_err() {
-                                ok = false;
-                                break;
-                            }
-                        }
-                    }
+        let referenced = state_close
+            .borrow()
+            .database()
+            .map(|db| referenced_categories(db.conn(), lib_id, &categories));
+        let referenced = match referenced {
+            Some(Ok(referenced)) => referenced,
+            Some(Err(e)) => {
+                log::error!("Failed to check branches of removed categories: {}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        let (state_save, weak_save, journal_save) = (state_close.clone(), weak_close.clone(), journal_close.clone());
+        let (model_save, loaded_save, lang_save) = (model_close.clone(), loaded_close.clone(), lang_close.clone());
+        let save = move || {
+            let saved = state_save.borrow().database().map(|db| {
+                save_branch_categories(db.conn(), lib_id, &categories)?;
+                BranchCategoryRepo::new(db.conn()).list_by_library(lib_id)
+            });
+            let stored = match saved {
+                Some(Ok(stored)) => stored,
+                Some(Err(e)) => {
+                    report_save_error(&lang_save, EditorKind::BranchCategories, lib_id, &e);
+                    return;
                 }
-                if ok {
-                    let _ = conn.execute_batch("COMMIT");
+                None => return,
+            };
+            let ids: Vec<i64> = stored.iter().filter_map(|c| c.id).collect();
+            adopt_stored_ids(&model_save, &ids, |row, id| row.id = id);
+            loaded_save.saved(EditorRows::BranchCategories(row_categories(&model_save, lib_id)));
+            journal_save.finished();
+            notify_change(&state_save, ChangeEvent::BranchSetChanged(lib_id));
+            if let Some(ed) = weak_save.upgrade() {
+                if close {
+                    hide_remembering(&ed, WindowKind::BranchCategoriesEditor);
                 } else {
-                    log::error!("Rolling back branch categories save for library {}", lib_id);
-                    let _ = conn.execute_batch("ROLLBACK");
+                    ed.set_status_text(ui_tr(&lang_save, "Saved").into());
                 }
             }
+        };
+        let (state_reload, weak_reload, journal_reload) = (state_close.clone(), weak_close.clone(), journal_close.clone());
+        let (lib_name, lang) = (lib_name_close.clone(), lang_close.clone());
+        let reload = move || {
+            journal_reload.finished();
+            if let Some(ed) = weak_reload.upgrade() {
+                hide_remembering(&ed, WindowKind::BranchCategoriesEditor);
+            }
+            show_branch_categories_editor(state_reload.clone(), lib_id, &lib_name, &lang);
+        };
+        if referenced.is_empty() {
+            save_unless_changed(&lang_close, &loaded_close, save, reload);
+            return;
         }
-        let _ = ed.hide();
+        // Branches would lose their category: say which, and only go on when
+        // the user agrees to leave them without one
+        let name_pref = DisplayLang::from_code(&ed.get_name_language());
+        let name = |ru: &str, en: &str| display_name(ru, en, name_pref, &lang_close).to_string();
+        let lines: Vec<String> = referenced
+            .iter()
+            .map(|r| {
+                let branches: Vec<String> = r.branches.iter().map(|b| name(&b.name_ru, &b.name_en)).collect();
+                format!("{}: {}", name(&r.category.name_ru, &r.category.name_en), branches.join(", "))
+            })
+            .collect();
+        let message = ui_tr(
+            &lang_close,
+            "Branches still belong to the removed categories:\n{}\nRemove the categories and leave these branches without a category?",
+        )
+        .replacen("{}", &lines.join("\n"), 1);
+        let spec = ConfirmSpec::new("Branch categories", message, "Clear categories").danger();
+        let (save, reload) = (Rc::new(save), Rc::new(reload));
+        let (lang, loaded) = (lang_close.clone(), loaded_close.clone());
+        confirm(&lang_close, spec, move |confirmed| {
+            if confirmed {
+                let (save, reload) = (save.clone(), reload.clone());
+                save_unless_changed(&lang, &loaded, move || save(), move || reload());
+            }
+        });
     });
+    let save_click = save_rows.clone();
+    editor.on_save(move || save_click(false));
+    editor.on_close_editor(move || save_rows(true));
     let weak_sel = weak_editor.clone();
     let model_sel = model.clone();
     editor.on_selection_changed(move |index| {
         let Some(ed) = weak_sel.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_sel);
         if index >= 0 && (index as usize) < model_sel.row_count() {
             if let Some(r) = model_sel.row_data(index as usize) {
+                ed.set_current_index(index);
                 ed.set_current_name_ru(r.name_ru.clone());
                 ed.set_current_name_en(r.name_en.clone());
             }
         }
     });
+    let weak_exp = weak_editor.clone();
     let model_exp = model.clone();
     editor.on_export_categories(move || {
-        let categories: Vec<crate::models::BranchCategory> = (0..model_exp.row_count())
-            .filter_map(|i| model_exp.row_data(i))
-            .map(|r| {
-                crate::models::BranchCategory::new(
-                    lib_id,
-                    r.name_ru.to_string(),
-                    r.name_en.to_string(),
-                )
-            })
-            .collect();
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("JSON", &["json"])
-            .save_file()
-        {
+        if let Some(ed) = weak_exp.upgrade() {
+            commit_current_row(&ed, &model_exp);
+        }
+        let categories = row_categories(&model_exp, lib_id);
+        choose_file(FileRequest::save().filter("JSON", &["json"]), move |path| {
             if let Err(e) = export_branch_categories_to_path(path.as_path(), &categories) {
                 log::error!("Export branch categories: {}", e);
             }
+        });
+    });
+    let state_clip = state.clone();
+    let weak_clip = weak_editor.clone();
+    let model_clip = model.clone();
+    let lang_clip = lang.to_string();
+    editor.on_copy_all(move || {
+        let Some(ed) = weak_clip.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_clip);
+        let content = ClipboardContent::Categories(branch_category_exports(&row_categories(&model_clip, lib_id)));
+        let (text, status) = copy_to_clipboard(&state_clip, &lang_clip, content);
+        ed.invoke_copy_to_system_clipboard(text.into());
+        ed.set_status_text(status.into());
+    });
+    let state_paste = state.clone();
+    let weak_paste = weak_editor.clone();
+    let model_paste = model.clone();
+    let lang_paste = lang.to_string();
+    let note_paste = note_change.clone();
+    editor.on_paste_rows(move || {
+        let Some(ed) = weak_paste.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_paste);
+        let clipboard = state_paste.borrow().clipboard.clone();
+        let Some(ClipboardContent::Categories(rows)) = clipboard else {
+            ed.set_status_text(nothing_to_paste(&lang_paste).into());
+            return;
+        };
+        let pasted = paste_rows(model_paste.iter().map(|r| r.name_en), &rows, |c| &c.name_en);
+        for c in &pasted.rows {
+            model_paste.push(CategoryRow {
+                id: -1,
+                name_ru: c.name_ru.as_str().into(),
+                name_en: c.name_en.as_str().into(),
+            });
         }
+        if !pasted.rows.is_empty() {
+            note_paste();
+        }
+        ed.set_status_text(paste_status(&lang_paste, &pasted).into());
     });
     let weak_imp = weak_editor.clone();
     let model_imp = model.clone();
     editor.on_import_categories(move || {
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("JSON", &["json"])
-            .pick_file()
-        {
+        let weak_imp = weak_imp.clone();
+        let model_imp = model_imp.clone();
+        let note_imp = note_change.clone();
+        choose_file(FileRequest::open().filter("JSON", &["json"]), move |path| {
             match import_branch_categories_from_path(path.as_path()) {
                 Ok(imported) => {
                     while model_imp.row_count() > 0 {
@@ -248,10 +432,11 @@ pub(in crate::app) fn show_branch_categories_editor(
                             }
                         }
                     }
+                    note_imp();
                 }
                 Err(e) => log::error!("Import branch categories: {}", e),
             }
-        }
+        });
     });
     let state_copy = state.clone();
     let weak_copy = weak_editor.clone();
@@ -267,7 +452,7 @@ pub(in crate::app) fn show_branch_categories_editor(
         }
         let source_id = source_ids[idx as usize];
         let st = state_copy.borrow();
-        if let Some(ref db) = st.database {
+        if let Some(db) = st.database() {
             let cat_repo = BranchCategoryRepo::new(db.conn());
             if let Err(e) =
                 copy_branch_categories_between_libraries(&cat_repo, source_id, lib_id)
@@ -275,9 +460,11 @@ pub(in crate::app) fn show_branch_categories_editor(
                 log::error!("Copy branch categories: {}", e);
                 return;
             }
+            record_copy(db.conn(), lib_id, source_id, CopiedData::BranchCategories);
             drop(st);
+            loaded.refresh();
             let st2 = state_copy.borrow();
-            if let Some(ref db2) = st2.database {
+            if let Some(db2) = st2.database() {
                 let cat_repo2 = BranchCategoryRepo::new(db2.conn());
                 if let Ok(new_cats) = cat_repo2.list_by_library(lib_id) {
                     while model_copy.row_count() > 0 {
@@ -304,5 +491,9 @@ pub(in crate::app) fn show_branch_categories_editor(
             }
         }
     });
-    editor.show().unwrap_or_default();
+    remember_geometry(&editor, WindowKind::BranchCategoriesEditor);
+    if editor.show().is_ok() {
+        state.borrow_mut().open_windows.add(OpenWindow::of(&editor, translate));
+    }
+    register_open_editor(&editor, lib_id, EditorKind::BranchCategories);
 }
@@ -1,17 +1,82 @@
 //! Branches editor window
 
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
 use slint::{ComponentHandle, Model, ModelRc, VecModel};
 
-use crate::models::Branch;
+use crate::i18n::pick_name;
+use crate::models::{Branch, BranchCategory, CopiedData};
 use crate::db::repositories::{BranchRepo, BranchCategoryRepo};
 use crate::export::{
-    export_branches_to_path, import_branches_from_path, copy_branches_between_libraries,
+    branch_exports, export_branches_with_categories_to_path, import_branches_file_from_path, copy_branches_between_libraries,
+    resolve_branch_import, resolve_branches_file_import, BranchesFile, CategoryResolution, UnmatchedCategory,
 };
 
-use super::super::{BranchesEditor, BranchRow, OtherLibraryItem, CategoryItem, AppState};
+use super::super::{
+    BranchesEditor, BranchRow, OtherLibraryItem, CategoryItem, CategoryConflictDialog,
+    CategoryConflictRow, AppState,
+};
+use super::super::open_windows::OpenWindow;
 use super::super::translations::ui_tr;
+use super::super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::super::file_dialog::{choose_file, FileRequest};
+use super::super::confirm::{confirm, ConfirmSpec};
+use super::{
+    commit_row, copy_to_clipboard, focus_open_editor, library_display_language, move_row, name_language, nothing_to_paste,
+    paste_status, record_copy, register_open_editor, report_save_error, save_unless_changed, LoadedRows,
+};
+use super::super::notify_change;
+use super::super::recovery::EditorJournal;
+use crate::services::{paste_rows, save_branches, save_branches_with_categories, ChangeEvent, ClipboardContent, EditorKind, EditorRows};
+
+/// The editor's rows with the name fields of the selected row applied
+fn current_rows(ed: &BranchesEditor, model: &VecModel<BranchRow>, lib_id: i64) -> EditorRows {
+    let mut branches = row_branches(model, lib_id);
+    if let Some(b) = usize::try_from(ed.get_current_index()).ok().and_then(|i| branches.get_mut(i)) {
+        b.name_ru = ed.get_current_name_ru().to_string();
+        b.name_en = ed.get_current_name_en().to_string();
+    }
+    EditorRows::Branches(branches)
+}
+
+/// Write the name fields into the selected row
+fn commit_current_row(ed: &BranchesEditor, model: &VecModel<BranchRow>) {
+    commit_row(model, ed.get_current_index(), |r| {
+        r.name_ru = ed.get_current_name_ru();
+        r.name_en = ed.get_current_name_en();
+    });
+}
+
+fn row_branches(model: &VecModel<BranchRow>, lib_id: i64) -> Vec<Branch> {
+    model
+        .iter()
+        .map(|r| {
+            let cat_id = if r.category_id != -1 { Some(r.category_id as i64) } else { None };
+            Branch::with_category(lib_id, cat_id, r.name_ru.to_string(), r.name_en.to_string())
+        })
+        .collect()
+}
+
+/// Set the editor texts in `lang`
+fn translate(editor: &BranchesEditor, lang: &str) {
+    editor.set_name_language(name_language(&editor.get_display_language(), lang).into());
+    editor.set_tr_branches_title(ui_tr(lang, "Branches of service").into());
+    editor.set_tr_name_russian(ui_tr(lang, "Name (Russian)").into());
+    editor.set_tr_name_english(ui_tr(lang, "Name (English)").into());
+    editor.set_tr_category(ui_tr(lang, "Category").into());
+    editor.set_tr_add(ui_tr(lang, "Add").into());
+    editor.set_tr_delete(ui_tr(lang, "Delete").into());
+    editor.set_tr_export(ui_tr(lang, "Export…").into());
+    editor.set_tr_import(ui_tr(lang, "Import…").into());
+    editor.set_tr_move_up(ui_tr(lang, "Move Up").into());
+    editor.set_tr_move_down(ui_tr(lang, "Move Down").into());
+    editor.set_tr_copy_from_library(ui_tr(lang, "Copy from library").into());
+    editor.set_tr_copy_all(ui_tr(lang, "Copy all").into());
+    editor.set_tr_paste(ui_tr(lang, "Paste").into());
+    editor.set_tr_save(ui_tr(lang, "Save").into());
+    editor.set_tr_close(ui_tr(lang, "Close").into());
+}
 
 /// Open the Branches editor window for the given library.
 pub(in crate::app) fn show_branches_editor(
@@ -20,9 +85,12 @@ pub(in crate::app) fn show_branches_editor(
     lib_name: &str,
     lang: &str,
 ) {
-    let (branches, other_library_items, source_library_ids, category_items) = {
+    if focus_open_editor(lib_id, EditorKind::Branches) {
+        return;
+    }
+    let (branches, other_library_items, source_library_ids, initial_categories, name_pref) = {
         let st = state.borrow();
-        let db = match st.database.as_ref() {
+        let db = match st.database() {
             Some(d) => d,
             None => {
                 log::error!("Database not initialized");
@@ -57,38 +125,27 @@ pub(in crate::app) fn show_branches_editor(
             branches = branch_repo.list_by_library(lib_id).unwrap_or_default();
         }
         let lib_repo = crate::db::repositories::LibraryRepo::new(db.conn());
-        let all_libs = lib_repo.list_all().unwrap_or_default();
+        let all_libs = lib_repo.list_summaries().unwrap_or_default();
         let mut other_items = Vec::new();
         let mut source_ids = Vec::new();
-        for l in all_libs {
-            if l.id != Some(lib_id) {
-                if let Some(id) = l.id {
-                    other_items.push(OtherLibraryItem {
-                        id: id as i32,
-                        name: l.name.into(),
-                    });
-                    source_ids.push(id);
-                }
-            }
+        for l in all_libs.into_iter().filter(|l| l.id != lib_id) {
+            other_items.push(OtherLibraryItem {
+                id: l.id as i32,
+                name: l.name.into(),
+            });
+            source_ids.push(l.id);
         }
         let categories = BranchCategoryRepo::new(db.conn())
             .list_by_library(lib_id)
             .unwrap_or_default();
-        let category_items: Vec<CategoryItem> = categories
-            .iter()
-            .map(|c| CategoryItem {
-                id: c.id.unwrap_or(-1) as i32,
-                name: if lang == "ru" {
-                    c.name_ru.as_str()
-                } else {
-                    c.name_en.as_str()
-                }
-                .into(),
-            })
-            .collect();
-        (branches, other_items, source_ids, category_items)
+        let name_pref = library_display_language(db.conn(), lib_id);
+        let items = category_items(&categories, &name_pref, lang);
+        (branches, other_items, source_ids, items, name_pref)
     };
-    let category_items_clone = category_items.clone();
+    // Shared so categories created during import show up in the form
+    let category_model = Rc::new(VecModel::from(initial_categories));
+    // Categories chosen for creation during import; stored when the editor closes
+    let pending_categories: Rc<RefCell<Vec<BranchCategory>>> = Rc::new(RefCell::new(Vec::new()));
     let rows: Vec<BranchRow> = branches
         .into_iter()
         .map(|b| BranchRow {
@@ -107,33 +164,41 @@ pub(in crate::app) fn show_branches_editor(
     };
     editor.set_library_id(lib_id as i32);
     editor.set_library_name(lib_name.into());
+    editor.set_display_language(name_pref.as_str().into());
     let model = Rc::new(VecModel::from(rows));
     editor.set_branches(ModelRc::new(model.clone()));
     editor.set_current_index(-1);
     editor.set_current_name_ru(Default::default());
     editor.set_current_name_en(Default::default());
-    editor.set_tr_branches_title(ui_tr(lang, "Branches of service").into());
-    editor.set_tr_name_russian(ui_tr(lang, "Name (Russian)").into());
-    editor.set_tr_name_english(ui_tr(lang, "Name (English)").into());
-    editor.set_tr_category(ui_tr(lang, "Category").into());
-    editor.set_categories(ModelRc::new(VecModel::from(category_items.clone())));
+    translate(&editor, lang);
+    editor.set_categories(ModelRc::new(category_model.clone()));
     editor.set_current_category_index(-1);
-    editor.set_tr_add(ui_tr(lang, "Add").into());
-    editor.set_tr_delete(ui_tr(lang, "Delete").into());
-    editor.set_tr_export(ui_tr(lang, "Export…").into());
-    editor.set_tr_import(ui_tr(lang, "Import…").into());
-    editor.set_tr_copy_from_library(ui_tr(lang, "Copy from library").into());
-    editor.set_tr_close(ui_tr(lang, "Close").into());
     editor.set_other_libraries(ModelRc::new(VecModel::from(other_library_items)));
     editor.set_copy_source_index(-1);
     let state_close = state.clone();
     let weak_editor = editor.as_weak();
+    let journal = EditorJournal::new(&state, lib_id, lib_name, EditorKind::Branches);
+    let loaded = LoadedRows::new(&state, lib_id, EditorKind::Branches);
+    let note_change: Rc<dyn Fn()> = {
+        let weak = weak_editor.clone();
+        let model = model.clone();
+        let journal = journal.clone();
+        Rc::new(move || {
+            if let Some(ed) = weak.upgrade() {
+                journal.changed(current_rows(&ed, &model, lib_id));
+            }
+        })
+    };
+    let note_edit = note_change.clone();
+    editor.on_form_edited(move || note_edit());
     let weak_add = weak_editor.clone();
     let model_add = model.clone();
+    let note_add = note_change.clone();
     editor.on_add_branch(move || {
         let Some(ed) = weak_add.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_add);
         let row = BranchRow {
             id: -1,
             category_id: -1,
@@ -145,14 +210,17 @@ pub(in crate::app) fn show_branches_editor(
         ed.set_current_name_ru(row.name_ru.clone());
         ed.set_current_name_en(row.name_en.clone());
         ed.set_current_category_index(-1);
+        note_add();
     });
     let weak_del = weak_editor.clone();
     let model_del = model.clone();
-    let category_items_del = category_items_clone.clone();
+    let note_del = note_change.clone();
+    let category_items_del = category_model.clone();
     editor.on_delete_branch(move || {
         let Some(ed) = weak_del.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_del);
         let idx = ed.get_current_index();
         if idx >= 0 && (idx as usize) < model_del.row_count() {
             model_del.remove(idx as usize);
@@ -168,7 +236,7 @@ pub(in crate::app) fn show_branches_editor(
                 if let Some(r) = model_del.row_data(new_idx) {
                     ed.set_current_name_ru(r.name_ru.clone());
                     ed.set_current_name_en(r.name_en.clone());
-                    let cat_idx = if r.category_id > 0 {
+                    let cat_idx = if r.category_id != -1 {
                         category_items_del
                             .iter()
                             .position(|c| c.id == r.category_id)
@@ -180,81 +248,116 @@ pub(in crate::app) fn show_branches_editor(
                     ed.set_current_category_index(cat_idx);
                 }
             }
+            note_del();
+        }
+    });
+    let weak_up = weak_editor.clone();
+    let model_up = model.clone();
+    let note_up = note_change.clone();
+    editor.on_move_up(move || {
+        let Some(ed) = weak_up.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_up);
+        if let Some(new_idx) = move_row(&model_up, ed.get_current_index(), true) {
+            ed.set_current_index(new_idx);
+            note_up();
+        }
+    });
+    let weak_down = weak_editor.clone();
+    let model_down = model.clone();
+    let note_down = note_change.clone();
+    editor.on_move_down(move || {
+        let Some(ed) = weak_down.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_down);
+        if let Some(new_idx) = move_row(&model_down, ed.get_current_index(), false) {
+            ed.set_current_index(new_idx);
+            note_down();
         }
     });
     let weak_close = weak_editor.clone();
     let model_close = model.clone();
-    editor.on_close_editor(move || {
+    let pending_close = pending_categories.clone();
+    let journal_close = journal.clone();
+    let loaded_close = loaded.clone();
+    let lib_name_close = lib_name.to_string();
+    let lang_close = lang.to_string();
+    // Write the rows, then close the window when `close` is set. A failed
+    // write keeps the window open with the rows.
+    let save_rows: Rc<dyn Fn(bool)> = Rc::new(move |close| {
         let Some(ed) = weak_close.upgrade() else {
             return;
         };
-        let idx = ed.get_current_index();
-        if idx >= 0 && (idx as usize) < model_close.row_count() {
-            let ru = ed.get_current_name_ru();
-            let en = ed.get_current_name_en();
-            if let Some(r) = model_close.row_data(idx as usize) {
-                model_close.set_row_data(
-                    idx as usize,
-                    BranchRow {
-                        id: r.id,
-                        category_id: r.category_id,
-                        name_ru: ru,
-                        name_en: en,
-                    },
-                );
-            }
+        commit_current_row(&ed, &model_close);
+        let branches = row_branches(&model_close, lib_id);
+        let rows = EditorRows::Branches(branches.clone());
+        if close && pending_close.borrow().is_empty() && loaded_close.is_saved(&rows) {
+            journal_close.finished();
+            hide_remembering(&ed, WindowKind::BranchesEditor);
+            return;
         }
-        let st = state_close.borrow();
-        if let Some(ref db) = st.database {
-            let conn = db.conn();
-            if let Err(e) = conn.execute_batch("BEGIN IMMEDIATE") {
-                log::error!("Failed to begin transaction: {}", e);
-            } else {
-                let repo = BranchRepo::new(conn);
-                let mut ok = repo.delete_by_library(lib_id).is_ok();
-                if ok {
-                    for i in 0..model_close.row_count() {
-                        if let Some(r) = model_close.row_data(i) {
-                            let cat_id = if r.category_id > 0 {
-                                Some(r.category_id as i64)
-                            } else {
-                                None
-                            };
-                            let mut b = Branch::with_category(
-                                lib_id,
-                                cat_id,
-                                r.name_ru.to_string(),
-                                r.name_en.to_string(),
-                            );
-                            if repo.create(&mut b).is_err() {
-                                ok = false;
-                                break;
-                            }
-                        }
-                    }
+        let (state_save, weak_save, journal_save) = (state_close.clone(), weak_close.clone(), journal_close.clone());
+        let (pending_save, loaded_save, lang_save) = (pending_close.clone(), loaded_close.clone(), lang_close.clone());
+        let save = move || {
+            let saved = state_save.borrow().database().map(|db| {
+                let pending = pending_save.borrow();
+                if pending.is_empty() {
+                    save_branches(db.conn(), lib_id, &branches)
+                } else {
+                    save_branches_with_categories(db.conn(), lib_id, &pending, &branches).map(drop)
+                }
+            });
+            match saved {
+                Some(Ok(())) => {
+                    pending_save.borrow_mut().clear();
+                    loaded_save.saved(rows.clone());
+                    journal_save.finished();
+                    notify_change(&state_save, ChangeEvent::BranchSetChanged(lib_id));
                 }
-                if ok {
-                    let _ = conn.execute_batch("COMMIT");
+                Some(Err(e)) => {
+                    report_save_error(&lang_save, EditorKind::Branches, lib_id, &e);
+                    return;
+                }
+                None => return,
+            }
+            if let Some(ed) = weak_save.upgrade() {
+                if close {
+                    hide_remembering(&ed, WindowKind::BranchesEditor);
                 } else {
-                    log::error!("Rolling back branches save for library {}", lib_id);
-                    let _ = conn.execute_batch("ROLLBACK");
+                    ed.set_status_text(ui_tr(&lang_save, "Saved").into());
                 }
             }
-        }
-        let _ = ed.hide();
+        };
+        let (state_reload, weak_reload, journal_reload) = (state_close.clone(), weak_close.clone(), journal_close.clone());
+        let (lib_name, lang) = (lib_name_close.clone(), lang_close.clone());
+        let reload = move || {
+            journal_reload.finished();
+            if let Some(ed) = weak_reload.upgrade() {
+                hide_remembering(&ed, WindowKind::BranchesEditor);
+            }
+            show_branches_editor(state_reload.clone(), lib_id, &lib_name, &lang);
+        };
+        save_unless_changed(&lang_close, &loaded_close, save, reload);
     });
+    let save_click = save_rows.clone();
+    editor.on_save(move || save_click(false));
+    editor.on_close_editor(move || save_rows(true));
     let weak_sel = weak_editor.clone();
     let model_sel = model.clone();
-    let category_items_sel = category_items_clone.clone();
+    let category_items_sel = category_model.clone();
     editor.on_selection_changed(move |index| {
         let Some(ed) = weak_sel.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_sel);
         if index >= 0 && (index as usize) < model_sel.row_count() {
             if let Some(r) = model_sel.row_data(index as usize) {
+                ed.set_current_index(index);
                 ed.set_current_name_ru(r.name_ru.clone());
                 ed.set_current_name_en(r.name_en.clone());
-                let cat_idx = if r.category_id > 0 {
+                let cat_idx = if r.category_id != -1 {
                     category_items_sel
                         .iter()
                         .position(|c| c.id == r.category_id)
@@ -267,9 +370,10 @@ pub(in crate::app) fn show_branches_editor(
             }
         }
     });
-    let category_items_cat = category_items_clone.clone();
+    let category_items_cat = category_model.clone();
     let weak_cat = weak_editor.clone();
     let model_cat = model.clone();
+    let note_cat = note_change.clone();
     editor.on_category_changed(move |index| {
         let Some(ed) = weak_cat.upgrade() else {
             return;
@@ -278,10 +382,13 @@ pub(in crate::app) fn show_branches_editor(
         if idx >= 0
             && (idx as usize) < model_cat.row_count()
             && index >= 0
-            && (index as usize) < category_items_cat.len()
+            && (index as usize) < category_items_cat.row_count()
         {
-            if let Some(r) = model_cat.row_data(idx as usize) {
-                let new_cat_id = category_items_cat[index as usize].id;
+            if let (Some(r), Some(cat)) = (
+                model_cat.row_data(idx as usize),
+                category_items_cat.row_data(index as usize),
+            ) {
+                let new_cat_id = cat.id;
                 model_cat.set_row_data(
                     idx as usize,
                     BranchRow {
@@ -292,67 +399,131 @@ pub(in crate::app) fn show_branches_editor(
                     },
                 );
                 ed.set_current_category_index(index);
+                note_cat();
             }
         }
     });
+    let state_exp = state.clone();
+    let weak_exp = weak_editor.clone();
     let model_exp = model.clone();
+    let pending_exp = pending_categories.clone();
     editor.on_export_branches(move || {
-        let branches: Vec<Branch> = (0..model_exp.row_count())
-            .filter_map(|i| model_exp.row_data(i))
-            .map(|r| {
-                Branch::with_category(lib_id, None, r.name_ru.to_string(), r.name_en.to_string())
-            })
-            .collect();
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("JSON", &["json"])
-            .save_file()
-        {
-            if let Err(e) = export_branches_to_path(path.as_path(), &branches) {
+        if let Some(ed) = weak_exp.upgrade() {
+            commit_current_row(&ed, &model_exp);
+        }
+        let branches = row_branches(&model_exp, lib_id);
+        let categories = library_categories(&state_exp, lib_id, &pending_exp.borrow());
+        choose_file(FileRequest::save().filter("JSON", &["json"]), move |path| {
+            if let Err(e) = export_branches_with_categories_to_path(path.as_path(), &branches, &categories) {
                 log::error!("Export branches: {}", e);
             }
+        });
+    });
+    let state_clip = state.clone();
+    let weak_clip = weak_editor.clone();
+    let model_clip = model.clone();
+    let pending_clip = pending_categories.clone();
+    let lang_clip = lang.to_string();
+    editor.on_copy_all(move || {
+        let Some(ed) = weak_clip.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_clip);
+        let categories = library_categories(&state_clip, lib_id, &pending_clip.borrow());
+        let content = ClipboardContent::Branches(branch_exports(&row_branches(&model_clip, lib_id), &categories));
+        let (text, status) = copy_to_clipboard(&state_clip, &lang_clip, content);
+        ed.invoke_copy_to_system_clipboard(text.into());
+        ed.set_status_text(status.into());
+    });
+    let state_paste = state.clone();
+    let weak_paste = weak_editor.clone();
+    let model_paste = model.clone();
+    let pending_paste = pending_categories.clone();
+    let lang_paste = lang.to_string();
+    let note_paste = note_change.clone();
+    editor.on_paste_rows(move || {
+        let Some(ed) = weak_paste.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_paste);
+        let clipboard = state_paste.borrow().clipboard.clone();
+        let Some(ClipboardContent::Branches(rows)) = clipboard else {
+            ed.set_status_text(nothing_to_paste(&lang_paste).into());
+            return;
+        };
+        let pasted = paste_rows(model_paste.iter().map(|r| r.name_en), &rows, |b| &b.name_en);
+        // Categories are matched by name; branches of other categories are pasted uncategorized
+        let categories = library_categories(&state_paste, lib_id, &pending_paste.borrow());
+        for b in resolve_branch_import(&categories, lib_id, &pasted.rows, &HashMap::new()).branches {
+            model_paste.push(BranchRow {
+                id: -1,
+                category_id: b.category_id.unwrap_or(-1) as i32,
+                name_ru: b.name_ru.into(),
+                name_en: b.name_en.into(),
+            });
+        }
+        if !pasted.rows.is_empty() {
+            note_paste();
         }
+        ed.set_status_text(paste_status(&lang_paste, &pasted).into());
     });
+    let state_imp = state.clone();
     let weak_imp = weak_editor.clone();
     let model_imp = model.clone();
+    let category_model_imp = category_model.clone();
+    let pending_imp = pending_categories.clone();
+    let lang_imp = lang.to_string();
+    let name_pref_imp = name_pref.clone();
+    let note_imp = note_change.clone();
     editor.on_import_branches(move || {
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("JSON", &["json"])
-            .pick_file()
-        {
-            match import_branches_from_path(path.as_path()) {
-                Ok(imported) => {
-                    while model_imp.row_count() > 0 {
-                        model_imp.remove(0);
-                    }
-                    for e in imported {
-                        model_imp.insert(
-                            model_imp.row_count(),
-                            BranchRow {
-                                id: -1,
-                                category_id: -1,
-                                name_ru: e.name_ru.into(),
-                                name_en: e.name_en.into(),
-                            },
-                        );
-                    }
-                    if let Some(ed) = weak_imp.upgrade() {
-                        ed.set_current_index(if model_imp.row_count() > 0 { 0 } else { -1 });
-                        if model_imp.row_count() > 0 {
-                            if let Some(r) = model_imp.row_data(0) {
-                                ed.set_current_name_ru(r.name_ru.clone());
-                                ed.set_current_name_en(r.name_en.clone());
-                            }
-                        }
-                    }
+        let state_imp = state_imp.clone();
+        let weak_imp = weak_imp.clone();
+        let model_imp = model_imp.clone();
+        let category_model_imp = category_model_imp.clone();
+        let lang_imp = lang_imp.clone();
+        let name_pref_imp = name_pref_imp.clone();
+        let pending_imp = pending_imp.clone();
+        let note_imp = note_imp.clone();
+        choose_file(FileRequest::open().filter("JSON", &["json"]), move |path| {
+            let imported = match import_branches_file_from_path(path.as_path()) {
+                Ok(imported) => imported,
+                Err(e) => {
+                    log::error!("Import branches: {}", e);
+                    return;
                 }
-                Err(e) => log::error!("Import branches: {}", e),
+            };
+            let existing = state_imp
+                .borrow()
+                .database()
+                .and_then(|db| BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).ok())
+                .unwrap_or_default();
+            let unmatched = imported.unmatched_categories(&existing);
+            let target = ImportTarget {
+                state: state_imp.clone(),
+                lib_id,
+                lang: lang_imp.clone(),
+                name_pref: name_pref_imp.clone(),
+                editor: weak_imp.clone(),
+                model: model_imp.clone(),
+                categories: category_model_imp.clone(),
+                pending: pending_imp.clone(),
+                changed: note_imp.clone(),
+            };
+            if unmatched.is_empty() {
+                target.apply(&imported, &HashMap::new());
+            } else {
+                show_category_conflict_dialog(target, imported, unmatched, existing);
             }
-        }
+        });
     });
     let state_copy = state.clone();
     let weak_copy = weak_editor.clone();
     let model_copy = model.clone();
+    let categories_copy = category_model.clone();
     let source_ids = source_library_ids.clone();
+    let pending_copy = pending_categories.clone();
+    let lang_copy = lang.to_string();
+    let loaded_copy = loaded.clone();
     editor.on_copy_from_library(move || {
         let Some(ed) = weak_copy.upgrade() else {
             return;
@@ -362,42 +533,261 @@ pub(in crate::app) fn show_branches_editor(
             return;
         }
         let source_id = source_ids[idx as usize];
-        let st = state_copy.borrow();
-        if let Some(ref db) = st.database {
-            let branch_repo = BranchRepo::new(db.conn());
-            if let Err(e) = copy_branches_between_libraries(&branch_repo, source_id, lib_id) {
-                log::error!("Copy branches: {}", e);
+        // Copying replaces every branch of this library
+        let spec = ConfirmSpec::new(
+            "Copy from library",
+            ui_tr(&lang_copy, "Replace all branches of this library with the branches of the selected library?"),
+            "Replace",
+        )
+        .danger();
+        let state_copy = state_copy.clone();
+        let weak_copy = weak_copy.clone();
+        let model_copy = model_copy.clone();
+        let pending_copy = pending_copy.clone();
+        let categories_copy = categories_copy.clone();
+        let name_pref = name_pref.clone();
+        let lang = lang_copy.clone();
+        let loaded = loaded_copy.clone();
+        confirm(&lang_copy, spec, move |confirmed| {
+            if !confirmed {
                 return;
             }
-            drop(st);
-            let st2 = state_copy.borrow();
-            if let Some(ref db2) = st2.database {
-                let branch_repo2 = BranchRepo::new(db2.conn());
-                if let Ok(new_branches) = branch_repo2.list_by_library(lib_id) {
-                    while model_copy.row_count() > 0 {
-                        model_copy.remove(0);
-                    }
-                    for b in new_branches {
-                        model_copy.insert(
-                            model_copy.row_count(),
-                            BranchRow {
-                                id: b.id.unwrap_or(-1) as i32,
-                                category_id: b.category_id.unwrap_or(-1) as i32,
-                                name_ru: b.name_ru.into(),
-                                name_en: b.name_en.into(),
-                            },
-                        );
-                    }
-                    ed.set_current_index(if model_copy.row_count() > 0 { 0 } else { -1 });
-                    if model_copy.row_count() > 0 {
-                        if let Some(r) = model_copy.row_data(0) {
-                            ed.set_current_name_ru(r.name_ru.clone());
-                            ed.set_current_name_en(r.name_en.clone());
+            let Some(ed) = weak_copy.upgrade() else {
+                return;
+            };
+            let st = state_copy.borrow();
+            if let Some(db) = st.database() {
+                let branch_repo = BranchRepo::new(db.conn());
+                let cat_repo = BranchCategoryRepo::new(db.conn());
+                if let Err(e) = copy_branches_between_libraries(&branch_repo, &cat_repo, source_id, lib_id) {
+                    log::error!("Copy branches: {}", e);
+                    return;
+                }
+                record_copy(db.conn(), lib_id, source_id, CopiedData::Branches);
+                drop(st);
+                pending_copy.borrow_mut().clear();
+                loaded.refresh();
+                let st2 = state_copy.borrow();
+                if let Some(db2) = st2.database() {
+                    let branch_repo2 = BranchRepo::new(db2.conn());
+                // Copying may have added categories
+                if let Ok(categories) = BranchCategoryRepo::new(db2.conn()).list_by_library(lib_id) {
+                    categories_copy.set_vec(category_items(&categories, &name_pref, &lang));
+                }
+                    if let Ok(new_branches) = branch_repo2.list_by_library(lib_id) {
+                        while model_copy.row_count() > 0 {
+                            model_copy.remove(0);
+                        }
+                        for b in new_branches {
+                            model_copy.insert(
+                                model_copy.row_count(),
+                                BranchRow {
+                                    id: b.id.unwrap_or(-1) as i32,
+                                    category_id: b.category_id.unwrap_or(-1) as i32,
+                                    name_ru: b.name_ru.into(),
+                                    name_en: b.name_en.into(),
+                                },
+                            );
+                        }
+                        ed.set_current_index(if model_copy.row_count() > 0 { 0 } else { -1 });
+                        if model_copy.row_count() > 0 {
+                            if let Some(r) = model_copy.row_data(0) {
+                                ed.set_current_name_ru(r.name_ru.clone());
+                                ed.set_current_name_en(r.name_en.clone());
+                            }
                         }
                     }
                 }
             }
+        });
+    });
+    remember_geometry(&editor, WindowKind::BranchesEditor);
+    if editor.show().is_ok() {
+        state.borrow_mut().open_windows.add(OpenWindow::of(&editor, translate));
+    }
+    register_open_editor(&editor, lib_id, EditorKind::Branches);
+}
+
+/// Stored categories of the library followed by those waiting to be created
+fn library_categories(state: &RefCell<AppState>, lib_id: i64, pending: &[BranchCategory]) -> Vec<BranchCategory> {
+    let mut categories = state
+        .borrow()
+        .database()
+        .and_then(|db| BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).ok())
+        .unwrap_or_default();
+    categories.extend(pending.iter().cloned());
+    categories
+}
+
+fn category_items(categories: &[BranchCategory], name_pref: &str, lang: &str) -> Vec<CategoryItem> {
+    categories
+        .iter()
+        .map(|c| CategoryItem {
+            id: c.id.unwrap_or(-1) as i32,
+            name: pick_name(name_pref, lang, &c.name_ru, &c.name_en).into(),
+        })
+        .collect()
+}
+
+/// Branches editor state that an import writes into
+struct ImportTarget {
+    state: Rc<RefCell<AppState>>,
+    lib_id: i64,
+    lang: String,
+    /// Library's preferred language for bilingual names
+    name_pref: String,
+    editor: slint::Weak<BranchesEditor>,
+    model: Rc<VecModel<BranchRow>>,
+    categories: Rc<VecModel<CategoryItem>>,
+    pending: Rc<RefCell<Vec<BranchCategory>>>,
+    /// Called once the rows are replaced
+    changed: Rc<dyn Fn()>,
+}
+
+impl ImportTarget {
+    /// Resolve categories, replace the editor rows and reload the category list.
+    fn apply(
+        &self,
+        imported: &BranchesFile,
+        resolutions: &HashMap<UnmatchedCategory, CategoryResolution>,
+    ) {
+        let mut categories = {
+            let st = self.state.borrow();
+            let Some(db) = st.database() else {
+                return;
+            };
+            BranchCategoryRepo::new(db.conn())
+                .list_by_library(self.lib_id)
+                .unwrap_or_default()
+        };
+        let resolved = resolve_branches_file_import(&categories, self.lib_id, imported, resolutions);
+        categories.extend(resolved.new_categories.iter().cloned());
+        *self.pending.borrow_mut() = resolved.new_categories;
+        self.categories.set_vec(category_items(&categories, &self.name_pref, &self.lang));
+        self.model.set_vec(
+            resolved
+                .branches
+                .into_iter()
+                .map(|b| BranchRow {
+                    id: -1,
+                    category_id: b.category_id.unwrap_or(-1) as i32,
+                    name_ru: b.name_ru.into(),
+                    name_en: b.name_en.into(),
+                })
+                .collect::<Vec<_>>(),
+        );
+        if let Some(ed) = self.editor.upgrade() {
+            ed.set_current_index(if self.model.row_count() > 0 { 0 } else { -1 });
+            ed.set_current_category_index(-1);
+            if let Some(r) = self.model.row_data(0) {
+                ed.set_current_name_ru(r.name_ru.clone());
+                ed.set_current_name_en(r.name_en.clone());
+                let cat_idx = self
+                    .categories
+                    .iter()
+                    .position(|c| c.id == r.category_id)
+                    .map(|i| i as i32)
+                    .unwrap_or(-1);
+                ed.set_current_category_index(cat_idx);
+            }
+        }
+        (self.changed)();
+    }
+}
+
+/// Ask how to handle every imported category missing from the library, then import.
+fn show_category_conflict_dialog(
+    target: ImportTarget,
+    imported: BranchesFile,
+    unmatched: Vec<UnmatchedCategory>,
+    existing: Vec<BranchCategory>,
+) {
+    const CHOICE_CREATE: i32 = 0;
+    const CHOICE_UNCATEGORIZED: i32 = 1;
+    const FIRST_EXISTING_CHOICE: i32 = 2;
+
+    let lang = target.lang.clone();
+    let dialog = match CategoryConflictDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create category conflict dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_tr_title(ui_tr(&lang, "Unknown categories").into());
+    dialog.set_tr_message(
+        ui_tr(
+            &lang,
+            "The imported file has categories that do not exist in this library. Choose what to do with each:",
+        )
+        .into(),
+    );
+    dialog.set_tr_apply(ui_tr(&lang, "Import").into());
+    dialog.set_tr_cancel(ui_tr(&lang, "Cancel").into());
+
+    let mut choices: Vec<slint::SharedString> = vec![
+        ui_tr(&lang, "Create category").into(),
+        ui_tr(&lang, "Leave uncategorized").into(),
+    ];
+    choices.extend(existing.iter().map(|c| {
+        format!("→ {}", pick_name(&target.name_pref, &lang, &c.name_ru, &c.name_en)).into()
+    }));
+    dialog.set_choices(ModelRc::new(VecModel::from(choices)));
+
+    let rows = Rc::new(VecModel::from(
+        unmatched
+            .iter()
+            .map(|u| CategoryConflictRow {
+                name: match (u.name_ru.trim().is_empty(), u.name_en.trim().is_empty()) {
+                    (false, false) => format!("{} / {}", u.name_ru, u.name_en),
+                    (false, true) => u.name_ru.clone(),
+                    _ => u.name_en.clone(),
+                }
+                .into(),
+                choice: CHOICE_CREATE,
+            })
+            .collect::<Vec<_>>(),
+    ));
+    dialog.set_rows(ModelRc::new(rows.clone()));
+
+    let rows_changed = rows.clone();
+    dialog.on_choice_changed(move |row, choice| {
+        if let Some(mut r) = rows_changed.row_data(row as usize) {
+            r.choice = choice;
+            rows_changed.set_row_data(row as usize, r);
+        }
+    });
+
+    let weak_apply = dialog.as_weak();
+    dialog.on_apply(move || {
+        let resolutions: HashMap<UnmatchedCategory, CategoryResolution> = unmatched
+            .iter()
+            .enumerate()
+            .map(|(i, u)| {
+                let choice = rows.row_data(i).map(|r| r.choice).unwrap_or(CHOICE_CREATE);
+                let resolution = match choice {
+                    CHOICE_CREATE => CategoryResolution::Create,
+                    CHOICE_UNCATEGORIZED => CategoryResolution::Uncategorized,
+                    n => existing
+                        .get((n - FIRST_EXISTING_CHOICE) as usize)
+                        .and_then(|c| c.id)
+                        .map(CategoryResolution::MapTo)
+                        .unwrap_or(CategoryResolution::Uncategorized),
+                };
+                (u.clone(), resolution)
+            })
+            .collect();
+        target.apply(&imported, &resolutions);
+        if let Some(d) = weak_apply.upgrade() {
+            hide_remembering(&d, WindowKind::CategoryConflictDialog);
+        }
+    });
+    let weak_cancel = dialog.as_weak();
+    dialog.on_cancel(move || {
+        if let Some(d) = weak_cancel.upgrade() {
+            hide_remembering(&d, WindowKind::CategoryConflictDialog);
         }
     });
-    editor.show().unwrap_or_default();
+    remember_geometry(&dialog, WindowKind::CategoryConflictDialog);
+    dialog.show().unwrap_or_default();
 }
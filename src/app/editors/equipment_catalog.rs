@@ -0,0 +1,431 @@
+//! Equipment and vehicles editor window (the library's equipment catalog)
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+
+use crate::db::repositories::EquipmentCatalogRepo;
+use crate::export::{
+    export_equipment_catalog_to_path, import_equipment_catalog_from_path,
+    copy_equipment_catalog_between_libraries,
+};
+use crate::models::{
+    format_aliases, merge_catalog_entries, parse_aliases, CopiedData, EquipmentAliases, EquipmentCatalogEntry,
+    EquipmentCategory,
+};
+
+use super::super::{EquipmentCatalogEditor, EquipmentCatalogRow, OtherLibraryItem, AppState};
+use super::super::open_windows::OpenWindow;
+use super::super::translations::ui_tr;
+use super::super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::super::file_dialog::{choose_file, FileRequest};
+use super::super::confirm::{confirm, ConfirmSpec};
+use super::super::dialogs::show_error_dialog;
+use super::{commit_row, focus_open_editor, move_row, record_copy, register_open_editor, save_unless_changed, LoadedRows};
+use super::super::notify_change;
+use super::super::recovery::EditorJournal;
+use crate::services::{save_editor_rows, ChangeEvent, EditorKind, EditorRows};
+
+fn category_index(category: EquipmentCategory) -> i32 {
+    EquipmentCategory::ALL.iter().position(|c| *c == category).unwrap_or(0) as i32
+}
+
+fn category_at(index: i32) -> EquipmentCategory {
+    usize::try_from(index)
+        .ok()
+        .and_then(|i| EquipmentCategory::ALL.get(i).copied())
+        .unwrap_or_default()
+}
+
+fn catalog_row(entry: EquipmentCatalogEntry) -> EquipmentCatalogRow {
+    EquipmentCatalogRow {
+        id: entry.id.unwrap_or(-1) as i32,
+        name_ru: entry.name_ru.into(),
+        name_en: entry.name_en.into(),
+        category: category_index(entry.category),
+        aliases: format_aliases(&entry.aliases).into(),
+    }
+}
+
+/// Catalog entries for the editor rows (rows never filled in are dropped on save)
+fn catalog_entries(model: &VecModel<EquipmentCatalogRow>, lib_id: i64) -> Vec<EquipmentCatalogEntry> {
+    model
+        .iter()
+        .map(|r| {
+            let mut entry =
+                EquipmentCatalogEntry::new(lib_id, r.name_ru.to_string(), r.name_en.to_string(), category_at(r.category));
+            entry.aliases = parse_aliases(&r.aliases);
+            entry
+        })
+        .collect()
+}
+
+/// The editor's rows with the name fields of the selected row applied
+fn current_rows(ed: &EquipmentCatalogEditor, model: &VecModel<EquipmentCatalogRow>, lib_id: i64) -> EditorRows {
+    let mut entries = catalog_entries(model, lib_id);
+    if let Some(e) = usize::try_from(ed.get_current_index()).ok().and_then(|i| entries.get_mut(i)) {
+        e.name_ru = ed.get_current_name_ru().to_string();
+        e.name_en = ed.get_current_name_en().to_string();
+        e.aliases = parse_aliases(&ed.get_current_aliases());
+    }
+    EditorRows::EquipmentCatalog(entries)
+}
+
+/// Show `index` in the form, or clear the form if there is no such row
+/// Write the name fields into the selected row
+fn commit_current_row(ed: &EquipmentCatalogEditor, model: &VecModel<EquipmentCatalogRow>) {
+    commit_row(model, ed.get_current_index(), |r| {
+        r.name_ru = ed.get_current_name_ru();
+        r.name_en = ed.get_current_name_en();
+        r.aliases = ed.get_current_aliases();
+    });
+}
+
+fn select_row(ed: &EquipmentCatalogEditor, model: &VecModel<EquipmentCatalogRow>, index: i32) {
+    match usize::try_from(index).ok().and_then(|i| model.row_data(i)) {
+        Some(r) => {
+            ed.set_current_index(index);
+            ed.set_current_name_ru(r.name_ru);
+            ed.set_current_name_en(r.name_en);
+            ed.set_current_aliases(r.aliases);
+            ed.set_current_category_index(r.category);
+        }
+        None => {
+            ed.set_current_index(-1);
+            ed.set_current_name_ru(Default::default());
+            ed.set_current_name_en(Default::default());
+            ed.set_current_aliases(Default::default());
+            ed.set_current_category_index(-1);
+        }
+    }
+}
+
+/// Set the editor texts in `lang`
+fn translate(editor: &EquipmentCatalogEditor, lang: &str) {
+    editor.set_tr_equipment_title(ui_tr(lang, "Equipment and Vehicles").into());
+    editor.set_tr_name_russian(ui_tr(lang, "Name (Russian)").into());
+    editor.set_tr_name_english(ui_tr(lang, "Name (English)").into());
+    editor.set_tr_aliases(ui_tr(lang, "Aliases (separated by semicolons)").into());
+    editor.set_tr_also_known_as(ui_tr(lang, "Also known as:").into());
+    editor.set_tr_category(ui_tr(lang, "Category").into());
+    editor.set_tr_add(ui_tr(lang, "Add").into());
+    editor.set_tr_delete(ui_tr(lang, "Delete").into());
+    editor.set_tr_export(ui_tr(lang, "Export…").into());
+    editor.set_tr_import(ui_tr(lang, "Import…").into());
+    editor.set_tr_move_up(ui_tr(lang, "Move Up").into());
+    editor.set_tr_move_down(ui_tr(lang, "Move Down").into());
+    editor.set_tr_copy_from_library(ui_tr(lang, "Copy from library").into());
+    editor.set_tr_close(ui_tr(lang, "Close").into());
+}
+
+/// Open the Equipment and Vehicles editor window for the given library.
+pub(in crate::app) fn show_equipment_catalog_editor(
+    state: Rc<RefCell<AppState>>,
+    lib_id: i64,
+    lib_name: &str,
+    lang: &str,
+) {
+    if focus_open_editor(lib_id, EditorKind::EquipmentCatalog) {
+        return;
+    }
+    let (entries, other_library_items, source_library_ids) = {
+        let st = state.borrow();
+        let db = match st.database() {
+            Some(d) => d,
+            None => {
+                log::error!("Database not initialized");
+                return;
+            }
+        };
+        let entries = match EquipmentCatalogRepo::new(db.conn()).list_by_library(lib_id) {
+            Ok(e) => e,
+            Err(e) => {
+                log::error!("Failed to load equipment catalog: {}", e);
+                return;
+            }
+        };
+        let lib_repo = crate::db::repositories::LibraryRepo::new(db.conn());
+        let all_libs = lib_repo.list_summaries().unwrap_or_default();
+        let mut other_items = Vec::new();
+        let mut source_ids = Vec::new();
+        for l in all_libs.into_iter().filter(|l| l.id != lib_id) {
+            other_items.push(OtherLibraryItem {
+                id: l.id as i32,
+                name: l.name.into(),
+            });
+            source_ids.push(l.id);
+        }
+        (entries, other_items, source_ids)
+    };
+    let rows: Vec<EquipmentCatalogRow> = entries.into_iter().map(catalog_row).collect();
+    let editor = match EquipmentCatalogEditor::new() {
+        Ok(e) => e,
+        Err(e) => {
+            log::error!("Failed to create Equipment editor: {}", e);
+            return;
+        }
+    };
+    editor.set_library_id(lib_id as i32);
+    editor.set_library_name(lib_name.into());
+    let model = Rc::new(VecModel::from(rows));
+    editor.set_entries(ModelRc::new(model.clone()));
+    editor.set_category_names(ModelRc::new(VecModel::from(
+        EquipmentCategory::ALL
+            .iter()
+            .map(|c| SharedString::from(ui_tr(lang, c.label())))
+            .collect::<Vec<_>>(),
+    )));
+    select_row(&editor, &model, -1);
+    translate(&editor, lang);
+    editor.set_other_libraries(ModelRc::new(VecModel::from(other_library_items)));
+    editor.set_copy_source_index(-1);
+
+    let weak_editor = editor.as_weak();
+    let journal = EditorJournal::new(&state, lib_id, lib_name, EditorKind::EquipmentCatalog);
+    let loaded = LoadedRows::new(&state, lib_id, EditorKind::EquipmentCatalog);
+    let note_change = {
+        let weak = weak_editor.clone();
+        let model = model.clone();
+        let journal = journal.clone();
+        Rc::new(move || {
+            if let Some(ed) = weak.upgrade() {
+                journal.changed(current_rows(&ed, &model, lib_id));
+            }
+        })
+    };
+    let note_edit = note_change.clone();
+    editor.on_form_edited(move || note_edit());
+    let weak_add = weak_editor.clone();
+    let model_add = model.clone();
+    let note_add = note_change.clone();
+    editor.on_add_entry(move || {
+        let Some(ed) = weak_add.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_add);
+        model_add.push(EquipmentCatalogRow {
+            id: -1,
+            name_ru: Default::default(),
+            name_en: Default::default(),
+            category: category_index(EquipmentCategory::default()),
+            aliases: Default::default(),
+        });
+        select_row(&ed, &model_add, model_add.row_count() as i32 - 1);
+        note_add();
+    });
+    let weak_del = weak_editor.clone();
+    let model_del = model.clone();
+    let note_del = note_change.clone();
+    editor.on_delete_entry(move || {
+        let Some(ed) = weak_del.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_del);
+        let idx = ed.get_current_index();
+        if idx >= 0 && (idx as usize) < model_del.row_count() {
+            model_del.remove(idx as usize);
+            let new_count = model_del.row_count() as i32;
+            select_row(&ed, &model_del, idx.min(new_count - 1));
+            note_del();
+        }
+    });
+    let weak_up = weak_editor.clone();
+    let model_up = model.clone();
+    let note_up = note_change.clone();
+    editor.on_move_up(move || {
+        let Some(ed) = weak_up.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_up);
+        if let Some(new_idx) = move_row(&model_up, ed.get_current_index(), true) {
+            ed.set_current_index(new_idx);
+            note_up();
+        }
+    });
+    let weak_down = weak_editor.clone();
+    let model_down = model.clone();
+    let note_down = note_change.clone();
+    editor.on_move_down(move || {
+        let Some(ed) = weak_down.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_down);
+        if let Some(new_idx) = move_row(&model_down, ed.get_current_index(), false) {
+            ed.set_current_index(new_idx);
+            note_down();
+        }
+    });
+    let weak_sel = weak_editor.clone();
+    let model_sel = model.clone();
+    editor.on_selection_changed(move |index| {
+        if let Some(ed) = weak_sel.upgrade() {
+            commit_current_row(&ed, &model_sel);
+            if index >= 0 && (index as usize) < model_sel.row_count() {
+                select_row(&ed, &model_sel, index);
+            }
+        }
+    });
+    let weak_cat = weak_editor.clone();
+    let model_cat = model.clone();
+    let note_cat = note_change.clone();
+    editor.on_category_changed(move |index| {
+        let Some(ed) = weak_cat.upgrade() else {
+            return;
+        };
+        let idx = ed.get_current_index();
+        if let Some(mut r) = usize::try_from(idx).ok().and_then(|i| model_cat.row_data(i)) {
+            r.category = category_index(category_at(index));
+            model_cat.set_row_data(idx as usize, r);
+            note_cat();
+        }
+    });
+
+    let state_close = state.clone();
+    let weak_close = weak_editor.clone();
+    let model_close = model.clone();
+    let journal_close = journal.clone();
+    let loaded_close = loaded.clone();
+    let lib_name_close = lib_name.to_string();
+    let lang_close = lang.to_string();
+    editor.on_close_editor(move || {
+        let Some(ed) = weak_close.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_close);
+        let entries = catalog_entries(&model_close, lib_id);
+        // Refuse to save (and keep the window open) while two entries go by the same name
+        if let Some(collision) = EquipmentAliases::new(&entries).collisions().first() {
+            let claimants: Vec<&str> = collision.entries.iter().map(|&i| entries[i].display_name(&lang_close)).collect();
+            select_row(&ed, &model_close, collision.entries[0] as i32);
+            show_error_dialog(
+                &lang_close,
+                "Equipment and Vehicles",
+                "\"{}\" is a name of more than one entry: {}",
+                &[&collision.name, &claimants.join(", ")],
+            );
+            return;
+        }
+        let rows = EditorRows::EquipmentCatalog(entries);
+        let (state_save, weak_save, journal_save) = (state_close.clone(), weak_close.clone(), journal_close.clone());
+        let save = move || {
+            let saved = state_save.borrow().database().map(|db| save_editor_rows(db.conn(), lib_id, &rows));
+            match saved {
+                Some(Ok(())) => notify_change(&state_save, ChangeEvent::EquipmentCatalogChanged(lib_id)),
+                Some(Err(e)) => log::error!("Failed to save equipment catalog of library {}: {}", lib_id, e),
+                None => {}
+            }
+            journal_save.finished();
+            if let Some(ed) = weak_save.upgrade() {
+                hide_remembering(&ed, WindowKind::EquipmentCatalogEditor);
+            }
+        };
+        let (state_reload, weak_reload, journal_reload) = (state_close.clone(), weak_close.clone(), journal_close.clone());
+        let (lib_name, lang) = (lib_name_close.clone(), lang_close.clone());
+        let reload = move || {
+            journal_reload.finished();
+            if let Some(ed) = weak_reload.upgrade() {
+                hide_remembering(&ed, WindowKind::EquipmentCatalogEditor);
+            }
+            show_equipment_catalog_editor(state_reload.clone(), lib_id, &lib_name, &lang);
+        };
+        save_unless_changed(&lang_close, &loaded_close, save, reload);
+    });
+
+    let weak_exp = weak_editor.clone();
+    let model_exp = model.clone();
+    editor.on_export_catalog(move || {
+        if let Some(ed) = weak_exp.upgrade() {
+            commit_current_row(&ed, &model_exp);
+        }
+        let entries = catalog_entries(&model_exp, lib_id);
+        choose_file(FileRequest::save().filter("JSON", &["json"]), move |path| {
+            if let Err(e) = export_equipment_catalog_to_path(path.as_path(), &entries) {
+                log::error!("Export equipment catalog: {}", e);
+            }
+        });
+    });
+    let weak_imp = weak_editor.clone();
+    let model_imp = model.clone();
+    editor.on_import_catalog(move || {
+        if let Some(ed) = weak_imp.upgrade() {
+            commit_current_row(&ed, &model_imp);
+        }
+        let weak_imp = weak_imp.clone();
+        let model_imp = model_imp.clone();
+        let note_imp = note_change.clone();
+        choose_file(FileRequest::open().filter("JSON", &["json"]), move |path| {
+            match import_equipment_catalog_from_path(path.as_path()) {
+                Ok(imported) => {
+                    // Items already in the list take the imported names as aliases
+                    let merged = merge_catalog_entries(
+                        catalog_entries(&model_imp, lib_id),
+                        imported.iter().map(|e| e.to_entry(lib_id)).collect(),
+                    );
+                    model_imp.set_vec(merged.into_iter().map(catalog_row).collect::<Vec<_>>());
+                    if let Some(ed) = weak_imp.upgrade() {
+                        select_row(&ed, &model_imp, if model_imp.row_count() > 0 { 0 } else { -1 });
+                    }
+                    note_imp();
+                }
+                Err(e) => log::error!("Import equipment catalog: {}", e),
+            }
+        });
+    });
+    let state_copy = state.clone();
+    let weak_copy = weak_editor.clone();
+    let model_copy = model.clone();
+    let lang_copy = lang.to_string();
+    editor.on_copy_from_library(move || {
+        let Some(ed) = weak_copy.upgrade() else {
+            return;
+        };
+        let Some(source_id) = usize::try_from(ed.get_copy_source_index())
+            .ok()
+            .and_then(|i| source_library_ids.get(i).copied())
+        else {
+            return;
+        };
+        // Copying replaces the whole catalog of this library
+        let spec = ConfirmSpec::new(
+            "Copy from library",
+            ui_tr(&lang_copy, "Replace the equipment catalog of this library with the catalog of the selected library?"),
+            "Replace",
+        )
+        .danger();
+        let state_copy = state_copy.clone();
+        let weak_copy = weak_copy.clone();
+        let model_copy = model_copy.clone();
+        let loaded = loaded.clone();
+        confirm(&lang_copy, spec, move |confirmed| {
+            if !confirmed {
+                return;
+            }
+            let copied = {
+                let st = state_copy.borrow();
+                let Some(db) = st.database() else {
+                    return;
+                };
+                let repo = EquipmentCatalogRepo::new(db.conn());
+                copy_equipment_catalog_between_libraries(&repo, source_id, lib_id)
+                    .inspect(|()| record_copy(db.conn(), lib_id, source_id, CopiedData::EquipmentCatalog))
+                    .and_then(|()| repo.list_by_library(lib_id))
+            };
+            match copied {
+                Ok(entries) => {
+                    loaded.refresh();
+                    model_copy.set_vec(entries.into_iter().map(catalog_row).collect::<Vec<_>>());
+                    if let Some(ed) = weak_copy.upgrade() {
+                        select_row(&ed, &model_copy, if model_copy.row_count() > 0 { 0 } else { -1 });
+                    }
+                    notify_change(&state_copy, ChangeEvent::EquipmentCatalogChanged(lib_id));
+                }
+                Err(e) => log::error!("Copy equipment catalog: {}", e),
+            }
+        });
+    });
+    remember_geometry(&editor, WindowKind::EquipmentCatalogEditor);
+    if editor.show().is_ok() {
+        state.borrow_mut().open_windows.add(OpenWindow::of(&editor, translate));
+    }
+    register_open_editor(&editor, lib_id, EditorKind::EquipmentCatalog);
+}
@@ -4,15 +4,286 @@ use std::rc::Rc;
 use std::cell::RefCell;
 use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
 
-use crate::models::{StandardFormationLevel, CustomFormationLevel};
+use crate::models::{
+    StandardFormationLevel, CustomFormationLevel, CopiedData, validate_formation_level_set, validate_personnel_range,
+};
 use crate::db::repositories::FormationLevelRepo;
-use crate::export::{
-    export_formation_levels_to_path, import_formation_levels_from_path,
-    copy_formation_levels_between_libraries,
+use crate::export::{export_formation_levels_to_path, formation_level_exports, import_formation_levels_from_path};
+
+use super::super::{FormationLevelsEditor, FormationLevelRow, LevelRemapDialog, LevelRemapRow, OtherLibraryItem, AppState};
+use super::super::open_windows::OpenWindow;
+use super::super::translations::{ui_tr, ui_tr_plural};
+use super::super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::super::file_dialog::{choose_file, FileRequest};
+use super::super::dialogs::show_error_dialog;
+use super::super::confirm::{confirm, ConfirmSpec};
+use super::super::notify_change;
+use super::{
+    adopt_stored_ids, commit_row, copy_to_clipboard, focus_open_editor, library_display_language, move_row, name_language,
+    nothing_to_paste, paste_status, record_copy, register_open_editor, report_save_error, save_unless_changed, LoadedRows,
+};
+use super::super::recovery::EditorJournal;
+use crate::services::{
+    orphaned_levels, paste_rows, save_formation_levels, suggest_level_remap, ChangeEvent, ClipboardContent, EditorKind,
+    EditorRows, LevelRemap, OrphanedLevel,
 };
 
-use super::super::{FormationLevelsEditor, FormationLevelRow, OtherLibraryItem, AppState};
-use super::super::translations::ui_tr;
+/// Editor text for an optional range bound
+fn bound_text(bound: Option<u32>) -> SharedString {
+    bound.map(|b| b.to_string()).unwrap_or_default().into()
+}
+
+/// Parse the expected personnel range as typed (empty = no bound).
+/// The error is an untranslated message.
+fn parse_personnel_range(min: &str, max: &str) -> Result<(Option<u32>, Option<u32>), String> {
+    let parse = |text: &str| -> Result<Option<u32>, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        text.parse::<u32>()
+            .map(Some)
+            .map_err(|_| "Expected personnel must be a whole number".to_string())
+    };
+    let (min, max) = (parse(min)?, parse(max)?);
+    match validate_personnel_range(min, max).into_iter().next() {
+        Some(error) => Err(error.message),
+        None => Ok((min, max)),
+    }
+}
+
+/// Level to store for an editor row; an invalid range is left out. Rows of
+/// stored levels keep their id so units stay at that level.
+fn row_level(lib_id: i64, r: &FormationLevelRow) -> CustomFormationLevel {
+    let mut level = CustomFormationLevel::new(
+        lib_id,
+        r.name_ru.to_string(),
+        r.name_en.to_string(),
+        r.standard_level_ordinal,
+    );
+    level.id = (r.id >= 0).then_some(r.id as i64);
+    if let Ok((min, max)) = parse_personnel_range(&r.min_personnel, &r.max_personnel) {
+        level.min_personnel = min;
+        level.max_personnel = max;
+    }
+    level
+}
+
+/// The editor's rows with the name fields of the selected row applied
+fn current_rows(ed: &FormationLevelsEditor, model: &VecModel<FormationLevelRow>, lib_id: i64) -> EditorRows {
+    let current = usize::try_from(ed.get_current_index()).ok();
+    EditorRows::FormationLevels(
+        model
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let mut level = row_level(lib_id, &r);
+                if Some(i) == current {
+                    level.name_ru = ed.get_current_name_ru().to_string();
+                    level.name_en = ed.get_current_name_en().to_string();
+                }
+                level
+            })
+            .collect(),
+    )
+}
+
+/// Write the form fields into the selected row
+fn commit_current_row(ed: &FormationLevelsEditor, model: &VecModel<FormationLevelRow>) {
+    commit_row(model, ed.get_current_index(), |r| {
+        r.name_ru = ed.get_current_name_ru();
+        r.name_en = ed.get_current_name_en();
+        r.standard_level_ordinal = ed.get_current_standard_ordinal();
+        r.min_personnel = ed.get_current_min_personnel();
+        r.max_personnel = ed.get_current_max_personnel();
+    });
+}
+
+/// Editor row for a stored level
+fn level_row(level: CustomFormationLevel) -> FormationLevelRow {
+    FormationLevelRow {
+        id: level.id.unwrap_or(-1) as i32,
+        min_personnel: bound_text(level.min_personnel),
+        max_personnel: bound_text(level.max_personnel),
+        name_ru: level.name_ru.into(),
+        name_en: level.name_en.into(),
+        standard_level_ordinal: level.standard_level_ordinal,
+    }
+}
+
+/// Both names of a level, or the one that is filled in
+fn level_label(level: &CustomFormationLevel) -> String {
+    match (level.name_ru.trim().is_empty(), level.name_en.trim().is_empty()) {
+        (false, false) => format!("{} / {}", level.name_ru, level.name_en),
+        (false, true) => level.name_ru.clone(),
+        _ => level.name_en.clone(),
+    }
+}
+
+/// Save the levels, moving the units of dropped levels as `remap` says.
+/// Returns the ids of the stored levels in list order.
+fn save_levels(
+    state: &Rc<RefCell<AppState>>,
+    lib_id: i64,
+    levels: &[CustomFormationLevel],
+    remap: &LevelRemap,
+) -> anyhow::Result<Vec<i64>> {
+    let ids = {
+        let st = state.borrow();
+        let db = st.database().ok_or_else(|| anyhow::anyhow!("Database not initialized"))?;
+        save_formation_levels(db.conn(), lib_id, levels, remap)?;
+        FormationLevelRepo::new(db.conn())
+            .list_by_library(lib_id)?
+            .into_iter()
+            .filter_map(|l| l.id)
+            .collect()
+    };
+    notify_change(state, ChangeEvent::FormationLevelsChanged(lib_id));
+    Ok(ids)
+}
+
+/// Ask which new level the units of each removed level move to, then save the
+/// levels with that mapping and run `on_saved` with the stored ids. Cancelling,
+/// or a failed save, goes back to the editor.
+fn show_level_remap_dialog(
+    state: Rc<RefCell<AppState>>,
+    lib_id: i64,
+    lang: &str,
+    levels: Vec<CustomFormationLevel>,
+    orphans: Vec<OrphanedLevel>,
+    on_saved: impl Fn(Vec<i64>) + 'static,
+) {
+    const CHOICE_NO_LEVEL: i32 = 0;
+
+    let dialog = match LevelRemapDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create level remap dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_tr_title(ui_tr(lang, "Removed formation levels").into());
+    dialog.set_tr_message(ui_tr(lang, "Units still use the levels below. Choose the new level for each:").into());
+    dialog.set_tr_apply(ui_tr(lang, "Save").into());
+    dialog.set_tr_cancel(ui_tr(lang, "Cancel").into());
+
+    let mut choices: Vec<SharedString> = vec![ui_tr(lang, "(no level)").into()];
+    choices.extend(levels.iter().map(|l| format!("→ {}", level_label(l)).into()));
+    dialog.set_choices(ModelRc::new(VecModel::from(choices)));
+
+    let suggested = suggest_level_remap(&orphans, &levels);
+    let rows = Rc::new(VecModel::from(
+        orphans
+            .iter()
+            .map(|o| LevelRemapRow {
+                name: level_label(&o.level).into(),
+                units: ui_tr_plural(lang, o.unit_count as u64, "{} unit", "{} units").into(),
+                choice: o
+                    .level
+                    .id
+                    .and_then(|id| suggested.get(&id).copied().flatten())
+                    .map_or(CHOICE_NO_LEVEL, |i| i as i32 + 1),
+            })
+            .collect::<Vec<_>>(),
+    ));
+    dialog.set_rows(ModelRc::new(rows.clone()));
+
+    let rows_changed = rows.clone();
+    dialog.on_choice_changed(move |row, choice| {
+        if let Some(mut r) = rows_changed.row_data(row as usize) {
+            r.choice = choice;
+            rows_changed.set_row_data(row as usize, r);
+        }
+    });
+
+    let weak_apply = dialog.as_weak();
+    let lang_apply = lang.to_string();
+    dialog.on_apply(move || {
+        let remap: LevelRemap = orphans
+            .iter()
+            .enumerate()
+            .filter_map(|(i, o)| {
+                let choice = rows.row_data(i).map(|r| r.choice).unwrap_or(CHOICE_NO_LEVEL);
+                o.level.id.map(|id| (id, usize::try_from(choice - 1).ok()))
+            })
+            .collect();
+        let saved = save_levels(&state, lib_id, &levels, &remap);
+        if let Some(d) = weak_apply.upgrade() {
+            hide_remembering(&d, WindowKind::LevelRemapDialog);
+        }
+        match saved {
+            Ok(ids) => on_saved(ids),
+            Err(e) => report_save_error(&lang_apply, EditorKind::FormationLevels, lib_id, &e),
+        }
+    });
+    let weak_cancel = dialog.as_weak();
+    dialog.on_cancel(move || {
+        if let Some(d) = weak_cancel.upgrade() {
+            hide_remembering(&d, WindowKind::LevelRemapDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::LevelRemapDialog);
+    dialog.show().unwrap_or_default();
+}
+
+/// Show a row's fields in the form
+fn show_row(ed: &FormationLevelsEditor, row: &FormationLevelRow, lang: &str) {
+    ed.set_current_name_ru(row.name_ru.clone());
+    ed.set_current_name_en(row.name_en.clone());
+    ed.set_current_standard_ordinal(row.standard_level_ordinal);
+    ed.set_current_min_personnel(row.min_personnel.clone());
+    ed.set_current_max_personnel(row.max_personnel.clone());
+    let error = parse_personnel_range(&row.min_personnel, &row.max_personnel).err();
+    ed.set_range_error(error.map(|e| ui_tr(lang, &e)).unwrap_or_default().into());
+}
+
+/// Levels in editor order: by standard level, then as the user arranged
+/// levels sharing one, then by name
+fn sorted_levels(mut levels: Vec<CustomFormationLevel>) -> Vec<CustomFormationLevel> {
+    levels.sort_by(|a, b| {
+        a.standard_level_ordinal
+            .cmp(&b.standard_level_ordinal)
+            .then(a.sort_order.cmp(&b.sort_order))
+            .then_with(|| a.name_en.to_lowercase().cmp(&b.name_en.to_lowercase()))
+    });
+    levels
+}
+
+/// Whether the row next to `index` (above it if `up`) corresponds to the same
+/// standard level. Only such rows swap places: levels are listed by standard
+/// level, so any other move would be undone when the editor next opens.
+fn same_level_neighbour(model: &VecModel<FormationLevelRow>, index: i32, up: bool) -> bool {
+    let Ok(index) = usize::try_from(index) else {
+        return false;
+    };
+    let neighbour = if up { index.checked_sub(1) } else { Some(index + 1) };
+    match (model.row_data(index), neighbour.and_then(|n| model.row_data(n))) {
+        (Some(row), Some(next)) => row.standard_level_ordinal == next.standard_level_ordinal,
+        _ => false,
+    }
+}
+
+/// Set the editor texts in `lang`
+fn translate(editor: &FormationLevelsEditor, lang: &str) {
+    editor.set_name_language(name_language(&editor.get_display_language(), lang).into());
+    editor.set_tr_formation_levels_title(ui_tr(lang, "Formation levels").into());
+    editor.set_tr_name_russian(ui_tr(lang, "Name (Russian)").into());
+    editor.set_tr_name_english(ui_tr(lang, "Name (English)").into());
+    editor.set_tr_corresponds_to(ui_tr(lang, "Corresponds to").into());
+    editor.set_tr_expected_personnel(ui_tr(lang, "Expected personnel (min – max)").into());
+    editor.set_tr_add_level(ui_tr(lang, "Add level").into());
+    editor.set_tr_delete_level(ui_tr(lang, "Delete level").into());
+    editor.set_tr_export(ui_tr(lang, "Export…").into());
+    editor.set_tr_import(ui_tr(lang, "Import…").into());
+    editor.set_tr_move_up(ui_tr(lang, "Move Up").into());
+    editor.set_tr_move_down(ui_tr(lang, "Move Down").into());
+    editor.set_tr_copy_from_library(ui_tr(lang, "Copy from library").into());
+    editor.set_tr_copy_all(ui_tr(lang, "Copy all").into());
+    editor.set_tr_paste(ui_tr(lang, "Paste").into());
+    editor.set_tr_save(ui_tr(lang, "Save").into());
+    editor.set_tr_close(ui_tr(lang, "Close").into());
+}
 
 /// Open the Formation levels editor window for the given library.
 pub(in crate::app) fn show_formation_levels_editor(
@@ -21,9 +292,12 @@ pub(in crate::app) fn show_formation_levels_editor(
     lib_name: &str,
     lang: &str,
 ) {
-    let (levels, other_library_items, source_library_ids) = {
+    if focus_open_editor(lib_id, EditorKind::FormationLevels) {
+        return;
+    }
+    let (levels, other_library_items, source_library_ids, display_language) = {
         let st = state.borrow();
-        let db = match st.database.as_ref() {
+        let db = match st.database() {
             Some(d) => d,
             None => {
                 log::error!("Database not initialized");
@@ -39,31 +313,19 @@ pub(in crate::app) fn show_formation_levels_editor(
             }
         };
         let lib_repo = crate::db::repositories::LibraryRepo::new(db.conn());
-        let all_libs = lib_repo.list_all().unwrap_or_default();
+        let all_libs = lib_repo.list_summaries().unwrap_or_default();
         let mut other_items = Vec::new();
         let mut source_ids = Vec::new();
-        for l in all_libs {
-            if l.id != Some(lib_id) {
-                if let Some(id) = l.id {
-                    other_items.push(OtherLibraryItem {
-                        id: id as i32,
-                        name: l.name.into(),
-                    });
-                    source_ids.push(id);
-                }
-            }
+        for l in all_libs.into_iter().filter(|l| l.id != lib_id) {
+            other_items.push(OtherLibraryItem {
+                id: l.id as i32,
+                name: l.name.into(),
+            });
+            source_ids.push(l.id);
         }
-        (levels, other_items, source_ids)
+        (levels, other_items, source_ids, library_display_language(db.conn(), lib_id))
     };
-    let rows: Vec<FormationLevelRow> = levels
-        .into_iter()
-        .map(|l| FormationLevelRow {
-            id: l.id.unwrap_or(-1) as i32,
-            name_ru: l.name_ru.into(),
-            name_en: l.name_en.into(),
-            standard_level_ordinal: l.standard_level_ordinal,
-        })
-        .collect();
+    let rows: Vec<FormationLevelRow> = sorted_levels(levels).into_iter().map(level_row).collect();
     let standard_names: Vec<SharedString> = StandardFormationLevel::all()
         .iter()
         .map(|s| s.name_en().into())
@@ -77,6 +339,7 @@ pub(in crate::app) fn show_formation_levels_editor(
     };
     editor.set_library_id(lib_id as i32);
     editor.set_library_name(lib_name.into());
+    editor.set_display_language(display_language.into());
     editor.set_standard_level_names(ModelRc::new(VecModel::from(standard_names)));
     let model = Rc::new(VecModel::from(rows));
     editor.set_custom_levels(ModelRc::new(model.clone()));
@@ -84,44 +347,59 @@ pub(in crate::app) fn show_formation_levels_editor(
     editor.set_current_name_ru(Default::default());
     editor.set_current_name_en(Default::default());
     editor.set_current_standard_ordinal(0);
-    editor.set_tr_formation_levels_title(ui_tr(lang, "Formation levels").into());
-    editor.set_tr_name_russian(ui_tr(lang, "Name (Russian)").into());
-    editor.set_tr_name_english(ui_tr(lang, "Name (English)").into());
-    editor.set_tr_corresponds_to(ui_tr(lang, "Corresponds to").into());
-    editor.set_tr_add_level(ui_tr(lang, "Add level").into());
-    editor.set_tr_delete_level(ui_tr(lang, "Delete level").into());
-    editor.set_tr_export(ui_tr(lang, "Export…").into());
-    editor.set_tr_import(ui_tr(lang, "Import…").into());
-    editor.set_tr_copy_from_library(ui_tr(lang, "Copy from library").into());
-    editor.set_tr_close(ui_tr(lang, "Close").into());
+    editor.set_current_min_personnel(Default::default());
+    editor.set_current_max_personnel(Default::default());
+    editor.set_range_error(Default::default());
+    translate(&editor, lang);
     editor.set_other_libraries(ModelRc::new(VecModel::from(other_library_items)));
     editor.set_copy_source_index(-1);
     let state_close = state.clone();
     let weak_editor = editor.as_weak();
+    let journal = EditorJournal::new(&state, lib_id, lib_name, EditorKind::FormationLevels);
+    let loaded = LoadedRows::new(&state, lib_id, EditorKind::FormationLevels);
+    let note_change = {
+        let weak = weak_editor.clone();
+        let model = model.clone();
+        let journal = journal.clone();
+        Rc::new(move || {
+            if let Some(ed) = weak.upgrade() {
+                journal.changed(current_rows(&ed, &model, lib_id));
+            }
+        })
+    };
+    let note_edit = note_change.clone();
+    editor.on_form_edited(move || note_edit());
     let weak_add = weak_editor.clone();
     let model_add = model.clone();
+    let note_add = note_change.clone();
+    let lang_add = lang.to_string();
     editor.on_add_level(move || {
         let Some(ed) = weak_add.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_add);
         let row = FormationLevelRow {
             id: -1,
             name_ru: Default::default(),
             name_en: Default::default(),
             standard_level_ordinal: 0,
+            min_personnel: Default::default(),
+            max_personnel: Default::default(),
         };
         model_add.insert(model_add.row_count(), row.clone());
         ed.set_current_index(model_add.row_count() as i32 - 1);
-        ed.set_current_name_ru(row.name_ru.clone());
-        ed.set_current_name_en(row.name_en.clone());
-        ed.set_current_standard_ordinal(row.standard_level_ordinal);
+        show_row(&ed, &row, &lang_add);
+        note_add();
     });
     let weak_del = weak_editor.clone();
     let model_del = model.clone();
+    let lang_del = lang.to_string();
+    let note_del = note_change.clone();
     editor.on_delete_level(move || {
         let Some(ed) = weak_del.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_del);
         let idx = ed.get_current_index();
         if idx >= 0 && (idx as usize) < model_del.row_count() {
             model_del.remove(idx as usize);
@@ -131,149 +409,318 @@ pub(in crate::app) fn show_formation_levels_editor(
                 ed.set_current_name_ru(Default::default());
                 ed.set_current_name_en(Default::default());
                 ed.set_current_standard_ordinal(0);
+                ed.set_current_min_personnel(Default::default());
+                ed.set_current_max_personnel(Default::default());
+                ed.set_range_error(Default::default());
             } else {
                 let new_idx = (idx as usize).min(new_count - 1);
                 ed.set_current_index(new_idx as i32);
                 if let Some(r) = model_del.row_data(new_idx) {
-                    ed.set_current_name_ru(r.name_ru.clone());
-                    ed.set_current_name_en(r.name_en.clone());
-                    ed.set_current_standard_ordinal(r.standard_level_ordinal);
+                    show_row(&ed, &r, &lang_del);
                 }
             }
+            note_del();
+        }
+    });
+    let weak_up = weak_editor.clone();
+    let model_up = model.clone();
+    let note_up = note_change.clone();
+    editor.on_move_up(move || {
+        let Some(ed) = weak_up.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_up);
+        if !same_level_neighbour(&model_up, ed.get_current_index(), true) {
+            return;
+        }
+        if let Some(new_idx) = move_row(&model_up, ed.get_current_index(), true) {
+            ed.set_current_index(new_idx);
+            note_up();
+        }
+    });
+    let weak_down = weak_editor.clone();
+    let model_down = model.clone();
+    let note_down = note_change.clone();
+    editor.on_move_down(move || {
+        let Some(ed) = weak_down.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_down);
+        if !same_level_neighbour(&model_down, ed.get_current_index(), false) {
+            return;
+        }
+        if let Some(new_idx) = move_row(&model_down, ed.get_current_index(), false) {
+            ed.set_current_index(new_idx);
+            note_down();
         }
     });
     let weak_close = weak_editor.clone();
     let model_close = model.clone();
-    editor.on_close_editor(move || {
+    let lang_close = lang.to_string();
+    let journal_close = journal.clone();
+    let loaded_close = loaded.clone();
+    let lib_name_close = lib_name.to_string();
+    // Write the rows, then close the window when `close` is set. A failed
+    // write keeps the window open with the rows.
+    let save_rows: Rc<dyn Fn(bool)> = Rc::new(move |close| {
         let Some(ed) = weak_close.upgrade() else {
             return;
         };
-        let idx = ed.get_current_index();
-        if idx >= 0 && (idx as usize) < model_close.row_count() {
-            let ru = ed.get_current_name_ru();
-            let en = ed.get_current_name_en();
-            let ord = ed.get_current_standard_ordinal();
-            if let Some(r) = model_close.row_data(idx as usize) {
-                model_close.set_row_data(
-                    idx as usize,
-                    FormationLevelRow {
-                        id: r.id,
-                        name_ru: ru,
-                        name_en: en,
-                        standard_level_ordinal: ord,
-                    },
-                );
+        commit_current_row(&ed, &model_close);
+        // Refuse to save (and keep the window open) while a range is invalid
+        let mut levels = Vec::with_capacity(model_close.row_count());
+        for i in 0..model_close.row_count() {
+            let Some(r) = model_close.row_data(i) else {
+                continue;
+            };
+            match parse_personnel_range(&r.min_personnel, &r.max_personnel) {
+                Ok(_) => levels.push(row_level(lib_id, &r)),
+                Err(e) => {
+                    ed.set_current_index(i as i32);
+                    show_row(&ed, &r, &lang_close);
+                    let name = if r.name_en.is_empty() { r.name_ru.clone() } else { r.name_en.clone() };
+                    let message = format!("{}: {}", name, ui_tr(&lang_close, &e));
+                    show_error_dialog(&lang_close, "Formation levels", &message, &[]);
+                    return;
+                }
             }
         }
-        let st = state_close.borrow();
-        if let Some(ref db) = st.database {
-            let conn = db.conn();
-            if let Err(e) = conn.execute_batch("BEGIN IMMEDIATE") {
-                log::error!("Failed to begin transaction: {}", e);
-            } else {
-                let repo = FormationLevelRepo::new(conn);
-                let mut ok = true;
-                let existing = repo.list_by_library(lib_id).unwrap_or_default();
-                for old in &existing {
-                    if let Some(id) = old.id {
-                        if repo.delete(id).is_err() {
-                            ok = false;
-                            break;
-                        }
-                    }
-                }
-                if ok {
-                    for i in 0..model_close.row_count() {
-                        if let Some(r) = model_close.row_data(i) {
-                            let mut custom = CustomFormationLevel::new(
-                                lib_id,
-                                r.name_ru.to_string(),
-                                r.name_en.to_string(),
-                                r.standard_level_ordinal,
-                            );
-                            if repo.create(&mut custom).is_err() {
-                                ok = false;
-                                break;
-                            }
-                        }
-                    }
-                }
-                if ok {
-                    let _ = conn.execute_batch("COMMIT");
+        // Two levels with one English name cannot be told apart; several
+        // levels at one standard level are allowed after a warning
+        let issues = validate_formation_level_set(&levels);
+        if let Some(name) = issues.duplicate_names.first() {
+            let key = name.to_lowercase();
+            let duplicate = (0..model_close.row_count())
+                .filter_map(|i| model_close.row_data(i).map(|r| (i, r)))
+                .filter(|(_, r)| r.name_en.trim().to_lowercase() == key)
+                .nth(1);
+            if let Some((i, r)) = duplicate {
+                ed.set_current_index(i as i32);
+                show_row(&ed, &r, &lang_close);
+            }
+            let message = ui_tr(&lang_close, "Several levels have the English name \"{}\". Level names must be unique.")
+                .replacen("{}", name, 1);
+            show_error_dialog(&lang_close, "Formation levels", &message, &[]);
+            return;
+        }
+        if close && loaded_close.is_saved(&EditorRows::FormationLevels(levels.clone())) {
+            journal_close.finished();
+            hide_remembering(&ed, WindowKind::FormationLevelsEditor);
+            return;
+        }
+        let (weak_saved, journal_saved, loaded_saved, model_saved, lang_saved) = (
+            weak_close.clone(),
+            journal_close.clone(),
+            loaded_close.clone(),
+            model_close.clone(),
+            lang_close.clone(),
+        );
+        let saved = Rc::new(move |ids: Vec<i64>| {
+            adopt_stored_ids(&model_saved, &ids, |row, id| row.id = id);
+            let rows = model_saved.iter().map(|r| row_level(lib_id, &r)).collect();
+            loaded_saved.saved(EditorRows::FormationLevels(rows));
+            journal_saved.finished();
+            if let Some(ed) = weak_saved.upgrade() {
+                if close {
+                    hide_remembering(&ed, WindowKind::FormationLevelsEditor);
                 } else {
-                    log::error!("Rolling back formation levels save for library {}", lib_id);
-                    let _ = conn.execute_batch("ROLLBACK");
+                    ed.set_status_text(ui_tr(&lang_saved, "Saved").into());
+                }
+            }
+        });
+        let (state_save, lang_save) = (state_close.clone(), lang_close.clone());
+        let save = move || {
+            let orphans = state_save
+                .borrow()
+                .database()
+                .map(|db| orphaned_levels(db.conn(), lib_id, &levels));
+            match orphans {
+                Some(Ok(orphans)) if !orphans.is_empty() => {
+                    // Units would lose their level: ask where they go before saving
+                    let saved = saved.clone();
+                    show_level_remap_dialog(state_save.clone(), lib_id, &lang_save, levels.clone(), orphans, move |ids| {
+                        saved(ids)
+                    });
+                    return;
                 }
+                Some(Err(e)) => log::error!("Failed to check units of removed formation levels: {}", e),
+                _ => {}
             }
+            match save_levels(&state_save, lib_id, &levels, &LevelRemap::new()) {
+                Ok(ids) => saved(ids),
+                Err(e) => report_save_error(&lang_save, EditorKind::FormationLevels, lib_id, &e),
+            }
+        };
+        let (state_reload, weak_reload, journal_reload) = (state_close.clone(), weak_close.clone(), journal_close.clone());
+        let (lib_name, lang) = (lib_name_close.clone(), lang_close.clone());
+        let reload = move || {
+            journal_reload.finished();
+            if let Some(ed) = weak_reload.upgrade() {
+                hide_remembering(&ed, WindowKind::FormationLevelsEditor);
+            }
+            show_formation_levels_editor(state_reload.clone(), lib_id, &lib_name, &lang);
+        };
+        if issues.shared_ordinals.is_empty() {
+            save_unless_changed(&lang_close, &loaded_close, save, reload);
+            return;
         }
-        let _ = ed.hide();
+        let shared: Vec<String> = issues
+            .shared_ordinals
+            .iter()
+            .map(|(ordinal, names)| {
+                let level = StandardFormationLevel::from_ordinal(*ordinal)
+                    .map_or("", |l| if lang_close == "ru" { l.name_ru() } else { l.name_en() });
+                format!("{}: {}", level, names.join(", "))
+            })
+            .collect();
+        let message = ui_tr(&lang_close, "Several levels correspond to the same standard level:\n{}\nSave anyway?")
+            .replacen("{}", &shared.join("\n"), 1);
+        let spec = ConfirmSpec::new("Formation levels", message, "Save anyway");
+        let (save, reload) = (Rc::new(save), Rc::new(reload));
+        let (lang, loaded) = (lang_close.clone(), loaded_close.clone());
+        confirm(&lang_close, spec, move |confirmed| {
+            if confirmed {
+                let (save, reload) = (save.clone(), reload.clone());
+                save_unless_changed(&lang, &loaded, move || save(), move || reload());
+            }
+        });
     });
+    let save_click = save_rows.clone();
+    editor.on_save(move || save_click(false));
+    editor.on_close_editor(move || save_rows(true));
     let weak_sel = weak_editor.clone();
     let model_sel = model.clone();
+    let lang_sel = lang.to_string();
     editor.on_selection_changed(move |index| {
         let Some(ed) = weak_sel.upgrade() else {
             return;
         };
+        commit_current_row(&ed, &model_sel);
         if index >= 0 && (index as usize) < model_sel.row_count() {
             if let Some(r) = model_sel.row_data(index as usize) {
-                ed.set_current_name_ru(r.name_ru.clone());
-                ed.set_current_name_en(r.name_en.clone());
-                ed.set_current_standard_ordinal(r.standard_level_ordinal);
+                ed.set_current_index(index);
+                show_row(&ed, &r, &lang_sel);
             }
         }
     });
     let weak_form = weak_editor.clone();
     let model_form = model.clone();
+    let note_form = note_change.clone();
     editor.on_form_changed(move |ru, en, ord| {
         let Some(ed) = weak_form.upgrade() else {
             return;
         };
         let idx = ed.get_current_index();
         if idx >= 0 && (idx as usize) < model_form.row_count() {
-            let id = model_form
-                .row_data(idx as usize)
-                .map(|r| r.id)
-                .unwrap_or(-1);
-            model_form.set_row_data(
-                idx as usize,
-                FormationLevelRow {
-                    id,
-                    name_ru: ru,
-                    name_en: en,
-                    standard_level_ordinal: ord,
-                },
-            );
+            if let Some(r) = model_form.row_data(idx as usize) {
+                model_form.set_row_data(
+                    idx as usize,
+                    FormationLevelRow {
+                        name_ru: ru,
+                        name_en: en,
+                        standard_level_ordinal: ord,
+                        ..r
+                    },
+                );
+                note_form();
+            }
         }
     });
+    let weak_range = weak_editor.clone();
+    let model_range = model.clone();
+    let lang_range = lang.to_string();
+    let note_range = note_change.clone();
+    editor.on_range_edited(move |min, max| {
+        let Some(ed) = weak_range.upgrade() else {
+            return;
+        };
+        let error = parse_personnel_range(&min, &max).err();
+        ed.set_range_error(error.map(|e| ui_tr(&lang_range, &e)).unwrap_or_default().into());
+        let idx = ed.get_current_index();
+        if idx >= 0 && (idx as usize) < model_range.row_count() {
+            if let Some(r) = model_range.row_data(idx as usize) {
+                model_range.set_row_data(
+                    idx as usize,
+                    FormationLevelRow {
+                        min_personnel: min,
+                        max_personnel: max,
+                        ..r
+                    },
+                );
+                note_range();
+            }
+        }
+    });
+    let weak_exp = weak_editor.clone();
     let model_exp = model.clone();
     editor.on_export_levels(move || {
-        let levels: Vec<CustomFormationLevel> = (0..model_exp.row_count())
-            .filter_map(|i| model_exp.row_data(i))
-            .map(|r| {
-                CustomFormationLevel::new(
-                    lib_id,
-                    r.name_ru.to_string(),
-                    r.name_en.to_string(),
-                    r.standard_level_ordinal,
-                )
-            })
-            .collect();
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("JSON", &["json"])
-            .save_file()
-        {
+        if let Some(ed) = weak_exp.upgrade() {
+            commit_current_row(&ed, &model_exp);
+        }
+        // Invalid ranges are left out rather than blocking the export
+        let levels: Vec<CustomFormationLevel> = model_exp.iter().map(|r| row_level(lib_id, &r)).collect();
+        choose_file(FileRequest::save().filter("JSON", &["json"]), move |path| {
             if let Err(e) = export_formation_levels_to_path(path.as_path(), &levels) {
                 log::error!("Export formation levels: {}", e);
             }
+        });
+    });
+    let state_clip = state.clone();
+    let weak_clip = weak_editor.clone();
+    let model_clip = model.clone();
+    let lang_clip = lang.to_string();
+    editor.on_copy_all(move || {
+        let Some(ed) = weak_clip.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_clip);
+        let levels: Vec<CustomFormationLevel> = model_clip.iter().map(|r| row_level(lib_id, &r)).collect();
+        let content = ClipboardContent::FormationLevels(formation_level_exports(&levels));
+        let (text, status) = copy_to_clipboard(&state_clip, &lang_clip, content);
+        ed.invoke_copy_to_system_clipboard(text.into());
+        ed.set_status_text(status.into());
+    });
+    let state_paste = state.clone();
+    let weak_paste = weak_editor.clone();
+    let model_paste = model.clone();
+    let lang_paste = lang.to_string();
+    let note_paste = note_change.clone();
+    editor.on_paste_rows(move || {
+        let Some(ed) = weak_paste.upgrade() else {
+            return;
+        };
+        commit_current_row(&ed, &model_paste);
+        let clipboard = state_paste.borrow().clipboard.clone();
+        let Some(ClipboardContent::FormationLevels(rows)) = clipboard else {
+            ed.set_status_text(nothing_to_paste(&lang_paste).into());
+            return;
+        };
+        let pasted = paste_rows(model_paste.iter().map(|r| r.name_en), &rows, |l| &l.name_en);
+        for l in &pasted.rows {
+            model_paste.push(FormationLevelRow {
+                id: -1,
+                name_ru: l.name_ru.as_str().into(),
+                name_en: l.name_en.as_str().into(),
+                standard_level_ordinal: l.standard_level_ordinal,
+                min_personnel: bound_text(l.min_personnel),
+                max_personnel: bound_text(l.max_personnel),
+            });
         }
+        if !pasted.rows.is_empty() {
+            note_paste();
+        }
+        ed.set_status_text(paste_status(&lang_paste, &pasted).into());
     });
+    let note_copy = note_change.clone();
     let weak_imp = weak_editor.clone();
     let model_imp = model.clone();
+    let lang_imp = lang.to_string();
     editor.on_import_levels(move || {
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("JSON", &["json"])
-            .pick_file()
-        {
+        let weak_imp = weak_imp.clone();
+        let model_imp = model_imp.clone();
+        let lang_imp = lang_imp.clone();
+        let note_imp = note_change.clone();
+        choose_file(FileRequest::open().filter("JSON", &["json"]), move |path| {
             match import_formation_levels_from_path(path.as_path()) {
                 Ok(imported) => {
                     while model_imp.row_count() > 0 {
@@ -287,6 +734,8 @@ pub(in crate::app) fn show_formation_levels_editor(
                                 name_ru: e.name_ru.into(),
                                 name_en: e.name_en.into(),
                                 standard_level_ordinal: e.standard_level_ordinal,
+                                min_personnel: bound_text(e.min_personnel),
+                                max_personnel: bound_text(e.max_personnel),
                             },
                         );
                     }
@@ -294,20 +743,20 @@ pub(in crate::app) fn show_formation_levels_editor(
                         ed.set_current_index(if model_imp.row_count() > 0 { 0 } else { -1 });
                         if model_imp.row_count() > 0 {
                             if let Some(r) = model_imp.row_data(0) {
-                                ed.set_current_name_ru(r.name_ru.clone());
-                                ed.set_current_name_en(r.name_en.clone());
-                                ed.set_current_standard_ordinal(r.standard_level_ordinal);
+                                show_row(&ed, &r, &lang_imp);
                             }
                         }
                     }
+                    note_imp();
                 }
                 Err(e) => log::error!("Import formation levels: {}", e),
             }
-        }
+        });
     });
     let state_copy = state.clone();
     let weak_copy = weak_editor.clone();
     let model_copy = model.clone();
+    let lang_copy = lang.to_string();
     let source_ids = source_library_ids.clone();
     editor.on_copy_from_library(move || {
         let Some(ed) = weak_copy.upgrade() else {
@@ -318,45 +767,78 @@ pub(in crate::app) fn show_formation_levels_editor(
             return;
         }
         let source_id = source_ids[idx as usize];
-        let st = state_copy.borrow();
-        if let Some(ref db) = st.database {
-            let level_repo = FormationLevelRepo::new(db.conn());
-            if let Err(e) =
-                copy_formation_levels_between_libraries(&level_repo, source_id, lib_id)
-            {
-                log::error!("Copy formation levels: {}", e);
+        // The copy replaces the editor's rows and is stored on close, where
+        // units of the levels it drops are mapped onto the copied ones
+        let copied = {
+            let st = state_copy.borrow();
+            let Some(db) = st.database() else {
                 return;
-            }
-            drop(st);
-            let st2 = state_copy.borrow();
-            if let Some(ref db2) = st2.database {
-                let level_repo2 = FormationLevelRepo::new(db2.conn());
-                if let Ok(new_levels) = level_repo2.list_by_library(lib_id) {
-                    while model_copy.row_count() > 0 {
-                        model_copy.remove(0);
-                    }
-                    for l in new_levels {
-                        model_copy.insert(
-                            model_copy.row_count(),
-                            FormationLevelRow {
-                                id: l.id.unwrap_or(-1) as i32,
-                                name_ru: l.name_ru.into(),
-                                name_en: l.name_en.into(),
-                                standard_level_ordinal: l.standard_level_ordinal,
-                            },
-                        );
-                    }
-                    ed.set_current_index(if model_copy.row_count() > 0 { 0 } else { -1 });
-                    if model_copy.row_count() > 0 {
-                        if let Some(r) = model_copy.row_data(0) {
-                            ed.set_current_name_ru(r.name_ru.clone());
-                            ed.set_current_name_en(r.name_en.clone());
-                            ed.set_current_standard_ordinal(r.standard_level_ordinal);
-                        }
-                    }
+            };
+            match FormationLevelRepo::new(db.conn()).list_by_library(source_id) {
+                Ok(levels) => {
+                    record_copy(db.conn(), lib_id, source_id, CopiedData::FormationLevels);
+                    levels
+                }
+                Err(e) => {
+                    log::error!("Copy formation levels: {}", e);
+                    return;
                 }
             }
+        };
+        while model_copy.row_count() > 0 {
+            model_copy.remove(0);
+        }
+        for mut l in copied {
+            l.id = None;
+            model_copy.insert(model_copy.row_count(), level_row(l));
+        }
+        ed.set_current_index(if model_copy.row_count() > 0 { 0 } else { -1 });
+        if let Some(r) = model_copy.row_data(0) {
+            show_row(&ed, &r, &lang_copy);
         }
+        note_copy();
     });
-    editor.show().unwrap_or_default();
+    remember_geometry(&editor, WindowKind::FormationLevelsEditor);
+    if editor.show().is_ok() {
+        state.borrow_mut().open_windows.add(OpenWindow::of(&editor, translate));
+    }
+    register_open_editor(&editor, lib_id, EditorKind::FormationLevels);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_personnel_range() {
+        assert_eq!(parse_personnel_range("", " "), Ok((None, None)));
+        assert_eq!(parse_personnel_range(" 25", "45 "), Ok((Some(25), Some(45))));
+        assert_eq!(parse_personnel_range("30", ""), Ok((Some(30), None)));
+        assert_eq!(
+            parse_personnel_range("45", "30"),
+            Err("Minimum personnel cannot exceed maximum personnel".to_string())
+        );
+        assert_eq!(
+            parse_personnel_range("-1", ""),
+            Err("Expected personnel must be a whole number".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sorted_levels_by_standard_level_then_order_then_name() {
+        let level = |en: &str, ordinal: i32, order: i64| {
+            let mut l = CustomFormationLevel::new(1, String::new(), en.to_string(), ordinal);
+            l.sort_order = order;
+            l
+        };
+        let levels = vec![
+            level("company", 3, 0),
+            level("troop", 2, 5),
+            level("Battery", 3, 1),
+            level("platoon", 2, 5),
+            level("section", 2, 1),
+        ];
+        let names: Vec<String> = sorted_levels(levels).into_iter().map(|l| l.name_en).collect();
+        assert_eq!(names, vec!["section", "platoon", "troop", "company", "Battery"]);
+    }
 }
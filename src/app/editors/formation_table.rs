@@ -0,0 +1,364 @@
+//! Formation table editor: the personnel and equipment rows of one formation
+//!
+//! Saving writes only the rows that differ from the stored ones
+//! ([`UnitService::save_unit_rows`]) and counts each written row as an
+//! unsaved change of the library. Unsaved rows are journaled like those of
+//! the other editors, so they survive a crash.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use slint::{ComponentHandle, Model, ModelRc, VecModel};
+
+use crate::db::repositories::{LibraryRepo, UnitRepo};
+use crate::models::{Equipment, Personnel};
+use crate::services::{EditorKind, EditorRows, UnitService};
+use super::super::{AppState, FormationEquipmentRow, FormationPersonnelRow, FormationTableEditor};
+use super::super::open_windows::OpenWindow;
+use super::super::translations::{ui_tr, ui_tr_args};
+use super::super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::super::confirm::{confirm, ConfirmSpec};
+use super::super::dialogs::show_error_dialog;
+use super::super::notify_rows_saved;
+use super::super::recovery::EditorJournal;
+use super::{focus_open_editor, register_open_editor, save_unless_changed, LoadedRows};
+
+/// A unit's stored rows with their ids
+type StoredRows = (Vec<(i64, Personnel)>, Vec<(i64, Equipment)>);
+
+/// Edited rows with their stored ids, `None` for new rows
+type EditedRows = (Vec<(Option<i64>, Personnel)>, Vec<(Option<i64>, Equipment)>);
+
+fn personnel_row((id, p): (i64, Personnel)) -> FormationPersonnelRow {
+    FormationPersonnelRow {
+        id: id as i32,
+        position: p.position.into(),
+        rank: p.rank.unwrap_or_default().into(),
+    }
+}
+
+fn equipment_row((id, e): (i64, Equipment)) -> FormationEquipmentRow {
+    FormationEquipmentRow {
+        id: id as i32,
+        name: e.name.into(),
+        quantity: e.quantity.to_string().into(),
+    }
+}
+
+fn stored_id(id: i32) -> Option<i64> {
+    (id >= 0).then_some(i64::from(id))
+}
+
+/// The rows to save: personnel without a position and equipment without a
+/// name are dropped. Fails with the index of the first equipment row whose
+/// quantity is not a whole number.
+fn edited_rows(personnel: &[FormationPersonnelRow], equipment: &[FormationEquipmentRow]) -> Result<EditedRows, usize> {
+    let personnel = personnel
+        .iter()
+        .filter(|r| !r.position.trim().is_empty())
+        .map(|r| {
+            let rank = r.rank.trim();
+            let p = Personnel {
+                position: r.position.trim().to_string(),
+                rank: (!rank.is_empty()).then(|| rank.to_string()),
+            };
+            (stored_id(r.id), p)
+        })
+        .collect();
+    let mut rows = Vec::with_capacity(equipment.len());
+    for (i, r) in equipment.iter().enumerate() {
+        if r.name.trim().is_empty() {
+            continue;
+        }
+        let quantity = r.quantity.trim().parse::<usize>().map_err(|_| i)?;
+        rows.push((stored_id(r.id), Equipment::new(r.name.trim().to_string(), quantity)));
+    }
+    Ok((personnel, rows))
+}
+
+fn load_rows(state: &RefCell<AppState>, unit_id: i64) -> anyhow::Result<StoredRows> {
+    let st = state.borrow();
+    let db = st.database().ok_or_else(|| anyhow::anyhow!("Database not initialized"))?;
+    let repo = UnitRepo::new(db.conn());
+    Ok((repo.list_personnel_rows(unit_id)?, repo.list_equipment_rows(unit_id)?))
+}
+
+/// Show `rows` in the editor's lists
+fn show_rows(
+    personnel: &VecModel<FormationPersonnelRow>,
+    equipment: &VecModel<FormationEquipmentRow>,
+    (stored_personnel, stored_equipment): StoredRows,
+) {
+    personnel.set_vec(stored_personnel.into_iter().map(personnel_row).collect::<Vec<_>>());
+    equipment.set_vec(stored_equipment.into_iter().map(equipment_row).collect::<Vec<_>>());
+}
+
+/// Set the window texts in `lang`
+fn translate(editor: &FormationTableEditor, lang: &str) {
+    editor.set_tr_table_title(ui_tr(lang, "Formation Table").into());
+    editor.set_tr_personnel(ui_tr(lang, "Personnel").into());
+    editor.set_tr_position(ui_tr(lang, "Position").into());
+    editor.set_tr_rank(ui_tr(lang, "Rank").into());
+    editor.set_tr_equipment(ui_tr(lang, "Equipment").into());
+    editor.set_tr_name(ui_tr(lang, "Name").into());
+    editor.set_tr_quantity(ui_tr(lang, "Quantity").into());
+    editor.set_tr_add(ui_tr(lang, "Add").into());
+    editor.set_tr_remove(ui_tr(lang, "Remove").into());
+    editor.set_tr_save(ui_tr(lang, "Save").into());
+    editor.set_tr_close(ui_tr(lang, "Close").into());
+}
+
+/// Open the table editor of formation `unit_id`.
+pub(in crate::app) fn show_formation_table_editor(state: Rc<RefCell<AppState>>, unit_id: i64, lang: &str) {
+    let found = {
+        let st = state.borrow();
+        let Some(db) = st.database() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        let repo = UnitRepo::new(db.conn());
+        let name = repo.get_by_id(unit_id).ok().flatten().map(|u| u.name);
+        let lib_id = repo.get_placement(unit_id).ok().flatten().map(|(lib_id, _)| lib_id);
+        name.zip(lib_id).map(|(name, lib_id)| {
+            let lib_name = LibraryRepo::new(db.conn()).get_by_id(lib_id).ok().flatten().map(|l| l.name);
+            (name, lib_id, lib_name.unwrap_or_default())
+        })
+    };
+    let Some((name, lib_id, lib_name)) = found else {
+        log::error!("Formation {} not found", unit_id);
+        return;
+    };
+    let kind = EditorKind::FormationTable(unit_id);
+    if focus_open_editor(lib_id, kind) {
+        return;
+    }
+    let stored = match load_rows(&state, unit_id) {
+        Ok(rows) => rows,
+        Err(e) => {
+            log::error!("Failed to load the rows of formation {}: {}", unit_id, e);
+            show_error_dialog(lang, "Error", "Failed to load the formation table: {}", &[&e]);
+            return;
+        }
+    };
+    let editor = match FormationTableEditor::new() {
+        Ok(e) => e,
+        Err(e) => {
+            log::error!("Failed to create Formation Table editor: {}", e);
+            return;
+        }
+    };
+    editor.set_unit_id(unit_id as i32);
+    editor.set_unit_name(name.into());
+    let personnel = Rc::new(VecModel::default());
+    let equipment = Rc::new(VecModel::default());
+    show_rows(&personnel, &equipment, stored);
+    editor.set_personnel(ModelRc::new(personnel.clone()));
+    editor.set_equipment(ModelRc::new(equipment.clone()));
+    editor.set_dirty(false);
+    translate(&editor, lang);
+
+    let weak_editor = editor.as_weak();
+    let journal = EditorJournal::new(&state, lib_id, &lib_name, kind);
+    let loaded = LoadedRows::new(&state, lib_id, kind);
+    let mark_dirty = {
+        let weak = weak_editor.clone();
+        let (personnel, equipment, journal) = (personnel.clone(), equipment.clone(), journal.clone());
+        Rc::new(move || {
+            if let Some(ed) = weak.upgrade() {
+                ed.set_dirty(true);
+                ed.set_status_text(Default::default());
+            }
+            // Rows with a bad quantity are journaled once it is fixed
+            let (personnel_rows, equipment_rows): (Vec<_>, Vec<_>) = (personnel.iter().collect(), equipment.iter().collect());
+            if let Ok((personnel, equipment)) = edited_rows(&personnel_rows, &equipment_rows) {
+                journal.changed(EditorRows::FormationTable { unit_id, personnel, equipment });
+            }
+        })
+    };
+
+    let (model, dirty) = (personnel.clone(), mark_dirty.clone());
+    editor.on_personnel_edited(move |index, position, rank| {
+        let Some(mut row) = usize::try_from(index).ok().and_then(|i| model.row_data(i)) else {
+            return;
+        };
+        row.position = position;
+        row.rank = rank;
+        model.set_row_data(index as usize, row);
+        dirty();
+    });
+    let (model, dirty) = (equipment.clone(), mark_dirty.clone());
+    editor.on_equipment_edited(move |index, name, quantity| {
+        let Some(mut row) = usize::try_from(index).ok().and_then(|i| model.row_data(i)) else {
+            return;
+        };
+        row.name = name;
+        row.quantity = quantity;
+        model.set_row_data(index as usize, row);
+        dirty();
+    });
+    let (model, dirty) = (personnel.clone(), mark_dirty.clone());
+    editor.on_add_personnel(move || {
+        model.push(FormationPersonnelRow { id: -1, position: Default::default(), rank: Default::default() });
+        dirty();
+    });
+    let (model, dirty) = (equipment.clone(), mark_dirty.clone());
+    editor.on_add_equipment(move || {
+        model.push(FormationEquipmentRow { id: -1, name: Default::default(), quantity: "1".into() });
+        dirty();
+    });
+    let (model, dirty) = (personnel.clone(), mark_dirty.clone());
+    editor.on_remove_personnel(move |index| {
+        if let Some(i) = usize::try_from(index).ok().filter(|&i| i < model.row_count()) {
+            model.remove(i);
+            dirty();
+        }
+    });
+    let (model, dirty) = (equipment.clone(), mark_dirty);
+    editor.on_remove_equipment(move |index| {
+        if let Some(i) = usize::try_from(index).ok().filter(|&i| i < model.row_count()) {
+            model.remove(i);
+            dirty();
+        }
+    });
+
+    let weak_save = weak_editor.clone();
+    let state_save = state.clone();
+    let (personnel_save, equipment_save) = (personnel.clone(), equipment.clone());
+    let lang_save = lang.to_string();
+    let (journal_save, loaded_save) = (journal.clone(), loaded.clone());
+    editor.on_save(move || {
+        let Some(ed) = weak_save.upgrade() else {
+            return;
+        };
+        let personnel_rows: Vec<_> = personnel_save.iter().collect();
+        let equipment_rows: Vec<_> = equipment_save.iter().collect();
+        let (personnel, equipment) = match edited_rows(&personnel_rows, &equipment_rows) {
+            Ok(rows) => rows,
+            Err(row) => {
+                let message = ui_tr_args(&lang_save, "The quantity in equipment row {} must be a whole number.", &[&(row + 1)]);
+                ed.set_status_text(message.into());
+                return;
+            }
+        };
+        let (weak, state, journal, loaded) =
+            (weak_save.clone(), state_save.clone(), journal_save.clone(), loaded_save.clone());
+        let (personnel_model, equipment_model, lang) = (personnel_save.clone(), equipment_save.clone(), lang_save.clone());
+        let save = move || {
+            let saved = {
+                let st = state.borrow();
+                let Some(db) = st.database() else {
+                    return;
+                };
+                UnitService::new(db.conn()).save_unit_rows(unit_id, &personnel, &equipment)
+            };
+            match saved {
+                Ok(changes) => {
+                    journal.finished();
+                    loaded.refresh();
+                    notify_rows_saved(&state, unit_id, changes);
+                    // New rows get their stored ids, so the next save updates them
+                    match load_rows(&state, unit_id) {
+                        Ok(stored) => show_rows(&personnel_model, &equipment_model, stored),
+                        Err(e) => log::warn!("Failed to reload the rows of formation {}: {}", unit_id, e),
+                    }
+                    let Some(ed) = weak.upgrade() else {
+                        return;
+                    };
+                    ed.set_dirty(false);
+                    let status = ui_tr_args(
+                        &lang,
+                        "Saved: {} added, {} changed, {} removed",
+                        &[&changes.inserted, &changes.updated, &changes.deleted],
+                    );
+                    ed.set_status_text(status.into());
+                }
+                Err(e) => {
+                    log::error!("Failed to save the rows of formation {}: {:#}", unit_id, e);
+                    show_error_dialog(
+                        &lang,
+                        "Formation Table",
+                        "The changes could not be saved. The editor stays open with them.\n\n{}",
+                        &[&format!("{:#}", e)],
+                    );
+                }
+            }
+        };
+        let (weak, state, journal, lang) = (weak_save.clone(), state_save.clone(), journal_save.clone(), lang_save.clone());
+        let reload = move || {
+            journal.finished();
+            if let Some(ed) = weak.upgrade() {
+                hide_remembering(&ed, WindowKind::FormationTableEditor);
+            }
+            show_formation_table_editor(state.clone(), unit_id, &lang);
+        };
+        save_unless_changed(&lang_save, &loaded_save, save, reload);
+    });
+
+    let weak_close = weak_editor.clone();
+    let lang_close = lang.to_string();
+    let journal_close = journal.clone();
+    editor.on_close_editor(move || {
+        let Some(ed) = weak_close.upgrade() else {
+            return;
+        };
+        if !ed.get_dirty() {
+            hide_remembering(&ed, WindowKind::FormationTableEditor);
+            return;
+        }
+        let spec = ConfirmSpec::new(
+            "Formation Table",
+            ui_tr(&lang_close, "Close the table without saving your changes?"),
+            "Discard",
+        )
+        .danger();
+        let (weak, journal) = (weak_close.clone(), journal_close.clone());
+        confirm(&lang_close, spec, move |confirmed| {
+            if let Some(ed) = weak.upgrade().filter(|_| confirmed) {
+                journal.finished();
+                hide_remembering(&ed, WindowKind::FormationTableEditor);
+            }
+        });
+    });
+
+    remember_geometry(&editor, WindowKind::FormationTableEditor);
+    if editor.show().is_ok() {
+        state.borrow_mut().open_windows.add(OpenWindow::of(&editor, translate));
+    }
+    register_open_editor(&editor, lib_id, kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn personnel(id: i32, position: &str, rank: &str) -> FormationPersonnelRow {
+        FormationPersonnelRow { id, position: position.into(), rank: rank.into() }
+    }
+
+    fn equipment(id: i32, name: &str, quantity: &str) -> FormationEquipmentRow {
+        FormationEquipmentRow { id, name: name.into(), quantity: quantity.into() }
+    }
+
+    #[test]
+    fn test_edited_rows_keep_ids_and_drop_blank_rows() {
+        let (p, e) = edited_rows(
+            &[personnel(4, " Rifleman ", ""), personnel(-1, "  ", "PFC"), personnel(-1, "Medic", " SPC ")],
+            &[equipment(9, "M4 Carbine", " 9 "), equipment(-1, "", "x")],
+        )
+        .unwrap();
+        assert_eq!(
+            p,
+            vec![
+                (Some(4), Personnel::new("Rifleman".to_string())),
+                (None, Personnel::with_rank("Medic".to_string(), "SPC".to_string())),
+            ]
+        );
+        assert_eq!(e, vec![(Some(9), Equipment::new("M4 Carbine".to_string(), 9))]);
+    }
+
+    #[test]
+    fn test_edited_rows_reject_bad_quantities() {
+        let rows = [equipment(1, "M4 Carbine", "9"), equipment(2, "HMMWV", "two")];
+        assert_eq!(edited_rows(&[], &rows), Err(1));
+        assert_eq!(edited_rows(&[], &[equipment(-1, "HMMWV", "-1")]), Err(0));
+    }
+}
@@ -0,0 +1,306 @@
+//! History window: the snapshots of a library, with restore, delete and prune.
+//!
+//! A prune runs on a worker thread with its own connection; the window polls
+//! the operation registry to show a banner meanwhile and reloads afterwards.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::sync::mpsc;
+use std::time::Duration;
+use slint::{ComponentHandle, Model, ModelRc, VecModel, Weak};
+
+use crate::db::Database;
+use crate::i18n::{format_timestamp, time_ago};
+use crate::models::Snapshot;
+use crate::services::{prune_history, ChangeEvent, LibraryService, MaintenanceChange, MaintenanceWatch, PrunePolicy};
+use super::super::{HistoryWindow, HistoryRow, MainWindow, AppState};
+use super::super::open_windows::OpenWindow;
+use super::super::translations::ui_tr;
+use super::super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::super::dialogs::show_error_dialog;
+use super::super::confirm::{confirm, ConfirmSpec};
+use super::super::notify_change;
+
+fn history_rows(snapshots: &[Snapshot], lang: &str) -> Vec<HistoryRow> {
+    let now = chrono::Utc::now().timestamp();
+    snapshots
+        .iter()
+        .map(|s| HistoryRow {
+            id: s.id.unwrap_or(-1) as i32,
+            version: s.version as i32,
+            timestamp: format_timestamp(s.timestamp).into(),
+            relative: time_ago(s.timestamp, now, lang).into(),
+            description: if s.is_autosave {
+                ui_tr(lang, "Autosave").into()
+            } else {
+                s.description.clone().unwrap_or_default().into()
+            },
+            autosave: s.is_autosave,
+        })
+        .collect()
+}
+
+/// How often an open History window checks whether a prune is running
+const MAINTENANCE_POLL: Duration = Duration::from_millis(500);
+
+/// Reload the rows, versions and autosaves, after a restore or delete;
+/// `None` if the database is gone
+fn reload(state: &Rc<RefCell<AppState>>, lib_id: i64, lang: &str) -> Option<Vec<HistoryRow>> {
+    let st = state.borrow();
+    let db = st.database()?;
+    match LibraryService::new(db.conn()).get_history(lib_id) {
+        Ok(snapshots) => Some(history_rows(&snapshots, lang)),
+        Err(e) => {
+            log::error!("Failed to load versions: {}", e);
+            None
+        }
+    }
+}
+
+/// Set the window texts in `lang`
+fn translate(window: &HistoryWindow, lang: &str) {
+    window.set_tr_history_title(ui_tr(lang, "History").into());
+    window.set_tr_version(ui_tr(lang, "Version").into());
+    window.set_tr_date(ui_tr(lang, "Date").into());
+    window.set_tr_description(ui_tr(lang, "Description").into());
+    window.set_tr_only_version(ui_tr(lang, "The library has only one version.").into());
+    window.set_tr_restore(ui_tr(lang, "Restore").into());
+    window.set_tr_delete(ui_tr(lang, "Delete").into());
+    window.set_tr_close(ui_tr(lang, "Close").into());
+    window.set_tr_prune(ui_tr(lang, "Prune…").into());
+    window.set_tr_maintenance(ui_tr(lang, "Pruning old versions… The list reloads when it is done.").into());
+}
+
+/// Open the History window for the given library.
+pub(in crate::app) fn show_history_window(
+    state: Rc<RefCell<AppState>>,
+    weak_window: Weak<MainWindow>,
+    lib_id: i64,
+    lib_name: &str,
+    lang: &str,
+) {
+    let Some(rows) = reload(&state, lib_id, lang) else {
+        log::error!("Database not initialized");
+        return;
+    };
+    let window = match HistoryWindow::new() {
+        Ok(w) => w,
+        Err(e) => {
+            log::error!("Failed to create History window: {}", e);
+            return;
+        }
+    };
+    window.set_library_id(lib_id as i32);
+    window.set_library_name(lib_name.into());
+    let model = Rc::new(VecModel::from(rows));
+    window.set_rows(ModelRc::new(model.clone()));
+    window.set_current_index(-1);
+    translate(&window, lang);
+
+    let weak_history = window.as_weak();
+    let weak_sel = weak_history.clone();
+    window.on_selection_changed(move |index| {
+        if let Some(w) = weak_sel.upgrade() {
+            w.set_current_index(index);
+        }
+    });
+
+    let weak_restore = weak_history.clone();
+    let model_restore = model.clone();
+    let state_restore = state.clone();
+    let lang_restore = lang.to_string();
+    let weak_main_restore = weak_window.clone();
+    window.on_restore_version(move || {
+        let Some(w) = weak_restore.upgrade() else {
+            return;
+        };
+        let Some(row) = usize::try_from(w.get_current_index()).ok().and_then(|i| model_restore.row_data(i)) else {
+            return;
+        };
+        let restored = {
+            let st = state_restore.borrow();
+            let Some(manager) = st.library_manager.as_ref() else {
+                return;
+            };
+            let _hold = st.operations.hold_versions(lib_id, &[row.version as i64]);
+            if row.autosave {
+                manager.restore_autosave(lib_id, row.id as i64)
+            } else {
+                manager.restore_version(lib_id, row.version as i64)
+            }
+        };
+        match restored {
+            Ok(snapshot) => {
+                log::info!("Restored library {} from v{} as v{}", lib_id, row.version, snapshot.version);
+                if let Some(rows) = reload(&state_restore, lib_id, &lang_restore) {
+                    model_restore.set_vec(rows);
+                }
+                w.set_current_index(0);
+                notify_change(&state_restore, ChangeEvent::LibraryChanged(lib_id));
+                // Reload the open library so the main window shows the restored state
+                if let Some(main) = weak_main_restore.upgrade() {
+                    if main.get_current_library_id() == lib_id as i32 {
+                        main.invoke_library_selected(lib_id as i32);
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to restore version: {}", e);
+                show_error_dialog(&lang_restore, "Error", "Failed to restore version: {}", &[&e]);
+            }
+        }
+    });
+
+    let weak_delete = weak_history.clone();
+    let model_delete = model.clone();
+    let state_delete = state.clone();
+    let lang_delete = lang.to_string();
+    window.on_delete_version(move || {
+        let Some(w) = weak_delete.upgrade() else {
+            return;
+        };
+        let Some(row) = usize::try_from(w.get_current_index()).ok().and_then(|i| model_delete.row_data(i)) else {
+            return;
+        };
+        let question = if row.autosave {
+            "Delete the autosave of version {} from the history?"
+        } else {
+            "Delete version {} from the history?"
+        };
+        let message = ui_tr(&lang_delete, question).replacen("{}", &row.version.to_string(), 1);
+        let spec = ConfirmSpec::new(
+            &ui_tr(&lang_delete, "Delete Version"),
+            message,
+            &ui_tr(&lang_delete, "Delete"),
+        )
+        .danger();
+        let weak_confirm = weak_delete.clone();
+        let model_confirm = model_delete.clone();
+        let state_confirm = state_delete.clone();
+        let lang_confirm = lang_delete.clone();
+        confirm(&lang_delete, spec, move |ok| {
+            if !ok {
+                return;
+            }
+            let deleted = {
+                let st = state_confirm.borrow();
+                let Some(db) = st.database() else {
+                    return;
+                };
+                LibraryService::new(db.conn()).delete_snapshot(lib_id, row.id as i64)
+            };
+            match deleted {
+                Ok(()) => {
+                    log::info!("Deleted v{} of library {}", row.version, lib_id);
+                    if let Some(rows) = reload(&state_confirm, lib_id, &lang_confirm) {
+                        model_confirm.set_vec(rows);
+                    }
+                    if let Some(w) = weak_confirm.upgrade() {
+                        w.set_current_index(-1);
+                    }
+                }
+                Err(e) => {
+                    log::error!("Failed to delete version: {}", e);
+                    show_error_dialog(&lang_confirm, "Error", "Failed to delete version: {}", &[&e]);
+                }
+            }
+        });
+    });
+
+    // Prunes started here report back through this channel; the timer below
+    // reads it along with the registry
+    let (pruned_tx, pruned_rx) = mpsc::channel::<Result<usize, String>>();
+    let weak_prune = weak_history.clone();
+    let state_prune = state.clone();
+    let lang_prune = lang.to_string();
+    window.on_prune_history(move || {
+        let policy = PrunePolicy::default();
+        let message = ui_tr(&lang_prune, "Delete all versions except the newest {} and those with a description?")
+            .replacen("{}", &policy.keep_latest.to_string(), 1);
+        let spec = ConfirmSpec::new(&ui_tr(&lang_prune, "Prune History"), message, &ui_tr(&lang_prune, "Prune")).danger();
+        let weak_confirm = weak_prune.clone();
+        let state_confirm = state_prune.clone();
+        let pruned_tx = pruned_tx.clone();
+        confirm(&lang_prune, spec, move |ok| {
+            if !ok {
+                return;
+            }
+            let st = state_confirm.borrow();
+            let Some(db) = st.database() else {
+                return;
+            };
+            let registry = st.operations.clone();
+            if let Some(w) = weak_confirm.upgrade() {
+                w.set_maintenance(true);
+            }
+            let Some(path) = db.path() else {
+                // An in-memory database has no other connection to prune on
+                let result = prune_history(db.conn(), &registry, lib_id, policy).map_err(|e| format!("{:#}", e));
+                pruned_tx.send(result).unwrap_or_default();
+                return;
+            };
+            let pruned_tx = pruned_tx.clone();
+            std::thread::spawn(move || {
+                let result = Database::open(&path)
+                    .and_then(|db| prune_history(db.conn(), &registry, lib_id, policy))
+                    .map_err(|e| format!("{:#}", e));
+                pruned_tx.send(result).unwrap_or_default();
+            });
+        });
+    });
+
+    let timer = Rc::new(slint::Timer::default());
+    let weak_poll = weak_history.clone();
+    let state_poll = state.clone();
+    let model_poll = model.clone();
+    let lang_poll = lang.to_string();
+    let registry = state.borrow().operations.clone();
+    let mut watch = MaintenanceWatch::new();
+    timer.start(slint::TimerMode::Repeated, MAINTENANCE_POLL, move || {
+        let Some(w) = weak_poll.upgrade() else {
+            return;
+        };
+        let change = watch.poll(&registry, lib_id);
+        let result = pruned_rx.try_recv().ok();
+        if change == MaintenanceChange::Started {
+            w.set_maintenance(true);
+        }
+        if change == MaintenanceChange::Finished || result.is_some() {
+            w.set_maintenance(watch.is_running());
+            if let Some(rows) = reload(&state_poll, lib_id, &lang_poll) {
+                model_poll.set_vec(rows);
+            }
+            w.set_current_index(-1);
+        }
+        match result {
+            Some(Ok(count)) => {
+                log::info!("Pruned {} versions of library {}", count, lib_id);
+                notify_change(&state_poll, ChangeEvent::LibraryChanged(lib_id));
+                if let Some(main) = weak_window.upgrade() {
+                    main.set_status_text(
+                        ui_tr(&lang_poll, "Deleted {} old versions").replacen("{}", &count.to_string(), 1).into(),
+                    );
+                }
+            }
+            Some(Err(e)) => {
+                log::error!("Failed to prune history: {}", e);
+                show_error_dialog(&lang_poll, "Error", "Failed to prune history: {}", &[&e]);
+            }
+            None => {}
+        }
+    });
+
+    let weak_close = weak_history.clone();
+    window.on_close_editor(move || {
+        // The timer lives as long as the window's callbacks
+        let _ = &timer;
+        if let Some(w) = weak_close.upgrade() {
+            hide_remembering(&w, WindowKind::HistoryWindow);
+        }
+    });
+
+    remember_geometry(&window, WindowKind::HistoryWindow);
+    if window.show().is_ok() {
+        state.borrow_mut().open_windows.add(OpenWindow::of(&window, translate));
+    }
+}
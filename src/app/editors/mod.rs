@@ -1,9 +1,200 @@
-//! Editor windows for branches, categories, and formation levels
+//! Editor windows for branches, categories, formation levels, the equipment catalog, formation tables, tags and library history
 
 mod branches;
 mod branch_categories;
+mod equipment_catalog;
 mod formation_levels;
+mod formation_table;
+mod history;
+mod open_editors;
+mod tags;
+
+use std::cell::RefCell;
+use slint::{Model, VecModel};
+
+use crate::db::repositories::{LibraryRepo, ProvenanceRepo};
+use crate::models::{CopiedData, DisplayLang};
+use crate::services::{ClipboardContent, Pasted};
+
+use super::AppState;
+use super::translations::{ui_tr, ui_tr_args};
+
+use open_editors::{focus_open_editor, register_open_editor, report_save_error, save_unless_changed, LoadedRows};
 
 pub(super) use branches::show_branches_editor;
 pub(super) use branch_categories::show_branch_categories_editor;
+pub(super) use equipment_catalog::show_equipment_catalog_editor;
 pub(super) use formation_levels::show_formation_levels_editor;
+pub(super) use formation_table::show_formation_table_editor;
+pub(super) use history::show_history_window;
+pub(super) use tags::show_tags_editor;
+
+/// Remember that `what` was copied from another library, so deleting that
+/// library can warn about it
+fn record_copy(conn: &rusqlite::Connection, target_id: i64, source_id: i64, what: CopiedData) {
+    if let Err(e) = ProvenanceRepo::new(conn).record(target_id, source_id, what) {
+        log::warn!("Failed to record copy of {} from library {}: {}", what.code(), source_id, e);
+    }
+}
+
+/// Name language the library `lib_id` prefers, as stored ("auto" if unknown)
+fn library_display_language(conn: &rusqlite::Connection, lib_id: i64) -> String {
+    LibraryRepo::new(conn)
+        .get_by_id(lib_id)
+        .ok()
+        .flatten()
+        .map_or_else(|| DisplayLang::default().code().to_string(), |l| l.display_language)
+}
+
+/// Language of the names an editor lists: the library's preference, or the
+/// UI language `lang` when it has none
+fn name_language(display_language: &str, lang: &str) -> &'static str {
+    DisplayLang::from_code(display_language).resolve(lang)
+}
+
+/// Put `content` on the application clipboard. Returns the text for the
+/// system clipboard and the status line to show.
+fn copy_to_clipboard(state: &RefCell<AppState>, lang: &str, content: ClipboardContent) -> (String, String) {
+    let text = content.to_json();
+    let status = ui_tr_args(lang, "Copied: {}", &[&content.len()]);
+    state.borrow_mut().clipboard = Some(content);
+    (text, status)
+}
+
+/// Status line after a paste
+fn paste_status<T>(lang: &str, pasted: &Pasted<T>) -> String {
+    if pasted.skipped == 0 {
+        ui_tr_args(lang, "Pasted: {}", &[&pasted.rows.len()])
+    } else {
+        ui_tr_args(lang, "Pasted: {}, skipped as duplicates: {}", &[&pasted.rows.len(), &pasted.skipped])
+    }
+}
+
+/// Status line when the clipboard holds no rows of the editor's kind
+fn nothing_to_paste(lang: &str) -> String {
+    ui_tr(lang, "The clipboard has no rows for this list.")
+}
+
+/// Write the form fields back into the selected row at `index` so they are
+/// not lost when the selection or the rows change. Nothing happens without a
+/// selected row. Returns whether the row changed.
+fn commit_row<T: Clone + PartialEq + 'static>(model: &VecModel<T>, index: i32, apply: impl FnOnce(&mut T)) -> bool {
+    let Some((i, mut row)) = usize::try_from(index).ok().and_then(|i| Some((i, model.row_data(i)?))) else {
+        return false;
+    };
+    let before = row.clone();
+    apply(&mut row);
+    if row == before {
+        return false;
+    }
+    model.set_row_data(i, row);
+    true
+}
+
+/// After a save that keeps the editor open, give each row the id of the
+/// stored row at its position: rows the save created get theirs, so the next
+/// save updates them instead of creating them again
+fn adopt_stored_ids<T: Clone + PartialEq + 'static>(model: &VecModel<T>, ids: &[i64], set_id: impl Fn(&mut T, i32)) {
+    for (i, &id) in ids.iter().enumerate().take(model.row_count()) {
+        commit_row(model, i as i32, |row| set_id(row, id as i32));
+    }
+}
+
+/// Swap the row at `index` with its neighbour above (`up`) or below.
+/// Returns the row's new index, or `None` if it cannot move.
+fn move_row<T: Clone + 'static>(model: &VecModel<T>, index: i32, up: bool) -> Option<i32> {
+    if index < 0 || index as usize >= model.row_count() {
+        return None;
+    }
+    let from = index as usize;
+    let to = if up {
+        from.checked_sub(1)?
+    } else if from + 1 < model.row_count() {
+        from + 1
+    } else {
+        return None;
+    };
+    let a = model.row_data(from)?;
+    let b = model.row_data(to)?;
+    model.set_row_data(from, b);
+    model.set_row_data(to, a);
+    Some(to as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(model: &VecModel<&'static str>) -> Vec<&'static str> {
+        model.iter().collect()
+    }
+
+    /// Form of a headless editor: the selected row and its text field
+    struct Form {
+        index: i32,
+        text: &'static str,
+    }
+
+    impl Form {
+        fn commit(&self, model: &VecModel<&'static str>) -> bool {
+            commit_row(model, self.index, |row| *row = self.text)
+        }
+
+        /// Click a row the way the editors' selection handlers do
+        fn select(&mut self, model: &VecModel<&'static str>, index: i32) {
+            self.commit(model);
+            self.index = index;
+            self.text = model.row_data(index as usize).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_edit_then_switch_selection_keeps_edit() {
+        let model = VecModel::from(vec!["a", "b", "c", "d", "e"]);
+        let mut form = Form { index: 1, text: "b" };
+        form.text = "b edited";
+        form.select(&model, 4);
+        assert_eq!(rows(&model), vec!["a", "b edited", "c", "d", "e"]);
+        assert_eq!(form.text, "e");
+
+        // Selecting again without edits changes nothing
+        form.select(&model, 1);
+        assert_eq!(form.text, "b edited");
+        assert!(!form.commit(&model));
+    }
+
+    #[test]
+    fn test_commit_without_selection_is_noop() {
+        let model = VecModel::from(vec!["a"]);
+        assert!(!commit_row(&model, -1, |row| *row = "x"));
+        assert!(!commit_row(&model, 3, |row| *row = "x"));
+        assert!(commit_row(&model, 0, |row| *row = "x"));
+        assert_eq!(rows(&model), vec!["x"]);
+    }
+
+    #[test]
+    fn test_adopt_stored_ids_by_position() {
+        let model = VecModel::from(vec![(7, "a"), (-1, "new"), (-1, "newer")]);
+        adopt_stored_ids(&model, &[7, 12], |row, id| row.0 = id);
+        assert_eq!(model.iter().collect::<Vec<_>>(), vec![(7, "a"), (12, "new"), (-1, "newer")]);
+    }
+
+    #[test]
+    fn test_move_row_up_and_down() {
+        let model = VecModel::from(vec!["a", "b", "c"]);
+        assert_eq!(move_row(&model, 2, true), Some(1));
+        assert_eq!(rows(&model), vec!["a", "c", "b"]);
+        assert_eq!(move_row(&model, 0, false), Some(1));
+        assert_eq!(rows(&model), vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_move_row_at_bounds_is_noop() {
+        let model = VecModel::from(vec!["a", "b"]);
+        assert_eq!(move_row(&model, 0, true), None);
+        assert_eq!(move_row(&model, 1, false), None);
+        assert_eq!(move_row(&model, -1, false), None);
+        assert_eq!(move_row(&model, 5, true), None);
+        assert_eq!(rows(&model), vec!["a", "b"]);
+    }
+}
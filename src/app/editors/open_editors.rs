@@ -0,0 +1,233 @@
+//! One window per library and list editor, and noticing at save time that
+//! the list was changed elsewhere since the editor loaded it
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use slint::ComponentHandle;
+
+use crate::services::{stored_fingerprint, EditorKind, EditorRows, RowsFingerprint};
+use super::super::{AppState, StaleEditorDialog};
+use super::super::dialogs::show_error_dialog;
+use super::super::translations::ui_tr;
+
+/// Windows by the library and list they edit
+struct OpenEditors<W> {
+    windows: HashMap<(i64, EditorKind), W>,
+}
+
+impl<W> OpenEditors<W> {
+    fn new() -> Self {
+        Self { windows: HashMap::new() }
+    }
+
+    fn get(&self, library_id: i64, kind: EditorKind) -> Option<&W> {
+        self.windows.get(&(library_id, kind))
+    }
+
+    /// Returns the window it replaces
+    fn insert(&mut self, library_id: i64, kind: EditorKind, window: W) -> Option<W> {
+        self.windows.insert((library_id, kind), window)
+    }
+
+    fn remove(&mut self, library_id: i64, kind: EditorKind) -> Option<W> {
+        self.windows.remove(&(library_id, kind))
+    }
+}
+
+/// Brings a window to the front; `false` once it was closed
+type Raise = Rc<dyn Fn() -> bool>;
+
+thread_local! {
+    static OPEN_EDITORS: RefCell<OpenEditors<Raise>> = RefCell::new(OpenEditors::new());
+}
+
+/// Bring the library's open editor of this kind to the front. Returns `false`
+/// when there is none, and the caller opens a new one.
+pub(super) fn focus_open_editor(library_id: i64, kind: EditorKind) -> bool {
+    let raise = OPEN_EDITORS.with(|open| open.borrow().get(library_id, kind).cloned());
+    match raise {
+        Some(raise) if raise() => true,
+        Some(_) => {
+            OPEN_EDITORS.with(|open| open.borrow_mut().remove(library_id, kind));
+            false
+        }
+        None => false,
+    }
+}
+
+/// Note a shown window as the library's editor of this kind
+pub(super) fn register_open_editor<C: ComponentHandle + 'static>(component: &C, library_id: i64, kind: EditorKind) {
+    let weak = component.as_weak();
+    let raise: Raise = Rc::new(move || {
+        let Some(component) = weak.upgrade() else {
+            return false;
+        };
+        if !component.window().is_visible() {
+            return false;
+        }
+        component.window().set_minimized(false);
+        component.show().is_ok()
+    });
+    OPEN_EDITORS.with(|open| open.borrow_mut().insert(library_id, kind, raise));
+}
+
+/// The stored list as an editor loaded it, or last saved it
+pub(super) struct LoadedRows {
+    state: Rc<RefCell<AppState>>,
+    library_id: i64,
+    kind: EditorKind,
+    fingerprint: Cell<Option<RowsFingerprint>>,
+    /// Rows the editor last wrote, `None` before its first save
+    saved: RefCell<Option<EditorRows>>,
+}
+
+impl LoadedRows {
+    /// Remember the stored list as it is now
+    pub(super) fn new(state: &Rc<RefCell<AppState>>, library_id: i64, kind: EditorKind) -> Rc<Self> {
+        let loaded = Rc::new(Self {
+            state: state.clone(),
+            library_id,
+            kind,
+            fingerprint: Cell::new(None),
+            saved: RefCell::new(None),
+        });
+        loaded.refresh();
+        loaded
+    }
+
+    /// Remember the stored list again after the editor wrote it itself
+    pub(super) fn refresh(&self) {
+        self.fingerprint.set(self.stored());
+    }
+
+    /// Remember `rows` as written by the editor's Save
+    pub(super) fn saved(&self, rows: EditorRows) {
+        self.refresh();
+        *self.saved.borrow_mut() = Some(rows);
+    }
+
+    /// Whether the editor already saved exactly `rows` and nobody changed the
+    /// list since, so closing has nothing to write
+    pub(super) fn is_saved(&self, rows: &EditorRows) -> bool {
+        self.saved.borrow().as_ref() == Some(rows) && !self.changed_elsewhere()
+    }
+
+    fn stored(&self) -> Option<RowsFingerprint> {
+        let st = self.state.borrow();
+        let db = st.database()?;
+        match stored_fingerprint(db.conn(), self.library_id, self.kind) {
+            Ok(fingerprint) => Some(fingerprint),
+            Err(e) => {
+                log::warn!("Failed to read {} of library {}: {}", self.kind.label(), self.library_id, e);
+                None
+            }
+        }
+    }
+
+    /// Whether the stored list changed since the editor loaded it
+    fn changed_elsewhere(&self) -> bool {
+        changed(self.fingerprint.get(), self.stored())
+    }
+}
+
+/// A list that could not be read counts as unchanged, so a read error never
+/// keeps the editor from saving
+fn changed(loaded: Option<RowsFingerprint>, stored: Option<RowsFingerprint>) -> bool {
+    matches!((loaded, stored), (Some(loaded), Some(stored)) if loaded != stored)
+}
+
+/// Tell the user the editor's rows were not written; the editor stays open
+/// with them so nothing is lost
+pub(super) fn report_save_error(lang: &str, kind: EditorKind, library_id: i64, error: &anyhow::Error) {
+    log::error!("Failed to save {} of library {}: {:#}", kind.label(), library_id, error);
+    show_error_dialog(
+        lang,
+        kind.label(),
+        "The changes could not be saved. The editor stays open with them.\n\n{}",
+        &[&format!("{:#}", error)],
+    );
+}
+
+/// Run `save` unless the stored list changed since the editor loaded it.
+/// Otherwise ask whether to overwrite those changes, reload the list with
+/// `reload`, or go back to the editor.
+pub(super) fn save_unless_changed(lang: &str, loaded: &LoadedRows, save: impl Fn() + 'static, reload: impl Fn() + 'static) {
+    if !loaded.changed_elsewhere() {
+        save();
+        return;
+    }
+    let dialog = match StaleEditorDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create stale editor dialog: {}", e);
+            return;
+        }
+    };
+    let message = ui_tr(
+        lang,
+        "{} of this library were changed in another window since this editor opened. Overwrite those changes with yours, or reload the list and drop your edits?",
+    )
+    .replacen("{}", &ui_tr(lang, loaded.kind.label()), 1);
+    dialog.set_tr_title(ui_tr(lang, "List changed elsewhere").into());
+    dialog.set_tr_message(message.into());
+    dialog.set_tr_overwrite(ui_tr(lang, "Overwrite").into());
+    dialog.set_tr_reload(ui_tr(lang, "Reload").into());
+    dialog.set_tr_cancel(ui_tr(lang, "Cancel").into());
+
+    let weak = dialog.as_weak();
+    dialog.on_overwrite(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+        save();
+    });
+    let weak = dialog.as_weak();
+    dialog.on_reload(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+        reload();
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancel(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+    dialog.show().unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_keys_by_library_and_editor() {
+        let mut open = OpenEditors::new();
+        assert!(open.insert(1, EditorKind::Branches, "branches of 1").is_none());
+        open.insert(1, EditorKind::BranchCategories, "categories of 1");
+        open.insert(2, EditorKind::Branches, "branches of 2");
+
+        assert_eq!(open.get(1, EditorKind::Branches), Some(&"branches of 1"));
+        assert_eq!(open.get(1, EditorKind::BranchCategories), Some(&"categories of 1"));
+        assert_eq!(open.get(2, EditorKind::Branches), Some(&"branches of 2"));
+        assert_eq!(open.get(2, EditorKind::FormationLevels), None);
+
+        // A second window for the same list takes the first one's place
+        assert_eq!(open.insert(1, EditorKind::Branches, "again"), Some("branches of 1"));
+        assert_eq!(open.remove(1, EditorKind::Branches), Some("again"));
+        assert_eq!(open.get(1, EditorKind::Branches), None);
+        assert_eq!(open.get(2, EditorKind::Branches), Some(&"branches of 2"));
+    }
+
+    #[test]
+    fn test_unreadable_list_counts_as_unchanged() {
+        let a = RowsFingerprint { row_count: 2, hash: 1 };
+        let b = RowsFingerprint { row_count: 2, hash: 2 };
+        assert!(!changed(Some(a), Some(a)));
+        assert!(changed(Some(a), Some(b)));
+        assert!(!changed(None, Some(b)));
+        assert!(!changed(Some(a), None));
+    }
+}
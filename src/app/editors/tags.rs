@@ -0,0 +1,174 @@
+//! Tags editor: the tags of one library, with the tags of all libraries to pick from
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::db::repositories::LibraryRepo;
+use crate::models::normalize_tags;
+use crate::services::{ChangeEvent, LibraryService};
+use super::super::{TagsEditor, AppState};
+use super::super::open_windows::OpenWindow;
+use super::super::translations::ui_tr;
+use super::super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+use super::super::dialogs::show_error_dialog;
+use super::super::notify_change;
+
+fn string_model(items: &[String]) -> ModelRc<SharedString> {
+    ModelRc::new(VecModel::from(items.iter().map(SharedString::from).collect::<Vec<_>>()))
+}
+
+/// Set the window texts in `lang`
+fn translate(window: &TagsEditor, lang: &str) {
+    window.set_tr_tags_title(ui_tr(lang, "Manage Tags").into());
+    window.set_tr_library_tags(ui_tr(lang, "Tags of this library").into());
+    window.set_tr_all_tags(ui_tr(lang, "Tags in all libraries").into());
+    window.set_tr_no_tags(ui_tr(lang, "The library has no tags.").into());
+    window.set_tr_new_tag(ui_tr(lang, "New tag").into());
+    window.set_tr_add(ui_tr(lang, "Add").into());
+    window.set_tr_remove(ui_tr(lang, "Remove").into());
+    window.set_tr_save(ui_tr(lang, "Save").into());
+    window.set_tr_close(ui_tr(lang, "Close").into());
+}
+
+/// Open the tags editor for the given library.
+pub(in crate::app) fn show_tags_editor(
+    state: Rc<RefCell<AppState>>,
+    lib_id: i64,
+    lang: &str,
+) {
+    let loaded = {
+        let st = state.borrow();
+        let Some(db) = st.database() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        LibraryService::new(db.conn())
+            .get_library(lib_id)
+            .and_then(|lib| Ok((lib, LibraryRepo::new(db.conn()).list_all_tags()?)))
+    };
+    let (library, all_tags) = match loaded {
+        Ok((Some(library), all_tags)) => (library, all_tags),
+        Ok((None, _)) => {
+            log::error!("Library {} not found", lib_id);
+            return;
+        }
+        Err(e) => {
+            log::error!("Failed to load tags: {}", e);
+            show_error_dialog(lang, "Error", "Failed to load tags: {}", &[&e]);
+            return;
+        }
+    };
+    let window = match TagsEditor::new() {
+        Ok(w) => w,
+        Err(e) => {
+            log::error!("Failed to create Tags editor: {}", e);
+            return;
+        }
+    };
+    let tags = Rc::new(RefCell::new(normalize_tags(&library.tags)));
+    window.set_library_name(library.name.clone().into());
+    window.set_tags(string_model(&tags.borrow()));
+    window.set_all_tags(string_model(&all_tags));
+    window.set_dirty(false);
+    translate(&window, lang);
+
+    // Adding a tag that only differs in case or spacing from an existing one is a no-op
+    let add = {
+        let weak = window.as_weak();
+        let tags = tags.clone();
+        move |tag: &str| {
+            let Some(w) = weak.upgrade() else {
+                return;
+            };
+            let mut tags = tags.borrow_mut();
+            let updated = normalize_tags(tags.iter().map(String::as_str).chain([tag]));
+            if updated != *tags {
+                *tags = updated;
+                w.set_tags(string_model(&tags));
+                w.set_dirty(true);
+            }
+        }
+    };
+
+    let weak_add = window.as_weak();
+    let add_typed = add.clone();
+    window.on_add_tag(move || {
+        let Some(w) = weak_add.upgrade() else {
+            return;
+        };
+        add_typed(&w.get_new_tag_text());
+        w.set_new_tag_text("".into());
+    });
+
+    window.on_use_tag(move |index| {
+        if let Some(tag) = usize::try_from(index).ok().and_then(|i| all_tags.get(i)) {
+            add(tag);
+        }
+    });
+
+    let weak_remove = window.as_weak();
+    let tags_remove = tags.clone();
+    window.on_remove_tag(move |index| {
+        let Some(w) = weak_remove.upgrade() else {
+            return;
+        };
+        let mut tags = tags_remove.borrow_mut();
+        let Some(i) = usize::try_from(index).ok().filter(|&i| i < tags.len()) else {
+            return;
+        };
+        tags.remove(i);
+        w.set_tags(string_model(&tags));
+        w.set_dirty(true);
+    });
+
+    let weak_save = window.as_weak();
+    let state_save = state.clone();
+    let lang_save = lang.to_string();
+    window.on_save(move || {
+        let Some(w) = weak_save.upgrade() else {
+            return;
+        };
+        let saved = {
+            let st = state_save.borrow();
+            let Some(db) = st.database() else {
+                return;
+            };
+            let service = LibraryService::new(db.conn());
+            service.get_library(lib_id).and_then(|lib| {
+                let mut lib = lib.ok_or_else(|| anyhow::anyhow!("Library {} not found", lib_id))?;
+                lib.tags = tags.borrow().clone();
+                service.save_library(lib, false)
+            })
+        };
+        match saved {
+            Ok(lib) => {
+                log::info!("Saved tags of library {}: {:?}", lib_id, lib.tags);
+                {
+                    let mut st = state_save.borrow_mut();
+                    if let Some(current) = st.current_library.as_mut().filter(|c| c.id == Some(lib_id)) {
+                        current.tags = lib.tags.clone();
+                    }
+                }
+                w.set_dirty(false);
+                notify_change(&state_save, ChangeEvent::LibraryChanged(lib_id));
+            }
+            Err(e) => {
+                log::error!("Failed to save tags: {}", e);
+                show_error_dialog(&lang_save, "Error", "Failed to save tags: {}", &[&e]);
+            }
+        }
+    });
+
+    let weak_close = window.as_weak();
+    window.on_close_editor(move || {
+        if let Some(w) = weak_close.upgrade() {
+            hide_remembering(&w, WindowKind::TagsEditor);
+        }
+    });
+
+    remember_geometry(&window, WindowKind::TagsEditor);
+    if window.show().is_ok() {
+        state.borrow_mut().open_windows.add(OpenWindow::of(&window, translate));
+    }
+}
@@ -0,0 +1,391 @@
+//! Export presets in the application: File > Export with Preset, the preset
+//! manager, and one-click exports from the library context menu
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::api::LibraryManager;
+use crate::config::Settings;
+use crate::export::presets::{
+    delimiter_value, orientation_value, parse_delimiter, parse_orientation, OPT_CSV_DELIMITER, OPT_SVG_ORIENTATION,
+};
+use crate::export::{export_with_preset, preset_name_problem, ExportPreset, PresetFormat, PresetIssue, PresetNameProblem, SvgOrientation};
+use super::{AppState, ExportPresetDialog, ExportPresetsDialog};
+use super::dialogs::show_error_dialog;
+use super::workers::run_with_manager;
+use super::file_dialog::{choose_file, FileRequest};
+use super::translations::{ui_tr, ui_tr_args};
+use super::window_geometry::{hide_remembering, remember_geometry, WindowKind};
+
+/// CSV delimiters offered in the preset manager, with their labels
+const DELIMITERS: [(char, &str); 4] = [(',', "Comma"), (';', "Semicolon"), ('\t', "Tab"), ('|', "Vertical bar")];
+
+/// SVG layouts offered in the preset manager, with their labels
+const ORIENTATIONS: [(SvgOrientation, &str); 2] = [(SvgOrientation::TopDown, "Top to bottom"), (SvgOrientation::LeftRight, "Left to right")];
+
+/// Presets from the settings, renamed options under their current keys
+pub(super) fn saved_presets() -> Vec<ExportPreset> {
+    Settings::load()
+        .unwrap_or_default()
+        .export_presets
+        .into_iter()
+        .map(ExportPreset::migrated)
+        .collect()
+}
+
+fn string_model(items: impl IntoIterator<Item = String>) -> ModelRc<SharedString> {
+    ModelRc::new(VecModel::from(items.into_iter().map(SharedString::from).collect::<Vec<_>>()))
+}
+
+fn preset_names(presets: &[ExportPreset]) -> ModelRc<SharedString> {
+    string_model(presets.iter().map(|p| p.name.clone()))
+}
+
+fn issue_text(lang: &str, issue: &PresetIssue) -> String {
+    match issue {
+        PresetIssue::UnknownOption(key) => ui_tr_args(lang, "Unknown option \"{}\" is ignored.", &[key]),
+        PresetIssue::InvalidValue { key, value } => {
+            ui_tr_args(lang, "Option \"{}\" cannot be \"{}\"; the default is used.", &[key, value])
+        }
+    }
+}
+
+fn unknown_format_text(lang: &str, preset: &ExportPreset) -> String {
+    ui_tr_args(lang, "This preset uses the format \"{}\", which cannot be exported.", &[&preset.format])
+}
+
+/// What `preset` does, one setting per line, and the problems found in it
+fn preset_summary(lang: &str, preset: &ExportPreset) -> (String, String) {
+    let resolved = match preset.resolve() {
+        Ok(resolved) => resolved,
+        Err(_) => return (String::new(), unknown_format_text(lang, preset)),
+    };
+    let mut lines = vec![ui_tr_args(lang, "Format: {}", &[&resolved.format.label()])];
+    match resolved.format {
+        PresetFormat::Csv => {
+            let label = DELIMITERS.iter().find(|(d, _)| *d == resolved.csv.delimiter).map_or("Comma", |(_, l)| l);
+            lines.push(ui_tr_args(lang, "Delimiter: {}", &[&ui_tr(lang, label)]));
+        }
+        PresetFormat::Svg => {
+            let label = ORIENTATIONS.iter().find(|(o, _)| *o == resolved.svg.orientation).map_or("Top to bottom", |(_, l)| l);
+            lines.push(ui_tr_args(lang, "Layout: {}", &[&ui_tr(lang, label)]));
+        }
+        PresetFormat::Json | PresetFormat::Yaml => {}
+    }
+    if !resolved.filters.unit_types.is_empty() {
+        lines.push(ui_tr_args(lang, "Unit types: {}", &[&resolved.filters.unit_types.join(", ")]));
+    }
+    if resolved.filters.as_of {
+        lines.push(ui_tr(lang, "Only units valid on the \"as of\" date"));
+    }
+    if resolved.anonymize.is_some() {
+        lines.push(ui_tr(lang, "Anonymize author and sources"));
+    }
+    lines.push(ui_tr_args(lang, "File name: {}", &[&preset.file_name_pattern]));
+    let issues: Vec<String> = resolved.issues.iter().map(|i| issue_text(lang, i)).collect();
+    (lines.join("\n"), issues.join("\n"))
+}
+
+/// Export library `lib_id` with `preset`, asking where to save
+pub(super) fn export_library_with_preset(state: &Rc<RefCell<AppState>>, lang: &str, lib_id: i64, lib_name: &str, preset: &ExportPreset) {
+    let resolved = match preset.resolve() {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            log::error!("{:#}", e);
+            show_error_dialog(lang, "Export Error", "{}", &[&unknown_format_text(lang, preset)]);
+            return;
+        }
+    };
+    for issue in &resolved.issues {
+        log::warn!("Export preset \"{}\": {}", preset.name, issue);
+    }
+    let format = resolved.format;
+    let as_of = state.borrow().current_as_of();
+    let (state, lang) = (state.clone(), lang.to_string());
+    choose_file(
+        FileRequest::save()
+            .filter(format.label(), &[format.extension()])
+            .file_name(preset.file_name(format, lib_name, chrono::Local::now().date_naive())),
+        move |path| {
+            let work = move |manager: &LibraryManager| {
+                manager.library(lib_id).and_then(|library| export_with_preset(&library, &resolved, as_of, &path))
+            };
+            run_with_manager(&state, "Export with Preset", work, move |result| match result {
+                Ok(files) => log::info!("Library exported with a preset to: {:?}", files),
+                Err(e) => {
+                    log::error!("Failed to export library with a preset: {:#}", e);
+                    show_error_dialog(&lang, "Export Error", "Failed to export library: {}", &[&format!("{:#}", e)]);
+                }
+            });
+        },
+    );
+}
+
+/// File > Export with Preset: pick a preset for library `lib_id` and export
+pub(super) fn show_export_preset_dialog(state: &Rc<RefCell<AppState>>, lang: &str, lib_id: i64, lib_name: &str) {
+    let dialog = match ExportPresetDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create export preset dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    let presets = Rc::new(RefCell::new(saved_presets()));
+    dialog.set_dialog_title(ui_tr(lang, "Export with Preset").into());
+    dialog.set_preset_label(ui_tr(lang, "Preset:").into());
+    dialog.set_manage_text(ui_tr(lang, "Manage Presets…").into());
+    dialog.set_ok_text(ui_tr(lang, "Export").into());
+    dialog.set_cancel_text(ui_tr(lang, "Cancel").into());
+
+    let show_preset = {
+        let (weak, presets, lang) = (dialog.as_weak(), presets.clone(), lang.to_string());
+        move |index: usize| {
+            let Some(d) = weak.upgrade() else {
+                return;
+            };
+            let presets = presets.borrow();
+            let index = index.min(presets.len().saturating_sub(1));
+            let (summary, problems) = match presets.get(index) {
+                Some(preset) => preset_summary(&lang, preset),
+                None => (ui_tr(&lang, "There are no presets; add one with Manage Presets."), String::new()),
+            };
+            d.set_presets(preset_names(&presets));
+            d.set_preset_index(index as i32);
+            d.set_summary_text(summary.into());
+            d.set_error_text(problems.into());
+        }
+    };
+    show_preset(0);
+
+    let show = Rc::new(show_preset);
+    let show_selected = show.clone();
+    dialog.on_preset_selected(move |index| show_selected(index.max(0) as usize));
+
+    let (weak, presets_manage, lang_manage) = (dialog.as_weak(), presets.clone(), lang.to_string());
+    dialog.on_manage(move || {
+        let (weak, presets, show) = (weak.clone(), presets_manage.clone(), show.clone());
+        show_export_presets_dialog(&lang_manage, move |saved| {
+            let index = weak.upgrade().map_or(0, |d| d.get_preset_index().max(0) as usize);
+            *presets.borrow_mut() = saved.to_vec();
+            show(index);
+        });
+    });
+
+    let (weak, state, lang_ok, lib_name) = (dialog.as_weak(), state.clone(), lang.to_string(), lib_name.to_string());
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let Some(preset) = presets.borrow().get(d.get_preset_index().max(0) as usize).cloned() else {
+            return;
+        };
+        d.hide().unwrap_or_default();
+        export_library_with_preset(&state, &lang_ok, lib_id, &lib_name, &preset);
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
+
+/// Show `preset` in the manager's form
+fn load_form(dialog: &ExportPresetsDialog, lang: &str, preset: Option<&ExportPreset>) {
+    let Some(preset) = preset else {
+        dialog.set_name_text("".into());
+        dialog.set_unit_types_text("".into());
+        dialog.set_file_name_text("".into());
+        dialog.set_anonymize(false);
+        dialog.set_as_of(false);
+        dialog.set_error_text("".into());
+        return;
+    };
+    let format = PresetFormat::from_key(&preset.format);
+    let format_index = format.and_then(|f| PresetFormat::ALL.iter().position(|a| *a == f)).unwrap_or(0);
+    let delimiter = preset.option(OPT_CSV_DELIMITER).and_then(parse_delimiter).unwrap_or(',');
+    let orientation = preset.option(OPT_SVG_ORIENTATION).and_then(parse_orientation).unwrap_or_default();
+    dialog.set_name_text(preset.name.clone().into());
+    dialog.set_format_index(format_index as i32);
+    dialog.set_delimiter_index(DELIMITERS.iter().position(|(d, _)| *d == delimiter).unwrap_or(0) as i32);
+    dialog.set_orientation_index(ORIENTATIONS.iter().position(|(o, _)| *o == orientation).unwrap_or(0) as i32);
+    dialog.set_unit_types_text(preset.filters.unit_types.join(", ").into());
+    dialog.set_file_name_text(preset.file_name_pattern.clone().into());
+    dialog.set_anonymize(preset.anonymize);
+    dialog.set_as_of(preset.filters.as_of);
+    let error = match format {
+        None => unknown_format_text(lang, preset),
+        Some(_) => preset_summary(lang, preset).1,
+    };
+    dialog.set_error_text(error.into());
+}
+
+/// Write the manager's form back into `preset`; options the form does not
+/// show are kept
+fn store_form(dialog: &ExportPresetsDialog, preset: &mut ExportPreset) {
+    let format = PresetFormat::ALL[(dialog.get_format_index().max(0) as usize).min(PresetFormat::ALL.len() - 1)];
+    preset.name = dialog.get_name_text().trim().to_string();
+    preset.format = format.key().to_string();
+    match format {
+        PresetFormat::Csv => {
+            let (delimiter, _) = DELIMITERS[(dialog.get_delimiter_index().max(0) as usize).min(DELIMITERS.len() - 1)];
+            preset.options.insert(OPT_CSV_DELIMITER.to_string(), delimiter_value(delimiter));
+        }
+        PresetFormat::Svg => {
+            let (orientation, _) = ORIENTATIONS[(dialog.get_orientation_index().max(0) as usize).min(ORIENTATIONS.len() - 1)];
+            preset.options.insert(OPT_SVG_ORIENTATION.to_string(), orientation_value(orientation).to_string());
+        }
+        PresetFormat::Json | PresetFormat::Yaml => {}
+    }
+    preset.filters.unit_types = dialog
+        .get_unit_types_text()
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+    preset.filters.as_of = dialog.get_as_of();
+    preset.anonymize = dialog.get_anonymize();
+    preset.file_name_pattern = dialog.get_file_name_text().trim().to_string();
+}
+
+/// Name for a new preset that no other preset has
+fn new_preset_name(lang: &str, presets: &[ExportPreset]) -> String {
+    let base = ui_tr(lang, "New preset");
+    let taken = |name: &str| presets.iter().any(|p| p.name.trim().eq_ignore_ascii_case(name));
+    (1..)
+        .map(|n| if n == 1 { base.clone() } else { format!("{} {}", base, n) })
+        .find(|name| !taken(name))
+        .unwrap_or(base)
+}
+
+/// The preset manager. Changes are kept in the dialog until Save, which
+/// writes them to the settings and passes them to `on_saved`.
+pub(super) fn show_export_presets_dialog(lang: &str, on_saved: impl Fn(&[ExportPreset]) + 'static) {
+    let dialog = match ExportPresetsDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create export presets dialog: {}", e);
+            return;
+        }
+    };
+    dialog.set_dialog_title(ui_tr(lang, "Export Presets").into());
+    dialog.set_preset_label(ui_tr(lang, "Preset:").into());
+    dialog.set_name_label(ui_tr(lang, "Name:").into());
+    dialog.set_format_label(ui_tr(lang, "Format:").into());
+    dialog.set_formats(string_model(PresetFormat::ALL.iter().map(|f| f.label().to_string())));
+    dialog.set_delimiter_label(ui_tr(lang, "Delimiter:").into());
+    dialog.set_delimiters(string_model(DELIMITERS.iter().map(|(_, label)| ui_tr(lang, label))));
+    dialog.set_orientation_label(ui_tr(lang, "Layout:").into());
+    dialog.set_orientations(string_model(ORIENTATIONS.iter().map(|(_, label)| ui_tr(lang, label))));
+    dialog.set_anonymize_text(ui_tr(lang, "Anonymize author and sources").into());
+    dialog.set_as_of_text(ui_tr(lang, "Only units valid on the \"as of\" date").into());
+    dialog.set_unit_types_label(ui_tr(lang, "Unit types:").into());
+    dialog.set_unit_types_hint(ui_tr(lang, "Comma-separated; empty exports every unit").into());
+    dialog.set_file_name_label(ui_tr(lang, "File name:").into());
+    dialog.set_file_name_hint(ui_tr(lang, "{library}, {date} and {format} are replaced").into());
+    dialog.set_add_text(ui_tr(lang, "New").into());
+    dialog.set_delete_text(ui_tr(lang, "Delete").into());
+    dialog.set_save_text(ui_tr(lang, "Save").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
+
+    let presets = Rc::new(RefCell::new(saved_presets()));
+    let current = Rc::new(Cell::new(0usize));
+    dialog.set_presets(preset_names(&presets.borrow()));
+    load_form(&dialog, lang, presets.borrow().first());
+
+    // Keeps the form's edits of the shown preset before showing another
+    let store_current = {
+        let (weak, presets, current) = (dialog.as_weak(), presets.clone(), current.clone());
+        move || {
+            if let (Some(d), Some(preset)) = (weak.upgrade(), presets.borrow_mut().get_mut(current.get())) {
+                store_form(&d, preset);
+            }
+        }
+    };
+    let show_index = {
+        let (weak, presets, current, lang) = (dialog.as_weak(), presets.clone(), current.clone(), lang.to_string());
+        move |index: usize| {
+            let Some(d) = weak.upgrade() else {
+                return;
+            };
+            let presets = presets.borrow();
+            let index = index.min(presets.len().saturating_sub(1));
+            current.set(index);
+            d.set_presets(preset_names(&presets));
+            d.set_preset_index(index as i32);
+            load_form(&d, &lang, presets.get(index));
+        }
+    };
+    let (store_current, show_index) = (Rc::new(store_current), Rc::new(show_index));
+
+    let (store, show) = (store_current.clone(), show_index.clone());
+    dialog.on_preset_selected(move |index| {
+        store();
+        show(index.max(0) as usize);
+    });
+
+    let (store, show, presets_add, lang_add) = (store_current.clone(), show_index.clone(), presets.clone(), lang.to_string());
+    dialog.on_add_preset(move || {
+        store();
+        let index = {
+            let mut presets = presets_add.borrow_mut();
+            let name = new_preset_name(&lang_add, &presets);
+            presets.push(ExportPreset::new(&name, PresetFormat::Json));
+            presets.len() - 1
+        };
+        show(index);
+    });
+
+    let (show, presets_delete, current_delete) = (show_index.clone(), presets.clone(), current.clone());
+    dialog.on_delete_preset(move || {
+        let index = current_delete.get();
+        {
+            let mut presets = presets_delete.borrow_mut();
+            if index < presets.len() {
+                presets.remove(index);
+            }
+        }
+        show(index);
+    });
+
+    let (store, weak, lang_save) = (store_current.clone(), dialog.as_weak(), lang.to_string());
+    dialog.on_save_presets(move || {
+        store();
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let presets = presets.borrow();
+        if let Some(problem) = preset_name_problem(&presets) {
+            let text = match problem {
+                PresetNameProblem::Blank => ui_tr(&lang_save, "Every preset needs a name."),
+                PresetNameProblem::Duplicate(name) => ui_tr_args(&lang_save, "Two presets are named \"{}\".", &[&name]),
+            };
+            d.set_error_text(text.into());
+            return;
+        }
+        let mut settings = Settings::load().unwrap_or_default();
+        settings.export_presets = presets.clone();
+        if let Err(e) = settings.save() {
+            log::error!("Failed to save settings: {}", e);
+            show_error_dialog(&lang_save, "Error", "Failed to save settings: {}", &[&e]);
+            return;
+        }
+        on_saved(&presets);
+        hide_remembering(&d, WindowKind::ExportPresetsDialog);
+    });
+
+    let weak = dialog.as_weak();
+    dialog.on_closed(move || {
+        if let Some(d) = weak.upgrade() {
+            hide_remembering(&d, WindowKind::ExportPresetsDialog);
+        }
+    });
+
+    remember_geometry(&dialog, WindowKind::ExportPresetsDialog);
+    dialog.show().unwrap_or_default();
+}
@@ -0,0 +1,397 @@
+//! File chooser used by every import/export action
+//!
+//! Native dialogs come from rfd. On some Linux setups (no portal, broken
+//! Wayland integration) rfd returns `None` at once instead of showing
+//! anything; after [`IMMEDIATE_FAILURE_THRESHOLD`] such returns in a session
+//! the rest of the session uses a path-entry dialog instead.
+
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use super::translations::ui_tr;
+use super::PathEntryDialog;
+
+/// A dialog returning faster than this without a path did not really show
+const IMMEDIATE_FAILURE: Duration = Duration::from_millis(150);
+
+/// Immediate failures in one session before switching to the fallback
+pub(crate) const IMMEDIATE_FAILURE_THRESHOLD: u32 = 2;
+
+/// What to ask for
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FileRequest {
+    save: bool,
+    /// Pick a folder instead of a file
+    folder: bool,
+    filters: Vec<(String, Vec<String>)>,
+    file_name: Option<String>,
+    directory: Option<PathBuf>,
+}
+
+impl FileRequest {
+    /// Pick an existing file
+    pub fn open() -> Self {
+        Self { save: false, folder: false, filters: Vec::new(), file_name: None, directory: None }
+    }
+
+    /// Choose where to write a file
+    pub fn save() -> Self {
+        Self { save: true, ..Self::open() }
+    }
+
+    /// Pick an existing folder
+    pub fn folder() -> Self {
+        Self { folder: true, ..Self::open() }
+    }
+
+    pub fn filter(mut self, name: &str, extensions: &[&str]) -> Self {
+        self.filters
+            .push((name.to_string(), extensions.iter().map(|e| e.to_string()).collect()));
+        self
+    }
+
+    pub fn file_name(mut self, name: impl Into<String>) -> Self {
+        self.file_name = Some(name.into());
+        self
+    }
+
+    /// Folder the dialog starts in
+    pub fn directory(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.directory = Some(dir.into());
+        self
+    }
+
+    /// Folder the dialog starts in; saving defaults to the export folder
+    /// from the settings
+    fn start_dir(&self) -> Option<PathBuf> {
+        self.directory
+            .clone()
+            .or_else(|| self.save.then(|| crate::config::Settings::load().unwrap_or_default().export_dir()))
+    }
+
+    /// Extension appended to typed save paths that have none
+    fn default_extension(&self) -> Option<&str> {
+        self.filters.first().and_then(|(_, exts)| exts.first()).map(String::as_str)
+    }
+}
+
+/// Counts native dialogs that came back empty too fast to have been shown
+#[derive(Debug, Default)]
+pub(crate) struct FailureTracker {
+    immediate_failures: u32,
+    cause_logged: bool,
+}
+
+impl FailureTracker {
+    /// Record one native dialog result
+    pub fn record(&mut self, elapsed: Duration, chose_path: bool) {
+        if !chose_path && elapsed < IMMEDIATE_FAILURE {
+            self.immediate_failures += 1;
+        }
+    }
+
+    /// Whether the native dialog should be skipped from now on
+    pub fn use_fallback(&self) -> bool {
+        self.immediate_failures >= IMMEDIATE_FAILURE_THRESHOLD
+    }
+
+    /// True exactly once, when the fallback is first taken
+    fn take_log_once(&mut self) -> bool {
+        self.use_fallback() && !std::mem::replace(&mut self.cause_logged, true)
+    }
+}
+
+thread_local! {
+    static TRACKER: RefCell<FailureTracker> = RefCell::new(FailureTracker::default());
+}
+
+/// Why a typed path was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PathEntryError {
+    Empty,
+    MissingFolder(PathBuf),
+    MissingFile(PathBuf),
+    IsFolder(PathBuf),
+}
+
+impl PathEntryError {
+    pub fn message(&self, lang: &str) -> String {
+        let with_path = |key: &str, path: &Path| ui_tr(lang, key).replacen("{}", &path.display().to_string(), 1);
+        match self {
+            PathEntryError::Empty => ui_tr(lang, "Enter a file path."),
+            PathEntryError::MissingFolder(p) => with_path("Folder does not exist: {}", p),
+            PathEntryError::MissingFile(p) => with_path("File does not exist: {}", p),
+            PathEntryError::IsFolder(p) => with_path("This is a folder, not a file: {}", p),
+        }
+    }
+}
+
+/// Resolve a typed path: `~` and relative paths are taken from `home`. Files to
+/// open must exist; files to save need an existing folder and get
+/// `default_extension` when typed without one.
+pub(crate) fn resolve_entered_path(
+    text: &str,
+    home: &Path,
+    save: bool,
+    default_extension: Option<&str>,
+) -> Result<PathBuf, PathEntryError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(PathEntryError::Empty);
+    }
+    let mut path = expand_home(text, home);
+    if path.is_dir() {
+        return Err(PathEntryError::IsFolder(path));
+    }
+    if save {
+        if path.extension().is_none() {
+            if let Some(ext) = default_extension {
+                path.set_extension(ext);
+            }
+        }
+        match path.parent() {
+            Some(parent) if parent.is_dir() => Ok(path),
+            Some(parent) => Err(PathEntryError::MissingFolder(parent.to_path_buf())),
+            None => Err(PathEntryError::MissingFolder(path)),
+        }
+    } else if path.is_file() {
+        Ok(path)
+    } else {
+        Err(PathEntryError::MissingFile(path))
+    }
+}
+
+/// `~` and relative paths are taken from `home`
+fn expand_home(text: &str, home: &Path) -> PathBuf {
+    match text.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            home.join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => home.join(text),
+    }
+}
+
+/// Resolve a typed folder like [`resolve_entered_path`]; it must exist
+pub(crate) fn resolve_entered_folder(text: &str, home: &Path) -> Result<PathBuf, PathEntryError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(PathEntryError::Empty);
+    }
+    let path = expand_home(text, home);
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(PathEntryError::MissingFolder(path))
+    }
+}
+
+fn home_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|d| d.home_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Ask for a file; `on_chosen` runs only if the user picks one.
+/// Uses the native dialog unless it has been failing this session.
+pub(crate) fn choose_file(request: FileRequest, on_chosen: impl FnOnce(PathBuf) + 'static) {
+    if !TRACKER.with(|t| t.borrow().use_fallback()) {
+        let started = Instant::now();
+        let chosen = native_dialog(&request);
+        let use_fallback = TRACKER.with(|t| {
+            let mut tracker = t.borrow_mut();
+            tracker.record(started.elapsed(), chosen.is_some());
+            if tracker.take_log_once() {
+                log::warn!(
+                    "Native file dialog returned immediately {} times (DISPLAY={:?}, WAYLAND_DISPLAY={:?}, \
+                     XDG_CURRENT_DESKTOP={:?}); using path entry for the rest of the session",
+                    tracker.immediate_failures,
+                    std::env::var("DISPLAY").ok(),
+                    std::env::var("WAYLAND_DISPLAY").ok(),
+                    std::env::var("XDG_CURRENT_DESKTOP").ok(),
+                );
+            }
+            tracker.use_fallback()
+        });
+        match chosen {
+            Some(path) => return on_chosen(path),
+            None if !use_fallback => return,
+            None => {}
+        }
+    }
+    show_path_entry_dialog(request, on_chosen);
+}
+
+fn native_dialog(request: &FileRequest) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new();
+    for (name, extensions) in &request.filters {
+        dialog = dialog.add_filter(name, extensions);
+    }
+    if let Some(ref name) = request.file_name {
+        dialog = dialog.set_file_name(name);
+    }
+    if let Some(dir) = request.start_dir() {
+        dialog = dialog.set_directory(dir);
+    }
+    if request.folder {
+        dialog.pick_folder()
+    } else if request.save {
+        dialog.save_file()
+    } else {
+        dialog.pick_file()
+    }
+}
+
+/// Path entry in the saved UI language, with the recent paths from the settings
+fn show_path_entry_dialog(request: FileRequest, on_chosen: impl FnOnce(PathBuf) + 'static) {
+    let dialog = match PathEntryDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create path entry dialog: {}", e);
+            return;
+        }
+    };
+    let settings = crate::config::Settings::load().unwrap_or_default();
+    let lang = settings.language.clone();
+    let recent: Vec<SharedString> = settings
+        .recent_paths
+        .iter()
+        .map(|p| p.display().to_string().into())
+        .collect();
+    let home = home_dir();
+    let start_dir = request.start_dir().unwrap_or_else(|| home.clone());
+    let initial = if request.folder {
+        start_dir.display().to_string()
+    } else {
+        request
+            .file_name
+            .as_ref()
+            .filter(|_| request.save)
+            .map(|name| start_dir.join(name).display().to_string())
+            .unwrap_or_default()
+    };
+    let title = if request.folder {
+        "Choose Folder"
+    } else if request.save {
+        "Save File"
+    } else {
+        "Open File"
+    };
+    dialog.set_dialog_title(ui_tr(&lang, title).into());
+    dialog.set_path_label(ui_tr(&lang, "Path:").into());
+    dialog.set_recent_label(ui_tr(&lang, "Recent:").into());
+    dialog.set_ok_text(ui_tr(&lang, "OK").into());
+    dialog.set_cancel_text(ui_tr(&lang, "Cancel").into());
+    dialog.set_path_text(initial.into());
+    dialog.set_recent_paths(ModelRc::new(VecModel::from(recent)));
+
+    let on_chosen = Rc::new(RefCell::new(Some(on_chosen)));
+    let weak = dialog.as_weak();
+    dialog.on_accepted(move || {
+        let Some(d) = weak.upgrade() else {
+            return;
+        };
+        let resolved = if request.folder {
+            resolve_entered_folder(&d.get_path_text(), &home)
+        } else {
+            resolve_entered_path(&d.get_path_text(), &home, request.save, request.default_extension())
+        };
+        match resolved {
+            Ok(path) => {
+                let mut settings = crate::config::Settings::load().unwrap_or_default();
+                settings.record_recent_path(&path);
+                if let Err(e) = settings.save() {
+                    log::error!("Failed to save settings: {}", e);
+                }
+                d.hide().unwrap_or_default();
+                if let Some(callback) = on_chosen.borrow_mut().take() {
+                    callback(path);
+                }
+            }
+            Err(e) => d.set_error_text(e.message(&lang).into()),
+        }
+    });
+    let weak = dialog.as_weak();
+    dialog.on_cancelled(move || {
+        if let Some(d) = weak.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+    dialog.show().unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_tracker_switches_after_threshold() {
+        let mut tracker = FailureTracker::default();
+        // A user taking their time to cancel is not a failure
+        tracker.record(Duration::from_secs(3), false);
+        tracker.record(Duration::from_millis(5), true);
+        assert!(!tracker.use_fallback());
+        for _ in 0..IMMEDIATE_FAILURE_THRESHOLD {
+            tracker.record(Duration::from_millis(5), false);
+        }
+        assert!(tracker.use_fallback());
+        assert!(tracker.take_log_once());
+        assert!(!tracker.take_log_once());
+    }
+
+    #[test]
+    fn test_relative_and_home_paths_resolve_against_home() {
+        let home = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(home.path().join("exports")).unwrap();
+        let resolved = resolve_entered_path("exports/army", home.path(), true, Some("json")).unwrap();
+        assert_eq!(resolved, home.path().join("exports").join("army.json"));
+        let resolved = resolve_entered_path(" ~/exports/army.yaml ", home.path(), true, Some("json")).unwrap();
+        assert_eq!(resolved, home.path().join("exports").join("army.yaml"));
+        // Absolute paths are kept as typed
+        let absolute = home.path().join("army.json");
+        let typed = absolute.display().to_string();
+        assert_eq!(resolve_entered_path(&typed, Path::new("/elsewhere"), true, None).unwrap(), absolute);
+    }
+
+    #[test]
+    fn test_save_needs_existing_folder() {
+        let home = tempfile::TempDir::new().unwrap();
+        let err = resolve_entered_path("missing/army.json", home.path(), true, None).unwrap_err();
+        assert_eq!(err, PathEntryError::MissingFolder(home.path().join("missing")));
+        assert_eq!(
+            err.message("en"),
+            format!("Folder does not exist: {}", home.path().join("missing").display())
+        );
+        assert_eq!(resolve_entered_path("  ", home.path(), true, None), Err(PathEntryError::Empty));
+        assert_eq!(
+            resolve_entered_path("~", home.path(), true, None),
+            Err(PathEntryError::IsFolder(home.path().to_path_buf()))
+        );
+    }
+
+    #[test]
+    fn test_folder_must_exist() {
+        let home = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(home.path().join("exports")).unwrap();
+        assert_eq!(resolve_entered_folder(" ~/exports ", home.path()).unwrap(), home.path().join("exports"));
+        assert_eq!(resolve_entered_folder("~", home.path()).unwrap(), home.path());
+        assert_eq!(
+            resolve_entered_folder("missing", home.path()),
+            Err(PathEntryError::MissingFolder(home.path().join("missing")))
+        );
+        assert_eq!(resolve_entered_folder("", home.path()), Err(PathEntryError::Empty));
+    }
+
+    #[test]
+    fn test_open_needs_existing_file() {
+        let home = tempfile::TempDir::new().unwrap();
+        let err = resolve_entered_path("army.json", home.path(), false, Some("json")).unwrap_err();
+        assert_eq!(err, PathEntryError::MissingFile(home.path().join("army.json")));
+        std::fs::write(home.path().join("army.json"), "{}").unwrap();
+        assert_eq!(
+            resolve_entered_path("army.json", home.path(), false, Some("json")).unwrap(),
+            home.path().join("army.json")
+        );
+    }
+}
@@ -3,75 +3,209 @@
 mod translations;
 mod dialogs;
 mod editors;
+mod confirm;
+mod file_dialog;
+mod recovery;
+mod print;
+mod settings;
+mod window_geometry;
+mod export_presets;
+mod shortcuts;
+mod accessibility;
+mod open_windows;
+mod tabs;
+mod workers;
 
 slint::include_modules!();
 
 use anyhow::Result;
 use slint::{ComponentHandle, Model, ModelRc, VecModel, Weak, SharedString};
-use crate::i18n::Language;
-use crate::models::{Library, validate_library};
-use crate::services::LibraryService;
+use crate::i18n::{language_info, supported_code};
+use crate::models::{format_date, DisplayLang, parse_date, Library, normalize_tags, number_units, unit_prefixes, NumberingOptions, NumberingStyle, tag_states, validate_library, TreeLimitIssue, TreeLimits, Unit};
+use crate::services::{compare_formation, DirtyState, find_library_duplicates, find_unit, unit_path, ChangeBus, ChangeEvent, ClipboardContent, ComparedRow, DuplicateOptions, Journal, LevelMappingService, LibraryService, RowChanges, RowMark, OperationRegistry, RefreshTarget, Subscription, UnitService};
 use crate::export;
-use crate::db::Database;
+use crate::import::LibraryImport;
+use crate::view::zoom;
+use crate::api::{ExportFormat, LibraryManager};
+use crate::db::{Database, DbError, OpenOutcome};
+use crate::db::repositories::{IgnoredDuplicateRepo, LibraryRepo, TreeRow, UnitRepo};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::cell::RefCell;
-
-use translations::{ui_tr, apply_ui_translations};
-use dialogs::{show_library_dialog, show_library_dialog_for_edit, show_error_dialog};
-use editors::{show_branches_editor, show_branch_categories_editor, show_formation_levels_editor};
+use std::cell::{Cell, RefCell};
+
+use translations::{ui_tr, ui_tr_plural, apply_ui_translations};
+use confirm::{confirm, ConfirmSpec};
+use file_dialog::{choose_file, FileRequest};
+use recovery::show_recovery_dialog;
+use export_presets::{export_library_with_preset, saved_presets, show_export_preset_dialog, show_export_presets_dialog};
+use settings::apply_settings;
+use accessibility::Announcements;
+use open_windows::OpenWindows;
+use tabs::{tab_title, TabManager};
+use workers::{run_in_background, run_with_manager};
+use shortcuts::{cycle_tab, map_shortcut, AppAction, OpenDialogs};
+use dialogs::{show_library_dialog, show_library_dialog_for_edit, show_error_dialog, show_metrics_dialog, show_summary_table_dialog, show_import_preview_dialog, show_settings_dialog, show_data_paths_dialog, reset_settings, import_library_with_confirmation, create_library_with_confirmation, complete_library_import, show_compare_versions_dialog,
+    show_running_operations_dialog, show_unsaved_changes_dialog, show_snapshot_dialog, show_tags_dialog, show_unit_properties_dialog, show_version_picker_dialog, show_level_mapping_dialog, show_duplicate_units_dialog, DuplicateAction, show_diagram_export_dialog, show_export_options_dialog, show_cloud_sync_dialog, show_find_dialog, show_open_library_dialog, tree_limit_text};
+use editors::{show_branches_editor, show_branch_categories_editor, show_formation_levels_editor, show_formation_table_editor, show_history_window, show_tags_editor, show_equipment_catalog_editor};
+
+/// Deepest indentation in the formations sidebar, in levels; deeper units
+/// line up with that level so their names keep some room
+const SIDEBAR_INDENT_LEVELS: i32 = 8;
+
+/// Pause in typing before the libraries sidebar filter is applied
+const LIBRARY_SEARCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(300);
 
 /// Application state shared between callbacks
 pub(crate) struct AppState {
-    pub(crate) database: Option<Database>,
+    /// Libraries of the open database; `None` while no database is open
+    pub(crate) library_manager: Option<LibraryManager>,
     pub(crate) current_library: Option<Library>,
+    /// Change notifications for views and caches
+    pub(crate) events: Rc<ChangeBus>,
+    /// File chosen with Save Library As, per library id; plain Save also writes there
+    pub(crate) export_paths: HashMap<i64, PathBuf>,
+    /// Background operations that must finish (or be cancelled) before exit
+    pub(crate) operations: OperationRegistry,
+    /// Libraries sidebar filter; empty shows every library
+    pub(crate) library_filter: String,
+    /// Crash recovery journal of this session; `None` without a data directory
+    pub(crate) journal: Option<Journal>,
+    /// "As of" date per library id; views and exports show only units valid then
+    pub(crate) as_of: HashMap<i64, chrono::NaiveDate>,
+    /// Depth and width limits of unit trees, from the settings
+    pub(crate) tree_limits: TreeLimits,
+    /// Formations sidebar groups listed in full after "show all", by library
+    /// id and parent (`None`: the top level)
+    pub(crate) expanded_groups: HashSet<(i64, Option<i64>)>,
+    /// Rows copied with "Copy all" in an editor window, for "Paste" in another
+    pub(crate) clipboard: Option<ClipboardContent>,
+    /// Dialogs shown over the main window; keyboard shortcuts wait for them
+    pub(crate) open_dialogs: OpenDialogs,
+    /// Status bar messages, read out by assistive technology
+    pub(crate) announcements: Announcements,
+    /// Editor windows that follow theme and language switches
+    pub(crate) open_windows: OpenWindows,
+    /// Changes to the current library not yet saved as a version; a `Cell`
+    /// so change notifications can count them while the state is borrowed
+    pub(crate) dirty: Cell<DirtyState>,
+}
+
+impl AppState {
+    /// The open database, if any
+    pub(crate) fn database(&self) -> Option<&Database> {
+        self.library_manager.as_ref().map(LibraryManager::database)
+    }
+
+    /// Work on `database` from now on; `None` closes the current one
+    pub(crate) fn set_database(&mut self, database: Option<Database>) {
+        self.library_manager = database.map(|db| LibraryManager::new(db).with_tree_limits(self.tree_limits));
+    }
+
+    /// Apply new unit tree limits to the state and its library manager
+    pub(crate) fn set_tree_limits(&mut self, limits: TreeLimits) {
+        self.tree_limits = limits;
+        if let Some(manager) = self.library_manager.as_mut() {
+            manager.set_tree_limits(limits);
+        }
+    }
+
+    /// "As of" date of the current library, if one is set
+    pub(crate) fn current_as_of(&self) -> Option<chrono::NaiveDate> {
+        let id = self.current_library.as_ref()?.id?;
+        self.as_of.get(&id).copied()
+    }
+
+    /// Formation numbering of the current library; off while none is open
+    pub(crate) fn numbering(&self) -> NumberingOptions {
+        self.current_library.as_ref().map(|l| l.numbering.clone()).unwrap_or_default()
+    }
+
+    /// Change the current library's numbering and store it with the library
+    pub(crate) fn update_numbering(&mut self, change: impl FnOnce(&mut NumberingOptions)) {
+        let Some(library) = self.current_library.as_mut() else {
+            return;
+        };
+        change(&mut library.numbering);
+        let (Some(id), Some(manager)) = (library.id, self.library_manager.as_ref()) else {
+            return;
+        };
+        if let Err(e) = LibraryRepo::new(manager.database().conn()).update_numbering(id, &library.numbering) {
+            log::error!("Failed to store the numbering of library {}: {}", id, e);
+        }
+    }
 }
 
 /// Main application window structure
 pub struct AppMainWindow {
     window: MainWindow,
-    #[allow(dead_code)]
     state: Rc<RefCell<AppState>>,
+    _refresh_subscription: Subscription,
+    _autosave_timer: slint::Timer,
 }
 
 impl AppMainWindow {
     /// Create new main window
     pub fn new() -> Result<Self> {
         let window = MainWindow::new()?;
+        window_geometry::set_main_window(&window);
         let settings = crate::config::Settings::load().unwrap_or_default();
 
-        // Load language from settings
-        let lang = Language::from_code(&settings.language);
-
-        // Try to set initial translation
-        let lang_code = lang.code();
-        if let Err(e) = slint::select_bundled_translation(lang_code) {
-            log::warn!("Could not set initial translation: {}", e);
-        }
-
         // Initialize database
-        let db_path = settings.database_path.clone()
-            .unwrap_or_else(|| crate::config::Settings::default_database_path().unwrap_or_default());
-        let database = match crate::db::Database::open(&db_path) {
-            Ok(db) => {
+        let db_path = settings.resolved_database_path().unwrap_or_default();
+        let (database, open_problem) = match crate::db::Database::open_or_recover(&db_path) {
+            Ok((db, outcome)) => {
                 log::info!("Database opened: {:?}", db_path);
-                Some(db)
+                (Some(db), Some(outcome).filter(|o| *o != OpenOutcome::Opened).map(Ok))
+            }
+            Err(e) => {
+                log::warn!("Failed to open database: {:#}", e);
+                (None, Some(Err(e)))
+            }
+        };
+
+        print::cleanup_old_print_files();
+
+        // Journals still on disk were left by a session that did not exit cleanly
+        let (journal, leftover_journals) = match crate::config::Settings::data_dir() {
+            Ok(dir) => {
+                let dir = dir.join("recovery");
+                let leftovers = crate::services::leftover_journals(&dir).unwrap_or_else(|e| {
+                    log::warn!("Failed to look for recovery journals: {}", e);
+                    Vec::new()
+                });
+                (Some(Journal::for_session(&dir)), leftovers)
             }
             Err(e) => {
-                log::warn!("Failed to open database: {}", e);
-                None
+                log::warn!("Crash recovery is off: {}", e);
+                (None, Vec::new())
             }
         };
 
         let state = Rc::new(RefCell::new(AppState {
-            database,
+            library_manager: database.map(|db| LibraryManager::new(db).with_tree_limits(settings.tree_limits)),
             current_library: None,
+            events: Rc::new(ChangeBus::new()),
+            export_paths: HashMap::new(),
+            operations: OperationRegistry::new(),
+            library_filter: String::new(),
+            journal,
+            as_of: HashMap::new(),
+            tree_limits: settings.tree_limits,
+            expanded_groups: HashSet::new(),
+            clipboard: None,
+            open_dialogs: OpenDialogs::default(),
+            announcements: Announcements::default(),
+            open_windows: OpenWindows::default(),
+            dirty: Cell::new(DirtyState::default()),
         }));
 
-        // Set initial theme from settings
-        let theme = if settings.color_scheme == "dark" { "dark" } else { "light" };
-        window.set_theme(theme.into());
-        AppTheme::get(&window).set_mode(theme.into());
-        log::info!("Initial theme set to: {}", theme);
+        // Slow-operation warnings in the status bar
+        let weak_status = window.as_weak();
+        crate::services::metrics::set_slow_listener(move |slow| {
+            let message = slow.message();
+            let _ = weak_status.upgrade_in_event_loop(move |w| w.invoke_announce(message.into()));
+        });
 
         // Set up UI callbacks
         log::info!("Setting up callbacks...");
@@ -80,28 +214,72 @@ impl AppMainWindow {
 
         // Initialize toolbar
         init_toolbar(&window)?;
+        window.set_languages(translations::ui_languages());
 
-        // Set window title
-        let version = env!("CARGO_PKG_VERSION");
-        window.set_window_title(format!("TOEditor v{}", version).into());
+        // Language, theme and the rest of the settings; also used when they are reset
+        apply_settings(&window, &state, &settings);
+        let lang_code = supported_code(&settings.language);
 
-        // Set initial language property and UI strings from Rust
-        window.set_current_language(lang_code.into());
-        apply_ui_translations(&window, lang_code);
-        log::info!("Initial language set to: {}", lang_code);
+        // Refresh views selectively when data changes
+        let refresh_subscription = subscribe_view_refresh(&window, state.clone());
 
         // Load libraries into UI
         refresh_libraries_list(&window, state.clone());
 
+        if !leftover_journals.is_empty() {
+            show_recovery_dialog(state.clone(), lang_code, leftover_journals);
+        }
+
+        match open_problem {
+            Some(Ok(OpenOutcome::Recovered { set_aside, error })) => show_error_dialog(
+                lang_code,
+                "Database damaged",
+                "The database file could not be read ({}). It was set aside as {} and a new, empty database was created. Your data is still in the set-aside file.",
+                &[&error, &set_aside.display()],
+            ),
+            Some(Err(e)) => match e.downcast_ref::<DbError>() {
+                Some(DbError::SchemaTooNew { found, supported }) => show_error_dialog(
+                    lang_code,
+                    "Database not opened",
+                    "The database {} was saved by a newer version of TOEditor (schema {}; this version supports up to {}). Please upgrade TOEditor to open it.",
+                    &[&db_path.display(), found, supported],
+                ),
+                _ => show_error_dialog(
+                    lang_code,
+                    "Database not opened",
+                    "The database could not be opened: {}\n\n{}",
+                    &[&db_path.display(), &format!("{:#}", e)],
+                ),
+            },
+            _ => {}
+        }
+
+        let sync_provider = crate::db::storage::cloud_sync_provider(&db_path, &crate::db::storage::known_sync_roots());
+        if state.borrow().database().is_some() && crate::db::storage::should_warn(sync_provider, &db_path, &settings) {
+            show_cloud_sync_dialog(state.clone(), lang_code, db_path.clone(), sync_provider.unwrap_or_default());
+        }
+
+        let autosave_timer = start_autosave(&state, &settings);
+
         Ok(Self {
             window,
             state,
+            _refresh_subscription: refresh_subscription,
+            _autosave_timer: autosave_timer,
         })
     }
 
     /// Run the application
     pub fn run(self) -> Result<(), slint::PlatformError> {
-        self.window.run()
+        self.window.run()?;
+        back_up_in_safe_mode(&self.state.borrow());
+        // Clean exit: editor edits were saved or deliberately dropped
+        if let Some(ref journal) = self.state.borrow().journal {
+            if let Err(e) = journal.remove() {
+                log::warn!("Failed to remove recovery journal: {}", e);
+            }
+        }
+        Ok(())
     }
 
     /// Get weak reference to window
@@ -126,99 +304,94 @@ fn setup_callbacks_with_state(
     // IMPORTANT: This must be registered BEFORE the window is shown
     window.on_switch_language({
         let weak = weak_window.clone();
+        let state = state.clone();
         move |lang_code: SharedString| {
             log::debug!("Language switch to: {}", lang_code);
 
             if let Some(window) = weak.upgrade() {
-                let lang = Language::from_code(lang_code.as_ref());
+                let code = supported_code(&lang_code);
 
                 // Update settings
                 let mut settings = crate::config::Settings::load().unwrap_or_default();
-                settings.language = lang.code().to_string();
+                settings.language = code.to_string();
                 if let Err(e) = settings.save() {
                     log::error!("Failed to save settings: {}", e);
                 }
 
                 // Update the language property
-                window.set_current_language(lang_code.clone());
+                window.set_current_language(code.into());
 
                 // Try to switch bundled translation
-                if let Err(e) = slint::select_bundled_translation(lang_code.as_ref()) {
+                if let Err(e) = slint::select_bundled_translation(code) {
                     log::warn!("Translation API: {}", e);
                 }
 
                 // Update title and all UI strings
                 let version = env!("CARGO_PKG_VERSION");
-                let new_title = format!("TOEditor v{} [{}]", version, lang.name());
+                let name = language_info(code).map_or(code, |l| l.name);
+                let new_title = format!("TOEditor v{} [{}]", version, name);
                 window.set_window_title(new_title.into());
-                apply_ui_translations(&window, lang_code.as_ref());
+                apply_ui_translations(&window, code);
+                state.borrow_mut().open_windows.set_language(code);
                 window.window().request_redraw();
             }
         }
     });
 
-    // Per-language callbacks delegate to the main switch_language callback
-    window.on_switch_to_english({
-        let weak = weak_window.clone();
-        move || {
-            if let Some(window) = weak.upgrade() {
-                window.invoke_switch_language("en".into());
-            }
-        }
-    });
-
-    window.on_switch_to_russian({
-        let weak = weak_window.clone();
-        move || {
-            if let Some(window) = weak.upgrade() {
-                window.invoke_switch_language("ru".into());
-            }
-        }
-    });
-
     // File menu actions
     window.on_file_exit({
         let weak = weak_window.clone();
+        let state = state.clone();
         move || {
             if let Some(window) = weak.upgrade() {
                 log::debug!("File > Exit called");
-                let _ = window.hide();
+                if request_exit(&window, &state) {
+                    let _ = window.hide();
+                }
             }
         }
     });
+    window.window().on_close_requested({
+        let weak = weak_window.clone();
+        let state = state.clone();
+        move || match weak.upgrade() {
+            Some(window) if !request_exit(&window, &state) => slint::CloseRequestResponse::KeepWindowShown,
+            _ => slint::CloseRequestResponse::HideWindow,
+        }
+    });
 
     // Library management handlers
     let weak_window = window.as_weak();
+    let state_new = state.clone();
     window.on_file_new_library(move || {
         log::debug!("File > New Library");
         if let Some(window) = weak_window.upgrade() {
-            show_library_dialog(&window, "new", -1);
+            show_library_dialog(&window, "new", -1, &state_new);
         }
     });
 
     // Library dialog handlers
     let state_clone = state.clone();
     let weak_window = window.as_weak();
-    window.on_library_dialog_accepted(move |name: SharedString, country: SharedString, era: SharedString, author: SharedString, tags: SharedString, library_id: i32| {
-        log::debug!("Library dialog accepted: name={}, country={}, era={}, author={}, tags={}, id={}",
-                  name, country, era, author, tags, library_id);
+    window.on_library_dialog_accepted(move |name: SharedString, country: SharedString, era: SharedString, author: SharedString, tags: SharedString, display_language: SharedString, library_id: i32| {
+        log::debug!("Library dialog accepted: name={}, country={}, era={}, author={}, tags={}, display_language={}, id={}",
+                  name, country, era, author, tags, display_language, library_id);
+
+        let lang = weak_window.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
 
         // Validate input
         let validation_errors = validate_library(name.as_str(), country.as_str(), era.as_str());
         if !validation_errors.is_empty() {
             let msg = validation_errors.iter()
-                .map(|e| e.to_string())
+                .map(|e| ui_tr(&lang, &e.message))
                 .collect::<Vec<_>>()
                 .join("\n");
-            show_error_dialog("Validation Error", &msg);
+            show_error_dialog(&lang, "Validation Error", "{}", &[&msg]);
             return;
         }
 
         // Parse tags
-        let tags_vec: Vec<String> = tags.split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
+        let tags_vec = normalize_tags(tags.split(','));
 
         let lib_to_update = {
             let state = state_clone.borrow();
@@ -227,7 +400,7 @@ fn setup_callbacks_with_state(
 
         {
             let state = state_clone.borrow();
-            if let Some(ref db) = state.database {
+            if let Some(db) = state.database() {
                 let service = LibraryService::new(db.conn());
 
                 if library_id == -1 {
@@ -236,37 +409,28 @@ fn setup_callbacks_with_state(
                         id: None,
                         name: name.to_string(),
                         country: country.to_string(),
+                        country_code: crate::models::resolve_country_code(&country).map(str::to_string),
                         era: era.to_string(),
                         author: author.to_string(),
                         version: 1,
                         tags: tags_vec,
                         units: Vec::new(),
+                        display_language: display_language.to_string(),
+                        numbering: NumberingOptions::default(),
                     };
-                    match service.create_library(library) {
-                        Ok(lib) => {
-                            log::info!("Library created: {} (ID: {:?})", lib.name, lib.id);
-                            drop(state);
-                            let lib_id = lib.id.map(|x| x as i32).unwrap_or(-1);
-                            state_clone.borrow_mut().current_library = Some(lib.clone());
-                            if let Some(window) = weak_window.upgrade() {
-                                window.set_current_library_name(lib.name.clone().into());
-                                window.set_current_library_id(lib_id);
-                                refresh_libraries_list(&window, state_clone.clone());
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("Failed to create library: {}", e);
-                            show_error_dialog("Error", &format!("Failed to create library: {}", e));
-                        }
-                    }
+                    drop(state);
+                    create_library_with_confirmation(&lang, library, false, state_clone.clone(), weak_window.clone());
                 } else {
                     // Update existing library
                     if let Some(mut lib) = lib_to_update {
+                        let renamed = lib.name != name.as_str();
                         lib.name = name.to_string();
                         lib.country = country.to_string();
+                        lib.country_code = crate::models::resolve_country_code(&country).map(str::to_string);
                         lib.era = era.to_string();
                         lib.author = author.to_string();
                         lib.tags = tags_vec.clone();
+                        lib.display_language = display_language.to_string();
                         match service.save_library(lib.clone(), false) {
                             Ok(_) => {
                                 log::info!("Library updated successfully");
@@ -276,12 +440,19 @@ fn setup_callbacks_with_state(
                                 if let Some(window) = weak_window.upgrade() {
                                     window.set_current_library_name(name.clone());
                                     window.set_current_library_id(lib_id);
-                                    refresh_libraries_list(&window, state_clone.clone());
+                                    let mut settings = crate::config::Settings::load().unwrap_or_default();
+                                    if renamed && settings.rename_recent_library(lib_id as i64, &name) {
+                                        if let Err(e) = settings.save() {
+                                            log::error!("Failed to save settings: {}", e);
+                                        }
+                                        set_recent_libraries(&window, &settings);
+                                    }
                                 }
+                                notify_change(&state_clone, ChangeEvent::LibraryChanged(lib_id as i64));
                             }
                             Err(e) => {
                                 log::error!("Failed to update library: {}", e);
-                                show_error_dialog("Error", &format!("Failed to update library: {}", e));
+                                show_error_dialog(&lang, "Error", "Failed to update library: {}", &[&e]);
                             }
                         }
                     }
@@ -300,11 +471,29 @@ fn setup_callbacks_with_state(
     let weak_window = window.as_weak();
     window.on_file_open_library(move || {
         log::debug!("File > Open Library");
-        // Refresh libraries list
-        if let Some(window) = weak_window.upgrade() {
-            refresh_libraries_list(&window, state_clone.clone());
+        let Some(window) = weak_window.upgrade() else {
+            return;
+        };
+        if state_clone.borrow().database().is_none() {
+            log::error!("Database not initialized");
+            return;
         }
-        // TODO: Show dialog to select library
+        let lang = window.get_current_language().to_string();
+        let (weak_open, weak_file) = (weak_window.clone(), weak_window.clone());
+        show_open_library_dialog(
+            &lang,
+            state_clone.clone(),
+            move |library_id| {
+                if let Some(w) = weak_open.upgrade() {
+                    w.invoke_library_selected(library_id as i32);
+                }
+            },
+            move || {
+                if let Some(w) = weak_file.upgrade() {
+                    w.invoke_file_import_library();
+                }
+            },
+        );
     });
 
     // Sidebar toggles
@@ -321,16 +510,37 @@ fn setup_callbacks_with_state(
         }
     });
 
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_formations_show_all(move |parent_id| {
+        let Some(w) = weak_window.upgrade() else {
+            return;
+        };
+        {
+            let mut st = state_clone.borrow_mut();
+            let Some(library_id) = st.current_library.as_ref().and_then(|l| l.id) else {
+                return;
+            };
+            let parent = (parent_id >= 0).then_some(parent_id as i64);
+            st.expanded_groups.insert((library_id, parent));
+        }
+        refresh_formations_list(&w, &state_clone.borrow());
+    });
+
     // Library context menu (right-click): show LibraryContextMenu window
     let state_clone = state.clone();
     let weak_window = window.as_weak();
-    window.on_library_right_clicked(move |library_id, item_index| {
+    window.on_library_right_clicked(move |library_id, click_x, click_y| {
         let lib_id = library_id;
         let menu = match LibraryContextMenu::new() {
             Ok(m) => m,
             Err(_) => return,
         };
         menu.set_library_id(lib_id);
+        let presets = Rc::new(saved_presets());
+        menu.set_presets(ModelRc::new(VecModel::from(
+            presets.iter().map(|p| SharedString::from(p.name.as_str())).collect::<Vec<_>>(),
+        )));
 
         // Apply theme and translations to context menu
         if let Some(w) = weak_window.upgrade() {
@@ -339,28 +549,29 @@ fn setup_callbacks_with_state(
             AppTheme::get(&menu).set_mode(theme.into());
             menu.set_tr_properties(ui_tr(&lang, "Library Properties…").into());
             menu.set_tr_export(ui_tr(&lang, "Export Library…").into());
+            menu.set_tr_export_with_preset(ui_tr(&lang, "Export with preset").into());
+            menu.set_tr_manage_presets(ui_tr(&lang, "Manage presets…").into());
             menu.set_tr_history(ui_tr(&lang, "View History…").into());
+            menu.set_tr_tags(ui_tr(&lang, "Tags…").into());
             menu.set_tr_delete(ui_tr(&lang, "Delete").into());
         }
 
-        // Position menu near the clicked library item
-        let menu_window = menu.window();
-        if let Some(main_window) = weak_window.upgrade() {
-            let main_window_handle = main_window.window();
-            let main_pos = main_window_handle.position();
-            let item_height = 28;
-            let sidebar_header_height = 50;
-            let menu_x = (main_pos.x + 220) as f32;
-            let menu_y = (main_pos.y + sidebar_header_height + (item_index * item_height)) as f32;
-            menu_window.set_position(slint::WindowPosition::Logical(
-                slint::LogicalPosition::new(menu_x, menu_y),
-            ));
+        // Open the menu at the click, kept inside the main window
+        if let Some(area) = window_geometry::visible_area() {
+            let position = window_geometry::popup_position(
+                slint::LogicalPosition::new(area.x as f32, area.y as f32),
+                slint::LogicalPosition::new(click_x, click_y),
+                slint::LogicalSize::new(menu.get_menu_width(), menu.get_menu_height()),
+                area,
+            );
+            menu.window().set_position(position);
         }
 
         let weak_menu1 = menu.as_weak();
         let weak_menu2 = weak_menu1.clone();
         let weak_menu3 = weak_menu1.clone();
         let weak_menu4 = weak_menu1.clone();
+        let weak_menu5 = weak_menu1.clone();
         let state_c1 = state_clone.clone();
         let state_c2 = state_clone.clone();
         let state_c4 = state_clone.clone();
@@ -382,14 +593,47 @@ fn setup_callbacks_with_state(
             }
             if let Some(w) = weak_win2.upgrade() {
                 select_library_if_needed(state_c2.clone(), &w, lib_id);
-                w.invoke_file_export_library();
+                export_library_json(&weak_win2, &state_c2, i64::from(lib_id), None);
+            }
+        });
+        let (weak_menu6, state_c6, weak_win6) = (menu.as_weak(), state_clone.clone(), weak_window.clone());
+        menu.on_export_with_preset(move |index| {
+            if let Some(m) = weak_menu6.upgrade() {
+                m.hide().ok();
+            }
+            let (Some(w), Some(preset)) = (weak_win6.upgrade(), presets.get(index.max(0) as usize)) else {
+                return;
+            };
+            select_library_if_needed(state_c6.clone(), &w, lib_id);
+            let lib_name = state_c6.borrow().current_library.as_ref().map(|l| l.name.clone()).unwrap_or_default();
+            export_library_with_preset(&state_c6, &w.get_current_language(), i64::from(lib_id), &lib_name, preset);
+        });
+        let (weak_menu7, weak_win7) = (menu.as_weak(), weak_window.clone());
+        menu.on_manage_presets(move || {
+            if let Some(m) = weak_menu7.upgrade() {
+                m.hide().ok();
             }
+            let lang = weak_win7.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+            show_export_presets_dialog(&lang, |_| {});
         });
+        let state_c3 = state_clone.clone();
+        let weak_win3 = weak_window.clone();
         menu.on_history(move || {
             if let Some(m) = weak_menu3.upgrade() {
                 m.hide().ok();
             }
-            log::debug!("Library > View history");
+            if let Some(w) = weak_win3.upgrade() {
+                select_library_if_needed(state_c3.clone(), &w, lib_id);
+                w.invoke_library_view_history();
+            }
+        });
+        let state_c5 = state_clone.clone();
+        let weak_win5 = weak_window.clone();
+        menu.on_tags(move || {
+            if let Some(m) = weak_menu5.upgrade() {
+                m.hide().ok();
+            }
+            edit_library_tags(&weak_win5, &state_c5, lib_id);
         });
         menu.on_delete_library(move || {
             if let Some(m) = weak_menu4.upgrade() {
@@ -404,6 +648,28 @@ fn setup_callbacks_with_state(
         menu.show().ok();
     });
 
+    let weak_window = window.as_weak();
+    window.on_library_toggle_selected(move |library_id| {
+        if let Some(w) = weak_window.upgrade() {
+            toggle_library_selection(&w, Some(library_id));
+        }
+    });
+
+    // Libraries sidebar filter, applied once typing pauses
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    let search_timer = Rc::new(slint::Timer::default());
+    window.on_library_search_changed(move |text| {
+        state_clone.borrow_mut().library_filter = text.trim().to_string();
+        let state_for_timer = state_clone.clone();
+        let weak_for_timer = weak_window.clone();
+        search_timer.start(slint::TimerMode::SingleShot, LIBRARY_SEARCH_DEBOUNCE, move || {
+            if let Some(w) = weak_for_timer.upgrade() {
+                refresh_libraries_list(&w, state_for_timer.clone());
+            }
+        });
+    });
+
     // Library selection callback
     let state_clone = state.clone();
     let weak_window = window.as_weak();
@@ -411,7 +677,7 @@ fn setup_callbacks_with_state(
         log::debug!("Library selected: {}", library_id);
         let has_db = {
             let state = state_clone.borrow();
-            state.database.is_some()
+            state.database().is_some()
         };
 
         if !has_db {
@@ -421,7 +687,7 @@ fn setup_callbacks_with_state(
 
         {
             let state = state_clone.borrow();
-            if let Some(ref db) = state.database {
+            if let Some(db) = state.database() {
                 let service = LibraryService::new(db.conn());
                 match service.get_library(library_id as i64) {
                     Ok(Some(lib)) => {
@@ -429,9 +695,13 @@ fn setup_callbacks_with_state(
                         drop(state);
                         state_clone.borrow_mut().current_library = Some(lib.clone());
                         if let Some(window) = weak_window.upgrade() {
+                            track_current_library(&window, &state_clone);
+                            toggle_library_selection(&window, None);
                             window.set_current_library_name(lib.name.clone().into());
                             window.set_current_library_id(library_id);
-                            refresh_formations_list(&window);
+                            refresh_formations_list(&window, &state_clone.borrow());
+                            remember_recent_library(&window, &lib);
+                            refresh_level_mapping_count(&window, &state_clone.borrow(), library_id as i64);
                         }
                     }
                     Err(e) => log::error!("Failed to load library: {}", e),
@@ -441,58 +711,78 @@ fn setup_callbacks_with_state(
         }
     });
 
-    // Formation open (add tab)
-    let tabs1 = open_tabs_model.clone();
+    // Formation open: focus its tab, or add one titled with its name
+    let tabs1 = TabManager::new(open_tabs_model.clone());
     let weak_win_tabs = window.as_weak();
+    let state_tabs1 = state.clone();
     window.on_formation_open(move |formation_id| {
-        let title = format!("Formation {}", formation_id);
-        let tab = FormationTab {
-            id: formation_id,
-            title: title.clone().into(),
-            view_mode: "table".into(),
+        let Some(w) = weak_win_tabs.upgrade() else {
+            return;
         };
-        tabs1.push(tab);
-        let idx = tabs1.row_count() - 1;
-        if let Some(w) = weak_win_tabs.upgrade() {
-            w.set_current_tab_index(idx as i32);
-            w.set_current_tab_title(title.into());
-            w.set_current_tab_view_mode("table".into());
-        }
+        let title = || {
+            let st = state_tabs1.borrow();
+            let name = st.database().and_then(|db| match UnitRepo::new(db.conn()).get_by_id(formation_id as i64) {
+                Ok(unit) => unit.map(|u| u.name),
+                Err(e) => {
+                    log::error!("Failed to load formation {}: {}", formation_id, e);
+                    None
+                }
+            });
+            tab_title(formation_id, name)
+        };
+        // A new tab starts at the zoom last used
+        let index = tabs1.open_or_focus(formation_id, title, w.get_zoom_level());
+        w.invoke_tab_select(index as i32);
     });
 
-    // Tab select / close / set view mode
+    // Tab select / close / rename / set view mode
     let tabs2 = open_tabs_model.clone();
     let weak_win_tabs2 = window.as_weak();
+    let state_tabs2 = state.clone();
     window.on_tab_select(move |index| {
         if let Some(w) = weak_win_tabs2.upgrade() {
             w.set_current_tab_index(index);
             if index >= 0 && (index as usize) < tabs2.row_count() {
                 if let Some(row) = tabs2.row_data(index as usize) {
-                    w.set_current_tab_title(row.title.clone());
-                    w.set_current_tab_view_mode(row.view_mode.clone());
+                    show_tab(&w, &state_tabs2, &row);
                 }
             }
         }
     });
-    let tabs3 = open_tabs_model.clone();
+    let tabs3 = TabManager::new(open_tabs_model.clone());
     let weak_win_tabs3 = window.as_weak();
+    let state_tabs3 = state.clone();
     window.on_tab_close(move |index| {
-        if index >= 0 && (index as usize) < tabs3.row_count() {
-            tabs3.remove(index as usize);
-            if let Some(w) = weak_win_tabs3.upgrade() {
-                let count = tabs3.row_count();
-                if count == 0 {
-                    w.set_current_tab_index(-1);
-                    w.set_current_tab_title("".into());
-                    w.set_current_tab_view_mode("table".into());
-                } else {
-                    let new_idx = (index as usize).min(count.saturating_sub(1));
-                    w.set_current_tab_index(new_idx as i32);
-                    if let Some(row) = tabs3.row_data(new_idx) {
-                        w.set_current_tab_title(row.title.clone());
-                        w.set_current_tab_view_mode(row.view_mode.clone());
-                    }
-                }
+        let Some(index) = usize::try_from(index).ok().filter(|&i| tabs3.get(i).is_some()) else {
+            return;
+        };
+        let next = tabs3.close(index);
+        let Some(w) = weak_win_tabs3.upgrade() else {
+            return;
+        };
+        match next.and_then(|i| Some((i, tabs3.get(i)?))) {
+            Some((i, row)) => {
+                w.set_current_tab_index(i as i32);
+                show_tab(&w, &state_tabs3, &row);
+            }
+            None => {
+                w.set_current_tab_index(-1);
+                w.set_current_tab_title("".into());
+                w.set_current_tab_view_mode("table".into());
+                w.set_current_tab_compare_version(0);
+            }
+        }
+    });
+    let tabs_rename = TabManager::new(open_tabs_model.clone());
+    let weak_win_rename = window.as_weak();
+    window.on_tab_rename(move |formation_id, name| {
+        let title = tab_title(formation_id, Some(name.to_string()));
+        let Some(index) = tabs_rename.rename(formation_id, &title) else {
+            return;
+        };
+        if let Some(w) = weak_win_rename.upgrade() {
+            if w.get_current_tab_index() == index as i32 {
+                w.set_current_tab_title(title.into());
             }
         }
     });
@@ -512,13 +802,207 @@ fn setup_callbacks_with_state(
         }
     });
 
-    window.on_file_recent_libraries(|| {
-        log::debug!("File > Recent Libraries");
+    // Status bar messages, kept for assistive technology
+    let weak_win_announce = window.as_weak();
+    let state_announce = state.clone();
+    window.on_announce(move |message| {
+        let Some(w) = weak_win_announce.upgrade() else {
+            return;
+        };
+        let latest: SharedString = {
+            let mut st = state_announce.borrow_mut();
+            st.announcements.push(message.as_str());
+            st.announcements.latest().unwrap_or_default().into()
+        };
+        w.set_status_text(latest.clone());
+        w.set_announcement(latest);
+    });
+
+    // Keyboard shortcuts, ignored while a dialog is open over the main window
+    let tabs_keys = open_tabs_model.clone();
+    let weak_win_keys = window.as_weak();
+    let state_keys = state.clone();
+    window.on_key_pressed(move |key, ctrl, shift, alt| {
+        let Some(action) = map_shortcut(&key, ctrl, shift, alt) else {
+            return false;
+        };
+        if state_keys.borrow_mut().open_dialogs.count() > 0 {
+            log::debug!("Shortcut {:?} ignored while a dialog is open", action);
+            return false;
+        }
+        let Some(w) = weak_win_keys.upgrade() else {
+            return false;
+        };
+        let current = w.get_current_tab_index();
+        match action {
+            AppAction::NewLibrary => w.invoke_file_new_library(),
+            AppAction::OpenLibrary => w.invoke_file_open_library(),
+            AppAction::SaveLibrary => w.invoke_file_save_library(),
+            AppAction::PrintCurrentTab => w.invoke_file_print_current_tab(),
+            AppAction::CloseTab => w.invoke_tab_close(current),
+            AppAction::NextTab | AppAction::PreviousTab => {
+                let forward = action == AppAction::NextTab;
+                if let Some(index) = cycle_tab(current, tabs_keys.row_count(), forward) {
+                    w.invoke_tab_select(index);
+                }
+            }
+            AppAction::TableView => w.invoke_tab_set_view_mode(current, "table".into()),
+            AppAction::DiagramView => w.invoke_tab_set_view_mode(current, "diagram".into()),
+            AppAction::ZoomIn => w.invoke_view_zoom_in(),
+            AppAction::ZoomOut => w.invoke_view_zoom_out(),
+            AppAction::ZoomReset => w.invoke_view_zoom_reset(),
+            AppAction::LibraryMenu => {
+                let library_id = w.get_current_library_id();
+                if !w.get_libraries().iter().any(|l| l.id == library_id) {
+                    return false;
+                }
+                // No click to open at: use the middle of the window
+                let size = w.window().size().to_logical(w.window().scale_factor());
+                w.invoke_library_right_clicked(library_id, size.width / 2.0, size.height / 2.0);
+            }
+            AppAction::Exit => w.invoke_file_exit(),
+        }
+        true
+    });
+
+    let tabs_edit = open_tabs_model.clone();
+    let weak_win_edit = window.as_weak();
+    let state_edit = state.clone();
+    window.on_tab_edit_table(move |index| {
+        let Some(tab) = usize::try_from(index).ok().and_then(|i| tabs_edit.row_data(i)) else {
+            return;
+        };
+        let Some(w) = weak_win_edit.upgrade() else {
+            return;
+        };
+        show_formation_table_editor(state_edit.clone(), i64::from(tab.id), &w.get_current_language());
+    });
+
+    let tabs5 = open_tabs_model.clone();
+    let weak_win_tabs5 = window.as_weak();
+    let state_tabs5 = state.clone();
+    window.on_tab_compare_with_version(move |index| {
+        let Some(tab) = usize::try_from(index).ok().and_then(|i| tabs5.row_data(i)) else {
+            return;
+        };
+        let Some(w) = weak_win_tabs5.upgrade() else {
+            return;
+        };
+        let lang = w.get_current_language().to_string();
+        let snapshots = {
+            let st = state_tabs5.borrow();
+            let Some(db) = st.database() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            crate::db::repositories::UnitRepo::new(db.conn())
+                .get_placement(tab.id as i64)
+                .and_then(|placement| match placement {
+                    Some((lib_id, _)) => LibraryService::new(db.conn()).get_library_versions(lib_id),
+                    None => Err(anyhow::anyhow!("Formation {} not found", tab.id)),
+                })
+        };
+        let snapshots = match snapshots {
+            Ok(snapshots) if snapshots.len() >= 2 => snapshots,
+            Ok(_) => {
+                show_error_dialog(
+                    &lang,
+                    "Compare with Version…",
+                    "The library has only one version; save it to create another.",
+                    &[],
+                );
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to load versions: {}", e);
+                show_error_dialog(&lang, "Error", "Failed to load versions: {}", &[&e]);
+                return;
+            }
+        };
+        let tabs_pick = tabs5.clone();
+        let lang_pick = lang.clone();
+        let weak_pick = weak_win_tabs5.clone();
+        let state_pick = state_tabs5.clone();
+        show_version_picker_dialog(&lang, "Compare with Version…", "Compare", &snapshots, move |version| {
+            // Check the version holds the formation before switching the tab over
+            if let Err(e) = formation_compare_rows(&state_pick, tab.id as i64, version) {
+                log::error!("Failed to compare formation {} with v{}: {}", tab.id, version, e);
+                show_error_dialog(&lang_pick, "Error", "Failed to compare with version {}: {}", &[&version, &e]);
+                return;
+            }
+            let Some(i) = (0..tabs_pick.row_count()).find(|&i| tabs_pick.row_data(i).is_some_and(|t| t.id == tab.id)) else {
+                return;
+            };
+            let mut row = tabs_pick.row_data(i).unwrap_or_else(|| tab.clone());
+            row.compare_version = version as i32;
+            tabs_pick.set_row_data(i, row.clone());
+            if let Some(w) = weak_pick.upgrade() {
+                if w.get_current_tab_index() == i as i32 {
+                    show_tab(&w, &state_pick, &row);
+                }
+            }
+        });
+    });
+    let tabs6 = open_tabs_model.clone();
+    let weak_win_tabs6 = window.as_weak();
+    let state_tabs6 = state.clone();
+    window.on_tab_exit_compare(move |index| {
+        let Some(i) = usize::try_from(index).ok() else {
+            return;
+        };
+        let Some(mut row) = tabs6.row_data(i) else {
+            return;
+        };
+        row.compare_version = 0;
+        tabs6.set_row_data(i, row.clone());
+        if let Some(w) = weak_win_tabs6.upgrade() {
+            if w.get_current_tab_index() == index {
+                show_tab(&w, &state_tabs6, &row);
+            }
+        }
+    });
+
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_recent_library_selected(move |library_id| {
+        log::debug!("File > Recent Libraries: {}", library_id);
+        let exists = {
+            let state = state_clone.borrow();
+            let Some(db) = state.database() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            LibraryService::new(db.conn()).get_library(library_id as i64)
+        };
+        let Some(window) = weak_window.upgrade() else {
+            return;
+        };
+        match exists {
+            Ok(Some(_)) => window.invoke_library_selected(library_id),
+            Ok(None) => {
+                let mut settings = crate::config::Settings::load().unwrap_or_default();
+                settings.forget_recent_library(library_id as i64);
+                if let Err(e) = settings.save() {
+                    log::error!("Failed to save settings: {}", e);
+                }
+                set_recent_libraries(&window, &settings);
+                let lang = window.get_current_language().to_string();
+                show_error_dialog(
+                    &lang,
+                    "Recent Libraries",
+                    "This library no longer exists. It has been removed from the recent libraries list.",
+                    &[],
+                );
+            }
+            Err(e) => log::error!("Failed to load library: {}", e),
+        }
     });
 
     let state_clone = state.clone();
+    let weak_save = window.as_weak();
     window.on_file_save_library(move || {
         log::debug!("File > Save Library");
+        let lang = weak_save.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
         let lib_to_save = {
             let state = state_clone.borrow();
             state.current_library.clone()
@@ -526,17 +1010,31 @@ fn setup_callbacks_with_state(
 
         if let Some(lib) = lib_to_save {
             let state = state_clone.borrow();
-            if let Some(ref db) = state.database {
+            if let Some(db) = state.database() {
                 let service = LibraryService::new(db.conn());
                 match service.save_library(lib, true) {
-                    Ok(_) => {
+                    Ok(saved) => {
+                        let export_path = saved.id.and_then(|id| state.export_paths.get(&id).cloned());
                         drop(state);
                         log::info!("Library saved successfully");
+                        if let Some(path) = export_path {
+                            match export::export_library_to_path(&saved, &path) {
+                                Ok(()) => log::info!("Library also written to: {:?}", path),
+                                Err(e) => {
+                                    log::error!("Failed to write library file: {:#}", e);
+                                    show_error_dialog(&lang, "Error", "Failed to save library: {}", &[&format!("{:#}", e)]);
+                                }
+                            }
+                        }
+                        state_clone.borrow_mut().current_library = Some(saved);
+                        if let Some(w) = weak_save.upgrade() {
+                            update_dirty(&w, &state_clone, DirtyState::saved);
+                        }
                     }
                     Err(e) => {
                         drop(state);
                         log::error!("Failed to save library: {}", e);
-                        show_error_dialog("Error", &format!("Failed to save library: {}", e));
+                        show_error_dialog(&lang, "Error", "Failed to save library: {}", &[&e]);
                     }
                 }
             } else {
@@ -547,78 +1045,494 @@ fn setup_callbacks_with_state(
         }
     });
 
-    window.on_file_save_library_as(|| {
+    let state_clone = state.clone();
+    let weak_save_as = window.as_weak();
+    window.on_file_save_library_as(move || {
         log::debug!("File > Save Library As");
-        show_error_dialog("Not implemented", "Save Library As is not yet implemented.");
+        let lang = weak_save_as.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+        let Some(lib) = state_clone.borrow().current_library.clone() else {
+            log::warn!("No library to save. Create or open a library first.");
+            return;
+        };
+        let state_clone = state_clone.clone();
+        choose_file(
+            FileRequest::save()
+                .filter("JSON", &["json"])
+                .filter("YAML", &["yaml", "yml"])
+                .file_name(export::default_file_name(&lib.name, "json")),
+            move |path| {
+                let operations = state_clone.borrow().operations.clone();
+                let lib_id = lib.id;
+                let target = path.clone();
+                let work = move || export::export_library_to_path(&lib, &target);
+                run_in_background(&operations, "Save Library As", work, move |result| match result {
+                    Ok(()) => {
+                        log::info!("Library saved as: {:?}", path);
+                        if let Some(id) = lib_id {
+                            state_clone.borrow_mut().export_paths.insert(id, path);
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Failed to save library as {:?}: {:#}", path, e);
+                        show_error_dialog(&lang, "Error", "Failed to save library: {}", &[&format!("{:#}", e)]);
+                    }
+                });
+            },
+        );
     });
 
     let state_clone = state.clone();
+    let weak_window = window.as_weak();
     window.on_file_import_library(move || {
         log::debug!("File > Import Library");
-        let state = state_clone.borrow();
-        if state.database.is_some() {
-            log::info!("Import functionality - TODO: implement file dialog");
+        if state_clone.borrow().database().is_none() {
+            log::error!("Database not initialized");
+            return;
         }
+        let state_clone = state_clone.clone();
+        let weak_window = weak_window.clone();
+        choose_file(
+            FileRequest::open()
+                .filter("TOEditor library", &["json", "yaml", "yml"])
+                .filter("Excel workbook", &["xlsx"]),
+            move |path| {
+                let lang = weak_window
+                    .upgrade()
+                    .map(|w| w.get_current_language().to_string())
+                    .unwrap_or_else(|| "en".to_string());
+                let is_xlsx = path
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("xlsx"));
+                let operations = state_clone.borrow().operations.clone();
+                if is_xlsx {
+                    let source = path.clone();
+                    let work = move || crate::import::xlsx::import_xlsx(&source);
+                    run_in_background(&operations, "Import workbook", work, move |result| match result {
+                        Ok(preview) => show_import_preview_dialog(&lang, preview, state_clone, weak_window),
+                        Err(e) => {
+                            log::error!("Failed to read workbook {:?}: {}", path, e);
+                            show_error_dialog(&lang, "Import Error", "Failed to import library: {}", &[&e]);
+                        }
+                    });
+                    return;
+                }
+                let source = path.clone();
+                let work = move || crate::import::read_library_file(&source);
+                run_in_background(&operations, "Import Library", work, move |import| match import {
+                    Ok(import) => import_library_with_confirmation(&lang, import, state_clone, weak_window),
+                    Err(e) => {
+                        log::error!("Failed to read library file {:?}: {}", path, e);
+                        show_error_dialog(&lang, "Import Error", &import_error_message(&lang, &e), &[]);
+                    }
+                });
+            },
+        );
     });
 
-    window.on_file_import_formation(|| {
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_file_import_formation(move || {
         log::debug!("File > Import Formation");
-        show_error_dialog("Not implemented", "Import Formation is not yet implemented.");
+        import_formation_into_current(&weak_window, &state_clone);
     });
 
     let state_clone = state.clone();
+    let weak_export = window.as_weak();
     window.on_file_export_library(move || {
         log::debug!("File > Export Library");
-        let state = state_clone.borrow();
-        if let Some(ref lib) = state.current_library {
-            let path = std::env::temp_dir().join(format!("{}.json", lib.name));
-            match export::export_json(lib, &path) {
-                Ok(_) => log::info!("Library exported to: {:?}", path),
-                Err(e) => {
-                    log::error!("Failed to export library: {}", e);
-                    show_error_dialog("Export Error", &format!("Failed to export library: {}", e));
-                }
+        let lang = weak_export.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+        let (lib_id, lib_name) = match state_clone.borrow().current_library.as_ref() {
+            Some(Library { id: Some(id), name, .. }) => (*id, name.clone()),
+            _ => {
+                log::warn!("No library to export. Create or open a library first.");
+                return;
             }
-        } else {
-            log::warn!("No library to export. Create or open a library first.");
-        }
+        };
+        let state_clone = state_clone.clone();
+        choose_file(
+            FileRequest::save()
+                .filter("TOEditor library", &["json"])
+                .file_name(export::default_file_name(&lib_name, "json")),
+            move |path| {
+                let target = path.clone();
+                let work = move |manager: &LibraryManager| manager.export_full(lib_id, &target);
+                run_with_manager(&state_clone, "Export Library", work, move |result| match result {
+                    Ok(()) => log::info!("Library exported to: {:?}", path),
+                    Err(e) => {
+                        log::error!("Failed to export library: {:#}", e);
+                        show_error_dialog(&lang, "Export Error", "Failed to export library: {}", &[&format!("{:#}", e)]);
+                    }
+                });
+            },
+        );
     });
 
-    window.on_file_export_formation(|| {
+    // Same file without the author, for sharing publicly
+    let state_clone = state.clone();
+    let weak_anonymized = window.as_weak();
+    window.on_file_export_library_anonymized(move || {
+        log::debug!("File > Export Anonymized Library");
+        let lib_id = match state_clone.borrow().current_library.as_ref() {
+            Some(Library { id: Some(id), .. }) => *id,
+            _ => {
+                log::warn!("No library to export. Create or open a library first.");
+                return;
+            }
+        };
+        export_library_json(&weak_anonymized, &state_clone, lib_id, Some(export::AnonymizeOptions::default()));
+    });
+
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_file_export_formation(move || {
         log::debug!("File > Export Formation");
-        show_error_dialog("Not implemented", "Export Formation is not yet implemented.");
+        export_selected_formation(&weak_window, &state_clone);
     });
-    window.on_file_export_spreadsheet(|| {
+    let state_clone = state.clone();
+    let weak_spreadsheet = window.as_weak();
+    window.on_file_export_spreadsheet(move || {
         log::debug!("File > Export Spreadsheet");
-        show_error_dialog("Not implemented", "Export Spreadsheet is not yet implemented.");
+        let lang = weak_spreadsheet.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+        let (lib_id, lib_name) = match state_clone.borrow().current_library.as_ref() {
+            Some(Library { id: Some(id), name, .. }) => (*id, name.clone()),
+            _ => {
+                log::warn!("No library to export. Create or open a library first.");
+                return;
+            }
+        };
+        let (as_of, numbering) = {
+            let st = state_clone.borrow();
+            (st.current_as_of(), st.numbering())
+        };
+        let (state_ok, lang_ok) = (state_clone.clone(), lang.clone());
+        let initial = export::ExportOptions::default();
+        show_export_options_dialog(&lang, &state_clone, "Export Spreadsheet", false, initial, None, move |_, anonymize| {
+            let (state_clone, lang, numbering) = (state_ok.clone(), lang_ok.clone(), numbering.clone());
+            choose_file(
+                FileRequest::save()
+                    .filter("CSV", &["csv"])
+                    .filter("Excel Workbook", &["xlsx"])
+                    .file_name(export::default_file_name(&lib_name, "csv")),
+                move |path| {
+                    let target = path.clone();
+                    let work = move |manager: &LibraryManager| ExportFormat::from_path(&target).and_then(|format| match format {
+                        ExportFormat::Xlsx => manager
+                            .library_as_of(lib_id, as_of)
+                            .and_then(|library| export::export_xlsx(&library, &library.units, anonymize.as_ref(), &target)),
+                        ExportFormat::Csv => manager.library_as_of(lib_id, as_of).and_then(|mut library| {
+                            if let Some(anonymize) = &anonymize {
+                                library = export::anonymize_library(library, anonymize);
+                            }
+                            // Import reads workbooks back, so only the CSV text carries the numbering
+                            if numbering.enabled {
+                                number_units(&mut library.units, &numbering);
+                            }
+                            export::export_csv(&library, &target)
+                        }),
+                        format => manager.export_library_as_of(lib_id, &target, format, as_of),
+                    });
+                    run_with_manager(&state_clone, "Export Spreadsheet", work, move |result| match result {
+                        Ok(()) => log::info!("Spreadsheet exported to: {:?}", path),
+                        Err(e) => {
+                            log::error!("Failed to export spreadsheet: {:#}", e);
+                            show_error_dialog(&lang, "Export Error", "Failed to export library: {}", &[&format!("{:#}", e)]);
+                        }
+                    });
+                },
+            );
+        });
     });
-    window.on_file_export_diagram(|| {
+    let state_clone = state.clone();
+    let weak_diagram = window.as_weak();
+    window.on_file_export_diagram(move || {
         log::debug!("File > Export Diagram");
-        show_error_dialog("Not implemented", "Export Diagram is not yet implemented.");
-    });
-
-    // Edit menu actions
-    window.on_edit_find(|| { log::debug!("Edit > Find"); show_error_dialog("Not implemented", "Find is not yet implemented."); });
-    window.on_edit_find_replace(|| { log::debug!("Edit > Find and Replace"); show_error_dialog("Not implemented", "Find and Replace is not yet implemented."); });
-    window.on_edit_undo(|| { log::debug!("Edit > Undo"); show_error_dialog("Not implemented", "Undo is not yet implemented."); });
-    window.on_edit_redo(|| { log::debug!("Edit > Redo"); show_error_dialog("Not implemented", "Redo is not yet implemented."); });
-    window.on_edit_cut(|| { log::debug!("Edit > Cut"); show_error_dialog("Not implemented", "Cut is not yet implemented."); });
-    window.on_edit_copy(|| { log::debug!("Edit > Copy"); show_error_dialog("Not implemented", "Copy is not yet implemented."); });
-    window.on_edit_paste(|| { log::debug!("Edit > Paste"); show_error_dialog("Not implemented", "Paste is not yet implemented."); });
-    window.on_edit_delete(|| { log::debug!("Edit > Delete"); show_error_dialog("Not implemented", "Delete is not yet implemented."); });
-    window.on_edit_add_formation(|| { log::debug!("Edit > Add Formation"); show_error_dialog("Not implemented", "Add Formation is not yet implemented."); });
-    window.on_edit_edit_properties(|| { log::debug!("Edit > Edit Properties"); show_error_dialog("Not implemented", "Edit Properties is not yet implemented."); });
-
-    // Library menu actions
-    window.on_library_positions_editor(|| { log::debug!("Library > Positions Editor"); show_error_dialog("Not implemented", "Positions and Ranks Editor is not yet implemented."); });
-    window.on_library_equipment_editor(|| { log::debug!("Library > Equipment Editor"); show_error_dialog("Not implemented", "Equipment and Vehicles Editor is not yet implemented."); });
-
-    // Formation levels editor (separate window)
-    let state_formation = state.clone();
-    let weak_window_formation = window.as_weak();
-    window.on_library_formation_levels(move || {
-        let (lib_id, lib_name) = {
-            let st = state_formation.borrow();
+        let (lib_id, lib_name) = match state_clone.borrow().current_library.as_ref() {
+            Some(Library { id: Some(id), name, .. }) => (*id, name.clone()),
+            _ => {
+                log::warn!("No library to export. Create or open a library first.");
+                return;
+            }
+        };
+        let lang = weak_diagram
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let (as_of, numbering) = {
+            let st = state_clone.borrow();
+            (st.current_as_of(), st.numbering())
+        };
+        let initial = export::SvgOptions {
+            page_limit: crate::config::Settings::load()
+                .unwrap_or_default()
+                .diagram_page_limit
+                .map(|[width, height]| export::PageLimit { width, height }),
+            ..export::SvgOptions::default()
+        };
+        let (state_clone, dialog_lang) = (state_clone.clone(), lang.clone());
+        show_diagram_export_dialog(&dialog_lang, initial, false, move |options, _| {
+            let mut settings = crate::config::Settings::load().unwrap_or_default();
+            settings.diagram_page_limit = options.page_limit.map(|l| [l.width, l.height]);
+            if let Err(e) = settings.save() {
+                log::error!("Failed to save settings: {}", e);
+            }
+            let (state_clone, lang, numbering) = (state_clone.clone(), lang.clone(), numbering.clone());
+            choose_file(
+                FileRequest::save()
+                    .filter("SVG", &["svg"])
+                    .file_name(export::default_file_name(&lib_name, "svg")),
+                move |path| {
+                    let work = move |manager: &LibraryManager| {
+                        manager.library_as_of(lib_id, as_of).and_then(|mut library| {
+                            number_units(&mut library.units, &numbering);
+                            export::export_svg_pages(&library, &library.units, &path, &options)
+                        })
+                    };
+                    run_with_manager(&state_clone, "Export Diagram", work, move |result| match result {
+                        Ok(files) => log::info!("Diagram exported to: {:?}", files),
+                        Err(e) => {
+                            log::error!("Failed to export diagram: {:#}", e);
+                            show_error_dialog(&lang, "Export Error", "Failed to export diagram: {}", &[&format!("{:#}", e)]);
+                        }
+                    });
+                },
+            );
+        });
+    });
+
+    let state_batch = state.clone();
+    let weak_batch = window.as_weak();
+    window.on_file_export_diagrams_per_formation(move || {
+        log::debug!("File > Export Diagrams per Formation");
+        let Some(lib_id) = state_batch.borrow().current_library.as_ref().and_then(|l| l.id) else {
+            log::warn!("No library to export. Create or open a library first.");
+            return;
+        };
+        let Some(w) = weak_batch.upgrade() else {
+            return;
+        };
+        let lang = w.get_current_language().to_string();
+        let (as_of, numbering) = {
+            let st = state_batch.borrow();
+            (st.current_as_of(), st.numbering())
+        };
+        let settings = crate::config::Settings::load().unwrap_or_default();
+        let initial = export::SvgOptions {
+            page_limit: settings.diagram_page_limit.map(|[width, height]| export::PageLimit { width, height }),
+            ..export::SvgOptions::default()
+        };
+        let export_dir = settings.export_dir();
+        let (state, weak, dialog_lang) = (state_batch.clone(), weak_batch.clone(), lang.clone());
+        show_diagram_export_dialog(&dialog_lang, initial, true, move |options, depth| {
+            let mut settings = crate::config::Settings::load().unwrap_or_default();
+            settings.diagram_page_limit = options.page_limit.map(|l| [l.width, l.height]);
+            if let Err(e) = settings.save() {
+                log::error!("Failed to save settings: {}", e);
+            }
+            let (state, weak, lang, numbering) = (state.clone(), weak.clone(), lang.clone(), numbering.clone());
+            choose_file(FileRequest::folder().directory(export_dir.clone()), move |dir| {
+                let target = dir.clone();
+                let work = move |manager: &LibraryManager| {
+                    manager.library_as_of(lib_id, as_of).map(|mut library| {
+                        number_units(&mut library.units, &numbering);
+                        export::export_diagrams_per_formation(&library, &library.units, &target, depth, &options)
+                    })
+                };
+                run_with_manager(&state, "Export Diagrams per Formation", work, move |result| match result {
+                    Ok(report) => report_diagrams_per_formation(&weak, &lang, &dir, &report),
+                    Err(e) => {
+                        log::error!("Failed to load the library for export: {:#}", e);
+                        show_error_dialog(&lang, "Export Error", "Failed to export diagram: {}", &[&format!("{:#}", e)]);
+                    }
+                });
+            });
+        });
+    });
+    let state_html = state.clone();
+    let weak_win_html = window.as_weak();
+    window.on_file_export_html(move || {
+        log::debug!("File > Export Interactive HTML");
+        let (lib_id, lib_name) = match state_html.borrow().current_library.as_ref() {
+            Some(Library { id: Some(id), name, .. }) => (*id, name.clone()),
+            _ => {
+                log::warn!("No library to export. Create or open a library first.");
+                return;
+            }
+        };
+        let lang = weak_win_html
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let as_of = state_html.borrow().current_as_of();
+        let (state_ok, lang_ok) = (state_html.clone(), lang.clone());
+        let initial = export::ExportOptions::default();
+        show_export_options_dialog(&lang, &state_html, "Export Interactive HTML", false, initial, None, move |_, anonymize| {
+            let (state_html, lang) = (state_ok.clone(), lang_ok.clone());
+            choose_file(
+                FileRequest::save()
+                    .filter("HTML", &["html", "htm"])
+                    .file_name(export::default_file_name(&lib_name, "html")),
+                move |path| {
+                    let numbering = state_html.borrow().numbering();
+                    let (target, tree_lang) = (path.clone(), lang.clone());
+                    let work = move |manager: &LibraryManager| {
+                        let conn = manager.database().conn();
+                        export::html::export_interactive_tree(conn, lib_id, &tree_lang, as_of, &numbering, anonymize.as_ref(), &target)
+                    };
+                    run_with_manager(&state_html, "Export Interactive HTML", work, move |result| match result {
+                        Ok(()) => log::info!("Interactive tree exported to: {:?}", path),
+                        Err(e) => {
+                            log::error!("Failed to export interactive tree: {}", e);
+                            show_error_dialog(&lang, "Export Error", "Failed to export library: {}", &[&e]);
+                        }
+                    });
+                },
+            );
+        });
+    });
+
+    let state_preset = state.clone();
+    let weak_win_preset = window.as_weak();
+    window.on_file_export_with_preset(move || {
+        log::debug!("File > Export with Preset");
+        let (lib_id, lib_name) = match state_preset.borrow().current_library.as_ref() {
+            Some(Library { id: Some(id), name, .. }) => (*id, name.clone()),
+            _ => {
+                log::warn!("No library to export. Create or open a library first.");
+                return;
+            }
+        };
+        let lang = weak_win_preset
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        show_export_preset_dialog(&state_preset, &lang, lib_id, &lib_name);
+    });
+
+    let weak_win_presets = window.as_weak();
+    window.on_file_manage_export_presets(move || {
+        log::debug!("File > Manage Export Presets");
+        let lang = weak_win_presets
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        show_export_presets_dialog(&lang, |_| {});
+    });
+
+    let state_print = state.clone();
+    let weak_win_print = window.as_weak();
+    let tabs_print = open_tabs_model.clone();
+    window.on_file_print_current_tab(move || {
+        log::debug!("File > Print Current Tab");
+        let Some(w) = weak_win_print.upgrade() else {
+            return;
+        };
+        let index = w.get_current_tab_index();
+        let Some(tab) = (index >= 0).then(|| tabs_print.row_data(index as usize)).flatten() else {
+            return;
+        };
+        let lang = w.get_current_language().to_string();
+        if let Err(e) = print::print_formation(&state_print, tab.id as i64, &tab.title, &lang) {
+            log::error!("Failed to print formation {}: {}", tab.id, e);
+            show_error_dialog(&lang, "Print Error", "Failed to prepare the page for printing: {}", &[&e]);
+        }
+    });
+
+    // Edit menu actions
+    let state_find = state.clone();
+    let weak_find = window.as_weak();
+    window.on_edit_find(move || {
+        log::debug!("Edit > Find");
+        let Some(w) = weak_find.upgrade() else {
+            return;
+        };
+        let weak = weak_find.clone();
+        show_find_dialog(&w.get_current_language(), state_find.clone(), move |unit_id| {
+            if let Some(w) = weak.upgrade() {
+                w.set_selected_formation_id(unit_id as i32);
+                w.invoke_formation_open(unit_id as i32);
+            }
+        });
+    });
+    window.on_edit_find_replace(not_implemented(window, "Edit > Find and Replace", "Find and Replace is not yet implemented."));
+    window.on_edit_undo(not_implemented(window, "Edit > Undo", "Undo is not yet implemented."));
+    window.on_edit_redo(not_implemented(window, "Edit > Redo", "Redo is not yet implemented."));
+    window.on_edit_cut(not_implemented(window, "Edit > Cut", "Cut is not yet implemented."));
+    window.on_edit_copy(not_implemented(window, "Edit > Copy", "Copy is not yet implemented."));
+    window.on_edit_paste(not_implemented(window, "Edit > Paste", "Paste is not yet implemented."));
+    window.on_edit_delete(not_implemented(window, "Edit > Delete", "Delete is not yet implemented."));
+    window.on_edit_add_formation(not_implemented(window, "Edit > Add Formation", "Add Formation is not yet implemented."));
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_edit_edit_properties(move || {
+        log::debug!("Edit > Edit Properties");
+        let Some(w) = weak_window.upgrade() else {
+            return;
+        };
+        let unit_id = w.get_selected_formation_id() as i64;
+        if unit_id < 0 {
+            log::warn!("No formation selected");
+            return;
+        }
+        let unit = {
+            let st = state_clone.borrow();
+            let Some(db) = st.database() else {
+                return;
+            };
+            crate::db::repositories::UnitRepo::new(db.conn()).get_by_id(unit_id)
+        };
+        let unit = match unit {
+            Ok(Some(unit)) => unit,
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("Failed to load formation: {}", e);
+                show_error_dialog(&w.get_current_language(), "Error", "Failed to load formation: {}", &[&e]);
+                return;
+            }
+        };
+        let state_save = state_clone.clone();
+        show_unit_properties_dialog(&w.get_current_language(), unit, move |edited| {
+            {
+                let st = state_save.borrow();
+                let db = st.database().ok_or_else(|| anyhow::anyhow!("Database not initialized"))?;
+                let unique = crate::config::Settings::load().unwrap_or_default().unique_toe_numbers;
+                UnitService::new(db.conn()).with_unique_toe_numbers(unique).update_properties(&edited)?;
+            }
+            let lib_id = state_save.borrow().current_library.as_ref().and_then(|l| l.id);
+            if let Some(lib_id) = lib_id {
+                notify_change(&state_save, ChangeEvent::UnitTreeChanged(lib_id));
+            }
+            Ok(())
+        });
+    });
+
+    // Library menu actions
+    window.on_library_positions_editor(not_implemented(window, "Library > Positions Editor", "Positions and Ranks Editor is not yet implemented."));
+    let state_equipment = state.clone();
+    let weak_win_equipment = window.as_weak();
+    window.on_library_equipment_editor(move || {
+        log::debug!("Library > Equipment Editor");
+        let Some((lib_id, lib_name)) = state_equipment
+            .borrow()
+            .current_library
+            .as_ref()
+            .and_then(|l| l.id.map(|id| (id, l.name.clone())))
+        else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_win_equipment
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        show_equipment_catalog_editor(state_equipment.clone(), lib_id, &lib_name, &lang);
+    });
+
+    // Formation levels editor (separate window)
+    let state_formation = state.clone();
+    let weak_window_formation = window.as_weak();
+    window.on_library_formation_levels(move || {
+        let (lib_id, lib_name) = {
+            let st = state_formation.borrow();
             match &st.current_library {
                 Some(lib) => match lib.id {
                     Some(id) => (id, lib.name.clone()),
@@ -712,12 +1626,362 @@ fn setup_callbacks_with_state(
         }
     });
 
-    window.on_library_manage_tags(|| { log::debug!("Library > Manage Tags"); show_error_dialog("Not implemented", "Manage Tags is not yet implemented."); });
-    window.on_library_export_library(|| { log::debug!("Library > Export Library"); show_error_dialog("Not implemented", "Export Library is not yet implemented."); });
-    window.on_library_view_history(|| { log::debug!("Library > View History"); show_error_dialog("Not implemented", "View History is not yet implemented."); });
-    window.on_library_create_snapshot(|| { log::debug!("Library > Create Snapshot"); show_error_dialog("Not implemented", "Create Snapshot is not yet implemented."); });
-    window.on_library_compare_versions(|| { log::debug!("Library > Compare Versions"); show_error_dialog("Not implemented", "Compare Versions is not yet implemented."); });
-    window.on_library_revert_to_version(|| { log::debug!("Library > Revert to Version"); show_error_dialog("Not implemented", "Revert to Version is not yet implemented."); });
+    let state_levels = state.clone();
+    let weak_levels = window.as_weak();
+    window.on_library_review_level_mappings(move || {
+        log::debug!("Library > Review Level Mappings");
+        let Some(lib_id) = state_levels.borrow().current_library.as_ref().and_then(|l| l.id) else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_levels
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let loaded = {
+            let st = state_levels.borrow();
+            let Some(db) = st.database() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            let service = LevelMappingService::new(db.conn());
+            service.scan_library(lib_id).and_then(|_| {
+                let levels = crate::db::repositories::FormationLevelRepo::new(db.conn()).list_by_library(lib_id)?;
+                Ok((service.pending(lib_id)?, levels))
+            })
+        };
+        match loaded {
+            Ok((_, levels)) if levels.is_empty() => show_error_dialog(
+                &lang,
+                "Review Level Mappings",
+                "The library has no formation levels yet. Add them in Library → Formation levels…",
+                &[],
+            ),
+            Ok((entries, _)) if entries.is_empty() => {
+                if let Some(w) = weak_levels.upgrade() {
+                    w.set_level_mapping_count(0);
+                }
+                show_error_dialog(
+                    &lang,
+                    "Review Level Mappings",
+                    "Every unit type is mapped to a formation level.",
+                    &[],
+                );
+            }
+            Ok((entries, levels)) => {
+                let state_apply = state_levels.clone();
+                let weak_apply = weak_levels.clone();
+                let lang_apply = lang.clone();
+                let name_pref = state_levels
+                    .borrow()
+                    .current_library
+                    .as_ref()
+                    .map(|l| DisplayLang::from_code(&l.display_language))
+                    .unwrap_or_default();
+                show_level_mapping_dialog(&lang, name_pref, entries, levels, move |decisions| {
+                    let applied = {
+                        let st = state_apply.borrow();
+                        let Some(db) = st.database() else {
+                            log::error!("Database not initialized");
+                            return;
+                        };
+                        LevelMappingService::new(db.conn()).apply(lib_id, &decisions)
+                    };
+                    match applied {
+                        Ok(count) => {
+                            log::info!("Applied {} level mappings in library {}", count, lib_id);
+                            if let Some(w) = weak_apply.upgrade() {
+                                refresh_level_mapping_count(&w, &state_apply.borrow(), lib_id);
+                            }
+                            notify_change(&state_apply, ChangeEvent::LibraryChanged(lib_id));
+                        }
+                        Err(e) => {
+                            log::error!("Failed to apply level mappings: {}", e);
+                            show_error_dialog(&lang_apply, "Error", "Failed to apply level mappings: {}", &[&e]);
+                        }
+                    }
+                });
+            }
+            Err(e) => {
+                log::error!("Failed to load level mappings: {}", e);
+                show_error_dialog(&lang, "Error", "Failed to load level mappings: {}", &[&e]);
+            }
+        }
+    });
+    let state_dups = state.clone();
+    let weak_dups = window.as_weak();
+    window.on_library_find_duplicate_units(move || {
+        log::debug!("Library > Find Duplicate Units");
+        let Some(lib_id) = state_dups.borrow().current_library.as_ref().and_then(|l| l.id) else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_dups
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let found = {
+            let st = state_dups.borrow();
+            let Some(db) = st.database() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            find_library_duplicates(db.conn(), lib_id, &DuplicateOptions::default())
+        };
+        let pairs = match found {
+            Ok(pairs) => pairs,
+            Err(e) => {
+                log::error!("Failed to look for duplicate units: {}", e);
+                show_error_dialog(&lang, "Error", "Failed to look for duplicate units: {}", &[&e]);
+                return;
+            }
+        };
+        let state_act = state_dups.clone();
+        let lang_act = lang.clone();
+        show_duplicate_units_dialog(&lang, pairs, move |pair, action| {
+            let (first, second) = (pair.first.id, pair.second.id);
+            let done = {
+                let st = state_act.borrow();
+                let Some(db) = st.database() else {
+                    return false;
+                };
+                match &action {
+                    DuplicateAction::Merge => UnitService::new(db.conn()).merge_units(first, second),
+                    DuplicateAction::Rename(name) if name.is_empty() => {
+                        Err(anyhow::anyhow!(ui_tr(&lang_act, "Formation name cannot be empty")))
+                    }
+                    DuplicateAction::Rename(name) => UnitRepo::new(db.conn()).get_by_id(second).and_then(|unit| {
+                        let unit = unit.ok_or_else(|| anyhow::anyhow!("Unit {} not found", second))?;
+                        UnitService::new(db.conn()).update_properties(&Unit { name: name.clone(), ..unit })
+                    }),
+                    DuplicateAction::Ignore => IgnoredDuplicateRepo::new(db.conn()).ignore(lib_id, first, second),
+                }
+            };
+            match done {
+                Ok(()) => {
+                    match action {
+                        DuplicateAction::Merge => log::info!("Merged unit {} into {}", second, first),
+                        DuplicateAction::Rename(name) => log::info!("Renamed unit {} to {:?}", second, name),
+                        DuplicateAction::Ignore => log::info!("Units {} and {} are not duplicates", first, second),
+                    }
+                    notify_change(&state_act, ChangeEvent::UnitTreeChanged(lib_id));
+                    true
+                }
+                Err(e) => {
+                    log::error!("Failed to resolve duplicate units: {}", e);
+                    show_error_dialog(&lang_act, "Error", "Failed to resolve duplicate units: {}", &[&e]);
+                    false
+                }
+            }
+        });
+    });
+    let state_tags = state.clone();
+    let weak_tags = window.as_weak();
+    window.on_library_manage_tags(move || {
+        log::debug!("Library > Manage Tags");
+        let Some(lib_id) = state_tags.borrow().current_library.as_ref().and_then(|l| l.id) else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_tags
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        show_tags_editor(state_tags.clone(), lib_id, &lang);
+    });
+    let (state_clone, weak_window) = (state.clone(), window.as_weak());
+    window.on_library_export_library(move || {
+        log::debug!("Library > Export Library");
+        let lib_id = state_clone.borrow().current_library.as_ref().and_then(|l| l.id);
+        match lib_id {
+            Some(lib_id) => export_library_json(&weak_window, &state_clone, lib_id, None),
+            None => log::warn!("No library to export. Create or open a library first."),
+        }
+    });
+    let state_history = state.clone();
+    let weak_history = window.as_weak();
+    window.on_library_view_history(move || {
+        log::debug!("Library > View History");
+        let Some((lib_id, lib_name)) = state_history
+            .borrow()
+            .current_library
+            .as_ref()
+            .and_then(|l| l.id.map(|id| (id, l.name.clone())))
+        else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_history
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        show_history_window(state_history.clone(), weak_history.clone(), lib_id, &lib_name, &lang);
+    });
+    let state_snapshot = state.clone();
+    let weak_snapshot = window.as_weak();
+    window.on_library_create_snapshot(move || {
+        log::debug!("Library > Create Snapshot");
+        let Some(lib_id) = state_snapshot.borrow().current_library.as_ref().and_then(|l| l.id) else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_snapshot
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let (state_create, lang_create) = (state_snapshot.clone(), lang.clone());
+        show_snapshot_dialog(&lang, move |description| {
+            let created = {
+                let st = state_create.borrow();
+                let Some(manager) = st.library_manager.as_ref() else {
+                    log::error!("Database not initialized");
+                    return;
+                };
+                manager.create_snapshot(lib_id, &description)
+            };
+            match created {
+                Ok(snapshot) => {
+                    log::info!("Created snapshot v{} of library {}", snapshot.version, lib_id);
+                    if let Some(lib) = state_create.borrow_mut().current_library.as_mut() {
+                        lib.set_version(snapshot.version);
+                    }
+                    notify_change(&state_create, ChangeEvent::LibraryChanged(lib_id));
+                }
+                Err(e) => {
+                    log::error!("Failed to create snapshot: {}", e);
+                    show_error_dialog(&lang_create, "Error", "Failed to create snapshot: {}", &[&e]);
+                }
+            }
+        });
+    });
+    let state_compare = state.clone();
+    let weak_compare = window.as_weak();
+    window.on_library_compare_versions(move || {
+        log::debug!("Library > Compare Versions");
+        let Some(lib_id) = state_compare.borrow().current_library.as_ref().and_then(|l| l.id) else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_compare
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let snapshots = {
+            let st = state_compare.borrow();
+            let Some(manager) = st.library_manager.as_ref() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            manager.versions(lib_id)
+        };
+        match snapshots {
+            Ok(mut snapshots) if snapshots.len() >= 2 => {
+                snapshots.sort_by_key(|s| s.version);
+                let state_fork = state_compare.clone();
+                let weak_fork = weak_compare.clone();
+                show_compare_versions_dialog(&lang, snapshots, move |version| {
+                    open_version_as_new_library(&weak_fork, &state_fork, lib_id, version);
+                });
+            }
+            Ok(_) => show_error_dialog(
+                &lang,
+                "Compare Versions",
+                "The library has only one version; save it to create another.",
+                &[],
+            ),
+            Err(e) => {
+                log::error!("Failed to load versions: {}", e);
+                show_error_dialog(&lang, "Error", "Failed to load versions: {}", &[&e]);
+            }
+        }
+    });
+    let state_revert = state.clone();
+    let weak_revert = window.as_weak();
+    window.on_library_revert_to_version(move || {
+        log::debug!("Library > Revert to Version");
+        let Some((lib_id, lib_name)) = state_revert
+            .borrow()
+            .current_library
+            .as_ref()
+            .and_then(|l| l.id.map(|id| (id, l.name.clone())))
+        else {
+            log::warn!("No library selected");
+            return;
+        };
+        let lang = weak_revert
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let snapshots = {
+            let st = state_revert.borrow();
+            let Some(manager) = st.library_manager.as_ref() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            manager.versions(lib_id)
+        };
+        let snapshots = match snapshots {
+            Ok(snapshots) if snapshots.len() >= 2 => snapshots,
+            Ok(_) => {
+                show_error_dialog(
+                    &lang,
+                    "Revert to Version",
+                    "The library has only one version; save it to create another.",
+                    &[],
+                );
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to load versions: {}", e);
+                show_error_dialog(&lang, "Error", "Failed to load versions: {}", &[&e]);
+                return;
+            }
+        };
+        let state_pick = state_revert.clone();
+        let weak_pick = weak_revert.clone();
+        let lang_pick = lang.clone();
+        show_version_picker_dialog(&lang, "Revert to Version", "Revert", &snapshots, move |version| {
+            let message = ui_tr(&lang_pick, "Revert \"{}\" to version {}? The current state stays in the history.")
+                .replacen("{}", &lib_name, 1)
+                .replacen("{}", &version.to_string(), 1);
+            let spec = ConfirmSpec::new(
+                &ui_tr(&lang_pick, "Revert to Version"),
+                message,
+                &ui_tr(&lang_pick, "Revert"),
+            )
+            .danger();
+            let state_confirm = state_pick.clone();
+            let weak_confirm = weak_pick.clone();
+            let lang_confirm = lang_pick.clone();
+            confirm(&lang_pick, spec, move |ok| {
+                if !ok {
+                    return;
+                }
+                let reverted = {
+                    let st = state_confirm.borrow();
+                    let Some(manager) = st.library_manager.as_ref() else {
+                        log::error!("Database not initialized");
+                        return;
+                    };
+                    manager.revert_to_version(lib_id, version)
+                };
+                match reverted {
+                    Ok(snapshot) => {
+                        log::info!("Reverted library {} to v{} as v{}", lib_id, version, snapshot.version);
+                        notify_change(&state_confirm, ChangeEvent::LibraryChanged(lib_id));
+                        // Reload the library so the open tabs show the reverted state
+                        if let Some(w) = weak_confirm.upgrade() {
+                            w.invoke_library_selected(lib_id as i32);
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Failed to revert to version: {}", e);
+                        show_error_dialog(&lang_confirm, "Error", "Failed to revert to version: {}", &[&e]);
+                    }
+                }
+            });
+        });
+    });
 
     // Library delete: show confirmation dialog, then delete on confirm
     let state_clone = state.clone();
@@ -744,35 +2008,49 @@ fn setup_callbacks_with_state(
             .upgrade()
             .map(|w| w.get_current_language().to_string())
             .unwrap_or_else(|| "en".to_string());
-        let message = ui_tr(&lang, "Delete library \"{}\"? This will delete all versions.").replace("{}", &lib_name);
-        let dialog = match ConfirmDeleteDialog::new() {
-            Ok(d) => d,
-            Err(e) => {
-                log::error!("Failed to create confirm dialog: {}", e);
-                return;
-            }
+        // Libraries that copied branches or levels from this one lose the link
+        let copy_targets = match state_clone.borrow().database() {
+            Some(db) => crate::db::repositories::ProvenanceRepo::new(db.conn())
+                .copy_targets(lib_id)
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to look up copies of library {}: {}", lib_id, e);
+                    Vec::new()
+                }),
+            None => Vec::new(),
         };
-        dialog.set_dialog_title(ui_tr(&lang, "Delete library?").into());
-        dialog.set_cancel_text(ui_tr(&lang, "Cancel").into());
-        dialog.set_delete_text(ui_tr(&lang, "Delete").into());
-        dialog.set_message(message.into());
-        let weak_dialog1 = dialog.as_weak();
-        let weak_dialog2 = dialog.as_weak();
+        let mut message = ui_tr(&lang, "Delete library \"{}\"? This will delete all versions.").replace("{}", &lib_name);
+        if !copy_targets.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(&ui_tr_plural(
+                &lang,
+                copy_targets.len() as u64,
+                "{} library copied lists from this one. It will keep its data but lose the link.",
+                "{} libraries copied lists from this one. They will keep their data but lose the link.",
+            ));
+            for target in &copy_targets {
+                let copied: Vec<String> = target.copied.iter().map(|what| ui_tr(&lang, what.label())).collect();
+                message.push_str(&format!("\n• {}: {}", target.library_name, copied.join(", ")));
+            }
+        }
+        let spec = ConfirmSpec::new("Delete library?", message, "Delete")
+            .danger()
+            .require_text(lib_name);
         let state_for_confirm = state_clone.clone();
         let weak_window_confirm = weak_window.clone();
-        dialog.on_confirmed(move || {
-            if let Some(d) = weak_dialog1.upgrade() {
-                d.hide().unwrap_or_default();
+        let lang_confirm = lang.clone();
+        confirm(&lang, spec, move |confirmed| {
+            if !confirmed {
+                return;
             }
             let delete_ok = {
                 let state = state_for_confirm.borrow();
-                if let Some(ref db) = state.database {
+                if let Some(db) = state.database() {
                     let service = LibraryService::new(db.conn());
                     match service.delete_library(lib_id) {
                         Ok(_) => true,
                         Err(e) => {
                             log::error!("Failed to delete library: {}", e);
-                            show_error_dialog("Error", &format!("Failed to delete library: {}", e));
+                            show_error_dialog(&lang_confirm, "Error", "Failed to delete library: {}", &[&e]);
                             false
                         }
                     }
@@ -783,80 +2061,1096 @@ fn setup_callbacks_with_state(
             if delete_ok {
                 log::info!("Library deleted successfully");
                 state_for_confirm.borrow_mut().current_library = None;
+                notify_change(&state_for_confirm, ChangeEvent::LibraryChanged(lib_id));
                 if let Some(window) = weak_window_confirm.upgrade() {
                     window.set_current_library_name("".into());
                     window.set_current_library_id(-1);
-                    refresh_libraries_list(&window, state_for_confirm.clone());
+                    track_current_library(&window, &state_for_confirm);
                 }
             }
         });
-        dialog.on_cancelled(move || {
-            if let Some(d) = weak_dialog2.upgrade() {
-                d.hide().unwrap_or_default();
-            }
-        });
-        dialog.show().unwrap_or_default();
     });
 
     // Unit menu actions
-    window.on_unit_add_child(|| { log::debug!("Unit > Add Child"); show_error_dialog("Not implemented", "Add Child Formation is not yet implemented."); });
-    window.on_unit_delete(|| { log::debug!("Unit > Delete"); show_error_dialog("Not implemented", "Delete Formation is not yet implemented."); });
-    window.on_unit_move_up(|| { log::debug!("Unit > Move Up"); show_error_dialog("Not implemented", "Move Up is not yet implemented."); });
-    window.on_unit_move_down(|| { log::debug!("Unit > Move Down"); show_error_dialog("Not implemented", "Move Down is not yet implemented."); });
-    window.on_unit_summary_table(|| { log::debug!("Unit > Summary Table"); show_error_dialog("Not implemented", "Summary Table is not yet implemented."); });
-    window.on_unit_export(|| { log::debug!("Unit > Export"); show_error_dialog("Not implemented", "Export Formation is not yet implemented."); });
-    window.on_unit_view_history(|| { log::debug!("Unit > View History"); show_error_dialog("Not implemented", "View History is not yet implemented."); });
-    window.on_unit_create_snapshot(|| { log::debug!("Unit > Create Snapshot"); show_error_dialog("Not implemented", "Create Snapshot is not yet implemented."); });
-    window.on_unit_compare_versions(|| { log::debug!("Unit > Compare Versions"); show_error_dialog("Not implemented", "Compare Versions is not yet implemented."); });
-    window.on_unit_revert_to_version(|| { log::debug!("Unit > Revert to Version"); show_error_dialog("Not implemented", "Revert to Version is not yet implemented."); });
-
-    // View menu actions
-    window.on_view_table(|| { log::debug!("View > Table"); });
-    window.on_view_diagram(|| { log::debug!("View > Diagram"); });
-    window.on_view_table_and_diagram(|| { log::debug!("View > Table and Diagram"); });
-    window.on_view_symbols_nato(|| { log::debug!("View > Symbols NATO"); show_error_dialog("Not implemented", "NATO symbols are not yet implemented."); });
-    window.on_view_symbols_russia(|| { log::debug!("View > Symbols Russia"); show_error_dialog("Not implemented", "Russian symbols are not yet implemented."); });
-    window.on_view_load_symbols(|| { log::debug!("View > Load Symbols"); show_error_dialog("Not implemented", "Load Custom Symbols is not yet implemented."); });
-    // Theme switching callback
+    let state_clone = state.clone();
     let weak_window = window.as_weak();
-    window.on_switch_theme(move |theme: slint::SharedString| {
-        let theme_str = theme.to_string();
-        log::debug!("Switching theme to: {}", theme_str);
-
-        if let Some(w) = weak_window.upgrade() {
-            w.set_theme(theme.clone());
-            AppTheme::get(&w).set_mode(theme.clone());
-
-            // Save theme to settings
-            let mut settings = crate::config::Settings::load().unwrap_or_default();
-            settings.color_scheme = theme_str.clone();
-            if let Err(e) = settings.save() {
+    window.on_unit_add_child(move || {
+        log::debug!("Unit > Add Child");
+        let Some(w) = weak_window.upgrade() else {
+            return;
+        };
+        let lang = w.get_current_language().to_string();
+        let name = ui_tr(&lang, "New Formation");
+        let selected = w.get_selected_formation_id();
+        let (lib_id, added) = {
+            let state = state_clone.borrow();
+            let (Some(db), Some(lib_id)) = (state.database(), state.current_library.as_ref().and_then(|l| l.id)) else {
+                log::warn!("No library selected");
+                return;
+            };
+            let service = UnitService::new(db.conn()).with_tree_limits(state.tree_limits);
+            let parent = (selected >= 0).then_some(selected as i64);
+            let added = service.check_new_unit(lib_id, parent).and_then(|warnings| {
+                let unit = match parent {
+                    Some(parent_id) => service.add_child(parent_id, &name, "")?,
+                    None => service.add_root(lib_id, &name, "")?,
+                };
+                Ok((unit, warnings))
+            });
+            (lib_id, added)
+        };
+        match added {
+            Ok((unit, warnings)) => {
+                let id = unit.id.unwrap_or_default();
+                w.set_selected_formation_id(id as i32);
+                notify_change(&state_clone, ChangeEvent::UnitTreeChanged(lib_id));
+                if let Some(issue) = warnings.first() {
+                    w.invoke_announce(tree_limit_text(&lang, issue).into());
+                }
+            }
+            Err(e) => match e.downcast_ref::<TreeLimitIssue>() {
+                Some(issue) => show_error_dialog(&lang, "Add Child Formation", &tree_limit_text(&lang, issue), &[]),
+                None => {
+                    log::error!("Failed to add formation: {}", e);
+                    show_error_dialog(&lang, "Error", "Failed to add formation: {}", &[&e]);
+                }
+            },
+        }
+    });
+
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_unit_delete(move || {
+        log::debug!("Unit > Delete");
+        let Some(w) = weak_window.upgrade() else {
+            return;
+        };
+        let unit_id = w.get_selected_formation_id() as i64;
+        let lang = w.get_current_language().to_string();
+        let found = {
+            let state = state_clone.borrow();
+            let Some(db) = state.database() else {
+                return;
+            };
+            let repo = crate::db::repositories::UnitRepo::new(db.conn());
+            repo.get_by_id(unit_id)
+                .map(|unit| unit.map(|u| (u.name, repo.count_subtree(unit_id))))
+        };
+        let (name, subtree) = match found {
+            Ok(Some((name, Ok(subtree)))) => (name, subtree),
+            Ok(None) => {
+                show_error_dialog(&lang, "Delete Formation", "Select a formation first.", &[]);
+                return;
+            }
+            Ok(Some((_, Err(e)))) | Err(e) => {
+                log::error!("Failed to load formation: {}", e);
+                return;
+            }
+        };
+        let message = ui_tr(&lang, "Delete \"{}\" and its {} subordinate formations?")
+            .replacen("{}", &name, 1)
+            .replacen("{}", &(subtree - 1).to_string(), 1);
+        let spec = ConfirmSpec::new("Delete Formation", message, "Delete").danger();
+        let state_for_confirm = state_clone.clone();
+        let weak_window_confirm = weak_window.clone();
+        let lang_confirm = lang.clone();
+        confirm(&lang, spec, move |confirmed| {
+            if !confirmed {
+                return;
+            }
+            let deleted = {
+                let state = state_for_confirm.borrow();
+                let Some(db) = state.database() else {
+                    return;
+                };
+                UnitService::new(db.conn()).delete_subtree(unit_id)
+            };
+            match deleted {
+                Ok(count) => {
+                    log::info!("Deleted formation {} ({} units)", unit_id, count);
+                    if let Some(w) = weak_window_confirm.upgrade() {
+                        w.set_selected_formation_id(-1);
+                    }
+                    let lib_id = state_for_confirm.borrow().current_library.as_ref().and_then(|l| l.id);
+                    if let Some(lib_id) = lib_id {
+                        notify_change(&state_for_confirm, ChangeEvent::UnitTreeChanged(lib_id));
+                    }
+                }
+                Err(e) => {
+                    log::error!("Failed to delete formation: {}", e);
+                    show_error_dialog(&lang_confirm, "Error", "Failed to delete formation: {}", &[&e]);
+                }
+            }
+        });
+    });
+
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_unit_move_up(move || {
+        log::debug!("Unit > Move Up");
+        move_selected_formation(&weak_window, &state_clone, true);
+    });
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_unit_move_down(move || {
+        log::debug!("Unit > Move Down");
+        move_selected_formation(&weak_window, &state_clone, false);
+    });
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_unit_summary_table(move || {
+        log::debug!("Unit > Summary Table");
+        let Some(w) = weak_window.upgrade() else {
+            return;
+        };
+        let lang = w.get_current_language().to_string();
+        let unit_id = w.get_selected_formation_id();
+        if unit_id < 0 {
+            show_error_dialog(&lang, "Summary Table", "Select a formation first.", &[]);
+            return;
+        }
+        let summary = {
+            let st = state_clone.borrow();
+            let Some(db) = st.database() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            crate::services::summary::compute_summary(unit_id as i64, db.conn())
+        };
+        match summary {
+            Ok(summary) => show_summary_table_dialog(&lang, summary),
+            Err(e) => {
+                log::error!("Failed to compute summary table: {:#}", e);
+                show_error_dialog(&lang, "Summary Table", "{}", &[&format!("{:#}", e)]);
+            }
+        }
+    });
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_unit_export(move || {
+        log::debug!("Unit > Export");
+        export_selected_formation(&weak_window, &state_clone);
+    });
+    window.on_unit_view_history(not_implemented(window, "Unit > View History", "View History is not yet implemented."));
+    window.on_unit_create_snapshot(not_implemented(window, "Unit > Create Snapshot", "Create Snapshot is not yet implemented."));
+    window.on_unit_compare_versions(not_implemented(window, "Unit > Compare Versions", "Compare Versions is not yet implemented."));
+    window.on_unit_revert_to_version(not_implemented(window, "Unit > Revert to Version", "Revert to Version is not yet implemented."));
+
+    // View menu actions
+    window.on_view_table(|| { log::debug!("View > Table"); });
+    window.on_view_diagram(|| { log::debug!("View > Diagram"); });
+    window.on_view_table_and_diagram(|| { log::debug!("View > Table and Diagram"); });
+    window.on_view_symbols_nato(not_implemented(window, "View > Symbols NATO", "NATO symbols are not yet implemented."));
+    window.on_view_symbols_russia(not_implemented(window, "View > Symbols Russia", "Russian symbols are not yet implemented."));
+    window.on_view_load_symbols(not_implemented(window, "View > Load Symbols", "Load Custom Symbols is not yet implemented."));
+    // Theme switching callback
+    let weak_window = window.as_weak();
+    let state_theme = state.clone();
+    window.on_switch_theme(move |theme: slint::SharedString| {
+        let theme_str = theme.to_string();
+        log::debug!("Switching theme to: {}", theme_str);
+
+        if let Some(w) = weak_window.upgrade() {
+            w.set_theme(theme.clone());
+            AppTheme::get(&w).set_mode(theme.clone());
+            state_theme.borrow_mut().open_windows.set_theme(&theme_str);
+
+            // Save theme to settings
+            let mut settings = crate::config::Settings::load().unwrap_or_default();
+            settings.color_scheme = theme_str.clone();
+            if let Err(e) = settings.save() {
                 log::error!("Failed to save theme setting: {}", e);
             } else {
                 log::info!("Theme saved: {}", theme_str);
             }
         }
     });
-    window.on_view_show_images(|| { log::debug!("View > Show Images"); show_error_dialog("Not implemented", "Show Equipment Images is not yet implemented."); });
-    window.on_view_zoom_in(|| { log::debug!("View > Zoom In"); show_error_dialog("Not implemented", "Zoom In is not yet implemented."); });
-    window.on_view_zoom_out(|| { log::debug!("View > Zoom Out"); show_error_dialog("Not implemented", "Zoom Out is not yet implemented."); });
-    window.on_view_zoom_reset(|| { log::debug!("View > Zoom Reset"); show_error_dialog("Not implemented", "Reset Zoom is not yet implemented."); });
+    window.on_view_show_images(not_implemented(window, "View > Show Images", "Show Equipment Images is not yet implemented."));
+    // Formation numbering style
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_view_numbering(move |style: SharedString| {
+        log::debug!("View > Formation Numbering: {}", style);
+        state_clone.borrow_mut().update_numbering(|numbering| match NumberingStyle::from_code(style.as_str()) {
+            Some(s) => {
+                numbering.enabled = true;
+                numbering.style = s;
+            }
+            None => numbering.enabled = false,
+        });
+        if let Some(w) = weak_window.upgrade() {
+            refresh_numbered_views(&w, &state_clone);
+        }
+    });
+    // "As of" date of the current library
+    let state_clone = state.clone();
+    let weak_window = window.as_weak();
+    window.on_as_of_changed(move |text: SharedString| {
+        let Some(w) = weak_window.upgrade() else {
+            return;
+        };
+        let Some(lib_id) = state_clone.borrow().current_library.as_ref().and_then(|l| l.id) else {
+            return;
+        };
+        match parse_date(&text) {
+            Ok(date) => {
+                log::debug!("As of {:?} for library {}", date, lib_id);
+                let mut st = state_clone.borrow_mut();
+                match date {
+                    Some(date) => st.as_of.insert(lib_id, date),
+                    None => st.as_of.remove(&lib_id),
+                };
+            }
+            Err(_) => {
+                w.set_as_of_error(ui_tr(&w.get_current_language(), "Enter a date as YYYY-MM-DD").into());
+                return;
+            }
+        }
+        refresh_formations_list(&w, &state_clone.borrow());
+    });
+    let (weak_zoom, tabs_zoom) = (window.as_weak(), open_tabs_model.clone());
+    window.on_view_zoom_in(move || {
+        if let Some(w) = weak_zoom.upgrade() {
+            set_zoom(&w, &tabs_zoom, zoom::zoom_in(w.get_zoom_level()));
+        }
+    });
+    let (weak_zoom, tabs_zoom) = (window.as_weak(), open_tabs_model.clone());
+    window.on_view_zoom_out(move || {
+        if let Some(w) = weak_zoom.upgrade() {
+            set_zoom(&w, &tabs_zoom, zoom::zoom_out(w.get_zoom_level()));
+        }
+    });
+    let (weak_zoom, tabs_zoom) = (window.as_weak(), open_tabs_model.clone());
+    window.on_view_zoom_reset(move || {
+        if let Some(w) = weak_zoom.upgrade() {
+            set_zoom(&w, &tabs_zoom, zoom::DEFAULT_ZOOM);
+        }
+    });
     window.on_view_refresh(|| { log::debug!("View > Refresh"); });
 
     // Tools menu actions
-    window.on_tools_settings(|| { log::debug!("Tools > Settings"); show_error_dialog("Not implemented", "Settings dialog is not yet implemented."); });
+    let state_settings = state.clone();
+    let weak_settings = window.as_weak();
+    let tabs_settings = open_tabs_model.clone();
+    window.on_tools_settings(move || {
+        log::debug!("Tools > Settings");
+        let Some(w) = weak_settings.upgrade() else {
+            return;
+        };
+        let weak = weak_settings.clone();
+        let tabs = tabs_settings.clone();
+        let state = state_settings.clone();
+        show_settings_dialog(&w, state_settings.clone(), move || {
+            if let Some(w) = weak.upgrade() {
+                show_switched_database(&w, &tabs, &state);
+            }
+        });
+    });
     window.on_tools_language(|| { log::debug!("Tools > Language"); });
-    window.on_tools_data_paths(|| { log::debug!("Tools > Data Paths"); show_error_dialog("Not implemented", "Data Paths dialog is not yet implemented."); });
-    window.on_tools_reset_settings(|| { log::debug!("Tools > Reset Settings"); show_error_dialog("Not implemented", "Reset Settings is not yet implemented."); });
+    let state_paths = state.clone();
+    let weak_paths = window.as_weak();
+    let tabs_paths = open_tabs_model.clone();
+    window.on_tools_data_paths(move || {
+        log::debug!("Tools > Data Paths");
+        let Some(w) = weak_paths.upgrade() else {
+            return;
+        };
+        let weak = weak_paths.clone();
+        let tabs = tabs_paths.clone();
+        let state = state_paths.clone();
+        show_data_paths_dialog(&w.get_current_language(), state_paths.clone(), move || {
+            if let Some(w) = weak.upgrade() {
+                show_switched_database(&w, &tabs, &state);
+            }
+        });
+    });
+    let state_reset = state.clone();
+    let weak_reset = window.as_weak();
+    let tabs_reset = open_tabs_model.clone();
+    window.on_tools_reset_settings(move || {
+        log::debug!("Tools > Reset Settings");
+        let Some(w) = weak_reset.upgrade() else {
+            return;
+        };
+        let weak = weak_reset.clone();
+        let tabs = tabs_reset.clone();
+        let state = state_reset.clone();
+        reset_settings(&w, state_reset.clone(), move || {
+            if let Some(w) = weak.upgrade() {
+                show_switched_database(&w, &tabs, &state);
+            }
+        });
+    });
+    let weak_metrics = window.as_weak();
+    window.on_tools_performance_metrics(move || {
+        log::debug!("Tools > Performance Metrics");
+        if let Some(w) = weak_metrics.upgrade() {
+            show_metrics_dialog(&w.get_current_language());
+        }
+    });
+
+    let state_backup = state.clone();
+    let weak_backup = window.as_weak();
+    window.on_tools_backup_database(move || {
+        log::debug!("Tools > Back Up Database");
+        let lang = weak_backup
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let state = state_backup.clone();
+        choose_file(
+            FileRequest::save()
+                .filter("SQLite", &["db"])
+                .file_name(format!("toeditor-{}.db", chrono::Local::now().format("%Y-%m-%d"))),
+            move |path| {
+                let target = path.clone();
+                let work = move |manager: &LibraryManager| manager.database().backup_to(&target);
+                run_with_manager(&state, "Back Up Database", work, move |result| match result {
+                    Ok(()) => log::info!("Database backed up to {:?}", path),
+                    Err(e) => {
+                        log::error!("Failed to back up the database: {:#}", e);
+                        show_error_dialog(&lang, "Backup Error", "Failed to back up the database: {}", &[&format!("{:#}", e)]);
+                    }
+                });
+            },
+        );
+    });
+
+    let state_schema = state.clone();
+    let weak_schema = window.as_weak();
+    window.on_tools_export_schema(move || {
+        log::debug!("Tools > Export Database Schema");
+        let lang = weak_schema
+            .upgrade()
+            .map(|w| w.get_current_language().to_string())
+            .unwrap_or_else(|| "en".to_string());
+        let doc = {
+            let st = state_schema.borrow();
+            let Some(db) = st.database() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            match crate::db::schema_doc::generate(db.conn()) {
+                Ok(doc) => doc,
+                Err(e) => {
+                    log::error!("Failed to read the database schema: {}", e);
+                    show_error_dialog(&lang, "Export Error", "Failed to read the database schema: {}", &[&e]);
+                    return;
+                }
+            }
+        };
+        choose_file(
+            FileRequest::save()
+                .filter("SQL", &["sql"])
+                .file_name(format!("toeditor-schema-v{}.sql", doc.version)),
+            move |path| {
+                // The description goes next to the SQL, as <name>.md
+                let markdown_path = path.with_extension("md");
+                let result = std::fs::write(&path, &doc.sql).and_then(|()| std::fs::write(&markdown_path, &doc.markdown));
+                match result {
+                    Ok(()) => log::info!("Database schema exported to {:?} and {:?}", path, markdown_path),
+                    Err(e) => {
+                        log::error!("Failed to export the database schema: {}", e);
+                        show_error_dialog(&lang, "Export Error", "Failed to export the database schema: {}", &[&e]);
+                    }
+                }
+            },
+        );
+    });
 
     // Help menu actions
-    window.on_help_user_guide(|| { log::debug!("Help > User Guide"); show_error_dialog("Not implemented", "User Guide is not yet available."); });
-    window.on_help_about(|| { log::debug!("Help > About"); show_error_dialog("About TOEditor", "TOEditor - Table of Organization Editor\nA desktop application for creating and managing military organizational structures."); });
-    window.on_help_check_updates(|| { log::debug!("Help > Check Updates"); show_error_dialog("Not implemented", "Check for Updates is not yet implemented."); });
+    window.on_help_user_guide(not_implemented(window, "Help > User Guide", "User Guide is not yet available."));
+    let weak_about = window.as_weak();
+    window.on_help_about(move || {
+        log::debug!("Help > About");
+        let lang = weak_about.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+        show_error_dialog(
+            &lang,
+            "About TOEditor",
+            "TOEditor - Table of Organization Editor\nA desktop application for creating and managing military organizational structures.",
+            &[],
+        );
+    });
+    window.on_help_check_updates(not_implemented(window, "Help > Check Updates", "Check for Updates is not yet implemented."));
+
+    let state_sample = state.clone();
+    let weak_sample = window.as_weak();
+    window.on_help_create_sample_library(move || {
+        log::debug!("Help > Create Sample Library");
+        let existing: Vec<String> = {
+            let st = state_sample.borrow();
+            let Some(db) = st.database() else {
+                log::error!("Database not initialized");
+                return;
+            };
+            match LibraryService::new(db.conn()).list_libraries() {
+                Ok(libraries) => libraries.into_iter().map(|l| l.name).collect(),
+                Err(e) => {
+                    log::error!("Failed to list libraries: {}", e);
+                    Vec::new()
+                }
+            }
+        };
+        // Goes through the regular import so the sample exercises that path too
+        match crate::services::sample::sample_library(&existing) {
+            Ok(export) => complete_library_import(LibraryImport::Full(export), &state_sample, &weak_sample),
+            Err(e) => {
+                log::error!("Failed to load sample library: {:#}", e);
+                let lang = weak_sample.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+                show_error_dialog(&lang, "Import Error", "Failed to import library: {}", &[&format!("{:#}", e)]);
+            }
+        }
+    });
 
     Ok(())
 }
 
+/// Publish a change event without holding a borrow of the state while handlers run.
+pub(crate) fn notify_change(state: &Rc<RefCell<AppState>>, event: ChangeEvent) {
+    let bus = {
+        let st = state.borrow();
+        let mut dirty = st.dirty.get();
+        dirty.record(&event);
+        st.dirty.set(dirty);
+        st.events.clone()
+    };
+    bus.publish(event);
+}
+
+/// Publish a save of unit `unit_id`'s personnel and equipment rows, counting
+/// each row written as an unsaved change. A save that wrote nothing changes
+/// nothing.
+pub(crate) fn notify_rows_saved(state: &Rc<RefCell<AppState>>, unit_id: i64, changes: RowChanges) {
+    if changes.is_empty() {
+        return;
+    }
+    let bus = {
+        let st = state.borrow();
+        let mut dirty = st.dirty.get();
+        dirty.record_rows(changes);
+        st.dirty.set(dirty);
+        st.events.clone()
+    };
+    bus.publish(ChangeEvent::UnitChanged(unit_id));
+}
+
+/// Change the unsaved-changes state and show it in the window title
+pub(crate) fn update_dirty(window: &MainWindow, state: &RefCell<AppState>, change: impl FnOnce(&mut DirtyState)) {
+    let st = state.borrow();
+    let mut dirty = st.dirty.get();
+    change(&mut dirty);
+    st.dirty.set(dirty);
+    window.set_library_dirty(dirty.is_dirty());
+}
+
+/// Whether the main window may close now. With operations still running or
+/// unsaved changes, asks the user first and closes later if they agree.
+fn request_exit(window: &MainWindow, state: &Rc<RefCell<AppState>>) -> bool {
+    let lang = window.get_current_language();
+    let operations = state.borrow().operations.clone();
+    if !operations.is_idle() {
+        show_running_operations_dialog(&lang, operations);
+        return false;
+    }
+    if !state.borrow().dirty.get().is_dirty() {
+        return true;
+    }
+    let name = state.borrow().current_library.as_ref().map(|l| l.name.clone()).unwrap_or_default();
+    let (weak_save, weak_discard) = (window.as_weak(), window.as_weak());
+    let (state_save, state_discard) = (state.clone(), state.clone());
+    show_unsaved_changes_dialog(
+        state,
+        &lang,
+        &name,
+        move || {
+            if let Some(window) = weak_save.upgrade() {
+                window.invoke_file_save_library();
+                // A failed save keeps the changes, and the app, open
+                if !state_save.borrow().dirty.get().is_dirty() {
+                    let _ = window.hide();
+                }
+            }
+        },
+        move || {
+            if let Some(window) = weak_discard.upgrade() {
+                update_dirty(&window, &state_discard, DirtyState::discarded);
+                let _ = window.hide();
+            }
+        },
+    );
+    false
+}
+
+/// Count unsaved changes for the library that is now current
+pub(crate) fn track_current_library(window: &MainWindow, state: &RefCell<AppState>) {
+    let current = state.borrow().current_library.as_ref().and_then(|l| l.id);
+    update_dirty(window, state, |dirty| dirty.open(current));
+}
+
+/// Subscribe the main window views to the change bus.
+/// Callback for a menu item whose action does not exist yet: logs `item`
+/// and tells the user so with the translated `message`.
+fn not_implemented(window: &MainWindow, item: &'static str, message: &'static str) -> impl Fn() + 'static {
+    let weak = window.as_weak();
+    move || {
+        log::debug!("{}", item);
+        let lang = weak.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+        show_error_dialog(&lang, "Not implemented", message, &[]);
+    }
+}
+
+/// Autosave the open library at the interval in `settings` while it has
+/// unsaved changes, skipping a tick when nothing changed since the last
+/// autosave. The interval is read once, at startup.
+fn start_autosave(state: &Rc<RefCell<AppState>>, settings: &crate::config::Settings) -> slint::Timer {
+    let timer = slint::Timer::default();
+    let Some(interval) = settings.autosave_interval() else {
+        log::info!("Autosave is off");
+        return timer;
+    };
+    let keep = settings.autosave_keep;
+    let state = Rc::downgrade(state);
+    let last_autosaved = Cell::new(None::<(i64, u32)>);
+    timer.start(slint::TimerMode::Repeated, interval, move || {
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        let st = state.borrow();
+        let dirty = st.dirty.get();
+        let Some(lib_id) = dirty.library_id().filter(|_| dirty.is_dirty()) else {
+            return;
+        };
+        if last_autosaved.get() == Some((lib_id, dirty.changes())) {
+            return;
+        }
+        let Some(manager) = st.library_manager.as_ref() else {
+            return;
+        };
+        match manager.create_autosave_snapshot(lib_id, keep) {
+            Ok(snapshot) => {
+                last_autosaved.set(Some((lib_id, dirty.changes())));
+                log::info!("Autosaved library {} at v{}", lib_id, snapshot.version);
+            }
+            Err(e) => log::error!("Failed to autosave library {}: {:#}", lib_id, e),
+        }
+    });
+    timer
+}
+
+fn subscribe_view_refresh(window: &MainWindow, state: Rc<RefCell<AppState>>) -> Subscription {
+    let weak = window.as_weak();
+    let bus = state.borrow().events.clone();
+    let state_weak = Rc::downgrade(&state);
+    bus.subscribe(move |event| {
+        let (Some(w), Some(state)) = (weak.upgrade(), state_weak.upgrade()) else {
+            return;
+        };
+        let current = state.borrow().current_library.as_ref().and_then(|l| l.id);
+        w.set_library_dirty(state.borrow().dirty.get().is_dirty());
+        if let ChangeEvent::UnitTreeChanged(_) = *event {
+            let open: Vec<i64> = w.get_open_tabs().iter().map(|tab| i64::from(tab.id)).collect();
+            for unit_id in open {
+                retitle_open_tab(&w, &state, unit_id);
+            }
+        }
+        for target in event.refresh_targets(current) {
+            match target {
+                RefreshTarget::LibrariesList => refresh_libraries_list(&w, state.clone()),
+                RefreshTarget::FormationsTree => {
+                    if current.is_some() {
+                        refresh_formations_list(&w, &state.borrow());
+                    }
+                }
+                RefreshTarget::FormationCounts => {
+                    if current.is_some() {
+                        refresh_formation_counts(&w, &state.borrow());
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Retitle the tab of unit `unit_id`, if it has one, after units changed
+fn retitle_open_tab(window: &MainWindow, state: &Rc<RefCell<AppState>>, unit_id: i64) {
+    if !window.get_open_tabs().iter().any(|tab| i64::from(tab.id) == unit_id) {
+        return;
+    }
+    let name = {
+        let st = state.borrow();
+        let Some(db) = st.database() else {
+            return;
+        };
+        match UnitRepo::new(db.conn()).get_by_id(unit_id) {
+            Ok(unit) => unit.map(|u| u.name),
+            Err(e) => {
+                log::error!("Failed to load formation {}: {}", unit_id, e);
+                return;
+            }
+        }
+    };
+    if let Some(name) = name {
+        window.invoke_tab_rename(unit_id as i32, name.into());
+    }
+}
+
+/// Copy one version of a library into a new library and select it
+fn open_version_as_new_library(weak_window: &Weak<MainWindow>, state: &Rc<RefCell<AppState>>, library_id: i64, version: i64) {
+    let forked = {
+        let st = state.borrow();
+        let Some(manager) = st.library_manager.as_ref() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        // A prune running on another connection leaves the version alone
+        let _hold = st.operations.hold_versions(library_id, &[version]);
+        manager.fork_from_version(library_id, version)
+    };
+    match forked {
+        Ok(library) => {
+            let id = library.id.unwrap_or_default();
+            log::info!("Opened v{} of library {} as library {}", version, library_id, id);
+            notify_change(state, ChangeEvent::LibraryChanged(id));
+            if let Some(w) = weak_window.upgrade() {
+                w.invoke_library_selected(id as i32);
+            }
+        }
+        Err(e) => {
+            log::error!("Failed to open version as new library: {}", e);
+            let lang = weak_window.upgrade().map(|w| w.get_current_language().to_string()).unwrap_or_default();
+            show_error_dialog(&lang, "Error", "Failed to open version as new library: {}", &[&e]);
+        }
+    }
+}
+
+/// Export library `lib_id` to a JSON file, asking how to write it and where;
+/// `anonymize` presets the dialog to strip the author and notes
+fn export_library_json(
+    weak_window: &Weak<MainWindow>,
+    state: &Rc<RefCell<AppState>>,
+    lib_id: i64,
+    anonymize: Option<export::AnonymizeOptions>,
+) {
+    let Some(w) = weak_window.upgrade() else {
+        return;
+    };
+    let lang = w.get_current_language().to_string();
+    let lib_name = state.borrow().current_library.as_ref().map(|l| l.name.clone()).unwrap_or_default();
+    let title = if anonymize.is_some() { "Export Anonymized Library" } else { "Export Library" };
+    let initial = export::ExportOptions { include_versions: anonymize.is_some(), ..export::ExportOptions::default() };
+    let (state_ok, lang_ok) = (state.clone(), lang.clone());
+    show_export_options_dialog(&lang, state, title, true, initial, anonymize, move |options, anonymize| {
+        let (state, lang) = (state_ok.clone(), lang_ok.clone());
+        choose_file(
+            FileRequest::save()
+                .filter("JSON", &["json"])
+                .file_name(export::default_file_name(&lib_name, "json")),
+            move |path| {
+                let target = path.clone();
+                let work = move |manager: &LibraryManager| match &anonymize {
+                    Some(anonymize) => manager.export_anonymized(lib_id, &target, anonymize),
+                    None => manager.export_json(lib_id, &target, &options),
+                };
+                run_with_manager(&state, title, work, move |result| match result {
+                    Ok(()) => log::info!("Library exported to: {:?}", path),
+                    Err(e) => {
+                        log::error!("Failed to export library: {:#}", e);
+                        show_error_dialog(&lang, "Export Error", "Failed to export library: {}", &[&format!("{:#}", e)]);
+                    }
+                });
+            },
+        );
+    });
+}
+
+/// Announce how many diagrams per formation were written to `dir`, and show
+/// the ones that failed
+fn report_diagrams_per_formation(weak: &Weak<MainWindow>, lang: &str, dir: &std::path::Path, report: &export::DiagramBatchReport) {
+    log::info!("Exported {} of {} diagrams to {}", report.succeeded(), report.results.len(), dir.display());
+    let summary = ui_tr(lang, "Exported {} of {} diagrams to {}")
+        .replacen("{}", &report.succeeded().to_string(), 1)
+        .replacen("{}", &report.results.len().to_string(), 1)
+        .replacen("{}", &dir.display().to_string(), 1);
+    if let Some(w) = weak.upgrade() {
+        w.invoke_announce(summary.clone().into());
+    }
+    let failures: Vec<String> = report
+        .failed()
+        .map(|r| format!("{}: {}", r.unit_name, r.outcome.as_ref().err().map(String::as_str).unwrap_or_default()))
+        .collect();
+    if report.results.is_empty() {
+        show_error_dialog(lang, "Export Error", "No formations at that level.", &[]);
+    } else if !failures.is_empty() {
+        show_error_dialog(lang, "Export Error", "{}\n\n{}", &[&summary, &failures.join("\n")]);
+    }
+}
+
+/// Save the selected formation with everything below it to a JSON file
+fn export_selected_formation(weak_window: &Weak<MainWindow>, state: &Rc<RefCell<AppState>>) {
+    let Some(w) = weak_window.upgrade() else {
+        return;
+    };
+    let lang = w.get_current_language().to_string();
+    let unit_id = w.get_selected_formation_id() as i64;
+    let name = {
+        let st = state.borrow();
+        let Some(db) = st.database() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        crate::db::repositories::UnitRepo::new(db.conn()).get_by_id(unit_id).ok().flatten().map(|u| u.name)
+    };
+    let Some(name) = name else {
+        show_error_dialog(&lang, "Export Formation", "Select a formation first.", &[]);
+        return;
+    };
+    let state = state.clone();
+    choose_file(
+        FileRequest::save()
+            .filter("TOEditor formation", &["json"])
+            .file_name(export::default_file_name(&name, "json")),
+        move |path| {
+            let target = path.clone();
+            let work = move |manager: &LibraryManager| export::export_formation_json(unit_id, manager.database().conn(), &target);
+            run_with_manager(&state, "Export Formation", work, move |result| match result {
+                Ok(()) => log::info!("Formation {} exported to: {:?}", unit_id, path),
+                Err(e) => {
+                    log::error!("Failed to export formation: {:#}", e);
+                    show_error_dialog(&lang, "Export Error", "{}", &[&format!("{:#}", e)]);
+                }
+            });
+        },
+    );
+}
+
+/// Add a formation from a JSON file to the open library, under the selected
+/// formation or at the top level when none is selected
+fn import_formation_into_current(weak_window: &Weak<MainWindow>, state: &Rc<RefCell<AppState>>) {
+    let Some(w) = weak_window.upgrade() else {
+        return;
+    };
+    let lang = w.get_current_language().to_string();
+    let Some(lib_id) = state.borrow().current_library.as_ref().and_then(|l| l.id) else {
+        show_error_dialog(&lang, "Import Formation", "Open a library first.", &[]);
+        return;
+    };
+    let selected = w.get_selected_formation_id();
+    let parent_id = (selected >= 0).then_some(selected as i64);
+    let state = state.clone();
+    let weak_window = weak_window.clone();
+    choose_file(FileRequest::open().filter("TOEditor formation", &["json"]), move |path| {
+        let imported = {
+            let st = state.borrow();
+            let Some(db) = st.database() else {
+                return;
+            };
+            export::import_formation_json(&path, lib_id, parent_id, st.tree_limits, db.conn())
+        };
+        match imported {
+            Ok(import) => {
+                log::info!("Imported {} units from {:?} into library {}", import.unit_count, path, lib_id);
+                notify_change(&state, ChangeEvent::UnitTreeChanged(lib_id));
+                if let Some(w) = weak_window.upgrade() {
+                    w.set_selected_formation_id(import.unit_id as i32);
+                    w.invoke_announce(
+                        ui_tr(&lang, "Imported {} formations").replacen("{}", &import.unit_count.to_string(), 1).into(),
+                    );
+                    if let Some(issue) = import.limit_warnings.first() {
+                        w.invoke_announce(tree_limit_text(&lang, issue).into());
+                    }
+                }
+                if !import.warnings.is_empty() {
+                    let lines: Vec<String> = import
+                        .warnings
+                        .iter()
+                        .map(|u| {
+                            ui_tr(&lang, "\"{}\": no formation level \"{}\" in this library")
+                                .replacen("{}", &u.unit_name, 1)
+                                .replacen("{}", &u.level, 1)
+                        })
+                        .collect();
+                    show_error_dialog(
+                        &lang,
+                        "Import Formation",
+                        "Imported without a formation level:\n\n{}",
+                        &[&lines.join("\n")],
+                    );
+                }
+            }
+            Err(e) => match e.downcast_ref::<TreeLimitIssue>() {
+                Some(issue) => show_error_dialog(&lang, "Import Formation", &tree_limit_text(&lang, issue), &[]),
+                None => {
+                    log::error!("Failed to import formation from {:?}: {:#}", path, e);
+                    show_error_dialog(&lang, "Import Error", "{}", &[&format!("{:#}", e)]);
+                }
+            },
+        }
+    });
+}
+
+/// Zoom the current tab's view to `level`, and remember it as the zoom of
+/// new tabs and the next session
+fn set_zoom(window: &MainWindow, tabs: &VecModel<FormationTab>, level: f32) {
+    let level = zoom::clamp_zoom(level);
+    window.set_zoom_level(level);
+    if let Some(index) = usize::try_from(window.get_current_tab_index()).ok().filter(|&i| i < tabs.row_count()) {
+        if let Some(mut tab) = tabs.row_data(index) {
+            tab.zoom = level;
+            tabs.set_row_data(index, tab);
+        }
+    }
+    let mut settings = crate::config::Settings::load().unwrap_or_default();
+    if settings.zoom != level {
+        settings.zoom = level;
+        if let Err(e) = settings.save() {
+            log::error!("Failed to save settings: {}", e);
+        }
+    }
+}
+
+/// Libraries added to the sidebar selection with Ctrl+click, in list order
+/// Show a tab's title, view mode and, when it is being compared, the compared rows
+fn show_tab(window: &MainWindow, state: &Rc<RefCell<AppState>>, tab: &FormationTab) {
+    window.set_current_tab_title(tab.title.clone());
+    window.set_current_tab_view_mode(tab.view_mode.clone());
+    window.set_zoom_level(zoom::clamp_zoom(tab.zoom));
+    let rows = if tab.compare_version > 0 {
+        match formation_compare_rows(state, tab.id as i64, tab.compare_version as i64) {
+            Ok(rows) => rows,
+            Err(e) => {
+                log::error!("Failed to compare formation {} with v{}: {}", tab.id, tab.compare_version, e);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let lang = window.get_current_language().to_string();
+    window.set_compare_banner(
+        ui_tr(&lang, "Compared with version {}")
+            .replacen("{}", &tab.compare_version.to_string(), 1)
+            .into(),
+    );
+    window.set_compare_rows(ModelRc::new(VecModel::from(rows)));
+    window.set_current_tab_compare_version(tab.compare_version);
+}
+
+/// Show the formation tree and the compare table of the current tab again
+/// after the numbering options changed
+fn refresh_numbered_views(window: &MainWindow, state: &Rc<RefCell<AppState>>) {
+    if window.get_current_library_id() >= 0 {
+        refresh_formations_list(window, &state.borrow());
+    }
+    let tab = usize::try_from(window.get_current_tab_index())
+        .ok()
+        .and_then(|i| window.get_open_tabs().row_data(i));
+    if let Some(tab) = tab.filter(|t| t.compare_version > 0) {
+        show_tab(window, state, &tab);
+    }
+}
+
+/// Rows of formation `unit_id` aligned with the same formation in `version` of its library
+fn formation_compare_rows(state: &Rc<RefCell<AppState>>, unit_id: i64, version: i64) -> Result<Vec<CompareRow>> {
+    let st = state.borrow();
+    let db = st.database().ok_or_else(|| anyhow::anyhow!("Database not initialized"))?;
+    let repo = crate::db::repositories::UnitRepo::new(db.conn());
+    let (lib_id, _) = repo
+        .get_placement(unit_id)?
+        .ok_or_else(|| anyhow::anyhow!("Formation {} not found", unit_id))?;
+    let current = repo
+        .get_by_id(unit_id)?
+        .ok_or_else(|| anyhow::anyhow!("Formation {} not found", unit_id))?;
+    let units = repo.get_by_library_id(lib_id)?;
+    let path = unit_path(&units, unit_id).unwrap_or_default();
+    let old_library = LibraryService::new(db.conn())
+        .restore_from_version(lib_id, version)?
+        .ok_or_else(|| anyhow::anyhow!("Version {} not found", version))?;
+    let old = find_unit(&old_library.units, unit_id, &path)
+        .ok_or_else(|| anyhow::anyhow!("The formation is not in version {}", version))?;
+    // Numbered after comparing, so a new ordinal alone does not mark a row changed
+    let numbering = st.numbering();
+    let prefixes = (unit_prefixes(&units, &numbering), unit_prefixes(&old_library.units, &numbering));
+    Ok(compare_formation(old, &current).iter().map(|row| compare_row(row, &prefixes)).collect())
+}
+
+/// Compare table row; `prefixes` number the current and the older tree by unit id
+fn compare_row(row: &ComparedRow, prefixes: &(HashMap<i64, String>, HashMap<i64, String>)) -> CompareRow {
+    let prefix = |numbered: &HashMap<i64, String>| row.id.and_then(|id| numbered.get(&id)).cloned().unwrap_or_default();
+    let (current, old) = prefixes;
+    let shown = if row.mark == RowMark::Removed { old } else { current };
+    let mark = match row.mark {
+        RowMark::Unchanged => "",
+        RowMark::Added => "added",
+        RowMark::Removed => "removed",
+        RowMark::Changed => "changed",
+    };
+    CompareRow {
+        mark: mark.into(),
+        name: format!("{}{}", prefix(shown), row.name).into(),
+        old_name: row.old_name.as_ref().map(|name| format!("{}{}", prefix(old), name)).unwrap_or_default().into(),
+        unit_type: row.unit_type.clone().into(),
+        depth: row.depth as i32,
+        personnel: row.personnel.text().into(),
+        personnel_changed: row.personnel.is_changed(),
+        equipment: row.equipment.text().into(),
+        equipment_changed: row.equipment.is_changed(),
+    }
+}
+
+/// Map the library's unit types where they name a level outright and show how
+/// many units wait for review in the Library menu
+fn refresh_level_mapping_count(window: &MainWindow, state: &AppState, library_id: i64) {
+    let Some(db) = state.database() else {
+        return;
+    };
+    match LevelMappingService::new(db.conn()).scan_library(library_id) {
+        Ok(outcome) => {
+            if outcome.mapped > 0 {
+                log::info!("Mapped {} unit types to formation levels in library {}", outcome.mapped, library_id);
+            }
+            window.set_level_mapping_count(outcome.pending as i32);
+        }
+        Err(e) => log::error!("Failed to map unit types to formation levels: {}", e),
+    }
+}
+
+fn multi_selected_library_ids(window: &MainWindow) -> Vec<i64> {
+    window
+        .get_libraries()
+        .iter()
+        .filter(|item| item.selected)
+        .map(|item| item.id as i64)
+        .collect()
+}
+
+/// Flip one library's Ctrl+click selection, or clear the selection with `None`
+fn toggle_library_selection(window: &MainWindow, library_id: Option<i32>) {
+    let libraries = window.get_libraries();
+    for row in 0..libraries.row_count() {
+        let Some(mut item) = libraries.row_data(row) else {
+            continue;
+        };
+        let selected = match library_id {
+            Some(id) if id == item.id => !item.selected,
+            Some(_) => item.selected,
+            None => false,
+        };
+        if selected != item.selected {
+            item.selected = selected;
+            libraries.set_row_data(row, item);
+        }
+    }
+}
+
+/// Bulk tag editing from the sidebar context menu. Acts on the whole selection
+/// (the open library plus Ctrl+clicked ones) when the clicked library is part
+/// of it, otherwise on the clicked library alone.
+fn edit_library_tags(weak_window: &Weak<MainWindow>, state: &Rc<RefCell<AppState>>, clicked_id: i32) {
+    let Some(w) = weak_window.upgrade() else {
+        return;
+    };
+    let mut selection = multi_selected_library_ids(&w);
+    let current = w.get_current_library_id() as i64;
+    if current >= 0 && !selection.contains(&current) {
+        selection.insert(0, current);
+    }
+    let clicked_id = clicked_id as i64;
+    let library_ids = if selection.len() > 1 && selection.contains(&clicked_id) {
+        selection
+    } else {
+        vec![clicked_id]
+    };
+    let tag_sets: Result<Vec<Vec<String>>> = {
+        let st = state.borrow();
+        let Some(db) = st.database() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        let service = LibraryService::new(db.conn());
+        library_ids
+            .iter()
+            .map(|&id| Ok(service.get_library(id)?.map(|l| l.tags).unwrap_or_default()))
+            .collect()
+    };
+    let lang = w.get_current_language().to_string();
+    let tag_sets = match tag_sets {
+        Ok(sets) => sets,
+        Err(e) => {
+            log::error!("Failed to load library tags: {}", e);
+            show_error_dialog(&lang, "Error", "Failed to load library tags: {}", &[&e]);
+            return;
+        }
+    };
+    let (state, lang_apply) = (state.clone(), lang.clone());
+    show_tags_dialog(&lang, library_ids.len(), tag_states(&tag_sets), move |add, remove| {
+        let applied = {
+            let st = state.borrow();
+            let Some(db) = st.database() else {
+                return;
+            };
+            let service = LibraryService::new(db.conn());
+            service.apply_bulk_tags(&library_ids, &add, &remove).and_then(|changed| {
+                let current = match st.current_library.as_ref().and_then(|l| l.id) {
+                    Some(id) if library_ids.contains(&id) => service.get_library(id)?,
+                    _ => None,
+                };
+                Ok((changed, current))
+            })
+        };
+        match applied {
+            Ok((changed, current)) => {
+                log::info!("Updated tags of {} of {} libraries", changed, library_ids.len());
+                if let Some(lib) = current {
+                    state.borrow_mut().current_library = Some(lib);
+                }
+                for &id in &library_ids {
+                    notify_change(&state, ChangeEvent::LibraryChanged(id));
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to update tags: {}", e);
+                show_error_dialog(&lang_apply, "Error", "Failed to update tags: {}", &[&e]);
+            }
+        }
+    });
+}
+
+/// Swap the formation selected in the tree with its previous (`up`) or next sibling
+fn move_selected_formation(weak_window: &Weak<MainWindow>, state: &Rc<RefCell<AppState>>, up: bool) {
+    let Some(w) = weak_window.upgrade() else {
+        return;
+    };
+    let unit_id = w.get_selected_formation_id();
+    if unit_id < 0 {
+        return;
+    }
+    let moved = {
+        let state = state.borrow();
+        let Some(db) = state.database() else {
+            return;
+        };
+        let service = UnitService::new(db.conn());
+        if up {
+            service.move_up(unit_id as i64)
+        } else {
+            service.move_down(unit_id as i64)
+        }
+    };
+    match moved {
+        Ok(true) => {
+            let lib_id = state.borrow().current_library.as_ref().and_then(|l| l.id);
+            if let Some(lib_id) = lib_id {
+                notify_change(state, ChangeEvent::UnitTreeChanged(lib_id));
+            }
+        }
+        Ok(false) => {}
+        Err(e) => {
+            log::error!("Failed to move formation: {}", e);
+            show_error_dialog(&w.get_current_language(), "Error", "Failed to move formation: {}", &[&e]);
+        }
+    }
+}
+
 /// Ensure the given library is loaded as current; select it in UI if needed.
 fn select_library_if_needed(state: Rc<RefCell<AppState>>, window: &MainWindow, library_id: i32) {
     let need_load = {
@@ -866,7 +3160,7 @@ fn select_library_if_needed(state: Rc<RefCell<AppState>>, window: &MainWindow, l
     if need_load {
         let lib_result = {
             let st = state.borrow();
-            if let Some(ref db) = st.database {
+            if let Some(db) = st.database() {
                 let svc = LibraryService::new(db.conn());
                 svc.get_library(library_id as i64)
             } else {
@@ -877,42 +3171,246 @@ fn select_library_if_needed(state: Rc<RefCell<AppState>>, window: &MainWindow, l
             state.borrow_mut().current_library = Some(lib.clone());
             window.set_current_library_name(lib.name.clone().into());
             window.set_current_library_id(library_id);
+            track_current_library(window, &state);
         }
     }
 }
 
+/// Update the personnel and equipment totals of the formations tree in place,
+/// leaving the rows, their order and the selection as they are
+fn refresh_formation_counts(window: &MainWindow, state: &AppState) {
+    crate::services::metrics::timed("refresh_formation_counts", || {
+        let (Some(db), Some(lib_id)) = (state.database(), state.current_library.as_ref().and_then(|l| l.id)) else {
+            return;
+        };
+        let counts = match UnitRepo::new(db.conn()).subtree_counts(lib_id) {
+            Ok(counts) => counts,
+            Err(e) => {
+                log::error!("Failed to total formation strength: {}", e);
+                return;
+            }
+        };
+        let formations = window.get_formations();
+        for row in 0..formations.row_count() {
+            let Some(mut item) = formations.row_data(row) else {
+                continue;
+            };
+            if item.more > 0 {
+                continue;
+            }
+            let (personnel, equipment) = counts.get(&i64::from(item.id)).map_or((0, 0), |c| (c.0 as i32, c.1 as i32));
+            if (item.personnel_count, item.equipment_count) != (personnel, equipment) {
+                item.personnel_count = personnel;
+                item.equipment_count = equipment;
+                formations.set_row_data(row, item);
+            }
+        }
+    });
+}
+
 /// Refresh formations list in the UI (placeholder until we have real formation tree).
-fn refresh_formations_list(window: &MainWindow) {
-    let formations = vec![
-        FormationTreeItem {
-            id: 1,
-            name: "Root formation".into(),
-            depth: 0,
-        },
-    ];
-    window.set_formations(ModelRc::new(VecModel::from(formations)));
+fn refresh_formations_list(window: &MainWindow, state: &AppState) {
+    crate::services::metrics::timed("refresh_formations_list", || {
+        let (mut nodes, counts) = match (state.database(), state.current_library.as_ref().and_then(|l| l.id)) {
+            (Some(db), Some(lib_id)) => {
+                let repo = UnitRepo::new(db.conn());
+                let nodes = repo.list_tree_by_library(lib_id).unwrap_or_else(|e| {
+                    log::error!("Failed to load formations: {}", e);
+                    Vec::new()
+                });
+                let counts = repo.subtree_counts(lib_id).unwrap_or_else(|e| {
+                    log::error!("Failed to total formation strength: {}", e);
+                    HashMap::new()
+                });
+                (nodes, counts)
+            }
+            _ => (Vec::new(), HashMap::new()),
+        };
+        let as_of = state.current_as_of();
+        if let Some(date) = as_of {
+            nodes = crate::db::repositories::tree_as_of(nodes, date);
+        }
+        window.set_as_of_text(as_of.map(format_date).unwrap_or_default().into());
+        window.set_as_of_error("".into());
+        let prefixes = crate::db::repositories::tree_prefixes(&nodes, &state.numbering());
+        let (levels, problems) = formation_level_problems(state);
+        if !nodes.iter().any(|n| n.id == window.get_selected_formation_id() as i64) {
+            window.set_selected_formation_id(-1);
+        }
+        let library_id = state.current_library.as_ref().and_then(|l| l.id);
+        let expanded: HashSet<Option<i64>> = state
+            .expanded_groups
+            .iter()
+            .filter(|(lib, _)| Some(*lib) == library_id)
+            .map(|(_, parent)| *parent)
+            .collect();
+        let lang = window.get_current_language();
+        let rows = crate::db::repositories::collapse_wide_groups(nodes, state.tree_limits.sidebar_children, &expanded);
+        let formations: Vec<FormationTreeItem> = rows
+            .into_iter()
+            .map(|row| match row {
+                TreeRow::Unit(n) => FormationTreeItem {
+                    id: n.id as i32,
+                    name: n.name.clone().into(),
+                    depth: n.depth.min(SIDEBAR_INDENT_LEVELS),
+                    prefix: prefixes.get(&n.id).cloned().unwrap_or_default().into(),
+                    capacity: levels
+                        .iter()
+                        .find(|l| l.matches_name(&n.unit_type))
+                        .and_then(|l| l.personnel_range_label())
+                        .unwrap_or_default()
+                        .into(),
+                    problem: problems.get(&n.id).cloned().unwrap_or_default().into(),
+                    personnel_count: counts.get(&n.id).map_or(0, |c| c.0 as i32),
+                    equipment_count: counts.get(&n.id).map_or(0, |c| c.1 as i32),
+                    more: 0,
+                },
+                TreeRow::More { parent_id, depth, hidden } => FormationTreeItem {
+                    id: parent_id.map_or(-1, |id| id as i32),
+                    name: ui_tr(&lang, "Show all ({} more)").replacen("{}", &hidden.to_string(), 1).into(),
+                    depth: depth.min(SIDEBAR_INDENT_LEVELS),
+                    prefix: "".into(),
+                    capacity: "".into(),
+                    problem: "".into(),
+                    personnel_count: 0,
+                    equipment_count: 0,
+                    more: hidden as i32,
+                },
+            })
+            .collect();
+        window.set_formations(ModelRc::new(VecModel::from(formations)));
+    });
+}
+
+/// What to tell the user when a library file cannot be imported
+fn import_error_message(lang: &str, error: &anyhow::Error) -> String {
+    use crate::import::ImportError;
+    let (summary, detail) = match error.downcast_ref::<ImportError>() {
+        Some(ImportError::NotJson(detail)) => ("The file is not valid JSON.", detail.clone()),
+        Some(ImportError::NotALibrary(detail)) => ("The file is not a TOEditor library.", detail.clone()),
+        Some(ImportError::Io(e)) => ("The file could not be read.", format!("{:#}", e)),
+        None => ("Failed to import library", format!("{:#}", error)),
+    };
+    format!("{}\n\n{}", ui_tr(lang, summary), detail)
+}
+
+/// Formation levels of the current library and lint problems keyed by unit id.
+/// The unit tree is only loaded when some level has a personnel range.
+fn formation_level_problems(
+    state: &AppState,
+) -> (Vec<crate::models::CustomFormationLevel>, HashMap<i64, String>) {
+    let (Some(db), Some(lib_id)) = (state.database(), state.current_library.as_ref().and_then(|l| l.id)) else {
+        return (Vec::new(), HashMap::new());
+    };
+    let levels = crate::db::repositories::FormationLevelRepo::new(db.conn())
+        .list_by_library(lib_id)
+        .unwrap_or_default();
+    if !levels.iter().any(|l| l.has_personnel_range()) {
+        return (levels, HashMap::new());
+    }
+    let units = crate::db::repositories::UnitRepo::new(db.conn())
+        .get_by_library_id(lib_id)
+        .unwrap_or_else(|e| {
+            log::error!("Failed to load units for lint: {}", e);
+            Vec::new()
+        });
+    let problems = crate::services::lint_units(&units, &levels)
+        .into_iter()
+        .filter_map(|p| Some((p.unit_id?, p.message)))
+        .collect();
+    (levels, problems)
+}
+
+/// In safe database mode, keep a copy of the database in the local data folder
+fn back_up_in_safe_mode(state: &AppState) {
+    let settings = crate::config::Settings::load().unwrap_or_default();
+    let Some(db) = state.database() else {
+        return;
+    };
+    if !settings.safe_database_mode {
+        return;
+    }
+    let result = crate::config::Settings::data_dir()
+        .and_then(|dir| db.backup_to(dir.join("backups").join("toeditor.db.bak")));
+    if let Err(e) = result {
+        log::warn!("Failed to back up the database: {}", e);
+    }
+}
+
+/// Clear what belonged to the database that was just closed: the open tabs
+/// and the selected library. The libraries list shows the new database.
+fn show_switched_database(window: &MainWindow, tabs: &VecModel<FormationTab>, state: &Rc<RefCell<AppState>>) {
+    tabs.set_vec(Vec::new());
+    window.set_current_tab_index(-1);
+    window.set_current_tab_title("".into());
+    window.set_current_library_name("".into());
+    window.set_current_library_id(-1);
+    refresh_libraries_list(window, state.clone());
+    refresh_formations_list(window, &state.borrow());
+}
+
+/// Show the recent libraries from `settings` in the File menu
+fn set_recent_libraries(window: &MainWindow, settings: &crate::config::Settings) {
+    let items: Vec<RecentLibraryItem> = settings
+        .recent_libraries
+        .iter()
+        .map(|e| RecentLibraryItem {
+            id: e.library_id as i32,
+            name: e.name.clone().into(),
+        })
+        .collect();
+    window.set_recent_libraries(ModelRc::new(VecModel::from(items)));
+}
+
+/// Put a library at the top of the recent libraries list and save the settings
+fn remember_recent_library(window: &MainWindow, library: &Library) {
+    let Some(id) = library.id else {
+        return;
+    };
+    let mut settings = crate::config::Settings::load().unwrap_or_default();
+    settings.record_recent_library(id, &library.name, chrono::Utc::now().timestamp());
+    if let Err(e) = settings.save() {
+        log::error!("Failed to save settings: {}", e);
+    }
+    set_recent_libraries(window, &settings);
 }
 
 /// Refresh libraries list in the UI
 fn refresh_libraries_list(window: &MainWindow, state: Rc<RefCell<AppState>>) {
     let state = state.borrow();
-    if let Some(ref db) = state.database {
+    if let Some(db) = state.database() {
         let service = LibraryService::new(db.conn());
-        match service.list_libraries() {
-            Ok(libraries) => {
-                let library_items: Vec<LibraryItem> = libraries
+        let lang = window.get_current_language().to_string();
+        let now = chrono::Utc::now().timestamp();
+        let summaries = service.list_library_summaries().and_then(|summaries| {
+            if state.library_filter.is_empty() {
+                return Ok(summaries);
+            }
+            let matching: HashSet<i64> = service
+                .search_libraries(&state.library_filter)?
+                .into_iter()
+                .filter_map(|lib| lib.id)
+                .collect();
+            Ok(summaries.into_iter().filter(|s| matching.contains(&s.id)).collect())
+        });
+        match summaries {
+            Ok(summaries) => {
+                let library_items: Vec<LibraryItem> = summaries
                     .iter()
-                    .filter_map(|lib| {
-                        lib.id.map(|id| LibraryItem {
-                            id: id as i32,
-                            name: lib.name.clone().into(),
-                            country: lib.country.clone().into(),
-                            era: lib.era.clone().into(),
-                        })
+                    .map(|lib| LibraryItem {
+                        id: lib.id as i32,
+                        name: lib.name.clone().into(),
+                        country: lib.country.clone().into(),
+                        era: lib.era.clone().into(),
+                        unit_count: lib.unit_count as i32,
+                        unit_count_text: crate::i18n::format_number(lib.unit_count as i64, &lang).into(),
+                        changed: crate::i18n::time_ago(lib.updated_at, now, &lang).into(),
+                        changed_at: crate::i18n::format_timestamp(lib.updated_at).into(),
+                        selected: false,
                     })
                     .collect();
                 window.set_libraries(ModelRc::new(VecModel::from(library_items)));
-                log::info!("Refreshed libraries list: {} libraries", libraries.len());
+                log::info!("Refreshed libraries list: {} libraries", summaries.len());
             }
             Err(e) => {
                 log::error!("Failed to load libraries: {}", e);
@@ -921,77 +3419,9 @@ fn refresh_libraries_list(window: &MainWindow, state: Rc<RefCell<AppState>>) {
     }
 }
 
-/// Initialize toolbar
+/// Initialize toolbar. The buttons themselves are set, in the current
+/// language, by `apply_ui_translations`.
 fn init_toolbar(window: &MainWindow) -> Result<()> {
-    let toolbar_buttons = vec![
-        ToolbarButton {
-            id: "new_library".into(),
-            text: "📄 New".into(),
-            icon: "icons/document-new.svg".into(),
-            tooltip: "New Library (Ctrl+N)".into(),
-            enabled: true,
-            is_separator: false,
-        },
-        ToolbarButton {
-            id: "open_library".into(),
-            text: "📂 Open".into(),
-            icon: "icons/document-open.svg".into(),
-            tooltip: "Open Library (Ctrl+O)".into(),
-            enabled: true,
-            is_separator: false,
-        },
-        ToolbarButton {
-            id: "save_library".into(),
-            text: "💾 Save".into(),
-            icon: "icons/document-save.svg".into(),
-            tooltip: "Save Library (Ctrl+S)".into(),
-            enabled: true,
-            is_separator: false,
-        },
-        ToolbarButton {
-            id: "".into(),
-            text: "".into(),
-            icon: "".into(),
-            tooltip: "".into(),
-            enabled: false,
-            is_separator: true,
-        },
-        ToolbarButton {
-            id: "new_formation".into(),
-            text: "➕ Formation".into(),
-            icon: "icons/list-add.svg".into(),
-            tooltip: "New Formation".into(),
-            enabled: false,
-            is_separator: false,
-        },
-        ToolbarButton {
-            id: "".into(),
-            text: "".into(),
-            icon: "".into(),
-            tooltip: "".into(),
-            enabled: false,
-            is_separator: true,
-        },
-        ToolbarButton {
-            id: "table_view".into(),
-            text: "📊 Table".into(),
-            icon: "icons/view-table.svg".into(),
-            tooltip: "Table View".into(),
-            enabled: true,
-            is_separator: false,
-        },
-        ToolbarButton {
-            id: "diagram_view".into(),
-            text: "🔀 Diagram".into(),
-            icon: "icons/view-diagram.svg".into(),
-            tooltip: "Diagram View".into(),
-            enabled: true,
-            is_separator: false,
-        },
-    ];
-
-    window.set_toolbar(ModelRc::new(VecModel::from(toolbar_buttons)));
-
     // Wire up toolbar button clicks to existing callbacks
     let weak = window.as_weak();
     window.on_toolbar_clicked(move |id: SharedString| {
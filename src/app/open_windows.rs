@@ -0,0 +1,152 @@
+//! Editor windows that follow the main window's theme and language
+//!
+//! Editors are added once shown; a theme or language switch in the main
+//! window is passed on to those still open. To check by hand: open the
+//! branches, categories and formation levels editors, switch View > Theme
+//! and then the language, and see each open editor change along with the
+//! main window; close one, switch back, and see the others follow again.
+
+use slint::ComponentHandle;
+use super::AppTheme;
+
+/// Window held weakly: whether it is still open, and how to restyle and
+/// retranslate it
+pub(crate) struct OpenWindow {
+    is_open: Box<dyn Fn() -> bool>,
+    set_theme: Box<dyn Fn(&str)>,
+    translate: Box<dyn Fn(&str)>,
+}
+
+impl OpenWindow {
+    pub(crate) fn new(
+        is_open: impl Fn() -> bool + 'static,
+        set_theme: impl Fn(&str) + 'static,
+        translate: impl Fn(&str) + 'static,
+    ) -> Self {
+        Self { is_open: Box::new(is_open), set_theme: Box::new(set_theme), translate: Box::new(translate) }
+    }
+
+    /// A shown component, open until it is hidden or dropped. `translate`
+    /// sets its texts in a language.
+    pub(crate) fn of<C>(component: &C, translate: fn(&C, &str)) -> Self
+    where
+        C: ComponentHandle + 'static,
+        for<'a> AppTheme<'a>: slint::Global<'a, C>,
+    {
+        let (open, themed, translated) = (component.as_weak(), component.as_weak(), component.as_weak());
+        Self::new(
+            move || open.upgrade().is_some_and(|c| c.window().is_visible()),
+            move |theme| {
+                if let Some(c) = themed.upgrade() {
+                    c.global::<AppTheme>().set_mode(theme.into());
+                }
+            },
+            move |lang| {
+                if let Some(c) = translated.upgrade() {
+                    translate(&c, lang);
+                }
+            },
+        )
+    }
+}
+
+/// Open editor windows, with the theme and language they were last given
+pub(crate) struct OpenWindows {
+    windows: Vec<OpenWindow>,
+    theme: String,
+    lang: String,
+}
+
+impl Default for OpenWindows {
+    fn default() -> Self {
+        Self { windows: Vec::new(), theme: "light".to_string(), lang: "en".to_string() }
+    }
+}
+
+impl OpenWindows {
+    /// Add a window, giving it the current theme and language
+    pub(crate) fn add(&mut self, window: OpenWindow) {
+        self.prune();
+        (window.set_theme)(&self.theme);
+        (window.translate)(&self.lang);
+        self.windows.push(window);
+    }
+
+    /// Forget the windows closed since
+    fn prune(&mut self) {
+        self.windows.retain(|w| (w.is_open)());
+    }
+
+    /// Switch every open window, and the ones added later, to a theme
+    pub(crate) fn set_theme(&mut self, theme: &str) {
+        self.prune();
+        self.theme = theme.to_string();
+        for window in &self.windows {
+            (window.set_theme)(theme);
+        }
+    }
+
+    /// Retranslate every open window, and the ones added later
+    pub(crate) fn set_language(&mut self, lang: &str) {
+        self.prune();
+        self.lang = lang.to_string();
+        for window in &self.windows {
+            (window.translate)(lang);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Window stand-in recording what it was given; open while `alive` lives
+    fn stand_in(alive: &Rc<()>, log: &Rc<RefCell<Vec<String>>>, name: &str) -> OpenWindow {
+        let weak = Rc::downgrade(alive);
+        let (theme_log, lang_log) = (log.clone(), log.clone());
+        let (theme_name, lang_name) = (name.to_string(), name.to_string());
+        OpenWindow::new(
+            move || weak.upgrade().is_some(),
+            move |theme| theme_log.borrow_mut().push(format!("{} theme {}", theme_name, theme)),
+            move |lang| lang_log.borrow_mut().push(format!("{} lang {}", lang_name, lang)),
+        )
+    }
+
+    fn open_count(windows: &mut OpenWindows) -> usize {
+        windows.prune();
+        windows.windows.len()
+    }
+
+    #[test]
+    fn test_added_window_gets_current_theme_and_language() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let alive = Rc::new(());
+        let mut windows = OpenWindows::default();
+        windows.set_theme("dark");
+        windows.set_language("ru");
+        windows.add(stand_in(&alive, &log, "branches"));
+        assert_eq!(*log.borrow(), ["branches theme dark", "branches lang ru"]);
+        assert_eq!(open_count(&mut windows), 1);
+    }
+
+    #[test]
+    fn test_switches_reach_open_windows_only() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let (first, second) = (Rc::new(()), Rc::new(()));
+        let mut windows = OpenWindows::default();
+        windows.add(stand_in(&first, &log, "levels"));
+        windows.add(stand_in(&second, &log, "categories"));
+        log.borrow_mut().clear();
+
+        drop(first);
+        windows.set_theme("dark");
+        windows.set_language("ru");
+        assert_eq!(*log.borrow(), ["categories theme dark", "categories lang ru"]);
+        assert_eq!(open_count(&mut windows), 1);
+
+        drop(second);
+        assert_eq!(open_count(&mut windows), 0);
+    }
+}
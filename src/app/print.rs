@@ -0,0 +1,48 @@
+//! Printing a formation tab through the default browser
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::export::print::{cleanup_print_files, print_dir, print_file_name, PRINT_FILE_MAX_AGE};
+use super::AppState;
+
+/// Remove print files left by earlier sessions
+pub(super) fn cleanup_old_print_files() {
+    match cleanup_print_files(&print_dir(), PRINT_FILE_MAX_AGE, std::time::SystemTime::now()) {
+        Ok(0) => {}
+        Ok(n) => log::info!("Removed {} old print files", n),
+        Err(e) => log::warn!("Failed to clean up print files: {}", e),
+    }
+}
+
+/// Write the print page of formation `unit_id` and open it in the browser
+pub(super) fn print_formation(state: &Rc<RefCell<AppState>>, unit_id: i64, title: &str, lang: &str) -> anyhow::Result<()> {
+    let st = state.borrow();
+    let db = st.database().ok_or_else(|| anyhow::anyhow!("Database not initialized"))?;
+    let dir = print_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(print_file_name(title, chrono::Utc::now().timestamp()));
+    crate::export::html::export_print_page(db.conn(), unit_id, lang, st.current_as_of(), &st.numbering(), &path)?;
+    open_with_system(&path)?;
+    log::info!("Print page opened: {:?}", path);
+    Ok(())
+}
+
+/// Open a local file with the system's default handler, or a folder in the
+/// file manager
+pub(super) fn open_with_system(path: &Path) -> anyhow::Result<()> {
+    use std::process::Command;
+
+    let status = if cfg!(target_os = "windows") {
+        Command::new("cmd").arg("/C").arg("start").arg("").arg(path).status()?
+    } else if cfg!(target_os = "macos") {
+        Command::new("open").arg(path).status()?
+    } else {
+        Command::new("xdg-open").arg(path).status()?
+    };
+    if !status.success() {
+        anyhow::bail!("could not open {}", path.display());
+    }
+    Ok(())
+}
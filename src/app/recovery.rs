@@ -0,0 +1,205 @@
+//! Crash recovery: journaling open editor windows, and offering a crashed session's edits at startup
+
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use slint::{ComponentHandle, Model, ModelRc, VecModel};
+
+use crate::services::{apply_entry, remove_journals, Debounce, EditorKind, EditorRows, Journal, JournalEntry};
+use super::{AppState, RecoveryDialog, RecoveryRow};
+use super::translations::ui_tr;
+use super::dialogs::show_error_dialog;
+use super::notify_change;
+
+/// Pause in editing after which the editor's rows are journaled
+const JOURNAL_DELAY: Duration = Duration::from_secs(2);
+/// Longest time an edit stays unjournaled while the user keeps typing
+const JOURNAL_MAX_WAIT: Duration = Duration::from_secs(15);
+
+/// Journal writer of one editor window
+pub(super) struct EditorJournal {
+    journal: Option<Journal>,
+    /// Database the editor's library is in, recorded in every entry
+    database: Option<PathBuf>,
+    library_id: i64,
+    library_name: String,
+    editor: EditorKind,
+    debounce: RefCell<Debounce>,
+    timer: slint::Timer,
+    pending: RefCell<Option<EditorRows>>,
+    written: Cell<bool>,
+}
+
+impl EditorJournal {
+    pub(super) fn new(state: &Rc<RefCell<AppState>>, library_id: i64, library_name: &str, editor: EditorKind) -> Rc<Self> {
+        let st = state.borrow();
+        Rc::new(Self {
+            journal: st.journal.clone(),
+            database: st.database().and_then(|db| db.path()),
+            library_id,
+            library_name: library_name.to_string(),
+            editor,
+            debounce: RefCell::new(Debounce::new(JOURNAL_DELAY, JOURNAL_MAX_WAIT)),
+            timer: slint::Timer::default(),
+            pending: RefCell::new(None),
+            written: Cell::new(false),
+        })
+    }
+
+    /// Note the editor's current rows; they are journaled once editing pauses
+    pub(super) fn changed(self: &Rc<Self>, rows: EditorRows) {
+        if self.journal.is_none() {
+            return;
+        }
+        *self.pending.borrow_mut() = Some(rows);
+        let wait = self.debounce.borrow_mut().change(Instant::now());
+        let weak = Rc::downgrade(self);
+        self.timer.start(slint::TimerMode::SingleShot, wait, move || {
+            if let Some(journal) = weak.upgrade() {
+                journal.flush_if_due();
+            }
+        });
+    }
+
+    fn flush_if_due(&self) {
+        if !self.debounce.borrow_mut().due(Instant::now()) {
+            return;
+        }
+        if let Some(rows) = self.pending.borrow_mut().take() {
+            self.write(JournalEntry::pending(self.database.clone(), self.library_id, &self.library_name, rows));
+        }
+    }
+
+    /// The editor saved its rows; nothing is left to recover
+    pub(super) fn finished(&self) {
+        self.timer.stop();
+        self.pending.borrow_mut().take();
+        if self.written.get() {
+            self.write(JournalEntry::finished(self.database.clone(), self.library_id, &self.library_name, self.editor));
+        }
+    }
+
+    fn write(&self, entry: JournalEntry) {
+        let Some(ref journal) = self.journal else {
+            return;
+        };
+        match journal.append(&entry) {
+            Ok(()) => self.written.set(true),
+            Err(e) => log::warn!("Failed to write recovery journal {}: {}", journal.path().display(), e),
+        }
+    }
+}
+
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// Offer the edits in journals left by a crashed session. The journals are
+/// deleted once every entry was applied or discarded; closing the dialog
+/// earlier keeps them for the next start.
+pub(super) fn show_recovery_dialog(state: Rc<RefCell<AppState>>, lang: &str, leftovers: Vec<PathBuf>) {
+    let entries = match crate::services::pending_entries(&leftovers) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Failed to read recovery journals: {}", e);
+            return;
+        }
+    };
+    if entries.is_empty() {
+        if let Err(e) = remove_journals(&leftovers) {
+            log::warn!("Failed to remove recovery journals: {}", e);
+        }
+        return;
+    }
+    let dialog = match RecoveryDialog::new() {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create recovery dialog: {}", e);
+            return;
+        }
+    };
+    state.borrow_mut().open_dialogs.track(&dialog);
+    let rows: Vec<RecoveryRow> = entries
+        .iter()
+        .map(|e| RecoveryRow {
+            library: e.library_name.clone().into(),
+            editor: ui_tr(lang, e.editor.label()).into(),
+            saved_at: format_time(e.saved_at).into(),
+        })
+        .collect();
+    let model = Rc::new(VecModel::from(rows));
+    let entries = Rc::new(RefCell::new(entries));
+    dialog.set_rows(ModelRc::new(model.clone()));
+    dialog.set_dialog_title(ui_tr(lang, "Recover Unsaved Changes").into());
+    dialog.set_hint(ui_tr(lang, "TOEditor did not close properly last time. These editor changes were not saved.").into());
+    dialog.set_library_header(ui_tr(lang, "Library").into());
+    dialog.set_editor_header(ui_tr(lang, "Editor").into());
+    dialog.set_time_header(ui_tr(lang, "Changed").into());
+    dialog.set_apply_text(ui_tr(lang, "Apply").into());
+    dialog.set_discard_text(ui_tr(lang, "Discard").into());
+    dialog.set_close_text(ui_tr(lang, "Close").into());
+
+    // Drop the handled row; with none left the journals have served their purpose
+    let handled = {
+        let weak = dialog.as_weak();
+        let model = model.clone();
+        let entries = entries.clone();
+        move |index: usize| {
+            entries.borrow_mut().remove(index);
+            model.remove(index);
+            if model.row_count() == 0 {
+                if let Err(e) = remove_journals(&leftovers) {
+                    log::warn!("Failed to remove recovery journals: {}", e);
+                }
+                if let Some(d) = weak.upgrade() {
+                    d.hide().unwrap_or_default();
+                }
+            }
+        }
+    };
+
+    let entries_apply = entries.clone();
+    let handled_apply = handled.clone();
+    let lang_apply = lang.to_string();
+    dialog.on_apply(move |index| {
+        let Some(entry) = usize::try_from(index).ok().and_then(|i| entries_apply.borrow().get(i).cloned()) else {
+            return;
+        };
+        let applied = {
+            let st = state.borrow();
+            let Some(db) = st.database() else {
+                return;
+            };
+            apply_entry(db.conn(), &entry)
+        };
+        match applied {
+            Ok(()) => {
+                log::info!("Recovered {:?} rows of library {}", entry.editor, entry.library_id);
+                notify_change(&state, entry.editor.change_event(entry.library_id));
+                handled_apply(index as usize);
+            }
+            Err(e) => {
+                log::error!("Failed to recover changes: {}", e);
+                show_error_dialog(&lang_apply, "Error", "Failed to recover changes: {}", &[&e]);
+            }
+        }
+    });
+
+    dialog.on_discard(move |index| {
+        if usize::try_from(index).is_ok_and(|i| i < entries.borrow().len()) {
+            handled(index as usize);
+        }
+    });
+
+    let weak_close = dialog.as_weak();
+    dialog.on_closed(move || {
+        if let Some(d) = weak_close.upgrade() {
+            d.hide().unwrap_or_default();
+        }
+    });
+
+    dialog.show().unwrap_or_default();
+}
@@ -0,0 +1,90 @@
+//! Applying the saved settings to the running application, at startup and
+//! whenever they are replaced as a whole
+
+use slint::ComponentHandle;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::config::Settings;
+use crate::i18n::supported_code;
+use super::translations::apply_ui_translations;
+use super::{set_recent_libraries, AppState, AppTheme, MainWindow};
+
+/// Color scheme the settings select; anything unknown is the light theme
+fn theme_code(settings: &Settings) -> &'static str {
+    if settings.color_scheme == "dark" {
+        "dark"
+    } else {
+        "light"
+    }
+}
+
+/// Database file opened from `previous` settings that resetting them would
+/// leave behind: a custom path other than the default one
+pub(super) fn database_left_by_reset(previous: &Settings, default_path: &Path) -> Option<PathBuf> {
+    previous.database_path.clone().filter(|path| path != default_path)
+}
+
+/// Show `settings` in the window and apply them to the open database.
+/// Nothing is saved. The database file itself is not reopened, and safe
+/// mode, once on, stays on until the next database is opened.
+pub(super) fn apply_settings(window: &MainWindow, state: &Rc<RefCell<AppState>>, settings: &Settings) {
+    let lang = supported_code(&settings.language);
+    if let Err(e) = slint::select_bundled_translation(lang) {
+        log::warn!("Could not set translation: {}", e);
+    }
+    window.set_current_language(lang.into());
+    window.set_window_title(format!("TOEditor v{}", env!("CARGO_PKG_VERSION")).into());
+    apply_ui_translations(window, lang);
+
+    let theme = theme_code(settings);
+    window.set_theme(theme.into());
+    window.global::<AppTheme>().set_mode(theme.into());
+
+    window.set_show_debug_tools(settings.debug_tools);
+    window.set_zoom_level(crate::view::zoom::clamp_zoom(settings.zoom));
+    {
+        let mut st = state.borrow_mut();
+        st.set_tree_limits(settings.tree_limits);
+        st.open_windows.set_theme(theme);
+        st.open_windows.set_language(lang);
+    }
+    crate::import::limits::follow_tree_limits(&settings.tree_limits);
+    set_recent_libraries(window, settings);
+
+    if settings.safe_database_mode {
+        if let Some(db) = state.borrow().database() {
+            if let Err(e) = db.use_safe_mode() {
+                log::warn!("Failed to switch the database to safe mode: {}", e);
+            }
+        }
+    }
+    window.window().request_redraw();
+    log::info!("Settings applied: language {}, theme {}", lang, theme);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_code_falls_back_to_light() {
+        let mut settings = Settings::default();
+        assert_eq!(theme_code(&settings), "light");
+        settings.color_scheme = "dark".to_string();
+        assert_eq!(theme_code(&settings), "dark");
+        settings.color_scheme = "solarized".to_string();
+        assert_eq!(theme_code(&settings), "light");
+    }
+
+    #[test]
+    fn test_only_custom_database_is_left_by_reset() {
+        let default_path = Path::new("/data/toeditor.db");
+        let mut previous = Settings::default();
+        assert_eq!(database_left_by_reset(&previous, default_path), None);
+        previous.database_path = Some(default_path.to_path_buf());
+        assert_eq!(database_left_by_reset(&previous, default_path), None);
+        previous.database_path = Some(PathBuf::from("/sync/army.db"));
+        assert_eq!(database_left_by_reset(&previous, default_path), Some(PathBuf::from("/sync/army.db")));
+    }
+}
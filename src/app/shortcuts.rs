@@ -0,0 +1,271 @@
+//! Keyboard shortcuts of the main window
+//!
+//! Key presses the main window does not handle itself reach
+//! [`map_shortcut`], which looks them up in the [`ShortcutTable`]; the action
+//! found is then run by the main window callbacks.
+
+use slint::ComponentHandle;
+
+/// Text of the F2 key in key events
+const F2: char = '\u{F705}';
+/// Text of the F3 key in key events
+const F3: char = '\u{F706}';
+/// Text of the F10 key in key events
+const F10: char = '\u{F70D}';
+/// Text of the context menu key in key events
+const MENU: char = '\u{F735}';
+/// Text of Shift+Tab in key events on platforms reporting it as "back tab"
+const BACKTAB: char = '\u{0019}';
+
+/// Main window command a shortcut runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AppAction {
+    NewLibrary,
+    OpenLibrary,
+    SaveLibrary,
+    PrintCurrentTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    TableView,
+    DiagramView,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    /// Context menu of the current library, for keyboard users
+    LibraryMenu,
+    Exit,
+}
+
+/// Key combination bound to an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Shortcut {
+    /// Lowercase letter, `'\t'` or the text of a function key
+    pub(crate) key: char,
+    pub(crate) ctrl: bool,
+    pub(crate) shift: bool,
+    pub(crate) alt: bool,
+    pub(crate) action: AppAction,
+}
+
+impl Shortcut {
+    const fn ctrl(key: char, action: AppAction) -> Self {
+        Shortcut { key, ctrl: true, shift: false, alt: false, action }
+    }
+
+    const fn ctrl_shift(key: char, action: AppAction) -> Self {
+        Shortcut { key, ctrl: true, shift: true, alt: false, action }
+    }
+
+    const fn plain(key: char, action: AppAction) -> Self {
+        Shortcut { key, ctrl: false, shift: false, alt: false, action }
+    }
+
+    const fn shift(key: char, action: AppAction) -> Self {
+        Shortcut { key, ctrl: false, shift: true, alt: false, action }
+    }
+}
+
+/// Shortcuts of the main window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ShortcutTable {
+    shortcuts: Vec<Shortcut>,
+}
+
+impl Default for ShortcutTable {
+    fn default() -> Self {
+        ShortcutTable {
+            shortcuts: vec![
+                Shortcut::ctrl('n', AppAction::NewLibrary),
+                Shortcut::ctrl('o', AppAction::OpenLibrary),
+                Shortcut::ctrl('s', AppAction::SaveLibrary),
+                Shortcut::ctrl('p', AppAction::PrintCurrentTab),
+                Shortcut::ctrl('w', AppAction::CloseTab),
+                Shortcut::ctrl('\t', AppAction::NextTab),
+                Shortcut::ctrl_shift('\t', AppAction::PreviousTab),
+                Shortcut::plain(F2, AppAction::TableView),
+                Shortcut::plain(F3, AppAction::DiagramView),
+                // Ctrl+= is Ctrl++ without Shift on most layouts
+                Shortcut::ctrl('=', AppAction::ZoomIn),
+                Shortcut::ctrl('+', AppAction::ZoomIn),
+                Shortcut::ctrl_shift('+', AppAction::ZoomIn),
+                Shortcut::ctrl('-', AppAction::ZoomOut),
+                Shortcut::ctrl('0', AppAction::ZoomReset),
+                Shortcut::shift(F10, AppAction::LibraryMenu),
+                Shortcut::plain(MENU, AppAction::LibraryMenu),
+                Shortcut::ctrl('q', AppAction::Exit),
+            ],
+        }
+    }
+}
+
+impl ShortcutTable {
+    /// Action bound to a key event's text and modifiers
+    pub(crate) fn lookup(&self, key: &str, ctrl: bool, shift: bool, alt: bool) -> Option<AppAction> {
+        let mut chars = key.chars();
+        let (first, None) = (chars.next()?, chars.next()) else {
+            return None;
+        };
+        // Shift+Tab may arrive as its own key, with or without the Shift flag
+        let (key, shift) = match first {
+            BACKTAB => ('\t', true),
+            // Shift changes the text of letters; the table holds them lowercase
+            c => (c.to_lowercase().next().unwrap_or(c), shift),
+        };
+        self.shortcuts
+            .iter()
+            .find(|s| s.key == key && s.ctrl == ctrl && s.shift == shift && s.alt == alt)
+            .map(|s| s.action)
+    }
+}
+
+/// Action of the built-in shortcut for a key event, if any
+pub(crate) fn map_shortcut(key: &str, ctrl: bool, shift: bool, alt: bool) -> Option<AppAction> {
+    ShortcutTable::default().lookup(key, ctrl, shift, alt)
+}
+
+/// Tab selected after cycling forward or back from `current` among `count`
+/// tabs, wrapping around; `None` without tabs
+pub(crate) fn cycle_tab(current: i32, count: usize, forward: bool) -> Option<i32> {
+    let count = i32::try_from(count).ok().filter(|&c| c > 0)?;
+    let next = match (current, forward) {
+        (c, true) if c < 0 => 0,
+        (c, false) if c < 0 => count - 1,
+        (c, true) => c + 1,
+        (c, false) => c - 1,
+    };
+    Some(next.rem_euclid(count))
+}
+
+/// Dialog windows open over the main window; shortcuts are ignored while
+/// any of them is visible
+#[derive(Default)]
+pub(crate) struct OpenDialogs {
+    visible: Vec<Box<dyn Fn() -> bool>>,
+}
+
+impl OpenDialogs {
+    /// Count `dialog` as open until it is hidden
+    pub(crate) fn track<C: ComponentHandle + 'static>(&mut self, dialog: &C) {
+        let weak = dialog.as_weak();
+        self.visible.push(Box::new(move || weak.upgrade().is_some_and(|d| d.window().is_visible())));
+    }
+
+    /// Number of tracked dialogs still visible
+    pub(crate) fn count(&mut self) -> usize {
+        self.visible.retain(|visible| visible());
+        self.visible.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slint::platform::Key;
+
+    #[test]
+    fn test_function_key_texts_match_slint() {
+        assert_eq!(char::from(Key::F2), F2);
+        assert_eq!(char::from(Key::F3), F3);
+        assert_eq!(char::from(Key::F10), F10);
+        assert_eq!(char::from(Key::Menu), MENU);
+        assert_eq!(char::from(Key::Tab), '\t');
+        assert_eq!(char::from(Key::Backtab), BACKTAB);
+    }
+
+    #[test]
+    fn test_every_action_has_a_shortcut() {
+        let table = ShortcutTable::default();
+        let actions = [
+            AppAction::NewLibrary,
+            AppAction::OpenLibrary,
+            AppAction::SaveLibrary,
+            AppAction::PrintCurrentTab,
+            AppAction::CloseTab,
+            AppAction::NextTab,
+            AppAction::PreviousTab,
+            AppAction::TableView,
+            AppAction::DiagramView,
+            AppAction::ZoomIn,
+            AppAction::ZoomOut,
+            AppAction::ZoomReset,
+            AppAction::LibraryMenu,
+            AppAction::Exit,
+        ];
+        for action in actions {
+            let bound: Vec<&Shortcut> = table.shortcuts.iter().filter(|s| s.action == action).collect();
+            assert!(!bound.is_empty(), "{:?}", action);
+            for shortcut in bound {
+                let key = shortcut.key.to_string();
+                assert_eq!(table.lookup(&key, shortcut.ctrl, shortcut.shift, shortcut.alt), Some(action));
+            }
+        }
+        assert!(table.shortcuts.iter().all(|s| actions.contains(&s.action)));
+    }
+
+    #[test]
+    fn test_map_shortcut_bindings() {
+        assert_eq!(map_shortcut("n", true, false, false), Some(AppAction::NewLibrary));
+        assert_eq!(map_shortcut("o", true, false, false), Some(AppAction::OpenLibrary));
+        assert_eq!(map_shortcut("s", true, false, false), Some(AppAction::SaveLibrary));
+        assert_eq!(map_shortcut("p", true, false, false), Some(AppAction::PrintCurrentTab));
+        assert_eq!(map_shortcut("w", true, false, false), Some(AppAction::CloseTab));
+        assert_eq!(map_shortcut("q", true, false, false), Some(AppAction::Exit));
+        assert_eq!(map_shortcut("\t", true, false, false), Some(AppAction::NextTab));
+        assert_eq!(map_shortcut("\t", true, true, false), Some(AppAction::PreviousTab));
+        assert_eq!(map_shortcut("\u{F705}", false, false, false), Some(AppAction::TableView));
+        assert_eq!(map_shortcut("\u{F706}", false, false, false), Some(AppAction::DiagramView));
+        assert_eq!(map_shortcut("\u{F70D}", false, true, false), Some(AppAction::LibraryMenu));
+        assert_eq!(map_shortcut("\u{F735}", false, false, false), Some(AppAction::LibraryMenu));
+        assert_eq!(map_shortcut("=", true, false, false), Some(AppAction::ZoomIn));
+        assert_eq!(map_shortcut("+", true, true, false), Some(AppAction::ZoomIn));
+        assert_eq!(map_shortcut("-", true, false, false), Some(AppAction::ZoomOut));
+        assert_eq!(map_shortcut("0", true, false, false), Some(AppAction::ZoomReset));
+    }
+
+    #[test]
+    fn test_map_shortcut_normalizes_case_and_backtab() {
+        assert_eq!(map_shortcut("S", true, false, false), Some(AppAction::SaveLibrary));
+        assert_eq!(map_shortcut("\u{0019}", true, true, false), Some(AppAction::PreviousTab));
+        assert_eq!(map_shortcut("\u{0019}", true, false, false), Some(AppAction::PreviousTab));
+    }
+
+    #[test]
+    fn test_map_shortcut_requires_exact_modifiers() {
+        let keys = ["n", "o", "s", "p", "w", "q", "\t", "\u{F705}", "\u{F706}", "\u{F70D}", "\u{F735}", "=", "+", "-", "0"];
+        let table = ShortcutTable::default();
+        for key in keys {
+            let bound: Vec<(bool, bool, bool)> = table
+                .shortcuts
+                .iter()
+                .filter(|s| s.key.to_string() == key)
+                .map(|s| (s.ctrl, s.shift, s.alt))
+                .collect();
+            for bits in 0..8u8 {
+                let (ctrl, shift, alt) = (bits & 1 != 0, bits & 2 != 0, bits & 4 != 0);
+                let found = map_shortcut(key, ctrl, shift, alt);
+                assert_eq!(found.is_some(), bound.contains(&(ctrl, shift, alt)), "{:?} {}", key, bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_map_shortcut_ignores_other_keys() {
+        assert_eq!(map_shortcut("x", true, false, false), None);
+        assert_eq!(map_shortcut("n", false, false, false), None);
+        assert_eq!(map_shortcut("", true, false, false), None);
+        assert_eq!(map_shortcut("ns", true, false, false), None);
+        assert_eq!(map_shortcut("\u{F704}", false, false, false), None);
+    }
+
+    #[test]
+    fn test_cycle_tab_wraps() {
+        assert_eq!(cycle_tab(0, 3, true), Some(1));
+        assert_eq!(cycle_tab(2, 3, true), Some(0));
+        assert_eq!(cycle_tab(0, 3, false), Some(2));
+        assert_eq!(cycle_tab(-1, 3, true), Some(0));
+        assert_eq!(cycle_tab(-1, 3, false), Some(2));
+        assert_eq!(cycle_tab(0, 1, true), Some(0));
+        assert_eq!(cycle_tab(0, 0, true), None);
+    }
+}
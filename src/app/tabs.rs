@@ -0,0 +1,132 @@
+//! Open formation tabs: one per formation, titled with its name
+//!
+//! Opening a formation that already has a tab focuses that tab instead of
+//! adding another; a tab follows its formation when it is renamed.
+
+use std::rc::Rc;
+use slint::{Model, VecModel};
+use super::FormationTab;
+
+/// The formation tabs model, with the bookkeeping done on it
+#[derive(Clone)]
+pub(super) struct TabManager {
+    tabs: Rc<VecModel<FormationTab>>,
+}
+
+impl TabManager {
+    pub(super) fn new(tabs: Rc<VecModel<FormationTab>>) -> Self {
+        Self { tabs }
+    }
+
+    /// Index of the tab of formation `id`
+    pub(super) fn find_by_id(&self, id: i32) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.id == id)
+    }
+
+    /// Tab at `index`
+    pub(super) fn get(&self, index: usize) -> Option<FormationTab> {
+        self.tabs.row_data(index)
+    }
+
+    /// Index of the tab of formation `id`, added at the end with the title
+    /// from `title` and `zoom` when there is none yet
+    pub(super) fn open_or_focus(&self, id: i32, title: impl FnOnce() -> String, zoom: f32) -> usize {
+        if let Some(index) = self.find_by_id(id) {
+            return index;
+        }
+        self.tabs.push(FormationTab {
+            id,
+            title: title().into(),
+            view_mode: "table".into(),
+            compare_version: 0,
+            zoom,
+        });
+        self.tabs.row_count() - 1
+    }
+
+    /// Retitle the tab of formation `id`; its index, if it is open
+    pub(super) fn rename(&self, id: i32, title: &str) -> Option<usize> {
+        let index = self.find_by_id(id)?;
+        let mut tab = self.tabs.row_data(index)?;
+        if tab.title != title {
+            tab.title = title.into();
+            self.tabs.set_row_data(index, tab);
+        }
+        Some(index)
+    }
+
+    /// Close the tab at `index`; the index of the tab to show in its place,
+    /// `None` when it was the last one (or there is no such tab)
+    pub(super) fn close(&self, index: usize) -> Option<usize> {
+        if index >= self.tabs.row_count() {
+            return None;
+        }
+        self.tabs.remove(index);
+        let count = self.tabs.row_count();
+        (count > 0).then(|| index.min(count - 1))
+    }
+}
+
+/// Title of the tab of a formation: its name, or its id when it cannot be
+/// looked up
+pub(super) fn tab_title(id: i32, name: Option<String>) -> String {
+    match name {
+        Some(name) if !name.trim().is_empty() => name,
+        _ => id.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(tabs: &TabManager) -> Vec<String> {
+        tabs.tabs.iter().map(|t| t.title.to_string()).collect()
+    }
+
+    #[test]
+    fn test_open_or_focus_does_not_duplicate() {
+        let tabs = TabManager::new(Rc::new(VecModel::default()));
+        assert_eq!(tabs.open_or_focus(7, || "1st Battalion".to_string(), 1.0), 0);
+        assert_eq!(tabs.open_or_focus(9, || "2nd Battalion".to_string(), 1.5), 1);
+        let reopened = tabs.open_or_focus(7, || panic!("an open tab is not looked up again"), 2.0);
+        assert_eq!(reopened, 0);
+        assert_eq!(titles(&tabs), ["1st Battalion", "2nd Battalion"]);
+        assert_eq!(tabs.get(0).unwrap().zoom, 1.0);
+        assert_eq!(tabs.find_by_id(9), Some(1));
+        assert_eq!(tabs.find_by_id(8), None);
+    }
+
+    #[test]
+    fn test_rename_updates_only_the_open_tab() {
+        let tabs = TabManager::new(Rc::new(VecModel::default()));
+        tabs.open_or_focus(7, || "1st Battalion".to_string(), 1.0);
+        tabs.open_or_focus(9, || "2nd Battalion".to_string(), 1.0);
+        assert_eq!(tabs.rename(9, "Reserve Battalion"), Some(1));
+        assert_eq!(tabs.rename(8, "Nowhere"), None);
+        assert_eq!(titles(&tabs), ["1st Battalion", "Reserve Battalion"]);
+    }
+
+    #[test]
+    fn test_close_shows_the_next_tab() {
+        let tabs = TabManager::new(Rc::new(VecModel::default()));
+        for id in 1..=3 {
+            tabs.open_or_focus(id, || format!("Unit {}", id), 1.0);
+        }
+        assert_eq!(tabs.close(1), Some(1));
+        assert_eq!(titles(&tabs), ["Unit 1", "Unit 3"]);
+        assert_eq!(tabs.close(1), Some(0));
+        assert_eq!(tabs.close(5), None);
+        assert_eq!(tabs.close(0), None);
+        assert!(titles(&tabs).is_empty());
+        // A closed formation gets a new tab
+        assert_eq!(tabs.open_or_focus(2, || "Unit 2".to_string(), 1.0), 0);
+    }
+
+    #[test]
+    fn test_tab_title_falls_back_to_id() {
+        assert_eq!(tab_title(7, Some("1st Battalion".to_string())), "1st Battalion");
+        assert_eq!(tab_title(7, Some(" ".to_string())), "7");
+        assert_eq!(tab_title(7, None), "7");
+    }
+}
@@ -1,43 +1,106 @@
 //! UI translation functions
 //!
-//! Translations are loaded from embedded JSON files in the `i18n/` directory.
-//! To add or modify translations, edit the corresponding `i18n/<lang>.json` file.
-
-use std::collections::HashMap;
-use std::sync::OnceLock;
-
-/// Embedded Russian translation JSON (compiled into the binary).
-const RU_JSON: &str = include_str!("../../i18n/ru.json");
-
-/// Lazily parsed Russian translation map.
-fn ru_translations() -> &'static HashMap<String, String> {
-    static RU: OnceLock<HashMap<String, String>> = OnceLock::new();
-    RU.get_or_init(|| {
-        serde_json::from_str(RU_JSON).unwrap_or_else(|e| {
-            log::error!("Failed to parse ru.json translations: {}", e);
-            HashMap::new()
-        })
-    })
-}
+//! The translation tables are embedded per language by [`crate::i18n`]; to
+//! add or modify translations, edit the corresponding `i18n/<lang>.json` file.
+
+use slint::{ModelRc, VecModel};
 
-/// Return translated string for UI. Keys are English strings (msgid style).
-/// For non-Russian languages, returns the key unchanged.
+use crate::i18n::{available_languages, plural_form, translate, PluralForm};
+
+/// Return translated string for UI. Keys are English strings (msgid style);
+/// keys without a translation in `lang` are returned unchanged.
 pub(crate) fn ui_tr(lang: &str, key: &str) -> String {
-    if lang != "ru" {
-        return key.to_string();
-    }
-    ru_translations()
-        .get(key)
-        .cloned()
-        .unwrap_or_else(|| key.to_string())
+    translate(lang, key)
+}
+
+/// Languages the main window offers, with the label of its quick-switch button
+pub(crate) fn ui_languages() -> ModelRc<super::UiLanguage> {
+    let languages: Vec<super::UiLanguage> = available_languages()
+        .iter()
+        .map(|l| super::UiLanguage { code: l.code.into(), name: l.name.into(), short: l.code.to_uppercase().into() })
+        .collect();
+    ModelRc::new(VecModel::from(languages))
+}
+
+/// Translate `key` and fill its `{}` placeholders with `args`, in order.
+/// Placeholders beyond the last argument are left as they are.
+pub(crate) fn ui_tr_args(lang: &str, key: &str, args: &[&dyn std::fmt::Display]) -> String {
+    args.iter()
+        .fold(ui_tr(lang, key), |text, arg| text.replacen("{}", &arg.to_string(), 1))
+}
+
+/// Translate a message about `n` things, replacing the first `{}` with `n`.
+/// `one` and `many` are the English keys; the Russian translation of `many`
+/// holds the few and many forms separated by `|`.
+pub(crate) fn ui_tr_plural(lang: &str, n: u64, one: &str, many: &str) -> String {
+    let text = match plural_form(n, lang) {
+        PluralForm::One => ui_tr(lang, one),
+        form => {
+            let forms = ui_tr(lang, many);
+            match (form, forms.split_once('|')) {
+                (PluralForm::Few, Some((few, _))) => few.to_string(),
+                (_, Some((_, many))) => many.to_string(),
+                (_, None) => forms,
+            }
+        }
+    };
+    text.replacen("{}", &n.to_string(), 1)
+}
+
+/// A toolbar button; `label` and `tooltip` are translation keys
+struct ToolbarEntry {
+    id: &'static str,
+    glyph: &'static str,
+    label: &'static str,
+    icon: &'static str,
+    tooltip: &'static str,
+    shortcut: &'static str,
+    enabled: bool,
+}
+
+/// Toolbar buttons in order; an entry with an empty id is a separator
+const TOOLBAR: &[ToolbarEntry] = &[
+    ToolbarEntry { id: "new_library", glyph: "📄", label: "New", icon: "icons/document-new.svg", tooltip: "New Library", shortcut: "Ctrl+N", enabled: true },
+    ToolbarEntry { id: "open_library", glyph: "📂", label: "Open", icon: "icons/document-open.svg", tooltip: "Open Library", shortcut: "Ctrl+O", enabled: true },
+    ToolbarEntry { id: "save_library", glyph: "💾", label: "Save", icon: "icons/document-save.svg", tooltip: "Save Library", shortcut: "Ctrl+S", enabled: true },
+    ToolbarEntry { id: "", glyph: "", label: "", icon: "", tooltip: "", shortcut: "", enabled: false },
+    ToolbarEntry { id: "new_formation", glyph: "➕", label: "Formation", icon: "icons/list-add.svg", tooltip: "New Formation", shortcut: "", enabled: false },
+    ToolbarEntry { id: "", glyph: "", label: "", icon: "", tooltip: "", shortcut: "", enabled: false },
+    ToolbarEntry { id: "table_view", glyph: "📊", label: "Table", icon: "icons/view-table.svg", tooltip: "Table View", shortcut: "F2", enabled: true },
+    ToolbarEntry { id: "diagram_view", glyph: "🔀", label: "Diagram", icon: "icons/view-diagram.svg", tooltip: "Diagram View", shortcut: "F3", enabled: true },
+];
+
+/// The toolbar buttons with their texts and tooltips in `lang`.
+fn toolbar_buttons(lang: &str) -> Vec<super::ToolbarButton> {
+    TOOLBAR
+        .iter()
+        .map(|entry| {
+            let is_separator = entry.id.is_empty();
+            let text = if is_separator { String::new() } else { format!("{} {}", entry.glyph, ui_tr(lang, entry.label)) };
+            let mut tooltip = if is_separator { String::new() } else { ui_tr(lang, entry.tooltip) };
+            if !entry.shortcut.is_empty() {
+                tooltip = format!("{} ({})", tooltip, entry.shortcut);
+            }
+            super::ToolbarButton {
+                id: entry.id.into(),
+                text: text.into(),
+                icon: entry.icon.into(),
+                tooltip: tooltip.into(),
+                enabled: entry.enabled,
+                is_separator,
+            }
+        })
+        .collect()
 }
 
 /// Set all UI string properties from Rust so they update when language changes.
 pub(crate) fn apply_ui_translations(window: &super::MainWindow, lang: &str) {
+    window.set_toolbar(ModelRc::new(VecModel::from(toolbar_buttons(lang))));
     window.set_tr_new_library(ui_tr(lang, "New Library").into());
     window.set_tr_open_library(ui_tr(lang, "Open Library").into());
     window.set_tr_save_library(ui_tr(lang, "Save Library").into());
     window.set_tr_language(ui_tr(lang, "Language").into());
+    window.set_tr_as_of(ui_tr(lang, "As of").into());
     window.set_tr_libraries(ui_tr(lang, "Libraries").into());
     window.set_tr_welcome_title(ui_tr(lang, "Welcome to TOEditor").into());
     window.set_tr_welcome_desc(
@@ -46,15 +109,23 @@ pub(crate) fn apply_ui_translations(window: &super::MainWindow, lang: &str) {
     window.set_tr_file(ui_tr(lang, "File").into());
     window.set_tr_open_library_ellipsis(ui_tr(lang, "Open Library…").into());
     window.set_tr_recent_libraries(ui_tr(lang, "Recent Libraries").into());
+    window.set_tr_no_recent_libraries(ui_tr(lang, "No recent libraries").into());
+    window.set_tr_search_libraries(ui_tr(lang, "Search libraries…").into());
     window.set_tr_save_library_as(ui_tr(lang, "Save Library As…").into());
     window.set_tr_import(ui_tr(lang, "Import").into());
     window.set_tr_import_library_from_file(ui_tr(lang, "Import Library from File…").into());
     window.set_tr_import_formation_from_file(ui_tr(lang, "Import Formation from File…").into());
     window.set_tr_export(ui_tr(lang, "Export").into());
     window.set_tr_export_library_ellipsis(ui_tr(lang, "Export Library…").into());
+    window.set_tr_export_anonymized_library(ui_tr(lang, "Export Anonymized Library…").into());
     window.set_tr_export_selected_formation(ui_tr(lang, "Export Selected Formation…").into());
     window.set_tr_export_as_spreadsheet(ui_tr(lang, "Export as Spreadsheet…").into());
     window.set_tr_export_diagram(ui_tr(lang, "Export Diagram…").into());
+    window.set_tr_export_diagrams_per_formation(ui_tr(lang, "Export Diagrams per Formation…").into());
+    window.set_tr_export_interactive_html(ui_tr(lang, "Export Interactive HTML…").into());
+    window.set_tr_export_with_preset(ui_tr(lang, "Export with Preset…").into());
+    window.set_tr_manage_export_presets(ui_tr(lang, "Manage Export Presets…").into());
+    window.set_tr_print_current_tab(ui_tr(lang, "Print Current Tab…").into());
     window.set_tr_exit(ui_tr(lang, "Exit").into());
     window.set_tr_edit(ui_tr(lang, "Edit").into());
     window.set_tr_find(ui_tr(lang, "Find").into());
@@ -71,10 +142,13 @@ pub(crate) fn apply_ui_translations(window: &super::MainWindow, lang: &str) {
     window.set_tr_positions_and_ranks_editor(ui_tr(lang, "Positions and Ranks Editor…").into());
     window.set_tr_equipment_and_vehicles_editor(ui_tr(lang, "Equipment and Vehicles Editor…").into());
     window.set_tr_formation_levels(ui_tr(lang, "Formation levels…").into());
+    window.set_tr_review_level_mappings(ui_tr(lang, "Review Level Mappings…").into());
+    window.set_tr_find_duplicate_units(ui_tr(lang, "Find Duplicate Units…").into());
     window.set_tr_branches(ui_tr(lang, "Branches…").into());
     window.set_tr_branch_categories(ui_tr(lang, "Branch categories…").into());
     window.set_tr_library_properties(ui_tr(lang, "Library Properties…").into());
     window.set_tr_manage_tags(ui_tr(lang, "Manage Tags…").into());
+    window.set_tr_delete_library(ui_tr(lang, "Delete Library…").into());
     window.set_tr_version_control(ui_tr(lang, "Version Control").into());
     window.set_tr_view_history(ui_tr(lang, "View History…").into());
     window.set_tr_create_snapshot(ui_tr(lang, "Create Snapshot (Commit)…").into());
@@ -92,6 +166,13 @@ pub(crate) fn apply_ui_translations(window: &super::MainWindow, lang: &str) {
     window.set_tr_table(ui_tr(lang, "Table").into());
     window.set_tr_diagram(ui_tr(lang, "Diagram").into());
     window.set_tr_table_and_diagram(ui_tr(lang, "Table and Diagram").into());
+    window.set_tr_edit_table(ui_tr(lang, "Edit Table…").into());
+    window.set_tr_compare_with_version(ui_tr(lang, "Compare with Version…").into());
+    window.set_tr_exit_compare(ui_tr(lang, "Exit Comparison").into());
+    window.set_tr_column_name(ui_tr(lang, "Name").into());
+    window.set_tr_column_type(ui_tr(lang, "Type").into());
+    window.set_tr_column_personnel(ui_tr(lang, "Personnel").into());
+    window.set_tr_column_equipment(ui_tr(lang, "Equipment").into());
     window.set_tr_tactical_symbols(ui_tr(lang, "Tactical Symbols").into());
     window.set_tr_nato_app6(ui_tr(lang, "NATO (APP-6)").into());
     window.set_tr_russia_gost(ui_tr(lang, "Russia (ГОСТ РВ)").into());
@@ -100,6 +181,11 @@ pub(crate) fn apply_ui_translations(window: &super::MainWindow, lang: &str) {
     window.set_tr_light(ui_tr(lang, "Light").into());
     window.set_tr_dark(ui_tr(lang, "Dark").into());
     window.set_tr_show_equipment_images(ui_tr(lang, "Show Equipment Images").into());
+    window.set_tr_formation_numbering(ui_tr(lang, "Formation Numbering").into());
+    window.set_tr_numbering_off(ui_tr(lang, "Off").into());
+    window.set_tr_numbering_arabic(ui_tr(lang, "Arabic (1, 2, 3)").into());
+    window.set_tr_numbering_roman(ui_tr(lang, "Roman (I, II, III)").into());
+    window.set_tr_numbering_letters(ui_tr(lang, "Letters (A, B, C)").into());
     window.set_tr_zoom(ui_tr(lang, "Zoom").into());
     window.set_tr_zoom_in(ui_tr(lang, "Zoom In").into());
     window.set_tr_zoom_out(ui_tr(lang, "Zoom Out").into());
@@ -108,20 +194,27 @@ pub(crate) fn apply_ui_translations(window: &super::MainWindow, lang: &str) {
     window.set_tr_tools(ui_tr(lang, "Tools").into());
     window.set_tr_settings(ui_tr(lang, "Settings…").into());
     window.set_tr_interface_language(ui_tr(lang, "Interface Language").into());
-    window.set_tr_english(ui_tr(lang, "English").into());
-    window.set_tr_russian(ui_tr(lang, "Russian").into());
     window.set_tr_data_paths(ui_tr(lang, "Data Paths…").into());
     window.set_tr_reset_settings(ui_tr(lang, "Reset Settings").into());
+    window.set_tr_backup_database(ui_tr(lang, "Back Up Database…").into());
+    window.set_tr_performance_metrics(ui_tr(lang, "Performance Metrics…").into());
+    window.set_tr_export_database_schema(ui_tr(lang, "Export Database Schema…").into());
     window.set_tr_help(ui_tr(lang, "Help").into());
     window.set_tr_user_guide(ui_tr(lang, "User Guide").into());
+    window.set_tr_create_sample_library(ui_tr(lang, "Create Sample Library").into());
     window.set_tr_about_toeditor(ui_tr(lang, "About TOEditor…").into());
     window.set_tr_check_for_updates(ui_tr(lang, "Check for Updates").into());
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ui_tr;
+    use super::{ui_tr, ui_tr_args, ui_tr_plural, TOOLBAR};
     use crate::i18n::Language;
+    use std::collections::HashMap;
+
+    fn ru_translations() -> &'static HashMap<String, String> {
+        crate::i18n::translation_table("ru").expect("Russian translations are embedded")
+    }
 
     #[test]
     fn test_language_switching_callback_setup() {
@@ -167,6 +260,18 @@ mod tests {
         assert_eq!(ui_tr("ru", "Add"), "Добавить");
     }
 
+    #[test]
+    fn test_ui_tr_plural_picks_form() {
+        let one = "{} library copied lists from this one. It will keep its data but lose the link.";
+        let many = "{} libraries copied lists from this one. They will keep their data but lose the link.";
+        assert!(ui_tr_plural("en", 1, one, many).starts_with("1 library copied"));
+        assert!(ui_tr_plural("en", 3, one, many).starts_with("3 libraries copied"));
+        assert!(ui_tr_plural("ru", 21, one, many).starts_with("21 библиотека скопировала"));
+        assert!(ui_tr_plural("ru", 3, one, many).starts_with("3 библиотеки скопировали"));
+        assert!(ui_tr_plural("ru", 5, one, many).starts_with("5 библиотек скопировали"));
+        assert!(!ui_tr_plural("ru", 5, one, many).contains('|'));
+    }
+
     #[test]
     fn test_ui_tr_russian_unknown_key_returns_key() {
         assert_eq!(ui_tr("ru", "This key does not exist"), "This key does not exist");
@@ -189,8 +294,92 @@ mod tests {
 
     #[test]
     fn test_ru_translations_loaded() {
-        let map = super::ru_translations();
+        let map = ru_translations();
         assert!(map.len() > 90, "Expected 90+ translations, got {}", map.len());
         assert_eq!(map.get("File"), Some(&"Файл".to_string()));
     }
+
+    #[test]
+    fn test_ui_tr_args_fills_placeholders_in_order() {
+        let message = ui_tr_args("ru", "Failed to load tags: {}", &[&"disk full"]);
+        assert_eq!(message, "Не удалось загрузить теги: disk full");
+        assert_eq!(ui_tr_args("en", "{} of {}", &[&1, &2]), "1 of 2");
+        assert_eq!(ui_tr_args("en", "{} of {}", &[&1]), "1 of {}");
+    }
+
+    #[test]
+    fn test_toolbar_texts_are_translated() {
+        for entry in TOOLBAR.iter().filter(|e| !e.id.is_empty()) {
+            for key in [entry.label, entry.tooltip] {
+                assert!(ru_translations().contains_key(key), "toolbar key {:?} has no Russian translation", key);
+            }
+        }
+    }
+
+    /// The string literal `arg` stands for, if it is one
+    fn literal(arg: &str) -> Option<String> {
+        let inner = arg.trim().strip_prefix('"')?.strip_suffix('"')?;
+        Some(inner.replace("\\n", "\n").replace("\\\"", "\""))
+    }
+
+    /// Arguments of every call of `function` in `source`
+    fn calls(source: &str, function: &str) -> Vec<Vec<String>> {
+        let opening = format!("{}(", function);
+        let mut calls = Vec::new();
+        for (start, _) in source.match_indices(&opening) {
+            let mut args = vec![String::new()];
+            let (mut depth, mut in_string, mut escaped) = (0, false, false);
+            for c in source[start + opening.len()..].chars() {
+                if in_string {
+                    in_string = c != '"' || escaped;
+                    escaped = c == '\\' && !escaped;
+                } else if c == '"' {
+                    in_string = true;
+                } else if "([{".contains(c) {
+                    depth += 1;
+                } else if ")]}".contains(c) {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                } else if c == ',' && depth == 0 {
+                    args.push(String::new());
+                    continue;
+                }
+                args.last_mut().unwrap().push(c);
+            }
+            calls.push(args);
+        }
+        calls
+    }
+
+    #[test]
+    fn test_error_dialog_literals_are_translation_keys() {
+        let sources = [
+            ("mod.rs", include_str!("mod.rs")),
+            ("dialogs.rs", include_str!("dialogs.rs")),
+            ("recovery.rs", include_str!("recovery.rs")),
+            ("export_presets.rs", include_str!("export_presets.rs")),
+            ("editors/equipment_catalog.rs", include_str!("editors/equipment_catalog.rs")),
+            ("editors/formation_levels.rs", include_str!("editors/formation_levels.rs")),
+            ("editors/history.rs", include_str!("editors/history.rs")),
+            ("editors/tags.rs", include_str!("editors/tags.rs")),
+        ];
+        let mut missing = Vec::new();
+        for (file, source) in sources {
+            // Title and message of error dialogs, after the language, and
+            // the message of menu items not implemented yet
+            let error_keys = calls(source, "show_error_dialog").into_iter().flat_map(|args| args.into_iter().skip(1).take(2));
+            let menu_keys = calls(source, "not_implemented").into_iter().flat_map(|args| args.into_iter().skip(2).take(1));
+            for arg in error_keys.chain(menu_keys) {
+                if let Some(key) = literal(&arg) {
+                    let is_text = key.chars().any(char::is_alphabetic);
+                    if is_text && !ru_translations().contains_key(&key) {
+                        missing.push(format!("{}: {:?}", file, key));
+                    }
+                }
+            }
+        }
+        assert!(missing.is_empty(), "error dialog texts without a Russian translation:\n{}", missing.join("\n"));
+    }
 }
@@ -0,0 +1,201 @@
+//! Remembered size and position of editors and dialogs
+//!
+//! Call [`remember_geometry`] on a new window before showing it and
+//! [`hide_remembering`] wherever it is closed; closing from the title bar is
+//! covered by the former.
+
+use slint::{ComponentHandle, LogicalPosition, LogicalSize, Weak};
+use std::cell::RefCell;
+use crate::config::{Settings, WindowGeometry};
+use super::MainWindow;
+
+/// Auxiliary window whose geometry is kept in the settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum WindowKind {
+    BranchesEditor,
+    BranchCategoriesEditor,
+    FormationLevelsEditor,
+    EquipmentCatalogEditor,
+    FormationTableEditor,
+    TagsEditor,
+    HistoryWindow,
+    CategoryConflictDialog,
+    LevelRemapDialog,
+    MetricsDialog,
+    ImportPreviewDialog,
+    CompareVersionsDialog,
+    LevelMappingDialog,
+    DuplicateUnitsDialog,
+    ExportPresetsDialog,
+    SummaryTableDialog,
+    OpenLibraryDialog,
+}
+
+impl WindowKind {
+    /// Key of the window type in the settings file
+    pub(super) fn key(self) -> &'static str {
+        match self {
+            WindowKind::BranchesEditor => "branches_editor",
+            WindowKind::BranchCategoriesEditor => "branch_categories_editor",
+            WindowKind::FormationLevelsEditor => "formation_levels_editor",
+            WindowKind::EquipmentCatalogEditor => "equipment_catalog_editor",
+            WindowKind::FormationTableEditor => "formation_table_editor",
+            WindowKind::TagsEditor => "tags_editor",
+            WindowKind::HistoryWindow => "history_window",
+            WindowKind::CategoryConflictDialog => "category_conflict_dialog",
+            WindowKind::LevelRemapDialog => "level_remap_dialog",
+            WindowKind::MetricsDialog => "metrics_dialog",
+            WindowKind::ImportPreviewDialog => "import_preview_dialog",
+            WindowKind::CompareVersionsDialog => "compare_versions_dialog",
+            WindowKind::LevelMappingDialog => "level_mapping_dialog",
+            WindowKind::DuplicateUnitsDialog => "duplicate_units_dialog",
+            WindowKind::ExportPresetsDialog => "export_presets_dialog",
+            WindowKind::SummaryTableDialog => "summary_table_dialog",
+            WindowKind::OpenLibraryDialog => "open_library_dialog",
+        }
+    }
+}
+
+thread_local! {
+    /// Slint does not report screen bounds; the main window, which the user
+    /// can see, stands in for the visible area
+    static MAIN_WINDOW: RefCell<Option<Weak<MainWindow>>> = const { RefCell::new(None) };
+}
+
+/// Use `window` as the visible area auxiliary windows are kept inside
+pub(super) fn set_main_window(window: &MainWindow) {
+    MAIN_WINDOW.with(|main| *main.borrow_mut() = Some(window.as_weak()));
+}
+
+/// Geometry of a shown window; `None` before it has a size
+fn geometry_of(window: &slint::Window) -> Option<WindowGeometry> {
+    let scale = window.scale_factor();
+    let size = window.size().to_logical(scale);
+    let position = window.position().to_logical(scale);
+    (size.width > 0.0 && size.height > 0.0).then(|| WindowGeometry {
+        x: position.x.round() as i32,
+        y: position.y.round() as i32,
+        width: size.width.round() as u32,
+        height: size.height.round() as u32,
+    })
+}
+
+/// Logical geometry of the main window, the area popups are kept inside
+pub(super) fn visible_area() -> Option<WindowGeometry> {
+    MAIN_WINDOW.with(|main| {
+        let main = main.borrow().as_ref()?.upgrade()?;
+        geometry_of(main.window())
+    })
+}
+
+/// Give a window about to be shown the size and position it had when last
+/// closed, and remember them again when it is closed from the title bar
+pub(super) fn remember_geometry<C: ComponentHandle + 'static>(component: &C, kind: WindowKind) {
+    let saved = Settings::load().ok().and_then(|s| s.window_geometry(kind.key()));
+    if let Some(saved) = saved {
+        let geometry = match visible_area() {
+            Some(area) => saved.clamped_to(area),
+            None => saved.with_min_size(),
+        };
+        let window = component.window();
+        window.set_size(LogicalSize::new(geometry.width as f32, geometry.height as f32));
+        window.set_position(LogicalPosition::new(geometry.x as f32, geometry.y as f32));
+    }
+
+    let weak = component.as_weak();
+    component.window().on_close_requested(move || {
+        if let Some(component) = weak.upgrade() {
+            save_geometry(component.window(), kind);
+        }
+        slint::CloseRequestResponse::HideWindow
+    });
+}
+
+/// Remember a window's geometry and hide it
+pub(super) fn hide_remembering<C: ComponentHandle>(component: &C, kind: WindowKind) {
+    save_geometry(component.window(), kind);
+    component.hide().unwrap_or_default();
+}
+
+fn save_geometry(window: &slint::Window, kind: WindowKind) {
+    let Some(geometry) = geometry_of(window) else {
+        return;
+    };
+    let mut settings = Settings::load().unwrap_or_default();
+    if settings.window_geometry(kind.key()) == Some(geometry) {
+        return;
+    }
+    settings.remember_window_geometry(kind.key(), geometry);
+    if let Err(e) = settings.save() {
+        log::error!("Failed to save window geometry: {}", e);
+    }
+}
+
+/// Screen position of a popup opened by a click at `click` in a window whose
+/// top left corner is at `window_pos`. The popup opens below and right of the
+/// click, or above or left of it where it would stick out of `bounds`, and is
+/// then moved inside them; one larger than `bounds` keeps its top left corner
+/// inside. All in logical pixels.
+pub(super) fn popup_position(
+    window_pos: LogicalPosition,
+    click: LogicalPosition,
+    menu_size: LogicalSize,
+    bounds: WindowGeometry,
+) -> LogicalPosition {
+    let place = |at: f32, size: f32, start: i32, extent: u32| {
+        let (start, end) = (start as f32, start as f32 + extent as f32);
+        let at = if at + size > end && at - size >= start { at - size } else { at };
+        at.min(end - size).max(start)
+    };
+    LogicalPosition::new(
+        place(window_pos.x + click.x, menu_size.width, bounds.x, bounds.width),
+        place(window_pos.y + click.y, menu_size.height, bounds.y, bounds.height),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MENU: LogicalSize = LogicalSize { width: 200.0, height: 150.0 };
+
+    fn bounds(x: i32, y: i32) -> WindowGeometry {
+        WindowGeometry { x, y, width: 1000, height: 800 }
+    }
+
+    fn at(window: (f32, f32), click: (f32, f32), area: WindowGeometry) -> (f32, f32) {
+        let p = popup_position(LogicalPosition::new(window.0, window.1), LogicalPosition::new(click.0, click.1), MENU, area);
+        (p.x, p.y)
+    }
+
+    #[test]
+    fn test_popup_opens_at_the_click() {
+        assert_eq!(at((100.0, 50.0), (30.0, 40.0), bounds(100, 50)), (130.0, 90.0));
+    }
+
+    #[test]
+    fn test_popup_flips_at_the_bottom_right_corner() {
+        assert_eq!(at((0.0, 0.0), (990.0, 790.0), bounds(0, 0)), (790.0, 640.0));
+        assert_eq!(at((0.0, 0.0), (990.0, 10.0), bounds(0, 0)), (790.0, 10.0));
+        assert_eq!(at((0.0, 0.0), (10.0, 790.0), bounds(0, 0)), (10.0, 640.0));
+    }
+
+    #[test]
+    fn test_popup_stays_inside_at_the_top_left_corner() {
+        assert_eq!(at((0.0, 0.0), (-20.0, -5.0), bounds(0, 0)), (0.0, 0.0));
+        assert_eq!(at((0.0, 0.0), (0.0, 0.0), bounds(0, 0)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_popup_on_a_screen_left_of_the_primary() {
+        // Window on a monitor at negative coordinates
+        assert_eq!(at((-1920.0, -200.0), (50.0, 60.0), bounds(-1920, -200)), (-1870.0, -140.0));
+        assert_eq!(at((-1920.0, -200.0), (995.0, 795.0), bounds(-1920, -200)), (-1125.0, 445.0));
+    }
+
+    #[test]
+    fn test_popup_larger_than_the_bounds() {
+        let small = WindowGeometry { x: 10, y: 20, width: 150, height: 100 };
+        assert_eq!(at((10.0, 20.0), (100.0, 80.0), small), (10.0, 20.0));
+    }
+}
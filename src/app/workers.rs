@@ -0,0 +1,98 @@
+//! Long-running work off the UI thread
+//!
+//! Exports, imports and backups run on worker threads. Each job stays in the
+//! [`OperationRegistry`] until its result has been handled on the UI thread, so
+//! closing the main window waits for it instead of cutting it off halfway.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::api::LibraryManager;
+use crate::services::OperationRegistry;
+use super::AppState;
+
+type Finish = Box<dyn FnOnce(Box<dyn Any + Send>)>;
+
+thread_local! {
+    /// Result handlers of running jobs by job id; they hold UI state and so
+    /// never leave the UI thread
+    static PENDING: RefCell<HashMap<u64, Finish>> = RefCell::new(HashMap::new());
+    static NEXT_JOB: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Run `work` on a worker thread as operation `name`, then hand its result to
+/// `done` on the UI thread
+pub(super) fn run_in_background<T: Send + 'static>(
+    operations: &OperationRegistry,
+    name: &str,
+    work: impl FnOnce() -> T + Send + 'static,
+    done: impl FnOnce(T) + 'static,
+) {
+    let job = NEXT_JOB.with(|next| {
+        next.set(next.get() + 1);
+        next.get()
+    });
+    let finish: Finish = Box::new(move |result| match result.downcast::<T>() {
+        Ok(result) => done(*result),
+        Err(_) => log::error!("Background job {} returned an unexpected result", job),
+    });
+    PENDING.with(|pending| pending.borrow_mut().insert(job, finish));
+    let op = operations.register(name, false);
+    let spawned = std::thread::Builder::new().name(format!("toeditor-job-{}", job)).spawn(move || {
+        let result: Box<dyn Any + Send> = Box::new(work());
+        let delivered = slint::invoke_from_event_loop(move || {
+            finish_job(job, result);
+            // Listed until the result is handled, so the window stays open for it
+            drop(op);
+        });
+        if let Err(e) = delivered {
+            log::warn!("Result of background job {} dropped: {}", job, e);
+        }
+    });
+    if let Err(e) = spawned {
+        log::error!("Failed to start background job {}: {}", job, e);
+        PENDING.with(|pending| pending.borrow_mut().remove(&job));
+    }
+}
+
+fn finish_job(job: u64, result: Box<dyn Any + Send>) {
+    if let Some(finish) = PENDING.with(|pending| pending.borrow_mut().remove(&job)) {
+        finish(result);
+    }
+}
+
+/// Run `work` as operation `name` on a worker with its own connection to the
+/// open database, then hand its result to `done` on the UI thread. An
+/// in-memory database has no other connection, so there `work` runs at once.
+pub(super) fn run_with_manager<T: Send + 'static>(
+    state: &Rc<RefCell<AppState>>,
+    name: &str,
+    work: impl FnOnce(&LibraryManager) -> anyhow::Result<T> + Send + 'static,
+    done: impl FnOnce(anyhow::Result<T>) + 'static,
+) {
+    let (path, tree_limits, operations) = {
+        let st = state.borrow();
+        let Some(manager) = st.library_manager.as_ref() else {
+            log::error!("Database not initialized");
+            return;
+        };
+        let Some(path) = manager.database().path() else {
+            let result = {
+                let _op = st.operations.register(name, false);
+                work(manager)
+            };
+            drop(st);
+            done(result);
+            return;
+        };
+        (path, st.tree_limits, st.operations.clone())
+    };
+    run_in_background(
+        &operations,
+        name,
+        move || LibraryManager::open_existing(&path).and_then(|manager| work(&manager.with_tree_limits(tree_limits))),
+        done,
+    );
+}
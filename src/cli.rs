@@ -0,0 +1,456 @@
+//! Command-line interface for headless operations
+//!
+//! When no subcommand is given the GUI starts as usual.
+
+use anyhow::{bail, Context, Result};
+use rusqlite::Connection;
+use std::path::PathBuf;
+
+use crate::api::{ExportFormat, LibraryManager};
+use crate::db::repositories::LibraryRepo;
+use crate::models::Library;
+
+/// Successful run
+pub const EXIT_OK: i32 = 0;
+/// Operation failed
+pub const EXIT_FAILURE: i32 = 1;
+/// Invalid command line
+pub const EXIT_USAGE: i32 = 2;
+/// Requested library does not exist
+pub const EXIT_LIBRARY_NOT_FOUND: i32 = 3;
+
+/// Usage text printed on argument errors
+pub const USAGE: &str = "Usage:
+  toeditor                                   start the graphical editor
+  toeditor list [--db <path>]
+  toeditor export --library <id|name> --out <file> [--format json|full|yaml|csv|xlsx] [--db <path>]
+  toeditor import <file> [--db <path>]
+  toeditor snapshot --library <id|name> [--description <text>] [--db <path>]
+  toeditor stats --library <id|name> [--db <path>]
+  toeditor migrate [--db <path>]
+  toeditor export-versions --library <id|name> --out-dir <dir> [--db <path>]
+
+The database defaults to the one in the settings. Without --format the
+export format follows the extension of --out.";
+
+/// A parsed subcommand
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Print the id, name, country, era and version of every library
+    List { db: Option<PathBuf> },
+    /// Write one library to a file
+    Export {
+        db: Option<PathBuf>,
+        library: String,
+        format: ExportFormat,
+        out: PathBuf,
+    },
+    /// Store a JSON, YAML or Excel library file as a new library
+    Import { db: Option<PathBuf>, file: PathBuf },
+    /// Store a library's current state as its next version
+    Snapshot {
+        db: Option<PathBuf>,
+        library: String,
+        description: String,
+    },
+    /// Print a library's counts and dates as JSON
+    Stats { db: Option<PathBuf>, library: String },
+    /// Open the database, running any pending migrations
+    Migrate { db: Option<PathBuf> },
+    /// Write every snapshot of a library as v{N}.json plus index.json
+    ExportVersions {
+        db: Option<PathBuf>,
+        library: String,
+        out_dir: PathBuf,
+    },
+}
+
+/// Options and positional arguments of one subcommand
+struct Args {
+    options: Vec<(String, String)>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Split `args` into `--name value` pairs (only the names in `allowed`)
+    /// and positional arguments
+    fn parse(args: impl Iterator<Item = String>, allowed: &[&str]) -> Result<Self> {
+        let mut args = args;
+        let mut parsed = Args { options: Vec::new(), positional: Vec::new() };
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                parsed.positional.push(arg);
+                continue;
+            }
+            if !allowed.contains(&arg.as_str()) {
+                bail!("Unknown option: {}", arg);
+            }
+            let Some(value) = args.next() else {
+                bail!("Missing value for {}", arg);
+            };
+            parsed.options.push((arg, value));
+        }
+        Ok(parsed)
+    }
+
+    /// Value of an option; the last one wins when it is repeated
+    fn get(&self, name: &str) -> Option<String> {
+        self.options.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.clone())
+    }
+
+    fn require(&self, name: &str) -> Result<String> {
+        self.get(name).ok_or_else(|| anyhow::anyhow!("Missing required option {}", name))
+    }
+
+    fn db(&self) -> Option<PathBuf> {
+        self.get("--db").map(PathBuf::from)
+    }
+
+    /// Fail on positional arguments past the first `expected`
+    fn no_more_than(&self, expected: usize) -> Result<()> {
+        match self.positional.get(expected) {
+            Some(extra) => bail!("Unexpected argument: {}", extra),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse command-line arguments (without the program name).
+/// Returns `Ok(None)` when no subcommand is given.
+pub fn parse_args<I>(args: I) -> Result<Option<Command>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let Some(subcommand) = args.next() else {
+        return Ok(None);
+    };
+    let command = match subcommand.as_str() {
+        "list" => {
+            let args = Args::parse(args, &["--db"])?;
+            args.no_more_than(0)?;
+            Command::List { db: args.db() }
+        }
+        "export" => {
+            let args = Args::parse(args, &["--db", "--library", "--format", "--out"])?;
+            args.no_more_than(0)?;
+            let library = args.require("--library")?;
+            let out = PathBuf::from(args.require("--out")?);
+            let format = match args.get("--format") {
+                Some(name) => ExportFormat::from_name(&name)?,
+                None => ExportFormat::from_path(&out)?,
+            };
+            Command::Export { db: args.db(), library, format, out }
+        }
+        "import" => {
+            let args = Args::parse(args, &["--db"])?;
+            args.no_more_than(1)?;
+            let Some(file) = args.positional.first() else {
+                bail!("Missing the file to import");
+            };
+            Command::Import { db: args.db(), file: PathBuf::from(file) }
+        }
+        "snapshot" => {
+            let args = Args::parse(args, &["--db", "--library", "--description"])?;
+            args.no_more_than(0)?;
+            Command::Snapshot {
+                db: args.db(),
+                library: args.require("--library")?,
+                description: args.get("--description").unwrap_or_default(),
+            }
+        }
+        "stats" => {
+            let args = Args::parse(args, &["--db", "--library"])?;
+            args.no_more_than(0)?;
+            Command::Stats { db: args.db(), library: args.require("--library")? }
+        }
+        "migrate" => {
+            let args = Args::parse(args, &["--db"])?;
+            args.no_more_than(0)?;
+            Command::Migrate { db: args.db() }
+        }
+        "export-versions" => {
+            let args = Args::parse(args, &["--db", "--library", "--out-dir"])?;
+            args.no_more_than(0)?;
+            let library = args.require("--library")?;
+            let out_dir = PathBuf::from(args.require("--out-dir")?);
+            Command::ExportVersions { db: args.db(), library, out_dir }
+        }
+        other => bail!("Unknown command: {}", other),
+    };
+    Ok(Some(command))
+}
+
+/// Find a library by numeric id or exact name.
+pub fn resolve_library(conn: &Connection, id_or_name: &str) -> Result<Option<Library>> {
+    let repo = LibraryRepo::new(conn);
+    if let Ok(id) = id_or_name.trim().parse::<i64>() {
+        if let Some(lib) = repo.get_by_id(id)? {
+            return Ok(Some(lib));
+        }
+    }
+    Ok(repo
+        .list_all()?
+        .into_iter()
+        .find(|l| l.name == id_or_name))
+}
+
+/// The database given with `--db`, or the one in the settings. Reading the
+/// settings writes nothing, not even on first run.
+fn database_path(db: &Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(p) = db {
+        return Ok(p.clone());
+    }
+    let settings = crate::config::Settings::load_read_only().unwrap_or_default();
+    match settings.database_path {
+        Some(p) => Ok(p),
+        None => crate::config::Settings::default_database_file(),
+    }
+}
+
+/// Open the database of a command that works on existing libraries; a
+/// missing file is an error rather than created
+fn open_manager(db: &Option<PathBuf>) -> Result<LibraryManager> {
+    let path = database_path(db)?;
+    LibraryManager::open_existing(&path).with_context(|| format!("Failed to open database {}", path.display()))
+}
+
+/// Open the database of `import` or `migrate`, creating it if needed
+fn open_or_create_manager(db: &Option<PathBuf>) -> Result<LibraryManager> {
+    let path = database_path(db)?;
+    LibraryManager::open(&path).with_context(|| format!("Failed to open database {}", path.display()))
+}
+
+/// Why a command did not finish
+enum Failure {
+    LibraryNotFound(String),
+    Error(anyhow::Error),
+}
+
+impl<E: Into<anyhow::Error>> From<E> for Failure {
+    fn from(e: E) -> Self {
+        Failure::Error(e.into())
+    }
+}
+
+/// The library named by `--library`
+fn library(manager: &LibraryManager, id_or_name: &str) -> Result<Library, Failure> {
+    resolve_library(manager.database().conn(), id_or_name)
+        .context("Failed to look up library")?
+        .ok_or_else(|| Failure::LibraryNotFound(id_or_name.to_string()))
+}
+
+/// Execute a command and return the process exit code.
+pub fn run(command: Command) -> i32 {
+    match execute(command) {
+        Ok(()) => EXIT_OK,
+        Err(Failure::LibraryNotFound(name)) => {
+            eprintln!("Library not found: {}", name);
+            EXIT_LIBRARY_NOT_FOUND
+        }
+        Err(Failure::Error(e)) => {
+            eprintln!("{:#}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn execute(command: Command) -> Result<(), Failure> {
+    match command {
+        Command::List { db } => {
+            let manager = open_manager(&db)?;
+            for lib in manager.libraries()? {
+                println!(
+                    "{}\t{}\t{}\t{}\tv{}",
+                    lib.id.unwrap_or_default(),
+                    lib.name,
+                    lib.country,
+                    lib.era,
+                    lib.version
+                );
+            }
+        }
+        Command::Export { db, library: name, format, out } => {
+            let manager = open_manager(&db)?;
+            let lib = library(&manager, &name)?;
+            manager
+                .export_library_as(lib.id.unwrap_or_default(), &out, format)
+                .context("Export failed")?;
+            println!("Exported \"{}\" to {}", lib.name, out.display());
+        }
+        Command::Import { db, file } => {
+            let manager = open_or_create_manager(&db)?;
+            let lib = manager
+                .import_library(&file)
+                .with_context(|| format!("Failed to import {}", file.display()))?;
+            println!("Imported \"{}\" as library {}", lib.name, lib.id.unwrap_or_default());
+        }
+        Command::Snapshot { db, library: name, description } => {
+            let manager = open_manager(&db)?;
+            let lib = library(&manager, &name)?;
+            let snapshot = manager
+                .create_snapshot(lib.id.unwrap_or_default(), &description)
+                .context("Snapshot failed")?;
+            println!("Stored version {} of \"{}\"", snapshot.version, lib.name);
+        }
+        Command::Stats { db, library: name } => {
+            let manager = open_manager(&db)?;
+            let lib = library(&manager, &name)?;
+            let stats = manager.stats(lib.id.unwrap_or_default())?;
+            println!("{}", serde_json::to_string_pretty(&stats).context("Failed to write the statistics")?);
+        }
+        Command::Migrate { db } => {
+            let manager = open_or_create_manager(&db)?;
+            let database = manager.database();
+            let path = database.path().unwrap_or_default();
+            println!("{} is at schema version {}", path.display(), database.schema_version());
+        }
+        Command::ExportVersions { db, library: name, out_dir } => {
+            let manager = open_manager(&db)?;
+            let lib = library(&manager, &name)?;
+            let index = crate::export::versions::export_all_versions(
+                manager.database().conn(),
+                lib.id.unwrap_or_default(),
+                &out_dir,
+            )
+            .context("Export failed")?;
+            println!("Exported {} versions of \"{}\" to {}", index.len(), lib.name, out_dir.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_args_starts_gui() {
+        assert_eq!(parse_args(args(&[])).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_export_versions() {
+        let cmd = parse_args(args(&[
+            "export-versions", "--db", "a.db", "--library", "US Army", "--out-dir", "out",
+        ]))
+        .unwrap();
+        assert_eq!(
+            cmd,
+            Some(Command::ExportVersions {
+                db: Some(PathBuf::from("a.db")),
+                library: "US Army".to_string(),
+                out_dir: PathBuf::from("out"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_export_format_from_option_or_extension() {
+        let cmd = parse_args(args(&["export", "--library", "US Army 2003", "--format", "csv", "--out", "out.txt"]))
+            .unwrap();
+        assert_eq!(
+            cmd,
+            Some(Command::Export {
+                db: None,
+                library: "US Army 2003".to_string(),
+                format: ExportFormat::Csv,
+                out: PathBuf::from("out.txt"),
+            })
+        );
+        let cmd = parse_args(args(&["export", "--out", "army.yml", "--library", "3", "--db", "a.db"])).unwrap();
+        assert!(matches!(cmd, Some(Command::Export { format: ExportFormat::Yaml, db: Some(_), .. })));
+        assert!(parse_args(args(&["export", "--library", "3", "--out", "army.txt"])).is_err());
+        assert!(parse_args(args(&["export", "--library", "3", "--format", "pdf", "--out", "a.json"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_list_import_snapshot_migrate() {
+        assert_eq!(parse_args(args(&["list"])).unwrap(), Some(Command::List { db: None }));
+        assert_eq!(
+            parse_args(args(&["import", "file.json", "--db", "path.db"])).unwrap(),
+            Some(Command::Import {
+                db: Some(PathBuf::from("path.db")),
+                file: PathBuf::from("file.json"),
+            })
+        );
+        assert_eq!(
+            parse_args(args(&["snapshot", "--library", "3", "--description", "Before review"])).unwrap(),
+            Some(Command::Snapshot {
+                db: None,
+                library: "3".to_string(),
+                description: "Before review".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_args(args(&["stats", "--library", "3"])).unwrap(),
+            Some(Command::Stats { db: None, library: "3".to_string() })
+        );
+        assert_eq!(
+            parse_args(args(&["migrate", "--db", "path.db"])).unwrap(),
+            Some(Command::Migrate { db: Some(PathBuf::from("path.db")) })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_args(args(&["import"])).is_err());
+        assert!(parse_args(args(&["import", "a.json", "b.json"])).is_err());
+        assert!(parse_args(args(&["list", "extra"])).is_err());
+        assert!(parse_args(args(&["snapshot", "--description", "x"])).is_err());
+        assert!(parse_args(args(&["migrate", "--library", "3"])).is_err());
+        assert!(parse_args(args(&["stats"])).is_err());
+        assert!(parse_args(args(&["export-versions", "--out-dir", "out"])).is_err());
+        assert!(parse_args(args(&["export-versions", "--library"])).is_err());
+        assert!(parse_args(args(&["export-versions", "--bogus", "x"])).is_err());
+        assert!(parse_args(args(&["frobnicate"])).is_err());
+    }
+
+    #[test]
+    fn test_resolve_library_by_id_and_name() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut lib = Library::new(
+            "US Army 2003".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        repo.create(&mut lib).unwrap();
+        let id = lib.id.unwrap();
+        assert_eq!(resolve_library(db.conn(), &id.to_string()).unwrap().unwrap().id, Some(id));
+        assert_eq!(resolve_library(db.conn(), "US Army 2003").unwrap().unwrap().id, Some(id));
+        assert!(resolve_library(db.conn(), "Missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_run_library_not_found() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("cli.db");
+        drop(Database::open(&path).unwrap());
+        let code = run(Command::ExportVersions {
+            db: Some(path),
+            library: "Nope".to_string(),
+            out_dir: dir.path().join("out"),
+        });
+        assert_eq!(code, EXIT_LIBRARY_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_run_snapshot_library_not_found() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("cli.db");
+        drop(Database::open(&path).unwrap());
+        let code = run(Command::Snapshot {
+            db: Some(path),
+            library: "Nope".to_string(),
+            description: String::new(),
+        });
+        assert_eq!(code, EXIT_LIBRARY_NOT_FOUND);
+    }
+}
@@ -2,8 +2,82 @@
 
 use anyhow::Result;
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize, Serializer};
+use crate::export::{builtin_presets, ExportPreset};
+use crate::i18n::language_for_locale;
+use crate::models::TreeLimits;
+use crate::services::DEFAULT_AUTOSAVE_KEEP;
+use crate::view::zoom::DEFAULT_ZOOM;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Most entries kept in the Recent Libraries list
+pub const MAX_RECENT_LIBRARIES: usize = 10;
+
+/// Most entries kept in the path-entry dialog's recent paths
+pub const MAX_RECENT_PATHS: usize = 10;
+
+/// A library in the File > Recent Libraries list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentEntry {
+    pub library_id: i64,
+    /// Name when last opened (shown in the menu)
+    pub name: String,
+    /// Unix timestamp of the last time the library was opened
+    pub last_opened: i64,
+}
+
+/// Minutes between autosaves of a library with unsaved changes
+pub const DEFAULT_AUTOSAVE_MINUTES: u32 = 10;
+
+/// Smallest size a remembered window is restored at, in logical pixels
+pub const MIN_WINDOW_SIZE: (u32, u32) = (200, 120);
+
+/// Width of a window's top edge that must stay inside the visible area, so
+/// the window can still be grabbed and moved
+const MIN_VISIBLE_WIDTH: i64 = 80;
+
+/// Height of the title bar kept inside the visible area
+const MIN_VISIBLE_HEIGHT: i64 = 32;
+
+/// Size and position of a window in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowGeometry {
+    /// The same geometry, at least [`MIN_WINDOW_SIZE`]
+    pub fn with_min_size(self) -> Self {
+        Self {
+            width: self.width.max(MIN_WINDOW_SIZE.0),
+            height: self.height.max(MIN_WINDOW_SIZE.1),
+            ..self
+        }
+    }
+
+    /// The geometry moved so the window's title bar overlaps `area`: a window
+    /// left on a screen that is gone comes back to the nearest edge of `area`
+    pub fn clamped_to(self, area: WindowGeometry) -> Self {
+        let g = self.with_min_size();
+        let (width, height) = (i64::from(g.width), i64::from(g.height));
+        let (area_x, area_y) = (i64::from(area.x), i64::from(area.y));
+        let (area_width, area_height) = (i64::from(area.width), i64::from(area.height));
+
+        let visible_width = MIN_VISIBLE_WIDTH.min(width).min(area_width);
+        let x = i64::from(g.x).clamp(area_x + visible_width - width, area_x + area_width - visible_width);
+        let visible_height = MIN_VISIBLE_HEIGHT.min(height).min(area_height);
+        let y = i64::from(g.y).clamp(area_y, area_y + area_height - visible_height);
+        Self {
+            x: x as i32,
+            y: y as i32,
+            ..g
+        }
+    }
+}
 
 /// Application settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +90,70 @@ pub struct Settings {
     pub language: String,
     /// Database path
     pub database_path: Option<PathBuf>,
+    /// Show developer tools (Tools > Performance Metrics)
+    #[serde(default)]
+    pub debug_tools: bool,
+    /// Paths entered in the fallback file dialog, most recent first
+    #[serde(default)]
+    pub recent_paths: Vec<PathBuf>,
+    /// Recently opened libraries, most recent first
+    #[serde(default)]
+    pub recent_libraries: Vec<RecentEntry>,
+    /// Largest diagram page as [width, height] in pixels; `None` exports
+    /// diagrams as a single file
+    #[serde(default)]
+    pub diagram_page_limit: Option<[u32; 2]>,
+    /// Database in a cloud-synced folder that the user chose to keep there
+    /// without being warned again
+    #[serde(default)]
+    pub cloud_sync_warning_silenced: Option<PathBuf>,
+    /// Use journal settings safe for a synced folder and back the database
+    /// up on exit
+    #[serde(default)]
+    pub safe_database_mode: bool,
+    /// Zoom last used in a formation view, for new tabs and the next session
+    #[serde(default = "default_zoom")]
+    pub zoom: f32,
+    /// Minutes between autosave snapshots of the open library while it has
+    /// unsaved changes; `None` (written as 0) turns autosave off
+    #[serde(default = "default_autosave_minutes", serialize_with = "serialize_autosave_minutes")]
+    pub autosave_minutes: Option<u32>,
+    /// Autosave snapshots kept per library; older ones are deleted
+    #[serde(default = "default_autosave_keep")]
+    pub autosave_keep: usize,
+    /// Last size and position of editors and dialogs, by window type
+    #[serde(default)]
+    pub window_geometry: BTreeMap<String, WindowGeometry>,
+    /// Folder export save dialogs start in; `None` uses the documents folder
+    #[serde(default)]
+    pub default_export_dir: Option<PathBuf>,
+    /// Depth and width limits of unit trees
+    #[serde(default)]
+    pub tree_limits: TreeLimits,
+    /// Refuse a TOE number another unit of the same library already has
+    #[serde(default)]
+    pub unique_toe_numbers: bool,
+    /// Named export setups, offered in File > Export with Preset and the
+    /// library context menu
+    #[serde(default = "builtin_presets")]
+    pub export_presets: Vec<ExportPreset>,
+}
+
+fn default_zoom() -> f32 {
+    DEFAULT_ZOOM
+}
+
+fn default_autosave_minutes() -> Option<u32> {
+    Some(DEFAULT_AUTOSAVE_MINUTES)
+}
+
+fn default_autosave_keep() -> usize {
+    DEFAULT_AUTOSAVE_KEEP
+}
+
+/// TOML has no null: autosave turned off is written as 0 minutes
+fn serialize_autosave_minutes<S: Serializer>(minutes: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u32(minutes.unwrap_or(0))
 }
 
 impl Default for Settings {
@@ -25,6 +163,20 @@ impl Default for Settings {
             color_scheme: "light".to_string(),
             language: "en".to_string(),
             database_path: None,
+            debug_tools: false,
+            recent_libraries: Vec::new(),
+            recent_paths: Vec::new(),
+            diagram_page_limit: None,
+            cloud_sync_warning_silenced: None,
+            safe_database_mode: false,
+            zoom: DEFAULT_ZOOM,
+            autosave_minutes: default_autosave_minutes(),
+            autosave_keep: DEFAULT_AUTOSAVE_KEEP,
+            window_geometry: BTreeMap::new(),
+            default_export_dir: None,
+            tree_limits: TreeLimits::default(),
+            unique_toe_numbers: false,
+            export_presets: builtin_presets(),
         }
     }
 }
@@ -33,23 +185,165 @@ impl Settings {
     /// Load settings from file
     pub fn load() -> Result<Self> {
         let config_path = Self::config_dir()?.join("settings.toml");
-        if config_path.exists() {
-            let content = std::fs::read_to_string(&config_path)?;
-            let settings: Settings = toml::from_str(&content)?;
-            Ok(settings)
-        } else {
-            Ok(Settings::default())
+        Self::load_or_init(&config_path, sys_locale::get_locale)
+    }
+
+    /// Like [`Self::load`], but without writing the settings on first run
+    pub fn load_read_only() -> Result<Self> {
+        let config_path = Self::config_dir()?.join("settings.toml");
+        Self::read_or_first_run(&config_path, sys_locale::get_locale)
+    }
+
+    /// Load settings from `config_path`.
+    ///
+    /// On first run (no settings file yet) the initial language is taken from the
+    /// system locale and the settings are written, so later launches keep the
+    /// user's choice instead of detecting again.
+    pub fn load_or_init<F>(config_path: &Path, system_locale: F) -> Result<Self>
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        let first_run = !config_path.exists();
+        let settings = Self::read_or_first_run(config_path, system_locale)?;
+        if first_run {
+            if let Err(e) = settings.save_to(config_path) {
+                log::warn!("Could not write initial settings: {}", e);
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Settings from `config_path`, falling back to its backup when it is
+    /// unreadable, or the first-run settings when there is no file; nothing
+    /// is written
+    pub fn read_or_first_run<F>(config_path: &Path, system_locale: F) -> Result<Self>
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        if !config_path.exists() {
+            return Ok(Settings::first_run(system_locale().as_deref()));
         }
+        match Self::read_file(config_path) {
+            Ok(settings) => Ok(settings),
+            Err(e) => {
+                let backup = backup_path(config_path);
+                let settings = Self::read_file(&backup).map_err(|_| e)?;
+                log::warn!(
+                    "Settings file {} is unreadable, using backup {}",
+                    config_path.display(),
+                    backup.display()
+                );
+                Ok(settings)
+            }
+        }
+    }
+
+    /// Default settings with the language chosen from the system locale
+    pub fn first_run(system_locale: Option<&str>) -> Self {
+        Self {
+            language: language_for_locale(system_locale.unwrap_or_default()).to_string(),
+            ..Settings::default()
+        }
+    }
+
+    /// Time between autosaves, `None` when autosave is off
+    pub fn autosave_interval(&self) -> Option<std::time::Duration> {
+        self.autosave_minutes
+            .filter(|&minutes| minutes > 0)
+            .map(|minutes| std::time::Duration::from_secs(u64::from(minutes) * 60))
+    }
+
+    /// Move a library to the top of the recent list (adding it if needed),
+    /// keeping at most [`MAX_RECENT_LIBRARIES`] entries
+    pub fn record_recent_library(&mut self, library_id: i64, name: &str, opened_at: i64) {
+        self.recent_libraries.retain(|e| e.library_id != library_id);
+        self.recent_libraries.insert(
+            0,
+            RecentEntry {
+                library_id,
+                name: name.to_string(),
+                last_opened: opened_at,
+            },
+        );
+        self.recent_libraries.truncate(MAX_RECENT_LIBRARIES);
+    }
+
+    /// Show a renamed library under its new name in the recent list, keeping
+    /// its place; returns whether it was listed
+    pub fn rename_recent_library(&mut self, library_id: i64, name: &str) -> bool {
+        let entry = self.recent_libraries.iter_mut().find(|e| e.library_id == library_id);
+        entry.map(|e| e.name = name.to_string()).is_some()
+    }
+
+    /// Drop a library from the recent list; returns whether it was listed
+    pub fn forget_recent_library(&mut self, library_id: i64) -> bool {
+        let before = self.recent_libraries.len();
+        self.recent_libraries.retain(|e| e.library_id != library_id);
+        self.recent_libraries.len() != before
+    }
+
+    /// Move a path to the top of the recent paths (adding it if needed),
+    /// keeping at most [`MAX_RECENT_PATHS`] entries
+    pub fn record_recent_path(&mut self, path: &Path) {
+        self.recent_paths.retain(|p| p != path);
+        self.recent_paths.insert(0, path.to_path_buf());
+        self.recent_paths.truncate(MAX_RECENT_PATHS);
+    }
+
+    /// Remembered geometry of a window type
+    pub fn window_geometry(&self, kind: &str) -> Option<WindowGeometry> {
+        self.window_geometry.get(kind).copied()
+    }
+
+    /// Remember the geometry of a window type
+    pub fn remember_window_geometry(&mut self, kind: &str, geometry: WindowGeometry) {
+        self.window_geometry.insert(kind.to_string(), geometry);
     }
 
     /// Save settings to file
     pub fn save(&self) -> Result<()> {
-        let config_dir = Self::config_dir()?;
-        std::fs::create_dir_all(&config_dir)?;
-        let config_path = config_dir.join("settings.toml");
+        self.save_to(&Self::config_dir()?.join("settings.toml"))
+    }
+
+    /// Save settings to the given file, creating its directory.
+    ///
+    /// The previous file is kept as `<name>.bak` if it was valid.
+    pub fn save_to(&self, config_path: &Path) -> Result<()> {
+        if let Some(dir) = config_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        if Self::read_file(config_path).is_ok() {
+            std::fs::copy(config_path, backup_path(config_path))?;
+        }
         let content = toml::to_string_pretty(self)?;
-        std::fs::write(&config_path, content)?;
-        Ok(())
+        atomic_write(config_path, content.as_bytes())
+    }
+
+    /// Replace the settings file with the defaults and return them
+    pub fn reset() -> Result<Self> {
+        Self::reset_at(&Self::config_dir()?.join("settings.toml"))
+    }
+
+    /// Write the default settings to `config_path` from scratch, so keys
+    /// left by older versions go as well, and remove the backup that still
+    /// holds the old settings.
+    pub fn reset_at(config_path: &Path) -> Result<Self> {
+        let settings = Settings::default();
+        if let Some(dir) = config_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        atomic_write(config_path, toml::to_string_pretty(&settings)?.as_bytes())?;
+        if let Err(e) = std::fs::remove_file(backup_path(config_path)) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        Ok(settings)
+    }
+
+    fn read_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
     }
 
     /// Get application config directory
@@ -70,10 +364,67 @@ impl Settings {
     pub fn default_database_path() -> Result<PathBuf> {
         let data_dir = Self::data_dir()?;
         std::fs::create_dir_all(&data_dir)?;
-        Ok(data_dir.join("toeditor.db"))
+        Self::default_database_file()
+    }
+
+    /// Like [`Self::default_database_path`], without creating its directory
+    pub fn default_database_file() -> Result<PathBuf> {
+        Ok(Self::data_dir()?.join("toeditor.db"))
+    }
+
+    /// Database file to open: the chosen one, else the default
+    pub fn resolved_database_path(&self) -> Result<PathBuf> {
+        match self.database_path {
+            Some(ref path) => Ok(path.clone()),
+            None => Self::default_database_path(),
+        }
+    }
+
+    /// Folder export save dialogs start in
+    pub fn export_dir(&self) -> PathBuf {
+        let fallback = directories::UserDirs::new()
+            .map(|dirs| dirs.document_dir().unwrap_or(dirs.home_dir()).to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."));
+        resolve_export_dir(self.default_export_dir.as_deref(), fallback)
     }
 }
 
+/// The configured export folder while it still exists, else `fallback`
+pub fn resolve_export_dir(configured: Option<&Path>, fallback: PathBuf) -> PathBuf {
+    configured.filter(|dir| dir.is_dir()).map(Path::to_path_buf).unwrap_or(fallback)
+}
+
+/// Path of the backup kept next to `path` (`settings.toml` → `settings.toml.bak`)
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Replace `path` with `contents` without ever leaving a partially written file.
+///
+/// Writes a temporary file in the same directory, flushes it to disk and renames
+/// it over the target.
+pub fn atomic_write(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    let result = (|| -> Result<()> {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,4 +435,388 @@ mod tests {
         assert_eq!(settings.symbol_style, "NATO");
         assert_eq!(settings.language, "en");
     }
+
+    #[test]
+    fn test_first_run_uses_system_locale() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config").join("settings.toml");
+        let settings = Settings::load_or_init(&path, || Some("ru_RU.UTF-8".to_string())).unwrap();
+        assert_eq!(settings.language, "ru");
+        assert!(path.exists());
+
+        // The recorded choice wins over the locale on later launches
+        let again = Settings::load_or_init(&path, || Some("en_US".to_string())).unwrap();
+        assert_eq!(again.language, "ru");
+    }
+
+    #[test]
+    fn test_read_or_first_run_writes_nothing() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config").join("settings.toml");
+        let settings = Settings::read_or_first_run(&path, || Some("ru_RU.UTF-8".to_string())).unwrap();
+        assert_eq!(settings.language, "ru");
+        assert!(!path.exists());
+        assert!(!dir.path().join("config").exists());
+    }
+
+    #[test]
+    fn test_existing_settings_skip_detection() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let saved = Settings {
+            language: "en".to_string(),
+            ..Settings::default()
+        };
+        saved.save_to(&path).unwrap();
+        let settings = Settings::load_or_init(&path, || panic!("locale detection must not run")).unwrap();
+        assert_eq!(settings.language, "en");
+    }
+
+    #[test]
+    fn test_first_run_without_locale() {
+        assert_eq!(Settings::first_run(None).language, "en");
+        assert_eq!(Settings::first_run(Some("ru-RU")).symbol_style, "NATO");
+    }
+
+    #[test]
+    fn test_export_presets_default_to_builtins_and_persist() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "symbol_style = \"NATO\"\ncolor_scheme = \"light\"\nlanguage = \"en\"\n").unwrap();
+        let mut settings = Settings::read_file(&path).unwrap();
+        assert_eq!(settings.export_presets, builtin_presets());
+
+        settings.export_presets[1].name = "Mine".to_string();
+        settings.export_presets.remove(0);
+        settings.save_to(&path).unwrap();
+        let loaded = Settings::read_file(&path).unwrap();
+        assert_eq!(loaded.export_presets, settings.export_presets);
+
+        // Deleting every preset does not bring the built-in ones back
+        settings.export_presets.clear();
+        settings.save_to(&path).unwrap();
+        assert!(Settings::read_file(&path).unwrap().export_presets.is_empty());
+    }
+
+    #[test]
+    fn test_atomic_write_replaces_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        atomic_write(&path, b"first").unwrap();
+        atomic_write(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        assert!(!dir.path().join("state.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_save_keeps_backup_of_previous_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let mut settings = Settings {
+            database_path: Some(PathBuf::from("/data/first.db")),
+            ..Settings::default()
+        };
+        settings.save_to(&path).unwrap();
+        assert!(!backup_path(&path).exists());
+
+        settings.database_path = Some(PathBuf::from("/data/second.db"));
+        settings.save_to(&path).unwrap();
+        let backup = Settings::read_file(&backup_path(&path)).unwrap();
+        assert_eq!(backup.database_path, Some(PathBuf::from("/data/first.db")));
+    }
+
+    #[test]
+    fn test_corrupted_settings_fall_back_to_backup() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let settings = Settings {
+            language: "ru".to_string(),
+            database_path: Some(PathBuf::from("/data/library.db")),
+            ..Settings::default()
+        };
+        settings.save_to(&path).unwrap();
+        settings.save_to(&path).unwrap();
+
+        // Simulate a crash that truncated the primary file
+        std::fs::write(&path, "").unwrap();
+        let loaded = Settings::load_or_init(&path, || None).unwrap();
+        assert_eq!(loaded.language, "ru");
+        assert_eq!(loaded.database_path, Some(PathBuf::from("/data/library.db")));
+
+        // Half-written file
+        std::fs::write(&path, "symbol_style = \"NATO\"\nlanguage = \"r").unwrap();
+        let loaded = Settings::load_or_init(&path, || None).unwrap();
+        assert_eq!(loaded.database_path, Some(PathBuf::from("/data/library.db")));
+    }
+
+    #[test]
+    fn test_corrupted_settings_without_backup_is_error() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "not = [valid").unwrap();
+        assert!(Settings::load_or_init(&path, || None).is_err());
+    }
+
+    #[test]
+    fn test_reset_drops_old_keys_and_backup() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let saved = Settings {
+            language: "ru".to_string(),
+            color_scheme: "dark".to_string(),
+            ..Settings::default()
+        };
+        saved.save_to(&path).unwrap();
+        // A key older versions wrote, at the top so it stays a top-level key
+        // whatever tables the settings end with
+        let old = format!("show_grid = true\n{}", std::fs::read_to_string(&path).unwrap());
+        std::fs::write(&path, old).unwrap();
+        saved.save_to(&path).unwrap();
+        assert!(backup_path(&path).exists());
+
+        let reset = Settings::reset_at(&path).unwrap();
+        assert_eq!(reset.language, "en");
+        assert_eq!(reset.color_scheme, "light");
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("show_grid"));
+        assert!(!backup_path(&path).exists());
+        assert_eq!(Settings::load_or_init(&path, || None).unwrap().language, "en");
+
+        // Nothing to reset yet
+        let fresh = dir.path().join("new").join("settings.toml");
+        assert_eq!(Settings::reset_at(&fresh).unwrap().color_scheme, "light");
+        assert!(fresh.exists());
+    }
+
+    #[test]
+    fn test_record_recent_library() {
+        let mut settings = Settings::default();
+        for id in 1..=12 {
+            settings.record_recent_library(id, &format!("Library {}", id), 1000 + id);
+        }
+        assert_eq!(settings.recent_libraries.len(), MAX_RECENT_LIBRARIES);
+        assert_eq!(settings.recent_libraries[0].library_id, 12);
+        assert_eq!(settings.recent_libraries[9].library_id, 3);
+
+        // Reopening moves the entry to the top without duplicating it
+        settings.record_recent_library(5, "Renamed", 2000);
+        assert_eq!(settings.recent_libraries.len(), MAX_RECENT_LIBRARIES);
+        assert_eq!(settings.recent_libraries[0], RecentEntry {
+            library_id: 5,
+            name: "Renamed".to_string(),
+            last_opened: 2000,
+        });
+        assert_eq!(settings.recent_libraries.iter().filter(|e| e.library_id == 5).count(), 1);
+
+        assert!(settings.rename_recent_library(3, "Renamed again"));
+        assert_eq!(settings.recent_libraries[9].name, "Renamed again");
+        assert!(!settings.rename_recent_library(99, "Missing"));
+
+        assert!(settings.forget_recent_library(5));
+        assert!(!settings.forget_recent_library(5));
+        assert_eq!(settings.recent_libraries[0].library_id, 12);
+    }
+
+    #[test]
+    fn test_recent_libraries_round_trip_and_old_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let mut settings = Settings::default();
+        settings.record_recent_library(3, "US Army \"2003\"", 1700000000);
+        settings.record_recent_library(7, "Армия", 1700000100);
+        settings.save_to(&path).unwrap();
+        let loaded = Settings::read_file(&path).unwrap();
+        assert_eq!(loaded.recent_libraries, settings.recent_libraries);
+
+        // Files written before the list existed still load
+        std::fs::write(&path, "symbol_style = \"NATO\"\ncolor_scheme = \"light\"\nlanguage = \"en\"\n").unwrap();
+        assert!(Settings::read_file(&path).unwrap().recent_libraries.is_empty());
+    }
+
+    #[test]
+    fn test_recent_paths_persist_capped_and_deduplicated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let mut settings = Settings::default();
+        for i in 0..MAX_RECENT_PATHS + 3 {
+            settings.record_recent_path(&PathBuf::from(format!("/exports/army_{}.json", i)));
+        }
+        settings.record_recent_path(Path::new("/exports/army_5.json"));
+        settings.save_to(&path).unwrap();
+
+        let loaded = Settings::read_file(&path).unwrap();
+        assert_eq!(loaded.recent_paths.len(), MAX_RECENT_PATHS);
+        assert_eq!(loaded.recent_paths[0], PathBuf::from("/exports/army_5.json"));
+        assert_eq!(loaded.recent_paths[1], PathBuf::from("/exports/army_12.json"));
+        assert_eq!(loaded.recent_paths.iter().filter(|p| p.ends_with("army_5.json")).count(), 1);
+    }
+
+    #[test]
+    fn test_default_export_dir_round_trip_and_old_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        assert_eq!(Settings::default().default_export_dir, None);
+        let settings = Settings {
+            default_export_dir: Some(dir.path().join("exports")),
+            ..Settings::default()
+        };
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::read_file(&path).unwrap().default_export_dir, Some(dir.path().join("exports")));
+
+        std::fs::write(&path, "symbol_style = \"NATO\"\ncolor_scheme = \"light\"\nlanguage = \"en\"\n").unwrap();
+        assert_eq!(Settings::read_file(&path).unwrap().default_export_dir, None);
+    }
+
+    #[test]
+    fn test_zoom_round_trip_and_old_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let settings = Settings { zoom: 1.44, ..Settings::default() };
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::read_file(&path).unwrap().zoom, 1.44);
+
+        std::fs::write(&path, "symbol_style = \"NATO\"\ncolor_scheme = \"light\"\nlanguage = \"en\"\n").unwrap();
+        assert_eq!(Settings::read_file(&path).unwrap().zoom, DEFAULT_ZOOM);
+    }
+
+    #[test]
+    fn test_autosave_round_trip_and_old_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let defaults = Settings::default();
+        assert_eq!(defaults.autosave_interval(), Some(std::time::Duration::from_secs(600)));
+
+        let settings = Settings { autosave_minutes: Some(3), autosave_keep: 2, ..Settings::default() };
+        settings.save_to(&path).unwrap();
+        let read = Settings::read_file(&path).unwrap();
+        assert_eq!((read.autosave_minutes, read.autosave_keep), (Some(3), 2));
+
+        // Turned off stays off, rather than falling back to the default
+        Settings { autosave_minutes: None, ..Settings::default() }.save_to(&path).unwrap();
+        assert_eq!(Settings::read_file(&path).unwrap().autosave_interval(), None);
+
+        std::fs::write(&path, "symbol_style = \"NATO\"\ncolor_scheme = \"light\"\nlanguage = \"en\"\n").unwrap();
+        let read = Settings::read_file(&path).unwrap();
+        assert_eq!((read.autosave_minutes, read.autosave_keep), (Some(DEFAULT_AUTOSAVE_MINUTES), DEFAULT_AUTOSAVE_KEEP));
+    }
+
+    #[test]
+    fn test_tree_limits_round_trip_and_partial_tables() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let mut settings = Settings::default();
+        settings.tree_limits.max_depth = 20;
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::read_file(&path).unwrap().tree_limits.max_depth, 20);
+
+        // Limits left out of the table keep their defaults
+        std::fs::write(&path, "symbol_style = \"NATO\"\ncolor_scheme = \"light\"\nlanguage = \"en\"\n\n[tree_limits]\nwarn_depth = 6\n").unwrap();
+        let limits = Settings::read_file(&path).unwrap().tree_limits;
+        assert_eq!(limits, TreeLimits { warn_depth: 6, ..TreeLimits::default() });
+    }
+
+    #[test]
+    fn test_export_dir_falls_back_when_missing() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let fallback = PathBuf::from("/home/user/Documents");
+        assert_eq!(resolve_export_dir(Some(dir.path()), fallback.clone()), dir.path());
+        assert_eq!(resolve_export_dir(Some(&dir.path().join("gone")), fallback.clone()), fallback);
+        assert_eq!(resolve_export_dir(None, fallback.clone()), fallback);
+
+        let settings = Settings {
+            default_export_dir: Some(dir.path().to_path_buf()),
+            ..Settings::default()
+        };
+        assert_eq!(settings.export_dir(), dir.path());
+    }
+
+    #[test]
+    fn test_chosen_database_path_wins_over_default() {
+        let settings = Settings {
+            database_path: Some(PathBuf::from("/sync/army.db")),
+            ..Settings::default()
+        };
+        assert_eq!(settings.resolved_database_path().unwrap(), PathBuf::from("/sync/army.db"));
+    }
+
+    #[test]
+    fn test_settings_dialog_fields_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let mut settings = Settings::default();
+        settings.record_recent_library(2, "Армия", 1700000000);
+        settings.save_to(&path).unwrap();
+
+        let mut edited = Settings::read_file(&path).unwrap();
+        edited.language = "ru".to_string();
+        edited.color_scheme = "dark".to_string();
+        edited.database_path = Some(dir.path().join("Данные").join("toeditor.db"));
+        edited.save_to(&path).unwrap();
+
+        let loaded = Settings::read_file(&path).unwrap();
+        assert_eq!(loaded.language, "ru");
+        assert_eq!(loaded.color_scheme, "dark");
+        assert_eq!(loaded.database_path, edited.database_path);
+        // Fields the dialog does not show are kept
+        assert_eq!(loaded.recent_libraries, settings.recent_libraries);
+    }
+
+    #[test]
+    fn test_window_geometry_clamped_to_visible_area() {
+        let area = WindowGeometry { x: 0, y: 0, width: 1920, height: 1080 };
+        let inside = WindowGeometry { x: 100, y: 200, width: 600, height: 400 };
+        assert_eq!(inside.clamped_to(area), inside);
+
+        // Left on a second screen to the right that is no longer there
+        let right = WindowGeometry { x: 2500, y: 300, width: 600, height: 400 };
+        assert_eq!(right.clamped_to(area), WindowGeometry { x: 1920 - 80, ..right });
+
+        // Mostly off the left and top edges: a grab handle stays visible
+        let left = WindowGeometry { x: -1000, y: -50, width: 600, height: 400 };
+        assert_eq!(left.clamped_to(area), WindowGeometry { x: 80 - 600, y: 0, ..left });
+
+        // Below the bottom edge the title bar is pulled back up
+        let below = WindowGeometry { x: 10, y: 5000, width: 600, height: 400 };
+        assert_eq!(below.clamped_to(area).y, 1080 - 32);
+
+        // The area need not start at the origin
+        let offset = WindowGeometry { x: -1920, y: 0, width: 1920, height: 1080 };
+        assert_eq!(right.clamped_to(offset).x, -80);
+    }
+
+    #[test]
+    fn test_window_geometry_min_size() {
+        let tiny = WindowGeometry { x: 10, y: 10, width: 5, height: 0 };
+        let area = WindowGeometry { x: 0, y: 0, width: 100, height: 20 };
+        let clamped = tiny.clamped_to(area);
+        assert_eq!((clamped.width, clamped.height), MIN_WINDOW_SIZE);
+        // A window wider than the area stays put while 80 pixels of its title
+        // bar are inside the area, and is pulled back once fewer are
+        assert_eq!(clamped.x, 10);
+        let past = WindowGeometry { x: 50, ..tiny };
+        assert_eq!(past.clamped_to(area).x, 100 - 80);
+        assert_eq!(clamped.y, 0);
+        assert_eq!(tiny.with_min_size().x, 10);
+    }
+
+    #[test]
+    fn test_window_geometry_round_trip_by_window_type() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        let mut settings = Settings::default();
+        let branches = WindowGeometry { x: -1800, y: 40, width: 900, height: 700 };
+        let levels = WindowGeometry { x: 300, y: 200, width: 640, height: 480 };
+        settings.remember_window_geometry("branches_editor", branches);
+        settings.remember_window_geometry("formation_levels_editor", levels);
+        settings.remember_window_geometry("branches_editor", WindowGeometry { width: 950, ..branches });
+        settings.save_to(&path).unwrap();
+
+        let loaded = Settings::read_file(&path).unwrap();
+        assert_eq!(loaded.window_geometry("branches_editor"), Some(WindowGeometry { width: 950, ..branches }));
+        assert_eq!(loaded.window_geometry("formation_levels_editor"), Some(levels));
+        assert_eq!(loaded.window_geometry("tags_editor"), None);
+
+        // Files written before geometry was remembered still load
+        std::fs::write(&path, "symbol_style = \"NATO\"\ncolor_scheme = \"light\"\nlanguage = \"en\"\n").unwrap();
+        assert!(Settings::read_file(&path).unwrap().window_geometry.is_empty());
+    }
 }
@@ -1,26 +1,143 @@
 //! Database connection and migration management
 
 pub mod repositories;
+pub mod schema_doc;
+pub mod storage;
 
-use anyhow::Result;
-use rusqlite::Connection;
-use std::path::Path;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// Database connection wrapper
 pub struct Database {
     conn: Connection,
 }
 
+/// What [`Database::open_or_recover`] had to do to open the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenOutcome {
+    /// The file was opened, or created if it did not exist
+    Opened,
+    /// The file was not a readable database. It was renamed to `set_aside`
+    /// and a new, empty database created in its place.
+    Recovered { set_aside: PathBuf, error: String },
+}
+
+/// Database errors callers handle rather than just report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbError {
+    /// The file was written by a newer TOEditor whose schema this build does
+    /// not know
+    SchemaTooNew { found: i64, supported: i64 },
+    /// [`Database::open_existing`] found no file at the path
+    NotFound(PathBuf),
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SchemaTooNew { found, supported } => write!(
+                f,
+                "The database has schema version {}, but this TOEditor supports up to {}; use a newer TOEditor",
+                found, supported
+            ),
+            Self::NotFound(path) => write!(f, "Database not found: {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// Whether opening failed because the file is not a database or is damaged
+fn is_corruption(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<rusqlite::Error>().and_then(|e| e.sqlite_error_code()),
+            Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt)
+        )
+    })
+}
+
+/// Name a damaged database file is set aside under: `<name>.corrupt-<stamp>`
+fn corrupt_path(path: &Path, stamp: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".corrupt-{}", stamp));
+    path.with_file_name(name)
+}
+
 impl Database {
-    /// Open or create database at path
+    /// Open or create database at path, creating its folder if needed
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let conn = Connection::open(path)?;
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        Self::set_up(Connection::open(path)?)
+    }
+
+    /// Like [`open`](Self::open), but fails with [`DbError::NotFound`]
+    /// instead of creating a missing file or its directories
+    pub fn open_existing<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(DbError::NotFound(path.to_path_buf()).into());
+        }
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        Self::set_up(conn)
+    }
+
+    /// Configure a file connection and bring its schema up to date
+    fn set_up(conn: Connection) -> Result<Self> {
+        // Editor windows write in their own transactions; WAL lets readers go
+        // on meanwhile and the timeout makes writers wait instead of failing
+        // with "database is locked"
         conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
         let db = Self { conn };
         db.run_migrations()?;
         Ok(db)
     }
 
+    /// How long a write waits for another connection's transaction to finish
+    pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Like [`open`](Self::open), but a file that is not a readable database
+    /// is renamed out of the way (with its `-wal` and `-shm` files) and a new
+    /// database created, so the application still starts. Other errors are
+    /// returned as they are.
+    pub fn open_or_recover<P: AsRef<Path>>(path: P) -> Result<(Self, OpenOutcome)> {
+        let path = path.as_ref();
+        let error = match Self::open(path) {
+            Ok(db) => return Ok((db, OpenOutcome::Opened)),
+            Err(e) if is_corruption(&e) => e,
+            Err(e) => return Err(e),
+        };
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let set_aside = corrupt_path(path, &stamp);
+        std::fs::rename(path, &set_aside)
+            .with_context(|| format!("Failed to move the damaged database {} aside", path.display()))?;
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.as_os_str().to_os_string();
+            sidecar.push(suffix);
+            let sidecar = PathBuf::from(sidecar);
+            if sidecar.exists() {
+                let mut target = set_aside.as_os_str().to_os_string();
+                target.push(suffix);
+                if let Err(e) = std::fs::rename(&sidecar, &target) {
+                    log::warn!("Failed to move {} aside: {}", sidecar.display(), e);
+                }
+            }
+        }
+        log::warn!("Database {} is damaged ({}); moved to {}", path.display(), error, set_aside.display());
+        let db = Self::open(path)?;
+        Ok((db, OpenOutcome::Recovered { set_aside, error: error.to_string() }))
+    }
+
     /// Open in-memory database (for testing)
     pub fn open_in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()?;
@@ -30,17 +147,46 @@ impl Database {
         Ok(db)
     }
 
+    /// Open an existing database without write access and without migrating.
+    /// Fails if the file is missing or its schema is not the one this build uses.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        let db = Self { conn };
+        let version = db.schema_version();
+        if version > Self::CURRENT_SCHEMA_VERSION {
+            return Err(DbError::SchemaTooNew { found: version, supported: Self::CURRENT_SCHEMA_VERSION }.into());
+        }
+        if version != Self::CURRENT_SCHEMA_VERSION {
+            anyhow::bail!(
+                "{} has schema version {}, expected {}; open it once in TOEditor to migrate",
+                path.display(),
+                version,
+                Self::CURRENT_SCHEMA_VERSION
+            );
+        }
+        Ok(db)
+    }
+
     /// Get underlying connection (for repositories)
     pub fn conn(&self) -> &Connection {
         &self.conn
     }
 
+    /// File the database was opened from; `None` for an in-memory database
+    pub fn path(&self) -> Option<PathBuf> {
+        self.conn.path().filter(|p| !p.is_empty()).map(PathBuf::from)
+    }
+
     /// Current schema version. Increment when adding new migrations.
-    #[cfg(test)]
-    const CURRENT_SCHEMA_VERSION: i64 = 3;
+    pub const CURRENT_SCHEMA_VERSION: i64 = 18;
 
     /// Get current schema version from the database (0 if table does not exist).
-    fn schema_version(&self) -> i64 {
+    pub fn schema_version(&self) -> i64 {
         self.conn
             .query_row(
                 "SELECT version FROM schema_version ORDER BY version DESC LIMIT 1",
@@ -59,7 +205,8 @@ impl Database {
         Ok(())
     }
 
-    /// Run database migrations sequentially based on schema version.
+    /// Run database migrations sequentially based on schema version. Fails
+    /// with [`DbError::SchemaTooNew`] for a schema newer than this build's.
     fn run_migrations(&self) -> Result<()> {
         // The schema_version table itself is always created first
         self.conn.execute_batch(
@@ -70,6 +217,9 @@ impl Database {
         )?;
 
         let current = self.schema_version();
+        if current > Self::CURRENT_SCHEMA_VERSION {
+            return Err(DbError::SchemaTooNew { found: current, supported: Self::CURRENT_SCHEMA_VERSION }.into());
+        }
 
         if current < 1 {
             self.migrate_v1()?;
@@ -83,6 +233,70 @@ impl Database {
             self.migrate_v3()?;
             self.set_schema_version(3)?;
         }
+        if current < 4 {
+            self.migrate_v4()?;
+            self.set_schema_version(4)?;
+        }
+        if current < 5 {
+            self.migrate_v5()?;
+            self.set_schema_version(5)?;
+        }
+        if current < 6 {
+            self.migrate_v6()?;
+            self.set_schema_version(6)?;
+        }
+        if current < 7 {
+            self.migrate_v7()?;
+            self.set_schema_version(7)?;
+        }
+        if current < 8 {
+            self.migrate_v8()?;
+            self.set_schema_version(8)?;
+        }
+        if current < 9 {
+            self.migrate_v9()?;
+            self.set_schema_version(9)?;
+        }
+        if current < 10 {
+            self.migrate_v10()?;
+            self.set_schema_version(10)?;
+        }
+        if current < 11 {
+            self.migrate_v11()?;
+            self.set_schema_version(11)?;
+        }
+        if current < 12 {
+            self.migrate_v12()?;
+            self.set_schema_version(12)?;
+        }
+        if current < 13 {
+            self.migrate_v13()?;
+            self.set_schema_version(13)?;
+        }
+        if current < 14 {
+            self.migrate_v14()?;
+            self.set_schema_version(14)?;
+        }
+        if current < 15 {
+            self.migrate_v15()?;
+            self.set_schema_version(15)?;
+        }
+        if current < 16 {
+            self.migrate_v16()?;
+            self.set_schema_version(16)?;
+        }
+        if current < 17 {
+            // The table rebuild and the version bump commit together, so an
+            // interrupted migration is redone from the old table next time
+            let tx = self.conn.unchecked_transaction()?;
+            self.migrate_v17()?;
+            self.set_schema_version(17)?;
+            tx.commit()?;
+        }
+        if current < 18 {
+            self.migrate_v18()?;
+            self.set_schema_version(18)?;
+        }
 
         Ok(())
     }
@@ -233,11 +447,353 @@ impl Database {
 
         Ok(())
     }
+
+    /// V4: sort_order column for branches, branch categories and formation levels
+    fn migrate_v4(&self) -> Result<()> {
+        for table in ["branches", "branch_categories", "formation_levels"] {
+            let _ = self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0", table),
+                [],
+            );
+        }
+        Ok(())
+    }
+
+    /// V5: preferred language for bilingual names per library
+    fn migrate_v5(&self) -> Result<()> {
+        let _ = self.conn.execute(
+            "ALTER TABLE libraries ADD COLUMN display_language TEXT NOT NULL DEFAULT 'auto'",
+            [],
+        );
+        Ok(())
+    }
+
+    /// V6: optional expected personnel range per formation level
+    fn migrate_v6(&self) -> Result<()> {
+        for column in ["min_personnel", "max_personnel"] {
+            let _ = self.conn.execute(
+                &format!("ALTER TABLE formation_levels ADD COLUMN {} INTEGER", column),
+                [],
+            );
+        }
+        Ok(())
+    }
+
+    /// V7: explicit sibling order for units, seeded from creation order
+    fn migrate_v7(&self) -> Result<()> {
+        let added = self
+            .conn
+            .execute("ALTER TABLE units ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0", [])
+            .is_ok();
+        if added {
+            self.conn.execute("UPDATE units SET sort_order = id", [])?;
+        }
+        Ok(())
+    }
+
+    /// V8: country code next to the free-text country, filled in where the text names a known country
+    fn migrate_v8(&self) -> Result<()> {
+        let _ = self.conn.execute("ALTER TABLE libraries ADD COLUMN country_code TEXT", []);
+        let rows: Vec<(i64, String)> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT id, country FROM libraries WHERE country_code IS NULL")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for (id, country) in rows {
+            if let Some(code) = crate::models::resolve_country_code(&country) {
+                self.conn.execute(
+                    "UPDATE libraries SET country_code = ?1 WHERE id = ?2",
+                    rusqlite::params![code, id],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// V9: formation level of a unit by id, and the queue of unit types that
+    /// still need a level confirmed. Mapping is lazy (see `services::level_mapping`),
+    /// so no unit type is guessed here.
+    fn migrate_v9(&self) -> Result<()> {
+        let _ = self.conn.execute(
+            "ALTER TABLE units ADD COLUMN formation_level_id INTEGER
+                REFERENCES formation_levels(id) ON DELETE SET NULL",
+            [],
+        );
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS level_mapping_queue (
+                unit_id INTEGER PRIMARY KEY,
+                library_id INTEGER NOT NULL,
+                unit_type TEXT NOT NULL,
+                suggested_level_id INTEGER,
+                score REAL NOT NULL DEFAULT 0,
+                dismissed INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (unit_id) REFERENCES units(id) ON DELETE CASCADE,
+                FOREIGN KEY (library_id) REFERENCES libraries(id) ON DELETE CASCADE,
+                FOREIGN KEY (suggested_level_id) REFERENCES formation_levels(id) ON DELETE SET NULL
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_level_mapping_queue_library_id ON level_mapping_queue(library_id)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// V10: equipment catalog per library. Unit equipment rows still carry
+    /// their own names; linking them to the catalog comes later.
+    fn migrate_v10(&self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS equipment_catalog (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                library_id INTEGER NOT NULL,
+                name_ru TEXT NOT NULL DEFAULT '',
+                name_en TEXT NOT NULL DEFAULT '',
+                category TEXT NOT NULL DEFAULT 'other',
+                sort_order INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (library_id) REFERENCES libraries(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_equipment_catalog_library_id ON equipment_catalog(library_id)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// V11: copy-from-library provenance. Deleting either library drops the
+    /// record; the copied rows stay in the target.
+    fn migrate_v11(&self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS library_provenance (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                target_library_id INTEGER NOT NULL,
+                source_library_id INTEGER NOT NULL,
+                what TEXT NOT NULL,
+                copied_at INTEGER NOT NULL,
+                FOREIGN KEY (target_library_id) REFERENCES libraries(id) ON DELETE CASCADE,
+                FOREIGN KEY (source_library_id) REFERENCES libraries(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_library_provenance_source ON library_provenance(source_library_id)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// V12: period in which a unit is part of the order of battle (`YYYY-MM-DD`, open when NULL)
+    fn migrate_v12(&self) -> Result<()> {
+        for column in ["effective_from", "effective_to"] {
+            let _ = self.conn.execute(&format!("ALTER TABLE units ADD COLUMN {} TEXT", column), []);
+        }
+        Ok(())
+    }
+
+    /// V13: other names of equipment catalog entries (JSON array of strings)
+    fn migrate_v13(&self) -> Result<()> {
+        let _ = self.conn.execute(
+            "ALTER TABLE equipment_catalog ADD COLUMN aliases TEXT NOT NULL DEFAULT '[]'",
+            [],
+        );
+        Ok(())
+    }
+
+    /// V14: number of the official TOE document a unit follows
+    fn migrate_v14(&self) -> Result<()> {
+        let _ = self.conn.execute("ALTER TABLE units ADD COLUMN toe_number TEXT", []);
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_units_toe_number ON units(library_id, toe_number)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// V15: creation and modification times (Unix seconds) of units, personnel
+    /// and equipment. Existing units take their library's times, and
+    /// personnel and equipment those of their unit.
+    fn migrate_v15(&self) -> Result<()> {
+        for table in ["units", "personnel", "equipment"] {
+            for column in ["created_at", "updated_at"] {
+                let _ = self.conn.execute(
+                    &format!("ALTER TABLE {} ADD COLUMN {} INTEGER NOT NULL DEFAULT 0", table, column),
+                    [],
+                );
+            }
+        }
+        self.conn.execute_batch(
+            "UPDATE units SET
+                 created_at = (SELECT created_at FROM libraries WHERE libraries.id = units.library_id),
+                 updated_at = (SELECT updated_at FROM libraries WHERE libraries.id = units.library_id)
+             WHERE created_at = 0;
+             UPDATE personnel SET
+                 created_at = (SELECT created_at FROM units WHERE units.id = personnel.unit_id),
+                 updated_at = (SELECT updated_at FROM units WHERE units.id = personnel.unit_id)
+             WHERE created_at = 0;
+             UPDATE equipment SET
+                 created_at = (SELECT created_at FROM units WHERE units.id = equipment.unit_id),
+                 updated_at = (SELECT updated_at FROM units WHERE units.id = equipment.unit_id)
+             WHERE created_at = 0;",
+        )?;
+        Ok(())
+    }
+
+    /// V16: pairs of units the duplicate finder was told are not duplicates
+    /// (smaller id first). Deleting either unit drops the pair.
+    fn migrate_v16(&self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS ignored_duplicates (
+                library_id INTEGER NOT NULL,
+                first_unit_id INTEGER NOT NULL,
+                second_unit_id INTEGER NOT NULL,
+                PRIMARY KEY (first_unit_id, second_unit_id),
+                FOREIGN KEY (library_id) REFERENCES libraries(id) ON DELETE CASCADE,
+                FOREIGN KEY (first_unit_id) REFERENCES units(id) ON DELETE CASCADE,
+                FOREIGN KEY (second_unit_id) REFERENCES units(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_ignored_duplicates_library ON ignored_duplicates(library_id)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// V17: autosave snapshots. They keep the version they were taken at, so
+    /// a version is unique among the other snapshots only; SQLite cannot
+    /// drop a table constraint, so the table is rebuilt. A copy left by a
+    /// build that ran this outside a transaction is discarded first.
+    fn migrate_v17(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "DROP TABLE IF EXISTS snapshots_v17;
+            CREATE TABLE snapshots_v17 (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                library_id INTEGER NOT NULL,
+                version INTEGER NOT NULL,
+                timestamp INTEGER NOT NULL,
+                data TEXT NOT NULL,
+                description TEXT,
+                is_autosave INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (library_id) REFERENCES libraries(id) ON DELETE CASCADE
+            );
+            INSERT INTO snapshots_v17 (id, library_id, version, timestamp, data, description)
+                SELECT id, library_id, version, timestamp, data, description FROM snapshots;
+            DROP TABLE snapshots;
+            ALTER TABLE snapshots_v17 RENAME TO snapshots;
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_snapshots_library_version
+                ON snapshots(library_id, version) WHERE is_autosave = 0;
+            CREATE INDEX IF NOT EXISTS idx_snapshots_library_id ON snapshots(library_id);",
+        )?;
+        Ok(())
+    }
+
+    /// V18: formation numbering options per library, as JSON; empty until set
+    fn migrate_v18(&self) -> Result<()> {
+        let _ = self.conn.execute("ALTER TABLE libraries ADD COLUMN numbering TEXT NOT NULL DEFAULT ''", []);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_creates_missing_folders() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("app data").join("nested").join("toeditor.db");
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.schema_version(), Database::CURRENT_SCHEMA_VERSION);
+        assert!(path.is_file());
+    }
+
+    #[test]
+    fn test_open_existing_creates_nothing() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("missing").join("toeditor.db");
+        let error = Database::open_existing(&path).err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>(), Some(&DbError::NotFound(path.clone())));
+        assert!(!dir.path().join("missing").exists());
+
+        drop(Database::open(&path).unwrap());
+        assert_eq!(Database::open_existing(&path).unwrap().schema_version(), Database::CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_garbage_file_is_set_aside() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("toeditor.db");
+        let garbage: Vec<u8> = (0..4096u32).map(|i| (i * 7 % 251) as u8).collect();
+        std::fs::write(&path, &garbage).unwrap();
+
+        let error = Database::open(&path).err().unwrap();
+        assert!(is_corruption(&error), "{:#}", error);
+
+        let (db, outcome) = Database::open_or_recover(&path).unwrap();
+        assert_eq!(db.schema_version(), Database::CURRENT_SCHEMA_VERSION);
+        let OpenOutcome::Recovered { set_aside, .. } = outcome else {
+            panic!("expected recovery, got {:?}", outcome);
+        };
+        assert!(set_aside.file_name().unwrap().to_string_lossy().starts_with("toeditor.db.corrupt-"));
+        assert_eq!(std::fs::read(&set_aside).unwrap(), garbage);
+
+        // The new database opens normally next time
+        drop(db);
+        assert_eq!(Database::open_or_recover(&path).unwrap().1, OpenOutcome::Opened);
+    }
+
+    #[test]
+    fn test_open_uses_wal_and_busy_timeout() {
+        let dir = TempDir::new().unwrap();
+        let db = Database::open(dir.path().join("toeditor.db")).unwrap();
+        let mode: String = db.conn().query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        let timeout: i64 = db.conn().query_row("PRAGMA busy_timeout", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+        assert_eq!(timeout, 5000);
+    }
+
+    #[test]
+    fn test_newer_schema_is_refused() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("toeditor.db");
+        let newer = Database::CURRENT_SCHEMA_VERSION + 1;
+        {
+            let db = Database::open(&path).unwrap();
+            db.set_schema_version(newer).unwrap();
+        }
+
+        let error = Database::open(&path).err().unwrap();
+        assert_eq!(
+            error.downcast_ref::<DbError>(),
+            Some(&DbError::SchemaTooNew { found: newer, supported: Database::CURRENT_SCHEMA_VERSION })
+        );
+        // Not mistaken for damage: the file stays where it is
+        assert!(Database::open_or_recover(&path).is_err());
+        assert!(path.is_file());
+        assert!(Database::open_read_only(&path).err().unwrap().downcast_ref::<DbError>().is_some());
+    }
+
+    #[test]
+    fn test_current_schema_reopens() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("toeditor.db");
+        drop(Database::open(&path).unwrap());
+        assert_eq!(Database::open(&path).unwrap().schema_version(), Database::CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_corrupt_path_keeps_folder() {
+        assert_eq!(
+            corrupt_path(Path::new("/data/toeditor.db"), "20240101-120000"),
+            PathBuf::from("/data/toeditor.db.corrupt-20240101-120000")
+        );
+    }
 
     #[test]
     fn test_database_creation() {
@@ -257,6 +813,8 @@ mod tests {
         assert!(tables.contains(&"formation_levels".to_string()));
         assert!(tables.contains(&"branch_categories".to_string()));
         assert!(tables.contains(&"branches".to_string()));
+        assert!(tables.contains(&"equipment_catalog".to_string()));
+        assert!(tables.contains(&"library_provenance".to_string()));
     }
 
     #[test]
@@ -278,6 +836,69 @@ mod tests {
         assert!(tables.contains(&"branch_categories".to_string()));
     }
 
+    /// Put the snapshots table back the way it was before v17
+    fn downgrade_to_v16(db: &Database) {
+        db.conn()
+            .execute_batch(
+                "DROP TABLE snapshots;
+                 CREATE TABLE snapshots (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     library_id INTEGER NOT NULL,
+                     version INTEGER NOT NULL,
+                     timestamp INTEGER NOT NULL,
+                     data TEXT NOT NULL,
+                     description TEXT,
+                     UNIQUE(library_id, version)
+                 );
+                 INSERT INTO libraries (id, name, country, era, created_at, updated_at) VALUES (1, 'L', 'US', '2003', 0, 0);
+                 INSERT INTO snapshots (library_id, version, timestamp, data) VALUES (1, 1, 0, '{}');
+                 DELETE FROM schema_version WHERE version >= 17;",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v17_recovers_from_an_interrupted_rebuild() {
+        let db = Database::open_in_memory().unwrap();
+        downgrade_to_v16(&db);
+        // Left behind by a rebuild that stopped after creating its copy
+        db.conn().execute_batch("CREATE TABLE snapshots_v17 (id INTEGER)").unwrap();
+
+        db.run_migrations().unwrap();
+        assert_eq!(db.schema_version(), Database::CURRENT_SCHEMA_VERSION);
+        let kept: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM snapshots WHERE is_autosave = 0", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kept, 1);
+    }
+
+    #[test]
+    fn test_failed_migrate_v17_leaves_v16_intact() {
+        let db = Database::open_in_memory().unwrap();
+        downgrade_to_v16(&db);
+        // Fail the version bump, after the table has been rebuilt
+        db.conn()
+            .execute_batch(
+                "CREATE TEMP TRIGGER fail_copy BEFORE INSERT ON schema_version
+                 BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+            )
+            .unwrap();
+
+        assert!(db.run_migrations().is_err());
+        assert_eq!(db.schema_version(), 16);
+        let old_table: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM pragma_table_info('snapshots') WHERE name = 'is_autosave'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(old_table, 0);
+        let copies: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'snapshots_v17'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(copies, 0);
+    }
+
     #[test]
     fn test_schema_version_tracking() {
         let db = Database::open_in_memory().unwrap();
@@ -301,6 +922,54 @@ mod tests {
         assert!(tables.contains(&"schema_version".to_string()));
     }
 
+    #[test]
+    fn test_units_have_effective_dates() {
+        let db = Database::open_in_memory().unwrap();
+        let mut stmt = db.conn().prepare("SELECT name FROM pragma_table_info('units')").unwrap();
+        let columns: Vec<String> = stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert!(columns.contains(&"effective_from".to_string()));
+        assert!(columns.contains(&"effective_to".to_string()));
+    }
+
+    #[test]
+    fn test_v3_database_keeps_rows_with_default_timestamps() {
+        // A database as version 3 left it, with some data
+        let db = Database { conn: Connection::open_in_memory().unwrap() };
+        db.conn.execute_batch("PRAGMA foreign_keys = ON;
+            CREATE TABLE schema_version (version INTEGER PRIMARY KEY, applied_at INTEGER NOT NULL);").unwrap();
+        db.migrate_v1().unwrap();
+        db.migrate_v2().unwrap();
+        db.migrate_v3().unwrap();
+        for version in 1..=3 {
+            db.set_schema_version(version).unwrap();
+        }
+        db.conn.execute_batch(
+            "INSERT INTO libraries (id, name, country, era, created_at, updated_at) VALUES (1, 'Old', 'US', '2003', 1000, 2000);
+             INSERT INTO units (id, library_id, name, unit_type) VALUES (1, 1, '1st Brigade', 'brigade');
+             INSERT INTO units (id, library_id, name, unit_type, parent_id) VALUES (2, 1, '1st Battalion', 'battalion', 1);
+             INSERT INTO personnel (unit_id, position, rank) VALUES (2, 'Commander', 'LTC');
+             INSERT INTO equipment (unit_id, name, quantity) VALUES (2, 'M1A2', 44);",
+        ).unwrap();
+
+        db.run_migrations().unwrap();
+        assert_eq!(db.schema_version(), Database::CURRENT_SCHEMA_VERSION);
+        let times = |table: &str| -> Vec<(i64, i64)> {
+            let mut stmt = db.conn.prepare(&format!("SELECT created_at, updated_at FROM {} ORDER BY id", table)).unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().map(|r| r.unwrap()).collect()
+        };
+        assert_eq!(times("units"), [(1000, 2000), (1000, 2000)]);
+        assert_eq!(times("personnel"), [(1000, 2000)]);
+        assert_eq!(times("equipment"), [(1000, 2000)]);
+
+        let unit = crate::db::repositories::UnitRepo::new(db.conn()).get_by_id(2).unwrap().unwrap();
+        assert_eq!(unit.name, "1st Battalion");
+        assert_eq!(unit.personnel.len(), 1);
+        assert_eq!(unit.equipment[0].quantity, 44);
+    }
+
     #[test]
     fn test_database_indexes_created() {
         let db = Database::open_in_memory().unwrap();
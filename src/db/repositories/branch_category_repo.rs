@@ -15,8 +15,8 @@ impl<'a> BranchCategoryRepo<'a> {
 
     pub fn create(&self, cat: &mut BranchCategory) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO branch_categories (library_id, name_ru, name_en) VALUES (?1, ?2, ?3)",
-            params![cat.library_id, cat.name_ru, cat.name_en],
+            "INSERT INTO branch_categories (library_id, name_ru, name_en, sort_order) VALUES (?1, ?2, ?3, ?4)",
+            params![cat.library_id, cat.name_ru, cat.name_en, cat.sort_order],
         )?;
         cat.id = Some(self.conn.last_insert_rowid());
         Ok(())
@@ -24,7 +24,7 @@ impl<'a> BranchCategoryRepo<'a> {
 
     pub fn get_by_id(&self, id: i64) -> Result<Option<BranchCategory>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, name_ru, name_en FROM branch_categories WHERE id = ?1",
+            "SELECT id, library_id, name_ru, name_en, sort_order FROM branch_categories WHERE id = ?1",
         )?;
         let mut rows = stmt.query_map(params![id], |row| {
             Ok(BranchCategory {
@@ -32,6 +32,7 @@ impl<'a> BranchCategoryRepo<'a> {
                 library_id: row.get(1)?,
                 name_ru: row.get(2)?,
                 name_en: row.get(3)?,
+                sort_order: row.get(4)?,
             })
         })?;
         match rows.next() {
@@ -43,7 +44,8 @@ impl<'a> BranchCategoryRepo<'a> {
 
     pub fn list_by_library(&self, library_id: i64) -> Result<Vec<BranchCategory>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, name_ru, name_en FROM branch_categories WHERE library_id = ?1 ORDER BY id",
+            "SELECT id, library_id, name_ru, name_en, sort_order FROM branch_categories
+             WHERE library_id = ?1 ORDER BY sort_order, id",
         )?;
         let rows = stmt.query_map(params![library_id], |row| {
             Ok(BranchCategory {
@@ -51,6 +53,7 @@ impl<'a> BranchCategoryRepo<'a> {
                 library_id: row.get(1)?,
                 name_ru: row.get(2)?,
                 name_en: row.get(3)?,
+                sort_order: row.get(4)?,
             })
         })?;
         let mut out = Vec::new();
@@ -69,6 +72,15 @@ impl<'a> BranchCategoryRepo<'a> {
         Ok(())
     }
 
+    /// Set a category's position in its library's list
+    pub fn set_sort_order(&self, id: i64, sort_order: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE branch_categories SET sort_order = ?1 WHERE id = ?2",
+            params![sort_order, id],
+        )?;
+        Ok(())
+    }
+
     pub fn delete(&self, id: i64) -> Result<()> {
         self.conn.execute("DELETE FROM branch_categories WHERE id = ?1", params![id])?;
         Ok(())
@@ -227,4 +239,25 @@ mod tests {
         assert_eq!(list1[0].name_en, "Combat");
         assert_eq!(list2[0].name_en, "Air defense");
     }
+
+    #[test]
+    fn test_category_list_ordered_by_sort_order() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new(
+            "Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string(),
+        );
+        lib_repo.create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+
+        let repo = BranchCategoryRepo::new(db.conn());
+        for (order, en) in [(1, "Support"), (0, "Combat"), (1, "Logistics")] {
+            let mut c = BranchCategory::new(lib_id, en.to_string(), en.to_string());
+            c.sort_order = order;
+            repo.create(&mut c).unwrap();
+        }
+        let names: Vec<String> = repo.list_by_library(lib_id).unwrap().into_iter().map(|c| c.name_en).collect();
+        // Equal sort_order keeps insertion order
+        assert_eq!(names, vec!["Combat", "Support", "Logistics"]);
+    }
 }
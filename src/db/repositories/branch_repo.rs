@@ -15,8 +15,9 @@ impl<'a> BranchRepo<'a> {
 
     pub fn create(&self, branch: &mut Branch) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO branches (library_id, category_id, name_ru, name_en) VALUES (?1, ?2, ?3, ?4)",
-            params![branch.library_id, branch.category_id, branch.name_ru, branch.name_en],
+            "INSERT INTO branches (library_id, category_id, name_ru, name_en, sort_order)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![branch.library_id, branch.category_id, branch.name_ru, branch.name_en, branch.sort_order],
         )?;
         branch.id = Some(self.conn.last_insert_rowid());
         Ok(())
@@ -24,7 +25,7 @@ impl<'a> BranchRepo<'a> {
 
     pub fn get_by_id(&self, id: i64) -> Result<Option<Branch>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, category_id, name_ru, name_en FROM branches WHERE id = ?1",
+            "SELECT id, library_id, category_id, name_ru, name_en, sort_order FROM branches WHERE id = ?1",
         )?;
         let mut rows = stmt.query_map(params![id], |row| {
             Ok(Branch {
@@ -33,6 +34,7 @@ impl<'a> BranchRepo<'a> {
                 category_id: row.get(2)?,
                 name_ru: row.get(3)?,
                 name_en: row.get(4)?,
+                sort_order: row.get(5)?,
             })
         })?;
         match rows.next() {
@@ -44,7 +46,8 @@ impl<'a> BranchRepo<'a> {
 
     pub fn list_by_library(&self, library_id: i64) -> Result<Vec<Branch>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, category_id, name_ru, name_en FROM branches WHERE library_id = ?1 ORDER BY id",
+            "SELECT id, library_id, category_id, name_ru, name_en, sort_order FROM branches
+             WHERE library_id = ?1 ORDER BY sort_order, id",
         )?;
         let rows = stmt.query_map(params![library_id], |row| {
             Ok(Branch {
@@ -53,6 +56,7 @@ impl<'a> BranchRepo<'a> {
                 category_id: row.get(2)?,
                 name_ru: row.get(3)?,
                 name_en: row.get(4)?,
+                sort_order: row.get(5)?,
             })
         })?;
         let mut out = Vec::new();
@@ -71,6 +75,25 @@ impl<'a> BranchRepo<'a> {
         Ok(())
     }
 
+    /// Number of branches in the given category
+    pub fn count_by_category(&self, category_id: i64) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM branches WHERE category_id = ?1",
+            params![category_id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Leave the branches of a category without one
+    pub fn clear_category(&self, category_id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE branches SET category_id = NULL WHERE category_id = ?1",
+            params![category_id],
+        )?;
+        Ok(())
+    }
+
     pub fn delete(&self, id: i64) -> Result<()> {
         self.conn.execute("DELETE FROM branches WHERE id = ?1", params![id])?;
         Ok(())
@@ -262,4 +285,51 @@ mod tests {
         assert_eq!(list1[0].name_en, "Infantry");
         assert_eq!(list2[0].name_en, "Armor");
     }
+
+    #[test]
+    fn test_branch_list_ordered_by_sort_order() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new(
+            "Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string(),
+        );
+        lib_repo.create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+
+        let repo = BranchRepo::new(db.conn());
+        for (order, en) in [(2, "Logistics"), (0, "Infantry"), (1, "Armor")] {
+            let mut b = Branch::new(lib_id, en.to_string(), en.to_string());
+            b.sort_order = order;
+            repo.create(&mut b).unwrap();
+        }
+        let names: Vec<String> = repo.list_by_library(lib_id).unwrap().into_iter().map(|b| b.name_en).collect();
+        assert_eq!(names, vec!["Infantry", "Armor", "Logistics"]);
+        let first = repo.list_by_library(lib_id).unwrap()[0].clone();
+        assert_eq!(repo.get_by_id(first.id.unwrap()).unwrap().unwrap().sort_order, 0);
+    }
+
+    #[test]
+    fn test_branch_count_and_clear_by_category() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new(
+            "Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string(),
+        );
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+        let mut category = crate::models::BranchCategory::new(lib_id, "Боевые".to_string(), "Combat".to_string());
+        crate::db::repositories::BranchCategoryRepo::new(db.conn()).create(&mut category).unwrap();
+        let cat_id = category.id.unwrap();
+
+        let repo = BranchRepo::new(db.conn());
+        for (cat, en) in [(Some(cat_id), "Infantry"), (Some(cat_id), "Armor"), (None, "Signals")] {
+            let mut b = Branch::with_category(lib_id, cat, String::new(), en.to_string());
+            repo.create(&mut b).unwrap();
+        }
+        assert_eq!(repo.count_by_category(cat_id).unwrap(), 2);
+        assert_eq!(repo.count_by_category(cat_id + 1).unwrap(), 0);
+
+        repo.clear_category(cat_id).unwrap();
+        assert_eq!(repo.count_by_category(cat_id).unwrap(), 0);
+        assert!(repo.list_by_library(lib_id).unwrap().iter().all(|b| b.category_id.is_none()));
+    }
 }
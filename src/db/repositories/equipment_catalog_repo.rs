@@ -0,0 +1,167 @@
+//! Repository for the equipment catalog per library.
+
+use anyhow::Result;
+use rusqlite::{params, Connection, Row};
+use crate::models::{EquipmentCatalogEntry, EquipmentCategory};
+
+pub struct EquipmentCatalogRepo<'a> {
+    conn: &'a Connection,
+}
+
+fn map_row(row: &Row) -> rusqlite::Result<EquipmentCatalogEntry> {
+    Ok(EquipmentCatalogEntry {
+        id: Some(row.get(0)?),
+        library_id: row.get(1)?,
+        name_ru: row.get(2)?,
+        name_en: row.get(3)?,
+        category: EquipmentCategory::from_code(&row.get::<_, String>(4)?),
+        sort_order: row.get(5)?,
+        aliases: serde_json::from_str(&row.get::<_, String>(6)?).unwrap_or_default(),
+    })
+}
+
+impl<'a> EquipmentCatalogRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn create(&self, entry: &mut EquipmentCatalogEntry) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO equipment_catalog (library_id, name_ru, name_en, category, sort_order, aliases)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                entry.library_id,
+                entry.name_ru,
+                entry.name_en,
+                entry.category.code(),
+                entry.sort_order,
+                serde_json::to_string(&entry.aliases)?,
+            ],
+        )?;
+        entry.id = Some(self.conn.last_insert_rowid());
+        Ok(())
+    }
+
+    pub fn get_by_id(&self, id: i64) -> Result<Option<EquipmentCatalogEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, library_id, name_ru, name_en, category, sort_order, aliases FROM equipment_catalog WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query_map(params![id], map_row)?;
+        match rows.next() {
+            Some(Ok(e)) => Ok(Some(e)),
+            Some(Err(e)) => Err(e.into()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn list_by_library(&self, library_id: i64) -> Result<Vec<EquipmentCatalogEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, library_id, name_ru, name_en, category, sort_order, aliases FROM equipment_catalog
+             WHERE library_id = ?1 ORDER BY sort_order, id",
+        )?;
+        let rows = stmt.query_map(params![library_id], map_row)?;
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row?);
+        }
+        Ok(out)
+    }
+
+    pub fn update(&self, entry: &EquipmentCatalogEntry) -> Result<()> {
+        let id = entry.id.ok_or_else(|| anyhow::anyhow!("Catalog entry has no id"))?;
+        self.conn.execute(
+            "UPDATE equipment_catalog SET name_ru = ?1, name_en = ?2, category = ?3, aliases = ?4 WHERE id = ?5",
+            params![entry.name_ru, entry.name_en, entry.category.code(), serde_json::to_string(&entry.aliases)?, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM equipment_catalog WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Delete the whole catalog of a library (e.g. before replacing it with an imported/copied one).
+    pub fn delete_by_library(&self, library_id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM equipment_catalog WHERE library_id = ?1", params![library_id])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::db::repositories::LibraryRepo;
+    use crate::models::Library;
+
+    fn library(db: &Database, name: &str) -> i64 {
+        let mut library = Library::new(name.to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        library.id.unwrap()
+    }
+
+    #[test]
+    fn test_catalog_crud() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db, "Test");
+        let repo = EquipmentCatalogRepo::new(db.conn());
+
+        let mut entry = EquipmentCatalogEntry::new(lib_id, "АК-74".to_string(), "AK-74".to_string(), EquipmentCategory::SmallArms);
+        repo.create(&mut entry).unwrap();
+        let loaded = repo.get_by_id(entry.id.unwrap()).unwrap().unwrap();
+        assert_eq!(loaded, entry);
+
+        let mut updated = loaded.clone();
+        updated.name_en = "AK-74M".to_string();
+        updated.category = EquipmentCategory::Other;
+        updated.aliases = vec!["Калашников".to_string(), "AK74".to_string()];
+        repo.update(&updated).unwrap();
+        assert_eq!(repo.get_by_id(entry.id.unwrap()).unwrap().unwrap(), updated);
+
+        repo.delete(entry.id.unwrap()).unwrap();
+        assert!(repo.get_by_id(entry.id.unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_catalog_update_without_id_fails() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = EquipmentCatalogRepo::new(db.conn());
+        let entry = EquipmentCatalogEntry::new(1, "БМП-2".to_string(), "BMP-2".to_string(), EquipmentCategory::Vehicles);
+        assert!(repo.update(&entry).is_err());
+    }
+
+    #[test]
+    fn test_catalog_list_ordered_and_isolated() {
+        let db = Database::open_in_memory().unwrap();
+        let lib1 = library(&db, "L1");
+        let lib2 = library(&db, "L2");
+        let repo = EquipmentCatalogRepo::new(db.conn());
+        for (order, en) in [(2, "Ural-4320"), (0, "AK-74"), (1, "BMP-2")] {
+            let mut e = EquipmentCatalogEntry::new(lib1, en.to_string(), en.to_string(), EquipmentCategory::Other);
+            e.sort_order = order;
+            repo.create(&mut e).unwrap();
+        }
+        let mut other = EquipmentCatalogEntry::new(lib2, "Т-72".to_string(), "T-72".to_string(), EquipmentCategory::Vehicles);
+        repo.create(&mut other).unwrap();
+
+        let names: Vec<String> = repo.list_by_library(lib1).unwrap().into_iter().map(|e| e.name_en).collect();
+        assert_eq!(names, vec!["AK-74", "BMP-2", "Ural-4320"]);
+        assert_eq!(repo.list_by_library(lib2).unwrap().len(), 1);
+
+        repo.delete_by_library(lib1).unwrap();
+        assert!(repo.list_by_library(lib1).unwrap().is_empty());
+        assert_eq!(repo.list_by_library(lib2).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_catalog_deleted_with_library() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db, "Test");
+        let repo = EquipmentCatalogRepo::new(db.conn());
+        let mut entry = EquipmentCatalogEntry::new(lib_id, "АК-74".to_string(), "AK-74".to_string(), EquipmentCategory::SmallArms);
+        repo.create(&mut entry).unwrap();
+        LibraryRepo::new(db.conn()).delete(lib_id).unwrap();
+        assert!(repo.list_by_library(lib_id).unwrap().is_empty());
+    }
+}
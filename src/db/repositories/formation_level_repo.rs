@@ -15,13 +15,17 @@ impl<'a> FormationLevelRepo<'a> {
 
     pub fn create(&self, level: &mut CustomFormationLevel) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO formation_levels (library_id, name_ru, name_en, standard_level_ordinal)
-             VALUES (?1, ?2, ?3, ?4)",
+            "INSERT INTO formation_levels
+                (library_id, name_ru, name_en, standard_level_ordinal, sort_order, min_personnel, max_personnel)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 level.library_id,
                 level.name_ru,
                 level.name_en,
                 level.standard_level_ordinal,
+                level.sort_order,
+                level.min_personnel,
+                level.max_personnel,
             ],
         )?;
         level.id = Some(self.conn.last_insert_rowid());
@@ -30,7 +34,8 @@ impl<'a> FormationLevelRepo<'a> {
 
     pub fn get_by_id(&self, id: i64) -> Result<Option<CustomFormationLevel>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, name_ru, name_en, standard_level_ordinal
+            "SELECT id, library_id, name_ru, name_en, standard_level_ordinal, sort_order,
+                    min_personnel, max_personnel
              FROM formation_levels WHERE id = ?1",
         )?;
         let mut rows = stmt.query_map(params![id], |row| {
@@ -40,6 +45,9 @@ impl<'a> FormationLevelRepo<'a> {
                 name_ru: row.get(2)?,
                 name_en: row.get(3)?,
                 standard_level_ordinal: row.get(4)?,
+                sort_order: row.get(5)?,
+                min_personnel: row.get(6)?,
+                max_personnel: row.get(7)?,
             })
         })?;
         match rows.next() {
@@ -51,8 +59,10 @@ impl<'a> FormationLevelRepo<'a> {
 
     pub fn list_by_library(&self, library_id: i64) -> Result<Vec<CustomFormationLevel>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, name_ru, name_en, standard_level_ordinal
-             FROM formation_levels WHERE library_id = ?1 ORDER BY standard_level_ordinal, id",
+            "SELECT id, library_id, name_ru, name_en, standard_level_ordinal, sort_order,
+                    min_personnel, max_personnel
+             FROM formation_levels WHERE library_id = ?1
+             ORDER BY sort_order, standard_level_ordinal, id",
         )?;
         let rows = stmt.query_map(params![library_id], |row| {
             Ok(CustomFormationLevel {
@@ -61,6 +71,9 @@ impl<'a> FormationLevelRepo<'a> {
                 name_ru: row.get(2)?,
                 name_en: row.get(3)?,
                 standard_level_ordinal: row.get(4)?,
+                sort_order: row.get(5)?,
+                min_personnel: row.get(6)?,
+                max_personnel: row.get(7)?,
             })
         })?;
         let mut out = Vec::new();
@@ -73,18 +86,30 @@ impl<'a> FormationLevelRepo<'a> {
     pub fn update(&self, level: &CustomFormationLevel) -> Result<()> {
         let id = level.id.ok_or_else(|| anyhow::anyhow!("CustomFormationLevel has no id"))?;
         self.conn.execute(
-            "UPDATE formation_levels SET name_ru = ?1, name_en = ?2, standard_level_ordinal = ?3
-             WHERE id = ?4",
+            "UPDATE formation_levels SET name_ru = ?1, name_en = ?2, standard_level_ordinal = ?3,
+                    min_personnel = ?4, max_personnel = ?5
+             WHERE id = ?6",
             params![
                 level.name_ru,
                 level.name_en,
                 level.standard_level_ordinal,
+                level.min_personnel,
+                level.max_personnel,
                 id,
             ],
         )?;
         Ok(())
     }
 
+    /// Set a level's position in its library's list
+    pub fn set_sort_order(&self, id: i64, sort_order: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE formation_levels SET sort_order = ?1 WHERE id = ?2",
+            params![sort_order, id],
+        )?;
+        Ok(())
+    }
+
     pub fn delete(&self, id: i64) -> Result<()> {
         self.conn.execute("DELETE FROM formation_levels WHERE id = ?1", params![id])?;
         Ok(())
@@ -145,6 +170,13 @@ mod tests {
         let after = repo.get_by_id(level.id.unwrap()).unwrap().unwrap();
         assert_eq!(after.name_ru, "взвод");
         assert_eq!(after.standard_level_ordinal, 3);
+        assert_eq!(after.min_personnel, None);
+
+        updated.min_personnel = Some(25);
+        updated.max_personnel = Some(45);
+        repo.update(&updated).unwrap();
+        let ranged = repo.get_by_id(level.id.unwrap()).unwrap().unwrap();
+        assert_eq!((ranged.min_personnel, ranged.max_personnel), (Some(25), Some(45)));
 
         repo.delete(level.id.unwrap()).unwrap();
         assert!(repo.get_by_id(level.id.unwrap()).unwrap().is_none());
@@ -187,4 +219,56 @@ mod tests {
         let result = repo.update(&level);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_level_list_ordered_by_sort_order() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new(
+            "Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string(),
+        );
+        lib_repo.create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+
+        let repo = FormationLevelRepo::new(db.conn());
+        for (order, en, ordinal) in [(0, "division", 8), (1, "brigade", 7), (2, "regiment", 6)] {
+            let mut l = CustomFormationLevel::new(lib_id, en.to_string(), en.to_string(), ordinal);
+            l.sort_order = order;
+            repo.create(&mut l).unwrap();
+        }
+        let names: Vec<String> = repo.list_by_library(lib_id).unwrap().into_iter().map(|l| l.name_en).collect();
+        assert_eq!(names, vec!["division", "brigade", "regiment"]);
+    }
+
+    #[test]
+    fn test_level_sort_order_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new(
+            "Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string(),
+        );
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+
+        let repo = FormationLevelRepo::new(db.conn());
+        let mut ids = Vec::new();
+        for (order, en) in [(0, "platoon"), (1, "troop")] {
+            let mut l = CustomFormationLevel::new(lib_id, en.to_string(), en.to_string(), 2);
+            l.sort_order = order;
+            repo.create(&mut l).unwrap();
+            ids.push(l.id.unwrap());
+        }
+        assert_eq!(repo.get_by_id(ids[1]).unwrap().unwrap().sort_order, 1);
+
+        // Levels sharing a standard level keep the order set for them
+        repo.set_sort_order(ids[0], 2).unwrap();
+        let listed: Vec<(String, i64)> =
+            repo.list_by_library(lib_id).unwrap().into_iter().map(|l| (l.name_en, l.sort_order)).collect();
+        assert_eq!(listed, vec![("troop".to_string(), 1), ("platoon".to_string(), 2)]);
+
+        // Updating the names leaves the position alone
+        let mut troop = repo.get_by_id(ids[1]).unwrap().unwrap();
+        troop.name_en = "squadron".to_string();
+        repo.update(&troop).unwrap();
+        assert_eq!(repo.get_by_id(ids[1]).unwrap().unwrap().sort_order, 1);
+    }
 }
@@ -0,0 +1,75 @@
+//! Repository for unit pairs the duplicate finder should not report again
+
+use std::collections::HashSet;
+use anyhow::Result;
+use rusqlite::{params, Connection};
+
+pub struct IgnoredDuplicateRepo<'a> {
+    conn: &'a Connection,
+}
+
+/// A pair of unit ids in stored order, smaller id first
+pub fn pair_key(a: i64, b: i64) -> (i64, i64) {
+    (a.min(b), a.max(b))
+}
+
+impl<'a> IgnoredDuplicateRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Remember that units `a` and `b` are not duplicates; either order
+    pub fn ignore(&self, library_id: i64, a: i64, b: i64) -> Result<()> {
+        let (first, second) = pair_key(a, b);
+        self.conn.execute(
+            "INSERT OR IGNORE INTO ignored_duplicates (library_id, first_unit_id, second_unit_id)
+             VALUES (?1, ?2, ?3)",
+            params![library_id, first, second],
+        )?;
+        Ok(())
+    }
+
+    /// Ignored pairs of a library, as [`pair_key`]s
+    pub fn list(&self, library_id: i64) -> Result<HashSet<(i64, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT first_unit_id, second_unit_id FROM ignored_duplicates WHERE library_id = ?1",
+        )?;
+        let rows = stmt.query_map(params![library_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::db::repositories::{LibraryRepo, UnitRepo};
+    use crate::models::{Library, Unit};
+
+    #[test]
+    fn test_ignored_pairs_persist_until_a_unit_is_deleted() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("L".to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+        let units = UnitRepo::new(db.conn());
+        let ids: Vec<i64> = (0..3)
+            .map(|i| {
+                let mut unit = Unit::new(format!("Unit {}", i), "Company".to_string());
+                units.create(lib_id, &mut unit).unwrap();
+                unit.id.unwrap()
+            })
+            .collect();
+        let repo = IgnoredDuplicateRepo::new(db.conn());
+        repo.ignore(lib_id, ids[1], ids[0]).unwrap();
+        repo.ignore(lib_id, ids[0], ids[1]).unwrap();
+        repo.ignore(lib_id, ids[2], ids[1]).unwrap();
+        assert_eq!(
+            repo.list(lib_id).unwrap(),
+            HashSet::from([(ids[0], ids[1]), (ids[1], ids[2])])
+        );
+
+        units.delete(ids[2]).unwrap();
+        assert_eq!(repo.list(lib_id).unwrap(), HashSet::from([(ids[0], ids[1])]));
+    }
+}
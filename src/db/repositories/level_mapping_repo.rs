@@ -0,0 +1,100 @@
+//! Repository for the queue of unit types awaiting a confirmed formation level
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+
+/// A unit whose type could not be mapped to a formation level with confidence
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelMappingEntry {
+    pub unit_id: i64,
+    pub library_id: i64,
+    pub unit_name: String,
+    pub unit_type: String,
+    /// Best matching level, if any came close enough
+    pub suggested_level_id: Option<i64>,
+    /// Similarity of the suggestion, 0.0..=1.0
+    pub score: f64,
+}
+
+pub struct LevelMappingRepo<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> LevelMappingRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Queue a unit, or refresh its suggestion if it is already queued.
+    /// A dismissed entry stays dismissed unless the unit type changed.
+    pub fn upsert(
+        &self,
+        library_id: i64,
+        unit_id: i64,
+        unit_type: &str,
+        suggested_level_id: Option<i64>,
+        score: f64,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO level_mapping_queue (unit_id, library_id, unit_type, suggested_level_id, score)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(unit_id) DO UPDATE SET
+                dismissed = CASE WHEN unit_type = excluded.unit_type THEN dismissed ELSE 0 END,
+                unit_type = excluded.unit_type,
+                suggested_level_id = excluded.suggested_level_id,
+                score = excluded.score",
+            params![unit_id, library_id, unit_type, suggested_level_id, score],
+        )?;
+        Ok(())
+    }
+
+    /// Entries of a library still waiting for review, grouped by unit type
+    pub fn list_pending(&self, library_id: i64) -> Result<Vec<LevelMappingEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT q.unit_id, q.library_id, u.name, q.unit_type, q.suggested_level_id, q.score
+             FROM level_mapping_queue q JOIN units u ON u.id = q.unit_id
+             WHERE q.library_id = ?1 AND q.dismissed = 0
+             ORDER BY q.unit_type, u.sort_order, u.id",
+        )?;
+        let rows = stmt.query_map(params![library_id], |row| {
+            Ok(LevelMappingEntry {
+                unit_id: row.get(0)?,
+                library_id: row.get(1)?,
+                unit_name: row.get(2)?,
+                unit_type: row.get(3)?,
+                suggested_level_id: row.get(4)?,
+                score: row.get(5)?,
+            })
+        })?;
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(row?);
+        }
+        Ok(entries)
+    }
+
+    /// Number of entries of a library still waiting for review
+    pub fn count_pending(&self, library_id: i64) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM level_mapping_queue WHERE library_id = ?1 AND dismissed = 0",
+            params![library_id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Take a unit off the queue (its level is now set)
+    pub fn remove(&self, unit_id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM level_mapping_queue WHERE unit_id = ?1", params![unit_id])?;
+        Ok(())
+    }
+
+    /// Keep a unit without a level and stop asking about it
+    pub fn dismiss(&self, unit_id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE level_mapping_queue SET dismissed = 1 WHERE unit_id = ?1",
+            params![unit_id],
+        )?;
+        Ok(())
+    }
+}
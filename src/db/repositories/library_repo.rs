@@ -1,26 +1,116 @@
 //! Repository for Library operations
 
 use anyhow::Result;
-use rusqlite::{params, Connection};
-use crate::models::Library;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::models::{Library, normalize_tags, resolve_country_code, toe_number_key, NumberingOptions};
+
+/// What lists of libraries show, without loading the libraries themselves
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibrarySummary {
+    pub id: i64,
+    pub name: String,
+    pub country: String,
+    pub era: String,
+    pub tag_count: usize,
+    pub unit_count: usize,
+    /// Last change (Unix seconds)
+    pub updated_at: i64,
+}
+
+/// Counts and dates of one library, for its properties dialog and the CLI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LibraryStats {
+    pub library_id: i64,
+    pub units: i64,
+    /// Personnel positions of all units
+    pub personnel: i64,
+    /// Equipment items of all units, quantities summed
+    pub equipment: i64,
+    /// Saved versions; autosaves are not counted
+    pub snapshots: i64,
+    pub branches: i64,
+    pub branch_categories: i64,
+    pub formation_levels: i64,
+    /// Creation (Unix seconds)
+    pub created_at: i64,
+    /// Last change (Unix seconds)
+    pub updated_at: i64,
+}
+
+/// Order of [`LibraryRepo::list_all_ordered`]; ties go by name, then id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrderBy {
+    #[default]
+    Name,
+    Country,
+    /// Most recently changed first
+    UpdatedAtDesc,
+    /// Most recently created first
+    CreatedAtDesc,
+}
+
+impl OrderBy {
+    fn sql(self) -> &'static str {
+        match self {
+            OrderBy::Name => "name, id",
+            OrderBy::Country => "country, name, id",
+            OrderBy::UpdatedAtDesc => "updated_at DESC, name, id",
+            OrderBy::CreatedAtDesc => "created_at DESC, name, id",
+        }
+    }
+}
 
 /// Repository for library database operations
 pub struct LibraryRepo<'a> {
     conn: &'a Connection,
 }
 
+/// Numbering options as stored: JSON, or empty for the defaults
+fn numbering_column(numbering: &NumberingOptions) -> Result<String> {
+    if numbering.is_default() {
+        return Ok(String::new());
+    }
+    Ok(serde_json::to_string(numbering)?)
+}
+
+/// Numbering options of a stored column; empty or unreadable text gives the defaults
+fn numbering_from_column(column: &str) -> NumberingOptions {
+    serde_json::from_str(column).unwrap_or_default()
+}
+
 impl<'a> LibraryRepo<'a> {
     /// Create new repository
     pub fn new(conn: &'a Connection) -> Self {
         Self { conn }
     }
 
+    /// Build a library from a row of `id, name, country, era, author, version, tags, display_language, country_code, numbering`
+    fn map_row(row: &rusqlite::Row) -> rusqlite::Result<Library> {
+        let tags_json: String = row.get(6)?;
+        let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
+        Ok(Library {
+            id: Some(row.get(0)?),
+            name: row.get(1)?,
+            country: row.get(2)?,
+            era: row.get(3)?,
+            author: row.get(4)?,
+            version: row.get(5)?,
+            tags,
+            units: Vec::new(), // Units loaded separately
+            display_language: row.get(7)?,
+            country_code: row.get(8)?,
+            numbering: numbering_from_column(&row.get::<_, String>(9)?),
+        })
+    }
+
     /// Create a new library
     pub fn create(&self, library: &mut Library) -> Result<()> {
         let now = chrono::Utc::now().timestamp();
         self.conn.execute(
-            "INSERT INTO libraries (name, country, era, author, version, tags, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            "INSERT INTO libraries (name, country, era, author, version, tags, created_at, updated_at, display_language, country_code, numbering)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 library.name,
                 library.country,
@@ -29,7 +119,10 @@ impl<'a> LibraryRepo<'a> {
                 library.version,
                 serde_json::to_string(&library.tags)?,
                 now,
-                now
+                now,
+                library.display_language,
+                library.country_code,
+                numbering_column(&library.numbering)?
             ],
         )?;
         library.id = Some(self.conn.last_insert_rowid());
@@ -39,23 +132,10 @@ impl<'a> LibraryRepo<'a> {
     /// Get library by ID
     pub fn get_by_id(&self, id: i64) -> Result<Option<Library>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, country, era, author, version, tags FROM libraries WHERE id = ?1"
+            "SELECT id, name, country, era, author, version, tags, display_language, country_code, numbering FROM libraries WHERE id = ?1"
         )?;
         
-        let mut rows = stmt.query_map(params![id], |row| {
-            let tags_json: String = row.get(6)?;
-            let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            Ok(Library {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                country: row.get(2)?,
-                era: row.get(3)?,
-                author: row.get(4)?,
-                version: row.get(5)?,
-                tags,
-                units: Vec::new(), // Units loaded separately
-            })
-        })?;
+        let mut rows = stmt.query_map(params![id], Self::map_row)?;
 
         match rows.next() {
             Some(Ok(lib)) => Ok(Some(lib)),
@@ -67,23 +147,10 @@ impl<'a> LibraryRepo<'a> {
     /// List all libraries
     pub fn list_all(&self) -> Result<Vec<Library>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, country, era, author, version, tags FROM libraries ORDER BY name"
+            "SELECT id, name, country, era, author, version, tags, display_language, country_code, numbering FROM libraries ORDER BY name"
         )?;
         
-        let rows = stmt.query_map([], |row| {
-            let tags_json: String = row.get(6)?;
-            let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            Ok(Library {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                country: row.get(2)?,
-                era: row.get(3)?,
-                author: row.get(4)?,
-                version: row.get(5)?,
-                tags,
-                units: Vec::new(),
-            })
-        })?;
+        let rows = stmt.query_map([], Self::map_row)?;
 
         let mut libraries = Vec::new();
         for row in rows {
@@ -92,34 +159,53 @@ impl<'a> LibraryRepo<'a> {
         Ok(libraries)
     }
 
-    /// Search libraries by name, country, era, author, or tags
+    /// List all libraries in the given order
+    pub fn list_all_ordered(&self, order: OrderBy) -> Result<Vec<Library>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, name, country, era, author, version, tags, display_language, country_code, numbering FROM libraries ORDER BY {}",
+            order.sql()
+        ))?;
+        let rows = stmt.query_map([], Self::map_row)?;
+        let mut libraries = Vec::new();
+        for row in rows {
+            libraries.push(row?);
+        }
+        Ok(libraries)
+    }
+
+    /// Search libraries by name, country, era, author, or tags (substring, ASCII
+    /// case-insensitive). Tags are matched one by one, so the query never hits
+    /// the JSON punctuation of the stored list; `%` and `_` match literally.
+    /// A query naming a known country also finds libraries by country code,
+    /// so "США" finds libraries entered as "US". Libraries with a unit whose
+    /// TOE number contains the query, spaces ignored, are found too.
     pub fn search(&self, query: &str) -> Result<Vec<Library>> {
-        let search_pattern = format!("%{}%", query);
+        let search_pattern = like_pattern(query.trim());
+        let country_code = resolve_country_code(query);
+        let toe_pattern = like_pattern(&toe_number_key(query));
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, country, era, author, version, tags 
-             FROM libraries 
-             WHERE name LIKE ?1 
-                OR country LIKE ?1 
-                OR era LIKE ?1 
-                OR author LIKE ?1 
-                OR tags LIKE ?1
+            "SELECT id, name, country, era, author, version, tags, display_language, country_code, numbering
+             FROM libraries
+             WHERE name LIKE ?1 ESCAPE '\\'
+                OR country LIKE ?1 ESCAPE '\\'
+                OR era LIKE ?1 ESCAPE '\\'
+                OR author LIKE ?1 ESCAPE '\\'
+                OR EXISTS (
+                    SELECT 1 FROM json_each(CASE WHEN json_valid(libraries.tags) THEN libraries.tags ELSE '[]' END) AS tag
+                    WHERE tag.value LIKE ?1 ESCAPE '\\'
+                )
+                -- Rows written before tags were stored as JSON hold plain comma-joined text
+                OR (NOT json_valid(libraries.tags) AND libraries.tags LIKE ?1 ESCAPE '\\')
+                OR country_code = ?2
+                OR EXISTS (
+                    SELECT 1 FROM units
+                    WHERE units.library_id = libraries.id
+                      AND REPLACE(units.toe_number, ' ', '') LIKE ?3 ESCAPE '\\'
+                )
              ORDER BY name"
         )?;
-        
-        let rows = stmt.query_map(params![search_pattern], |row| {
-            let tags_json: String = row.get(6)?;
-            let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            Ok(Library {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                country: row.get(2)?,
-                era: row.get(3)?,
-                author: row.get(4)?,
-                version: row.get(5)?,
-                tags,
-                units: Vec::new(),
-            })
-        })?;
+
+        let rows = stmt.query_map(params![search_pattern, country_code, toe_pattern], Self::map_row)?;
 
         let mut libraries = Vec::new();
         for row in rows {
@@ -128,13 +214,51 @@ impl<'a> LibraryRepo<'a> {
         Ok(libraries)
     }
 
+    /// First library with exactly this name, country and era
+    pub fn find_by_name_country_era(&self, name: &str, country: &str, era: &str) -> Result<Option<Library>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, country, era, author, version, tags, display_language, country_code, numbering
+             FROM libraries
+             WHERE name = ?1 AND country = ?2 AND era = ?3
+             ORDER BY id
+             LIMIT 1"
+        )?;
+        let mut rows = stmt.query_map(params![name, country, era], Self::map_row)?;
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+
+    /// First library with this name, country and era, ignoring case and
+    /// surrounding whitespace
+    pub fn find_by_identity(&self, name: &str, country: &str, era: &str) -> Result<Option<Library>> {
+        // Compared here rather than in SQL: SQLite's lower() only folds ASCII,
+        // and most libraries have Cyrillic names
+        let key = |value: &str| value.trim().to_lowercase();
+        let (name, country, era) = (key(name), key(country), key(era));
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, country, era, author, version, tags, display_language, country_code, numbering
+             FROM libraries
+             ORDER BY id"
+        )?;
+        for row in stmt.query_map([], Self::map_row)? {
+            let library = row?;
+            if key(&library.name) == name && key(&library.country) == country && key(&library.era) == era {
+                return Ok(Some(library));
+            }
+        }
+        Ok(None)
+    }
+
     /// Update library
     pub fn update(&self, library: &Library) -> Result<()> {
         let id = library.id.ok_or_else(|| anyhow::anyhow!("Cannot update library without id"))?;
         let now = chrono::Utc::now().timestamp();
         self.conn.execute(
-            "UPDATE libraries SET name = ?1, country = ?2, era = ?3, author = ?4, version = ?5, tags = ?6, updated_at = ?7
-             WHERE id = ?8",
+            "UPDATE libraries SET name = ?1, country = ?2, era = ?3, author = ?4, version = ?5, tags = ?6, updated_at = ?7,
+                display_language = ?8, country_code = ?9, numbering = ?10
+             WHERE id = ?11",
             params![
                 library.name,
                 library.country,
@@ -143,12 +267,147 @@ impl<'a> LibraryRepo<'a> {
                 library.version,
                 serde_json::to_string(&library.tags)?,
                 now,
+                library.display_language,
+                library.country_code,
+                numbering_column(&library.numbering)?,
                 id
             ],
         )?;
         Ok(())
     }
 
+    /// Store a library's numbering options without touching its version
+    pub fn update_numbering(&self, id: i64, numbering: &NumberingOptions) -> Result<()> {
+        self.conn.execute(
+            "UPDATE libraries SET numbering = ?1 WHERE id = ?2",
+            params![numbering_column(numbering)?, id],
+        )?;
+        Ok(())
+    }
+
+    /// Replace a library's tags without touching its version
+    pub fn update_tags(&self, id: i64, tags: &[String]) -> Result<()> {
+        self.conn.execute(
+            "UPDATE libraries SET tags = ?1, updated_at = ?2 WHERE id = ?3",
+            params![serde_json::to_string(tags)?, chrono::Utc::now().timestamp(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Every tag used by any library, normalized and without case-insensitive
+    /// duplicates (the spelling of the oldest library wins), sorted case-insensitively
+    pub fn list_all_tags(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT tags FROM libraries ORDER BY id")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut raw = Vec::new();
+        for row in rows {
+            let column = row?;
+            match serde_json::from_str::<Vec<String>>(&column) {
+                Ok(tags) => raw.extend(tags),
+                // Rows written before tags were stored as JSON hold plain comma-joined text
+                Err(_) => raw.extend(column.split(',').map(str::to_string)),
+            }
+        }
+        let mut tags = normalize_tags(raw);
+        tags.sort_by_key(|t| t.to_lowercase());
+        Ok(tags)
+    }
+
+    /// Count units per library with a single grouped query.
+    /// Libraries without units are absent from the map.
+    pub fn unit_counts_all(&self) -> Result<HashMap<i64, i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT library_id, COUNT(*) FROM units GROUP BY library_id"
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+
+        let mut counts = HashMap::new();
+        for row in rows {
+            let (library_id, count) = row?;
+            counts.insert(library_id, count);
+        }
+        Ok(counts)
+    }
+
+    /// Names of all libraries, ordered by name
+    pub fn list_names(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT name FROM libraries ORDER BY name")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Summaries of all libraries, ordered by name, from a single query
+    pub fn list_summaries(&self) -> Result<Vec<LibrarySummary>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT libraries.id, libraries.name, libraries.country, libraries.era,
+                    CASE
+                        WHEN json_valid(libraries.tags) THEN json_array_length(libraries.tags)
+                        -- Rows written before tags were stored as JSON hold plain comma-joined text
+                        WHEN TRIM(COALESCE(libraries.tags, '')) = '' THEN 0
+                        ELSE LENGTH(libraries.tags) - LENGTH(REPLACE(libraries.tags, ',', '')) + 1
+                    END,
+                    COUNT(units.id), libraries.updated_at
+             FROM libraries
+             LEFT JOIN units ON units.library_id = libraries.id
+             GROUP BY libraries.id
+             ORDER BY libraries.name"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(LibrarySummary {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                country: row.get(2)?,
+                era: row.get(3)?,
+                tag_count: row.get::<_, i64>(4)? as usize,
+                unit_count: row.get::<_, i64>(5)? as usize,
+                updated_at: row.get(6)?,
+            })
+        })?;
+
+        let mut summaries = Vec::new();
+        for row in rows {
+            summaries.push(row?);
+        }
+        Ok(summaries)
+    }
+
+    /// Counts and dates of a library, from a single query
+    pub fn stats(&self, library_id: i64) -> Result<LibraryStats> {
+        let stats = self
+            .conn
+            .query_row(
+                "SELECT id,
+                        (SELECT COUNT(*) FROM units WHERE library_id = ?1),
+                        (SELECT COUNT(*) FROM personnel JOIN units ON units.id = personnel.unit_id
+                         WHERE units.library_id = ?1),
+                        (SELECT COALESCE(SUM(equipment.quantity), 0) FROM equipment JOIN units ON units.id = equipment.unit_id
+                         WHERE units.library_id = ?1),
+                        (SELECT COUNT(*) FROM snapshots WHERE library_id = ?1 AND is_autosave = 0),
+                        (SELECT COUNT(*) FROM branches WHERE library_id = ?1),
+                        (SELECT COUNT(*) FROM branch_categories WHERE library_id = ?1),
+                        (SELECT COUNT(*) FROM formation_levels WHERE library_id = ?1),
+                        created_at, updated_at
+                 FROM libraries WHERE id = ?1",
+                params![library_id],
+                |row| {
+                    Ok(LibraryStats {
+                        library_id: row.get(0)?,
+                        units: row.get(1)?,
+                        personnel: row.get(2)?,
+                        equipment: row.get(3)?,
+                        snapshots: row.get(4)?,
+                        branches: row.get(5)?,
+                        branch_categories: row.get(6)?,
+                        formation_levels: row.get(7)?,
+                        created_at: row.get(8)?,
+                        updated_at: row.get(9)?,
+                    })
+                },
+            )
+            .optional()?;
+        stats.ok_or_else(|| anyhow::anyhow!("Library {} not found", library_id))
+    }
+
     /// Delete library
     pub fn delete(&self, id: i64) -> Result<()> {
         self.conn.execute("DELETE FROM libraries WHERE id = ?1", params![id])?;
@@ -156,10 +415,25 @@ impl<'a> LibraryRepo<'a> {
     }
 }
 
+/// `%query%` for LIKE, with the query's own wildcards escaped by a backslash
+fn like_pattern(query: &str) -> String {
+    let mut pattern = String::with_capacity(query.len() + 2);
+    pattern.push('%');
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db::Database;
+    use crate::models::NumberingStyle;
 
     #[test]
     fn test_create_library() {
@@ -176,6 +450,18 @@ mod tests {
         assert_eq!(library.version, 1);
     }
 
+    #[test]
+    fn test_list_names() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        assert!(repo.list_names().unwrap().is_empty());
+        for name in ["Beta", "Alpha"] {
+            let mut library = Library::new(name.to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+            repo.create(&mut library).unwrap();
+        }
+        assert_eq!(repo.list_names().unwrap(), ["Alpha", "Beta"]);
+    }
+
     #[test]
     fn test_get_library() {
         let db = Database::open_in_memory().unwrap();
@@ -234,6 +520,134 @@ mod tests {
         assert_eq!(results[0].author, "John Doe");
     }
 
+    #[test]
+    fn test_search_matches_tags() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut tagged = Library::new("Alpha".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        tagged.tags = vec!["infantry".to_string(), "Cold War".to_string()];
+        repo.create(&mut tagged).unwrap();
+        let mut other = Library::new("Beta".to_string(), "RU".to_string(), "2020".to_string(), "B".to_string());
+        other.tags = vec!["armor".to_string()];
+        repo.create(&mut other).unwrap();
+
+        let names = |query: &str| -> Vec<String> {
+            repo.search(query).unwrap().into_iter().map(|l| l.name).collect()
+        };
+        assert_eq!(names("infantry"), ["Alpha"]);
+        assert_eq!(names("fant"), ["Alpha"]);
+        assert_eq!(names("cold war"), ["Alpha"]);
+        assert_eq!(names("armor"), ["Beta"]);
+        // The JSON list syntax around the tags is not searchable
+        assert!(names("\",\"").is_empty());
+        assert!(names("[").is_empty());
+        assert!(names("infantry\",\"Cold").is_empty());
+    }
+
+    #[test]
+    fn test_search_matches_toe_numbers() {
+        use crate::db::repositories::UnitRepo;
+        use crate::models::Unit;
+
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new("Alpha".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        repo.create(&mut library).unwrap();
+        let mut unit = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+        unit.toe_number = Some("TOE 07015L000".to_string());
+        UnitRepo::new(db.conn()).create(library.id.unwrap(), &mut unit).unwrap();
+        repo.create(&mut Library::new("Beta".to_string(), "US".to_string(), "2003".to_string(), "B".to_string())).unwrap();
+
+        let names = |query: &str| -> Vec<String> {
+            repo.search(query).unwrap().into_iter().map(|l| l.name).collect()
+        };
+        assert_eq!(names("07015l"), ["Alpha"]);
+        assert_eq!(names("toe07015L000"), ["Alpha"]);
+        assert!(names("07245").is_empty());
+    }
+
+    #[test]
+    fn test_numbering_stored_without_a_new_version() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new("Alpha".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        repo.create(&mut library).unwrap();
+        let id = library.id.unwrap();
+        let column = || -> String {
+            db.conn().query_row("SELECT numbering FROM libraries WHERE id = ?1", [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(column(), "");
+
+        let mut numbering = NumberingOptions::with_style(NumberingStyle::Roman);
+        numbering.set_override(7, NumberingStyle::Letters);
+        repo.update_numbering(id, &numbering).unwrap();
+        let loaded = repo.get_by_id(id).unwrap().unwrap();
+        assert_eq!(loaded.numbering, numbering);
+        assert_eq!(loaded.version, library.version);
+
+        // A library update writes the numbering it carries
+        repo.update(&Library { numbering: NumberingOptions::default(), ..loaded }).unwrap();
+        assert_eq!(column(), "");
+    }
+
+    #[test]
+    fn test_country_code_stored_and_searched() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new("Alpha".to_string(), "USA".to_string(), "2003".to_string(), "A".to_string());
+        assert_eq!(library.country_code.as_deref(), Some("US"));
+        repo.create(&mut library).unwrap();
+        let mut prussia = Library::new("Beta".to_string(), "Prussia".to_string(), "1870".to_string(), "B".to_string());
+        repo.create(&mut prussia).unwrap();
+        let mut free = Library::new("Gamma".to_string(), "Free City".to_string(), "1920".to_string(), "C".to_string());
+        repo.create(&mut free).unwrap();
+
+        let loaded = repo.get_by_id(prussia.id.unwrap()).unwrap().unwrap();
+        assert_eq!(loaded.country_code.as_deref(), Some("X-PRU"));
+        assert_eq!(repo.get_by_id(free.id.unwrap()).unwrap().unwrap().country_code, None);
+
+        let names = |query: &str| -> Vec<String> {
+            repo.search(query).unwrap().into_iter().map(|l| l.name).collect()
+        };
+        assert_eq!(names("США"), ["Alpha"]);
+        assert_eq!(names("United States"), ["Alpha"]);
+        assert_eq!(names("пруссия"), ["Beta"]);
+        assert_eq!(names("Free City"), ["Gamma"]);
+    }
+
+    #[test]
+    fn test_search_wildcards_are_literal() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new("100% Mech".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        library.tags = vec!["tag_one".to_string()];
+        repo.create(&mut library).unwrap();
+        let mut other = Library::new("Other".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        other.tags = vec!["tagXone".to_string()];
+        repo.create(&mut other).unwrap();
+
+        assert_eq!(repo.search("%").unwrap().len(), 1);
+        let results = repo.search("tag_one").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "100% Mech");
+    }
+
+    #[test]
+    fn test_search_matches_legacy_comma_joined_tags() {
+        let db = Database::open_in_memory().unwrap();
+        db.conn()
+            .execute(
+                "INSERT INTO libraries (name, country, era, author, version, tags, created_at, updated_at)
+                 VALUES ('Legacy', 'US', '1944', '', 1, 'airborne, infantry', 0, 0)",
+                [],
+            )
+            .unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let results = repo.search("airborne").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "Legacy");
+    }
+
     #[test]
     fn test_update_library() {
         let db = Database::open_in_memory().unwrap();
@@ -257,6 +671,71 @@ mod tests {
         assert_eq!(retrieved.version, 2);
     }
 
+    #[test]
+    fn test_display_language_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new(
+            "Test".to_string(),
+            "SU".to_string(),
+            "1985".to_string(),
+            "Author".to_string(),
+        );
+        repo.create(&mut library).unwrap();
+        let id = library.id.unwrap();
+        assert_eq!(repo.get_by_id(id).unwrap().unwrap().display_language, "auto");
+
+        library.display_language = "ru".to_string();
+        repo.update(&library).unwrap();
+        assert_eq!(repo.get_by_id(id).unwrap().unwrap().display_language, "ru");
+        assert_eq!(repo.list_all().unwrap()[0].display_language, "ru");
+        assert_eq!(repo.search("1985").unwrap()[0].display_language, "ru");
+    }
+
+    #[test]
+    fn test_find_by_name_country_era() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new(
+            "US Army".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        repo.create(&mut library).unwrap();
+        let found = repo.find_by_name_country_era("US Army", "US", "2003").unwrap();
+        assert_eq!(found.unwrap().id, library.id);
+        assert!(repo.find_by_name_country_era("US Army", "US", "2020").unwrap().is_none());
+        assert!(repo.find_by_name_country_era("us army", "US", "2003").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_find_by_identity_ignores_case_and_whitespace() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let mut library = Library::new(
+            "US Army".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        repo.create(&mut library).unwrap();
+        let found = repo.find_by_identity("  us army ", "us", " 2003").unwrap();
+        assert_eq!(found.unwrap().id, library.id);
+        assert!(repo.find_by_identity("US Army", "US", "2004").unwrap().is_none());
+        assert!(repo.find_by_identity("US Army 2", "US", "2003").unwrap().is_none());
+
+        let mut cyrillic = Library::new(
+            "Сухопутные войска".to_string(),
+            "СССР".to_string(),
+            "1985".to_string(),
+            String::new(),
+        );
+        repo.create(&mut cyrillic).unwrap();
+        let found = repo.find_by_identity("СУХОПУТНЫЕ ВОЙСКА", "ссср", "1985").unwrap();
+        assert_eq!(found.unwrap().id, cyrillic.id);
+    }
+
     #[test]
     fn test_update_library_without_id_fails() {
         let db = Database::open_in_memory().unwrap();
@@ -272,4 +751,215 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("without id"));
     }
+
+    #[test]
+    fn test_list_all_ordered() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        // Created in this order, then changed oldest-created last
+        let mut ids = Vec::new();
+        for (i, (name, country)) in [("Bravo", "US"), ("Alpha", "RU"), ("Charlie", "DE")].into_iter().enumerate() {
+            let mut library = Library::new(name.to_string(), country.to_string(), "2003".to_string(), "A".to_string());
+            repo.create(&mut library).unwrap();
+            let id = library.id.unwrap();
+            db.conn()
+                .execute(
+                    "UPDATE libraries SET created_at = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![100 + i as i64, 300 - i as i64 * 100, id],
+                )
+                .unwrap();
+            ids.push(id);
+        }
+        let names = |order| -> Vec<String> {
+            repo.list_all_ordered(order).unwrap().into_iter().map(|l| l.name).collect()
+        };
+        assert_eq!(names(OrderBy::Name), ["Alpha", "Bravo", "Charlie"]);
+        assert_eq!(names(OrderBy::Country), ["Charlie", "Alpha", "Bravo"]);
+        assert_eq!(names(OrderBy::UpdatedAtDesc), ["Bravo", "Alpha", "Charlie"]);
+        assert_eq!(names(OrderBy::CreatedAtDesc), ["Charlie", "Alpha", "Bravo"]);
+
+        // Changing a library moves it to the front
+        let mut charlie = repo.get_by_id(ids[2]).unwrap().unwrap();
+        charlie.era = "2010".to_string();
+        repo.update(&charlie).unwrap();
+        assert_eq!(names(OrderBy::UpdatedAtDesc)[0], "Charlie");
+        assert_eq!(repo.list_all_ordered(OrderBy::Name).unwrap(), repo.list_all().unwrap());
+    }
+
+    #[test]
+    fn test_list_all_ordered_ties_go_by_name() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        for name in ["Zulu", "Yankee", "X-ray"] {
+            let mut library = Library::new(name.to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+            repo.create(&mut library).unwrap();
+        }
+        db.conn().execute("UPDATE libraries SET created_at = 1, updated_at = 1", []).unwrap();
+        for order in [OrderBy::Country, OrderBy::UpdatedAtDesc, OrderBy::CreatedAtDesc] {
+            let names: Vec<String> = repo.list_all_ordered(order).unwrap().into_iter().map(|l| l.name).collect();
+            assert_eq!(names, ["X-ray", "Yankee", "Zulu"], "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn test_list_summaries_counts_units_and_tags() {
+        use crate::db::repositories::UnitRepo;
+        use crate::models::Unit;
+
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let unit_repo = UnitRepo::new(db.conn());
+        let tx = db.conn().unchecked_transaction().unwrap();
+        let mut expected = Vec::new();
+        for i in 0..200 {
+            let mut library = Library::new(format!("Library {:03}", i), "US".to_string(), "2003".to_string(), "A".to_string());
+            library.tags = (0..i % 4).map(|t| format!("tag {}", t)).collect();
+            repo.create(&mut library).unwrap();
+            let lib_id = library.id.unwrap();
+            for u in 0..50 {
+                let mut unit = Unit::new(format!("Unit {}", u), "Company".to_string());
+                unit_repo.create(lib_id, &mut unit).unwrap();
+            }
+            expected.push((lib_id, i % 4));
+        }
+        let mut empty = Library::new("Empty".to_string(), "RU".to_string(), "1985".to_string(), "A".to_string());
+        repo.create(&mut empty).unwrap();
+        db.conn().execute("UPDATE libraries SET tags = 'old, legacy' WHERE id = ?1", params![empty.id]).unwrap();
+        tx.commit().unwrap();
+
+        let summaries = repo.list_summaries().unwrap();
+
+        assert_eq!(summaries.len(), 201);
+        // Ordered by name: "Empty" comes first
+        assert_eq!(summaries[0].id, empty.id.unwrap());
+        assert_eq!((summaries[0].unit_count, summaries[0].tag_count), (0, 2));
+        for (summary, (id, tags)) in summaries[1..].iter().zip(expected) {
+            assert_eq!(summary.id, id);
+            assert_eq!(summary.unit_count, 50);
+            assert_eq!(summary.tag_count, tags);
+            assert!(summary.updated_at > 0);
+        }
+    }
+
+    #[test]
+    fn test_unit_counts_all() {
+        use crate::db::repositories::UnitRepo;
+        use crate::models::Unit;
+
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let unit_repo = UnitRepo::new(db.conn());
+        let mut ids = Vec::new();
+        for (name, units) in [("Large", 3), ("Medium", 2), ("Small", 1), ("Empty", 0)] {
+            let mut library = Library::new(
+                name.to_string(),
+                "US".to_string(),
+                "2003".to_string(),
+                "Author".to_string(),
+            );
+            repo.create(&mut library).unwrap();
+            let lib_id = library.id.unwrap();
+            for i in 0..units {
+                let mut unit = Unit::new(format!("Unit {}", i), "Company".to_string());
+                unit_repo.create(lib_id, &mut unit).unwrap();
+            }
+            ids.push(lib_id);
+        }
+
+        let counts = repo.unit_counts_all().unwrap();
+        assert_eq!(counts.get(&ids[0]), Some(&3));
+        assert_eq!(counts.get(&ids[1]), Some(&2));
+        assert_eq!(counts.get(&ids[2]), Some(&1));
+        assert_eq!(counts.get(&ids[3]), None);
+        assert_eq!(counts.len(), 3);
+    }
+
+    #[test]
+    fn test_list_all_tags_merges_overlapping_tags() {
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        for (name, tags) in [
+            ("First", vec!["WW2", "infantry"]),
+            ("Second", vec!["ww2", "  Armor ", "cold   war"]),
+            ("Third", vec![]),
+        ] {
+            let mut library = Library::new(name.to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+            library.tags = tags.into_iter().map(str::to_string).collect();
+            repo.create(&mut library).unwrap();
+        }
+        db.conn()
+            .execute(
+                "INSERT INTO libraries (name, country, era, author, version, tags, created_at, updated_at)
+                 VALUES ('Legacy', 'US', '1944', 'Author', 1, 'Infantry, airborne', 0, 0)",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(repo.list_all_tags().unwrap(), ["airborne", "Armor", "cold war", "infantry", "WW2"]);
+    }
+
+    #[test]
+    fn test_stats_counts_one_library() {
+        use crate::db::repositories::{BranchCategoryRepo, BranchRepo, FormationLevelRepo, UnitRepo, VersionRepo};
+        use crate::models::{Branch, BranchCategory, CustomFormationLevel, Equipment, Personnel, Snapshot, Unit};
+
+        let db = Database::open_in_memory().unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        let unit_repo = UnitRepo::new(db.conn());
+        let mut ids = Vec::new();
+        for name in ["Counted", "Other"] {
+            let mut library = Library::new(name.to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+            repo.create(&mut library).unwrap();
+            let lib_id = library.id.unwrap();
+            let mut battalion = Unit::new("Battalion".to_string(), "Battalion".to_string());
+            unit_repo.create(lib_id, &mut battalion).unwrap();
+            let mut company = Unit::new("Company".to_string(), "Company".to_string());
+            company.parent_id = battalion.id;
+            unit_repo.create(lib_id, &mut company).unwrap();
+            for unit in [&battalion, &company] {
+                let unit_id = unit.id.unwrap();
+                unit_repo.create_personnel(unit_id, &mut Personnel::new("Commander".to_string())).unwrap();
+                unit_repo.create_equipment(unit_id, &Equipment::new("M2 Bradley".to_string(), 4)).unwrap();
+            }
+            unit_repo.create_equipment(company.id.unwrap(), &Equipment::new("M1A2".to_string(), 3)).unwrap();
+            ids.push(lib_id);
+        }
+        let lib_id = ids[0];
+        let mut category = BranchCategory::new(lib_id, "Боевые".to_string(), "Combat".to_string());
+        BranchCategoryRepo::new(db.conn()).create(&mut category).unwrap();
+        for (ru, en) in [("Пехота", "Infantry"), ("Связь", "Signals")] {
+            BranchRepo::new(db.conn()).create(&mut Branch::new(lib_id, ru.to_string(), en.to_string())).unwrap();
+        }
+        let mut level = CustomFormationLevel::new(lib_id, "Рота".to_string(), "Company".to_string(), 3);
+        FormationLevelRepo::new(db.conn()).create(&mut level).unwrap();
+        for version in 1..=3 {
+            VersionRepo::new(db.conn()).create(&mut Snapshot::new(lib_id, version, "{}".to_string())).unwrap();
+        }
+        let mut autosave = Snapshot::new(lib_id, 3, "{}".to_string());
+        autosave.is_autosave = true;
+        VersionRepo::new(db.conn()).create(&mut autosave).unwrap();
+        db.conn()
+            .execute("UPDATE libraries SET created_at = 100, updated_at = 200 WHERE id = ?1", params![lib_id])
+            .unwrap();
+
+        assert_eq!(
+            repo.stats(lib_id).unwrap(),
+            LibraryStats {
+                library_id: lib_id,
+                units: 2,
+                personnel: 2,
+                equipment: 11,
+                snapshots: 3,
+                branches: 2,
+                branch_categories: 1,
+                formation_levels: 1,
+                created_at: 100,
+                updated_at: 200,
+            }
+        );
+        let other = repo.stats(ids[1]).unwrap();
+        assert_eq!((other.units, other.personnel, other.equipment), (2, 2, 11));
+        assert_eq!((other.snapshots, other.branches, other.branch_categories, other.formation_levels), (0, 0, 0, 0));
+        assert!(repo.stats(ids[1] + 1).is_err());
+    }
 }
@@ -6,10 +6,18 @@ pub mod version_repo;
 pub mod formation_level_repo;
 pub mod branch_repo;
 pub mod branch_category_repo;
+pub mod level_mapping_repo;
+pub mod equipment_catalog_repo;
+pub mod provenance_repo;
+pub mod ignored_duplicate_repo;
 
-pub use library_repo::LibraryRepo;
-pub use unit_repo::UnitRepo;
+pub use library_repo::{LibraryRepo, LibraryStats, LibrarySummary, OrderBy};
+pub use unit_repo::{collapse_wide_groups, tree_as_of, tree_prefixes, TreeRow, UnitRepo, UnitTreeNode};
 pub use version_repo::VersionRepo;
 pub use formation_level_repo::FormationLevelRepo;
 pub use branch_repo::BranchRepo;
 pub use branch_category_repo::BranchCategoryRepo;
+pub use level_mapping_repo::{LevelMappingEntry, LevelMappingRepo};
+pub use equipment_catalog_repo::EquipmentCatalogRepo;
+pub use provenance_repo::{CopyTarget, ProvenanceRepo};
+pub use ignored_duplicate_repo::{pair_key, IgnoredDuplicateRepo};
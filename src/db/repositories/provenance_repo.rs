@@ -0,0 +1,199 @@
+//! Repository for copy-from-library provenance.
+
+use anyhow::Result;
+use rusqlite::{params, Connection, Row};
+use crate::models::{CopiedData, CopyRecord};
+
+pub struct ProvenanceRepo<'a> {
+    conn: &'a Connection,
+}
+
+/// A library that copied lists from the one being looked up
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyTarget {
+    pub library_id: i64,
+    pub library_name: String,
+    /// What was copied, without repeats
+    pub copied: Vec<CopiedData>,
+}
+
+fn map_row(row: &Row) -> rusqlite::Result<Option<CopyRecord>> {
+    // Rows written by a newer version with an unknown kind are skipped
+    let Some(what) = CopiedData::from_code(&row.get::<_, String>(3)?) else {
+        return Ok(None);
+    };
+    Ok(Some(CopyRecord {
+        id: Some(row.get(0)?),
+        target_library_id: row.get(1)?,
+        source_library_id: row.get(2)?,
+        what,
+        copied_at: row.get(4)?,
+    }))
+}
+
+impl<'a> ProvenanceRepo<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Record that `source_library_id` was copied into `target_library_id` now
+    pub fn record(&self, target_library_id: i64, source_library_id: i64, what: CopiedData) -> Result<CopyRecord> {
+        let mut record = CopyRecord {
+            id: None,
+            target_library_id,
+            source_library_id,
+            what,
+            copied_at: chrono::Utc::now().timestamp(),
+        };
+        self.create(&mut record)?;
+        Ok(record)
+    }
+
+    pub fn create(&self, record: &mut CopyRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO library_provenance (target_library_id, source_library_id, what, copied_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![record.target_library_id, record.source_library_id, record.what.code(), record.copied_at],
+        )?;
+        record.id = Some(self.conn.last_insert_rowid());
+        Ok(())
+    }
+
+    /// Copies into the library, newest first
+    pub fn list_by_target(&self, target_library_id: i64) -> Result<Vec<CopyRecord>> {
+        self.list("target_library_id", target_library_id)
+    }
+
+    /// Copies from the library into others, newest first
+    pub fn list_by_source(&self, source_library_id: i64) -> Result<Vec<CopyRecord>> {
+        self.list("source_library_id", source_library_id)
+    }
+
+    fn list(&self, column: &str, library_id: i64) -> Result<Vec<CopyRecord>> {
+        let sql = format!(
+            "SELECT id, target_library_id, source_library_id, what, copied_at FROM library_provenance
+             WHERE {} = ?1 ORDER BY copied_at DESC, id DESC",
+            column
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![library_id], map_row)?;
+        let mut out = Vec::new();
+        for row in rows {
+            out.extend(row?);
+        }
+        Ok(out)
+    }
+
+    /// Other libraries that copied lists from this one, by name. Used to warn
+    /// before the library is deleted.
+    pub fn copy_targets(&self, source_library_id: i64) -> Result<Vec<CopyTarget>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.target_library_id, l.name, p.what FROM library_provenance p
+             JOIN libraries l ON l.id = p.target_library_id
+             WHERE p.source_library_id = ?1 AND p.target_library_id != ?1
+             ORDER BY l.name COLLATE NOCASE, p.target_library_id",
+        )?;
+        let rows = stmt.query_map(params![source_library_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?;
+        // Rows of one target are adjacent thanks to the ORDER BY
+        let mut targets: Vec<CopyTarget> = Vec::new();
+        for row in rows {
+            let (library_id, library_name, code) = row?;
+            if targets.last().map(|t| t.library_id) != Some(library_id) {
+                targets.push(CopyTarget {
+                    library_id,
+                    library_name,
+                    copied: Vec::new(),
+                });
+            }
+            let Some(target) = targets.last_mut() else {
+                continue;
+            };
+            if let Some(what) = CopiedData::from_code(&code) {
+                if !target.copied.contains(&what) {
+                    target.copied.push(what);
+                }
+            }
+        }
+        for target in &mut targets {
+            target.copied.sort();
+        }
+        Ok(targets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::db::repositories::LibraryRepo;
+    use crate::models::Library;
+
+    fn library(db: &Database, name: &str) -> i64 {
+        let mut library = Library::new(name.to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        library.id.unwrap()
+    }
+
+    #[test]
+    fn test_record_and_list() {
+        let db = Database::open_in_memory().unwrap();
+        let master = library(&db, "Master");
+        let target = library(&db, "Target");
+        let repo = ProvenanceRepo::new(db.conn());
+        let record = repo.record(target, master, CopiedData::Branches).unwrap();
+        assert!(record.id.is_some());
+
+        assert_eq!(repo.list_by_target(target).unwrap(), vec![record.clone()]);
+        assert_eq!(repo.list_by_source(master).unwrap(), vec![record]);
+        assert!(repo.list_by_source(target).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_copy_targets_groups_by_library() {
+        let db = Database::open_in_memory().unwrap();
+        let master = library(&db, "Master");
+        let b = library(&db, "b library");
+        let a = library(&db, "A library");
+        let repo = ProvenanceRepo::new(db.conn());
+        repo.record(b, master, CopiedData::FormationLevels).unwrap();
+        repo.record(b, master, CopiedData::Branches).unwrap();
+        repo.record(b, master, CopiedData::Branches).unwrap();
+        repo.record(a, master, CopiedData::EquipmentCatalog).unwrap();
+        repo.record(master, b, CopiedData::BranchCategories).unwrap();
+
+        let targets = repo.copy_targets(master).unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                CopyTarget {
+                    library_id: a,
+                    library_name: "A library".to_string(),
+                    copied: vec![CopiedData::EquipmentCatalog],
+                },
+                CopyTarget {
+                    library_id: b,
+                    library_name: "b library".to_string(),
+                    copied: vec![CopiedData::Branches, CopiedData::FormationLevels],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deleting_either_library_drops_records() {
+        let db = Database::open_in_memory().unwrap();
+        let master = library(&db, "Master");
+        let target = library(&db, "Target");
+        let other = library(&db, "Other");
+        let repo = ProvenanceRepo::new(db.conn());
+        repo.record(target, master, CopiedData::Branches).unwrap();
+        repo.record(other, master, CopiedData::Branches).unwrap();
+
+        LibraryRepo::new(db.conn()).delete(other).unwrap();
+        assert_eq!(repo.copy_targets(master).unwrap().len(), 1);
+        LibraryRepo::new(db.conn()).delete(master).unwrap();
+        assert!(repo.list_by_target(target).unwrap().is_empty());
+    }
+}
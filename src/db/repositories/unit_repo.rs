@@ -1,8 +1,36 @@
 //! Repository for Unit operations
 
 use anyhow::Result;
-use rusqlite::{params, Connection};
-use crate::models::{Unit, Equipment, Personnel};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use chrono::NaiveDate;
+use crate::models::{assemble_forest, compute_prefixes, is_effective_at, NumberingNode, NumberingOptions, Unit, Equipment, Personnel};
+
+/// Unit row positioned in the formation tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitTreeNode {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    pub name: String,
+    pub unit_type: String,
+    /// 0 for roots
+    pub depth: i32,
+    pub effective_from: Option<String>,
+    pub effective_to: Option<String>,
+    pub toe_number: Option<String>,
+    /// Position among siblings; gaps are allowed
+    pub sort_order: i64,
+    pub formation_level_id: Option<i64>,
+}
+
+/// Row of the formations sidebar
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeRow {
+    Unit(UnitTreeNode),
+    /// Stands for the `hidden` last subordinates of `parent_id` (top-level
+    /// units when `None`), with their subtrees
+    More { parent_id: Option<i64>, depth: i32, hidden: usize },
+}
 
 /// Repository for unit database operations
 pub struct UnitRepo<'a> {
@@ -15,12 +43,26 @@ impl<'a> UnitRepo<'a> {
         Self { conn }
     }
 
-    /// Create a new unit
+    /// Create a new unit, placed after its existing siblings
     pub fn create(&self, library_id: i64, unit: &mut Unit) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO units (library_id, name, unit_type, parent_id)
-             VALUES (?1, ?2, ?3, ?4)",
-            params![library_id, unit.name, unit.unit_type, unit.parent_id],
+            "INSERT INTO units (library_id, name, unit_type, parent_id, effective_from, effective_to, toe_number, sort_order,
+                                formation_level_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7,
+                     (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM units
+                      WHERE library_id = ?1 AND parent_id IS ?4),
+                     ?8, ?9, ?9)",
+            params![
+                library_id,
+                unit.name,
+                unit.unit_type,
+                unit.parent_id,
+                unit.effective_from,
+                unit.effective_to,
+                unit.toe_number,
+                unit.formation_level_id,
+                chrono::Utc::now().timestamp()
+            ],
         )?;
         unit.id = Some(self.conn.last_insert_rowid());
         
@@ -37,11 +79,27 @@ impl<'a> UnitRepo<'a> {
         Ok(())
     }
 
+    /// Create a unit and all of its children, linking each child to its parent
+    pub fn create_tree(&self, library_id: i64, unit: &mut Unit) -> Result<()> {
+        // Parents before children and siblings in order, so each unit is
+        // placed after the siblings before it
+        let mut pending = vec![unit];
+        while let Some(unit) = pending.pop() {
+            self.create(library_id, unit)?;
+            let id = unit.id;
+            for child in unit.children.iter_mut().rev() {
+                child.parent_id = id;
+                pending.push(child);
+            }
+        }
+        Ok(())
+    }
+
     /// Create personnel entry
     pub fn create_personnel(&self, unit_id: i64, personnel: &mut Personnel) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO personnel (unit_id, position, rank) VALUES (?1, ?2, ?3)",
-            params![unit_id, personnel.position, personnel.rank],
+            "INSERT INTO personnel (unit_id, position, rank, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![unit_id, personnel.position, personnel.rank, chrono::Utc::now().timestamp()],
         )?;
         Ok(())
     }
@@ -49,8 +107,63 @@ impl<'a> UnitRepo<'a> {
     /// Create equipment entry
     pub fn create_equipment(&self, unit_id: i64, equipment: &Equipment) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO equipment (unit_id, name, quantity) VALUES (?1, ?2, ?3)",
-            params![unit_id, equipment.name, equipment.quantity],
+            "INSERT INTO equipment (unit_id, name, quantity, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![unit_id, equipment.name, equipment.quantity, chrono::Utc::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// Personnel of a unit with their row ids, in id order
+    pub fn list_personnel_rows(&self, unit_id: i64) -> Result<Vec<(i64, Personnel)>> {
+        let mut stmt = self.conn.prepare("SELECT id, position, rank FROM personnel WHERE unit_id = ?1 ORDER BY id")?;
+        let rows = stmt.query_map(params![unit_id], |row| {
+            Ok((row.get(0)?, Personnel { position: row.get(1)?, rank: row.get(2)? }))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Equipment of a unit with their row ids, in id order
+    pub fn list_equipment_rows(&self, unit_id: i64) -> Result<Vec<(i64, Equipment)>> {
+        let mut stmt = self.conn.prepare("SELECT id, name, quantity FROM equipment WHERE unit_id = ?1 ORDER BY id")?;
+        let rows = stmt.query_map(params![unit_id], |row| {
+            Ok((row.get(0)?, Equipment { name: row.get(1)?, quantity: row.get(2)? }))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Overwrite one personnel row
+    pub fn update_personnel(&self, id: i64, personnel: &Personnel) -> Result<()> {
+        self.conn.execute(
+            "UPDATE personnel SET position = ?1, rank = ?2, updated_at = ?3 WHERE id = ?4",
+            params![personnel.position, personnel.rank, chrono::Utc::now().timestamp(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Overwrite one equipment row
+    pub fn update_equipment(&self, id: i64, equipment: &Equipment) -> Result<()> {
+        self.conn.execute(
+            "UPDATE equipment SET name = ?1, quantity = ?2, updated_at = ?3 WHERE id = ?4",
+            params![equipment.name, equipment.quantity, chrono::Utc::now().timestamp(), id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_personnel(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM personnel WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn delete_equipment(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM equipment WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Mark a unit as modified now
+    pub fn touch(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE units SET updated_at = ?1 WHERE id = ?2",
+            params![chrono::Utc::now().timestamp(), id],
         )?;
         Ok(())
     }
@@ -58,7 +171,8 @@ impl<'a> UnitRepo<'a> {
     /// Get unit by ID with all related data
     pub fn get_by_id(&self, id: i64) -> Result<Option<Unit>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, unit_type, parent_id FROM units WHERE id = ?1"
+            "SELECT id, name, unit_type, parent_id, effective_from, effective_to, toe_number, formation_level_id
+             FROM units WHERE id = ?1"
         )?;
         
         let mut rows = stmt.query_map(params![id], |row| {
@@ -70,6 +184,10 @@ impl<'a> UnitRepo<'a> {
                 personnel: Vec::new(), // Loaded separately
                 equipment: Vec::new(),  // Loaded separately
                 children: Vec::new(),  // Loaded separately
+                effective_from: row.get(4)?,
+                effective_to: row.get(5)?,
+                toe_number: row.get(6)?,
+                formation_level_id: row.get(7)?,
             })
         })?;
 
@@ -129,39 +247,52 @@ impl<'a> UnitRepo<'a> {
         Ok(equipment)
     }
 
-    /// Load child units
+    /// Load child units and everything below them
     fn load_children(&self, parent_id: i64) -> Result<Vec<Unit>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, unit_type, parent_id FROM units WHERE parent_id = ?1"
+            "SELECT id, name, unit_type, parent_id, effective_from, effective_to, toe_number, formation_level_id
+             FROM units WHERE parent_id = ?1 ORDER BY sort_order, id"
         )?;
-        
-        let rows = stmt.query_map(params![parent_id], |row| {
-            Ok(Unit {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                unit_type: row.get(2)?,
-                parent_id: Some(row.get(3)?),
-                personnel: Vec::new(),
-                equipment: Vec::new(),
-                children: Vec::new(),
-            })
-        })?;
 
-        let mut children = Vec::new();
-        for mut child in rows.flatten() {
-            let child_id = child.id.unwrap();
-            child.personnel = self.load_personnel(child_id)?;
-            child.equipment = self.load_equipment(child_id)?;
-            child.children = self.load_children(child_id)?;
-            children.push(child);
+        // Units read so far, each with the position of its parent among them
+        let mut nodes: Vec<(Unit, Option<usize>)> = Vec::new();
+        let mut pending: Vec<(i64, Option<usize>)> = vec![(parent_id, None)];
+        // A parent cycle in the file must not make the loading go round forever
+        let mut seen = HashSet::from([parent_id]);
+        while let Some((id, parent)) = pending.pop() {
+            let rows = stmt.query_map(params![id], |row| {
+                Ok(Unit {
+                    id: Some(row.get(0)?),
+                    name: row.get(1)?,
+                    unit_type: row.get(2)?,
+                    parent_id: Some(row.get(3)?),
+                    personnel: Vec::new(),
+                    equipment: Vec::new(),
+                    children: Vec::new(),
+                    effective_from: row.get(4)?,
+                    effective_to: row.get(5)?,
+                    toe_number: row.get(6)?,
+                    formation_level_id: row.get(7)?,
+                })
+            })?;
+            for mut child in rows.flatten() {
+                let child_id = child.id.unwrap();
+                if !seen.insert(child_id) {
+                    continue;
+                }
+                child.personnel = self.load_personnel(child_id)?;
+                child.equipment = self.load_equipment(child_id)?;
+                pending.push((child_id, Some(nodes.len())));
+                nodes.push((child, parent));
+            }
         }
-        Ok(children)
+        Ok(assemble_forest(nodes, |unit, children| unit.children = children))
     }
 
     /// Get all units for a library
     pub fn get_by_library_id(&self, library_id: i64) -> Result<Vec<Unit>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id FROM units WHERE library_id = ?1 AND parent_id IS NULL"
+            "SELECT id FROM units WHERE library_id = ?1 AND parent_id IS NULL ORDER BY sort_order, id"
         )?;
         
         let rows = stmt.query_map(params![library_id], |row| {
@@ -176,6 +307,355 @@ impl<'a> UnitRepo<'a> {
         }
         Ok(units)
     }
+
+    /// All units of a library in depth-first order with their depth.
+    ///
+    /// Units whose parent is missing are shown as roots. Units caught in a
+    /// parent_id cycle are still listed once each; the cycle is broken at the
+    /// lowest id.
+    pub fn list_tree_by_library(&self, library_id: i64) -> Result<Vec<UnitTreeNode>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, parent_id, name, unit_type, effective_from, effective_to, toe_number, sort_order, formation_level_id
+             FROM units WHERE library_id = ?1 ORDER BY sort_order, id"
+        )?;
+        let rows = stmt.query_map(params![library_id], |row| {
+            Ok(UnitTreeNode {
+                id: row.get(0)?,
+                parent_id: row.get(1)?,
+                name: row.get(2)?,
+                unit_type: row.get(3)?,
+                depth: 0,
+                effective_from: row.get(4)?,
+                effective_to: row.get(5)?,
+                toe_number: row.get(6)?,
+                sort_order: row.get(7)?,
+                formation_level_id: row.get(8)?,
+            })
+        })?;
+        let mut units = Vec::new();
+        for row in rows {
+            units.push(row?);
+        }
+        Ok(order_depth_first(units))
+    }
+
+    /// Personnel positions and equipment quantity of every unit of the
+    /// library, each with everything below it, keyed by unit id. One query:
+    /// a recursive CTE pairs each unit with its descendants (itself
+    /// included) and sums their rows. Units caught in a parent_id cycle
+    /// count each unit of the cycle once.
+    pub fn subtree_counts(&self, library_id: i64) -> Result<HashMap<i64, (i64, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "WITH RECURSIVE
+                own(id, personnel, equipment) AS (
+                    SELECT u.id,
+                           (SELECT COUNT(*) FROM personnel p WHERE p.unit_id = u.id),
+                           (SELECT COALESCE(SUM(e.quantity), 0) FROM equipment e WHERE e.unit_id = u.id)
+                    FROM units u WHERE u.library_id = ?1
+                ),
+                below(ancestor, descendant) AS (
+                    SELECT id, id FROM units WHERE library_id = ?1
+                    UNION
+                    SELECT below.ancestor, u.id FROM below
+                    JOIN units u ON u.parent_id = below.descendant AND u.library_id = ?1
+                )
+             SELECT below.ancestor, SUM(own.personnel), SUM(own.equipment)
+             FROM below JOIN own ON own.id = below.descendant
+             GROUP BY below.ancestor"
+        )?;
+        let rows = stmt.query_map(params![library_id], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?;
+        let mut counts = HashMap::new();
+        for row in rows {
+            let (id, totals) = row?;
+            counts.insert(id, totals);
+        }
+        Ok(counts)
+    }
+
+    /// Library and parent of a unit, if it exists
+    pub fn get_placement(&self, id: i64) -> Result<Option<(i64, Option<i64>)>> {
+        let mut stmt = self.conn.prepare("SELECT library_id, parent_id FROM units WHERE id = ?1")?;
+        let mut rows = stmt.query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Ids of the units sharing a parent (roots when `parent_id` is None), in sibling order
+    pub fn list_sibling_ids(&self, library_id: i64, parent_id: Option<i64>) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id FROM units WHERE library_id = ?1 AND parent_id IS ?2 ORDER BY sort_order, id"
+        )?;
+        let rows = stmt.query_map(params![library_id, parent_id], |row| row.get(0))?;
+        let mut ids = Vec::new();
+        for row in rows {
+            ids.push(row?);
+        }
+        Ok(ids)
+    }
+
+    /// Id, name and TOE number of the units of a library that have one
+    pub fn list_toe_numbers(&self, library_id: i64) -> Result<Vec<(i64, String, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, toe_number FROM units
+             WHERE library_id = ?1 AND toe_number IS NOT NULL ORDER BY sort_order, id"
+        )?;
+        let rows = stmt.query_map(params![library_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        let mut numbers = Vec::new();
+        for row in rows {
+            numbers.push(row?);
+        }
+        Ok(numbers)
+    }
+
+    /// Set a unit's name, type, effective period and TOE number
+    pub fn update_properties(&self, unit: &Unit) -> Result<()> {
+        let id = unit.id.ok_or_else(|| anyhow::anyhow!("Unit has no id"))?;
+        let changed = self.conn.execute(
+            "UPDATE units SET name = ?1, unit_type = ?2, effective_from = ?3, effective_to = ?4, toe_number = ?5,
+                              updated_at = ?6
+             WHERE id = ?7",
+            params![
+                unit.name,
+                unit.unit_type,
+                unit.effective_from,
+                unit.effective_to,
+                unit.toe_number,
+                chrono::Utc::now().timestamp(),
+                id
+            ],
+        )?;
+        if changed == 0 {
+            anyhow::bail!("Unit {} not found", id);
+        }
+        Ok(())
+    }
+
+    /// Set (or clear) the formation level a unit belongs to
+    pub fn set_formation_level(&self, id: i64, level_id: Option<i64>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE units SET formation_level_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![level_id, chrono::Utc::now().timestamp(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Formation level of a unit, if one is set
+    pub fn formation_level(&self, id: i64) -> Result<Option<i64>> {
+        let level = self
+            .conn
+            .query_row("SELECT formation_level_id FROM units WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        Ok(level.flatten())
+    }
+
+    /// Number of units of a library at each formation level, by level id
+    pub fn count_by_formation_level(&self, library_id: i64) -> Result<HashMap<i64, usize>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT formation_level_id, COUNT(*) FROM units
+             WHERE library_id = ?1 AND formation_level_id IS NOT NULL
+             GROUP BY formation_level_id"
+        )?;
+        let rows = stmt.query_map(params![library_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+        let mut counts = HashMap::new();
+        for row in rows {
+            let (level_id, count) = row?;
+            counts.insert(level_id, count as usize);
+        }
+        Ok(counts)
+    }
+
+    /// Move every unit of a library at level `from` to level `to` (or to no
+    /// level); returns the number of units changed
+    pub fn reassign_formation_level(&self, library_id: i64, from: i64, to: Option<i64>) -> Result<usize> {
+        let changed = self.conn.execute(
+            "UPDATE units SET formation_level_id = ?1 WHERE library_id = ?2 AND formation_level_id = ?3",
+            params![to, library_id, from],
+        )?;
+        Ok(changed)
+    }
+
+    /// Id and unit type of the units of a library that have a type but no formation level yet
+    pub fn list_without_level(&self, library_id: i64) -> Result<Vec<(i64, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, unit_type FROM units
+             WHERE library_id = ?1 AND formation_level_id IS NULL AND TRIM(unit_type) <> ''
+             ORDER BY sort_order, id"
+        )?;
+        let rows = stmt.query_map(params![library_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut units = Vec::new();
+        for row in rows {
+            units.push(row?);
+        }
+        Ok(units)
+    }
+
+    /// Place a unit under `parent_id` (top level when `None`) at `sort_order`
+    pub fn set_parent(&self, id: i64, parent_id: Option<i64>, sort_order: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE units SET parent_id = ?1, sort_order = ?2, updated_at = ?3 WHERE id = ?4",
+            params![parent_id, sort_order, chrono::Utc::now().timestamp(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Set a unit's position among its siblings
+    pub fn set_sort_order(&self, id: i64, sort_order: i64) -> Result<()> {
+        self.conn.execute("UPDATE units SET sort_order = ?1 WHERE id = ?2", params![sort_order, id])?;
+        Ok(())
+    }
+
+    /// Number of units in the subtree rooted at `id`, the unit itself included
+    pub fn count_subtree(&self, id: i64) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "WITH RECURSIVE subtree(id) AS (
+                 SELECT id FROM units WHERE id = ?1
+                 UNION
+                 SELECT u.id FROM units u JOIN subtree s ON u.parent_id = s.id
+             )
+             SELECT COUNT(*) FROM subtree",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Levels from the top of the tree down to the unit, the unit itself
+    /// included; 0 when it does not exist
+    pub fn count_levels(&self, id: i64) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "WITH RECURSIVE ancestors(id, parent_id) AS (
+                 SELECT id, parent_id FROM units WHERE id = ?1
+                 UNION
+                 SELECT u.id, u.parent_id FROM units u JOIN ancestors a ON u.id = a.parent_id
+             )
+             SELECT COUNT(*) FROM ancestors",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Delete a unit; subordinates, personnel and equipment go with it (ON DELETE CASCADE)
+    pub fn delete(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM units WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Delete every unit of a library
+    pub fn delete_by_library(&self, library_id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM units WHERE library_id = ?1", params![library_id])?;
+        Ok(())
+    }
+}
+
+/// Nodes of a depth-first tree valid at `as_of`; a node that is left out
+/// takes its subtree with it
+pub fn tree_as_of(nodes: Vec<UnitTreeNode>, as_of: NaiveDate) -> Vec<UnitTreeNode> {
+    let mut hidden_below: Option<i32> = None;
+    nodes
+        .into_iter()
+        .filter(|n| {
+            if hidden_below.is_some_and(|depth| n.depth > depth) {
+                return false;
+            }
+            hidden_below = None;
+            let shown = is_effective_at(n.effective_from.as_deref(), n.effective_to.as_deref(), as_of);
+            if !shown {
+                hidden_below = Some(n.depth);
+            }
+            shown
+        })
+        .collect()
+}
+
+/// Numbering prefixes of a depth-first tree keyed by unit id, siblings
+/// counted in `sort_order`
+pub fn tree_prefixes(nodes: &[UnitTreeNode], options: &NumberingOptions) -> HashMap<i64, String> {
+    let numbering_nodes: Vec<NumberingNode> = nodes
+        .iter()
+        .map(|n| NumberingNode {
+            id: n.id,
+            // Orphans and cycle breaks are shown as roots
+            parent_id: if n.depth == 0 { None } else { n.parent_id },
+            sort_order: Some(n.sort_order),
+            level_id: n.formation_level_id,
+        })
+        .collect();
+    compute_prefixes(&numbering_nodes, options)
+}
+
+/// Rows for a depth-first tree listing at most `shown` units under one
+/// parent. The rest of a larger group folds into one [`TreeRow::More`] row,
+/// unless the group's parent (`None` for the top level) is in `expanded`.
+pub fn collapse_wide_groups(nodes: Vec<UnitTreeNode>, shown: usize, expanded: &HashSet<Option<i64>>) -> Vec<TreeRow> {
+    // Orphans and cycle breaks are shown as roots
+    let group = |n: &UnitTreeNode| if n.depth == 0 { None } else { n.parent_id };
+    let mut sizes: HashMap<Option<i64>, usize> = HashMap::new();
+    for node in &nodes {
+        *sizes.entry(group(node)).or_default() += 1;
+    }
+
+    let mut listed: HashMap<Option<i64>, usize> = HashMap::new();
+    let mut hidden_below: Option<i32> = None;
+    let mut rows = Vec::new();
+    for node in nodes {
+        if hidden_below.is_some_and(|depth| node.depth > depth) {
+            continue;
+        }
+        hidden_below = None;
+        let key = group(&node);
+        let size = sizes[&key];
+        let position = listed.entry(key).or_default();
+        *position += 1;
+        if size > shown && *position > shown && !expanded.contains(&key) {
+            if *position == shown + 1 {
+                rows.push(TreeRow::More { parent_id: key, depth: node.depth, hidden: size - shown });
+            }
+            hidden_below = Some(node.depth);
+            continue;
+        }
+        rows.push(TreeRow::Unit(node));
+    }
+    rows
+}
+
+/// Order units depth-first (siblings in input order) and fill in their depth.
+fn order_depth_first(units: Vec<UnitTreeNode>) -> Vec<UnitTreeNode> {
+    let ids: HashSet<i64> = units.iter().map(|u| u.id).collect();
+    let mut children: HashMap<i64, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (i, unit) in units.iter().enumerate() {
+        match unit.parent_id {
+            Some(parent) if parent != unit.id && ids.contains(&parent) => {
+                children.entry(parent).or_default().push(i)
+            }
+            _ => roots.push(i),
+        }
+    }
+
+    let mut visited = vec![false; units.len()];
+    let mut ordered = Vec::with_capacity(units.len());
+    let walk = |start: usize, ordered: &mut Vec<UnitTreeNode>, visited: &mut [bool]| {
+        let mut stack = vec![(start, 0)];
+        while let Some((i, depth)) = stack.pop() {
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            ordered.push(UnitTreeNode { depth, ..units[i].clone() });
+            if let Some(kids) = children.get(&units[i].id) {
+                stack.extend(kids.iter().rev().map(|&k| (k, depth + 1)));
+            }
+        }
+    };
+    for &root in &roots {
+        walk(root, &mut ordered, &mut visited);
+    }
+    // Whatever is left is only reachable through a cycle
+    while let Some(start) = visited.iter().position(|v| !v) {
+        log::warn!("parent_id cycle detected at unit {}", units[start].id);
+        walk(start, &mut ordered, &mut visited);
+    }
+    ordered
 }
 
 #[cfg(test)]
@@ -183,6 +663,7 @@ mod tests {
     use super::*;
     use crate::db::Database;
     use crate::db::repositories::LibraryRepo;
+    use crate::models::tree::unit_chain;
     use crate::models::Library;
 
     #[test]
@@ -202,4 +683,213 @@ mod tests {
         repo.create(library.id.unwrap(), &mut unit).unwrap();
         assert!(unit.id.is_some());
     }
+
+    #[test]
+    fn test_effective_dates_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Test".to_string(), "SU".to_string(), "1943".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let repo = UnitRepo::new(db.conn());
+        let mut unit = Unit::new("Regiment".to_string(), "Regiment".to_string());
+        unit.effective_from = Some("1943-01-01".to_string());
+        let mut child = Unit::new("Battalion".to_string(), "Battalion".to_string());
+        child.effective_to = Some("1943-07-12".to_string());
+        unit.add_child(child);
+        repo.create_tree(library.id.unwrap(), &mut unit).unwrap();
+
+        let mut loaded = repo.get_by_id(unit.id.unwrap()).unwrap().unwrap();
+        assert_eq!(loaded.effective_from.as_deref(), Some("1943-01-01"));
+        assert_eq!(loaded.children[0].effective_to.as_deref(), Some("1943-07-12"));
+        let tree = repo.list_tree_by_library(library.id.unwrap()).unwrap();
+        assert_eq!(tree[1].effective_to.as_deref(), Some("1943-07-12"));
+
+        loaded.effective_from = None;
+        loaded.effective_to = Some("1944-12-31".to_string());
+        repo.update_properties(&loaded).unwrap();
+        let updated = repo.get_by_id(unit.id.unwrap()).unwrap().unwrap();
+        assert_eq!((updated.effective_from, updated.effective_to.as_deref()), (None, Some("1944-12-31")));
+    }
+
+    #[test]
+    fn test_tree_prefixes_follow_sort_order() {
+        use crate::db::repositories::FormationLevelRepo;
+        use crate::models::{CustomFormationLevel, NumberingStyle};
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Test".to_string(), "SU".to_string(), "1943".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let library_id = library.id.unwrap();
+        let mut battalion = CustomFormationLevel::new(library_id, "батальон".to_string(), "battalion".to_string(), 5);
+        FormationLevelRepo::new(db.conn()).create(&mut battalion).unwrap();
+        let repo = UnitRepo::new(db.conn());
+        let mut regiment = Unit::new("Regiment".to_string(), "Regiment".to_string());
+        let mut first = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+        first.formation_level_id = battalion.id;
+        regiment.add_child(first);
+        regiment.add_child(Unit::new("2nd Battalion".to_string(), "Battalion".to_string()));
+        repo.create_tree(library_id, &mut regiment).unwrap();
+        let first = regiment.children[0].id.unwrap();
+        let second = regiment.children[1].id.unwrap();
+        repo.set_sort_order(first, 10).unwrap();
+
+        let mut options = NumberingOptions::with_style(NumberingStyle::Arabic);
+        options.set_override(battalion.id.unwrap(), NumberingStyle::Roman);
+        let prefixes = tree_prefixes(&repo.list_tree_by_library(library_id).unwrap(), &options);
+        assert_eq!(prefixes[&regiment.id.unwrap()], "1. ");
+        assert_eq!(prefixes[&second], "1. ");
+        assert_eq!(prefixes[&first], "II. ");
+    }
+
+    #[test]
+    fn test_toe_number_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let library_id = library.id.unwrap();
+        let repo = UnitRepo::new(db.conn());
+        let mut unit = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+        unit.toe_number = Some("TOE 07015L000".to_string());
+        unit.add_child(Unit::new("A Company".to_string(), "Company".to_string()));
+        repo.create_tree(library_id, &mut unit).unwrap();
+
+        let mut loaded = repo.get_by_id(unit.id.unwrap()).unwrap().unwrap();
+        assert_eq!(loaded.toe_number.as_deref(), Some("TOE 07015L000"));
+        assert_eq!(repo.list_tree_by_library(library_id).unwrap()[0].toe_number.as_deref(), Some("TOE 07015L000"));
+        assert_eq!(
+            repo.list_toe_numbers(library_id).unwrap(),
+            vec![(unit.id.unwrap(), "1st Battalion".to_string(), "TOE 07015L000".to_string())]
+        );
+
+        loaded.toe_number = None;
+        repo.update_properties(&loaded).unwrap();
+        assert!(repo.list_toe_numbers(library_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_writes_set_timestamps() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let repo = UnitRepo::new(db.conn());
+        let mut unit = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+        unit.equipment.push(Equipment { name: "M1A2".to_string(), quantity: 44 });
+        repo.create(library.id.unwrap(), &mut unit).unwrap();
+        let id = unit.id.unwrap();
+        let times = |table: &str, column: &str| -> (i64, i64) {
+            db.conn()
+                .query_row(
+                    &format!("SELECT created_at, updated_at FROM {} WHERE {} = ?1", table, column),
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap()
+        };
+        let (created, updated) = times("units", "id");
+        assert!(created > 0);
+        assert_eq!(created, updated);
+        assert!(times("equipment", "unit_id").0 >= created);
+
+        db.conn().execute("UPDATE units SET created_at = 1, updated_at = 1 WHERE id = ?1", params![id]).unwrap();
+        unit.name = "2nd Battalion".to_string();
+        repo.update_properties(&unit).unwrap();
+        let (created, updated) = times("units", "id");
+        assert_eq!(created, 1);
+        assert!(updated > 1);
+    }
+
+    fn node(id: i64, parent_id: Option<i64>) -> UnitTreeNode {
+        UnitTreeNode {
+            id,
+            parent_id,
+            name: format!("U{}", id),
+            unit_type: String::new(),
+            depth: 0,
+            effective_from: None,
+            effective_to: None,
+            toe_number: None,
+            sort_order: 0,
+            formation_level_id: None,
+        }
+    }
+
+    #[test]
+    fn test_tree_as_of_hides_subtrees() {
+        let as_of = NaiveDate::from_ymd_opt(1943, 7, 5).unwrap();
+        let mut gone = node(2, Some(1));
+        gone.effective_to = Some("1943-07-04".to_string());
+        let mut later = node(5, None);
+        later.effective_from = Some("1943-07-05".to_string());
+        let nodes = order_depth_first(vec![node(1, None), gone, node(3, Some(2)), node(4, Some(1)), later]);
+
+        let ids: Vec<i64> = tree_as_of(nodes, as_of).iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![1, 4, 5]);
+    }
+
+    #[test]
+    fn test_order_depth_first_breaks_cycles() {
+        // 1 <-> 2 form a cycle, 3 hangs off 2, 4 points at itself
+        let ordered = order_depth_first(vec![
+            node(1, Some(2)),
+            node(2, Some(1)),
+            node(3, Some(2)),
+            node(4, Some(4)),
+        ]);
+        let got: Vec<(i64, i32)> = ordered.iter().map(|n| (n.id, n.depth)).collect();
+        assert_eq!(got, vec![(4, 0), (1, 0), (2, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn test_collapse_wide_groups() {
+        // Root 1 with children 2..=6, child 2 with children 7 and 8; root 9
+        let mut units = vec![node(1, None)];
+        units.extend((2..=6).map(|id| node(id, Some(1))));
+        units.extend([node(7, Some(2)), node(8, Some(2)), node(9, None)]);
+        let nodes = order_depth_first(units);
+        let ids = |rows: &[TreeRow]| -> Vec<String> {
+            rows.iter()
+                .map(|row| match row {
+                    TreeRow::Unit(n) => n.id.to_string(),
+                    TreeRow::More { parent_id, hidden, .. } => format!("+{}:{:?}", hidden, parent_id),
+                })
+                .collect()
+        };
+
+        let rows = collapse_wide_groups(nodes.clone(), 2, &HashSet::new());
+        assert_eq!(ids(&rows), ["1", "2", "7", "8", "3", "+3:Some(1)", "9"]);
+        assert!(matches!(rows[5], TreeRow::More { depth: 1, .. }));
+        // A group one over the limit folds too; an expanded group is listed in full
+        let rows = collapse_wide_groups(nodes.clone(), 1, &HashSet::from([Some(1)]));
+        assert_eq!(ids(&rows), ["1", "2", "7", "+1:Some(2)", "3", "4", "5", "6", "+1:None"]);
+        let all = collapse_wide_groups(nodes.clone(), 5, &HashSet::new());
+        assert_eq!(all.len(), nodes.len());
+    }
+
+    #[test]
+    fn test_collapse_wide_groups_on_pathological_trees() {
+        let mut units = vec![node(1, None)];
+        units.extend((2..=5001).map(|id| node(id, Some(1))));
+        let rows = collapse_wide_groups(order_depth_first(units), 50, &HashSet::new());
+        assert_eq!(rows.len(), 52);
+        assert_eq!(rows[51], TreeRow::More { parent_id: Some(1), depth: 1, hidden: 4950 });
+
+        let chain: Vec<UnitTreeNode> = (1..=5000).map(|id| node(id, (id > 1).then(|| id - 1))).collect();
+        let rows = collapse_wide_groups(order_depth_first(chain), 50, &HashSet::new());
+        assert_eq!(rows.len(), 5000);
+        assert!(matches!(&rows[4999], TreeRow::Unit(n) if n.depth == 4999));
+    }
+
+    #[test]
+    fn test_deep_tree_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Deep".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let mut top = unit_chain(500).remove(0);
+        let repo = UnitRepo::new(db.conn());
+        repo.create_tree(library.id.unwrap(), &mut top).unwrap();
+
+        let loaded = repo.get_by_id(top.id.unwrap()).unwrap().unwrap();
+        let deepest = crate::models::walk_units(std::slice::from_ref(&loaded)).last().unwrap();
+        assert_eq!((deepest.depth, deepest.unit.name.as_str()), (499, "U499"));
+        assert_eq!(repo.count_levels(deepest.unit.id.unwrap()).unwrap(), 500);
+        assert_eq!(repo.count_levels(-1).unwrap(), 0);
+    }
 }
@@ -1,7 +1,7 @@
 //! Repository for Version/Snapshot operations
 
 use anyhow::Result;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension, Row};
 use crate::models::Snapshot;
 
 /// Repository for snapshot database operations
@@ -18,73 +18,120 @@ impl<'a> VersionRepo<'a> {
     /// Create a new snapshot
     pub fn create(&self, snapshot: &mut Snapshot) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO snapshots (library_id, version, timestamp, data, description)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO snapshots (library_id, version, timestamp, data, description, is_autosave)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
                 snapshot.library_id,
                 snapshot.version,
                 snapshot.timestamp,
                 snapshot.data,
-                snapshot.description
+                snapshot.description,
+                snapshot.is_autosave
             ],
         )?;
         snapshot.id = Some(self.conn.last_insert_rowid());
         Ok(())
     }
 
-    /// Get latest snapshot for a library
-    pub fn get_latest(&self, library_id: i64) -> Result<Option<Snapshot>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, version, timestamp, data, description
+    fn row_to_snapshot(row: &Row) -> rusqlite::Result<Snapshot> {
+        Ok(Snapshot {
+            id: Some(row.get(0)?),
+            library_id: row.get(1)?,
+            version: row.get(2)?,
+            timestamp: row.get(3)?,
+            data: row.get(4)?,
+            description: row.get(5)?,
+            is_autosave: row.get(6)?,
+        })
+    }
+
+    /// Snapshots of a library matching `filter`, in `order`
+    fn query(&self, library_id: i64, filter: &str, order: &str) -> Result<Vec<Snapshot>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, library_id, version, timestamp, data, description, is_autosave
              FROM snapshots
-             WHERE library_id = ?1
-             ORDER BY version DESC
-             LIMIT 1"
+             WHERE library_id = ?1 AND {}
+             ORDER BY {}",
+            filter, order
+        ))?;
+        let rows = stmt.query_map(params![library_id], Self::row_to_snapshot)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Get latest snapshot for a library (autosaves aside)
+    pub fn get_latest(&self, library_id: i64) -> Result<Option<Snapshot>> {
+        Ok(self.query(library_id, "is_autosave = 0", "version DESC LIMIT 1")?.pop())
+    }
+
+    /// One snapshot by id
+    pub fn get_by_id(&self, snapshot_id: i64) -> Result<Option<Snapshot>> {
+        let snapshot = self
+            .conn
+            .query_row(
+                "SELECT id, library_id, version, timestamp, data, description, is_autosave
+                 FROM snapshots WHERE id = ?1",
+                params![snapshot_id],
+                Self::row_to_snapshot,
+            )
+            .optional()?;
+        Ok(snapshot)
+    }
+
+    /// The snapshot of one version of a library (autosaves aside)
+    pub fn get_by_version(&self, library_id: i64, version: i64) -> Result<Option<Snapshot>> {
+        let snapshot = self
+            .conn
+            .query_row(
+                "SELECT id, library_id, version, timestamp, data, description, is_autosave
+                 FROM snapshots WHERE library_id = ?1 AND version = ?2 AND is_autosave = 0",
+                params![library_id, version],
+                Self::row_to_snapshot,
+            )
+            .optional()?;
+        Ok(snapshot)
+    }
+
+    /// Highest stored version for a library, if it has any snapshots
+    pub fn latest_version(&self, library_id: i64) -> Result<Option<i64>> {
+        let version = self.conn.query_row(
+            "SELECT MAX(version) FROM snapshots WHERE library_id = ?1",
+            params![library_id],
+            |row| row.get(0),
         )?;
-        
-        let mut rows = stmt.query_map(params![library_id], |row| {
-            Ok(Snapshot {
-                id: Some(row.get(0)?),
-                library_id: row.get(1)?,
-                version: row.get(2)?,
-                timestamp: row.get(3)?,
-                data: row.get(4)?,
-                description: row.get(5)?,
-            })
-        })?;
-
-        match rows.next() {
-            Some(Ok(snapshot)) => Ok(Some(snapshot)),
-            Some(Err(e)) => Err(e.into()),
-            None => Ok(None),
-        }
-    }
-
-    /// Get all snapshots for a library
+        Ok(version)
+    }
+
+    /// Delete one snapshot
+    pub fn delete(&self, snapshot_id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM snapshots WHERE id = ?1", params![snapshot_id])?;
+        Ok(())
+    }
+
+    /// Get all snapshots for a library, newest version first; autosaves
+    /// are listed by [`Self::list_autosaves`]
     pub fn list_by_library(&self, library_id: i64) -> Result<Vec<Snapshot>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, library_id, version, timestamp, data, description
-             FROM snapshots
-             WHERE library_id = ?1
-             ORDER BY version DESC"
+        self.query(library_id, "is_autosave = 0", "version DESC")
+    }
+
+    /// Autosave snapshots of a library, newest first
+    pub fn list_autosaves(&self, library_id: i64) -> Result<Vec<Snapshot>> {
+        self.query(library_id, "is_autosave = 1", "timestamp DESC, id DESC")
+    }
+
+    /// Delete all but the newest `keep` autosaves of a library; returns how
+    /// many were deleted
+    pub fn prune_autosaves(&self, library_id: i64, keep: usize) -> Result<usize> {
+        let deleted = self.conn.execute(
+            "DELETE FROM snapshots
+             WHERE library_id = ?1 AND is_autosave = 1 AND id NOT IN (
+                 SELECT id FROM snapshots
+                 WHERE library_id = ?1 AND is_autosave = 1
+                 ORDER BY timestamp DESC, id DESC
+                 LIMIT ?2
+             )",
+            params![library_id, keep as i64],
         )?;
-        
-        let rows = stmt.query_map(params![library_id], |row| {
-            Ok(Snapshot {
-                id: Some(row.get(0)?),
-                library_id: row.get(1)?,
-                version: row.get(2)?,
-                timestamp: row.get(3)?,
-                data: row.get(4)?,
-                description: row.get(5)?,
-            })
-        })?;
-
-        let mut snapshots = Vec::new();
-        for row in rows {
-            snapshots.push(row?);
-        }
-        Ok(snapshots)
+        Ok(deleted)
     }
 }
 
@@ -113,4 +160,63 @@ mod tests {
         repo.create(&mut snapshot).unwrap();
         assert!(snapshot.id.is_some());
     }
+
+    fn library(db: &Database) -> i64 {
+        use crate::db::repositories::LibraryRepo;
+        use crate::models::Library;
+        let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        library.id.unwrap()
+    }
+
+    fn autosave(repo: &VersionRepo, lib_id: i64, version: i64, timestamp: i64) -> i64 {
+        let mut snapshot = Snapshot::with_description(lib_id, version, "{}".to_string(), "autosave".to_string());
+        snapshot.is_autosave = true;
+        snapshot.timestamp = timestamp;
+        repo.create(&mut snapshot).unwrap();
+        snapshot.id.unwrap()
+    }
+
+    #[test]
+    fn test_autosaves_share_versions_and_are_listed_apart() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let repo = VersionRepo::new(db.conn());
+        repo.create(&mut Snapshot::new(lib_id, 1, "{}".to_string())).unwrap();
+        autosave(&repo, lib_id, 1, 100);
+        autosave(&repo, lib_id, 1, 200);
+        // A version is still unique among the other snapshots
+        assert!(repo.create(&mut Snapshot::new(lib_id, 1, "{}".to_string())).is_err());
+
+        assert_eq!(repo.list_by_library(lib_id).unwrap().len(), 1);
+        assert!(!repo.get_latest(lib_id).unwrap().unwrap().is_autosave);
+        let autosaves = repo.list_autosaves(lib_id).unwrap();
+        assert_eq!(autosaves.iter().map(|s| s.timestamp).collect::<Vec<_>>(), [200, 100]);
+        assert!(autosaves.iter().all(|s| s.is_autosave && s.version == 1));
+        assert_eq!(repo.get_by_id(autosaves[0].id.unwrap()).unwrap(), Some(autosaves[0].clone()));
+        assert_eq!(repo.get_by_id(-1).unwrap(), None);
+        // Looking a version up finds the saved snapshot, not its autosaves
+        let saved = repo.get_by_version(lib_id, 1).unwrap().unwrap();
+        assert!(!saved.is_autosave);
+        assert_eq!(repo.get_by_version(lib_id, 2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_prune_autosaves_keeps_newest() {
+        let db = Database::open_in_memory().unwrap();
+        let (lib_id, other_id) = (library(&db), library(&db));
+        let repo = VersionRepo::new(db.conn());
+        repo.create(&mut Snapshot::new(lib_id, 1, "{}".to_string())).unwrap();
+        let ids: Vec<i64> = [300, 100, 500, 200, 400].iter().map(|&t| autosave(&repo, lib_id, 1, t)).collect();
+        autosave(&repo, other_id, 1, 50);
+
+        assert_eq!(repo.prune_autosaves(lib_id, 2).unwrap(), 3);
+        let kept: Vec<Option<i64>> = repo.list_autosaves(lib_id).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(kept, [Some(ids[2]), Some(ids[4])]);
+        // Versions and other libraries' autosaves are left alone
+        assert_eq!(repo.list_by_library(lib_id).unwrap().len(), 1);
+        assert_eq!(repo.list_autosaves(other_id).unwrap().len(), 1);
+        assert_eq!(repo.prune_autosaves(lib_id, 2).unwrap(), 0);
+        assert_eq!(repo.prune_autosaves(lib_id, 0).unwrap(), 2);
+    }
 }
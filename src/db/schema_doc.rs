@@ -0,0 +1,340 @@
+//! Documentation of the database schema for people querying the file with
+//! their own tools: the CREATE statements as SQL, and a Markdown description
+//! of every table and column.
+//!
+//! Column descriptions live in [`SCHEMA_DESCRIPTION`]; a test fails when a
+//! migration adds a table or column without describing it.
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+/// Description of one table and its columns
+pub struct TableDoc {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Column name and description
+    pub columns: &'static [(&'static str, &'static str)],
+}
+
+/// What each table and column holds
+pub const SCHEMA_DESCRIPTION: &[TableDoc] = &[
+    TableDoc {
+        name: "schema_version",
+        description: "Migrations applied to the file; the highest version is the schema in use.",
+        columns: &[
+            ("version", "Schema version after the migration"),
+            ("applied_at", "When the migration ran (Unix seconds)"),
+        ],
+    },
+    TableDoc {
+        name: "libraries",
+        description: "Libraries: one order of battle each, with their unit trees and settings.",
+        columns: &[
+            ("id", "Library id"),
+            ("name", "Library name"),
+            ("country", "Country as entered"),
+            ("era", "Era or year as entered"),
+            ("author", "Author"),
+            ("version", "Current version number; raised on each save"),
+            ("tags", "Tags as a JSON array of strings"),
+            ("created_at", "Creation time (Unix seconds)"),
+            ("updated_at", "Last change (Unix seconds)"),
+            ("display_language", "Language of bilingual names: 'auto', 'ru' or 'en'"),
+            ("country_code", "ISO country code resolved from `country`, if known"),
+            ("numbering", "Formation numbering options as JSON; empty when never set"),
+        ],
+    },
+    TableDoc {
+        name: "units",
+        description: "Formations and units. Each row is a node of its library's unit tree.",
+        columns: &[
+            ("id", "Unit id"),
+            ("library_id", "Library the unit belongs to"),
+            ("name", "Unit name"),
+            ("unit_type", "Unit type as entered (e.g. Battalion)"),
+            ("parent_id", "Parent unit; NULL for top-level units"),
+            ("sort_order", "Position among siblings"),
+            ("formation_level_id", "Formation level of the unit, if assigned"),
+            ("effective_from", "First day the unit is in the order of battle (YYYY-MM-DD); NULL when open"),
+            ("effective_to", "Last day the unit is in the order of battle (YYYY-MM-DD); NULL when open"),
+            ("toe_number", "Number of the official TOE document the unit follows, as entered"),
+            ("created_at", "Creation time (Unix seconds); the library's for units older than the column"),
+            ("updated_at", "Last change to the unit's own fields (Unix seconds)"),
+        ],
+    },
+    TableDoc {
+        name: "personnel",
+        description: "Positions of a unit.",
+        columns: &[
+            ("id", "Row id"),
+            ("unit_id", "Unit"),
+            ("position", "Position name"),
+            ("rank", "Rank, if given"),
+            ("created_at", "Creation time (Unix seconds); the unit's for rows older than the column"),
+            ("updated_at", "Last change (Unix seconds)"),
+        ],
+    },
+    TableDoc {
+        name: "equipment",
+        description: "Equipment of a unit.",
+        columns: &[
+            ("id", "Row id"),
+            ("unit_id", "Unit"),
+            ("name", "Equipment name"),
+            ("quantity", "Number of items"),
+            ("created_at", "Creation time (Unix seconds); the unit's for rows older than the column"),
+            ("updated_at", "Last change (Unix seconds)"),
+        ],
+    },
+    TableDoc {
+        name: "snapshots",
+        description: "Saved versions of a library, and autosaves between them.",
+        columns: &[
+            ("id", "Snapshot id"),
+            ("library_id", "Library"),
+            ("version", "Library version the snapshot records"),
+            ("timestamp", "When the snapshot was taken (Unix seconds)"),
+            ("data", "The library with its units as JSON"),
+            ("description", "Note entered when saving, if any"),
+            ("is_autosave", "1 for an autosave of the version, which may repeat"),
+        ],
+    },
+    TableDoc {
+        name: "formation_levels",
+        description: "Formation levels of a library (platoon, company, ...).",
+        columns: &[
+            ("id", "Level id"),
+            ("library_id", "Library"),
+            ("name_ru", "Russian name"),
+            ("name_en", "English name"),
+            ("standard_level_ordinal", "Standard echelon the level corresponds to (0 = smallest)"),
+            ("sort_order", "Position in the library's list"),
+            ("min_personnel", "Smallest expected personnel, if set"),
+            ("max_personnel", "Largest expected personnel, if set"),
+        ],
+    },
+    TableDoc {
+        name: "branch_categories",
+        description: "Groups of branches of service within a library.",
+        columns: &[
+            ("id", "Category id"),
+            ("library_id", "Library"),
+            ("name_ru", "Russian name"),
+            ("name_en", "English name"),
+            ("sort_order", "Position in the library's list"),
+        ],
+    },
+    TableDoc {
+        name: "branches",
+        description: "Branches of service of a library.",
+        columns: &[
+            ("id", "Branch id"),
+            ("library_id", "Library"),
+            ("name_ru", "Russian name"),
+            ("name_en", "English name"),
+            ("category_id", "Branch category, if any"),
+            ("sort_order", "Position in the library's list"),
+        ],
+    },
+    TableDoc {
+        name: "level_mapping_queue",
+        description: "Units whose type still needs a formation level confirmed.",
+        columns: &[
+            ("unit_id", "Unit"),
+            ("library_id", "Library of the unit"),
+            ("unit_type", "Unit type to map"),
+            ("suggested_level_id", "Best matching formation level, if any"),
+            ("score", "How well the suggestion matches (0 to 1)"),
+            ("dismissed", "1 when the user chose to leave the unit without a level"),
+        ],
+    },
+    TableDoc {
+        name: "equipment_catalog",
+        description: "Equipment and vehicle types known to a library.",
+        columns: &[
+            ("id", "Entry id"),
+            ("library_id", "Library"),
+            ("name_ru", "Russian name"),
+            ("name_en", "English name"),
+            ("category", "Category code: small_arms, vehicles or other"),
+            ("sort_order", "Position in the library's list"),
+            ("aliases", "Other names of the entry (JSON array of strings)"),
+        ],
+    },
+    TableDoc {
+        name: "library_provenance",
+        description: "Record of data copied from one library into another.",
+        columns: &[
+            ("id", "Row id"),
+            ("target_library_id", "Library the data was copied into"),
+            ("source_library_id", "Library the data was copied from"),
+            ("what", "Data copied: branches, branch_categories, formation_levels or equipment_catalog"),
+            ("copied_at", "When it was copied (Unix seconds)"),
+        ],
+    },
+    TableDoc {
+        name: "ignored_duplicates",
+        description: "Pairs of similar units the user marked as not duplicates.",
+        columns: &[
+            ("library_id", "Library of the units"),
+            ("first_unit_id", "Unit of the pair with the smaller id"),
+            ("second_unit_id", "Unit of the pair with the larger id"),
+        ],
+    },
+];
+
+/// Schema documentation of one database
+pub struct SchemaDoc {
+    pub version: i64,
+    /// CREATE statements for tables and indexes
+    pub sql: String,
+    /// Tables and columns with their descriptions
+    pub markdown: String,
+}
+
+/// Document the schema of the database behind `conn`
+pub fn generate(conn: &Connection) -> Result<SchemaDoc> {
+    let version: i64 = conn
+        .query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+        .unwrap_or(0);
+
+    let mut stmt = conn.prepare(
+        "SELECT type, name, sql FROM sqlite_master
+         WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+         ORDER BY type = 'index', name",
+    )?;
+    let objects = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut sql = format!("-- TOEditor database schema, version {}\n\n", version);
+    for (_, _, create) in &objects {
+        sql.push_str(create.trim());
+        sql.push_str(";\n\n");
+    }
+
+    let mut markdown = format!("# TOEditor database schema\n\nSchema version: {}\n", version);
+    for (_, table, _) in objects.iter().filter(|(kind, _, _)| kind == "table") {
+        let doc = SCHEMA_DESCRIPTION.iter().find(|t| t.name == table);
+        markdown.push_str(&format!("\n## {}\n\n", table));
+        if let Some(doc) = doc {
+            markdown.push_str(&format!("{}\n\n", doc.description));
+        }
+        markdown.push_str("| Column | Type | Required | Default | Description |\n");
+        markdown.push_str("|---|---|---|---|---|\n");
+        for column in table_columns(conn, table)? {
+            let description = doc
+                .and_then(|d| d.columns.iter().find(|(name, _)| *name == column.name))
+                .map(|(_, text)| *text)
+                .unwrap_or("");
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                column.name,
+                column.declared_type,
+                if column.not_null || column.primary_key { "yes" } else { "no" },
+                column.default.as_deref().unwrap_or(""),
+                description.replace('|', "\\|")
+            ));
+        }
+    }
+
+    Ok(SchemaDoc { version, sql, markdown })
+}
+
+/// A column as SQLite reports it
+#[derive(Debug, Clone, PartialEq)]
+struct Column {
+    name: String,
+    declared_type: String,
+    not_null: bool,
+    default: Option<String>,
+    primary_key: bool,
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<Column>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table.replace('"', "\"\"")))?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok(Column {
+                name: row.get(1)?,
+                declared_type: row.get(2)?,
+                not_null: row.get::<_, i64>(3)? != 0,
+                default: row.get(4)?,
+                primary_key: row.get::<_, i64>(5)? != 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().map(|r| r.unwrap()).collect()
+    }
+
+    fn indexes(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL ORDER BY name")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_every_table_and_column_is_described() {
+        let db = Database::open_in_memory().unwrap();
+        for table in tables(db.conn()) {
+            let doc = SCHEMA_DESCRIPTION
+                .iter()
+                .find(|t| t.name == table)
+                .unwrap_or_else(|| panic!("table {} is missing from SCHEMA_DESCRIPTION", table));
+            let columns = table_columns(db.conn(), &table).unwrap();
+            for column in &columns {
+                assert!(
+                    doc.columns.iter().any(|(name, _)| *name == column.name),
+                    "column {}.{} is missing from SCHEMA_DESCRIPTION",
+                    table,
+                    column.name
+                );
+            }
+            for (name, _) in doc.columns {
+                assert!(columns.iter().any(|c| c.name == *name), "{}.{} is described but does not exist", table, name);
+            }
+        }
+        assert_eq!(SCHEMA_DESCRIPTION.len(), tables(db.conn()).len());
+    }
+
+    #[test]
+    fn test_sql_recreates_the_schema() {
+        let db = Database::open_in_memory().unwrap();
+        let doc = generate(db.conn()).unwrap();
+        assert_eq!(doc.version, Database::CURRENT_SCHEMA_VERSION);
+
+        let blank = Connection::open_in_memory().unwrap();
+        blank.execute_batch(&doc.sql).unwrap();
+        assert_eq!(tables(&blank), tables(db.conn()));
+        assert_eq!(indexes(&blank), indexes(db.conn()));
+        for table in tables(db.conn()) {
+            assert_eq!(table_columns(&blank, &table).unwrap(), table_columns(db.conn(), &table).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_markdown_lists_columns_with_descriptions() {
+        let db = Database::open_in_memory().unwrap();
+        let doc = generate(db.conn()).unwrap();
+        assert!(doc.markdown.contains(&format!("Schema version: {}", Database::CURRENT_SCHEMA_VERSION)));
+        assert!(doc.markdown.contains("\n## units\n"));
+        assert!(doc
+            .markdown
+            .contains("| effective_from | TEXT | no |  | First day the unit is in the order of battle"));
+        assert!(doc.markdown.contains("| id | INTEGER | yes |  | Library id |"));
+        assert!(!doc.markdown.contains("sqlite_sequence"));
+    }
+}
@@ -0,0 +1,398 @@
+//! Where the database file lives: spotting cloud-synced folders, the safer
+//! journal settings for them, and moving the file somewhere else.
+//!
+//! Sync clients copy files while SQLite has them open and can upload a
+//! database without its `-wal` file, so a database in a synced folder is easily
+//! corrupted or reverted.
+
+use anyhow::{bail, Context, Result};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::Database;
+
+/// Name of the sync service whose folder contains `path`, if any.
+///
+/// `known_roots` are sync folders the platform reports (see
+/// [`known_sync_roots`]); beyond those, folder names used by the common
+/// clients on Windows, macOS and Linux are recognised. Both `/` and `\`
+/// separate folders, so Windows paths are recognised on every platform.
+pub fn cloud_sync_provider(path: &Path, known_roots: &[(PathBuf, &'static str)]) -> Option<&'static str> {
+    let text = path.to_string_lossy();
+    let components: Vec<&str> = text.split(['/', '\\']).filter(|c| !c.is_empty()).collect();
+
+    for (root, provider) in known_roots {
+        let root = root.to_string_lossy();
+        let root: Vec<&str> = root.split(['/', '\\']).filter(|c| !c.is_empty()).collect();
+        if !root.is_empty()
+            && components.len() > root.len()
+            && components.iter().zip(&root).all(|(a, b)| a.eq_ignore_ascii_case(b))
+        {
+            return Some(provider);
+        }
+    }
+
+    // Folders above the file name only
+    let folders = &components[..components.len().saturating_sub(1)];
+    for (i, folder) in folders.iter().enumerate() {
+        let name = folder.to_lowercase();
+        // macOS File Provider folders: ~/Library/CloudStorage/<Provider>-<account>
+        if name == "cloudstorage" && i > 0 && folders[i - 1].eq_ignore_ascii_case("library") {
+            if let Some(next) = folders.get(i + 1) {
+                return Some(provider_by_folder(&next.to_lowercase()).unwrap_or("cloud storage"));
+            }
+        }
+        if let Some(provider) = provider_by_folder(&name) {
+            return Some(provider);
+        }
+    }
+    None
+}
+
+/// Sync service using a folder with this (lowercase) name
+fn provider_by_folder(name: &str) -> Option<&'static str> {
+    let starts = |prefix: &str| name == prefix || name.starts_with(&format!("{} ", prefix)) || name.starts_with(&format!("{}-", prefix));
+    if starts("dropbox") {
+        // "Dropbox", "Dropbox (Company)", "Dropbox-Personal" on macOS CloudStorage
+        Some("Dropbox")
+    } else if starts("onedrive") {
+        // "OneDrive", "OneDrive - Company"
+        Some("OneDrive")
+    } else if starts("google drive") || starts("googledrive") || name == "my drive" {
+        Some("Google Drive")
+    } else if name == "icloud drive" || name == "iclouddrive" || name == "mobile documents" {
+        Some("iCloud Drive")
+    } else if name == "box sync" || name.starts_with("box-") {
+        Some("Box")
+    } else if name == "pcloud drive" || name == "pclouddrive" {
+        Some("pCloud")
+    } else if name == "yandex.disk" || name == "yandexdisk" {
+        Some("Yandex.Disk")
+    } else {
+        None
+    }
+}
+
+/// Sync folders announced by the platform: the OneDrive environment
+/// variables on Windows. Other clients are found by folder name.
+pub fn known_sync_roots() -> Vec<(PathBuf, &'static str)> {
+    ["OneDrive", "OneDriveConsumer", "OneDriveCommercial"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .filter(|value| !value.is_empty())
+        .map(|value| (PathBuf::from(value), "OneDrive"))
+        .collect()
+}
+
+/// Whether to warn about a database at `path` in `provider`'s folder, given
+/// what the user chose before
+pub fn should_warn(provider: Option<&str>, path: &Path, settings: &crate::config::Settings) -> bool {
+    provider.is_some()
+        && !settings.safe_database_mode
+        && settings.cloud_sync_warning_silenced.as_deref() != Some(path)
+}
+
+impl Database {
+    /// Journal settings that survive a sync client copying the file: no
+    /// write-ahead log beside the database, and every commit flushed to the
+    /// file itself before it returns.
+    pub fn use_safe_mode(&self) -> Result<()> {
+        let mode: String = self.conn.query_row("PRAGMA journal_mode = DELETE", [], |row| row.get(0))?;
+        if !mode.eq_ignore_ascii_case("delete") {
+            bail!("database stayed in {} journal mode", mode);
+        }
+        self.conn.execute_batch("PRAGMA synchronous = FULL;")?;
+        Ok(())
+    }
+
+    /// Write a consistent copy of the database to `path`, replacing an older
+    /// copy. Works while the database is open and in use; the copy is a
+    /// single file that opens on its own.
+    pub fn backup_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        let _ = std::fs::remove_file(&tmp_path);
+        {
+            let mut copy = Connection::open(&tmp_path)?;
+            Backup::new(&self.conn, &mut copy)?
+                .run_to_completion(256, Duration::from_millis(50), None)
+                .with_context(|| format!("backing up to {}", path.display()))?;
+            // Pages copied from a WAL database leave the copy in WAL mode
+            copy.pragma_update_and_check(None, "journal_mode", "DELETE", |row| row.get::<_, String>(0))?;
+        }
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Move the database file at `from` to `to`.
+///
+/// The copy is checked with `PRAGMA integrity_check` and must have the same
+/// row count in every table as the original; only then is the original (with
+/// its journal files) removed. Nobody may have `from` open. A failed check
+/// removes the copy and leaves the original alone.
+pub fn move_database(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        bail!("{} already exists", to.display());
+    }
+    if let Some(dir) = to.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let source = Connection::open_with_flags(from, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .with_context(|| format!("opening {}", from.display()))?;
+    source.execute("VACUUM INTO ?1", [to.to_string_lossy()])?;
+
+    if let Err(e) = verify_copy(&source, to) {
+        let _ = std::fs::remove_file(to);
+        return Err(e.context(format!("the copy at {} did not match", to.display())));
+    }
+    drop(source);
+
+    for suffix in ["", "-wal", "-shm", "-journal"] {
+        let mut name = from.as_os_str().to_os_string();
+        name.push(suffix);
+        let path = PathBuf::from(name);
+        if path.exists() {
+            std::fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Get `target` ready to be opened as the database. Its folder must exist.
+/// With `copy_from`, that database is copied to `target` and the copy checked
+/// like [`move_database`] does; an existing file is never overwritten. Without,
+/// the file already at `target` is used, or a new database is created there.
+pub fn prepare_database_switch(copy_from: Option<&Database>, target: &Path) -> Result<()> {
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        bail!("folder {} does not exist", dir.display());
+    }
+    if target.is_dir() {
+        bail!("{} is a folder", target.display());
+    }
+    let Some(source) = copy_from else {
+        return Ok(());
+    };
+    if target.exists() {
+        bail!("{} already exists", target.display());
+    }
+    source.backup_to(target)?;
+    if let Err(e) = verify_copy(&source.conn, target) {
+        let _ = std::fs::remove_file(target);
+        return Err(e.context(format!("the copy at {} did not match", target.display())));
+    }
+    Ok(())
+}
+
+fn verify_copy(source: &Connection, copy: &Path) -> Result<()> {
+    let copy = Connection::open_with_flags(copy, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let check: String = copy.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        bail!("integrity check failed: {}", check);
+    }
+    let source_counts = row_counts(source)?;
+    let copy_counts = row_counts(&copy)?;
+    if source_counts != copy_counts {
+        bail!("row counts differ");
+    }
+    Ok(())
+}
+
+/// Row count of every table, by table name
+fn row_counts(conn: &Connection) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tables
+        .into_iter()
+        .map(|table| {
+            let count = conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")), [], |row| row.get(0))?;
+            Ok((table, count))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::db::repositories::LibraryRepo;
+    use crate::models::Library;
+
+    fn provider(path: &str) -> Option<&'static str> {
+        cloud_sync_provider(Path::new(path), &[])
+    }
+
+    #[test]
+    fn test_detects_windows_sync_folders() {
+        assert_eq!(provider(r"C:\Users\anna\OneDrive\Documents\toeditor.db"), Some("OneDrive"));
+        assert_eq!(provider(r"C:\Users\anna\OneDrive - Contoso\toeditor.db"), Some("OneDrive"));
+        assert_eq!(provider(r"C:\Users\anna\Dropbox (Personal)\toeditor.db"), Some("Dropbox"));
+        assert_eq!(provider(r"G:\My Drive\TOE\toeditor.db"), Some("Google Drive"));
+        assert_eq!(provider(r"C:\Users\anna\iCloudDrive\toeditor.db"), Some("iCloud Drive"));
+        assert_eq!(provider(r"C:\Users\anna\AppData\Roaming\toeditor\TOEditor\data\toeditor.db"), None);
+    }
+
+    #[test]
+    fn test_detects_macos_sync_folders() {
+        assert_eq!(
+            provider("/Users/anna/Library/CloudStorage/GoogleDrive-anna@example.com/My Drive/toeditor.db"),
+            Some("Google Drive")
+        );
+        assert_eq!(provider("/Users/anna/Library/CloudStorage/Box-Box/toeditor.db"), Some("Box"));
+        assert_eq!(provider("/Users/anna/Library/CloudStorage/SomeNewClient/toeditor.db"), Some("cloud storage"));
+        assert_eq!(
+            provider("/Users/anna/Library/Mobile Documents/com~apple~CloudDocs/toeditor.db"),
+            Some("iCloud Drive")
+        );
+        assert_eq!(provider("/Users/anna/Library/Application Support/com.toeditor.TOEditor/toeditor.db"), None);
+    }
+
+    #[test]
+    fn test_detects_linux_sync_folders() {
+        assert_eq!(provider("/home/anna/Dropbox/wargames/toeditor.db"), Some("Dropbox"));
+        assert_eq!(provider("/home/anna/Yandex.Disk/toeditor.db"), Some("Yandex.Disk"));
+        assert_eq!(provider("/home/anna/.local/share/toeditor/toeditor.db"), None);
+        // Only folders count, and "Box" alone is too common a word
+        assert_eq!(provider("/home/anna/Documents/dropbox"), None);
+        assert_eq!(provider("/home/anna/box/toeditor.db"), None);
+    }
+
+    #[test]
+    fn test_known_roots() {
+        let roots = vec![(PathBuf::from(r"D:\Sync\Work"), "OneDrive")];
+        assert_eq!(cloud_sync_provider(Path::new(r"d:\sync\work\toeditor.db"), &roots), Some("OneDrive"));
+        assert_eq!(cloud_sync_provider(Path::new(r"D:\Sync\Workshop\toeditor.db"), &roots), None);
+        assert_eq!(cloud_sync_provider(Path::new(r"D:\Sync\Work"), &roots), None);
+    }
+
+    #[test]
+    fn test_should_warn_respects_choices() {
+        let path = Path::new("/home/anna/Dropbox/toeditor.db");
+        let mut settings = Settings::default();
+        assert!(should_warn(Some("Dropbox"), path, &settings));
+        assert!(!should_warn(None, path, &settings));
+
+        settings.cloud_sync_warning_silenced = Some(path.to_path_buf());
+        assert!(!should_warn(Some("Dropbox"), path, &settings));
+        // A different database location warns again
+        assert!(should_warn(Some("Dropbox"), Path::new("/home/anna/Dropbox/other.db"), &settings));
+
+        let settings = Settings {
+            safe_database_mode: true,
+            ..Settings::default()
+        };
+        assert!(!should_warn(Some("Dropbox"), path, &settings));
+    }
+
+    #[test]
+    fn test_safe_mode_turns_off_wal() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::open(dir.path().join("toeditor.db")).unwrap();
+        db.conn().execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+
+        db.use_safe_mode().unwrap();
+        let mode: String = db.conn().query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+        let synchronous: i64 = db.conn().query_row("PRAGMA synchronous", [], |r| r.get(0)).unwrap();
+        assert_eq!(mode, "delete");
+        assert_eq!(synchronous, 2);
+        assert!(!dir.path().join("toeditor.db-wal").exists());
+    }
+
+    #[test]
+    fn test_backup_to() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::open(dir.path().join("toeditor.db")).unwrap();
+        let repo = LibraryRepo::new(db.conn());
+        for name in ["Backed up", "Also backed up"] {
+            repo.create(&mut Library::new(name.into(), "DE".into(), "WWII".into(), String::new())).unwrap();
+        }
+
+        let backup = dir.path().join("backups").join("toeditor.db.bak");
+        db.backup_to(&backup).unwrap();
+        db.backup_to(&backup).unwrap();
+        // The copy stands alone and the original stays usable
+        assert!(!dir.path().join("backups").join("toeditor.db.bak-wal").exists());
+        let copy = Database::open_read_only(&backup).unwrap();
+        assert_eq!(LibraryRepo::new(copy.conn()).list_all().unwrap().len(), repo.list_all().unwrap().len());
+        repo.create(&mut Library::new("After".into(), "DE".into(), "WWII".into(), String::new())).unwrap();
+    }
+
+    #[test]
+    fn test_move_database_verifies_then_removes_original() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let from = dir.path().join("Dropbox").join("toeditor.db");
+        std::fs::create_dir_all(from.parent().unwrap()).unwrap();
+        {
+            let db = Database::open(&from).unwrap();
+            LibraryRepo::new(db.conn())
+                .create(&mut Library::new("Moved".into(), "DE".into(), "WWII".into(), String::new()))
+                .unwrap();
+        }
+        let to = dir.path().join("local").join("toeditor.db");
+
+        move_database(&from, &to).unwrap();
+        assert!(!from.exists());
+        let db = Database::open(&to).unwrap();
+        assert_eq!(LibraryRepo::new(db.conn()).list_all().unwrap()[0].name, "Moved");
+
+        // Never overwrites an existing file
+        assert!(move_database(&to, &to).is_err());
+        assert!(to.exists());
+    }
+
+    #[test]
+    fn test_move_database_failure_keeps_original() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let from = dir.path().join("toeditor.db");
+        Database::open(&from).unwrap();
+        let blocked = dir.path().join("file");
+        std::fs::write(&blocked, b"not a folder").unwrap();
+
+        assert!(move_database(&from, &blocked.join("toeditor.db")).is_err());
+        assert!(from.exists());
+    }
+
+    #[test]
+    fn test_prepare_database_switch() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let current = Database::open(dir.path().join("toeditor.db")).unwrap();
+        LibraryRepo::new(current.conn())
+            .create(&mut Library::new("Copied".into(), "FR".into(), "1916".into(), String::new()))
+            .unwrap();
+
+        // The folder has to exist already
+        let missing = dir.path().join("missing").join("toeditor.db");
+        assert!(prepare_database_switch(Some(&current), &missing).is_err());
+        assert!(!missing.parent().unwrap().exists());
+        assert!(prepare_database_switch(None, dir.path()).is_err());
+
+        let copy = dir.path().join("copy.db");
+        prepare_database_switch(Some(&current), &copy).unwrap();
+        let reopened = Database::open(&copy).unwrap();
+        assert_eq!(LibraryRepo::new(reopened.conn()).list_all().unwrap()[0].name, "Copied");
+        drop(reopened);
+
+        // A copy never replaces a file; switching to it without copying is fine
+        assert!(prepare_database_switch(Some(&current), &copy).is_err());
+        prepare_database_switch(None, &copy).unwrap();
+
+        // Without a copy a new file is left for Database::open to create
+        let fresh = dir.path().join("fresh.db");
+        prepare_database_switch(None, &fresh).unwrap();
+        assert!(!fresh.exists());
+        assert!(LibraryRepo::new(Database::open(&fresh).unwrap().conn()).list_all().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+//! Anonymization pass for exports that are shared publicly
+//!
+//! Works on the export data structures so every format (JSON, HTML, …) gets
+//! the same treatment. Every exported field is classified in the inventories
+//! below; tests fail when a new field appears without a classification.
+
+use serde::{Deserialize, Serialize};
+use crate::models::{Equipment, Library, Personnel, Snapshot, Unit};
+use super::full::{snapshot_library_json_mut, FullLibraryExport};
+
+/// Default replacement for the library author
+pub const DEFAULT_PLACEHOLDER_AUTHOR: &str = "Anonymous";
+
+/// How a field is treated by the anonymization pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldClass {
+    /// Kept as is
+    Public,
+    /// Replaced by the placeholder author
+    Author,
+    /// Free-form notes, removed
+    Notes,
+    /// Audit metadata (timestamps etc.), cleared
+    Audit,
+}
+
+/// Classification of every serialized `Library` field
+pub const LIBRARY_FIELDS: &[(&str, FieldClass)] = &[
+    ("id", FieldClass::Public),
+    ("name", FieldClass::Public),
+    ("country", FieldClass::Public),
+    // Derived from the country, so as public as it is
+    ("country_code", FieldClass::Public),
+    ("era", FieldClass::Public),
+    ("author", FieldClass::Author),
+    ("version", FieldClass::Public),
+    ("tags", FieldClass::Public),
+    ("units", FieldClass::Public),
+    // Which name of bilingual units to show; says nothing about the author
+    ("display_language", FieldClass::Public),
+];
+
+/// Classification of every serialized `Unit` field
+pub const UNIT_FIELDS: &[(&str, FieldClass)] = &[
+    ("id", FieldClass::Public),
+    ("name", FieldClass::Public),
+    ("unit_type", FieldClass::Public),
+    ("parent_id", FieldClass::Public),
+    ("personnel", FieldClass::Public),
+    ("equipment", FieldClass::Public),
+    ("children", FieldClass::Public),
+    ("effective_from", FieldClass::Public),
+    ("effective_to", FieldClass::Public),
+    ("toe_number", FieldClass::Public),
+    ("formation_level_id", FieldClass::Public),
+];
+
+/// Classification of every serialized `Personnel` field
+pub const PERSONNEL_FIELDS: &[(&str, FieldClass)] = &[
+    ("position", FieldClass::Public),
+    ("rank", FieldClass::Public),
+];
+
+/// Classification of every serialized `Equipment` field
+pub const EQUIPMENT_FIELDS: &[(&str, FieldClass)] = &[
+    ("name", FieldClass::Public),
+    ("quantity", FieldClass::Public),
+];
+
+/// Classification of every serialized `Snapshot` field
+pub const SNAPSHOT_FIELDS: &[(&str, FieldClass)] = &[
+    ("id", FieldClass::Public),
+    ("library_id", FieldClass::Public),
+    ("version", FieldClass::Public),
+    ("timestamp", FieldClass::Audit),
+    ("data", FieldClass::Public),
+    ("description", FieldClass::Notes),
+    ("is_autosave", FieldClass::Public),
+];
+
+/// Options for the anonymization pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnonymizeOptions {
+    /// Value written in place of the library author
+    pub placeholder_author: String,
+    /// Keep the snapshot history, each snapshot with its author replaced;
+    /// when false the versions are dropped
+    pub include_versions: bool,
+}
+
+impl Default for AnonymizeOptions {
+    fn default() -> Self {
+        Self {
+            placeholder_author: DEFAULT_PLACEHOLDER_AUTHOR.to_string(),
+            include_versions: true,
+        }
+    }
+}
+
+/// Library export payload: the library plus its (optional) snapshots
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LibraryExport {
+    pub library: Library,
+    #[serde(default)]
+    pub versions: Vec<Snapshot>,
+}
+
+/// Strip sensitive fields from an export payload.
+pub fn anonymize(export: LibraryExport, options: &AnonymizeOptions) -> LibraryExport {
+    LibraryExport {
+        library: anonymize_library(export.library, options),
+        versions: anonymize_versions(export.versions, options),
+    }
+}
+
+/// Snapshots with their audit data and authors stripped; none when `options`
+/// drop the history
+pub fn anonymize_versions(versions: Vec<Snapshot>, options: &AnonymizeOptions) -> Vec<Snapshot> {
+    if !options.include_versions {
+        return Vec::new();
+    }
+    versions.into_iter().map(|s| anonymize_snapshot(s, options)).collect()
+}
+
+/// Strip sensitive fields from a full export. The formation levels and
+/// branches are the library's reference tables and hold nothing personal.
+pub fn anonymize_full(export: FullLibraryExport, options: &AnonymizeOptions) -> FullLibraryExport {
+    let FullLibraryExport { format_version, library, formation_levels, branch_categories, branches } = export;
+    FullLibraryExport {
+        format_version,
+        library: anonymize_library(library, options),
+        formation_levels,
+        branch_categories,
+        branches,
+    }
+}
+
+/// Strip sensitive fields from a library and its units
+pub fn anonymize_library(library: Library, options: &AnonymizeOptions) -> Library {
+    // Exhaustive destructuring: adding a field to Library breaks the build here.
+    let Library { id, name, country, country_code, era, author: _, version, tags, units, display_language, numbering } = library;
+    Library {
+        id,
+        name,
+        country,
+        country_code,
+        era,
+        author: options.placeholder_author.clone(),
+        version,
+        tags,
+        units: units.into_iter().map(anonymize_unit).collect(),
+        display_language,
+        numbering,
+    }
+}
+
+fn anonymize_unit(unit: Unit) -> Unit {
+    // Exhaustive destructuring, as for Library: new unit fields need a decision here.
+    let Unit { id, name, unit_type, parent_id, personnel, equipment, children, effective_from, effective_to, toe_number, formation_level_id } = unit;
+    Unit {
+        id,
+        name,
+        unit_type,
+        parent_id,
+        personnel: personnel.into_iter().map(anonymize_personnel).collect(),
+        equipment: equipment.into_iter().map(anonymize_equipment).collect(),
+        children: children.into_iter().map(anonymize_unit).collect(),
+        effective_from,
+        effective_to,
+        toe_number,
+        formation_level_id,
+    }
+}
+
+fn anonymize_personnel(personnel: Personnel) -> Personnel {
+    let Personnel { position, rank } = personnel;
+    Personnel { position, rank }
+}
+
+fn anonymize_equipment(equipment: Equipment) -> Equipment {
+    let Equipment { name, quantity } = equipment;
+    Equipment { name, quantity }
+}
+
+fn anonymize_snapshot(snapshot: Snapshot, options: &AnonymizeOptions) -> Snapshot {
+    let Snapshot { id, library_id, version, timestamp: _, data, description: _, is_autosave } = snapshot;
+    Snapshot {
+        id,
+        library_id,
+        version,
+        timestamp: 0,
+        data: anonymize_snapshot_data(&data, options),
+        description: None,
+        is_autosave,
+    }
+}
+
+/// Snapshot data is a serialized library, nested in full-state snapshots;
+/// rewrite its author if it parses.
+fn anonymize_snapshot_data(data: &str, options: &AnonymizeOptions) -> String {
+    match serde_json::from_str::<serde_json::Value>(data) {
+        Ok(mut value) => {
+            if let Some(obj) = snapshot_library_json_mut(&mut value).as_object_mut() {
+                if obj.contains_key("author") {
+                    obj.insert(
+                        "author".to_string(),
+                        serde_json::Value::String(options.placeholder_author.clone()),
+                    );
+                }
+            }
+            serde_json::to_string(&value).unwrap_or_default()
+        }
+        Err(_) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// A unit with every optional field set, so all of them serialize
+    fn sample_unit() -> Unit {
+        let mut unit = Unit::new("1st Platoon".to_string(), "Platoon".to_string());
+        unit.id = Some(2);
+        unit.parent_id = Some(1);
+        unit.effective_from = Some("2003-03-20".to_string());
+        unit.effective_to = Some("2003-05-01".to_string());
+        unit.toe_number = Some("TOE 07247L000".to_string());
+        unit.formation_level_id = Some(4);
+        unit.add_personnel(Personnel::with_rank("Platoon Leader".to_string(), "LT".to_string()));
+        unit.add_equipment(Equipment::new("M4 Carbine".to_string(), 30));
+        unit.add_child(Unit::new("1st Squad".to_string(), "Squad".to_string()));
+        unit
+    }
+
+    fn sample_export() -> LibraryExport {
+        let mut library = Library::new(
+            "Test".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "John Smith".to_string(),
+        );
+        library.tags = vec!["army".to_string()];
+        library.add_unit(sample_unit());
+        let data = serde_json::to_string(&library).unwrap();
+        let snapshot = Snapshot::with_description(1, 1, data, "Internal note".to_string());
+        LibraryExport {
+            library,
+            versions: vec![snapshot],
+        }
+    }
+
+    fn serialized_keys<T: Serialize>(value: &T) -> BTreeSet<String> {
+        serde_json::to_value(value)
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    fn classified_keys(fields: &[(&str, FieldClass)]) -> BTreeSet<String> {
+        fields.iter().map(|(k, _)| k.to_string()).collect()
+    }
+
+    #[test]
+    fn test_every_library_field_is_classified() {
+        let export = sample_export();
+        assert_eq!(serialized_keys(&export.library), classified_keys(LIBRARY_FIELDS));
+    }
+
+    #[test]
+    fn test_every_unit_field_is_classified() {
+        let unit = sample_unit();
+        assert_eq!(serialized_keys(&unit), classified_keys(UNIT_FIELDS));
+        assert_eq!(serialized_keys(&unit.personnel[0]), classified_keys(PERSONNEL_FIELDS));
+        assert_eq!(serialized_keys(&unit.equipment[0]), classified_keys(EQUIPMENT_FIELDS));
+    }
+
+    #[test]
+    fn test_every_snapshot_field_is_classified() {
+        let export = sample_export();
+        assert_eq!(serialized_keys(&export.versions[0]), classified_keys(SNAPSHOT_FIELDS));
+    }
+
+    #[test]
+    fn test_anonymize_strips_sensitive_fields() {
+        let export = anonymize(sample_export(), &AnonymizeOptions::default());
+        assert_eq!(export.library.author, DEFAULT_PLACEHOLDER_AUTHOR);
+        assert_eq!(export.library.name, "Test");
+        assert_eq!(export.library.tags, vec!["army".to_string()]);
+        assert_eq!(export.library.units, vec![sample_unit()]);
+        assert_eq!(export.library.country_code.as_deref(), Some("US"));
+        let snap = &export.versions[0];
+        assert_eq!(snap.description, None);
+        assert_eq!(snap.timestamp, 0);
+        assert!(!snap.data.contains("John Smith"));
+        assert!(snap.data.contains(DEFAULT_PLACEHOLDER_AUTHOR));
+    }
+
+    #[test]
+    fn test_anonymize_custom_placeholder() {
+        let options = AnonymizeOptions {
+            placeholder_author: "TOE Team".to_string(),
+            ..AnonymizeOptions::default()
+        };
+        let export = anonymize(sample_export(), &options);
+        assert_eq!(export.library.author, "TOE Team");
+        let json = serde_json::to_string(&export).unwrap();
+        assert!(!json.contains("John Smith"));
+        assert!(!json.contains("Internal note"));
+    }
+
+    #[test]
+    fn test_anonymize_keeps_versions_unless_dropped() {
+        assert_eq!(anonymize(sample_export(), &AnonymizeOptions::default()).versions.len(), 1);
+        let options = AnonymizeOptions {
+            include_versions: false,
+            ..AnonymizeOptions::default()
+        };
+        assert!(anonymize(sample_export(), &options).versions.is_empty());
+    }
+
+    #[test]
+    fn test_anonymize_full_state_snapshot_author() {
+        let mut export = sample_export();
+        export.versions[0].data =
+            serde_json::json!({"format_version": 1, "library": {"name": "Test", "author": "John Smith"}}).to_string();
+        let export = anonymize(export, &AnonymizeOptions::default());
+        let data: serde_json::Value = serde_json::from_str(&export.versions[0].data).unwrap();
+        assert_eq!(data["library"]["author"], DEFAULT_PLACEHOLDER_AUTHOR);
+        assert!(data.get("author").is_none());
+    }
+
+    #[test]
+    fn test_anonymize_unparseable_snapshot_data_is_dropped() {
+        let mut export = sample_export();
+        export.versions[0].data = "John Smith wrote this".to_string();
+        let export = anonymize(export, &AnonymizeOptions::default());
+        assert!(export.versions[0].data.is_empty());
+    }
+}
@@ -1,22 +1,52 @@
-//! Export/import and copy for branches and formation levels (per-library data).
+//! Export/import and copy for branches, formation levels and the equipment catalog (per-library data).
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
-use crate::models::{Branch, BranchCategory, CustomFormationLevel};
-use crate::db::repositories::{BranchRepo, BranchCategoryRepo, FormationLevelRepo};
+use crate::models::{Branch, BranchCategory, CustomFormationLevel, EquipmentCatalogEntry, EquipmentCategory};
+use crate::db::repositories::{BranchRepo, BranchCategoryRepo, EquipmentCatalogRepo, FormationLevelRepo};
+use crate::import::limits::read_limited;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BranchExport {
     pub name_ru: String,
     pub name_en: String,
+    /// Category names (optional; absent in files written by older versions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_name_ru: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_name_en: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A category referenced by imported branches that has no match in the target library
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnmatchedCategory {
+    pub name_ru: String,
+    pub name_en: String,
+}
+
+/// What to do with branches pointing at an unmatched category
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CategoryResolution {
+    /// Create the category in the target library
+    Create,
+    /// Use an existing category of the target library
+    MapTo(i64),
+    /// Import the branches without a category
+    Uncategorized,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FormationLevelExport {
     pub name_ru: String,
     pub name_en: String,
     pub standard_level_ordinal: i32,
+    /// Expected personnel range (optional; absent in files written by older versions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_personnel: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_personnel: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,10 +56,63 @@ pub struct BranchCategoryExport {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct BranchesFile {
+pub struct EquipmentCatalogExport {
+    pub name_ru: String,
+    pub name_en: String,
+    /// Category code; missing or unknown codes import as "other"
+    #[serde(default)]
+    pub category: String,
+    /// Absent in files written by older versions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl EquipmentCatalogExport {
+    pub fn category(&self) -> EquipmentCategory {
+        EquipmentCategory::from_code(&self.category)
+    }
+
+    /// The entry for `library_id`
+    pub fn to_entry(&self, library_id: i64) -> EquipmentCatalogEntry {
+        let mut entry = EquipmentCatalogEntry::new(library_id, self.name_ru.clone(), self.name_en.clone(), self.category());
+        entry.aliases = self.aliases.clone();
+        entry
+    }
+}
+
+/// A branches file: the branches plus the categories of the library they came from
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BranchesFile {
+    /// Absent in files written by older versions
+    #[serde(default)]
+    pub categories: Vec<BranchCategoryExport>,
     pub branches: Vec<BranchExport>,
 }
 
+impl BranchesFile {
+    /// Categories of the file, and categories referenced by its branches, that
+    /// do not exist in `existing` (deduplicated, in file order)
+    pub fn unmatched_categories(&self, existing: &[BranchCategory]) -> Vec<UnmatchedCategory> {
+        let listed = self.categories.iter().map(|c| UnmatchedCategory {
+            name_ru: c.name_ru.clone(),
+            name_en: c.name_en.clone(),
+        });
+        let referenced = find_unmatched_categories(&self.branches, existing);
+        let mut unmatched: Vec<UnmatchedCategory> = Vec::new();
+        for cat in listed.chain(referenced) {
+            let blank = cat.name_ru.trim().is_empty() && cat.name_en.trim().is_empty();
+            let known = match_category(existing, Some(&cat.name_ru), Some(&cat.name_en)).is_some()
+                || unmatched
+                    .iter()
+                    .any(|u| names_match(Some(&cat.name_ru), Some(&cat.name_en), &u.name_ru, &u.name_en));
+            if !blank && !known {
+                unmatched.push(cat);
+            }
+        }
+        unmatched
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct BranchCategoriesFile {
     pub categories: Vec<BranchCategoryExport>,
@@ -40,46 +123,235 @@ struct FormationLevelsFile {
     pub formation_levels: Vec<FormationLevelExport>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EquipmentCatalogFile {
+    pub equipment: Vec<EquipmentCatalogExport>,
+}
+
 /// Export branches to a JSON file.
 pub fn export_branches_to_path(path: &Path, branches: &[Branch]) -> Result<()> {
-    let data: Vec<BranchExport> = branches
-        .iter()
-        .map(|b| BranchExport {
-            name_ru: b.name_ru.clone(),
-            name_en: b.name_en.clone(),
-        })
-        .collect();
-    let file = BranchesFile { branches: data };
+    export_branches_with_categories_to_path(path, branches, &[])
+}
+
+/// Export branches to a JSON file, writing each branch's category names
+/// (looked up by `category_id` in `categories`).
+pub fn export_branches_with_categories_to_path(
+    path: &Path,
+    branches: &[Branch],
+    categories: &[BranchCategory],
+) -> Result<()> {
+    let file = BranchesFile {
+        categories: branch_category_exports(categories),
+        branches: branch_exports(branches, categories),
+    };
     let json = serde_json::to_string_pretty(&file)?;
     std::fs::write(path, json)?;
     Ok(())
 }
 
+/// Branches as written to a file, with each branch's category names
+/// (looked up by `category_id` in `categories`).
+pub fn branch_exports(branches: &[Branch], categories: &[BranchCategory]) -> Vec<BranchExport> {
+    branches
+        .iter()
+        .map(|b| {
+            let category = b
+                .category_id
+                .and_then(|id| categories.iter().find(|c| c.id == Some(id)));
+            BranchExport {
+                name_ru: b.name_ru.clone(),
+                name_en: b.name_en.clone(),
+                category_name_ru: category.map(|c| c.name_ru.clone()),
+                category_name_en: category.map(|c| c.name_en.clone()),
+            }
+        })
+        .collect()
+}
+
 /// Import branches from a JSON file. Returns the list (without library_id); caller inserts into DB.
 pub fn import_branches_from_path(path: &Path) -> Result<Vec<BranchExport>> {
-    let json = std::fs::read_to_string(path)?;
-    let file: BranchesFile = serde_json::from_str(&json)?;
-    Ok(file.branches)
+    Ok(import_branches_file_from_path(path)?.branches)
+}
+
+/// Import a branches file with the categories it carries (none for files from older versions).
+pub fn import_branches_file_from_path(path: &Path) -> Result<BranchesFile> {
+    let json = read_limited(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    let a = a.trim();
+    !a.is_empty() && a.to_lowercase() == b.trim().to_lowercase()
+}
+
+/// Find a category by name in either language (trimmed, case-insensitive).
+pub fn match_category(
+    categories: &[BranchCategory],
+    name_ru: Option<&str>,
+    name_en: Option<&str>,
+) -> Option<i64> {
+    categories
+        .iter()
+        .find(|c| names_match(name_ru, name_en, &c.name_ru, &c.name_en))
+        .and_then(|c| c.id)
+}
+
+/// Whether either name matches either of a category's names
+fn names_match(name_ru: Option<&str>, name_en: Option<&str>, ru: &str, en: &str) -> bool {
+    name_ru.is_some_and(|n| same_name(n, ru) || same_name(n, en))
+        || name_en.is_some_and(|n| same_name(n, en) || same_name(n, ru))
+}
+
+fn category_ref(branch: &BranchExport) -> Option<UnmatchedCategory> {
+    let ru = branch.category_name_ru.clone().unwrap_or_default();
+    let en = branch.category_name_en.clone().unwrap_or_default();
+    if ru.trim().is_empty() && en.trim().is_empty() {
+        None
+    } else {
+        Some(UnmatchedCategory { name_ru: ru, name_en: en })
+    }
+}
+
+/// Categories referenced by imported branches that do not exist in `existing` (deduplicated, in file order).
+pub fn find_unmatched_categories(
+    imported: &[BranchExport],
+    existing: &[BranchCategory],
+) -> Vec<UnmatchedCategory> {
+    let mut unmatched: Vec<UnmatchedCategory> = Vec::new();
+    for b in imported {
+        if let Some(cat) = category_ref(b) {
+            let found = match_category(existing, Some(&cat.name_ru), Some(&cat.name_en));
+            if found.is_none() && !unmatched.contains(&cat) {
+                unmatched.push(cat);
+            }
+        }
+    }
+    unmatched
+}
+
+/// Branches resolved from an import, with the categories they need created
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedBranches {
+    pub branches: Vec<Branch>,
+    /// Categories resolved with `Create`, not stored yet. Each carries a
+    /// negative placeholder id that `branches` refer to until they are saved.
+    pub new_categories: Vec<BranchCategory>,
+}
+
+impl ResolvedBranches {
+    /// Id of the new category named `cat`, added after `existing` if missing
+    fn pending_category(&mut self, library_id: i64, existing: &[BranchCategory], cat: &UnmatchedCategory) -> i64 {
+        if let Some(id) = match_category(&self.new_categories, Some(&cat.name_ru), Some(&cat.name_en)) {
+            return id;
+        }
+        let index = self.new_categories.len() as i64;
+        let mut category = BranchCategory::new(library_id, cat.name_ru.clone(), cat.name_en.clone());
+        // -1 stands for "no category" in the editors
+        category.id = Some(-2 - index);
+        category.sort_order = existing.iter().map(|c| c.sort_order + 1).max().unwrap_or(0) + index;
+        self.new_categories.push(category);
+        -2 - index
+    }
+}
+
+/// Turn imported branches into branches of `library_id`, resolving categories by name.
+///
+/// Categories that match one of `existing` are used directly; unmatched ones follow
+/// `resolutions` (missing entries mean uncategorized). Nothing is stored: categories
+/// resolved with `Create` come back as new categories for the caller to save along
+/// with the branches.
+pub fn resolve_branch_import(
+    existing: &[BranchCategory],
+    library_id: i64,
+    imported: &[BranchExport],
+    resolutions: &HashMap<UnmatchedCategory, CategoryResolution>,
+) -> ResolvedBranches {
+    let mut resolved = ResolvedBranches::default();
+    resolve_branches_into(&mut resolved, existing, library_id, imported, resolutions);
+    resolved
+}
+
+fn resolve_branches_into(
+    resolved: &mut ResolvedBranches,
+    existing: &[BranchCategory],
+    library_id: i64,
+    imported: &[BranchExport],
+    resolutions: &HashMap<UnmatchedCategory, CategoryResolution>,
+) {
+    for (position, b) in imported.iter().enumerate() {
+        let category_id = match category_ref(b) {
+            None => None,
+            Some(cat) => match match_category(existing, Some(&cat.name_ru), Some(&cat.name_en)) {
+                Some(id) => Some(id),
+                None => match resolutions.get(&cat).copied().unwrap_or(CategoryResolution::Uncategorized) {
+                    CategoryResolution::MapTo(id) => Some(id),
+                    CategoryResolution::Uncategorized => None,
+                    CategoryResolution::Create => Some(resolved.pending_category(library_id, existing, &cat)),
+                },
+            },
+        };
+        let mut branch = Branch::with_category(
+            library_id,
+            category_id,
+            b.name_ru.clone(),
+            b.name_en.clone(),
+        );
+        branch.sort_order = position as i64;
+        resolved.branches.push(branch);
+    }
+}
+
+/// Turn a branches file into branches of `library_id`.
+///
+/// Like [`resolve_branch_import`], but categories listed in the file are
+/// resolved too, so a category resolved with `Create` is added (in file
+/// order) even when no branch uses it.
+pub fn resolve_branches_file_import(
+    existing: &[BranchCategory],
+    library_id: i64,
+    file: &BranchesFile,
+    resolutions: &HashMap<UnmatchedCategory, CategoryResolution>,
+) -> ResolvedBranches {
+    let mut resolved = ResolvedBranches::default();
+    for c in &file.categories {
+        let key = UnmatchedCategory {
+            name_ru: c.name_ru.clone(),
+            name_en: c.name_en.clone(),
+        };
+        if resolutions.get(&key) == Some(&CategoryResolution::Create)
+            && match_category(existing, Some(&c.name_ru), Some(&c.name_en)).is_none()
+        {
+            resolved.pending_category(library_id, existing, &key);
+        }
+    }
+    resolve_branches_into(&mut resolved, existing, library_id, &file.branches, resolutions);
+    resolved
 }
 
 /// Export branch categories to a JSON file.
 pub fn export_branch_categories_to_path(path: &Path, categories: &[BranchCategory]) -> Result<()> {
-    let data: Vec<BranchCategoryExport> = categories
+    let file = BranchCategoriesFile {
+        categories: branch_category_exports(categories),
+    };
+    let json = serde_json::to_string_pretty(&file)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+/// Branch categories as written to a file.
+pub fn branch_category_exports(categories: &[BranchCategory]) -> Vec<BranchCategoryExport> {
+    categories
         .iter()
         .map(|c| BranchCategoryExport {
             name_ru: c.name_ru.clone(),
             name_en: c.name_en.clone(),
         })
-        .collect();
-    let file = BranchCategoriesFile { categories: data };
-    let json = serde_json::to_string_pretty(&file)?;
-    std::fs::write(path, json)?;
-    Ok(())
+        .collect()
 }
 
 /// Import branch categories from a JSON file. Returns the list (without library_id); caller inserts into DB.
 pub fn import_branch_categories_from_path(path: &Path) -> Result<Vec<BranchCategoryExport>> {
-    let json = std::fs::read_to_string(path)?;
+    let json = read_limited(path)?;
     let file: BranchCategoriesFile = serde_json::from_str(&json)?;
     Ok(file.categories)
 }
@@ -102,41 +374,72 @@ pub fn copy_branch_categories_between_libraries(
 
 /// Export formation levels to a JSON file.
 pub fn export_formation_levels_to_path(path: &Path, levels: &[CustomFormationLevel]) -> Result<()> {
-    let data: Vec<FormationLevelExport> = levels
+    let file = FormationLevelsFile {
+        formation_levels: formation_level_exports(levels),
+    };
+    let json = serde_json::to_string_pretty(&file)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+/// Formation levels as written to a file.
+pub fn formation_level_exports(levels: &[CustomFormationLevel]) -> Vec<FormationLevelExport> {
+    levels
         .iter()
         .map(|l| FormationLevelExport {
             name_ru: l.name_ru.clone(),
             name_en: l.name_en.clone(),
             standard_level_ordinal: l.standard_level_ordinal,
+            min_personnel: l.min_personnel,
+            max_personnel: l.max_personnel,
         })
-        .collect();
-    let file = FormationLevelsFile {
-        formation_levels: data,
-    };
-    let json = serde_json::to_string_pretty(&file)?;
-    std::fs::write(path, json)?;
-    Ok(())
+        .collect()
 }
 
 /// Import formation levels from a JSON file.
 pub fn import_formation_levels_from_path(path: &Path) -> Result<Vec<FormationLevelExport>> {
-    let json = std::fs::read_to_string(path)?;
+    let json = read_limited(path)?;
     let file: FormationLevelsFile = serde_json::from_str(&json)?;
     Ok(file.formation_levels)
 }
 
 /// Copy all branches from source library to target library (replaces target's branches).
+///
+/// Each branch gets the target library's category with the same name; missing
+/// categories are created in the target library.
 pub fn copy_branches_between_libraries(
     branch_repo: &BranchRepo,
+    category_repo: &BranchCategoryRepo,
     source_library_id: i64,
     target_library_id: i64,
 ) -> Result<()> {
     let branches = branch_repo.list_by_library(source_library_id)?;
+    let source_categories = category_repo.list_by_library(source_library_id)?;
+    let mut target_categories = category_repo.list_by_library(target_library_id)?;
+    let mut next_order = target_categories.iter().map(|c| c.sort_order + 1).max().unwrap_or(0);
     branch_repo.delete_by_library(target_library_id)?;
     for mut b in branches {
         b.id = None;
         b.library_id = target_library_id;
-        b.category_id = None; // target library has its own categories
+        // Category ids belong to the source library; carry the category over by name
+        let source_category = b
+            .category_id
+            .and_then(|id| source_categories.iter().find(|c| c.id == Some(id)));
+        b.category_id = match source_category {
+            None => None,
+            Some(c) => match match_category(&target_categories, Some(&c.name_ru), Some(&c.name_en)) {
+                Some(id) => Some(id),
+                None => {
+                    let mut category = BranchCategory::new(target_library_id, c.name_ru.clone(), c.name_en.clone());
+                    category.sort_order = next_order;
+                    next_order += 1;
+                    category_repo.create(&mut category)?;
+                    let id = category.id;
+                    target_categories.push(category);
+                    id
+                }
+            },
+        };
         branch_repo.create(&mut b)?;
     }
     Ok(())
@@ -158,12 +461,52 @@ pub fn copy_formation_levels_between_libraries(
     Ok(())
 }
 
+/// Export an equipment catalog to a JSON file.
+pub fn export_equipment_catalog_to_path(path: &Path, entries: &[EquipmentCatalogEntry]) -> Result<()> {
+    let data: Vec<EquipmentCatalogExport> = entries
+        .iter()
+        .map(|e| EquipmentCatalogExport {
+            name_ru: e.name_ru.clone(),
+            name_en: e.name_en.clone(),
+            category: e.category.code().to_string(),
+            aliases: e.aliases.clone(),
+        })
+        .collect();
+    let file = EquipmentCatalogFile { equipment: data };
+    let json = serde_json::to_string_pretty(&file)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+/// Import an equipment catalog from a JSON file. Returns the list (without library_id); caller inserts into DB.
+pub fn import_equipment_catalog_from_path(path: &Path) -> Result<Vec<EquipmentCatalogExport>> {
+    let json = read_limited(path)?;
+    let file: EquipmentCatalogFile = serde_json::from_str(&json)?;
+    Ok(file.equipment)
+}
+
+/// Copy the equipment catalog from source library to target library (replaces target's).
+pub fn copy_equipment_catalog_between_libraries(
+    catalog_repo: &EquipmentCatalogRepo,
+    source_library_id: i64,
+    target_library_id: i64,
+) -> Result<()> {
+    let entries = catalog_repo.list_by_library(source_library_id)?;
+    catalog_repo.delete_by_library(target_library_id)?;
+    for mut e in entries {
+        e.id = None;
+        e.library_id = target_library_id;
+        catalog_repo.create(&mut e)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db::Database;
-    use crate::db::repositories::{BranchCategoryRepo, BranchRepo, FormationLevelRepo, LibraryRepo};
-    use crate::models::{BranchCategory, CustomFormationLevel, Library};
+    use crate::db::repositories::{BranchCategoryRepo, BranchRepo, EquipmentCatalogRepo, FormationLevelRepo, LibraryRepo};
+    use crate::models::{BranchCategory, CustomFormationLevel, EquipmentCatalogEntry, EquipmentCategory, Library};
     use tempfile::NamedTempFile;
 
     #[test]
@@ -194,8 +537,11 @@ mod tests {
     #[test]
     fn test_export_import_formation_levels_roundtrip() {
         let path = NamedTempFile::new().unwrap().into_temp_path();
+        let mut platoon = CustomFormationLevel::new(1, "взвод".to_string(), "platoon".to_string(), 3);
+        platoon.min_personnel = Some(25);
+        platoon.max_personnel = Some(45);
         let levels = vec![
-            CustomFormationLevel::new(1, "взвод".to_string(), "platoon".to_string(), 3),
+            platoon,
             CustomFormationLevel::new(1, "рота".to_string(), "company".to_string(), 4),
         ];
         export_formation_levels_to_path(path.as_ref(), &levels).unwrap();
@@ -203,7 +549,10 @@ mod tests {
         assert_eq!(imported.len(), 2);
         assert_eq!(imported[0].name_ru, "взвод");
         assert_eq!(imported[0].standard_level_ordinal, 3);
+        assert_eq!(imported[0].min_personnel, Some(25));
+        assert_eq!(imported[0].max_personnel, Some(45));
         assert_eq!(imported[1].name_en, "company");
+        assert_eq!(imported[1].min_personnel, None);
     }
 
     #[test]
@@ -219,7 +568,7 @@ mod tests {
         let id2 = lib2.id.unwrap();
         let mut b1 = Branch::new(id1, "Пехота".to_string(), "Infantry".to_string());
         branch_repo.create(&mut b1).unwrap();
-        copy_branches_between_libraries(&branch_repo, id1, id2).unwrap();
+        copy_branches_between_libraries(&branch_repo, &BranchCategoryRepo::new(db.conn()), id1, id2).unwrap();
         let target_branches = branch_repo.list_by_library(id2).unwrap();
         assert_eq!(target_branches.len(), 1);
         assert_eq!(target_branches[0].name_en, "Infantry");
@@ -379,7 +728,7 @@ mod tests {
         branch_repo.create(&mut source2).unwrap();
 
         // Copy should REPLACE target's branches
-        copy_branches_between_libraries(&branch_repo, id1, id2).unwrap();
+        copy_branches_between_libraries(&branch_repo, &BranchCategoryRepo::new(db.conn()), id1, id2).unwrap();
         let target_branches = branch_repo.list_by_library(id2).unwrap();
         assert_eq!(target_branches.len(), 2);
         assert!(target_branches.iter().all(|b| b.library_id == id2));
@@ -404,7 +753,7 @@ mod tests {
         let mut b = Branch::with_category(id1, Some(42), "Пехота".to_string(), "Infantry".to_string());
         branch_repo.create(&mut b).unwrap();
 
-        copy_branches_between_libraries(&branch_repo, id1, id2).unwrap();
+        copy_branches_between_libraries(&branch_repo, &BranchCategoryRepo::new(db.conn()), id1, id2).unwrap();
         let target = branch_repo.list_by_library(id2).unwrap();
         assert_eq!(target.len(), 1);
         // Category ID should be cleared because target library has different categories
@@ -428,7 +777,293 @@ mod tests {
         branch_repo.create(&mut b).unwrap();
 
         // Copy from empty source should clear target
-        copy_branches_between_libraries(&branch_repo, id1, id2).unwrap();
+        copy_branches_between_libraries(&branch_repo, &BranchCategoryRepo::new(db.conn()), id1, id2).unwrap();
         assert!(branch_repo.list_by_library(id2).unwrap().is_empty());
     }
+
+    // ---- Category-aware branch import ----
+
+    fn branch_export(ru: &str, en: &str, cat: Option<(&str, &str)>) -> BranchExport {
+        BranchExport {
+            name_ru: ru.to_string(),
+            name_en: en.to_string(),
+            category_name_ru: cat.map(|c| c.0.to_string()),
+            category_name_en: cat.map(|c| c.1.to_string()),
+        }
+    }
+
+    fn library_with_categories(db: &Database, cats: &[(&str, &str)]) -> (i64, Vec<BranchCategory>) {
+        let lib_repo = LibraryRepo::new(db.conn());
+        let cat_repo = BranchCategoryRepo::new(db.conn());
+        let mut library = Library::new("Lib".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        lib_repo.create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+        for (ru, en) in cats {
+            let mut c = BranchCategory::new(lib_id, ru.to_string(), en.to_string());
+            cat_repo.create(&mut c).unwrap();
+        }
+        (lib_id, cat_repo.list_by_library(lib_id).unwrap())
+    }
+
+    #[test]
+    fn test_import_old_format_branches_without_categories() {
+        let path = NamedTempFile::new().unwrap().into_temp_path();
+        let p: &std::path::Path = path.as_ref();
+        std::fs::write(p, r#"{"branches":[{"name_ru":"Пехота","name_en":"Infantry"}]}"#).unwrap();
+        let imported = import_branches_from_path(p).unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].category_name_en, None);
+
+        let db = Database::open_in_memory().unwrap();
+        let (lib_id, existing) = library_with_categories(&db, &[("Боевые", "Combat")]);
+        assert!(find_unmatched_categories(&imported, &existing).is_empty());
+        let resolved = resolve_branch_import(&existing, lib_id, &imported, &HashMap::new());
+        assert_eq!(resolved.branches[0].category_id, None);
+        assert!(resolved.new_categories.is_empty());
+    }
+
+    #[test]
+    fn test_export_with_categories_roundtrip_full_match() {
+        let db = Database::open_in_memory().unwrap();
+        let (lib_id, existing) = library_with_categories(&db, &[("Боевые", "Combat"), ("Тыл", "Support")]);
+        let branches = vec![
+            Branch::with_category(lib_id, existing[0].id, "Пехота".to_string(), "Infantry".to_string()),
+            Branch::with_category(lib_id, existing[1].id, "Связь".to_string(), "Signals".to_string()),
+        ];
+        let path = NamedTempFile::new().unwrap().into_temp_path();
+        export_branches_with_categories_to_path(path.as_ref(), &branches, &existing).unwrap();
+        let imported = import_branches_from_path(path.as_ref()).unwrap();
+        assert_eq!(imported[0].category_name_en.as_deref(), Some("Combat"));
+
+        let (target_id, target_cats) = library_with_categories(&db, &[("тыл", "Logistics"), ("Боевые", "Combat arms")]);
+        assert!(find_unmatched_categories(&imported, &target_cats).is_empty());
+        let resolved = resolve_branch_import(&target_cats, target_id, &imported, &HashMap::new()).branches;
+        // "Боевые" matches by Russian name, "Тыл" matches case-insensitively
+        assert_eq!(resolved[0].category_id, target_cats[1].id);
+        assert_eq!(resolved[1].category_id, target_cats[0].id);
+        assert!(resolved.iter().all(|b| b.library_id == target_id));
+    }
+
+    #[test]
+    fn test_import_with_mixed_resolutions() {
+        let db = Database::open_in_memory().unwrap();
+        let (lib_id, existing) = library_with_categories(&db, &[("Боевые", "Combat")]);
+        let imported = vec![
+            branch_export("Пехота", "Infantry", Some(("Боевые", "Combat"))),
+            branch_export("ПВО", "Air defense", Some(("ПВО", "Air defense"))),
+            branch_export("Связь", "Signals", Some(("Связь", "Communications"))),
+            branch_export("Инженеры", "Engineers", Some(("Инженерные", "Engineering"))),
+            branch_export("Радио", "Radio", Some(("Связь", "Communications"))),
+        ];
+        let unmatched = find_unmatched_categories(&imported, &existing);
+        assert_eq!(unmatched.len(), 3);
+
+        let mut resolutions = HashMap::new();
+        resolutions.insert(unmatched[0].clone(), CategoryResolution::Create);
+        resolutions.insert(unmatched[1].clone(), CategoryResolution::MapTo(existing[0].id.unwrap()));
+        // unmatched[2] left out: uncategorized
+
+        let ResolvedBranches { branches: resolved, new_categories } =
+            resolve_branch_import(&existing, lib_id, &imported, &resolutions);
+        // Nothing is stored until the caller saves
+        let cat_repo = BranchCategoryRepo::new(db.conn());
+        assert_eq!(cat_repo.list_by_library(lib_id).unwrap().len(), 1);
+        assert_eq!(new_categories.len(), 1);
+        assert_eq!(new_categories[0].name_en, "Air defense");
+        assert_eq!(resolved[0].category_id, existing[0].id);
+        assert_eq!(resolved[1].category_id, new_categories[0].id);
+        assert_eq!(resolved[2].category_id, existing[0].id);
+        assert_eq!(resolved[3].category_id, None);
+        assert_eq!(resolved[4].category_id, existing[0].id);
+    }
+
+    #[test]
+    fn test_branches_file_roundtrip_creates_categories() {
+        let db = Database::open_in_memory().unwrap();
+        let (source_id, source_cats) =
+            library_with_categories(&db, &[("Боевые", "Combat"), ("Тыл", "Support"), ("Резерв", "Reserve")]);
+        let branches = vec![
+            Branch::with_category(source_id, source_cats[0].id, "Пехота".to_string(), "Infantry".to_string()),
+            Branch::with_category(source_id, source_cats[1].id, "Связь".to_string(), "Signals".to_string()),
+        ];
+        let path = NamedTempFile::new().unwrap().into_temp_path();
+        export_branches_with_categories_to_path(path.as_ref(), &branches, &source_cats).unwrap();
+
+        let file = import_branches_file_from_path(path.as_ref()).unwrap();
+        assert_eq!(file.categories.len(), 3);
+        let (target_id, target_cats) = library_with_categories(&db, &[]);
+        let unmatched = file.unmatched_categories(&target_cats);
+        assert_eq!(unmatched.len(), 3);
+        let resolutions: HashMap<_, _> = unmatched.into_iter().map(|u| (u, CategoryResolution::Create)).collect();
+
+        let resolved = resolve_branches_file_import(&target_cats, target_id, &file, &resolutions);
+        let created = &resolved.new_categories;
+        let names: Vec<&str> = created.iter().map(|c| c.name_en.as_str()).collect();
+        // The unused "Reserve" category comes along, in the source order
+        assert_eq!(names, vec!["Combat", "Support", "Reserve"]);
+        assert_eq!(created.iter().map(|c| c.sort_order).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(resolved.branches[0].category_id, created[0].id);
+        assert_eq!(resolved.branches[1].category_id, created[1].id);
+        assert!(BranchCategoryRepo::new(db.conn()).list_by_library(target_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_branches_file_matches_existing_categories() {
+        let db = Database::open_in_memory().unwrap();
+        let (target_id, target_cats) = library_with_categories(&db, &[("боевые", "Combat arms")]);
+        let file = BranchesFile {
+            categories: vec![BranchCategoryExport {
+                name_ru: "Боевые".to_string(),
+                name_en: "Combat".to_string(),
+            }],
+            branches: vec![branch_export("Пехота", "Infantry", Some(("Боевые", "Combat")))],
+        };
+        assert!(file.unmatched_categories(&target_cats).is_empty());
+        let resolved = resolve_branches_file_import(&target_cats, target_id, &file, &HashMap::new());
+        assert_eq!(resolved.branches[0].category_id, target_cats[0].id);
+        assert!(resolved.new_categories.is_empty());
+    }
+
+    #[test]
+    fn test_old_format_file_has_no_categories() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), r#"{"branches":[{"name_ru":"Пехота","name_en":"Infantry"}]}"#).unwrap();
+        let imported = import_branches_file_from_path(file.path()).unwrap();
+        assert!(imported.categories.is_empty());
+        assert_eq!(imported.branches[0].name_en, "Infantry");
+        assert!(imported.unmatched_categories(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_copy_branches_carries_categories_by_name() {
+        let db = Database::open_in_memory().unwrap();
+        let branch_repo = BranchRepo::new(db.conn());
+        let cat_repo = BranchCategoryRepo::new(db.conn());
+        let (source_id, source_cats) = library_with_categories(&db, &[("Боевые", "Combat"), ("Тыл", "Support")]);
+        let (target_id, target_cats) = library_with_categories(&db, &[("Боевые", "Combat")]);
+        for (cat, en) in [(&source_cats[0], "Infantry"), (&source_cats[1], "Signals")] {
+            let mut b = Branch::with_category(source_id, cat.id, en.to_string(), en.to_string());
+            branch_repo.create(&mut b).unwrap();
+        }
+
+        copy_branches_between_libraries(&branch_repo, &cat_repo, source_id, target_id).unwrap();
+        let cats = cat_repo.list_by_library(target_id).unwrap();
+        assert_eq!(cats.len(), 2);
+        let support = cats.iter().find(|c| c.name_en == "Support").unwrap();
+        let copied = branch_repo.list_by_library(target_id).unwrap();
+        assert_eq!(copied[0].category_id, target_cats[0].id);
+        assert_eq!(copied[1].category_id, support.id);
+        // The source keeps its own categories
+        assert_eq!(cat_repo.list_by_library(source_id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_sort_order_preserved_through_copy_and_export() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_repo = LibraryRepo::new(db.conn());
+        let branch_repo = BranchRepo::new(db.conn());
+        let mut source = Library::new("S".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        let mut target = Library::new("T".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        lib_repo.create(&mut source).unwrap();
+        lib_repo.create(&mut target).unwrap();
+        let (source_id, target_id) = (source.id.unwrap(), target.id.unwrap());
+        // Inserted in one order, arranged in another
+        for (order, en) in [(2, "Logistics"), (0, "Infantry"), (1, "Armor")] {
+            let mut b = Branch::new(source_id, en.to_string(), en.to_string());
+            b.sort_order = order;
+            branch_repo.create(&mut b).unwrap();
+        }
+        let expected = vec!["Infantry", "Armor", "Logistics"];
+
+        copy_branches_between_libraries(&branch_repo, &BranchCategoryRepo::new(db.conn()), source_id, target_id).unwrap();
+        let copied: Vec<String> = branch_repo.list_by_library(target_id).unwrap().into_iter().map(|b| b.name_en).collect();
+        assert_eq!(copied, expected);
+
+        let path = NamedTempFile::new().unwrap().into_temp_path();
+        export_branches_to_path(path.as_ref(), &branch_repo.list_by_library(source_id).unwrap()).unwrap();
+        let imported = import_branches_from_path(path.as_ref()).unwrap();
+        let resolved = resolve_branch_import(&[], target_id, &imported, &HashMap::new()).branches;
+        let names: Vec<&str> = resolved.iter().map(|b| b.name_en.as_str()).collect();
+        assert_eq!(names, expected);
+        assert_eq!(resolved.iter().map(|b| b.sort_order).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_export_import_equipment_catalog_roundtrip() {
+        let path = NamedTempFile::new().unwrap().into_temp_path();
+        let mut entries = vec![
+            EquipmentCatalogEntry::new(1, "АК-74".to_string(), "AK-74".to_string(), EquipmentCategory::SmallArms),
+            EquipmentCatalogEntry::new(1, "БМП-2".to_string(), "BMP-2".to_string(), EquipmentCategory::Vehicles),
+            EquipmentCatalogEntry::new(1, "Р-168".to_string(), "R-168".to_string(), EquipmentCategory::Other),
+        ];
+        entries[0].aliases = vec!["Калашников".to_string()];
+        export_equipment_catalog_to_path(path.as_ref(), &entries).unwrap();
+        let imported = import_equipment_catalog_from_path(path.as_ref()).unwrap();
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported[0].name_ru, "АК-74");
+        assert_eq!(imported[0].category(), EquipmentCategory::SmallArms);
+        assert_eq!(imported[1].name_en, "BMP-2");
+        assert_eq!(imported[1].category(), EquipmentCategory::Vehicles);
+        assert_eq!(imported[2].category(), EquipmentCategory::Other);
+        assert_eq!(imported[0].to_entry(7).aliases, vec!["Калашников"]);
+        assert!(imported[1].aliases.is_empty());
+    }
+
+    #[test]
+    fn test_import_equipment_catalog_unknown_category() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            r#"{"equipment": [{"name_ru": "Ми-8", "name_en": "Mi-8", "category": "aircraft"}, {"name_ru": "", "name_en": "Tent"}]}"#,
+        )
+        .unwrap();
+        let imported = import_equipment_catalog_from_path(file.path()).unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!(imported[0].category(), EquipmentCategory::Other);
+        assert_eq!(imported[1].category(), EquipmentCategory::Other);
+    }
+
+    #[test]
+    fn test_import_equipment_catalog_invalid_json() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "{ not json").unwrap();
+        assert!(import_equipment_catalog_from_path(file.path()).is_err());
+        assert!(import_equipment_catalog_from_path(Path::new("/nonexistent/catalog.json")).is_err());
+    }
+
+    #[test]
+    fn test_copy_equipment_catalog_replaces_target() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_repo = LibraryRepo::new(db.conn());
+        let repo = EquipmentCatalogRepo::new(db.conn());
+        let mut lib1 = Library::new("Lib1".to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+        let mut lib2 = Library::new("Lib2".to_string(), "US".to_string(), "2003".to_string(), "B".to_string());
+        lib_repo.create(&mut lib1).unwrap();
+        lib_repo.create(&mut lib2).unwrap();
+        let id1 = lib1.id.unwrap();
+        let id2 = lib2.id.unwrap();
+        for (order, (ru, en, category)) in [
+            ("АК-74", "AK-74", EquipmentCategory::SmallArms),
+            ("БМП-2", "BMP-2", EquipmentCategory::Vehicles),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut e = EquipmentCatalogEntry::new(id1, ru.to_string(), en.to_string(), category);
+            e.sort_order = order as i64;
+            e.aliases = vec![format!("{} alias", en)];
+            repo.create(&mut e).unwrap();
+        }
+        let mut old = EquipmentCatalogEntry::new(id2, "".to_string(), "M16".to_string(), EquipmentCategory::SmallArms);
+        repo.create(&mut old).unwrap();
+
+        copy_equipment_catalog_between_libraries(&repo, id1, id2).unwrap();
+        let target = repo.list_by_library(id2).unwrap();
+        assert_eq!(target.len(), 2);
+        assert_eq!(target[0].name_en, "AK-74");
+        assert_eq!(target[1].category, EquipmentCategory::Vehicles);
+        assert_eq!(target[1].aliases, vec!["BMP-2 alias"]);
+        assert!(target.iter().all(|e| e.library_id == id2));
+        assert_eq!(repo.list_by_library(id1).unwrap().len(), 2);
+    }
 }
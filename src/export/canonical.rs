@@ -0,0 +1,58 @@
+//! Canonical JSON serialization (stable key ordering)
+//!
+//! Used wherever the same data must always produce the same bytes, e.g. per-version
+//! files kept under git, so that diffs only show real changes.
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Recursively rebuild a JSON value with object keys in sorted order.
+pub fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let sorted: BTreeMap<&String, Value> =
+                map.iter().map(|(k, v)| (k, canonicalize(v))).collect();
+            Value::Object(sorted.into_iter().map(|(k, v)| (k.clone(), v)).collect())
+        }
+        Value::Array(items) => Value::Array(items.iter().map(canonicalize).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Serialize any value as pretty-printed canonical JSON with a trailing newline.
+pub fn to_canonical_json_pretty<T: Serialize>(value: &T) -> Result<String> {
+    let value = canonicalize(&serde_json::to_value(value)?);
+    let mut out = serde_json::to_string_pretty(&value)?;
+    out.push('\n');
+    Ok(out)
+}
+
+/// Canonical pretty JSON for an already serialized JSON document.
+pub fn canonical_json_str_pretty(json: &str) -> Result<String> {
+    let value: Value = serde_json::from_str(json)?;
+    to_canonical_json_pretty(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_sorted_recursively() {
+        let a = canonical_json_str_pretty(r#"{"b":1,"a":{"z":1,"y":[{"d":1,"c":2}]}}"#).unwrap();
+        let b = canonical_json_str_pretty(r#"{"a":{"y":[{"c":2,"d":1}],"z":1},"b":1}"#).unwrap();
+        assert_eq!(a, b);
+        assert!(a.find("\"a\"").unwrap() < a.find("\"b\"").unwrap());
+        assert!(a.find("\"c\"").unwrap() < a.find("\"d\"").unwrap());
+    }
+
+    #[test]
+    fn test_array_order_is_preserved() {
+        let out = canonical_json_str_pretty(r#"[3,1,2]"#).unwrap();
+        let value: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value, serde_json::json!([3, 1, 2]));
+        assert!(out.ends_with('\n'));
+    }
+}
@@ -1,65 +1,185 @@
 //! CSV export functionality
+//!
+//! One file with a library row followed by three sections, each introduced
+//! by a row holding the section name and then a header row:
+//! units (parent, depth, personnel count, equipment summary), personnel per
+//! unit, and equipment per unit with a total row after each unit. When any
+//! unit has an effective period, the units section gains Effective From and
+//! Effective To columns, and a TOE Number column after them when any unit has
+//! a TOE number. Fields are quoted per RFC 4180 and records end with CRLF.
 
-use anyhow::Result;
-use crate::models::Library;
+use anyhow::{Context, Result};
+use crate::models::{walk_units, Library, Unit};
+use crate::services::metrics;
+use crate::services::summary::{SummaryRow, UnitSummary};
 use std::path::Path;
 
+/// Section names as written in the first column of a section row
+pub const UNITS_SECTION: &str = "Units";
+pub const PERSONNEL_SECTION: &str = "Personnel";
+pub const EQUIPMENT_SECTION: &str = "Equipment";
+
+/// Value of the Equipment column on the per-unit total row
+pub const TOTAL_LABEL: &str = "Total";
+
+/// Dialect of the written file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Field separator; spreadsheets in locales with a decimal comma expect `;`
+    pub delimiter: char,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self { delimiter: ',' }
+    }
+}
+
 /// Export library to CSV file.
-///
-/// Produces a CSV with library metadata header, then one row per unit
-/// listing personnel count and equipment summary.
 pub fn export_csv(library: &Library, path: &Path) -> Result<()> {
-    let mut lines = Vec::new();
-
-    // Header row
-    lines.push("Unit,Type,Parent,Personnel,Equipment".to_string());
-
-    fn write_units(
-        units: &[crate::models::Unit],
-        parent_name: &str,
-        lines: &mut Vec<String>,
-    ) {
-        for unit in units {
-            let personnel_count = unit.personnel.len();
-            let equipment_summary: Vec<String> = unit
-                .equipment
-                .iter()
-                .map(|e| format!("{}x{}", e.quantity, e.name))
-                .collect();
-            let eq_str = equipment_summary.join("; ");
-
-            // Escape CSV fields containing commas or quotes
-            let name = csv_escape(&unit.name);
-            let utype = csv_escape(&unit.unit_type);
-            let parent = csv_escape(parent_name);
-            let eq_escaped = csv_escape(&eq_str);
-
-            lines.push(format!(
-                "{},{},{},{},{}",
-                name, utype, parent, personnel_count, eq_escaped
-            ));
-
-            write_units(&unit.children, &unit.name, lines);
+    export_csv_with(library, path, &CsvOptions::default())
+}
+
+/// Export library to a CSV file in the dialect of `options`
+pub fn export_csv_with(library: &Library, path: &Path, options: &CsvOptions) -> Result<()> {
+    metrics::timed("export_csv", || {
+        std::fs::write(path, library_to_csv_with(library, options))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    })
+}
+
+/// CSV text for a library (see the module docs for the layout)
+pub fn library_to_csv(library: &Library) -> String {
+    library_to_csv_with(library, &CsvOptions::default())
+}
+
+/// CSV text for a library in the dialect of `options`
+pub fn library_to_csv_with(library: &Library, options: &CsvOptions) -> String {
+    let units = collect_units(&library.units);
+    let push_record = |out: &mut String, fields: &[&str]| push_record(out, fields, options.delimiter);
+
+    let mut out = String::new();
+    push_record(&mut out, &["Library", &library.name, &library.country, &library.era]);
+
+    push_record(&mut out, &[UNITS_SECTION]);
+    let dated = units
+        .iter()
+        .any(|(u, _, _)| u.effective_from.is_some() || u.effective_to.is_some());
+    let numbered = units.iter().any(|(u, _, _)| u.toe_number.is_some());
+    let mut header = vec!["Unit", "Type", "Parent", "Depth", "Personnel", "Equipment"];
+    if dated {
+        header.extend(["Effective From", "Effective To"]);
+    }
+    if numbered {
+        header.push("TOE Number");
+    }
+    push_record(&mut out, &header);
+    for (unit, parent, depth) in &units {
+        let equipment: Vec<String> = unit
+            .equipment
+            .iter()
+            .map(|e| format!("{}x{}", e.quantity, e.name))
+            .collect();
+        let depth = depth.to_string();
+        let personnel = unit.personnel.len().to_string();
+        let equipment = equipment.join("; ");
+        let mut record = vec![
+            unit.name.as_str(),
+            unit.unit_type.as_str(),
+            *parent,
+            depth.as_str(),
+            personnel.as_str(),
+            equipment.as_str(),
+        ];
+        if dated {
+            record.push(unit.effective_from.as_deref().unwrap_or(""));
+            record.push(unit.effective_to.as_deref().unwrap_or(""));
         }
+        if numbered {
+            record.push(unit.toe_number.as_deref().unwrap_or(""));
+        }
+        push_record(&mut out, &record);
     }
 
-    write_units(&library.units, "", &mut lines);
+    push_record(&mut out, &[PERSONNEL_SECTION]);
+    push_record(&mut out, &["Unit", "Position", "Rank"]);
+    for (unit, _, _) in &units {
+        for p in &unit.personnel {
+            push_record(&mut out, &[&unit.name, &p.position, p.rank.as_deref().unwrap_or("")]);
+        }
+    }
 
-    // If no units, still output the library info as a comment line
-    if library.units.is_empty() {
-        lines.push(format!(
-            "# Library: {} | Country: {} | Era: {}",
-            library.name, library.country, library.era
-        ));
+    push_record(&mut out, &[EQUIPMENT_SECTION]);
+    push_record(&mut out, &["Unit", "Equipment", "Quantity"]);
+    for (unit, _, _) in &units {
+        if unit.equipment.is_empty() {
+            continue;
+        }
+        for e in &unit.equipment {
+            push_record(&mut out, &[&unit.name, &e.name, &e.quantity.to_string()]);
+        }
+        let total: usize = unit.equipment.iter().map(|e| e.quantity).sum();
+        push_record(&mut out, &[&unit.name, TOTAL_LABEL, &total.to_string()]);
     }
+    out
+}
 
-    std::fs::write(path, lines.join("\n"))?;
-    Ok(())
+/// Write a formation's summary table to a CSV file
+pub fn export_summary_csv(summary: &UnitSummary, path: &Path) -> Result<()> {
+    std::fs::write(path, summary_to_csv(summary)).with_context(|| format!("Failed to write {}", path.display()))
 }
 
-/// Escape a string for CSV: wrap in quotes if it contains comma, quote, or newline.
-fn csv_escape(s: &str) -> String {
-    if s.contains(',') || s.contains('"') || s.contains('\n') {
+/// CSV text for a summary: a row with the formation and its personnel, then
+/// sections of rank, position and equipment totals and of subordinate units
+pub fn summary_to_csv(summary: &UnitSummary) -> String {
+    let mut out = String::new();
+    let push = |out: &mut String, fields: &[&str]| push_record(out, fields, ',');
+    push(&mut out, &["Formation", &summary.unit_name, &summary.personnel.to_string()]);
+    let sections: [(&str, &str, &[SummaryRow]); 3] = [
+        ("By Rank", "Rank", &summary.by_rank),
+        ("By Position", "Position", &summary.by_position),
+        (EQUIPMENT_SECTION, "Equipment", &summary.equipment),
+    ];
+    for (section, column, rows) in sections {
+        push(&mut out, &[section]);
+        push(&mut out, &[column, if column == "Equipment" { "Quantity" } else { "Personnel" }]);
+        for row in rows {
+            push(&mut out, &[&row.name, &row.count.to_string()]);
+        }
+    }
+    push(&mut out, &["Subordinate Units"]);
+    push(&mut out, &["Unit", "Personnel", "Equipment"]);
+    for child in &summary.children {
+        push(&mut out, &[&child.name, &child.personnel.to_string(), &child.equipment.to_string()]);
+    }
+    out
+}
+
+/// Units depth-first with their parent's name and depth
+fn collect_units(units: &[Unit]) -> Vec<(&Unit, &str, usize)> {
+    let mut out: Vec<(&Unit, &str, usize)> = Vec::new();
+    for visit in walk_units(units) {
+        let parent = visit.parent.map_or("", |p| out[p].0.name.as_str());
+        out.push((visit.unit, parent, visit.depth));
+    }
+    out
+}
+
+fn push_record(out: &mut String, fields: &[&str], delimiter: char) {
+    let escaped: Vec<String> = fields.iter().map(|f| escape_field(f, delimiter)).collect();
+    out.push_str(&escaped.join(delimiter.encode_utf8(&mut [0; 4])));
+    out.push_str("\r\n");
+}
+
+/// Escape a field per RFC 4180: quote it if it contains a comma, quote, CR or LF,
+/// doubling embedded quotes.
+pub fn csv_escape(s: &str) -> String {
+    escape_field(s, ',')
+}
+
+/// [`csv_escape`] for fields separated by `delimiter`
+fn escape_field(s: &str, delimiter: char) -> String {
+    if s.contains([delimiter, '"', '\n', '\r']) {
         format!("\"{}\"", s.replace('"', "\"\""))
     } else {
         s.to_string()
@@ -84,7 +204,7 @@ mod tests {
         export_csv(&library, file.path()).unwrap();
 
         let content = std::fs::read_to_string(file.path()).unwrap();
-        assert!(content.contains("Unit,Type,Parent,Personnel,Equipment"));
+        assert!(content.contains("Unit,Type,Parent,Depth,Personnel,Equipment"));
         assert!(content.contains("Test"));
     }
 
@@ -99,15 +219,69 @@ mod tests {
         let mut unit = Unit::new("1st Platoon".to_string(), "platoon".to_string());
         unit.personnel.push(Personnel::new("Platoon Leader".to_string()));
         unit.equipment.push(Equipment::new("Rifle".to_string(), 30));
+        unit.equipment.push(Equipment::new("Radio".to_string(), 2));
         library.units.push(unit);
 
         let file = NamedTempFile::new().unwrap();
         export_csv(&library, file.path()).unwrap();
 
         let content = std::fs::read_to_string(file.path()).unwrap();
-        assert!(content.contains("1st Platoon"));
-        assert!(content.contains("platoon"));
-        assert!(content.contains("30xRifle"));
+        assert!(content.contains("1st Platoon,platoon,,0,1,30xRifle; 2xRadio\r\n"));
+        assert!(content.contains("1st Platoon,Platoon Leader,\r\n"));
+        assert!(content.contains("1st Platoon,Total,32\r\n"));
+    }
+
+    #[test]
+    fn test_export_csv_effective_dates() {
+        let mut library = Library::new("Test".to_string(), "SU".to_string(), "1943".to_string(), "A".to_string());
+        let mut unit = Unit::new("Regiment".to_string(), "regiment".to_string());
+        unit.effective_from = Some("1943-01-01".to_string());
+        library.units.push(unit);
+        library.units.push(Unit::new("Reserve".to_string(), "regiment".to_string()));
+
+        let content = library_to_csv(&library);
+        assert!(content.contains("Unit,Type,Parent,Depth,Personnel,Equipment,Effective From,Effective To\r\n"));
+        assert!(content.contains("Regiment,regiment,,0,0,,1943-01-01,\r\n"));
+        assert!(content.contains("Reserve,regiment,,0,0,,,\r\n"));
+    }
+
+    #[test]
+    fn test_export_csv_toe_numbers() {
+        let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        let mut unit = Unit::new("1st Battalion".to_string(), "battalion".to_string());
+        unit.toe_number = Some("TOE 07015L000".to_string());
+        library.units.push(unit);
+
+        let content = library_to_csv(&library);
+        assert!(content.contains("Unit,Type,Parent,Depth,Personnel,Equipment,TOE Number\r\n"));
+        assert!(content.contains("1st Battalion,battalion,,0,0,,TOE 07015L000\r\n"));
+    }
+
+    #[test]
+    fn test_export_csv_deep_tree() {
+        let mut library = Library::new("Deep".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        library.units = crate::models::tree::unit_chain(1000);
+
+        let content = library_to_csv(&library);
+        assert!(content.contains("U0,unit,,0,0,\r\n"));
+        assert!(content.contains("U999,unit,U998,999,0,\r\n"));
+    }
+
+    #[test]
+    fn test_summary_csv() {
+        let mut company = Unit::new("A Company".to_string(), "company".to_string());
+        company.personnel.push(Personnel::with_rank("Commander".to_string(), "CPT".to_string()));
+        let mut platoon = Unit::new("1st Platoon".to_string(), "platoon".to_string());
+        platoon.personnel.push(Personnel::new("Rifleman, senior".to_string()));
+        platoon.equipment.push(Equipment::new("Rifle".to_string(), 30));
+        company.children.push(platoon);
+
+        let content = summary_to_csv(&crate::services::summary::summarize(&company));
+        assert!(content.starts_with("Formation,A Company,2\r\n"));
+        assert!(content.contains("By Rank\r\nRank,Personnel\r\nCPT,1\r\n,1\r\n"));
+        assert!(content.contains("\"Rifleman, senior\",1\r\n"));
+        assert!(content.contains("Equipment\r\nEquipment,Quantity\r\nRifle,30\r\n"));
+        assert!(content.ends_with("Unit,Personnel,Equipment\r\n1st Platoon,1,30\r\n"));
     }
 
     #[test]
@@ -124,4 +298,18 @@ mod tests {
     fn test_csv_escape_quotes() {
         assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
     }
+
+    #[test]
+    fn test_csv_escape_line_breaks() {
+        assert_eq!(csv_escape("a\r\nb"), "\"a\r\nb\"");
+    }
+
+    #[test]
+    fn test_semicolon_delimiter_quotes_semicolons_not_commas() {
+        let mut library = Library::new("Test".to_string(), "RU".to_string(), "1985".to_string(), "A".to_string());
+        library.units.push(Unit::new("1st Battalion; HQ".to_string(), "Battalion, rifle".to_string()));
+        let content = library_to_csv_with(&library, &CsvOptions { delimiter: ';' });
+        assert!(content.starts_with("Library;Test;RU;1985\r\n"));
+        assert!(content.contains("\"1st Battalion; HQ\";Battalion, rifle;;0;0;\r\n"));
+    }
 }
@@ -0,0 +1,197 @@
+//! One diagram per formation: every unit at a chosen level of a library's
+//! tree is drawn with its subtree to its own SVG file in a folder, named after
+//! the unit.
+
+use super::svg::{export_svg_pages, SvgOptions};
+use crate::models::{walk_units, Library, Unit};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Longest file name stem taken from a unit name, in characters
+const MAX_STEM_CHARS: usize = 80;
+
+/// What happened to one formation's diagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagramFileResult {
+    pub unit_name: String,
+    /// Files written (more than one when the diagram was split into pages),
+    /// or why writing failed
+    pub outcome: Result<Vec<PathBuf>, String>,
+}
+
+/// Results of a batch export, one per formation in tree order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiagramBatchReport {
+    pub results: Vec<DiagramFileResult>,
+}
+
+impl DiagramBatchReport {
+    /// Formations whose diagram was written
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|r| r.outcome.is_ok()).count()
+    }
+
+    /// Formations whose diagram could not be written
+    pub fn failed(&self) -> impl Iterator<Item = &DiagramFileResult> {
+        self.results.iter().filter(|r| r.outcome.is_err())
+    }
+}
+
+/// File name stem for a unit name: characters not allowed in file names on
+/// Windows, macOS or Linux become `_`, surrounding dots and spaces are
+/// trimmed and the result is cut to [`MAX_STEM_CHARS`]. Names with nothing
+/// left, or reserved on Windows, get a `_` prefix or become "unit".
+pub fn sanitize_file_stem(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"/\\|?*".contains(c) { '_' } else { c })
+        .take(MAX_STEM_CHARS)
+        .collect();
+    let stem = replaced.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if stem.is_empty() {
+        return "unit".to_string();
+    }
+    let device = stem.split('.').next().unwrap_or_default().to_ascii_uppercase();
+    let reserved = matches!(device.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || (device.len() == 4
+            && (device.starts_with("COM") || device.starts_with("LPT"))
+            && device.as_bytes()[3].is_ascii_digit());
+    if reserved {
+        format!("_{}", stem)
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Distinct file name stems for `names` in order: repeats get " (2)",
+/// " (3)", … Stems differing only in case count as repeats, since the file
+/// systems of Windows and macOS do not tell them apart.
+pub fn unique_file_stems<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut taken = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let base = sanitize_file_stem(name);
+            let mut stem = base.clone();
+            let mut n = 1;
+            while !taken.insert(stem.to_lowercase()) {
+                n += 1;
+                stem = format!("{} ({})", base, n);
+            }
+            stem
+        })
+        .collect()
+}
+
+/// Units `depth` levels below the top (0: the top-level units), in tree order
+pub fn formations_at_depth(units: &[Unit], depth: usize) -> Vec<&Unit> {
+    walk_units(units).filter(|v| v.depth == depth).map(|v| v.unit).collect()
+}
+
+/// Write a diagram of every formation `depth` levels below the top, with its
+/// subtree, to `<dir>/<unit name>.svg`. Each diagram is titled with the
+/// library and unit names and split into pages like a single diagram export.
+/// A formation that fails to export does not stop the others; the report
+/// has a result for each one.
+pub fn export_diagrams_per_formation(
+    library: &Library,
+    units: &[Unit],
+    dir: &Path,
+    depth: usize,
+    options: &SvgOptions,
+) -> DiagramBatchReport {
+    let formations = formations_at_depth(units, depth);
+    let stems = unique_file_stems(formations.iter().map(|u| u.name.as_str()));
+    let results = formations
+        .into_iter()
+        .zip(stems)
+        .map(|(unit, stem)| {
+            let titled = Library::new(
+                format!("{} — {}", library.name, unit.name),
+                library.country.clone(),
+                library.era.clone(),
+                library.author.clone(),
+            );
+            let path = dir.join(format!("{}.svg", stem));
+            let outcome = export_svg_pages(&titled, std::slice::from_ref(unit), &path, options)
+                .map_err(|e| format!("{:#}", e));
+            DiagramFileResult { unit_name: unit.name.clone(), outcome }
+        })
+        .collect();
+    DiagramBatchReport { results }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brigade(name: &str) -> Unit {
+        let mut unit = Unit::new(name.to_string(), "Brigade".to_string());
+        for battalion in ["1st Battalion", "2nd Battalion"] {
+            unit.add_child(Unit::new(battalion.to_string(), "Battalion".to_string()));
+        }
+        unit
+    }
+
+    #[test]
+    fn test_sanitize_file_stem() {
+        assert_eq!(sanitize_file_stem("1st Brigade"), "1st Brigade");
+        assert_eq!(sanitize_file_stem("A/B: \"HQ\"?"), "A_B_ _HQ__");
+        assert_eq!(sanitize_file_stem(" .. "), "unit");
+        assert_eq!(sanitize_file_stem("Штаб."), "Штаб");
+        assert_eq!(sanitize_file_stem("con"), "_con");
+        assert_eq!(sanitize_file_stem("COM1.old"), "_COM1.old");
+        assert_eq!(sanitize_file_stem("Company"), "Company");
+        assert_eq!(sanitize_file_stem(&"x".repeat(200)).chars().count(), MAX_STEM_CHARS);
+    }
+
+    #[test]
+    fn test_unique_file_stems_number_repeats() {
+        let stems = unique_file_stems(["Brigade", "brigade", "Brigade", "Brigade (2)", "A/B", "A_B"]);
+        assert_eq!(stems, ["Brigade", "brigade (2)", "Brigade (3)", "Brigade (2) (2)", "A_B", "A_B (2)"]);
+    }
+
+    #[test]
+    fn test_formations_at_depth() {
+        let units = vec![brigade("North"), brigade("South")];
+        let names = |depth| formations_at_depth(&units, depth).iter().map(|u| u.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(0), ["North", "South"]);
+        assert_eq!(names(1), ["1st Battalion", "2nd Battalion", "1st Battalion", "2nd Battalion"]);
+        assert!(names(2).is_empty());
+    }
+
+    #[test]
+    fn test_same_named_formations_get_their_own_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let library = Library::new("Corps".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        let units = vec![brigade("1st Brigade"), brigade("1st Brigade"), brigade("2/3 Brigade")];
+
+        let report = export_diagrams_per_formation(&library, &units, dir.path(), 0, &SvgOptions::default());
+        assert_eq!(report.results.len(), 3);
+        assert_eq!(report.succeeded(), 3);
+        assert_eq!(report.failed().count(), 0);
+        let files: Vec<PathBuf> = report.results.iter().flat_map(|r| r.outcome.clone().unwrap()).collect();
+        assert_eq!(
+            files,
+            ["1st Brigade.svg", "1st Brigade (2).svg", "2_3 Brigade.svg"].map(|name| dir.path().join(name))
+        );
+        let svg = std::fs::read_to_string(&files[1]).unwrap();
+        assert!(svg.contains("Corps — 1st Brigade"));
+        assert_eq!(svg.matches("Battalion</text>").count(), 2);
+    }
+
+    #[test]
+    fn test_failures_are_reported_per_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let library = Library::new("Corps".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        // A folder in the way of one file
+        std::fs::create_dir(dir.path().join("North.svg")).unwrap();
+        let units = vec![brigade("North"), brigade("South")];
+
+        let report = export_diagrams_per_formation(&library, &units, dir.path(), 0, &SvgOptions::default());
+        assert_eq!(report.succeeded(), 1);
+        let failed: Vec<&str> = report.failed().map(|r| r.unit_name.as_str()).collect();
+        assert_eq!(failed, ["North"]);
+        assert!(dir.path().join("South.svg").is_file());
+    }
+}
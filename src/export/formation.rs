@@ -0,0 +1,220 @@
+//! One formation as a standalone file: a unit with its subordinate units and
+//! the personnel and equipment of each, for moving a formation between
+//! libraries or databases.
+//!
+//! Units refer to their library's formation levels, which the file carries by
+//! name; an import maps them onto the target library's levels and reports the
+//! ones it cannot match.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+use super::branch_formation_io::FormationLevelExport;
+use crate::db::repositories::{FormationLevelRepo, LibraryRepo, UnitRepo};
+use crate::import::limits::read_limited;
+use crate::models::{for_each_unit_mut, tree_shape, walk_units, CustomFormationLevel, TreeLimitIssue, TreeLimits, Unit};
+
+/// Format version written by this build; bump when the layout changes
+pub const FORMATION_EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// A formation with everything below it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FormationExport {
+    pub format_version: u32,
+    /// Name of the library the formation was exported from
+    #[serde(default)]
+    pub source_library: String,
+    /// The formation, with personnel, equipment and subordinate units nested
+    pub unit: Unit,
+    /// Formation level of each unit of `unit`, in depth-first order (parents
+    /// before their children); missing entries mean no level
+    #[serde(default)]
+    pub unit_levels: Vec<Option<FormationLevelExport>>,
+}
+
+/// A unit whose formation level has no match in the target library; it is
+/// imported without a level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmatchedLevel {
+    pub unit_name: String,
+    /// The level as the file names it
+    pub level: String,
+}
+
+impl fmt::Display for UnmatchedLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\": no formation level \"{}\" in this library", self.unit_name, self.level)
+    }
+}
+
+/// Outcome of a formation import
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormationImport {
+    /// Id of the imported formation
+    pub unit_id: i64,
+    /// Units created, the formation included
+    pub unit_count: usize,
+    pub warnings: Vec<UnmatchedLevel>,
+    /// Soft tree limits the target tree goes past with the formation in it
+    pub limit_warnings: Vec<TreeLimitIssue>,
+}
+
+fn level_export(level: &CustomFormationLevel) -> FormationLevelExport {
+    FormationLevelExport {
+        name_ru: level.name_ru.clone(),
+        name_en: level.name_en.clone(),
+        standard_level_ordinal: level.standard_level_ordinal,
+        min_personnel: level.min_personnel,
+        max_personnel: level.max_personnel,
+    }
+}
+
+/// Level of `levels` named like `wanted` in either language, ignoring case
+fn match_level(levels: &[CustomFormationLevel], wanted: &FormationLevelExport) -> Option<i64> {
+    let same = |a: &str, b: &str| !a.trim().is_empty() && a.trim().to_lowercase() == b.trim().to_lowercase();
+    levels
+        .iter()
+        .find(|l| same(&l.name_en, &wanted.name_en) || same(&l.name_ru, &wanted.name_ru))
+        .and_then(|l| l.id)
+}
+
+/// Read the formation `unit_id` with everything below it
+pub fn formation_export(conn: &rusqlite::Connection, unit_id: i64) -> Result<FormationExport> {
+    let unit_repo = UnitRepo::new(conn);
+    let unit = unit_repo.get_by_id(unit_id)?.with_context(|| format!("Unit {} not found", unit_id))?;
+    let (library_id, _) = unit_repo.get_placement(unit_id)?.with_context(|| format!("Unit {} not found", unit_id))?;
+    let source_library = LibraryRepo::new(conn).get_by_id(library_id)?.map(|l| l.name).unwrap_or_default();
+    let levels = FormationLevelRepo::new(conn).list_by_library(library_id)?;
+    let mut unit_levels = Vec::new();
+    for visit in walk_units(std::slice::from_ref(&unit)) {
+        let level_id = match visit.unit.id {
+            Some(id) => unit_repo.formation_level(id)?,
+            None => None,
+        };
+        unit_levels.push(level_id.and_then(|id| levels.iter().find(|l| l.id == Some(id))).map(level_export));
+    }
+    Ok(FormationExport {
+        format_version: FORMATION_EXPORT_FORMAT_VERSION,
+        source_library,
+        unit,
+        unit_levels,
+    })
+}
+
+/// Write the formation `unit_id` with everything below it to `path` as JSON
+pub fn export_formation_json(unit_id: i64, conn: &rusqlite::Connection, path: &Path) -> Result<()> {
+    let export = formation_export(conn, unit_id)?;
+    let json = serde_json::to_string_pretty(&export)?;
+    std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Parse a formation file, refusing newer formats
+pub fn parse_formation_export(json: &str) -> Result<FormationExport> {
+    let value: serde_json::Value = serde_json::from_str(json).context("The file is not valid JSON")?;
+    let version = value
+        .get("format_version")
+        .and_then(|v| v.as_u64())
+        .context("The file is not a formation export")?;
+    if version == 0 || version > FORMATION_EXPORT_FORMAT_VERSION as u64 {
+        bail!(
+            "Unsupported formation file format version {} (this build reads up to {})",
+            version,
+            FORMATION_EXPORT_FORMAT_VERSION
+        );
+    }
+    serde_json::from_value(value).context("The file is not a formation export")
+}
+
+/// Re-create a formation under `target_parent_id` of `target_library_id`
+/// (at the top level when `None`), in one transaction. Units get new ids;
+/// formation levels are matched by name and left unset, with a warning, when
+/// the target library has no such level. Fails, importing nothing, when the
+/// target tree would go past a hard limit of `tree_limits`.
+pub fn import_formation(
+    export: &FormationExport,
+    target_library_id: i64,
+    target_parent_id: Option<i64>,
+    tree_limits: TreeLimits,
+    conn: &rusqlite::Connection,
+) -> Result<FormationImport> {
+    let unit_repo = UnitRepo::new(conn);
+    let levels_above = match target_parent_id {
+        Some(parent_id) => match unit_repo.get_placement(parent_id)? {
+            Some((library_id, _)) if library_id == target_library_id => unit_repo.count_levels(parent_id)?,
+            _ => bail!("Unit {} is not in library {}", parent_id, target_library_id),
+        },
+        None => 0,
+    };
+    let siblings = unit_repo.list_sibling_ids(target_library_id, target_parent_id)?.len() + 1;
+    let limit_warnings =
+        tree_limits.check(tree_shape(std::slice::from_ref(&export.unit)).placed_under(levels_above, siblings))?;
+    let levels = FormationLevelRepo::new(conn).list_by_library(target_library_id)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut unit = export.unit.clone();
+    unit.parent_id = target_parent_id;
+    // Level ids are those of the exporting library; levels go by name below
+    for_each_unit_mut(std::slice::from_mut(&mut unit), |u| u.formation_level_id = None);
+    unit_repo.create_tree(target_library_id, &mut unit)?;
+
+    let mut warnings = Vec::new();
+    let mut unit_count = 0;
+    for (i, visit) in walk_units(std::slice::from_ref(&unit)).enumerate() {
+        unit_count += 1;
+        let Some(Some(wanted)) = export.unit_levels.get(i) else {
+            continue;
+        };
+        match match_level(&levels, wanted) {
+            Some(level_id) => unit_repo.set_formation_level(visit.unit.id.unwrap_or_default(), Some(level_id))?,
+            None => warnings.push(UnmatchedLevel {
+                unit_name: visit.unit.name.clone(),
+                level: if wanted.name_en.trim().is_empty() { wanted.name_ru.clone() } else { wanted.name_en.clone() },
+            }),
+        }
+    }
+    tx.commit()?;
+    Ok(FormationImport {
+        unit_id: unit.id.unwrap_or_default(),
+        unit_count,
+        warnings,
+        limit_warnings,
+    })
+}
+
+/// Import the formation file at `path`; see [`import_formation`]
+pub fn import_formation_json(
+    path: &Path,
+    target_library_id: i64,
+    target_parent_id: Option<i64>,
+    tree_limits: TreeLimits,
+    conn: &rusqlite::Connection,
+) -> Result<FormationImport> {
+    let export = parse_formation_export(&read_limited(path)?)?;
+    import_formation(&export, target_library_id, target_parent_id, tree_limits, conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_refuses_other_files() {
+        let unit = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+        let export = FormationExport {
+            format_version: FORMATION_EXPORT_FORMAT_VERSION,
+            source_library: "Test".to_string(),
+            unit,
+            unit_levels: Vec::new(),
+        };
+        let json = serde_json::to_string(&export).unwrap();
+        assert_eq!(parse_formation_export(&json).unwrap(), export);
+
+        let newer = json.replace("\"format_version\":1", "\"format_version\":2");
+        let err = parse_formation_export(&newer).unwrap_err();
+        assert!(err.to_string().contains("Unsupported formation file format version 2"), "{}", err);
+        assert!(parse_formation_export("{\"name\": \"Library\"}").is_err());
+        assert!(parse_formation_export("not json").is_err());
+    }
+}
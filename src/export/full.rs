@@ -0,0 +1,158 @@
+//! Full library export: the library with its unit tree plus the per-library
+//! tables (formation levels, branch categories, branches) in one versioned file.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::models::{Branch, BranchCategory, CustomFormationLevel, Library};
+
+/// Format version written by this build; bump when the layout changes
+pub const FULL_EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Everything stored for one library.
+///
+/// Ids are the ones of the exporting database; branches reference categories
+/// by those ids and are remapped on import.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FullLibraryExport {
+    pub format_version: u32,
+    /// Library metadata with its units (personnel, equipment and children nested)
+    pub library: Library,
+    #[serde(default)]
+    pub formation_levels: Vec<CustomFormationLevel>,
+    #[serde(default)]
+    pub branch_categories: Vec<BranchCategory>,
+    #[serde(default)]
+    pub branches: Vec<Branch>,
+}
+
+/// Write a full export as pretty-printed JSON
+pub fn export_full_to_path(export: &FullLibraryExport, path: &Path) -> Result<()> {
+    let json = serde_json::to_string_pretty(export)?;
+    std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Parse a full export; `None` when the JSON has no `format_version` (a plain library file)
+pub fn parse_full_export(data: &serde_json::Value) -> Result<Option<FullLibraryExport>> {
+    let Some(version) = data.get("format_version") else {
+        return Ok(None);
+    };
+    let version = version
+        .as_u64()
+        .ok_or_else(|| anyhow::anyhow!("Invalid format_version: {}", version))?;
+    if version == 0 || version > FULL_EXPORT_FORMAT_VERSION as u64 {
+        bail!(
+            "Unsupported library file format version {} (this build reads up to {})",
+            version,
+            FULL_EXPORT_FORMAT_VERSION
+        );
+    }
+    Ok(Some(serde_json::from_value(data.clone())?))
+}
+
+/// What a stored snapshot holds: the full state of the library, or, for
+/// snapshots taken before they carried the per-library tables, the library
+/// with its units only
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotState {
+    Full(FullLibraryExport),
+    Library(Library),
+}
+
+impl SnapshotState {
+    /// Read snapshot data in either layout
+    pub fn from_data(data: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(data)?;
+        Ok(match parse_full_export(&value)? {
+            Some(export) => Self::Full(export),
+            None => Self::Library(serde_json::from_value(value)?),
+        })
+    }
+
+    /// The library with its units
+    pub fn library(&self) -> &Library {
+        match self {
+            Self::Full(export) => &export.library,
+            Self::Library(library) => library,
+        }
+    }
+
+    pub fn into_library(self) -> Library {
+        match self {
+            Self::Full(export) => export.library,
+            Self::Library(library) => library,
+        }
+    }
+}
+
+/// The library object of snapshot JSON: nested under `library` in full-state
+/// snapshots, the whole value in older ones
+pub fn snapshot_library_json(value: &serde_json::Value) -> &serde_json::Value {
+    match value.get("library") {
+        Some(library) if value.get("format_version").is_some() => library,
+        _ => value,
+    }
+}
+
+/// Mutable [`snapshot_library_json`]
+pub fn snapshot_library_json_mut(value: &mut serde_json::Value) -> &mut serde_json::Value {
+    if value.get("format_version").is_some() && value.get("library").is_some() {
+        &mut value["library"]
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> FullLibraryExport {
+        FullLibraryExport {
+            format_version: FULL_EXPORT_FORMAT_VERSION,
+            library: Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string()),
+            formation_levels: Vec::new(),
+            branch_categories: vec![BranchCategory::new(1, "Боевые".to_string(), "Combat".to_string())],
+            branches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_full_export_round_trip() {
+        let export = sample();
+        let value = serde_json::to_value(&export).unwrap();
+        assert_eq!(value["format_version"], 1);
+        assert_eq!(parse_full_export(&value).unwrap(), Some(export));
+    }
+
+    #[test]
+    fn test_parse_plain_library_is_not_full_export() {
+        let value = serde_json::to_value(&sample().library).unwrap();
+        assert!(parse_full_export(&value).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_rejects_newer_format() {
+        let mut value = serde_json::to_value(sample()).unwrap();
+        value["format_version"] = serde_json::json!(FULL_EXPORT_FORMAT_VERSION + 1);
+        let err = parse_full_export(&value).unwrap_err();
+        assert!(err.to_string().contains("Unsupported library file format version 2"));
+        value["format_version"] = serde_json::json!("one");
+        assert!(parse_full_export(&value).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_state_reads_both_layouts() {
+        let export = sample();
+        let full = SnapshotState::from_data(&serde_json::to_string(&export).unwrap()).unwrap();
+        assert_eq!(full, SnapshotState::Full(export.clone()));
+        let legacy = SnapshotState::from_data(&serde_json::to_string(&export.library).unwrap()).unwrap();
+        assert_eq!(legacy, SnapshotState::Library(export.library.clone()));
+        assert_eq!(full.library(), legacy.library());
+
+        let value = serde_json::to_value(&export).unwrap();
+        assert_eq!(snapshot_library_json(&value)["name"], "Test");
+        let value = serde_json::to_value(&export.library).unwrap();
+        assert_eq!(snapshot_library_json(&value)["name"], "Test");
+    }
+}
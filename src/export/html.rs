@@ -0,0 +1,711 @@
+//! Self-contained interactive HTML export
+//!
+//! Produces a single file with the unit tree embedded as JSON plus inline CSS/JS
+//! (collapsible tree, search-as-you-type, per-unit details). No external requests.
+//!
+//! The print variant ([`render_print_page`]) is a static page of one formation:
+//! a summary followed by a card per unit, styled for paper. Its equipment
+//! totals count aliases under the equipment catalog name.
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::db::repositories::{tree_as_of, tree_prefixes, EquipmentCatalogRepo, LibraryRepo, UnitRepo};
+use crate::models::{format_aliases, number_units, units_as_of, walk_units, EquipmentAliases, Library, NumberingOptions, Unit};
+use crate::services::metrics;
+use super::anonymize::{anonymize_library, AnonymizeOptions};
+
+/// Id of the `<script type="application/json">` element holding the data
+pub const DATA_ELEMENT_ID: &str = "toe-data";
+
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{LANG}}">
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+body { font-family: sans-serif; margin: 16px; color: #222; }
+h1 { font-size: 20px; margin: 0 0 4px 0; }
+.meta { color: #666; font-size: 13px; margin-bottom: 12px; }
+.toolbar { margin-bottom: 12px; display: flex; gap: 6px; }
+.toolbar input { flex: 1; max-width: 360px; padding: 4px 6px; }
+ul.tree { list-style: none; padding-left: 18px; margin: 0; }
+ul.tree.root { padding-left: 0; }
+.node { cursor: pointer; padding: 2px 4px; border-radius: 3px; display: inline-block; }
+.node:hover { background: #eef3fb; }
+.toggle { display: inline-block; width: 14px; color: #888; cursor: pointer; }
+.type { color: #888; font-size: 12px; margin-left: 6px; }
+.collapsed > ul { display: none; }
+.hidden { display: none; }
+.match > .node { background: #fff3b0; }
+#popover { position: fixed; right: 16px; top: 16px; width: 340px; max-height: 80vh; overflow: auto;
+  background: #fff; border: 1px solid #bbb; box-shadow: 0 2px 8px rgba(0,0,0,.2); padding: 10px; display: none; }
+#popover table { border-collapse: collapse; width: 100%; margin-bottom: 8px; font-size: 13px; }
+#popover th, #popover td { border: 1px solid #ddd; padding: 2px 4px; text-align: left; }
+#popover h2 { font-size: 16px; margin: 0 0 6px 0; }
+#popover h3 { font-size: 13px; margin: 8px 0 4px 0; }
+</style>
+</head>
+<body>
+<h1>{{TITLE}}</h1>
+<div class="meta">{{META}}</div>
+<div class="toolbar">
+<input id="search" type="search" placeholder="{{SEARCH_PLACEHOLDER}}">
+<button id="expand-all">{{EXPAND_ALL}}</button>
+<button id="collapse-all">{{COLLAPSE_ALL}}</button>
+</div>
+<ul class="tree root" id="tree"></ul>
+<div id="popover"></div>
+<script type="application/json" id="{{DATA_ID}}">{{DATA}}</script>
+<script>
+(function () {
+  var data = JSON.parse(document.getElementById("{{DATA_ID}}").textContent);
+  var labels = data.labels;
+  function el(tag, cls, text) {
+    var e = document.createElement(tag);
+    if (cls) e.className = cls;
+    if (text !== undefined) e.textContent = text;
+    return e;
+  }
+  function table(headers, rows) {
+    var t = el("table"), tr = el("tr");
+    headers.forEach(function (h) { tr.appendChild(el("th", null, h)); });
+    t.appendChild(tr);
+    rows.forEach(function (r) {
+      var row = el("tr");
+      r.forEach(function (c) { row.appendChild(el("td", null, c)); });
+      t.appendChild(row);
+    });
+    return t;
+  }
+  var popover = document.getElementById("popover");
+  function showDetails(unit) {
+    popover.innerHTML = "";
+    popover.appendChild(el("h2", null, unit.name));
+    popover.appendChild(el("div", "type", unit.toe_number ? unit.unit_type + " · " + unit.toe_number : unit.unit_type));
+    popover.appendChild(el("h3", null, labels.personnel + " (" + unit.personnel.length + ")"));
+    popover.appendChild(table([labels.position, labels.rank], unit.personnel.map(function (p) {
+      return [p.position, p.rank || ""];
+    })));
+    popover.appendChild(el("h3", null, labels.equipment));
+    popover.appendChild(table([labels.name, labels.quantity], unit.equipment.map(function (e) {
+      return [e.name, String(e.quantity)];
+    })));
+    var close = el("button", null, labels.close);
+    close.onclick = function () { popover.style.display = "none"; };
+    popover.appendChild(close);
+    popover.style.display = "block";
+  }
+  function build(units, parent) {
+    units.forEach(function (unit) {
+      var li = el("li");
+      li.dataset.search = (unit.name + " " + unit.unit_type + " " + (unit.toe_number || "")).toLowerCase();
+      var toggle = el("span", "toggle", unit.children.length ? "▾" : "");
+      toggle.onclick = function () {
+        li.classList.toggle("collapsed");
+        toggle.textContent = li.classList.contains("collapsed") ? "▸" : "▾";
+      };
+      var node = el("span", "node", unit.name);
+      node.appendChild(el("span", "type", unit.unit_type));
+      node.onclick = function () { showDetails(unit); };
+      li.appendChild(toggle);
+      li.appendChild(node);
+      if (unit.children.length) {
+        var ul = el("ul", "tree");
+        build(unit.children, ul);
+        li.appendChild(ul);
+      }
+      parent.appendChild(li);
+    });
+  }
+  var tree = document.getElementById("tree");
+  build(data.units, tree);
+  function setCollapsed(collapsed) {
+    tree.querySelectorAll("li").forEach(function (li) {
+      if (li.querySelector("ul")) {
+        li.classList.toggle("collapsed", collapsed);
+        li.firstChild.textContent = collapsed ? "▸" : "▾";
+      }
+    });
+  }
+  document.getElementById("expand-all").onclick = function () { setCollapsed(false); };
+  document.getElementById("collapse-all").onclick = function () { setCollapsed(true); };
+  function filter(li, q) {
+    var own = q === "" || li.dataset.search.indexOf(q) >= 0;
+    var child = false;
+    var ul = li.querySelector(":scope > ul");
+    if (ul) {
+      Array.prototype.forEach.call(ul.children, function (c) { if (filter(c, q)) child = true; });
+    }
+    li.classList.toggle("hidden", !(own || child));
+    li.classList.toggle("match", q !== "" && own);
+    if (q !== "" && child) li.classList.remove("collapsed");
+    return own || child;
+  }
+  document.getElementById("search").oninput = function (e) {
+    var q = e.target.value.trim().toLowerCase();
+    Array.prototype.forEach.call(tree.children, function (li) { filter(li, q); });
+  };
+})();
+</script>
+</body>
+</html>
+"#;
+
+/// Strings used by the page, in the export language
+#[derive(Debug, Clone, Serialize)]
+struct Labels {
+    search: &'static str,
+    expand_all: &'static str,
+    collapse_all: &'static str,
+    personnel: &'static str,
+    equipment: &'static str,
+    position: &'static str,
+    rank: &'static str,
+    name: &'static str,
+    quantity: &'static str,
+    close: &'static str,
+    summary: &'static str,
+    total_personnel: &'static str,
+    print_hint: &'static str,
+}
+
+impl Labels {
+    fn for_lang(lang: &str) -> Self {
+        if lang == "ru" {
+            Self {
+                search: "Поиск подразделения…",
+                expand_all: "Развернуть все",
+                collapse_all: "Свернуть все",
+                personnel: "Личный состав",
+                equipment: "Вооружение и техника",
+                position: "Должность",
+                rank: "Звание",
+                name: "Наименование",
+                quantity: "Количество",
+                close: "Закрыть",
+                summary: "Сводка",
+                total_personnel: "Всего личного состава",
+                print_hint: "Страница готова к печати: нажмите Ctrl+P (⌘P на macOS).",
+            }
+        } else {
+            Self {
+                search: "Search units…",
+                expand_all: "Expand all",
+                collapse_all: "Collapse all",
+                personnel: "Personnel",
+                equipment: "Equipment",
+                position: "Position",
+                rank: "Rank",
+                name: "Name",
+                quantity: "Quantity",
+                close: "Close",
+                summary: "Summary",
+                total_personnel: "Total personnel",
+                print_hint: "Ready to print: press Ctrl+P (⌘P on macOS).",
+            }
+        }
+    }
+}
+
+/// JSON blob embedded in the page
+#[derive(Serialize)]
+struct TreeData<'a> {
+    library: LibraryInfo<'a>,
+    units: &'a [Unit],
+    labels: Labels,
+}
+
+#[derive(Serialize)]
+struct LibraryInfo<'a> {
+    name: &'a str,
+    country: &'a str,
+    era: &'a str,
+    author: &'a str,
+    version: i64,
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Serialize to JSON that is safe inside a `<script>` element.
+fn script_safe_json<T: Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026"))
+}
+
+/// Subtitle of the interactive page, under the library name
+pub fn report_meta(library: &Library) -> String {
+    format!("{} · {} · {} · v{}", library.country, library.era, library.author, library.version)
+}
+
+/// Render the interactive page for a library and its unit tree.
+pub fn render_interactive_tree(library: &Library, units: &[Unit], lang: &str) -> Result<String> {
+    let labels = Labels::for_lang(lang);
+    let data = TreeData {
+        library: LibraryInfo {
+            name: &library.name,
+            country: &library.country,
+            era: &library.era,
+            author: &library.author,
+            version: library.version,
+        },
+        units,
+        labels: labels.clone(),
+    };
+    let meta = report_meta(library);
+    let replacements: [(&str, String); 8] = [
+        ("{{LANG}}", if lang == "ru" { "ru" } else { "en" }.to_string()),
+        ("{{TITLE}}", escape_html(&library.name)),
+        ("{{META}}", escape_html(&meta)),
+        ("{{SEARCH_PLACEHOLDER}}", escape_html(labels.search)),
+        ("{{EXPAND_ALL}}", escape_html(labels.expand_all)),
+        ("{{COLLAPSE_ALL}}", escape_html(labels.collapse_all)),
+        ("{{DATA_ID}}", DATA_ELEMENT_ID.to_string()),
+        ("{{DATA}}", script_safe_json(&data)?),
+    ];
+    Ok(fill_template(TEMPLATE, &replacements))
+}
+
+/// Substitute the placeholders of `template` in one pass, left to right, so
+/// a value that looks like a placeholder is left as it is
+fn fill_template(template: &str, replacements: &[(&str, String)]) -> String {
+    let mut html = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        html.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        match replacements.iter().find(|(placeholder, _)| tail.starts_with(placeholder)) {
+            Some((placeholder, value)) => {
+                html.push_str(value);
+                rest = &tail[placeholder.len()..];
+            }
+            None => {
+                html.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    html.push_str(rest);
+    html
+}
+
+const PRINT_STYLE: &str = "body { font-family: sans-serif; margin: 16px; color: #000; font-size: 12px; }
+h1 { font-size: 20px; margin: 0 0 4px 0; }
+h2 { font-size: 15px; margin: 16px 0 6px 0; }
+h3 { font-size: 13px; margin: 0 0 4px 0; }
+.meta { color: #444; margin-bottom: 12px; }
+.type { color: #555; font-weight: normal; margin-left: 6px; }
+.print-hint { background: #fff3b0; border: 1px solid #e0c860; padding: 6px 10px; margin-bottom: 12px; }
+.card { border: 1px solid #999; padding: 6px 8px; margin-bottom: 8px; break-inside: avoid; }
+table { border-collapse: collapse; margin-bottom: 4px; }
+th, td { border: 1px solid #bbb; padding: 2px 6px; text-align: left; }
+td.number { text-align: right; }
+@page { margin: 15mm; }
+@media print { body { margin: 0; } .print-hint { display: none; } }";
+
+/// Unit type followed by the TOE number, if the unit has one
+fn type_and_toe_number(unit: &Unit) -> String {
+    match &unit.toe_number {
+        Some(number) => format!("{} · {}", unit.unit_type, number),
+        None => unit.unit_type.clone(),
+    }
+}
+
+/// Render a print-ready page of `formation` and its subordinate units.
+pub fn render_print_page(library: &Library, formation: &Unit, aliases: &EquipmentAliases, lang: &str) -> String {
+    let labels = Labels::for_lang(lang);
+    let mut html = String::new();
+    html.push_str(&format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n",
+        if lang == "ru" { "ru" } else { "en" },
+        escape_html(&formation.name),
+        PRINT_STYLE
+    ));
+    html.push_str(&format!("<div class=\"print-hint\">{}</div>\n", escape_html(labels.print_hint)));
+    html.push_str(&format!(
+        "<h1>{}<span class=\"type\">{}</span></h1>\n",
+        escape_html(&formation.name),
+        escape_html(&type_and_toe_number(formation))
+    ));
+    let meta = format!("{} · {} · {} · v{}", library.name, library.country, library.era, library.version);
+    html.push_str(&format!("<div class=\"meta\">{}</div>\n", escape_html(&meta)));
+
+    html.push_str(&format!("<h2>{}</h2>\n", escape_html(labels.summary)));
+    html.push_str(&format!(
+        "<p>{}: {}</p>\n",
+        escape_html(labels.total_personnel),
+        formation.total_personnel()
+    ));
+    let mut equipment: Vec<(String, usize)> = formation.total_equipment_resolved(aliases, lang).into_iter().collect();
+    equipment.sort();
+    if !equipment.is_empty() {
+        push_equipment_totals(&mut html, &equipment, aliases, &labels);
+    }
+
+    for visit in walk_units(std::slice::from_ref(formation)) {
+        let unit = visit.unit;
+        html.push_str(&format!(
+            "<section class=\"card\" style=\"margin-left: {}px\">\n<h3>{}<span class=\"type\">{}</span></h3>\n",
+            visit.depth * 16,
+            escape_html(&unit.name),
+            escape_html(&type_and_toe_number(unit))
+        ));
+        if !unit.personnel.is_empty() {
+            push_table(
+                &mut html,
+                &[labels.position, labels.rank],
+                unit.personnel
+                    .iter()
+                    .map(|p| vec![p.position.clone(), p.rank.clone().unwrap_or_default()]),
+            );
+        }
+        if !unit.equipment.is_empty() {
+            push_table(
+                &mut html,
+                &[labels.equipment, labels.quantity],
+                unit.equipment.iter().map(|e| vec![e.name.clone(), e.quantity.to_string()]),
+            );
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Append a table; the last column is right-aligned when it is a quantity
+fn push_table(html: &mut String, headers: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    html.push_str("<table>\n<tr>");
+    for header in headers {
+        html.push_str(&format!("<th>{}</th>", escape_html(header)));
+    }
+    html.push_str("</tr>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for (i, cell) in row.iter().enumerate() {
+            let numeric = i + 1 == row.len() && !cell.is_empty() && cell.chars().all(|c| c.is_ascii_digit());
+            let class = if numeric { " class=\"number\"" } else { "" };
+            html.push_str(&format!("<td{}>{}</td>", class, escape_html(cell)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+/// Append the equipment totals; the aliases of a catalog item are the
+/// tooltip of its name
+fn push_equipment_totals(html: &mut String, totals: &[(String, usize)], aliases: &EquipmentAliases, labels: &Labels) {
+    html.push_str(&format!(
+        "<table>\n<tr><th>{}</th><th>{}</th></tr>\n",
+        escape_html(labels.equipment),
+        escape_html(labels.quantity)
+    ));
+    for (name, qty) in totals {
+        let title = aliases
+            .resolve(name)
+            .map(|entry| format_aliases(&entry.aliases))
+            .filter(|text| !text.is_empty())
+            .map(|text| format!(" title=\"{}\"", escape_html(&text)))
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<tr><td{}>{}</td><td class=\"number\">{}</td></tr>\n",
+            title,
+            escape_html(name),
+            qty
+        ));
+    }
+    html.push_str("</table>\n");
+}
+
+/// Write the print page of formation `unit_id`, optionally only the units
+/// valid at `as_of`, with names numbered as the formation tree numbers them.
+pub fn export_print_page(
+    conn: &Connection,
+    unit_id: i64,
+    lang: &str,
+    as_of: Option<NaiveDate>,
+    numbering: &NumberingOptions,
+    path: &Path,
+) -> Result<()> {
+    let units = UnitRepo::new(conn);
+    let (library_id, _) = units
+        .get_placement(unit_id)?
+        .with_context(|| format!("Formation {} not found", unit_id))?;
+    let library = LibraryRepo::new(conn)
+        .get_by_id(library_id)?
+        .with_context(|| format!("Library {} not found", library_id))?;
+    let mut formation = units
+        .get_by_id(unit_id)?
+        .with_context(|| format!("Formation {} not found", unit_id))?;
+    if let Some(date) = as_of {
+        formation.children = units_as_of(&formation.children, date);
+    }
+    if numbering.enabled {
+        let mut nodes = units.list_tree_by_library(library_id)?;
+        if let Some(date) = as_of {
+            nodes = tree_as_of(nodes, date);
+        }
+        let prefix = tree_prefixes(&nodes, numbering).remove(&unit_id).unwrap_or_default();
+        number_units(&mut formation.children, numbering);
+        formation.name = format!("{}{}", prefix, formation.name);
+    }
+    let catalog = EquipmentCatalogRepo::new(conn).list_by_library(library_id)?;
+    std::fs::write(path, render_print_page(&library, &formation, &EquipmentAliases::new(&catalog), lang))?;
+    Ok(())
+}
+
+/// Export a library's unit tree as a self-contained interactive HTML file,
+/// optionally only the units valid at `as_of`, with names numbered by
+/// `numbering`. With `anonymize` the library goes through the same
+/// anonymization pass as the JSON export.
+pub fn export_interactive_tree(
+    conn: &Connection,
+    library_id: i64,
+    lang: &str,
+    as_of: Option<NaiveDate>,
+    numbering: &NumberingOptions,
+    anonymize: Option<&AnonymizeOptions>,
+    path: &Path,
+) -> Result<()> {
+    let mut library = LibraryRepo::new(conn)
+        .get_by_id(library_id)?
+        .with_context(|| format!("Library {} not found", library_id))?;
+    metrics::timed("export_html", || {
+        let mut units = UnitRepo::new(conn).get_by_library_id(library_id)?;
+        if let Some(date) = as_of {
+            units = units_as_of(&units, date);
+        }
+        number_units(&mut units, numbering);
+        if let Some(options) = anonymize {
+            library.units = units;
+            library = anonymize_library(library, options);
+            units = std::mem::take(&mut library.units);
+        }
+        let html = render_interactive_tree(&library, &units, lang)?;
+        std::fs::write(path, html)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::models::{Equipment, Personnel};
+
+    fn fixture(db: &Database) -> i64 {
+        let mut library = Library::new(
+            "Test <Army>".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+        let repo = UnitRepo::new(db.conn());
+        let mut company = Unit::new("A Company".to_string(), "Company".to_string());
+        company.personnel.push(Personnel::new("Commander".to_string()));
+        repo.create(lib_id, &mut company).unwrap();
+        let mut platoon = Unit::new("1st Platoon </script>".to_string(), "Platoon".to_string());
+        platoon.parent_id = company.id;
+        platoon.equipment.push(Equipment::new("M4 Carbine".to_string(), 30));
+        repo.create(lib_id, &mut platoon).unwrap();
+        lib_id
+    }
+
+    fn embedded_json(html: &str) -> serde_json::Value {
+        let marker = format!("id=\"{}\">", DATA_ELEMENT_ID);
+        let start = html.find(&marker).unwrap() + marker.len();
+        let end = start + html[start..].find("</script>").unwrap();
+        serde_json::from_str(&html[start..end]).unwrap()
+    }
+
+    #[test]
+    fn test_embedded_json_matches_library() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = fixture(&db);
+        let html = render_interactive_tree(
+            &LibraryRepo::new(db.conn()).get_by_id(lib_id).unwrap().unwrap(),
+            &UnitRepo::new(db.conn()).get_by_library_id(lib_id).unwrap(),
+            "en",
+        )
+        .unwrap();
+        let data = embedded_json(&html);
+        assert_eq!(data["library"]["name"], "Test <Army>");
+        let units: Vec<Unit> = serde_json::from_value(data["units"].clone()).unwrap();
+        assert_eq!(units, UnitRepo::new(db.conn()).get_by_library_id(lib_id).unwrap());
+        assert_eq!(units[0].children[0].equipment[0].quantity, 30);
+        assert_eq!(data["labels"]["personnel"], "Personnel");
+    }
+
+    /// `{{NAME}}` tokens in `text`: upper-case letters and underscores in
+    /// double braces. Script code may have `}}` of its own, so only whole
+    /// tokens count.
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut found = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            let after = &rest[start + 2..];
+            let name_len = after.find(|c: char| !(c.is_ascii_uppercase() || c == '_')).unwrap_or(after.len());
+            if name_len > 0 && after[name_len..].starts_with("}}") {
+                found.push(&rest[start..start + 2 + name_len + 2]);
+            }
+            rest = after;
+        }
+        found
+    }
+
+    #[test]
+    fn test_all_placeholders_substituted() {
+        let keys = placeholders(TEMPLATE);
+        for key in ["{{LANG}}", "{{TITLE}}", "{{META}}", "{{DATA_ID}}", "{{DATA}}"] {
+            assert!(keys.contains(&key), "{} missing from the template", key);
+        }
+        let library = Library::new("L".to_string(), "RU".to_string(), "1985".to_string(), "A".to_string());
+        for lang in ["en", "ru"] {
+            let html = render_interactive_tree(&library, &[], lang).unwrap();
+            assert_eq!(placeholders(&html), Vec::<&str>::new(), "unsubstituted placeholders for {}", lang);
+            for key in &keys {
+                assert!(!html.contains(key), "{} left in the {} export", key, lang);
+            }
+        }
+        let html = render_interactive_tree(&library, &[], "ru").unwrap();
+        assert!(html.contains("Поиск подразделения"));
+    }
+
+    #[test]
+    fn test_values_that_look_like_placeholders_stay_as_they_are() {
+        let mut library = Library::new("{{DATA}}".to_string(), "RU".to_string(), "1985".to_string(), "{{TITLE}}".to_string());
+        library.version = 2;
+        let html = render_interactive_tree(&library, &[], "en").unwrap();
+        let data = embedded_json(&html);
+        assert_eq!(data["library"]["name"], "{{DATA}}");
+        assert_eq!(data["library"]["author"], "{{TITLE}}");
+        assert!(html.contains("<title>{{DATA}}</title>"));
+        assert!(html.contains("<h1>{{DATA}}</h1>"));
+        assert!(html.contains("RU · 1985 · {{TITLE}} · v2"));
+        assert_eq!(html.matches("<script type=\"application/json\"").count(), 1);
+    }
+
+    #[test]
+    fn test_export_is_self_contained() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = fixture(&db);
+        let file = tempfile::NamedTempFile::new().unwrap();
+        export_interactive_tree(db.conn(), lib_id, "en", None, &NumberingOptions::default(), None, file.path()).unwrap();
+        let html = std::fs::read_to_string(file.path()).unwrap();
+        assert!(!html.contains("http://") && !html.contains("https://"));
+        assert!(!html.contains("src="));
+        // Unit names cannot break out of the data element
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert!(html.contains("&lt;Army&gt;"));
+    }
+
+    #[test]
+    fn test_export_anonymized_hides_the_author() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = fixture(&db);
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let options = AnonymizeOptions { placeholder_author: "TOE Team".to_string(), ..AnonymizeOptions::default() };
+        export_interactive_tree(db.conn(), lib_id, "en", None, &NumberingOptions::default(), Some(&options), file.path()).unwrap();
+        let html = std::fs::read_to_string(file.path()).unwrap();
+        assert!(!html.contains("Author"));
+        assert!(html.contains("US · 2003 · TOE Team"));
+        let data = embedded_json(&html);
+        assert_eq!(data["library"]["author"], "TOE Team");
+        assert_eq!(data["units"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_export_as_of_leaves_out_expired_units() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = fixture(&db);
+        let repo = UnitRepo::new(db.conn());
+        let mut company = repo.get_by_library_id(lib_id).unwrap().remove(0);
+        company.effective_to = Some("2003-12-31".to_string());
+        repo.update_properties(&company).unwrap();
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let as_of = NaiveDate::from_ymd_opt(2004, 1, 1);
+        export_interactive_tree(db.conn(), lib_id, "en", as_of, &NumberingOptions::default(), None, file.path()).unwrap();
+        let data = embedded_json(&std::fs::read_to_string(file.path()).unwrap());
+        assert_eq!(data["units"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_print_page_has_cards_and_summary() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = fixture(&db);
+        let company = UnitRepo::new(db.conn()).get_by_library_id(lib_id).unwrap().remove(0);
+        let file = tempfile::NamedTempFile::new().unwrap();
+        export_print_page(db.conn(), company.id.unwrap(), "en", None, &NumberingOptions::default(), file.path()).unwrap();
+        let html = std::fs::read_to_string(file.path()).unwrap();
+
+        assert_eq!(html.matches("<section class=\"card\"").count(), 2);
+        assert!(html.contains("Total personnel: 1"));
+        assert!(html.contains("<td>M4 Carbine</td><td class=\"number\">30</td>"));
+        assert!(html.contains("1st Platoon &lt;/script&gt;"));
+        assert!(html.contains("@media print"));
+        // Static page: no scripts, nothing fetched
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("http://") && !html.contains("https://"));
+    }
+
+    #[test]
+    fn test_exports_number_formations() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = fixture(&db);
+        let company = UnitRepo::new(db.conn()).get_by_library_id(lib_id).unwrap().remove(0);
+        let numbering = NumberingOptions::with_style(crate::models::NumberingStyle::Roman);
+        let file = tempfile::NamedTempFile::new().unwrap();
+        export_print_page(db.conn(), company.id.unwrap(), "en", None, &numbering, file.path()).unwrap();
+        let html = std::fs::read_to_string(file.path()).unwrap();
+        assert!(html.contains("I. A Company"));
+        assert!(html.contains("I. 1st Platoon &lt;/script&gt;"));
+
+        export_interactive_tree(db.conn(), lib_id, "en", None, &numbering, None, file.path()).unwrap();
+        let data = embedded_json(&std::fs::read_to_string(file.path()).unwrap());
+        assert!(data["units"][0]["name"].as_str().unwrap().starts_with("I. "));
+    }
+
+    #[test]
+    fn test_print_page_language_and_hint() {
+        let library = Library::new("L".to_string(), "RU".to_string(), "1985".to_string(), "A".to_string());
+        let unit = Unit::new("Полк".to_string(), "Regiment".to_string());
+        let html = render_print_page(&library, &unit, &EquipmentAliases::new(&[]), "ru");
+        assert!(html.contains("<html lang=\"ru\">"));
+        assert!(html.contains("Страница готова к печати"));
+        assert!(html.contains(".print-hint { display: none; }"));
+    }
+
+    #[test]
+    fn test_print_page_totals_use_catalog_names() {
+        use crate::models::{EquipmentCatalogEntry, EquipmentCategory};
+        let library = Library::new("L".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        let mut unit = Unit::new("Company".to_string(), "Company".to_string());
+        unit.equipment.push(Equipment::new("M4 Carbine".to_string(), 30));
+        unit.equipment.push(Equipment::new("m4".to_string(), 2));
+        let mut m4 = EquipmentCatalogEntry::new(1, "М4".to_string(), "M4".to_string(), EquipmentCategory::SmallArms);
+        m4.aliases = vec!["M4 Carbine".to_string(), "\"Colt\"".to_string()];
+        let catalog = vec![m4];
+        let html = render_print_page(&library, &unit, &EquipmentAliases::new(&catalog), "en");
+        assert!(html.contains("<td title=\"M4 Carbine; &quot;Colt&quot;\">M4</td><td class=\"number\">32</td>"));
+    }
+
+    #[test]
+    fn test_export_missing_library_fails() {
+        let db = Database::open_in_memory().unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert!(export_interactive_tree(db.conn(), 42, "en", None, &NumberingOptions::default(), None, file.path()).is_err());
+    }
+}
@@ -1,8 +1,16 @@
 //! JSON export functionality
+//!
+//! A library is written inside an envelope naming the app version that
+//! wrote it and when: `{ "toeditor_version", "exported_at", "library" }`,
+//! plus `"versions"` when snapshots are included. Import also accepts bare
+//! library files written before the envelope.
 
-use anyhow::Result;
-use crate::models::Library;
+use anyhow::{bail, Context, Result};
+use crate::models::{Library, Snapshot};
 use crate::db::repositories::VersionRepo;
+use crate::services::metrics;
+use super::anonymize::{anonymize_full, anonymize_versions, AnonymizeOptions};
+use super::full::FullLibraryExport;
 use std::path::Path;
 
 /// Export options for library export
@@ -14,9 +22,101 @@ pub enum ExportMode {
     WithAllVersions,
 }
 
+/// How a library is written to JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Indented for reading, rather than on one line
+    pub pretty: bool,
+    /// Include the library's version snapshots
+    pub include_versions: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self { pretty: true, include_versions: false }
+    }
+}
+
+/// App version and time recorded in an export envelope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportInfo {
+    pub toeditor_version: String,
+    /// Unix timestamp, seconds
+    pub exported_at: i64,
+}
+
+impl ExportInfo {
+    /// This build, now
+    pub fn current() -> Self {
+        Self {
+            toeditor_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Envelope fields of parsed export `data`; `None` for bare libraries
+    pub fn from_value(data: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            toeditor_version: data.get("toeditor_version")?.as_str()?.to_string(),
+            exported_at: data.get("exported_at").and_then(|t| t.as_i64()).unwrap_or(0),
+        })
+    }
+}
+
 /// Export library to JSON file
 pub fn export_json(library: &Library, path: &Path) -> Result<()> {
-    export_json_with_mode(library, path, ExportMode::LatestOnly, None)
+    metrics::timed("export_json", || {
+        export_json_with_options(library, path, &ExportOptions::default(), None)
+    })
+}
+
+/// File format of a library export, chosen by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryFileFormat {
+    Json,
+    Yaml,
+}
+
+impl LibraryFileFormat {
+    /// Format for a path: `.json` (or no extension) and `.yaml`/`.yml`
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "" | "json" => Ok(Self::Json),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            other => bail!("Unsupported library file extension: .{}", other),
+        }
+    }
+}
+
+/// Export library to a file, inferring JSON or YAML from the extension
+pub fn export_library_to_path(library: &Library, path: &Path) -> Result<()> {
+    match LibraryFileFormat::from_path(path)? {
+        LibraryFileFormat::Json => {
+            export_json(library, path).with_context(|| format!("Failed to write {}", path.display()))
+        }
+        LibraryFileFormat::Yaml => super::yaml::export_yaml(library, path),
+    }
+}
+
+/// Write a full export with the author and notes stripped, for sharing
+/// publicly. The anonymized `versions` follow it under `"versions"` unless
+/// `options` drop them; import reads the file like any full export.
+pub fn export_json_anonymized(
+    export: FullLibraryExport,
+    versions: Vec<Snapshot>,
+    path: &Path,
+    options: &AnonymizeOptions,
+) -> Result<()> {
+    let mut data = serde_json::to_value(anonymize_full(export, options))?;
+    if options.include_versions {
+        data["versions"] = serde_json::to_value(anonymize_versions(versions, options))?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&data)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
 }
 
 /// Export library to JSON file with version control support
@@ -26,36 +126,50 @@ pub fn export_json_with_mode(
     mode: ExportMode,
     version_repo: Option<&VersionRepo>,
 ) -> Result<()> {
-    match mode {
-        ExportMode::LatestOnly => {
-            let json = serde_json::to_string_pretty(library)?;
-            std::fs::write(path, json)?;
-        }
-        ExportMode::WithAllVersions => {
-            let mut export_data = serde_json::json!({
-                "library": library,
-                "versions": serde_json::Value::Null,
-            });
-            
-            if let Some(repo) = version_repo {
-                if let Some(lib_id) = library.id {
-                    let snapshots = repo.list_by_library(lib_id)?;
-                    let versions: Vec<_> = snapshots.iter().map(|s| {
-                        serde_json::json!({
-                            "version": s.version,
-                            "timestamp": s.timestamp,
-                            "description": s.description,
-                            "data": s.data,
-                        })
-                    }).collect();
-                    export_data["versions"] = serde_json::json!(versions);
-                }
-            }
-            
-            let json = serde_json::to_string_pretty(&export_data)?;
-            std::fs::write(path, json)?;
+    let options = ExportOptions {
+        include_versions: matches!(mode, ExportMode::WithAllVersions),
+        ..ExportOptions::default()
+    };
+    export_json_with_options(library, path, &options, version_repo)
+}
+
+/// Export library to JSON file in an envelope, as `options` say. Versions
+/// are read from `version_repo`; without one, or for a library not yet
+/// saved, `"versions"` is null.
+pub fn export_json_with_options(
+    library: &Library,
+    path: &Path,
+    options: &ExportOptions,
+    version_repo: Option<&VersionRepo>,
+) -> Result<()> {
+    let info = ExportInfo::current();
+    let mut export_data = serde_json::json!({
+        "toeditor_version": info.toeditor_version,
+        "exported_at": info.exported_at,
+        "library": library,
+    });
+    if options.include_versions {
+        let mut versions = serde_json::Value::Null;
+        if let (Some(repo), Some(lib_id)) = (version_repo, library.id) {
+            let snapshots = repo.list_by_library(lib_id)?;
+            versions = snapshots.iter().map(|s| {
+                serde_json::json!({
+                    "version": s.version,
+                    "timestamp": s.timestamp,
+                    "description": s.description,
+                    "data": s.data,
+                })
+            }).collect();
         }
+        export_data["versions"] = versions;
     }
+
+    let json = if options.pretty {
+        serde_json::to_string_pretty(&export_data)?
+    } else {
+        serde_json::to_string(&export_data)?
+    };
+    std::fs::write(path, json)?;
     Ok(())
 }
 
@@ -117,4 +231,112 @@ mod tests {
         assert!(content.contains("Test"));
         assert!(content.contains("versions"));
     }
+
+    #[test]
+    fn test_export_library_to_path_by_extension() {
+        let library = Library::new(
+            "Test".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let json_path = dir.path().join("lib.json");
+        export_library_to_path(&library, &json_path).unwrap();
+        let envelope: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+        let from_json: Library = serde_json::from_value(envelope["library"].clone()).unwrap();
+        assert_eq!(from_json, library);
+
+        let yaml_path = dir.path().join("lib.YAML");
+        export_library_to_path(&library, &yaml_path).unwrap();
+        let from_yaml: Library =
+            serde_yaml::from_str(&std::fs::read_to_string(&yaml_path).unwrap()).unwrap();
+        assert_eq!(from_yaml, library);
+
+        assert!(export_library_to_path(&library, &dir.path().join("lib.txt")).is_err());
+        assert!(!dir.path().join("lib.txt").exists());
+    }
+
+    #[test]
+    fn test_export_library_to_path_reports_os_error() {
+        let library = Library::new(
+            "Test".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("missing").join("lib.json");
+        let err = export_library_to_path(&library, &path).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("Failed to write"));
+        assert!(message.contains("os error"));
+    }
+
+    #[test]
+    fn test_export_json_anonymized() {
+        use crate::export::full::FULL_EXPORT_FORMAT_VERSION;
+        let mut library = Library::new(
+            "Test".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        library.add_unit(crate::models::Unit::new("1st Battalion".to_string(), "Battalion".to_string()));
+        let export = FullLibraryExport {
+            format_version: FULL_EXPORT_FORMAT_VERSION,
+            library: library.clone(),
+            formation_levels: Vec::new(),
+            branch_categories: Vec::new(),
+            branches: Vec::new(),
+        };
+        let snapshot = Snapshot::with_description(1, 1, serde_json::to_string(&library).unwrap(), "Internal".to_string());
+        let file = NamedTempFile::new().unwrap();
+        export_json_anonymized(export.clone(), vec![snapshot.clone()], file.path(), &AnonymizeOptions::default()).unwrap();
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert!(!content.contains("\"Author\""));
+        assert!(!content.contains("Internal"));
+        let data: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(data["library"]["author"], "Anonymous");
+        assert_eq!(data["library"]["units"][0]["name"], "1st Battalion");
+        assert_eq!(data["versions"].as_array().unwrap().len(), 1);
+
+        let options = AnonymizeOptions { include_versions: false, ..AnonymizeOptions::default() };
+        export_json_anonymized(export, vec![snapshot], file.path(), &options).unwrap();
+        let data: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap();
+        assert!(data.get("versions").is_none());
+    }
+
+    #[test]
+    fn test_export_json_envelope() {
+        let library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        let file = NamedTempFile::new().unwrap();
+        export_json(&library, file.path()).unwrap();
+
+        let data: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap();
+        let info = ExportInfo::from_value(&data).unwrap();
+        assert_eq!(info.toeditor_version, env!("CARGO_PKG_VERSION"));
+        assert!(info.exported_at > 0);
+        assert_eq!(data["library"]["name"], "Test");
+        assert!(data.get("versions").is_none());
+        assert_eq!(ExportInfo::from_value(&data["library"]), None);
+    }
+
+    #[test]
+    fn test_export_json_pretty_and_compact() {
+        let library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        let (pretty, compact) = (NamedTempFile::new().unwrap(), NamedTempFile::new().unwrap());
+        let options = ExportOptions::default();
+        export_json_with_options(&library, pretty.path(), &options, None).unwrap();
+        export_json_with_options(&library, compact.path(), &ExportOptions { pretty: false, ..options }, None).unwrap();
+
+        let pretty = std::fs::read_to_string(pretty.path()).unwrap();
+        let compact = std::fs::read_to_string(compact.path()).unwrap();
+        assert!(pretty.contains('\n'));
+        assert!(!compact.contains('\n'));
+        assert!(compact.len() < pretty.len());
+    }
 }
@@ -2,17 +2,58 @@
 
 pub mod json;
 pub mod csv;
+pub mod xlsx;
+pub mod yaml;
 pub mod svg;
+pub mod diagram_batch;
 pub mod branch_formation_io;
+pub mod anonymize;
+pub mod canonical;
+pub mod versions;
+pub mod html;
+pub mod print;
+pub mod full;
+pub mod formation;
+pub mod presets;
 
-pub use json::export_json;
-pub use csv::export_csv;
-pub use svg::export_svg;
+/// File name offered when saving or exporting a library: its name made safe
+/// for file systems, with `extension`
+pub fn default_file_name(library_name: &str, extension: &str) -> String {
+    format!("{}.{}", sanitize_file_stem(library_name), extension)
+}
+
+pub use json::{
+    export_json, export_json_anonymized, export_json_with_options, export_library_to_path, ExportInfo, ExportOptions, LibraryFileFormat,
+};
+pub use anonymize::{anonymize, anonymize_full, anonymize_library, anonymize_versions, AnonymizeOptions, LibraryExport};
+pub use full::{
+    export_full_to_path, parse_full_export, snapshot_library_json, snapshot_library_json_mut, FullLibraryExport, SnapshotState,
+    FULL_EXPORT_FORMAT_VERSION,
+};
+pub use formation::{
+    export_formation_json, formation_export, import_formation, import_formation_json, parse_formation_export, FormationExport,
+    FormationImport, UnmatchedLevel, FORMATION_EXPORT_FORMAT_VERSION,
+};
+pub use presets::{
+    builtin_presets, export_with_preset, preset_name_problem, ExportPreset, PresetFilters, PresetFormat, PresetIssue, PresetNameProblem,
+    ResolvedPreset,
+};
+pub use csv::{export_csv, export_csv_with, export_summary_csv, CsvOptions};
+pub use xlsx::export_xlsx;
+pub use yaml::export_yaml;
+pub use diagram_batch::{export_diagrams_per_formation, formations_at_depth, sanitize_file_stem, unique_file_stems, DiagramBatchReport, DiagramFileResult};
+pub use svg::{export_svg, export_svg_pages, paginate, render_svg, ChartPage, PageLimit, SvgOptions, SvgOrientation, UnitPath};
 pub use branch_formation_io::{
     BranchExport, BranchCategoryExport, FormationLevelExport,
-    export_branches_to_path, import_branches_from_path,
+    BranchesFile, CategoryResolution, UnmatchedCategory,
+    branch_exports, branch_category_exports, formation_level_exports,
+    export_branches_to_path, export_branches_with_categories_to_path, import_branches_from_path,
+    import_branches_file_from_path,
+    match_category, find_unmatched_categories, resolve_branch_import, resolve_branches_file_import,
     export_branch_categories_to_path, import_branch_categories_from_path,
     export_formation_levels_to_path, import_formation_levels_from_path,
     copy_branches_between_libraries, copy_branch_categories_between_libraries,
     copy_formation_levels_between_libraries,
+    EquipmentCatalogExport, export_equipment_catalog_to_path, import_equipment_catalog_from_path,
+    copy_equipment_catalog_between_libraries,
 };
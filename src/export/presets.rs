@@ -0,0 +1,559 @@
+//! Export presets: a named format with its options, unit filters,
+//! anonymization and a file name pattern, kept in the settings so the same
+//! export can be repeated in one click.
+//!
+//! Options are stored as text under a key rather than as fields, so a preset
+//! written by an older version still loads after an option is renamed or
+//! dropped: renamed keys are read under their new name, and unknown keys or
+//! unreadable values are reported and the option keeps its default.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{assemble_forest, units_as_of, walk_units, Library, Unit};
+use super::{
+    anonymize, export_csv_with, export_json, export_svg_pages, export_yaml, sanitize_file_stem, AnonymizeOptions, CsvOptions,
+    LibraryExport, PageLimit, SvgOptions, SvgOrientation,
+};
+
+/// Field separator of CSV exports: `,`, `;` or `tab`
+pub const OPT_CSV_DELIMITER: &str = "csv_delimiter";
+/// Chart direction of SVG exports: `top_down` or `left_right`
+pub const OPT_SVG_ORIENTATION: &str = "svg_orientation";
+/// Largest SVG page as `WIDTHxHEIGHT`; empty writes one file
+pub const OPT_SVG_PAGE_LIMIT: &str = "svg_page_limit";
+/// Author written in place of the real one when the preset anonymizes
+pub const OPT_PLACEHOLDER_AUTHOR: &str = "placeholder_author";
+
+/// Option keys this version reads
+pub const OPTION_KEYS: [&str; 4] = [OPT_CSV_DELIMITER, OPT_SVG_ORIENTATION, OPT_SVG_PAGE_LIMIT, OPT_PLACEHOLDER_AUTHOR];
+
+/// Keys of earlier versions and the key each was renamed to
+const RENAMED_OPTIONS: &[(&str, &str)] = &[
+    ("delimiter", OPT_CSV_DELIMITER),
+    ("orientation", OPT_SVG_ORIENTATION),
+    ("page_limit", OPT_SVG_PAGE_LIMIT),
+];
+
+/// File name pattern of new presets
+pub const DEFAULT_FILE_NAME_PATTERN: &str = "{library}";
+
+/// File format a preset writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetFormat {
+    Json,
+    Csv,
+    Yaml,
+    Svg,
+}
+
+impl PresetFormat {
+    pub const ALL: [PresetFormat; 4] = [PresetFormat::Json, PresetFormat::Csv, PresetFormat::Yaml, PresetFormat::Svg];
+
+    /// Name stored in the settings
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Yaml => "yaml",
+            Self::Svg => "svg",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.key() == key.trim().to_lowercase())
+    }
+
+    pub fn extension(self) -> &'static str {
+        self.key()
+    }
+
+    /// Name in the format dropdown and the file dialog filter
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Csv => "CSV",
+            Self::Yaml => "YAML",
+            Self::Svg => "SVG",
+        }
+    }
+}
+
+/// Which units a preset exports
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetFilters {
+    /// Only units valid on the "as of" date chosen in the main window
+    pub as_of: bool,
+    /// Unit types to export (case-insensitive); a unit of another type stays
+    /// only as the parent of exported units. Empty exports every type.
+    pub unit_types: Vec<String>,
+}
+
+/// A named export setup as kept in the settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportPreset {
+    pub name: String,
+    /// [`PresetFormat::key`]; kept as text so a preset naming a format this
+    /// version lacks still loads and can be fixed
+    pub format: String,
+    /// Format options by key (`OPT_*`)
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    #[serde(default)]
+    pub filters: PresetFilters,
+    /// Replace the author and provenance like File > Export Anonymized
+    #[serde(default)]
+    pub anonymize: bool,
+    /// File name without extension; `{library}`, `{date}` and `{format}` are
+    /// replaced when exporting
+    #[serde(default = "default_file_name_pattern")]
+    pub file_name_pattern: String,
+}
+
+fn default_file_name_pattern() -> String {
+    DEFAULT_FILE_NAME_PATTERN.to_string()
+}
+
+/// Something in a preset this version could not use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetIssue {
+    /// An option key no version knows, skipped
+    UnknownOption(String),
+    /// A value the option cannot take; the default is used
+    InvalidValue { key: String, value: String },
+}
+
+impl fmt::Display for PresetIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOption(key) => write!(f, "unknown option \"{}\" ignored", key),
+            Self::InvalidValue { key, value } => write!(f, "option \"{}\" cannot be \"{}\", default used", key, value),
+        }
+    }
+}
+
+/// A preset turned into the option structs of its format
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedPreset {
+    pub format: PresetFormat,
+    pub csv: CsvOptions,
+    pub svg: SvgOptions,
+    /// `Some` when the preset anonymizes
+    pub anonymize: Option<AnonymizeOptions>,
+    pub filters: PresetFilters,
+    pub issues: Vec<PresetIssue>,
+}
+
+/// The key an option is read under, following renames
+pub fn current_option_key(key: &str) -> &str {
+    RENAMED_OPTIONS.iter().find(|(old, _)| *old == key).map_or(key, |(_, new)| new)
+}
+
+/// Delimiter stored as [`OPT_CSV_DELIMITER`]
+pub fn parse_delimiter(value: &str) -> Option<char> {
+    match value {
+        "tab" | "\t" => Some('\t'),
+        "," | ";" | "|" => value.chars().next(),
+        _ => None,
+    }
+}
+
+/// Orientation stored as [`OPT_SVG_ORIENTATION`]
+pub fn parse_orientation(value: &str) -> Option<SvgOrientation> {
+    match value.trim() {
+        "top_down" => Some(SvgOrientation::TopDown),
+        "left_right" => Some(SvgOrientation::LeftRight),
+        _ => None,
+    }
+}
+
+/// Value of [`OPT_CSV_DELIMITER`] for `delimiter`
+pub fn delimiter_value(delimiter: char) -> String {
+    match delimiter {
+        '\t' => "tab".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Value of [`OPT_SVG_ORIENTATION`] for `orientation`
+pub fn orientation_value(orientation: SvgOrientation) -> &'static str {
+    match orientation {
+        SvgOrientation::TopDown => "top_down",
+        SvgOrientation::LeftRight => "left_right",
+    }
+}
+
+/// `""` for no limit, otherwise `WIDTHxHEIGHT`
+fn parse_page_limit(value: &str) -> Option<Option<PageLimit>> {
+    if value.trim().is_empty() {
+        return Some(None);
+    }
+    let (width, height) = value.split_once(['x', 'X'])?;
+    PageLimit::parse(width, height).map(Some)
+}
+
+impl ExportPreset {
+    pub fn new(name: &str, format: PresetFormat) -> Self {
+        Self {
+            name: name.to_string(),
+            format: format.key().to_string(),
+            options: BTreeMap::new(),
+            filters: PresetFilters::default(),
+            anonymize: false,
+            file_name_pattern: default_file_name_pattern(),
+        }
+    }
+
+    /// The preset with an option set
+    pub fn with_option(mut self, key: &str, value: &str) -> Self {
+        self.options.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Value of an option, also found under the key it had before a rename
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| current_option_key(k) == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The preset with renamed option keys stored under their current names
+    pub fn migrated(mut self) -> Self {
+        self.options = self
+            .options
+            .into_iter()
+            .map(|(key, value)| (current_option_key(&key).to_string(), value))
+            .collect();
+        self
+    }
+
+    /// The option structs for this preset. Fails only when the format is
+    /// unknown; problems with options are listed in `issues`.
+    pub fn resolve(&self) -> Result<ResolvedPreset> {
+        let Some(format) = PresetFormat::from_key(&self.format) else {
+            bail!("Preset \"{}\" uses the unknown format \"{}\"", self.name, self.format);
+        };
+        let mut resolved = ResolvedPreset {
+            format,
+            csv: CsvOptions::default(),
+            svg: SvgOptions::default(),
+            anonymize: self.anonymize.then(AnonymizeOptions::default),
+            filters: self.filters.clone(),
+            issues: Vec::new(),
+        };
+        for (key, value) in &self.options {
+            let key = current_option_key(key);
+            let applied = match key {
+                OPT_CSV_DELIMITER => parse_delimiter(value).map(|d| resolved.csv.delimiter = d),
+                OPT_SVG_ORIENTATION => parse_orientation(value).map(|o| resolved.svg.orientation = o),
+                OPT_SVG_PAGE_LIMIT => parse_page_limit(value).map(|l| resolved.svg.page_limit = l),
+                OPT_PLACEHOLDER_AUTHOR => {
+                    if let Some(options) = resolved.anonymize.as_mut() {
+                        options.placeholder_author = value.clone();
+                    }
+                    Some(())
+                }
+                _ => {
+                    resolved.issues.push(PresetIssue::UnknownOption(key.to_string()));
+                    continue;
+                }
+            };
+            if applied.is_none() {
+                resolved.issues.push(PresetIssue::InvalidValue {
+                    key: key.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// File name for exporting `library_name` on `date`, with the extension
+    /// of `format`
+    pub fn file_name(&self, format: PresetFormat, library_name: &str, date: NaiveDate) -> String {
+        let pattern = match self.file_name_pattern.trim() {
+            "" => DEFAULT_FILE_NAME_PATTERN,
+            pattern => pattern,
+        };
+        let stem = pattern
+            .replace("{library}", library_name)
+            .replace("{date}", &date.format("%Y-%m-%d").to_string())
+            .replace("{format}", format.key());
+        format!("{}.{}", sanitize_file_stem(&stem), format.extension())
+    }
+}
+
+/// Why a list of presets cannot be saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetNameProblem {
+    /// A preset without a name
+    Blank,
+    /// Two presets with this name (trimmed, case-insensitive)
+    Duplicate(String),
+}
+
+/// The first naming problem of `presets`; presets are picked by name in the
+/// menus, so every one needs a name of its own
+pub fn preset_name_problem(presets: &[ExportPreset]) -> Option<PresetNameProblem> {
+    let mut seen = HashSet::new();
+    for preset in presets {
+        let name = preset.name.trim();
+        if name.is_empty() {
+            return Some(PresetNameProblem::Blank);
+        }
+        if !seen.insert(name.to_lowercase()) {
+            return Some(PresetNameProblem::Duplicate(name.to_string()));
+        }
+    }
+    None
+}
+
+/// Presets offered until the user changes the list
+pub fn builtin_presets() -> Vec<ExportPreset> {
+    let mut shared = ExportPreset::new("Anonymized JSON for sharing", PresetFormat::Json);
+    shared.anonymize = true;
+    shared.file_name_pattern = "{library} (shared {date})".to_string();
+    let spreadsheet = ExportPreset::new("Spreadsheet (CSV, semicolons)", PresetFormat::Csv).with_option(OPT_CSV_DELIMITER, ";");
+    vec![shared, spreadsheet]
+}
+
+/// `units` keeping those of `types` (trimmed, case-insensitive) and the
+/// parents they need
+pub fn units_of_types(units: &[Unit], types: &[String]) -> Vec<Unit> {
+    let types: HashSet<String> = types.iter().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()).collect();
+    if types.is_empty() {
+        return units.to_vec();
+    }
+    let visits: Vec<_> = walk_units(units).collect();
+    let mut needed: Vec<bool> = visits.iter().map(|v| types.contains(&v.unit.unit_type.trim().to_lowercase())).collect();
+    // Children come after their parents in the walk
+    for i in (0..visits.len()).rev() {
+        if let (true, Some(parent)) = (needed[i], visits[i].parent) {
+            needed[parent] = true;
+        }
+    }
+    let mut position: Vec<Option<usize>> = Vec::with_capacity(visits.len());
+    let mut nodes = Vec::new();
+    for (visit, needed) in visits.iter().zip(needed) {
+        if needed {
+            position.push(Some(nodes.len()));
+            nodes.push((visit.unit.without_children(), visit.parent.and_then(|p| position[p])));
+        } else {
+            position.push(None);
+        }
+    }
+    assemble_forest(nodes, |unit, children| unit.children = children)
+}
+
+/// Export `library` the way `preset` says. `as_of` is the date the
+/// [`PresetFilters::as_of`] filter uses. Returns the written files (several
+/// when an SVG chart is split into pages).
+pub fn export_with_preset(library: &Library, preset: &ResolvedPreset, as_of: Option<NaiveDate>, path: &Path) -> Result<Vec<PathBuf>> {
+    let mut library = library.clone();
+    if let (true, Some(date)) = (preset.filters.as_of, as_of) {
+        library.units = units_as_of(&library.units, date);
+    }
+    library.units = units_of_types(&library.units, &preset.filters.unit_types);
+    if let Some(options) = &preset.anonymize {
+        library = anonymize(LibraryExport { library, versions: Vec::new() }, options).library;
+    }
+    match preset.format {
+        PresetFormat::Json => export_json(&library, path)?,
+        PresetFormat::Csv => export_csv_with(&library, path, &preset.csv)?,
+        PresetFormat::Yaml => export_yaml(&library, path)?,
+        PresetFormat::Svg => return export_svg_pages(&library, &library.units, path, &preset.svg),
+    }
+    Ok(vec![path.to_path_buf()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn unit(name: &str, unit_type: &str, children: Vec<Unit>) -> Unit {
+        let mut unit = Unit::new(name.to_string(), unit_type.to_string());
+        unit.children = children;
+        unit
+    }
+
+    fn names(units: &[Unit]) -> Vec<String> {
+        walk_units(units).map(|v| v.unit.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_builtin_presets_resolve_cleanly() {
+        let presets = builtin_presets();
+        assert_eq!(presets.len(), 2);
+        for preset in &presets {
+            assert!(preset.resolve().unwrap().issues.is_empty(), "{}", preset.name);
+        }
+        assert!(presets[0].resolve().unwrap().anonymize.is_some());
+        assert_eq!(presets[1].resolve().unwrap().csv.delimiter, ';');
+    }
+
+    #[test]
+    fn test_preset_round_trips_through_toml() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            presets: Vec<ExportPreset>,
+        }
+        let mut preset = ExportPreset::new("Charts", PresetFormat::Svg)
+            .with_option(OPT_SVG_ORIENTATION, "left_right")
+            .with_option(OPT_SVG_PAGE_LIMIT, "1200x800");
+        preset.filters.unit_types = vec!["Battalion".to_string()];
+        let text = toml::to_string(&Wrapper { presets: vec![preset.clone()] }).unwrap();
+        let back: Wrapper = toml::from_str(&text).unwrap();
+        assert_eq!(back.presets, [preset]);
+    }
+
+    #[test]
+    fn test_missing_fields_take_defaults() {
+        let preset: ExportPreset = toml::from_str("name = \"Old\"\nformat = \"yaml\"").unwrap();
+        assert_eq!(preset.file_name_pattern, DEFAULT_FILE_NAME_PATTERN);
+        assert!(!preset.anonymize);
+        assert_eq!(preset.filters, PresetFilters::default());
+        let resolved = preset.resolve().unwrap();
+        assert_eq!(resolved.format, PresetFormat::Yaml);
+        assert_eq!(resolved.csv, CsvOptions::default());
+    }
+
+    #[test]
+    fn test_options_apply_onto_option_structs() {
+        let mut preset = ExportPreset::new("P", PresetFormat::Svg)
+            .with_option(OPT_SVG_ORIENTATION, "left_right")
+            .with_option(OPT_SVG_PAGE_LIMIT, "1200x800")
+            .with_option(OPT_CSV_DELIMITER, "tab")
+            .with_option(OPT_PLACEHOLDER_AUTHOR, "Somebody");
+        preset.anonymize = true;
+        let resolved = preset.resolve().unwrap();
+        assert_eq!(resolved.svg.orientation, SvgOrientation::LeftRight);
+        assert_eq!(resolved.svg.page_limit, Some(PageLimit { width: 1200, height: 800 }));
+        assert_eq!(resolved.csv.delimiter, '\t');
+        assert_eq!(resolved.anonymize.unwrap().placeholder_author, "Somebody");
+        assert!(resolved.issues.is_empty());
+    }
+
+    #[test]
+    fn test_renamed_options_are_read_under_their_new_key() {
+        let preset = ExportPreset::new("Old", PresetFormat::Csv).with_option("delimiter", ";");
+        assert_eq!(preset.option(OPT_CSV_DELIMITER), Some(";"));
+        let resolved = preset.resolve().unwrap();
+        assert_eq!(resolved.csv.delimiter, ';');
+        assert!(resolved.issues.is_empty());
+
+        let migrated = preset.migrated();
+        assert_eq!(migrated.options.keys().collect::<Vec<_>>(), [OPT_CSV_DELIMITER]);
+    }
+
+    #[test]
+    fn test_unknown_options_and_bad_values_are_reported() {
+        let preset = ExportPreset::new("Odd", PresetFormat::Csv)
+            .with_option("authorized_columns", "yes")
+            .with_option(OPT_CSV_DELIMITER, "::")
+            .with_option(OPT_SVG_PAGE_LIMIT, "10x10");
+        let resolved = preset.resolve().unwrap();
+        assert_eq!(resolved.csv.delimiter, ',');
+        assert_eq!(resolved.svg.page_limit, None);
+        assert_eq!(
+            resolved.issues,
+            [
+                PresetIssue::UnknownOption("authorized_columns".to_string()),
+                PresetIssue::InvalidValue {
+                    key: OPT_CSV_DELIMITER.to_string(),
+                    value: "::".to_string()
+                },
+                PresetIssue::InvalidValue {
+                    key: OPT_SVG_PAGE_LIMIT.to_string(),
+                    value: "10x10".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_format_fails_to_resolve() {
+        let mut preset = ExportPreset::new("Future", PresetFormat::Json);
+        preset.format = "xlsx".to_string();
+        assert!(preset.resolve().is_err());
+    }
+
+    #[test]
+    fn test_option_values_read_back() {
+        for delimiter in [',', ';', '\t', '|'] {
+            assert_eq!(parse_delimiter(&delimiter_value(delimiter)), Some(delimiter));
+        }
+        for orientation in [SvgOrientation::TopDown, SvgOrientation::LeftRight] {
+            assert_eq!(parse_orientation(orientation_value(orientation)), Some(orientation));
+        }
+    }
+
+    #[test]
+    fn test_preset_names_must_be_present_and_distinct() {
+        let mut presets = builtin_presets();
+        assert_eq!(preset_name_problem(&presets), None);
+        presets.push(ExportPreset::new(" spreadsheet (csv, SEMICOLONS) ", PresetFormat::Csv));
+        assert_eq!(
+            preset_name_problem(&presets),
+            Some(PresetNameProblem::Duplicate("spreadsheet (csv, SEMICOLONS)".to_string()))
+        );
+        presets[0].name = "  ".to_string();
+        assert_eq!(preset_name_problem(&presets), Some(PresetNameProblem::Blank));
+    }
+
+    #[test]
+    fn test_file_name_pattern() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        let mut preset = ExportPreset::new("P", PresetFormat::Csv);
+        assert_eq!(preset.file_name(PresetFormat::Csv, "Soviet Army", date), "Soviet Army.csv");
+        preset.file_name_pattern = "{library}/{format} {date}".to_string();
+        assert_eq!(preset.file_name(PresetFormat::Csv, "Soviet Army", date), "Soviet Army_csv 2024-03-09.csv");
+        preset.file_name_pattern = "  ".to_string();
+        assert_eq!(preset.file_name(PresetFormat::Yaml, "L", date), "L.yaml");
+    }
+
+    #[test]
+    fn test_unit_type_filter_keeps_needed_parents() {
+        let units = vec![
+            unit(
+                "Division",
+                "Division",
+                vec![
+                    unit("1st Regiment", "Regiment", vec![unit("1st Battalion", "battalion ", vec![unit("1st Company", "Company", vec![])])]),
+                    unit("Signal Regiment", "Regiment", vec![unit("Signal Company", "Company", vec![])]),
+                ],
+            ),
+            unit("Depot", "Depot", vec![]),
+        ];
+        let kept = units_of_types(&units, &["Battalion".to_string()]);
+        assert_eq!(names(&kept), ["Division", "1st Regiment", "1st Battalion"]);
+        assert_eq!(names(&units_of_types(&units, &[" ".to_string()])).len(), 7);
+    }
+
+    #[test]
+    fn test_export_with_preset_writes_filtered_anonymized_file() {
+        let dir = TempDir::new().unwrap();
+        let mut library = Library::new("L".to_string(), "RU".to_string(), "1985".to_string(), "Real Author".to_string());
+        library.units = vec![unit("HQ", "Headquarters", vec![unit("Tank Battalion", "Battalion", vec![])]), unit("Depot", "Depot", vec![])];
+        let mut preset = builtin_presets().remove(0);
+        preset.filters.unit_types = vec!["Battalion".to_string()];
+        let path = dir.path().join("out.json");
+        let written = export_with_preset(&library, &preset.resolve().unwrap(), None, &path).unwrap();
+        assert_eq!(written, std::slice::from_ref(&path));
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("Real Author"));
+        assert!(text.contains("Tank Battalion"));
+        assert!(!text.contains("Depot"));
+
+        let csv_path = dir.path().join("out.csv");
+        export_with_preset(&library, &builtin_presets()[1].resolve().unwrap(), None, &csv_path).unwrap();
+        assert!(std::fs::read_to_string(&csv_path).unwrap().starts_with("Library;L;RU;1985"));
+    }
+}
@@ -0,0 +1,104 @@
+//! Temporary files for printing through the browser
+//!
+//! Printing writes the print page (see [`super::html::render_print_page`]) to a
+//! file in [`print_dir`] and hands it to the default browser. The browser
+//! reads the file after we return, so files are only removed on a later start,
+//! once they are older than [`PRINT_FILE_MAX_AGE`].
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Age after which a print file is assumed to be done with
+pub const PRINT_FILE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Longest formation-name part of a print file name, in characters
+const MAX_NAME_CHARS: usize = 60;
+
+/// Folder holding print files
+pub fn print_dir() -> PathBuf {
+    std::env::temp_dir().join("toeditor-print")
+}
+
+/// File name for printing formation `title` at `now` (Unix seconds):
+/// the title reduced to characters safe in file names, then the time
+pub fn print_file_name(title: &str, now: i64) -> String {
+    let mut name: String = title
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .take(MAX_NAME_CHARS)
+        .collect();
+    if name.trim_matches('_').is_empty() {
+        name = "formation".to_string();
+    }
+    format!("{}-{}.html", name, now)
+}
+
+/// Remove print files in `dir` last modified more than `max_age` before `now`.
+/// Other files are left alone; a missing folder is nothing to clean.
+/// Returns how many files were removed.
+pub fn cleanup_print_files(dir: &Path, max_age: Duration, now: SystemTime) -> Result<usize> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("html") {
+            continue;
+        }
+        let modified = std::fs::metadata(&path)?.modified()?;
+        let age = now.duration_since(modified).unwrap_or_default();
+        if age > max_age {
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_file_name() {
+        assert_eq!(print_file_name("1st Battalion", 1700000000), "1st_Battalion-1700000000.html");
+        assert_eq!(print_file_name("Полк/Штаб: \"A\"", 5), "Полк_Штаб___A_-5.html");
+        assert_eq!(print_file_name(" / ", 5), "formation-5.html");
+        let long = print_file_name(&"x".repeat(200), 5);
+        assert_eq!(long.len(), MAX_NAME_CHARS + "-5.html".len());
+    }
+
+    #[test]
+    fn test_cleanup_removes_only_old_print_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let write = |name: &str, age: Duration| {
+            let path = dir.path().join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_modified(now - age).unwrap();
+            path
+        };
+        let old = write("old-1.html", PRINT_FILE_MAX_AGE * 2);
+        let fresh = write("fresh-2.html", Duration::from_secs(60));
+        let other = write("notes.txt", PRINT_FILE_MAX_AGE * 2);
+
+        assert_eq!(cleanup_print_files(dir.path(), PRINT_FILE_MAX_AGE, now).unwrap(), 1);
+        assert!(!old.exists());
+        assert!(fresh.exists());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn test_cleanup_missing_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(
+            cleanup_print_files(&dir.path().join("missing"), PRINT_FILE_MAX_AGE, SystemTime::now()).unwrap(),
+            0
+        );
+    }
+}
@@ -1,125 +1,321 @@
 //! SVG export functionality for organizational charts
-
-use anyhow::Result;
-use crate::models::{Library, Unit};
-use std::path::Path;
-
+//!
+//! Units are laid out as an org chart: one box per unit with its name and
+//! personnel/equipment counts, elbow connectors from parent to children.
+//! Each subtree reserves the breadth of its widest level so siblings never
+//! overlap; box widths follow an estimate of the label's rendered width.
+//!
+//! Charts larger than a page limit are split by subtree: a cover page shows
+//! the top-level units with their direct children, each child naming the page
+//! its subtree is drawn on.
+
+use anyhow::{Context, Result};
+use crate::models::{assemble_forest, walk_units, Library, TreeWalk, Unit, Visit};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Minimum box width; longer labels widen the box
 const BOX_WIDTH: f64 = 160.0;
 const BOX_HEIGHT: f64 = 50.0;
 const H_SPACING: f64 = 30.0;
 const V_SPACING: f64 = 60.0;
 const PADDING: f64 = 40.0;
+/// Space reserved above the chart for the library title
+const TITLE_HEIGHT: f64 = 30.0;
+/// Horizontal padding between a label and the box edge
+const TEXT_PADDING: f64 = 12.0;
+const LABEL_FONT_SIZE: f64 = 12.0;
+const SUBLABEL_FONT_SIZE: f64 = 10.0;
+
+/// Direction in which the chart grows from the top-level units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SvgOrientation {
+    /// Parents above children
+    #[default]
+    TopDown,
+    /// Parents left of children
+    LeftRight,
+}
 
-/// Calculated layout node
-struct LayoutNode {
-    x: f64,
-    y: f64,
-    label: String,
-    sublabel: String,
-    children: Vec<LayoutNode>,
+/// Largest page of a chart, in SVG pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimit {
+    pub width: u32,
+    pub height: u32,
 }
 
-/// Calculate the total width needed for a unit subtree.
-fn subtree_width(unit: &Unit) -> f64 {
-    if unit.children.is_empty() {
-        BOX_WIDTH
-    } else {
-        let children_total: f64 = unit
-            .children
-            .iter()
-            .map(subtree_width)
-            .sum::<f64>()
-            + H_SPACING * (unit.children.len() as f64 - 1.0).max(0.0);
-        children_total.max(BOX_WIDTH)
+impl PageLimit {
+    /// Smallest accepted page side; a single unit box needs about this much
+    pub const MIN_SIDE: u32 = 300;
+
+    /// Limit from width and height as typed; `None` unless both are whole
+    /// numbers of at least [`PageLimit::MIN_SIDE`]
+    pub fn parse(width: &str, height: &str) -> Option<PageLimit> {
+        let side = |text: &str| text.trim().parse::<u32>().ok().filter(|&v| v >= Self::MIN_SIDE);
+        Some(PageLimit {
+            width: side(width)?,
+            height: side(height)?,
+        })
     }
 }
 
-/// Layout a unit tree starting at (x, y) centered on the given width.
-fn layout_unit(unit: &Unit, x: f64, y: f64, available_width: f64) -> LayoutNode {
-    let cx = x + available_width / 2.0;
-    let sublabel = format!(
+/// Options for [`export_svg`] and [`export_svg_pages`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SvgOptions {
+    pub orientation: SvgOrientation,
+    /// Split charts larger than this into pages; `None` always writes one file
+    pub page_limit: Option<PageLimit>,
+}
+
+/// Estimated rendered width of `text` in a sans-serif font.
+///
+/// Latin glyphs average a little over half an em; Cyrillic glyphs are wider,
+/// and anything else (CJK, symbols) is assumed to take a full em.
+fn estimate_text_width(text: &str, font_size: f64) -> f64 {
+    let ems: f64 = text
+        .chars()
+        .map(|c| match c {
+            ' ' | '.' | ',' | ':' | ';' | '\'' | '|' | '!' | 'i' | 'l' | 'j' | 't' | 'f' | 'I' => 0.3,
+            'm' | 'w' | 'M' | 'W' => 0.85,
+            'A'..='Z' | '0'..='9' => 0.65,
+            _ if c.is_ascii() => 0.55,
+            '\u{0400}'..='\u{04FF}' => 0.7,
+            _ => 1.0,
+        })
+        .sum();
+    ems * font_size
+}
+
+/// Counts line shown under the unit name
+fn unit_sublabel(unit: &Unit) -> String {
+    format!(
         "P:{} E:{}",
         unit.personnel.len(),
         unit.equipment.iter().map(|e| e.quantity).sum::<usize>()
-    );
+    )
+}
 
-    let mut children_layouts = Vec::new();
+/// Width of a unit's box: wide enough for both text lines, at least [`BOX_WIDTH`]
+fn box_width(unit: &Unit) -> f64 {
+    let label = estimate_text_width(&unit.name, LABEL_FONT_SIZE);
+    let sublabel = estimate_text_width(&unit_sublabel(unit), SUBLABEL_FONT_SIZE);
+    (label.max(sublabel) + 2.0 * TEXT_PADDING).max(BOX_WIDTH).ceil()
+}
 
-    if !unit.children.is_empty() {
-        let total_children_width: f64 = unit
-            .children
-            .iter()
-            .map(subtree_width)
-            .sum::<f64>()
-            + H_SPACING * (unit.children.len() as f64 - 1.0).max(0.0);
-
-        let start_x = cx - total_children_width / 2.0;
-        let child_y = y + BOX_HEIGHT + V_SPACING;
-        let mut cur_x = start_x;
-
-        for child in &unit.children {
-            let cw = subtree_width(child);
-            children_layouts.push(layout_unit(child, cur_x, child_y, cw));
-            cur_x += cw + H_SPACING;
+/// Size of a box across the direction the chart grows
+fn box_breadth(unit: &Unit, orientation: SvgOrientation) -> f64 {
+    match orientation {
+        SvgOrientation::TopDown => box_width(unit),
+        SvgOrientation::LeftRight => BOX_HEIGHT,
+    }
+}
+
+/// Spacing between sibling subtrees
+fn sibling_spacing(orientation: SvgOrientation) -> f64 {
+    match orientation {
+        SvgOrientation::TopDown => H_SPACING,
+        SvgOrientation::LeftRight => H_SPACING / 2.0,
+    }
+}
+
+/// Breadth needed for a unit subtree: its own box or its children side by side,
+/// whichever is larger.
+fn subtree_breadth(unit: &Unit, orientation: SvgOrientation) -> f64 {
+    let visits: Vec<Visit<Unit>> = walk_units(std::slice::from_ref(unit)).collect();
+    subtree_breadths(&visits, &child_lists(&visits), orientation)[0]
+}
+
+/// Positions in `visits` of each visited unit's children
+fn child_lists(visits: &[Visit<Unit>]) -> Vec<Vec<usize>> {
+    let mut children = vec![Vec::new(); visits.len()];
+    for (i, visit) in visits.iter().enumerate() {
+        if let Some(parent) = visit.parent {
+            children[parent].push(i);
         }
     }
+    children
+}
 
-    LayoutNode {
-        x: cx - BOX_WIDTH / 2.0,
-        y,
-        label: unit.name.clone(),
-        sublabel,
-        children: children_layouts,
+/// [`subtree_breadth`] of every visited unit, children before their parent
+fn subtree_breadths(visits: &[Visit<Unit>], children: &[Vec<usize>], orientation: SvgOrientation) -> Vec<f64> {
+    let mut breadths = vec![0.0; visits.len()];
+    for i in (0..visits.len()).rev() {
+        let own = box_breadth(visits[i].unit, orientation);
+        breadths[i] = if children[i].is_empty() {
+            own
+        } else {
+            let side_by_side = children[i].iter().map(|&c| breadths[c]).sum::<f64>()
+                + sibling_spacing(orientation) * (children[i].len() - 1) as f64;
+            side_by_side.max(own)
+        };
     }
+    breadths
 }
 
-/// Calculate the maximum depth of a layout tree.
-fn max_depth(node: &LayoutNode) -> usize {
-    if node.children.is_empty() {
-        1
-    } else {
-        1 + node.children.iter().map(max_depth).max().unwrap_or(0)
+/// Offset of each tree level along the direction the chart grows.
+/// Top-down levels are evenly spaced; left-right columns are as wide as
+/// their widest box.
+fn level_offsets(units: &[Unit], orientation: SvgOrientation) -> Vec<f64> {
+    let mut widths: Vec<f64> = Vec::new();
+    for visit in walk_units(units) {
+        // Parents come first, so each level is reached from the one above
+        if widths.len() <= visit.depth {
+            widths.push(0.0);
+        }
+        widths[visit.depth] = widths[visit.depth].max(box_width(visit.unit));
+    }
+    let mut offsets = Vec::with_capacity(widths.len());
+    let mut offset = 0.0;
+    for width in widths {
+        offsets.push(offset);
+        offset += match orientation {
+            SvgOrientation::TopDown => BOX_HEIGHT + V_SPACING,
+            SvgOrientation::LeftRight => width + V_SPACING,
+        };
     }
+    offsets
 }
 
-/// Render a layout node and its children to SVG elements.
-fn render_node(node: &LayoutNode, elements: &mut Vec<String>) {
-    let cx = node.x + BOX_WIDTH / 2.0;
-    let cy = node.y + BOX_HEIGHT;
+/// Calculated layout node
+struct LayoutNode {
+    x: f64,
+    y: f64,
+    width: f64,
+    label: String,
+    sublabel: String,
+    children: Vec<LayoutNode>,
+}
 
-    // Draw connecting lines to children
-    for child in &node.children {
-        let child_cx = child.x + BOX_WIDTH / 2.0;
-        let child_top = child.y;
-        let mid_y = cy + V_SPACING / 2.0;
+/// Lays units out in one orientation; coordinates exclude padding and title
+struct Layout<'a> {
+    orientation: SvgOrientation,
+    level_offsets: &'a [f64],
+}
 
-        elements.push(format!(
-            r##"  <path d="M{cx},{cy} L{cx},{mid_y} L{child_cx},{mid_y} L{child_cx},{child_top}" fill="none" stroke="#666" stroke-width="1.5"/>"##,
-        ));
+impl Layout<'_> {
+    /// Layout a unit subtree occupying `breadth` starting at `start`.
+    fn node(&self, unit: &Unit, start: f64, breadth: f64, level: usize) -> LayoutNode {
+        let visits: Vec<Visit<Unit>> = walk_units(std::slice::from_ref(unit)).collect();
+        let children = child_lists(&visits);
+        let breadths = subtree_breadths(&visits, &children, self.orientation);
+        let spacing = sibling_spacing(self.orientation);
+
+        // Parents are visited first, so each unit's start is known when it is reached
+        let mut starts = vec![0.0; visits.len()];
+        starts[0] = start + (breadth - breadths[0]) / 2.0;
+        let mut nodes = Vec::with_capacity(visits.len());
+        for (i, visit) in visits.iter().enumerate() {
+            let unit = visit.unit;
+            let center = starts[i] + breadths[i] / 2.0;
+            let width = box_width(unit);
+            let depth = self.level_offsets[level + visit.depth];
+            let (x, y) = match self.orientation {
+                SvgOrientation::TopDown => (center - width / 2.0, depth),
+                SvgOrientation::LeftRight => (depth, center - BOX_HEIGHT / 2.0),
+            };
+
+            let total = children[i].iter().map(|&c| breadths[c]).sum::<f64>()
+                + spacing * children[i].len().saturating_sub(1) as f64;
+            let mut cur = center - total / 2.0;
+            for &c in &children[i] {
+                starts[c] = cur;
+                cur += breadths[c] + spacing;
+            }
+
+            let node = LayoutNode {
+                x,
+                y,
+                width,
+                label: unit.name.clone(),
+                sublabel: unit_sublabel(unit),
+                children: Vec::new(),
+            };
+            nodes.push((node, visit.parent));
+        }
+        assemble_forest(nodes, |node, children| node.children = children).remove(0)
     }
 
-    // Draw box
-    let escaped_label = xml_escape(&node.label);
-    let escaped_sub = xml_escape(&node.sublabel);
-    elements.push(format!(
-        r##"  <rect x="{}" y="{}" width="{}" height="{}" rx="6" ry="6" fill="#f0f4f8" stroke="#4a6fa5" stroke-width="1.5"/>"##,
-        node.x, node.y, BOX_WIDTH, BOX_HEIGHT
-    ));
-    elements.push(format!(
-        r##"  <text x="{}" y="{}" text-anchor="middle" font-size="12" font-family="sans-serif" fill="#1a1a2e">{}</text>"##,
-        node.x + BOX_WIDTH / 2.0,
-        node.y + 20.0,
-        escaped_label
-    ));
-    elements.push(format!(
-        r##"  <text x="{}" y="{}" text-anchor="middle" font-size="10" font-family="sans-serif" fill="#666">{}</text>"##,
-        node.x + BOX_WIDTH / 2.0,
-        node.y + 38.0,
-        escaped_sub
-    ));
+    /// Layout all top-level units side by side.
+    fn forest(&self, units: &[Unit]) -> Vec<LayoutNode> {
+        let mut cur = 0.0;
+        units
+            .iter()
+            .map(|unit| {
+                let breadth = subtree_breadth(unit, self.orientation);
+                let node = self.node(unit, cur, breadth, 0);
+                cur += breadth + sibling_spacing(self.orientation) * 2.0;
+                node
+            })
+            .collect()
+    }
+}
 
-    for child in &node.children {
-        render_node(child, elements);
+fn layout_children(node: &LayoutNode) -> &[LayoutNode] {
+    &node.children
+}
+
+/// Move a layout tree by (dx, dy).
+fn translate(node: &mut LayoutNode, dx: f64, dy: f64) {
+    let mut pending = vec![node];
+    while let Some(node) = pending.pop() {
+        node.x += dx;
+        node.y += dy;
+        pending.extend(node.children.iter_mut());
+    }
+}
+
+/// Right and bottom edges of a layout tree.
+fn extent(node: &LayoutNode) -> (f64, f64) {
+    TreeWalk::new(std::slice::from_ref(node), layout_children).fold((f64::MIN, f64::MIN), |(w, h), visit| {
+        (w.max(visit.unit.x + visit.unit.width), h.max(visit.unit.y + BOX_HEIGHT))
+    })
+}
+
+/// Render a layout node and its children to SVG elements.
+fn render_node(node: &LayoutNode, orientation: SvgOrientation, elements: &mut Vec<String>) {
+    for node in TreeWalk::new(std::slice::from_ref(node), layout_children).map(|v| v.unit) {
+        // Draw connecting lines to children
+        for child in &node.children {
+            let d = match orientation {
+                SvgOrientation::TopDown => {
+                    let (px, py) = (node.x + node.width / 2.0, node.y + BOX_HEIGHT);
+                    let (cx, cy) = (child.x + child.width / 2.0, child.y);
+                    let mid_y = py + (cy - py) / 2.0;
+                    format!("M{px},{py} L{px},{mid_y} L{cx},{mid_y} L{cx},{cy}")
+                }
+                SvgOrientation::LeftRight => {
+                    let (px, py) = (node.x + node.width, node.y + BOX_HEIGHT / 2.0);
+                    let (cx, cy) = (child.x, child.y + BOX_HEIGHT / 2.0);
+                    let mid_x = px + (cx - px) / 2.0;
+                    format!("M{px},{py} L{mid_x},{py} L{mid_x},{cy} L{cx},{cy}")
+                }
+            };
+            elements.push(format!(
+                r##"  <path d="{d}" fill="none" stroke="#666" stroke-width="1.5"/>"##,
+            ));
+        }
+
+        // Draw box
+        let escaped_label = xml_escape(&node.label);
+        let escaped_sub = xml_escape(&node.sublabel);
+        elements.push(format!(
+            r##"  <rect x="{}" y="{}" width="{}" height="{}" rx="6" ry="6" fill="#f0f4f8" stroke="#4a6fa5" stroke-width="1.5"/>"##,
+            node.x, node.y, node.width, BOX_HEIGHT
+        ));
+        elements.push(format!(
+            r##"  <text x="{}" y="{}" text-anchor="middle" font-size="{}" font-family="sans-serif" fill="#1a1a2e">{}</text>"##,
+            node.x + node.width / 2.0,
+            node.y + 20.0,
+            LABEL_FONT_SIZE,
+            escaped_label
+        ));
+        elements.push(format!(
+            r##"  <text x="{}" y="{}" text-anchor="middle" font-size="{}" font-family="sans-serif" fill="#666">{}</text>"##,
+            node.x + node.width / 2.0,
+            node.y + 38.0,
+            SUBLABEL_FONT_SIZE,
+            escaped_sub
+        ));
     }
 }
 
@@ -131,65 +327,262 @@ fn xml_escape(s: &str) -> String {
         .replace('"', "&quot;")
 }
 
-/// Export library to SVG organizational chart.
-///
-/// Renders each top-level unit as a tree with boxes and connecting lines.
-/// Produces a valid SVG file with the library name as title.
-pub fn export_svg(library: &Library, path: &Path) -> Result<()> {
-    let mut elements = Vec::new();
+/// Laid-out chart moved past the padding and title, with its total width and height
+fn layout_chart(units: &[Unit], orientation: SvgOrientation) -> (Vec<LayoutNode>, f64, f64) {
+    let offsets = level_offsets(units, orientation);
+    let layout = Layout {
+        orientation,
+        level_offsets: &offsets,
+    };
+    let mut layouts = layout.forest(units);
+    let (mut right, mut bottom) = (0.0f64, 0.0f64);
+    for node in &mut layouts {
+        translate(node, PADDING, PADDING + TITLE_HEIGHT);
+        let (r, b) = extent(node);
+        right = right.max(r);
+        bottom = bottom.max(b);
+    }
+    (layouts, right + PADDING, bottom + PADDING)
+}
 
-    if library.units.is_empty() {
+/// SVG document for an org chart of `units` under `title`
+fn render_chart(title: &str, units: &[Unit], orientation: SvgOrientation) -> String {
+    let escaped_title = xml_escape(title);
+    if units.is_empty() {
         // Minimal SVG for empty library
-        let title = xml_escape(&library.name);
-        let svg = format!(
+        return format!(
             r#"<?xml version="1.0" encoding="UTF-8"?>
 <svg xmlns="http://www.w3.org/2000/svg" width="400" height="100">
-  <text x="200" y="50" text-anchor="middle" font-size="16" font-family="sans-serif">{title} (no units)</text>
+  <text x="200" y="50" text-anchor="middle" font-size="16" font-family="sans-serif">{escaped_title} (no units)</text>
 </svg>"#
         );
-        std::fs::write(path, svg)?;
-        return Ok(());
-    }
-
-    // Layout all top-level units side by side
-    let mut layouts = Vec::new();
-    let mut total_width = PADDING;
-    for unit in &library.units {
-        let w = subtree_width(unit);
-        layouts.push(layout_unit(unit, total_width, PADDING + 30.0, w));
-        total_width += w + H_SPACING * 2.0;
     }
-    total_width += PADDING;
 
-    let depth = layouts.iter().map(max_depth).max().unwrap_or(1);
-    let total_height = PADDING * 2.0 + 30.0 + (depth as f64) * (BOX_HEIGHT + V_SPACING);
+    let (layouts, width, total_height) = layout_chart(units, orientation);
+    let title_width = estimate_text_width(title, 18.0) + 2.0 * PADDING;
+    let total_width = width.max(title_width);
 
-    // Title
-    let title = xml_escape(&library.name);
+    let mut elements = Vec::new();
     elements.push(format!(
         r##"  <text x="{}" y="30" text-anchor="middle" font-size="18" font-weight="bold" font-family="sans-serif" fill="#1a1a2e">{}</text>"##,
         total_width / 2.0,
-        title
+        escaped_title
     ));
-
-    for layout in &layouts {
-        render_node(layout, &mut elements);
+    for node in &layouts {
+        render_node(node, orientation, &mut elements);
     }
 
-    let svg = format!(
+    format!(
         "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n{}\n</svg>",
-        total_width as i64,
-        total_height as i64,
+        total_width.ceil() as i64,
+        total_height.ceil() as i64,
         elements.join("\n")
-    );
+    )
+}
+
+/// SVG document for an org chart of `units` titled with the library name.
+pub fn render_svg(library: &Library, units: &[Unit], options: &SvgOptions) -> String {
+    render_chart(&library.name, units, options.orientation)
+}
+
+/// Index path of a unit from the top level of the library
+pub type UnitPath = Vec<usize>;
+
+/// Contents of one page of a paginated chart
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChartPage {
+    /// Units with their direct children only; the children are drawn on later pages
+    Cover(Vec<UnitPath>),
+    /// Whole subtrees side by side
+    Subtrees(Vec<UnitPath>),
+}
 
-    std::fs::write(path, svg)?;
-    Ok(())
+fn unit_at<'a>(units: &'a [Unit], path: &[usize]) -> &'a Unit {
+    let (first, rest) = path.split_first().expect("unit paths are never empty");
+    rest.iter().fold(&units[*first], |unit, &i| &unit.children[i])
+}
+
+/// Split a chart into pages. `fits` tells whether the given units, drawn
+/// with their whole subtrees side by side, fit on one page.
+///
+/// If everything fits there is a single page. Otherwise the first page is a
+/// cover with the top-level units and their children, and the children's
+/// subtrees are packed in order onto as few pages as `fits` allows. A child
+/// too big for a page on its own gets a cover of its own and is split the
+/// same way; a childless unit too big for a page still gets one page.
+pub fn paginate(units: &[Unit], fits: impl Fn(&[&Unit]) -> bool) -> Vec<ChartPage> {
+    let top: Vec<UnitPath> = (0..units.len()).map(|i| vec![i]).collect();
+    if fits(&units.iter().collect::<Vec<_>>()) {
+        return vec![ChartPage::Subtrees(top)];
+    }
+    let mut pages = Vec::new();
+    paginate_cover(units, top, &fits, &mut pages);
+    pages
+}
+
+/// A cover page whose children are still being packed onto pages
+struct CoverFrame {
+    items: std::vec::IntoIter<UnitPath>,
+    current: Vec<UnitPath>,
+}
+
+/// Push a cover page for `parents` and start packing their children
+fn start_cover(units: &[Unit], parents: Vec<UnitPath>, pages: &mut Vec<ChartPage>) -> CoverFrame {
+    let items: Vec<UnitPath> = parents
+        .iter()
+        .flat_map(|parent| {
+            (0..unit_at(units, parent).children.len()).map(move |i| {
+                let mut path = parent.clone();
+                path.push(i);
+                path
+            })
+        })
+        .collect();
+    pages.push(ChartPage::Cover(parents));
+    CoverFrame {
+        items: items.into_iter(),
+        current: Vec::new(),
+    }
+}
+
+fn paginate_cover(units: &[Unit], parents: Vec<UnitPath>, fits: &impl Fn(&[&Unit]) -> bool, pages: &mut Vec<ChartPage>) {
+    let flush = |current: &mut Vec<UnitPath>, pages: &mut Vec<ChartPage>| {
+        if !current.is_empty() {
+            pages.push(ChartPage::Subtrees(std::mem::take(current)));
+        }
+    };
+    // A child too big for a page gets its cover, and its children their
+    // pages, before the packing of its siblings goes on
+    let mut covers = vec![start_cover(units, parents, pages)];
+    while let Some(cover) = covers.last_mut() {
+        let Some(item) = cover.items.next() else {
+            flush(&mut cover.current, pages);
+            covers.pop();
+            continue;
+        };
+        let unit = unit_at(units, &item);
+        if !fits(&[unit]) {
+            flush(&mut cover.current, pages);
+            if unit.children.is_empty() {
+                pages.push(ChartPage::Subtrees(vec![item]));
+            } else {
+                let nested = start_cover(units, vec![item], pages);
+                covers.push(nested);
+            }
+            continue;
+        }
+        cover.current.push(item);
+        let group: Vec<&Unit> = cover.current.iter().map(|p| unit_at(units, p)).collect();
+        if group.len() > 1 && !fits(&group) {
+            let item = cover.current.pop().expect("group has the new item");
+            flush(&mut cover.current, pages);
+            cover.current.push(item);
+        }
+    }
+}
+
+/// 1-based page on which each unit of `pages` first appears
+fn page_numbers(pages: &[ChartPage]) -> HashMap<&[usize], usize> {
+    let mut numbers = HashMap::new();
+    for (i, page) in pages.iter().enumerate() {
+        let (ChartPage::Cover(paths) | ChartPage::Subtrees(paths)) = page;
+        for path in paths {
+            numbers.entry(path.as_slice()).or_insert(i + 1);
+        }
+    }
+    numbers
+}
+
+/// Units to draw on one page; on a cover, children lose their subtrees and
+/// name the page they continue on
+fn page_units(units: &[Unit], page: &ChartPage, numbers: &HashMap<&[usize], usize>) -> Vec<Unit> {
+    match page {
+        ChartPage::Subtrees(paths) => paths.iter().map(|p| unit_at(units, p).clone()).collect(),
+        ChartPage::Cover(paths) => paths
+            .iter()
+            .map(|path| {
+                let mut unit = unit_at(units, path).clone();
+                for (i, child) in unit.children.iter_mut().enumerate() {
+                    let mut child_path = path.clone();
+                    child_path.push(i);
+                    child.children.clear();
+                    if let Some(n) = numbers.get(child_path.as_slice()) {
+                        child.name = format!("{} → p. {}", child.name, n);
+                    }
+                }
+                unit
+            })
+            .collect(),
+    }
+}
+
+/// Whether `units` drawn side by side fit within `limit`
+fn fits_limit(units: &[&Unit], orientation: SvgOrientation, limit: PageLimit) -> bool {
+    let forest: Vec<Unit> = units.iter().map(|u| (*u).clone()).collect();
+    let (_, width, height) = layout_chart(&forest, orientation);
+    width <= limit.width as f64 && height <= limit.height as f64
+}
+
+/// `dir/stem_suffix.ext` next to `path`
+fn sibling_path(path: &Path, suffix: &str, ext: &str) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!("{}_{}.{}", stem, suffix, ext))
+}
+
+/// Export an org chart of `units` to an SVG file.
+///
+/// Units are passed separately because libraries loaded from the database
+/// keep their tree in the units table rather than in `library.units`.
+pub fn export_svg(library: &Library, units: &[Unit], path: &Path, options: &SvgOptions) -> Result<()> {
+    std::fs::write(path, render_svg(library, units, options))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Export an org chart of `units` to `path`, or, if it is larger than
+/// `options.page_limit`, to numbered pages next to it (`name_p1.svg`,
+/// `name_p2.svg`, …) with an HTML index `name_index.html` linking them.
+/// Returns the files written.
+pub fn export_svg_pages(library: &Library, units: &[Unit], path: &Path, options: &SvgOptions) -> Result<Vec<PathBuf>> {
+    let pages = match options.page_limit {
+        Some(limit) => paginate(units, |group| fits_limit(group, options.orientation, limit)),
+        None => Vec::new(),
+    };
+    if pages.len() < 2 {
+        export_svg(library, units, path, options)?;
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let numbers = page_numbers(&pages);
+    let mut files = Vec::with_capacity(pages.len() + 1);
+    let mut entries = Vec::with_capacity(pages.len());
+    for (i, page) in pages.iter().enumerate() {
+        let page_path = sibling_path(path, &format!("p{}", i + 1), "svg");
+        let page_units = page_units(units, page, &numbers);
+        let title = format!("{} ({}/{})", library.name, i + 1, pages.len());
+        std::fs::write(&page_path, render_chart(&title, &page_units, options.orientation))
+            .with_context(|| format!("Failed to write {}", page_path.display()))?;
+        let (ChartPage::Cover(paths) | ChartPage::Subtrees(paths)) = page;
+        let names: Vec<String> = paths.iter().map(|p| xml_escape(&unit_at(units, p).name)).collect();
+        let file_name = page_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        entries.push(format!("  <li><a href=\"{}\">{}</a></li>", xml_escape(&file_name), names.join(", ")));
+        files.push(page_path);
+    }
+
+    let index_path = sibling_path(path, "index", "html");
+    let title = xml_escape(&library.name);
+    let index = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n<ol>\n{}\n</ol>\n</body>\n</html>\n",
+        entries.join("\n")
+    );
+    std::fs::write(&index_path, index).with_context(|| format!("Failed to write {}", index_path.display()))?;
+    files.push(index_path);
+    Ok(files)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::tree::unit_chain;
     use crate::models::{Library, Unit, Personnel, Equipment};
     use tempfile::NamedTempFile;
 
@@ -202,7 +595,7 @@ mod tests {
             "Author".to_string(),
         );
         let file = NamedTempFile::new().unwrap();
-        export_svg(&library, file.path()).unwrap();
+        export_svg(&library, &library.units, file.path(), &SvgOptions::default()).unwrap();
 
         let content = std::fs::read_to_string(file.path()).unwrap();
         assert!(content.contains("Test"));
@@ -230,7 +623,7 @@ mod tests {
         library.units.push(platoon);
 
         let file = NamedTempFile::new().unwrap();
-        export_svg(&library, file.path()).unwrap();
+        export_svg(&library, &library.units, file.path(), &SvgOptions::default()).unwrap();
 
         let content = std::fs::read_to_string(file.path()).unwrap();
         assert!(content.contains("US Army"));
@@ -251,7 +644,7 @@ mod tests {
     #[test]
     fn test_subtree_width_leaf() {
         let unit = Unit::new("Leaf".to_string(), "squad".to_string());
-        assert_eq!(subtree_width(&unit), BOX_WIDTH);
+        assert_eq!(subtree_breadth(&unit, SvgOrientation::TopDown), BOX_WIDTH);
     }
 
     #[test]
@@ -260,6 +653,215 @@ mod tests {
         parent.children.push(Unit::new("A".to_string(), "squad".to_string()));
         parent.children.push(Unit::new("B".to_string(), "squad".to_string()));
         let expected = BOX_WIDTH * 2.0 + H_SPACING;
-        assert!((subtree_width(&parent) - expected).abs() < 0.01);
+        assert!((subtree_breadth(&parent, SvgOrientation::TopDown) - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_box_width_fits_cyrillic_names() {
+        let short = Unit::new("Рота".to_string(), "company".to_string());
+        assert_eq!(box_width(&short), BOX_WIDTH);
+
+        let name = "Отдельный мотострелковый батальон специального назначения";
+        let long = Unit::new(name.to_string(), "battalion".to_string());
+        let text = estimate_text_width(name, LABEL_FONT_SIZE);
+        assert!(text > name.chars().count() as f64 * LABEL_FONT_SIZE * 0.5);
+        assert!(box_width(&long) >= text + 2.0 * TEXT_PADDING);
+    }
+
+    #[test]
+    fn test_siblings_do_not_overlap() {
+        let mut root = Unit::new("Division".to_string(), "division".to_string());
+        for i in 0..3 {
+            let mut brigade = Unit::new(format!("Brigade {}", i), "brigade".to_string());
+            for j in 0..4 {
+                brigade.children.push(Unit::new(format!("Очень длинное название батальона {}", j), "battalion".to_string()));
+            }
+            root.children.push(brigade);
+        }
+        for orientation in [SvgOrientation::TopDown, SvgOrientation::LeftRight] {
+            let offsets = level_offsets(std::slice::from_ref(&root), orientation);
+            let layout = Layout { orientation, level_offsets: &offsets };
+            let tree = layout.node(&root, 0.0, subtree_breadth(&root, orientation), 0);
+            let leaves: Vec<&LayoutNode> = tree.children.iter().flat_map(|b| b.children.iter()).collect();
+            assert_eq!(leaves.len(), 12);
+            for pair in leaves.windows(2) {
+                match orientation {
+                    SvgOrientation::TopDown => assert!(pair[0].x + pair[0].width <= pair[1].x),
+                    SvgOrientation::LeftRight => assert!(pair[0].y + BOX_HEIGHT <= pair[1].y),
+                }
+            }
+            // Children sit beyond their parent in the growth direction
+            match orientation {
+                SvgOrientation::TopDown => assert!(tree.children[0].y >= tree.y + BOX_HEIGHT),
+                SvgOrientation::LeftRight => assert!(tree.children[0].x >= tree.x + tree.width),
+            }
+        }
+    }
+
+    #[test]
+    fn test_page_limit_parse() {
+        assert_eq!(PageLimit::parse(" 4000", "3000 "), Some(PageLimit { width: 4000, height: 3000 }));
+        assert_eq!(PageLimit::parse("100", "3000"), None);
+        assert_eq!(PageLimit::parse("4000", ""), None);
+        assert_eq!(PageLimit::parse("-1", "abc"), None);
+    }
+
+    /// A company with `platoons` platoons of `squads` squads each
+    fn company(name: &str, platoons: usize, squads: usize) -> Unit {
+        let mut company = Unit::new(name.to_string(), "company".to_string());
+        for p in 0..platoons {
+            let mut platoon = Unit::new(format!("{} platoon {}", name, p), "platoon".to_string());
+            for s in 0..squads {
+                platoon.children.push(Unit::new(format!("{} squad {}.{}", name, p, s), "squad".to_string()));
+            }
+            company.children.push(platoon);
+        }
+        company
+    }
+
+    fn count(unit: &Unit) -> usize {
+        1 + unit.children.iter().map(count).sum::<usize>()
+    }
+
+    /// A page holds at most `n` units
+    fn at_most(n: usize) -> impl Fn(&[&Unit]) -> bool {
+        move |group| group.iter().map(|u| count(u)).sum::<usize>() <= n
+    }
+
+    #[test]
+    fn test_paginate_single_page_when_everything_fits() {
+        let units = vec![company("A", 2, 2), company("B", 1, 1)];
+        assert_eq!(paginate(&units, at_most(100)), vec![ChartPage::Subtrees(vec![vec![0], vec![1]])]);
+    }
+
+    #[test]
+    fn test_paginate_packs_children_greedily() {
+        // Five platoons of three units each, seven units per page
+        let units = vec![company("A", 5, 2)];
+        assert_eq!(
+            paginate(&units, at_most(7)),
+            vec![
+                ChartPage::Cover(vec![vec![0]]),
+                ChartPage::Subtrees(vec![vec![0, 0], vec![0, 1]]),
+                ChartPage::Subtrees(vec![vec![0, 2], vec![0, 3]]),
+                ChartPage::Subtrees(vec![vec![0, 4]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paginate_recurses_into_oversized_child() {
+        let mut root = Unit::new("Corps".to_string(), "corps".to_string());
+        root.children.push(company("A", 1, 1));
+        root.children.push(company("B", 3, 3));
+        root.children.push(Unit::new("Signals".to_string(), "battalion".to_string()));
+        let units = vec![root];
+        assert_eq!(
+            paginate(&units, at_most(8)),
+            vec![
+                ChartPage::Cover(vec![vec![0]]),
+                ChartPage::Subtrees(vec![vec![0, 0]]),
+                ChartPage::Cover(vec![vec![0, 1]]),
+                ChartPage::Subtrees(vec![vec![0, 1, 0], vec![0, 1, 1]]),
+                ChartPage::Subtrees(vec![vec![0, 1, 2]]),
+                ChartPage::Subtrees(vec![vec![0, 2]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paginate_gives_oversized_leaf_its_own_page() {
+        let mut root = Unit::new("Root".to_string(), "corps".to_string());
+        root.children.push(Unit::new("Small".to_string(), "squad".to_string()));
+        root.children.push(Unit::new("Huge".to_string(), "squad".to_string()));
+        root.children.push(Unit::new("Small too".to_string(), "squad".to_string()));
+        let units = vec![root];
+        let pages = paginate(&units, |group| {
+            group.iter().map(|u| count(u)).sum::<usize>() < 3 && group.iter().all(|u| u.name != "Huge")
+        });
+        assert_eq!(
+            pages,
+            vec![
+                ChartPage::Cover(vec![vec![0]]),
+                ChartPage::Subtrees(vec![vec![0, 0]]),
+                ChartPage::Subtrees(vec![vec![0, 1]]),
+                ChartPage::Subtrees(vec![vec![0, 2]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paginate_draws_every_unit_once() {
+        let units = vec![company("A", 4, 3), company("B", 6, 1), Unit::new("HQ".to_string(), "hq".to_string())];
+        let pages = paginate(&units, at_most(6));
+        let numbers = page_numbers(&pages);
+        // Every unit is either on a cover or inside exactly one subtree page
+        fn walk(unit: &Unit, path: UnitPath, numbers: &HashMap<&[usize], usize>, pages: &[ChartPage], drawn_above: bool) {
+            let own = numbers.get(path.as_slice()).copied();
+            let in_subtree = own.is_some_and(|n| matches!(pages[n - 1], ChartPage::Subtrees(_)));
+            assert!(own.is_some() != drawn_above, "unit {:?} is not drawn exactly once", path);
+            for (i, child) in unit.children.iter().enumerate() {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                walk(child, child_path, numbers, pages, drawn_above || in_subtree);
+            }
+        }
+        for (i, unit) in units.iter().enumerate() {
+            walk(unit, vec![i], &numbers, &pages, false);
+        }
+    }
+
+    #[test]
+    fn test_export_svg_pages_writes_pages_and_index() {
+        let library = Library::new("Corps".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        let units = vec![company("A", 6, 4), company("B", 6, 4)];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("corps.svg");
+        let options = SvgOptions {
+            page_limit: Some(PageLimit { width: 2000, height: 1000 }),
+            ..SvgOptions::default()
+        };
+        let files = export_svg_pages(&library, &units, &path, &options).unwrap();
+
+        assert!(files.len() > 2);
+        assert!(!path.exists());
+        assert_eq!(files[0], dir.path().join("corps_p1.svg"));
+        assert_eq!(files.last().unwrap(), &dir.path().join("corps_index.html"));
+        let cover = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(cover.contains("→ p. 2"));
+        assert!(!cover.contains("squad"));
+        let index = std::fs::read_to_string(files.last().unwrap()).unwrap();
+        assert!(index.contains("href=\"corps_p2.svg\""));
+
+        // Without a limit, or when the chart fits, one file is written as before
+        let single = export_svg_pages(&library, &units, &path, &SvgOptions::default()).unwrap();
+        assert_eq!(single, vec![path.clone()]);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_deep_and_wide_trees_render() {
+        let deep = unit_chain(1000);
+        let svg = render_chart("Deep", &deep, SvgOrientation::TopDown);
+        assert_eq!(svg.matches("<rect").count(), 1000);
+        assert_eq!(svg.matches("<path").count(), 999);
+        let (_, _, height) = layout_chart(&deep, SvgOrientation::TopDown);
+        assert!(height > 1000.0 * BOX_HEIGHT);
+
+        let mut wide = Unit::new("Root".to_string(), "unit".to_string());
+        wide.children = (0..3000).map(|i| Unit::new(format!("C{}", i), "unit".to_string())).collect();
+        let (layouts, width, _) = layout_chart(std::slice::from_ref(&wide), SvgOrientation::TopDown);
+        assert_eq!(layouts[0].children.len(), 3000);
+        assert!(width > 3000.0 * BOX_WIDTH);
+    }
+
+    #[test]
+    fn test_paginate_deep_chain() {
+        // Nothing but a single unit fits: a cover per level, the last unit on a page of its own
+        let units = unit_chain(500);
+        let pages = paginate(&units, |group| group.len() == 1 && group[0].children.is_empty());
+        assert_eq!(pages.len(), 500);
+        assert_eq!(pages[0], ChartPage::Cover(vec![vec![0]]));
+        assert_eq!(pages[499], ChartPage::Subtrees(vec![vec![0; 500]]));
     }
 }
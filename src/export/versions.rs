@@ -0,0 +1,154 @@
+//! Export every snapshot of a library as one JSON file per version
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::db::repositories::VersionRepo;
+use super::canonical::{canonical_json_str_pretty, to_canonical_json_pretty};
+use super::full::snapshot_library_json;
+
+/// Entry of `index.json` describing one exported version
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct VersionIndexEntry {
+    pub version: i64,
+    pub file: String,
+    pub timestamp: i64,
+    /// RFC 3339 date (UTC)
+    pub date: String,
+    pub description: Option<String>,
+    pub author: Option<String>,
+}
+
+/// File name used for a version
+pub fn version_file_name(version: i64) -> String {
+    format!("v{}.json", version)
+}
+
+/// Write `v{N}.json` for every snapshot of the library plus an `index.json`.
+///
+/// Output is canonical (sorted keys, pretty-printed) so repeated exports are byte-identical.
+/// Returns the index entries in ascending version order.
+pub fn export_all_versions(
+    conn: &Connection,
+    library_id: i64,
+    out_dir: &Path,
+) -> Result<Vec<VersionIndexEntry>> {
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("Failed to create output directory {}", out_dir.display()))?;
+
+    let mut snapshots = VersionRepo::new(conn).list_by_library(library_id)?;
+    snapshots.sort_by_key(|s| s.version);
+
+    let mut index = Vec::new();
+    for snapshot in &snapshots {
+        let file = version_file_name(snapshot.version);
+        let content = canonical_json_str_pretty(&snapshot.data)
+            .with_context(|| format!("Snapshot v{} is not valid JSON", snapshot.version))?;
+        std::fs::write(out_dir.join(&file), content)?;
+
+        let author = serde_json::from_str::<serde_json::Value>(&snapshot.data)
+            .ok()
+            .and_then(|v| snapshot_library_json(&v).get("author").and_then(|a| a.as_str()).map(str::to_string));
+        index.push(VersionIndexEntry {
+            version: snapshot.version,
+            file,
+            timestamp: snapshot.timestamp,
+            date: chrono::DateTime::from_timestamp(snapshot.timestamp, 0)
+                .map(|d| d.to_rfc3339())
+                .unwrap_or_default(),
+            description: snapshot.description.clone(),
+            author,
+        });
+    }
+
+    let index_path: PathBuf = out_dir.join("index.json");
+    std::fs::write(&index_path, to_canonical_json_pretty(&serde_json::json!({
+        "library_id": library_id,
+        "versions": index,
+    }))?)?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::db::repositories::LibraryRepo;
+    use crate::models::{Library, Snapshot};
+    use tempfile::TempDir;
+
+    fn three_version_fixture(db: &Database) -> i64 {
+        let lib_repo = LibraryRepo::new(db.conn());
+        let version_repo = VersionRepo::new(db.conn());
+        let mut library = Library::new(
+            "Test".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        lib_repo.create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+        for v in 1..=3 {
+            library.set_version(v);
+            library.tags = vec![format!("tag{}", v)];
+            let mut snapshot = Snapshot::with_description(
+                lib_id,
+                v,
+                serde_json::to_string(&library).unwrap(),
+                format!("Version {}", v),
+            );
+            snapshot.timestamp = 1_700_000_000 + v;
+            version_repo.create(&mut snapshot).unwrap();
+        }
+        lib_id
+    }
+
+    #[test]
+    fn test_export_all_versions_files_and_index() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = three_version_fixture(&db);
+        let dir = TempDir::new().unwrap();
+
+        let index = export_all_versions(db.conn(), lib_id, dir.path()).unwrap();
+        assert_eq!(index.len(), 3);
+        for v in 1..=3 {
+            assert!(dir.path().join(format!("v{}.json", v)).exists());
+        }
+        assert_eq!(index[0].version, 1);
+        assert_eq!(index[2].description.as_deref(), Some("Version 3"));
+        assert_eq!(index[1].author.as_deref(), Some("Author"));
+
+        let index_json: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(dir.path().join("index.json")).unwrap(),
+        )
+        .unwrap();
+        let versions = index_json["versions"].as_array().unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0]["file"], "v1.json");
+        assert_eq!(versions[2]["timestamp"], 1_700_000_003);
+
+        let v2: Library = serde_json::from_str(
+            &std::fs::read_to_string(dir.path().join("v2.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(v2.version, 2);
+        assert_eq!(v2.tags, vec!["tag2".to_string()]);
+    }
+
+    #[test]
+    fn test_export_all_versions_is_byte_identical() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = three_version_fixture(&db);
+        let dir1 = TempDir::new().unwrap();
+        let dir2 = TempDir::new().unwrap();
+        export_all_versions(db.conn(), lib_id, dir1.path()).unwrap();
+        export_all_versions(db.conn(), lib_id, dir2.path()).unwrap();
+        for name in ["v1.json", "v2.json", "v3.json", "index.json"] {
+            let a = std::fs::read(dir1.path().join(name)).unwrap();
+            let b = std::fs::read(dir2.path().join(name)).unwrap();
+            assert_eq!(a, b, "{} differs between runs", name);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Excel workbook export
+//!
+//! Four sheets: the library's metadata, the unit tree (names indented by
+//! depth, with personnel and equipment rolled up from subordinate units),
+//! and flat lists of personnel and equipment per unit. Counts are numeric
+//! cells, so spreadsheets can sum and filter them.
+
+use anyhow::{Context, Result};
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use crate::models::{walk_units, Library, Unit};
+use crate::services::metrics;
+use super::anonymize::{anonymize_library, AnonymizeOptions};
+use std::path::Path;
+
+/// Sheet names, in workbook order
+pub const LIBRARY_SHEET: &str = "Library";
+pub const UNITS_SHEET: &str = "Units";
+pub const PERSONNEL_SHEET: &str = "Personnel";
+pub const EQUIPMENT_SHEET: &str = "Equipment";
+
+/// Deepest indentation Excel displays; deeper units are indented as much
+const MAX_INDENT: usize = 15;
+
+/// Export `units` of a library (its own, or a subset such as those in
+/// effect at a date) to an Excel workbook, passed through the anonymization
+/// pass first with `anonymize`
+pub fn export_xlsx(library: &Library, units: &[Unit], anonymize: Option<&AnonymizeOptions>, path: &Path) -> Result<()> {
+    metrics::timed("export_xlsx", || {
+        let anonymized = anonymize.map(|options| {
+            let mut copy = library.clone();
+            copy.units = units.to_vec();
+            let mut copy = anonymize_library(copy, options);
+            let units = std::mem::take(&mut copy.units);
+            (copy, units)
+        });
+        let (library, units) = match &anonymized {
+            Some((library, units)) => (library, units.as_slice()),
+            None => (library, units),
+        };
+        let mut workbook = library_workbook(library, units).context("Failed to build workbook")?;
+        workbook
+            .save(path)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    })
+}
+
+/// Units depth-first with their parent's name, depth and rolled-up totals
+struct UnitRow<'a> {
+    unit: &'a Unit,
+    parent: &'a str,
+    depth: usize,
+    personnel: usize,
+    equipment: usize,
+}
+
+fn unit_rows(units: &[Unit]) -> Vec<UnitRow<'_>> {
+    let mut rows: Vec<UnitRow> = Vec::new();
+    let mut parents: Vec<Option<usize>> = Vec::new();
+    for visit in walk_units(units) {
+        rows.push(UnitRow {
+            unit: visit.unit,
+            parent: visit.parent.map_or("", |p| rows[p].unit.name.as_str()),
+            depth: visit.depth,
+            personnel: visit.unit.personnel.len(),
+            equipment: visit.unit.equipment.iter().map(|e| e.quantity).sum(),
+        });
+        parents.push(visit.parent);
+    }
+    // Children come after their parent, so adding from the end rolls totals up
+    for i in (0..rows.len()).rev() {
+        if let Some(p) = parents[i] {
+            rows[p].personnel += rows[i].personnel;
+            rows[p].equipment += rows[i].equipment;
+        }
+    }
+    rows
+}
+
+fn library_workbook(library: &Library, units: &[Unit]) -> Result<Workbook, XlsxError> {
+    let bold = Format::new().set_bold();
+    let mut workbook = Workbook::new();
+
+    let sheet = workbook.add_worksheet().set_name(LIBRARY_SHEET)?;
+    let metadata = [
+        ("Name", library.name.as_str()),
+        ("Country", library.country.as_str()),
+        ("Era", library.era.as_str()),
+        ("Author", library.author.as_str()),
+    ];
+    for (row, (label, value)) in (0u32..).zip(metadata) {
+        sheet.write_string_with_format(row, 0, label, &bold)?;
+        sheet.write_string(row, 1, value)?;
+    }
+    let row = metadata.len() as u32;
+    sheet.write_string_with_format(row, 0, "Version", &bold)?;
+    sheet.write_number(row, 1, library.version as f64)?;
+    sheet.write_string_with_format(row + 1, 0, "Tags", &bold)?;
+    sheet.write_string(row + 1, 1, library.tags.join(", "))?;
+    sheet.set_column_width(1, 40)?;
+
+    let rows = unit_rows(units);
+    let sheet = workbook.add_worksheet().set_name(UNITS_SHEET)?;
+    write_header(
+        sheet,
+        &["Unit", "Type", "Parent", "Depth", "Personnel", "Equipment", "Total Personnel", "Total Equipment"],
+        &bold,
+    )?;
+    for (row, unit) in (1u32..).zip(&rows) {
+        let indent = Format::new().set_indent(unit.depth.min(MAX_INDENT) as u8);
+        sheet.write_string_with_format(row, 0, &unit.unit.name, &indent)?;
+        sheet.write_string(row, 1, &unit.unit.unit_type)?;
+        sheet.write_string(row, 2, unit.parent)?;
+        sheet.write_number(row, 3, unit.depth as f64)?;
+        sheet.write_number(row, 4, unit.unit.personnel.len() as f64)?;
+        let own: usize = unit.unit.equipment.iter().map(|e| e.quantity).sum();
+        sheet.write_number(row, 5, own as f64)?;
+        sheet.write_number(row, 6, unit.personnel as f64)?;
+        sheet.write_number(row, 7, unit.equipment as f64)?;
+    }
+    sheet.set_column_width(0, 40)?;
+    sheet.set_freeze_panes(1, 0)?;
+
+    let sheet = workbook.add_worksheet().set_name(PERSONNEL_SHEET)?;
+    write_header(sheet, &["Unit", "Position", "Rank"], &bold)?;
+    let personnel = rows.iter().flat_map(|r| r.unit.personnel.iter().map(move |p| (r.unit, p)));
+    for (row, (unit, p)) in (1u32..).zip(personnel) {
+        sheet.write_string(row, 0, &unit.name)?;
+        sheet.write_string(row, 1, &p.position)?;
+        sheet.write_string(row, 2, p.rank.as_deref().unwrap_or(""))?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+
+    let sheet = workbook.add_worksheet().set_name(EQUIPMENT_SHEET)?;
+    write_header(sheet, &["Unit", "Equipment", "Quantity"], &bold)?;
+    let equipment = rows.iter().flat_map(|r| r.unit.equipment.iter().map(move |e| (r.unit, e)));
+    for (row, (unit, e)) in (1u32..).zip(equipment) {
+        sheet.write_string(row, 0, &unit.name)?;
+        sheet.write_string(row, 1, &e.name)?;
+        sheet.write_number(row, 2, e.quantity as f64)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+
+    Ok(workbook)
+}
+
+fn write_header(sheet: &mut Worksheet, titles: &[&str], format: &Format) -> Result<(), XlsxError> {
+    for (col, title) in (0u16..).zip(titles) {
+        sheet.write_string_with_format(0, col, *title, format)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Equipment, Personnel};
+    use std::io::Read;
+    use tempfile::NamedTempFile;
+
+    /// XML of each worksheet and the shared strings of a written workbook
+    fn read_workbook(path: &Path) -> (Vec<String>, String) {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut xml = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut xml).unwrap();
+            xml
+        };
+        let sheets = (1..=4).map(|i| read(&format!("xl/worksheets/sheet{}.xml", i))).collect();
+        (sheets, read("xl/sharedStrings.xml"))
+    }
+
+    fn sample() -> Library {
+        let mut library = Library::new("Армия 1943".to_string(), "SU".to_string(), "1943".to_string(), "A".to_string());
+        let mut regiment = Unit::new("Стрелковый полк".to_string(), "regiment".to_string());
+        regiment.personnel.push(Personnel::new("Командир".to_string()));
+        regiment.equipment.push(Equipment::new("ППШ".to_string(), 4));
+        let mut battalion = Unit::new("1-й батальон".to_string(), "battalion".to_string());
+        battalion.personnel.push(Personnel::new("Командир батальона".to_string()));
+        battalion.personnel.push(Personnel::new("Адъютант".to_string()));
+        battalion.equipment.push(Equipment::new("Винтовка".to_string(), 30));
+        regiment.children.push(battalion);
+        library.units.push(regiment);
+        library
+    }
+
+    #[test]
+    fn test_export_xlsx_sheets_and_cyrillic_names() {
+        let library = sample();
+        let file = NamedTempFile::new().unwrap();
+        export_xlsx(&library, &library.units, None, file.path()).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(file.path()).unwrap()).unwrap();
+        let mut workbook = String::new();
+        archive.by_name("xl/workbook.xml").unwrap().read_to_string(&mut workbook).unwrap();
+        let names: Vec<usize> = [LIBRARY_SHEET, UNITS_SHEET, PERSONNEL_SHEET, EQUIPMENT_SHEET]
+            .iter()
+            .map(|name| workbook.find(&format!("name=\"{}\"", name)).unwrap())
+            .collect();
+        assert!(names.windows(2).all(|w| w[0] < w[1]));
+
+        let (_, strings) = read_workbook(file.path());
+        for text in ["Армия 1943", "Стрелковый полк", "1-й батальон", "Адъютант", "Винтовка"] {
+            assert!(strings.contains(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_export_xlsx_writes_numbers_and_rolled_up_totals() {
+        let library = sample();
+        let file = NamedTempFile::new().unwrap();
+        export_xlsx(&library, &library.units, None, file.path()).unwrap();
+        let (sheets, _) = read_workbook(file.path());
+
+        // Regiment: own 1 person and 4 items, with the battalion 3 and 34
+        let units = &sheets[1];
+        for cell in ["<c r=\"E2\"><v>1</v>", "<c r=\"F2\"><v>4</v>", "<c r=\"G2\"><v>3</v>", "<c r=\"H2\"><v>34</v>"] {
+            assert!(units.contains(cell), "{}", cell);
+        }
+        assert!(units.contains("<c r=\"G3\"><v>2</v>"));
+        assert!(units.contains("<c r=\"D3\"><v>1</v>"));
+        assert!(sheets[3].contains("<c r=\"C3\"><v>30</v>"));
+        // Numbers are never written as strings
+        assert!(!units.contains("t=\"str\""));
+    }
+
+    #[test]
+    fn test_export_xlsx_anonymized_hides_the_author() {
+        let mut library = sample();
+        library.author = "John Smith".to_string();
+        let file = NamedTempFile::new().unwrap();
+        let options = AnonymizeOptions::default();
+        export_xlsx(&library, &library.units, Some(&options), file.path()).unwrap();
+        let (_, strings) = read_workbook(file.path());
+        assert!(!strings.contains("John Smith"));
+        assert!(strings.contains(&options.placeholder_author));
+        assert!(strings.contains("1-й батальон"));
+    }
+
+    #[test]
+    fn test_export_xlsx_indents_by_depth() {
+        let library = sample();
+        let file = NamedTempFile::new().unwrap();
+        export_xlsx(&library, &library.units, None, file.path()).unwrap();
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(file.path()).unwrap()).unwrap();
+        let mut styles = String::new();
+        archive.by_name("xl/styles.xml").unwrap().read_to_string(&mut styles).unwrap();
+        assert!(styles.contains("indent=\"1\""));
+    }
+
+    #[test]
+    fn test_unit_rows_roll_up_deep_trees() {
+        let mut units = crate::models::tree::unit_chain(1000);
+        fn last(unit: &mut Unit) -> &mut Unit {
+            if unit.children.is_empty() {
+                unit
+            } else {
+                last(unit.children.last_mut().unwrap())
+            }
+        }
+        last(&mut units[0]).personnel.push(Personnel::new("Rifleman".to_string()));
+        let rows = unit_rows(&units);
+        assert_eq!(rows.len(), 1000);
+        assert!(rows.iter().all(|r| r.personnel == 1));
+        assert_eq!(rows[999].parent, "U998");
+    }
+}
@@ -0,0 +1,48 @@
+//! YAML export functionality
+//!
+//! Same schema as the JSON export, so files read back with
+//! [`crate::import::import_yaml`].
+
+use anyhow::{Context, Result};
+use crate::models::Library;
+use crate::services::metrics;
+use std::path::Path;
+
+/// Export library to YAML file
+pub fn export_yaml(library: &Library, path: &Path) -> Result<()> {
+    metrics::timed("export_yaml", || {
+        let yaml = serde_yaml::to_string(library)?;
+        std::fs::write(path, yaml).with_context(|| format!("Failed to write {}", path.display()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::import_yaml;
+    use crate::models::{Equipment, Personnel, Unit};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_export_yaml_round_trip() {
+        let mut library = Library::new(
+            "Мотострелковый полк: 1985".to_string(),
+            "RU".to_string(),
+            "1985".to_string(),
+            "Author".to_string(),
+        );
+        library.tags = vec!["cold war".to_string(), "- dash".to_string()];
+        let mut battalion = Unit::new("1-й батальон".to_string(), "battalion".to_string());
+        battalion.add_personnel(Personnel {
+            position: "Командир".to_string(),
+            rank: Some("майор".to_string()),
+        });
+        battalion.add_equipment(Equipment::new("БМП-2".to_string(), 31));
+        battalion.add_child(Unit::new("yes".to_string(), "company".to_string()));
+        library.add_unit(battalion);
+
+        let file = NamedTempFile::new().unwrap();
+        export_yaml(&library, file.path()).unwrap();
+        assert_eq!(import_yaml(file.path()).unwrap(), library);
+    }
+}
@@ -1,8 +1,96 @@
 //! Internationalization support
+//!
+//! UI strings are keyed by their English text. Every other language has a
+//! table in `i18n/<code>.json`, embedded in the binary and listed in
+//! [`LANGUAGES`]; adding a language means adding its file and a line there.
+//! Keys missing from a table fall back to English.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::OnceLock;
 
-/// Supported languages
+/// A UI language
+#[derive(Debug)]
+pub struct LanguageInfo {
+    /// Code stored in the settings ("en", "ru")
+    pub code: &'static str,
+    /// Name of the language in itself, as offered in menus
+    pub name: &'static str,
+    /// Translation table source; `None` for English, the key language
+    table: Option<&'static str>,
+}
+
+/// UI languages, in menu order; the first is the fallback
+const LANGUAGES: &[LanguageInfo] = &[
+    LanguageInfo { code: "en", name: "English", table: None },
+    LanguageInfo { code: "ru", name: "Русский", table: Some(include_str!("../i18n/ru.json")) },
+];
+
+/// Languages the UI can be shown in
+pub fn available_languages() -> &'static [LanguageInfo] {
+    LANGUAGES
+}
+
+/// Language with the code `code`, if the UI has it
+pub fn language_info(code: &str) -> Option<&'static LanguageInfo> {
+    LANGUAGES.iter().find(|l| l.code == code)
+}
+
+/// `code` if the UI has that language, else English
+pub fn supported_code(code: &str) -> &'static str {
+    language_info(code).unwrap_or(&LANGUAGES[0]).code
+}
+
+/// UI language for an OS locale ("ru-RU", "ru_UA.UTF-8", "en_US", …);
+/// unsupported or malformed locales get English
+pub fn language_for_locale(locale: &str) -> &'static str {
+    let primary = locale
+        .trim()
+        .split(['-', '_', '.', '@'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    supported_code(&primary)
+}
+
+/// Parse a translation table: a JSON object from English keys to
+/// translations. Lines starting with `//` are comments.
+pub fn parse_translations(source: &str) -> Result<HashMap<String, String>> {
+    let json: Vec<&str> = source.lines().filter(|line| !line.trim_start().starts_with("//")).collect();
+    serde_json::from_str(&json.join("\n")).context("Invalid translation table")
+}
+
+/// Translation table of a language; `None` for English and unknown codes
+pub fn translation_table(code: &str) -> Option<&'static HashMap<String, String>> {
+    static TABLES: OnceLock<HashMap<&'static str, HashMap<String, String>>> = OnceLock::new();
+    TABLES
+        .get_or_init(|| {
+            LANGUAGES
+                .iter()
+                .filter_map(|l| Some((l.code, l.table?)))
+                .map(|(code, source)| {
+                    let table = parse_translations(source).unwrap_or_else(|e| {
+                        log::error!("Failed to parse {}.json translations: {:#}", code, e);
+                        HashMap::new()
+                    });
+                    (code, table)
+                })
+                .collect()
+        })
+        .get(code)
+}
+
+/// Text of `key` in the language `code`, or the English key when the
+/// language or the key's translation is missing
+pub fn translate(code: &str, key: &str) -> String {
+    translation_table(code)
+        .and_then(|table| table.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// Languages with grammar rules of their own (plurals, digit grouping);
+/// any other language follows English
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Language {
     English,
@@ -20,10 +108,7 @@ impl Language {
 
     /// Get language name
     pub fn name(&self) -> &'static str {
-        match self {
-            Language::English => "English",
-            Language::Russian => "Русский",
-        }
+        language_info(self.code()).map_or("English", |l| l.name)
     }
 
     /// Parse from code
@@ -33,37 +118,160 @@ impl Language {
             _ => Language::English,
         }
     }
+
+    /// Map an OS locale ("ru-RU", "ru_UA.UTF-8", "en_US", …) to a supported language.
+    /// Unsupported or malformed locales fall back to English.
+    pub fn from_locale(locale: &str) -> Self {
+        Self::from_code(language_for_locale(locale))
+    }
+}
+
+/// Accepted values of a library's preferred language for bilingual names
+pub const DISPLAY_LANGUAGES: [&str; 3] = ["auto", "ru", "en"];
+
+/// Choose between a Russian and an English name.
+///
+/// `lang_pref` is the library's display language ("ru", "en" or "auto");
+/// "auto" (or anything unknown) follows the UI language. When the preferred
+/// name is empty the other one is used (see [`crate::models::display_name`]).
+pub fn pick_name<'a>(lang_pref: &str, ui_lang: &str, ru: &'a str, en: &'a str) -> &'a str {
+    crate::models::display_name(ru, en, crate::models::DisplayLang::from_code(lang_pref), ui_lang)
+}
+
+/// Format an integer with the digit grouping of the given language
+/// ("1,234,567" for English, "1 234 567" with no-break spaces for Russian).
+pub fn format_number(n: i64, lang: &str) -> String {
+    let separator = match Language::from_code(lang) {
+        Language::English => ',',
+        Language::Russian => '\u{a0}',
+    };
+    let digits = n.unsigned_abs().to_string();
+    let mut out = String::new();
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(separator);
+        }
+        out.push(ch);
+    }
+    if n < 0 {
+        out.insert(0, '-');
+    }
+    out
+}
+
+/// Plural form of a count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluralForm {
+    /// 1 library / 21 библиотека
+    One,
+    /// 3 библиотеки (Russian only)
+    Few,
+    /// 5 libraries / 5 библиотек
+    Many,
+}
+
+/// Plural form of `n` in the given language. English has only one and many.
+pub fn plural_form(n: u64, lang: &str) -> PluralForm {
+    match Language::from_code(lang) {
+        Language::English => {
+            if n == 1 {
+                PluralForm::One
+            } else {
+                PluralForm::Many
+            }
+        }
+        Language::Russian => match (n % 10, n % 100) {
+            (1, r) if r != 11 => PluralForm::One,
+            (2..=4, r) if !(12..=14).contains(&r) => PluralForm::Few,
+            _ => PluralForm::Many,
+        },
+    }
+}
+
+/// Local date and time of a Unix timestamp, as "2024-06-07 12:30"
+pub fn format_timestamp(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// How long before `now` the Unix timestamp `ts` was, in the given language:
+/// "just now", "5 minutes ago", "2 дня назад". Beyond 30 days, and for times
+/// more than a minute in the future, the local date is given instead.
+pub fn time_ago(ts: i64, now: i64, lang: &str) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    let elapsed = now - ts;
+    let ru = Language::from_code(lang) == Language::Russian;
+    let (n, unit) = match elapsed {
+        e if !(-MINUTE..=30 * DAY).contains(&e) => {
+            return chrono::DateTime::from_timestamp(ts, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+        }
+        e if e < MINUTE => return if ru { "только что" } else { "just now" }.to_string(),
+        e if e < HOUR => (e / MINUTE, ["minute", "minutes", "минуту", "минуты", "минут"]),
+        e if e < DAY => (e / HOUR, ["hour", "hours", "час", "часа", "часов"]),
+        e => (e / DAY, ["day", "days", "день", "дня", "дней"]),
+    };
+    let [en_one, en_many, ru_one, ru_few, ru_many] = unit;
+    let word = match (ru, plural_form(n as u64, lang)) {
+        (false, PluralForm::One) => en_one,
+        (false, _) => en_many,
+        (true, PluralForm::One) => ru_one,
+        (true, PluralForm::Few) => ru_few,
+        (true, PluralForm::Many) => ru_many,
+    };
+    if ru {
+        format!("{} {} назад", n, word)
+    } else {
+        format!("{} {} ago", n, word)
+    }
 }
 
 /// Translation manager
 pub struct TranslationManager {
-    current_language: Language,
+    code: &'static str,
 }
 
 impl TranslationManager {
     /// Create new translation manager
     pub fn new() -> Self {
-        Self {
-            current_language: Language::English,
-        }
+        Self { code: LANGUAGES[0].code }
     }
 
     /// Load language from settings
     pub fn load_from_settings(&mut self) -> Result<()> {
         if let Ok(settings) = crate::config::Settings::load() {
-            self.current_language = Language::from_code(&settings.language);
+            self.set_language_code(&settings.language);
         }
         Ok(())
     }
 
     /// Set current language
     pub fn set_language(&mut self, lang: Language) {
-        self.current_language = lang;
+        self.code = lang.code();
+    }
+
+    /// Set the current language by code; unknown codes select English
+    pub fn set_language_code(&mut self, code: &str) {
+        self.code = supported_code(code);
     }
 
     /// Get current language
     pub fn current_language(&self) -> Language {
-        self.current_language
+        Language::from_code(self.code)
+    }
+
+    /// Code of the current language
+    pub fn language_code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Text of `key` in the current language (see [`translate`])
+    pub fn translate(&self, key: &str) -> String {
+        translate(self.code, key)
     }
 }
 
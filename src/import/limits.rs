@@ -0,0 +1,289 @@
+//! Size and nesting limits applied by every importer
+//!
+//! Files are checked against the size limit before anything is read, read
+//! through a capped reader (in case the file grows meanwhile), and text
+//! formats are pre-scanned for nesting depth before they reach serde. Block
+//! style YAML has no brackets to count, so YAML documents are checked again
+//! once parsed.
+
+use anyhow::{bail, Context, Result};
+use std::io::Read;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+use crate::models::{TreeLimits, DEFAULT_MAX_TREE_DEPTH, MAX_TREE_DEPTH};
+
+/// Default largest file accepted by an import (64 MiB)
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Deepest nesting the parsers read: serde_json stops past 127 levels
+/// (serde_yaml one level later), however the limits are set
+pub const PARSER_MAX_DEPTH: usize = 127;
+
+/// Nesting levels outside the unit tree: the export envelope, the library,
+/// its `units` array, and the personnel and equipment of the deepest units
+/// (four levels, with some to spare)
+const ENVELOPE_DEPTH: usize = 7;
+
+// Every tree the tree limits allow fits in a file the parsers read back
+const _: () = assert!(2 * MAX_TREE_DEPTH + ENVELOPE_DEPTH <= PARSER_MAX_DEPTH);
+
+/// Nesting depth of a library file whose unit tree is `tree_depth` levels
+/// deep: each unit adds its object and its `children` array. Never more than
+/// [`PARSER_MAX_DEPTH`].
+pub const fn document_depth_for(tree_depth: usize) -> usize {
+    let depth = tree_depth.saturating_mul(2).saturating_add(ENVELOPE_DEPTH);
+    if depth < PARSER_MAX_DEPTH {
+        depth
+    } else {
+        PARSER_MAX_DEPTH
+    }
+}
+
+/// Default deepest nesting of arrays/objects accepted by an import: enough
+/// for the deepest unit tree the default [`TreeLimits`] allow
+pub const DEFAULT_MAX_DEPTH: usize = document_depth_for(DEFAULT_MAX_TREE_DEPTH);
+
+/// Limits for one import
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportLimits {
+    /// Largest file size in bytes
+    pub max_file_size: u64,
+    /// Deepest nesting of arrays and objects; [`PARSER_MAX_DEPTH`] at most
+    pub max_depth: usize,
+}
+
+impl Default for ImportLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+fn global() -> &'static RwLock<ImportLimits> {
+    static LIMITS: OnceLock<RwLock<ImportLimits>> = OnceLock::new();
+    LIMITS.get_or_init(|| RwLock::new(ImportLimits::default()))
+}
+
+/// Limits used by the importers
+pub fn limits() -> ImportLimits {
+    *global().read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Change the limits used by the importers
+pub fn set_limits(limits: ImportLimits) {
+    *global().write().unwrap_or_else(|e| e.into_inner()) = limits;
+}
+
+/// Let the importers read files as deep as `tree` lets unit trees be
+pub fn follow_tree_limits(tree: &TreeLimits) {
+    set_limits(ImportLimits { max_depth: document_depth_for(tree.depth_limit()), ..limits() });
+}
+
+/// Human-readable byte count
+pub(crate) fn format_size(bytes: u64) -> String {
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= MIB {
+        format!("{:.1} MB", bytes as f64 / MIB as f64)
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+impl ImportLimits {
+    /// Fail if the file is larger than the size limit (checked without reading it)
+    pub fn check_file_size(&self, path: &Path) -> Result<()> {
+        let size = std::fs::metadata(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .len();
+        if size > self.max_file_size {
+            bail!(
+                "{} is too large to import: {} exceeds the file size limit of {}",
+                path.display(),
+                format_size(size),
+                format_size(self.max_file_size)
+            );
+        }
+        Ok(())
+    }
+
+    /// The depth limit in effect: `max_depth`, but never past what the
+    /// parsers read
+    pub fn depth_limit(&self) -> usize {
+        self.max_depth.min(PARSER_MAX_DEPTH)
+    }
+
+    /// Fail if arrays/objects nest deeper than the depth limit.
+    /// Brackets inside strings are ignored.
+    pub fn check_depth(&self, text: &str) -> Result<()> {
+        let limit = self.depth_limit();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        for (offset, byte) in text.bytes().enumerate() {
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => in_string = true,
+                b'[' | b'{' => {
+                    depth += 1;
+                    if depth > limit {
+                        bail!(
+                            "Document nests deeper than the nesting limit of {} levels (at byte {})",
+                            limit,
+                            offset
+                        );
+                    }
+                }
+                b']' | b'}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if a parsed YAML document nests sequences and mappings deeper
+    /// than the depth limit
+    pub fn check_yaml_depth(&self, document: &serde_yaml::Value) -> Result<()> {
+        use serde_yaml::Value;
+        let limit = self.depth_limit();
+        let mut pending = vec![(document, 0usize)];
+        while let Some((value, above)) = pending.pop() {
+            let depth = match value {
+                Value::Sequence(_) | Value::Mapping(_) => above + 1,
+                _ => above,
+            };
+            if depth > limit {
+                bail!("Document nests deeper than the nesting limit of {} levels", limit);
+            }
+            match value {
+                Value::Sequence(items) => pending.extend(items.iter().map(|item| (item, depth))),
+                Value::Mapping(map) => pending.extend(map.iter().flat_map(|(k, v)| [(k, depth), (v, depth)])),
+                Value::Tagged(tagged) => pending.push((&tagged.value, depth)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a text file for import, enforcing both limits
+    pub fn read_to_string(&self, path: &Path) -> Result<String> {
+        self.check_file_size(path)?;
+        let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut content = String::new();
+        file.take(self.max_file_size + 1)
+            .read_to_string(&mut content)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if content.len() as u64 > self.max_file_size {
+            bail!(
+                "{} is too large to import: it exceeds the file size limit of {}",
+                path.display(),
+                format_size(self.max_file_size)
+            );
+        }
+        self.check_depth(&content)?;
+        Ok(content)
+    }
+}
+
+/// Read a text file for import with the current limits
+pub fn read_limited(path: &Path) -> Result<String> {
+    limits().read_to_string(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tree::unit_chain_with;
+    use crate::models::{Library, Personnel, Unit};
+
+    fn small() -> ImportLimits {
+        ImportLimits {
+            max_file_size: 64,
+            max_depth: 3,
+        }
+    }
+
+    #[test]
+    fn test_check_depth() {
+        assert!(small().check_depth(r#"{"a": [[1]]}"#).is_ok());
+        let err = small().check_depth(r#"{"a": [[[1]]]}"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Document nests deeper than the nesting limit of 3 levels (at byte 8)"
+        );
+    }
+
+    #[test]
+    fn test_check_depth_ignores_brackets_in_strings() {
+        assert!(small().check_depth(r#"{"a": "[[[[{{{{", "b": "\"[[[["}"#).is_ok());
+    }
+
+    #[test]
+    fn test_check_yaml_depth() {
+        let shallow: serde_yaml::Value = serde_yaml::from_str("a:\n  - b: 1\n").unwrap();
+        assert!(small().check_yaml_depth(&shallow).is_ok());
+        // No brackets for the text scan to see
+        let text = "a:\n  - b:\n      - 1\n";
+        assert!(small().check_depth(text).is_ok());
+        let deep: serde_yaml::Value = serde_yaml::from_str(text).unwrap();
+        let err = small().check_yaml_depth(&deep).unwrap_err();
+        assert_eq!(err.to_string(), "Document nests deeper than the nesting limit of 3 levels");
+    }
+
+    #[test]
+    fn test_default_depth_fits_the_deepest_allowed_tree() {
+        let export = |depth: usize| {
+            let mut library = Library::new("Deep".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+            library.units = unit_chain_with(depth, |i| {
+                let mut unit = Unit::new(format!("U{}", i), "unit".to_string());
+                unit.personnel.push(Personnel::with_rank("Commander".to_string(), "CPT".to_string()));
+                unit
+            });
+            serde_json::json!({ "toeditor_version": "1", "library": library, "versions": [] }).to_string()
+        };
+        let limits = ImportLimits::default();
+        let deepest = export(DEFAULT_MAX_TREE_DEPTH);
+        assert!(limits.check_depth(&deepest).is_ok());
+        // The parser reads what the pre-scan lets through
+        assert!(serde_json::from_str::<serde_json::Value>(&deepest).is_ok());
+        assert!(limits.check_depth(&export(DEFAULT_MAX_TREE_DEPTH + ENVELOPE_DEPTH)).is_err());
+
+        // Limits set past the parsers stop where they do, with our error
+        let raised = ImportLimits { max_depth: 1000, ..limits };
+        assert_eq!(raised.depth_limit(), PARSER_MAX_DEPTH);
+        let err = raised.check_depth(&export(MAX_TREE_DEPTH + ENVELOPE_DEPTH)).unwrap_err();
+        assert!(err.to_string().contains("nesting limit of 127 levels"), "{}", err);
+        assert_eq!(document_depth_for(usize::MAX), PARSER_MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_depth_limit_stops_at_the_parsers() {
+        let raised = ImportLimits { max_depth: 1000, ..ImportLimits::default() };
+        assert_eq!(raised.depth_limit(), PARSER_MAX_DEPTH);
+        let deep = format!("{}{}", "[".repeat(PARSER_MAX_DEPTH + 1), "]".repeat(PARSER_MAX_DEPTH + 1));
+        let err = raised.check_depth(&deep).unwrap_err();
+        assert!(err.to_string().contains("nesting limit of 127 levels"), "{}", err);
+        // The parser reads what the pre-scan lets through
+        let deepest = format!("{}{}", "[".repeat(PARSER_MAX_DEPTH), "]".repeat(PARSER_MAX_DEPTH));
+        assert!(raised.check_depth(&deepest).is_ok());
+        assert!(serde_json::from_str::<serde_json::Value>(&deepest).is_ok());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(10), "10 bytes");
+        assert_eq!(format_size(2048), "2.0 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
@@ -1,50 +1,179 @@
 //! Import functionality for external files
 
-use anyhow::Result;
+pub mod xlsx;
+pub mod limits;
+
+use anyhow::{Context, Result};
+use crate::export::full::{parse_full_export, FullLibraryExport};
+use crate::export::{ExportInfo, LibraryFileFormat};
 use crate::models::Library;
+use crate::services::metrics;
 use std::path::Path;
 
-/// Import library from JSON file (supports both single library and library with versions)
-pub fn import_json(path: &Path) -> Result<Library> {
-    let content = std::fs::read_to_string(path)?;
-    
-    // Try to parse as library with versions first
-    if let Ok(data) = serde_json::from_str::<serde_json::Value>(&content) {
-        if data.is_object() && data.get("library").is_some() {
-            // It's a library with versions - extract just the library
-            let library: Library = serde_json::from_value(data["library"].clone())?;
-            return Ok(library);
+/// Why a library file could not be imported
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file could not be read, or is over the import limits
+    Io(anyhow::Error),
+    /// The file is not valid JSON
+    NotJson(String),
+    /// The file is valid JSON but not a TOEditor library
+    NotALibrary(String),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{:#}", e),
+            Self::NotJson(detail) => write!(f, "The file is not valid JSON: {}", detail),
+            Self::NotALibrary(detail) => write!(f, "The file is not a TOEditor library: {}", detail),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e.as_ref()),
+            _ => None,
         }
     }
-    
-    // Otherwise, parse as plain library
-    let library: Library = serde_json::from_str(&content)?;
+}
+
+/// Read a library file's text, within the import limits
+fn read_text(path: &Path) -> Result<String, ImportError> {
+    limits::read_limited(path).map_err(ImportError::Io)
+}
+
+fn parse_json(content: &str) -> Result<serde_json::Value, ImportError> {
+    serde_json::from_str(content).map_err(|e| ImportError::NotJson(e.to_string()))
+}
+
+/// A library from parsed data. Ids in the file belong to the database it was
+/// exported from, so they are dropped: saving must never touch existing rows.
+fn library_from_value(data: serde_json::Value) -> Result<Library, ImportError> {
+    if !data.is_object() {
+        return Err(ImportError::NotALibrary("expected an object".to_string()));
+    }
+    let mut library: Library = serde_json::from_value(data).map_err(|e| ImportError::NotALibrary(e.to_string()))?;
+    library.id = None;
+    let mut pending: Vec<&mut crate::models::Unit> = library.units.iter_mut().collect();
+    while let Some(unit) = pending.pop() {
+        unit.id = None;
+        unit.parent_id = None;
+        pending.extend(unit.children.iter_mut());
+    }
     Ok(library)
 }
 
+/// Import library from JSON file (supports both single library and library with versions)
+pub fn import_json(path: &Path) -> Result<Library, ImportError> {
+    metrics::timed("import_json", || import_json_with_versions(path).map(|(library, _)| library))
+}
+
 /// Import library with versions from JSON file
 /// Returns the library and optionally a list of version data
-pub fn import_json_with_versions(path: &Path) -> Result<(Library, Option<Vec<serde_json::Value>>)> {
-    let content = std::fs::read_to_string(path)?;
-    let data: serde_json::Value = serde_json::from_str(&content)?;
-    
+pub fn import_json_with_versions(path: &Path) -> Result<(Library, Option<Vec<serde_json::Value>>), ImportError> {
+    let content = read_text(path)?;
+    library_with_versions(parse_json(&content)?)
+}
+
+/// App version and time a JSON library file was exported, from its
+/// envelope; `None` for bare library files
+pub fn json_export_info(path: &Path) -> Result<Option<ExportInfo>, ImportError> {
+    let content = read_text(path)?;
+    Ok(ExportInfo::from_value(&parse_json(&content)?))
+}
+
+/// Split a parsed export, an envelope or a bare library, into the library
+/// and its optional versions block
+fn library_with_versions(data: serde_json::Value) -> Result<(Library, Option<Vec<serde_json::Value>>), ImportError> {
+    if let Some(info) = ExportInfo::from_value(&data) {
+        log::info!("Library file exported by TOEditor {} at {}", info.toeditor_version, info.exported_at);
+    }
     if data.is_object() && data.get("library").is_some() {
-        let library: Library = serde_json::from_value(data["library"].clone())?;
         let versions = data.get("versions")
             .and_then(|v| v.as_array())
             .cloned();
+        let library = library_from_value(data["library"].clone())?;
         Ok((library, versions))
     } else {
         // Plain library without versions
-        let library: Library = serde_json::from_value(data)?;
-        Ok((library, None))
+        Ok((library_from_value(data)?, None))
     }
 }
 
-/// Import library from YAML file
-pub fn import_yaml(_path: &Path) -> Result<Library> {
-    // TODO: Implement YAML parsing to Library
-    anyhow::bail!("YAML import not yet implemented")
+/// Read a full library export (see [`crate::export::FullLibraryExport`]).
+/// Returns `None` for plain library files without a `format_version`.
+pub fn import_full_json(path: &Path) -> Result<Option<FullLibraryExport>> {
+    let content = read_text(path)?;
+    let data = parse_json(&content)?;
+    parse_full_export(&data)
+}
+
+/// Import library from YAML file (same schema as the JSON export)
+pub fn import_yaml(path: &Path) -> Result<Library> {
+    metrics::timed("import_yaml", || import_yaml_with_versions(path).map(|(library, _)| library))
+}
+
+/// Import library with versions from YAML file.
+/// Anchors, aliases and merge keys are resolved before the library is read.
+pub fn import_yaml_with_versions(path: &Path) -> Result<(Library, Option<Vec<serde_json::Value>>)> {
+    let content = limits::read_limited(path)?;
+    let mut yaml: serde_yaml::Value = serde_yaml::from_str(&content)
+        .with_context(|| format!("{} is not valid YAML", path.display()))?;
+    yaml.apply_merge()?;
+    limits::limits().check_yaml_depth(&yaml)?;
+    let data = serde_json::to_value(yaml)
+        .with_context(|| format!("{} cannot be read as a library", path.display()))?;
+    Ok(library_with_versions(data)?)
+}
+
+/// Import library with versions from a JSON or YAML file, chosen by extension
+pub fn import_library_with_versions(path: &Path) -> Result<(Library, Option<Vec<serde_json::Value>>)> {
+    match LibraryFileFormat::from_path(path)? {
+        LibraryFileFormat::Json => Ok(import_json_with_versions(path)?),
+        LibraryFileFormat::Yaml => import_yaml_with_versions(path),
+    }
+}
+
+/// A library read from a file, waiting to be stored
+#[derive(Clone)]
+pub enum LibraryImport {
+    /// Library (with its units) and the exported version history
+    Plain {
+        library: Library,
+        versions: Vec<serde_json::Value>,
+    },
+    /// Full export including formation levels, branches and categories
+    Full(FullLibraryExport),
+}
+
+impl LibraryImport {
+    /// The library being imported
+    pub fn library(&self) -> &Library {
+        match self {
+            Self::Plain { library, .. } => library,
+            Self::Full(export) => &export.library,
+        }
+    }
+}
+
+/// Read a JSON or YAML library file, chosen by extension.
+/// Full exports are always JSON; YAML files are plain library exports.
+pub fn read_library_file(path: &Path) -> Result<LibraryImport> {
+    let full = match LibraryFileFormat::from_path(path)? {
+        LibraryFileFormat::Json => import_full_json(path)?,
+        LibraryFileFormat::Yaml => None,
+    };
+    if let Some(export) = full {
+        return Ok(LibraryImport::Full(export));
+    }
+    let (library, versions) = import_library_with_versions(path)?;
+    Ok(LibraryImport::Plain {
+        library,
+        versions: versions.unwrap_or_default(),
+    })
 }
 
 #[cfg(test)]
@@ -100,12 +229,55 @@ mod tests {
     }
 
     #[test]
-    fn test_import_yaml_not_implemented() {
+    fn test_import_yaml_with_versions_and_aliases() {
+        let yaml = r#"
+library:
+  id: null
+  name: &name Test
+  country: US
+  era: '2003'
+  author: *name
+  version: 2
+  tags: [a, b]
+  units:
+    - &squad
+      id: null
+      name: Alpha
+      unit_type: squad
+      parent_id: null
+      personnel: []
+      equipment: []
+      children: []
+    - <<: *squad
+      name: Bravo
+versions:
+  - version: 1
+    timestamp: 1234567890
+    description: Initial version
+    data: "{}"
+"#;
+        let file = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        std::fs::write(file.path(), yaml).unwrap();
+
+        let (imported, versions) = import_library_with_versions(file.path()).unwrap();
+        assert_eq!(imported.name, "Test");
+        assert_eq!(imported.author, "Test");
+        assert_eq!(imported.era, "2003");
+        let names: Vec<&str> = imported.units.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, vec!["Alpha", "Bravo"]);
+        assert_eq!(imported.units[1].unit_type, "squad");
+        assert_eq!(versions.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_import_yaml_not_a_library() {
         let file = NamedTempFile::new().unwrap();
-        std::fs::write(file.path(), "name: Test").unwrap();
-        let result = import_yaml(file.path());
-        assert!(result.is_err());
-        let err_msg = result.unwrap_err().to_string();
-        assert!(err_msg.contains("not yet implemented"));
+        std::fs::write(file.path(), "country: US\nera: '2003'\n").unwrap();
+        let err = import_yaml(file.path()).unwrap_err();
+        assert!(err.to_string().contains("missing field `name`"), "unexpected error: {}", err);
+
+        std::fs::write(file.path(), "name: [unclosed").unwrap();
+        let err = import_yaml(file.path()).unwrap_err();
+        assert!(err.to_string().contains("is not valid YAML"), "unexpected error: {}", err);
     }
 }
@@ -0,0 +1,661 @@
+//! Import a whole library from an Excel (.xlsx) workbook
+//!
+//! Workbook layout:
+//!
+//! * **Library** (optional): key/value rows in columns A and B. Recognized keys are
+//!   `Name`, `Country`, `Era`, `Author` and `Tags` (comma-separated). Without this
+//!   sheet the library is named after the file.
+//! * **Structure** (required): the first non-empty row is a header. `Name` (or
+//!   `Unit`) is required; `Type`, `Parent`, `Sheet`, `Effective From`,
+//!   `Effective To` and `TOE Number` are optional. Dates are `YYYY-MM-DD`
+//!   text or date cells.
+//!   - With a `Parent` column, each unit names its parent; units whose parent is
+//!     not found become top-level units.
+//!   - Without it the hierarchy comes from indentation: the columns from `Name` up
+//!     to the next header are level columns, and a name written one column further
+//!     right than the row above it is a child of that row.
+//!   - `Sheet` names the sheet with the unit's personnel and equipment; by default
+//!     a sheet named like the unit is used when it exists.
+//! * **Unit sheets**: a row whose first cell is `Personnel` or `Equipment` starts a
+//!   section; a header row right after it (`Position` / `Name`) is skipped.
+//!   Personnel rows are `Position | Rank | Count` (count defaults to 1), equipment
+//!   rows are `Name | Quantity`. Quantities may be numeric or text cells.
+//!
+//! Merged cells take the value of their top-left cell, except in the Structure
+//! level columns where only the top-left cell counts. Cells that cannot be
+//! understood are reported as warnings instead of failing the import.
+
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::path::Path;
+
+use crate::models::{assemble_forest, format_date, normalize_toe_number, parse_effective_dates, Equipment, Library, Personnel, Unit};
+use crate::services::metrics;
+
+/// Name of the required sheet with the unit hierarchy
+pub const STRUCTURE_SHEET: &str = "Structure";
+/// Name of the optional sheet with library metadata
+pub const LIBRARY_SHEET: &str = "Library";
+
+/// Value of one worksheet cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Number(f64),
+    /// Excel error value such as `#REF!`
+    Error(String),
+}
+
+impl Cell {
+    fn is_empty(&self) -> bool {
+        match self {
+            Cell::Empty => true,
+            Cell::Text(s) => s.trim().is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Trimmed text of the cell; whole numbers are written without a fraction
+    fn text(&self) -> String {
+        match self {
+            Cell::Empty | Cell::Error(_) => String::new(),
+            Cell::Text(s) => s.trim().to_string(),
+            Cell::Number(n) if n.fract() == 0.0 => format!("{}", *n as i64),
+            Cell::Number(n) => n.to_string(),
+        }
+    }
+}
+
+/// Inclusive range of merged cells, (row, column) zero-based
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergedRegion {
+    pub first: (usize, usize),
+    pub last: (usize, usize),
+}
+
+impl MergedRegion {
+    fn contains(&self, row: usize, col: usize) -> bool {
+        (self.first.0..=self.last.0).contains(&row) && (self.first.1..=self.last.1).contains(&col)
+    }
+}
+
+/// Worksheet contents with absolute (row, column) positions starting at A1
+#[derive(Debug, Clone, Default)]
+pub struct Sheet {
+    pub name: String,
+    pub rows: Vec<Vec<Cell>>,
+    pub merged: Vec<MergedRegion>,
+}
+
+impl Sheet {
+    /// Value stored in the cell itself
+    fn raw(&self, row: usize, col: usize) -> &Cell {
+        self.rows
+            .get(row)
+            .and_then(|r| r.get(col))
+            .unwrap_or(&Cell::Empty)
+    }
+
+    /// Value of the cell, taking merged regions into account
+    fn value(&self, row: usize, col: usize) -> &Cell {
+        match self.merged.iter().find(|m| m.contains(row, col)) {
+            Some(m) => self.raw(m.first.0, m.first.1),
+            None => self.raw(row, col),
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.rows.iter().map(|r| r.len()).max().unwrap_or(0)
+    }
+
+    fn row_is_empty(&self, row: usize) -> bool {
+        (0..self.width()).all(|col| self.value(row, col).is_empty())
+    }
+}
+
+/// Problem found in a cell that did not stop the import
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportWarning {
+    pub sheet: String,
+    /// Cell reference such as "B7"
+    pub cell: String,
+    pub message: String,
+}
+
+impl ImportWarning {
+    fn new(sheet: &str, row: usize, col: usize, message: impl Into<String>) -> Self {
+        Self {
+            sheet: sheet.to_string(),
+            cell: cell_ref(row, col),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}!{}: {}", self.sheet, self.cell, self.message)
+    }
+}
+
+/// Library read from a workbook plus the warnings collected on the way
+#[derive(Debug, Clone)]
+pub struct XlsxImport {
+    pub library: Library,
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// Spreadsheet-style reference for a zero-based (row, column), e.g. (6, 1) -> "B7"
+pub fn cell_ref(row: usize, col: usize) -> String {
+    let mut letters = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        letters.push(b'A' + rem as u8);
+        n = (n - 1) / 26;
+    }
+    letters.reverse();
+    format!("{}{}", String::from_utf8(letters).unwrap_or_default(), row + 1)
+}
+
+/// Parse a quantity cell: whole non-negative numbers, as numeric or text cells.
+pub fn parse_quantity(cell: &Cell) -> std::result::Result<usize, String> {
+    let value = match cell {
+        Cell::Empty => return Err("missing quantity".to_string()),
+        Cell::Error(e) => return Err(format!("cell contains error {}", e)),
+        Cell::Number(n) => *n,
+        Cell::Text(s) => {
+            let s = s.trim();
+            if s.is_empty() {
+                return Err("missing quantity".to_string());
+            }
+            s.parse::<f64>()
+                .map_err(|_| format!("\"{}\" is not a number", s))?
+        }
+    };
+    if value < 0.0 || value.fract() != 0.0 || !value.is_finite() {
+        return Err(format!("{} is not a whole non-negative number", value));
+    }
+    Ok(value as usize)
+}
+
+/// Text of a date cell; numbers are Excel serial dates
+fn date_text(cell: &Cell) -> String {
+    match cell {
+        Cell::Number(n) if n.is_finite() && *n >= 1.0 => chrono::NaiveDate::from_ymd_opt(1899, 12, 30)
+            .and_then(|epoch| epoch.checked_add_days(chrono::Days::new(*n as u64)))
+            .map(format_date)
+            .unwrap_or_else(|| cell.text()),
+        _ => cell.text(),
+    }
+}
+
+/// Read and parse a workbook; the file name is the fallback library name.
+pub fn import_xlsx(path: &Path) -> Result<XlsxImport> {
+    metrics::timed("import_xlsx", || {
+        let sheets = read_workbook(path)?;
+        let default_name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        parse_workbook(&sheets, &default_name)
+    })
+}
+
+/// Load every worksheet of an .xlsx file.
+pub fn read_workbook(path: &Path) -> Result<Vec<Sheet>> {
+    use calamine::{open_workbook, Data, Reader, Xlsx};
+
+    super::limits::limits().check_file_size(path)?;
+    let mut workbook: Xlsx<_> = open_workbook(path)
+        .with_context(|| format!("Failed to open workbook {}", path.display()))?;
+    let mut sheets = Vec::new();
+    for name in workbook.sheet_names().to_owned() {
+        let range = workbook
+            .worksheet_range(&name)
+            .with_context(|| format!("Failed to read sheet \"{}\"", name))?;
+        let merged = match workbook.worksheet_merge_cells(&name) {
+            Some(Ok(regions)) => regions
+                .iter()
+                .map(|d| MergedRegion {
+                    first: (d.start.0 as usize, d.start.1 as usize),
+                    last: (d.end.0 as usize, d.end.1 as usize),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let (row0, col0) = range.start().unwrap_or((0, 0));
+        let mut rows = vec![Vec::new(); row0 as usize];
+        for row in range.rows() {
+            let mut cells = vec![Cell::Empty; col0 as usize];
+            cells.extend(row.iter().map(|value| match value {
+                Data::Empty => Cell::Empty,
+                Data::String(s) => Cell::Text(s.clone()),
+                Data::Int(i) => Cell::Number(*i as f64),
+                Data::Float(f) => Cell::Number(*f),
+                Data::DateTime(d) => Cell::Number(d.as_f64()),
+                Data::Error(e) => Cell::Error(e.to_string()),
+                other => Cell::Text(other.to_string()),
+            }));
+            rows.push(cells);
+        }
+        sheets.push(Sheet { name, rows, merged });
+    }
+    Ok(sheets)
+}
+
+fn find_sheet<'s>(sheets: &'s [Sheet], name: &str) -> Option<&'s Sheet> {
+    sheets
+        .iter()
+        .find(|s| s.name.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// Build a library from already loaded worksheets.
+pub fn parse_workbook(sheets: &[Sheet], default_name: &str) -> Result<XlsxImport> {
+    let Some(structure) = find_sheet(sheets, STRUCTURE_SHEET) else {
+        bail!("Workbook has no \"{}\" sheet", STRUCTURE_SHEET);
+    };
+    let mut warnings = Vec::new();
+    let mut library = parse_library_sheet(sheets, default_name, &mut warnings);
+    let mut entries = parse_structure(structure, &mut warnings)?;
+
+    for entry in &mut entries {
+        let sheet_name = entry.sheet.clone().unwrap_or_else(|| entry.unit.name.clone());
+        match find_sheet(sheets, &sheet_name) {
+            Some(sheet) if sheet.name != structure.name => {
+                parse_unit_sheet(sheet, &mut entry.unit, &mut warnings)
+            }
+            _ if entry.sheet.is_some() => warnings.push(ImportWarning::new(
+                &structure.name,
+                entry.row,
+                0,
+                format!("Sheet \"{}\" for unit \"{}\" not found", sheet_name, entry.unit.name),
+            )),
+            _ => {}
+        }
+    }
+
+    library.units = assemble_tree(entries);
+    Ok(XlsxImport { library, warnings })
+}
+
+fn parse_library_sheet(sheets: &[Sheet], default_name: &str, warnings: &mut Vec<ImportWarning>) -> Library {
+    let mut library = Library::new(default_name.to_string(), String::new(), String::new(), String::new());
+    let Some(sheet) = find_sheet(sheets, LIBRARY_SHEET) else {
+        warnings.push(ImportWarning::new(
+            LIBRARY_SHEET,
+            0,
+            0,
+            "No Library sheet; country and era must be filled in after import",
+        ));
+        return library;
+    };
+    for row in 0..sheet.rows.len() {
+        let key = sheet.value(row, 0).text();
+        if key.is_empty() {
+            continue;
+        }
+        let value = sheet.value(row, 1).text();
+        match key.to_lowercase().as_str() {
+            "name" if !value.is_empty() => library.name = value,
+            "name" => {}
+            "country" => library.country = value,
+            "era" => library.era = value,
+            "author" => library.author = value,
+            "tags" => {
+                library.tags = value
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            }
+            _ => warnings.push(ImportWarning::new(
+                &sheet.name,
+                row,
+                0,
+                format!("Unknown library field \"{}\" ignored", key),
+            )),
+        }
+    }
+    library
+}
+
+/// Unit read from the Structure sheet before the tree is assembled
+struct StructureEntry {
+    unit: Unit,
+    /// Index of the parent entry
+    parent: Option<usize>,
+    /// Explicit sheet name from the Sheet column
+    sheet: Option<String>,
+    row: usize,
+}
+
+fn parse_structure(sheet: &Sheet, warnings: &mut Vec<ImportWarning>) -> Result<Vec<StructureEntry>> {
+    let Some(header_row) = (0..sheet.rows.len()).find(|&r| !sheet.row_is_empty(r)) else {
+        bail!("The \"{}\" sheet is empty", sheet.name);
+    };
+    let width = sheet.width();
+    let header = |names: &[&str]| {
+        (0..width).find(|&c| {
+            let h = sheet.raw(header_row, c).text().to_lowercase();
+            names.contains(&h.as_str())
+        })
+    };
+    let Some(name_col) = header(&["name", "unit"]) else {
+        bail!("The \"{}\" sheet has no Name column", sheet.name);
+    };
+    let type_col = header(&["type"]);
+    let parent_col = header(&["parent"]);
+    let sheet_col = header(&["sheet"]);
+    let from_col = header(&["effective from", "from"]);
+    let to_col = header(&["effective to", "to"]);
+    let toe_col = header(&["toe number", "toe", "mtoe"]);
+    let level_end = (name_col + 1..width)
+        .find(|&c| !sheet.raw(header_row, c).is_empty())
+        .unwrap_or(width);
+
+    let mut entries: Vec<StructureEntry> = Vec::new();
+    let mut parent_names: Vec<Option<String>> = Vec::new();
+    // Indices of the last entry seen at each depth (indentation mode)
+    let mut stack: Vec<usize> = Vec::new();
+
+    for row in header_row + 1..sheet.rows.len() {
+        let (col, name_cell) = if parent_col.is_some() {
+            (name_col, sheet.raw(row, name_col))
+        } else {
+            match (name_col..level_end).find(|&c| !sheet.raw(row, c).is_empty()) {
+                Some(c) => (c, sheet.raw(row, c)),
+                None => (name_col, &Cell::Empty),
+            }
+        };
+        if let Cell::Error(e) = name_cell {
+            warnings.push(ImportWarning::new(&sheet.name, row, col, format!("Unit name is an error value ({}); row skipped", e)));
+            continue;
+        }
+        let name = name_cell.text();
+        if name.is_empty() {
+            if !sheet.row_is_empty(row) {
+                warnings.push(ImportWarning::new(&sheet.name, row, name_col, "Row has no unit name; skipped"));
+            }
+            continue;
+        }
+
+        let unit_type = type_col.map(|c| sheet.value(row, c).text()).unwrap_or_default();
+        let explicit_sheet = sheet_col
+            .map(|c| sheet.value(row, c).text())
+            .filter(|s| !s.is_empty());
+        let index = entries.len();
+
+        let parent = if let Some(pc) = parent_col {
+            let parent_name = sheet.value(row, pc).text();
+            parent_names.push((!parent_name.is_empty()).then_some(parent_name));
+            None
+        } else {
+            let mut depth = col - name_col;
+            if depth > stack.len() {
+                warnings.push(ImportWarning::new(
+                    &sheet.name,
+                    row,
+                    col,
+                    format!("\"{}\" is indented more than one level below the row above", name),
+                ));
+                depth = stack.len();
+            }
+            stack.truncate(depth);
+            let parent = stack.last().copied();
+            stack.push(index);
+            parent
+        };
+
+        let mut unit = Unit::new(name, unit_type);
+        unit.toe_number = toe_col.and_then(|c| normalize_toe_number(&sheet.value(row, c).text()));
+        let date = |col: Option<usize>| col.map(|c| date_text(sheet.value(row, c))).unwrap_or_default();
+        match parse_effective_dates(&date(from_col), &date(to_col)) {
+            Ok((from, to)) => (unit.effective_from, unit.effective_to) = (from, to),
+            Err(errors) => {
+                for error in errors {
+                    let col = if error.field == "effective_from" { from_col } else { to_col };
+                    warnings.push(ImportWarning::new(
+                        &sheet.name,
+                        row,
+                        col.unwrap_or(name_col),
+                        format!("{}; effective dates of \"{}\" ignored", error.message, unit.name),
+                    ));
+                }
+            }
+        }
+
+        entries.push(StructureEntry {
+            unit,
+            parent,
+            sheet: explicit_sheet,
+            row,
+        });
+    }
+
+    if let Some(pc) = parent_col {
+        resolve_parents(sheet, pc, &mut entries, &parent_names, warnings);
+    }
+    Ok(entries)
+}
+
+/// Link entries to their parents by name (Parent column mode).
+fn resolve_parents(
+    sheet: &Sheet,
+    parent_col: usize,
+    entries: &mut [StructureEntry],
+    parent_names: &[Option<String>],
+    warnings: &mut Vec<ImportWarning>,
+) {
+    for i in 0..entries.len() {
+        let Some(parent_name) = &parent_names[i] else {
+            continue;
+        };
+        match entries.iter().position(|e| e.unit.name.eq_ignore_ascii_case(parent_name)) {
+            Some(p) if p != i => entries[i].parent = Some(p),
+            _ => warnings.push(ImportWarning::new(
+                &sheet.name,
+                entries[i].row,
+                parent_col,
+                format!("Parent \"{}\" not found; \"{}\" imported as a top-level unit", parent_name, entries[i].unit.name),
+            )),
+        }
+    }
+    // Break parent cycles so the tree can be assembled
+    for i in 0..entries.len() {
+        let mut seen = vec![i];
+        let mut current = entries[i].parent;
+        while let Some(p) = current {
+            if seen.contains(&p) {
+                warnings.push(ImportWarning::new(
+                    &sheet.name,
+                    entries[i].row,
+                    parent_col,
+                    format!("Parent cycle at \"{}\"; imported as a top-level unit", entries[i].unit.name),
+                ));
+                entries[i].parent = None;
+                break;
+            }
+            seen.push(p);
+            current = entries[p].parent;
+        }
+    }
+}
+
+fn assemble_tree(entries: Vec<StructureEntry>) -> Vec<Unit> {
+    let nodes = entries.into_iter().map(|e| (e.unit, e.parent)).collect();
+    assemble_forest(nodes, |unit, children| unit.children.extend(children))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    None,
+    Personnel,
+    Equipment,
+}
+
+fn parse_unit_sheet(sheet: &Sheet, unit: &mut Unit, warnings: &mut Vec<ImportWarning>) {
+    let mut section = Section::None;
+    let mut expect_header = false;
+    for row in 0..sheet.rows.len() {
+        if sheet.row_is_empty(row) {
+            continue;
+        }
+        let first = sheet.value(row, 0).text();
+        match first.to_lowercase().as_str() {
+            "personnel" => {
+                section = Section::Personnel;
+                expect_header = true;
+                continue;
+            }
+            "equipment" => {
+                section = Section::Equipment;
+                expect_header = true;
+                continue;
+            }
+            "position" | "name" | "item" if expect_header => {
+                expect_header = false;
+                continue;
+            }
+            _ => expect_header = false,
+        }
+        match section {
+            // A title row naming the unit is fine
+            Section::None if first == unit.name => {}
+            Section::None => warnings.push(ImportWarning::new(
+                &sheet.name,
+                row,
+                0,
+                "Row outside a Personnel or Equipment section ignored",
+            )),
+            Section::Personnel => {
+                if first.is_empty() {
+                    warnings.push(ImportWarning::new(&sheet.name, row, 0, "Personnel row has no position; skipped"));
+                    continue;
+                }
+                let rank = sheet.value(row, 1).text();
+                let count = match sheet.value(row, 2) {
+                    cell if cell.is_empty() => 1,
+                    cell => parse_quantity(cell).unwrap_or_else(|e| {
+                        warnings.push(ImportWarning::new(&sheet.name, row, 2, format!("Invalid count: {}; using 1", e)));
+                        1
+                    }),
+                };
+                for _ in 0..count {
+                    unit.add_personnel(if rank.is_empty() {
+                        Personnel::new(first.clone())
+                    } else {
+                        Personnel::with_rank(first.clone(), rank.clone())
+                    });
+                }
+            }
+            Section::Equipment => {
+                if first.is_empty() {
+                    warnings.push(ImportWarning::new(&sheet.name, row, 0, "Equipment row has no name; skipped"));
+                    continue;
+                }
+                match parse_quantity(sheet.value(row, 1)) {
+                    Ok(quantity) => unit.add_equipment(Equipment::new(first, quantity)),
+                    Err(e) => warnings.push(ImportWarning::new(
+                        &sheet.name,
+                        row,
+                        1,
+                        format!("Invalid quantity for \"{}\": {}; row skipped", first, e),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Cell {
+        Cell::Text(s.to_string())
+    }
+
+    fn sheet(name: &str, rows: Vec<Vec<Cell>>) -> Sheet {
+        Sheet {
+            name: name.to_string(),
+            rows,
+            merged: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_cell_ref() {
+        assert_eq!(cell_ref(0, 0), "A1");
+        assert_eq!(cell_ref(6, 1), "B7");
+        assert_eq!(cell_ref(0, 25), "Z1");
+        assert_eq!(cell_ref(9, 26), "AA10");
+        assert_eq!(cell_ref(0, 701), "ZZ1");
+        assert_eq!(cell_ref(0, 702), "AAA1");
+    }
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(parse_quantity(&Cell::Number(9.0)), Ok(9));
+        assert_eq!(parse_quantity(&text(" 12 ")), Ok(12));
+        assert_eq!(parse_quantity(&text("3.0")), Ok(3));
+        assert!(parse_quantity(&Cell::Number(2.5)).is_err());
+        assert!(parse_quantity(&Cell::Number(-1.0)).is_err());
+        assert!(parse_quantity(&text("nine")).is_err());
+        assert!(parse_quantity(&Cell::Empty).is_err());
+        assert!(parse_quantity(&Cell::Error("#REF!".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_indentation_deeper_than_one_level_is_clamped() {
+        let structure = sheet(
+            "Structure",
+            vec![
+                vec![text("Name"), Cell::Empty, Cell::Empty, text("Type")],
+                vec![text("Bn"), Cell::Empty, Cell::Empty, text("Battalion")],
+                vec![Cell::Empty, Cell::Empty, text("Plt"), text("Platoon")],
+            ],
+        );
+        let result = parse_workbook(&[structure], "Test").unwrap();
+        assert_eq!(result.library.units.len(), 1);
+        assert_eq!(result.library.units[0].children[0].name, "Plt");
+        assert!(result.warnings.iter().any(|w| w.cell == "C3"));
+    }
+
+    #[test]
+    fn test_effective_date_columns() {
+        let structure = sheet(
+            "Structure",
+            vec![
+                vec![text("Name"), text("Parent"), text("Effective From"), text("Effective To"), text("TOE Number")],
+                vec![text("Regiment"), Cell::Empty, text("1943-01-01"), Cell::Number(15887.0), text(" TOE  07015L000 ")],
+                vec![text("Battalion"), text("Regiment"), text("1944-01-01"), text("1943-01-01"), Cell::Empty],
+            ],
+        );
+        let result = parse_workbook(&[structure], "Test").unwrap();
+        let regiment = &result.library.units[0];
+        assert_eq!(regiment.effective_from.as_deref(), Some("1943-01-01"));
+        assert_eq!(regiment.effective_to.as_deref(), Some("1943-06-30"));
+        assert_eq!(regiment.toe_number.as_deref(), Some("TOE 07015L000"));
+        let battalion = &regiment.children[0];
+        assert_eq!((battalion.effective_from.as_ref(), battalion.effective_to.as_ref()), (None, None));
+        assert!(result.warnings.iter().any(|w| w.cell == "D3"));
+    }
+
+    #[test]
+    fn test_parent_cycle_is_broken() {
+        let structure = sheet(
+            "Structure",
+            vec![
+                vec![text("Name"), text("Parent")],
+                vec![text("A"), text("B")],
+                vec![text("B"), text("A")],
+            ],
+        );
+        let result = parse_workbook(&[structure], "Test").unwrap();
+        assert_eq!(result.library.units.len(), 1);
+        assert_eq!(result.library.units[0].children.len(), 1);
+        assert!(result.warnings.iter().any(|w| w.message.contains("cycle")));
+    }
+}
@@ -1,6 +1,8 @@
 //! TOEditor library - reusable components for testing
 
+#[cfg(feature = "gui")]
 pub mod app;
+pub mod api;
 pub mod models;
 pub mod db;
 pub mod config;
@@ -8,3 +10,5 @@ pub mod export;
 pub mod import;
 pub mod i18n;
 pub mod services;
+pub mod view;
+pub mod cli;
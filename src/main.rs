@@ -23,7 +23,22 @@ fn setup_panic_handler() {
 fn main() -> Result<()> {
     env_logger::init();
     setup_panic_handler();
+
+    // Headless subcommands skip the UI entirely
+    match toeditor::cli::parse_args(std::env::args().skip(1)) {
+        Ok(Some(command)) => std::process::exit(toeditor::cli::run(command)),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, toeditor::cli::USAGE);
+            std::process::exit(toeditor::cli::EXIT_USAGE);
+        }
+    }
     
+    run_gui()
+}
+
+#[cfg(feature = "gui")]
+fn run_gui() -> Result<()> {
     // Initialize translations if needed
     // Note: Translations are embedded via build.rs, but we can initialize them here
     // For bundled translations, select_bundled_translation() should work
@@ -36,3 +51,10 @@ fn main() -> Result<()> {
     
     Ok(())
 }
+
+/// Without the `gui` feature only the headless subcommands are available
+#[cfg(not(feature = "gui"))]
+fn run_gui() -> Result<()> {
+    eprintln!("This build has no GUI (built without the `gui` feature).\n\n{}", toeditor::cli::USAGE);
+    std::process::exit(toeditor::cli::EXIT_USAGE);
+}
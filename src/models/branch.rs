@@ -11,6 +11,9 @@ pub struct Branch {
     pub category_id: Option<i64>,
     pub name_ru: String,
     pub name_en: String,
+    /// Position in the library's list (ties fall back to id)
+    #[serde(default)]
+    pub sort_order: i64,
 }
 
 impl Branch {
@@ -21,6 +24,7 @@ impl Branch {
             category_id: None,
             name_ru,
             name_en,
+            sort_order: 0,
         }
     }
 
@@ -31,6 +35,7 @@ impl Branch {
             category_id,
             name_ru,
             name_en,
+            sort_order: 0,
         }
     }
 }
@@ -42,6 +47,9 @@ pub struct BranchCategory {
     pub library_id: i64,
     pub name_ru: String,
     pub name_en: String,
+    /// Position in the library's list (ties fall back to id)
+    #[serde(default)]
+    pub sort_order: i64,
 }
 
 impl BranchCategory {
@@ -51,6 +59,7 @@ impl BranchCategory {
             library_id,
             name_ru,
             name_en,
+            sort_order: 0,
         }
     }
 }
@@ -0,0 +1,174 @@
+//! Country suggestions for the library dialog
+//!
+//! The country field stays free text; these helpers rank suggestions for an
+//! autocomplete and resolve typed text to a code where one is known. Modern
+//! states use ISO 3166-1 alpha-2 codes, historical entities ISO 3166-3 codes
+//! or a curated `X-` code.
+
+use super::country_data::COUNTRIES;
+
+/// One entry of the country table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Country {
+    pub code: &'static str,
+    pub name_en: &'static str,
+    pub name_ru: &'static str,
+    /// Other spellings accepted when resolving or suggesting
+    pub aliases: &'static [&'static str],
+    /// No longer exists (USSR, Prussia, …)
+    pub historical: bool,
+}
+
+impl Country {
+    /// Name in the given UI language ("ru" or anything else for English)
+    pub fn name(&self, lang: &str) -> &'static str {
+        if lang == "ru" {
+            self.name_ru
+        } else {
+            self.name_en
+        }
+    }
+
+    fn names(&self) -> impl Iterator<Item = &'static str> {
+        [self.name_en, self.name_ru].into_iter().chain(self.aliases.iter().copied())
+    }
+}
+
+/// All known countries, modern ones first
+pub fn countries() -> &'static [Country] {
+    COUNTRIES
+}
+
+/// Country with this code (case-insensitive)
+pub fn country_by_code(code: &str) -> Option<&'static Country> {
+    let code = code.trim();
+    COUNTRIES.iter().find(|c| c.code.eq_ignore_ascii_case(code))
+}
+
+/// Lowercase with `ё` folded to `е`, so "Объединённые" matches "объединенные"
+fn normalize(text: &str) -> String {
+    text.trim().to_lowercase().replace('ё', "е")
+}
+
+/// How well `query` (normalized) matches one name; lower is better
+fn name_rank(name: &str, query: &str) -> Option<u8> {
+    let name = normalize(name);
+    if name == query {
+        Some(1)
+    } else if name.starts_with(query) {
+        Some(2)
+    } else if name
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '(')
+        .any(|word| word.starts_with(query))
+    {
+        Some(3)
+    } else if name.contains(query) {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// Rank of a country for a query; an exact code match beats any name match
+fn country_rank(country: &Country, query: &str) -> Option<u8> {
+    if country.code.eq_ignore_ascii_case(query) {
+        return Some(0);
+    }
+    country.names().filter_map(|n| name_rank(n, query)).min()
+}
+
+/// Up to `limit` countries matching `query`, best first. Exact code, exact
+/// name, name prefix, word prefix, then substring; ties keep modern states
+/// before historical entities, then table order.
+pub fn suggest_countries(query: &str, limit: usize) -> Vec<&'static Country> {
+    let query = normalize(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut ranked: Vec<(u8, bool, usize, &'static Country)> = COUNTRIES
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| country_rank(c, &query).map(|rank| (rank, c.historical, i, c)))
+        .collect();
+    ranked.sort_by_key(|&(rank, historical, i, _)| (rank, historical, i));
+    ranked.into_iter().take(limit).map(|(_, _, _, c)| c).collect()
+}
+
+/// Code for free text that is exactly a known code, name or alias
+/// (case-insensitive, either language); `None` for anything else
+pub fn resolve_country_code(text: &str) -> Option<&'static str> {
+    let text = normalize(text);
+    if text.is_empty() {
+        return None;
+    }
+    COUNTRIES
+        .iter()
+        .find(|c| c.code.eq_ignore_ascii_case(&text))
+        .or_else(|| COUNTRIES.iter().find(|c| c.names().any(|n| normalize(n) == text)))
+        .map(|c| c.code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn codes(found: Vec<&Country>) -> Vec<&'static str> {
+        found.into_iter().map(|c| c.code).collect()
+    }
+
+    #[test]
+    fn test_table_codes_are_unique() {
+        let mut seen = HashSet::new();
+        assert!(countries().iter().all(|c| seen.insert(c.code)));
+        assert!(countries().len() > 250);
+    }
+
+    #[test]
+    fn test_resolve_country_code() {
+        assert_eq!(resolve_country_code("US"), Some("US"));
+        assert_eq!(resolve_country_code(" usa "), Some("US"));
+        assert_eq!(resolve_country_code("United States of America"), Some("US"));
+        assert_eq!(resolve_country_code("США"), Some("US"));
+        assert_eq!(resolve_country_code("германия"), Some("DE"));
+        assert_eq!(resolve_country_code("USSR"), Some("SUHH"));
+        assert_eq!(resolve_country_code("СССР"), Some("SUHH"));
+        assert_eq!(resolve_country_code("Prussia"), Some("X-PRU"));
+        // ё and е are interchangeable
+        assert_eq!(resolve_country_code("Соединенное Королевство"), Some("GB"));
+        assert_eq!(resolve_country_code("Atlantis"), None);
+        assert_eq!(resolve_country_code("Germ"), None);
+        assert_eq!(resolve_country_code(""), None);
+    }
+
+    #[test]
+    fn test_suggest_ranks_code_then_prefix_then_substring() {
+        assert_eq!(codes(suggest_countries("de", 1)), ["DE"]);
+        let found = codes(suggest_countries("ger", 10));
+        assert_eq!(found[0], "DE");
+        // Historical entities follow the modern state with the same rank
+        assert!(found.contains(&"DDDE") || found.contains(&"X-DEK"));
+        let position = |code| found.iter().position(|c| *c == code).unwrap();
+        assert!(position("DE") < position("X-DEK"));
+        // "Niger" and "Nigeria" contain "ger" but do not start with it
+        assert!(position("X-DEK") < position("NE"));
+        assert!(suggest_countries("", 10).is_empty());
+        assert!(suggest_countries("zzzz", 10).is_empty());
+    }
+
+    #[test]
+    fn test_suggest_cyrillic_queries() {
+        assert_eq!(codes(suggest_countries("росс", 2)), ["RU", "X-RUE"]);
+        assert_eq!(codes(suggest_countries("Пруссия", 1)), ["X-PRU"]);
+        // Word prefix inside a longer Russian name
+        assert!(codes(suggest_countries("корея", 5)).contains(&"KR"));
+        assert_eq!(codes(suggest_countries("ФРАНЦ", 1)), ["FR"]);
+    }
+
+    #[test]
+    fn test_name_in_language() {
+        let us = country_by_code("us").unwrap();
+        assert_eq!(us.name("en"), "United States");
+        assert_eq!(us.name("ru"), "США");
+    }
+}
@@ -0,0 +1,308 @@
+//! Country table: ISO 3166-1 alpha-2 codes with English and Russian names,
+//! followed by historical entities (ISO 3166-3 codes where one exists)
+
+use super::country::Country;
+
+const fn modern(code: &'static str, name_en: &'static str, name_ru: &'static str) -> Country {
+    Country { code, name_en, name_ru, aliases: &[], historical: false }
+}
+
+const fn modern_aka(
+    code: &'static str,
+    name_en: &'static str,
+    name_ru: &'static str,
+    aliases: &'static [&'static str],
+) -> Country {
+    Country { code, name_en, name_ru, aliases, historical: false }
+}
+
+const fn historical(
+    code: &'static str,
+    name_en: &'static str,
+    name_ru: &'static str,
+    aliases: &'static [&'static str],
+) -> Country {
+    Country { code, name_en, name_ru, aliases, historical: true }
+}
+
+pub(super) static COUNTRIES: &[Country] = &[
+    modern("AF", "Afghanistan", "Афганистан"),
+    modern("AX", "Åland Islands", "Аландские острова"),
+    modern("AL", "Albania", "Албания"),
+    modern("DZ", "Algeria", "Алжир"),
+    modern("AS", "American Samoa", "Американское Самоа"),
+    modern("AD", "Andorra", "Андорра"),
+    modern("AO", "Angola", "Ангола"),
+    modern("AI", "Anguilla", "Ангилья"),
+    modern("AQ", "Antarctica", "Антарктида"),
+    modern("AG", "Antigua and Barbuda", "Антигуа и Барбуда"),
+    modern("AR", "Argentina", "Аргентина"),
+    modern("AM", "Armenia", "Армения"),
+    modern("AW", "Aruba", "Аруба"),
+    modern("AU", "Australia", "Австралия"),
+    modern("AT", "Austria", "Австрия"),
+    modern("AZ", "Azerbaijan", "Азербайджан"),
+    modern("BS", "Bahamas", "Багамские Острова"),
+    modern("BH", "Bahrain", "Бахрейн"),
+    modern("BD", "Bangladesh", "Бангладеш"),
+    modern("BB", "Barbados", "Барбадос"),
+    modern("BY", "Belarus", "Беларусь"),
+    modern("BE", "Belgium", "Бельгия"),
+    modern("BZ", "Belize", "Белиз"),
+    modern("BJ", "Benin", "Бенин"),
+    modern("BM", "Bermuda", "Бермудские Острова"),
+    modern("BT", "Bhutan", "Бутан"),
+    modern("BO", "Bolivia", "Боливия"),
+    modern("BQ", "Bonaire, Sint Eustatius and Saba", "Бонайре, Синт-Эстатиус и Саба"),
+    modern("BA", "Bosnia and Herzegovina", "Босния и Герцеговина"),
+    modern("BW", "Botswana", "Ботсвана"),
+    modern("BV", "Bouvet Island", "Остров Буве"),
+    modern("BR", "Brazil", "Бразилия"),
+    modern("IO", "British Indian Ocean Territory", "Британская территория в Индийском океане"),
+    modern("BN", "Brunei", "Бруней"),
+    modern("BG", "Bulgaria", "Болгария"),
+    modern("BF", "Burkina Faso", "Буркина-Фасо"),
+    modern("BI", "Burundi", "Бурунди"),
+    modern("CV", "Cabo Verde", "Кабо-Верде"),
+    modern("KH", "Cambodia", "Камбоджа"),
+    modern("CM", "Cameroon", "Камерун"),
+    modern("CA", "Canada", "Канада"),
+    modern("KY", "Cayman Islands", "Каймановы Острова"),
+    modern("CF", "Central African Republic", "Центральноафриканская Республика"),
+    modern("TD", "Chad", "Чад"),
+    modern("CL", "Chile", "Чили"),
+    modern_aka("CN", "China", "Китай", &["People's Republic of China", "PRC", "КНР"]),
+    modern("CX", "Christmas Island", "Остров Рождества"),
+    modern("CC", "Cocos (Keeling) Islands", "Кокосовые острова"),
+    modern("CO", "Colombia", "Колумбия"),
+    modern("KM", "Comoros", "Коморы"),
+    modern("CG", "Congo", "Республика Конго"),
+    modern("CD", "Congo (Democratic Republic)", "Демократическая Республика Конго"),
+    modern("CK", "Cook Islands", "Острова Кука"),
+    modern("CR", "Costa Rica", "Коста-Рика"),
+    modern("CI", "Côte d'Ivoire", "Кот-д’Ивуар"),
+    modern("HR", "Croatia", "Хорватия"),
+    modern("CU", "Cuba", "Куба"),
+    modern("CW", "Curaçao", "Кюрасао"),
+    modern("CY", "Cyprus", "Кипр"),
+    modern_aka("CZ", "Czechia", "Чехия", &["Czech Republic"]),
+    modern("DK", "Denmark", "Дания"),
+    modern("DJ", "Djibouti", "Джибути"),
+    modern("DM", "Dominica", "Доминика"),
+    modern("DO", "Dominican Republic", "Доминиканская Республика"),
+    modern("EC", "Ecuador", "Эквадор"),
+    modern("EG", "Egypt", "Египет"),
+    modern("SV", "El Salvador", "Сальвадор"),
+    modern("GQ", "Equatorial Guinea", "Экваториальная Гвинея"),
+    modern("ER", "Eritrea", "Эритрея"),
+    modern("EE", "Estonia", "Эстония"),
+    modern("SZ", "Eswatini", "Эсватини"),
+    modern("ET", "Ethiopia", "Эфиопия"),
+    modern("FK", "Falkland Islands", "Фолклендские острова"),
+    modern("FO", "Faroe Islands", "Фарерские острова"),
+    modern("FJ", "Fiji", "Фиджи"),
+    modern("FI", "Finland", "Финляндия"),
+    modern("FR", "France", "Франция"),
+    modern("GF", "French Guiana", "Французская Гвиана"),
+    modern("PF", "French Polynesia", "Французская Полинезия"),
+    modern("TF", "French Southern Territories", "Французские Южные и Антарктические территории"),
+    modern("GA", "Gabon", "Габон"),
+    modern("GM", "Gambia", "Гамбия"),
+    modern("GE", "Georgia", "Грузия"),
+    modern_aka("DE", "Germany", "Германия", &["Federal Republic of Germany", "ФРГ"]),
+    modern("GH", "Ghana", "Гана"),
+    modern("GI", "Gibraltar", "Гибралтар"),
+    modern("GR", "Greece", "Греция"),
+    modern("GL", "Greenland", "Гренландия"),
+    modern("GD", "Grenada", "Гренада"),
+    modern("GP", "Guadeloupe", "Гваделупа"),
+    modern("GU", "Guam", "Гуам"),
+    modern("GT", "Guatemala", "Гватемала"),
+    modern("GG", "Guernsey", "Гернси"),
+    modern("GN", "Guinea", "Гвинея"),
+    modern("GW", "Guinea-Bissau", "Гвинея-Бисау"),
+    modern("GY", "Guyana", "Гайана"),
+    modern("HT", "Haiti", "Гаити"),
+    modern("HM", "Heard Island and McDonald Islands", "Остров Херд и острова Макдональд"),
+    modern("VA", "Holy See", "Ватикан"),
+    modern("HN", "Honduras", "Гондурас"),
+    modern("HK", "Hong Kong", "Гонконг"),
+    modern("HU", "Hungary", "Венгрия"),
+    modern("IS", "Iceland", "Исландия"),
+    modern("IN", "India", "Индия"),
+    modern("ID", "Indonesia", "Индонезия"),
+    modern("IR", "Iran", "Иран"),
+    modern("IQ", "Iraq", "Ирак"),
+    modern("IE", "Ireland", "Ирландия"),
+    modern("IM", "Isle of Man", "Остров Мэн"),
+    modern("IL", "Israel", "Израиль"),
+    modern("IT", "Italy", "Италия"),
+    modern("JM", "Jamaica", "Ямайка"),
+    modern("JP", "Japan", "Япония"),
+    modern("JE", "Jersey", "Джерси"),
+    modern("JO", "Jordan", "Иордания"),
+    modern("KZ", "Kazakhstan", "Казахстан"),
+    modern("KE", "Kenya", "Кения"),
+    modern("KI", "Kiribati", "Кирибати"),
+    modern_aka("KP", "North Korea", "КНДР", &["Korea (Democratic People's Republic)", "Северная Корея"]),
+    modern_aka("KR", "South Korea", "Республика Корея", &["Korea (Republic)", "Южная Корея"]),
+    modern("KW", "Kuwait", "Кувейт"),
+    modern("KG", "Kyrgyzstan", "Киргизия"),
+    modern("LA", "Laos", "Лаос"),
+    modern("LV", "Latvia", "Латвия"),
+    modern("LB", "Lebanon", "Ливан"),
+    modern("LS", "Lesotho", "Лесото"),
+    modern("LR", "Liberia", "Либерия"),
+    modern("LY", "Libya", "Ливия"),
+    modern("LI", "Liechtenstein", "Лихтенштейн"),
+    modern("LT", "Lithuania", "Литва"),
+    modern("LU", "Luxembourg", "Люксембург"),
+    modern("MO", "Macao", "Макао"),
+    modern("MG", "Madagascar", "Мадагаскар"),
+    modern("MW", "Malawi", "Малави"),
+    modern("MY", "Malaysia", "Малайзия"),
+    modern("MV", "Maldives", "Мальдивы"),
+    modern("ML", "Mali", "Мали"),
+    modern("MT", "Malta", "Мальта"),
+    modern("MH", "Marshall Islands", "Маршалловы Острова"),
+    modern("MQ", "Martinique", "Мартиника"),
+    modern("MR", "Mauritania", "Мавритания"),
+    modern("MU", "Mauritius", "Маврикий"),
+    modern("YT", "Mayotte", "Майотта"),
+    modern("MX", "Mexico", "Мексика"),
+    modern("FM", "Micronesia", "Микронезия"),
+    modern("MD", "Moldova", "Молдавия"),
+    modern("MC", "Monaco", "Монако"),
+    modern("MN", "Mongolia", "Монголия"),
+    modern("ME", "Montenegro", "Черногория"),
+    modern("MS", "Montserrat", "Монтсеррат"),
+    modern("MA", "Morocco", "Марокко"),
+    modern("MZ", "Mozambique", "Мозамбик"),
+    modern("MM", "Myanmar", "Мьянма"),
+    modern("NA", "Namibia", "Намибия"),
+    modern("NR", "Nauru", "Науру"),
+    modern("NP", "Nepal", "Непал"),
+    modern_aka("NL", "Netherlands", "Нидерланды", &["Holland", "Голландия"]),
+    modern("NC", "New Caledonia", "Новая Каледония"),
+    modern("NZ", "New Zealand", "Новая Зеландия"),
+    modern("NI", "Nicaragua", "Никарагуа"),
+    modern("NE", "Niger", "Нигер"),
+    modern("NG", "Nigeria", "Нигерия"),
+    modern("NU", "Niue", "Ниуэ"),
+    modern("NF", "Norfolk Island", "Остров Норфолк"),
+    modern("MK", "North Macedonia", "Северная Македония"),
+    modern("MP", "Northern Mariana Islands", "Северные Марианские Острова"),
+    modern("NO", "Norway", "Норвегия"),
+    modern("OM", "Oman", "Оман"),
+    modern("PK", "Pakistan", "Пакистан"),
+    modern("PW", "Palau", "Палау"),
+    modern("PS", "Palestine", "Палестина"),
+    modern("PA", "Panama", "Панама"),
+    modern("PG", "Papua New Guinea", "Папуа — Новая Гвинея"),
+    modern("PY", "Paraguay", "Парагвай"),
+    modern("PE", "Peru", "Перу"),
+    modern("PH", "Philippines", "Филиппины"),
+    modern("PN", "Pitcairn", "Острова Питкэрн"),
+    modern("PL", "Poland", "Польша"),
+    modern("PT", "Portugal", "Португалия"),
+    modern("PR", "Puerto Rico", "Пуэрто-Рико"),
+    modern("QA", "Qatar", "Катар"),
+    modern("RE", "Réunion", "Реюньон"),
+    modern("RO", "Romania", "Румыния"),
+    modern_aka("RU", "Russia", "Россия", &["Russian Federation", "Российская Федерация", "РФ"]),
+    modern("RW", "Rwanda", "Руанда"),
+    modern("BL", "Saint Barthélemy", "Сен-Бартелеми"),
+    modern("SH", "Saint Helena, Ascension and Tristan da Cunha", "Острова Святой Елены, Вознесения и Тристан-да-Кунья"),
+    modern("KN", "Saint Kitts and Nevis", "Сент-Китс и Невис"),
+    modern("LC", "Saint Lucia", "Сент-Люсия"),
+    modern("MF", "Saint Martin", "Сен-Мартен"),
+    modern("PM", "Saint Pierre and Miquelon", "Сен-Пьер и Микелон"),
+    modern("VC", "Saint Vincent and the Grenadines", "Сент-Винсент и Гренадины"),
+    modern("WS", "Samoa", "Самоа"),
+    modern("SM", "San Marino", "Сан-Марино"),
+    modern("ST", "Sao Tome and Principe", "Сан-Томе и Принсипи"),
+    modern("SA", "Saudi Arabia", "Саудовская Аравия"),
+    modern("SN", "Senegal", "Сенегал"),
+    modern("RS", "Serbia", "Сербия"),
+    modern("SC", "Seychelles", "Сейшельские Острова"),
+    modern("SL", "Sierra Leone", "Сьерра-Леоне"),
+    modern("SG", "Singapore", "Сингапур"),
+    modern("SX", "Sint Maarten", "Синт-Мартен"),
+    modern("SK", "Slovakia", "Словакия"),
+    modern("SI", "Slovenia", "Словения"),
+    modern("SB", "Solomon Islands", "Соломоновы Острова"),
+    modern("SO", "Somalia", "Сомали"),
+    modern("ZA", "South Africa", "ЮАР"),
+    modern("GS", "South Georgia and the South Sandwich Islands", "Южная Георгия и Южные Сандвичевы Острова"),
+    modern("SS", "South Sudan", "Южный Судан"),
+    modern("ES", "Spain", "Испания"),
+    modern("LK", "Sri Lanka", "Шри-Ланка"),
+    modern("SD", "Sudan", "Судан"),
+    modern("SR", "Suriname", "Суринам"),
+    modern("SJ", "Svalbard and Jan Mayen", "Шпицберген и Ян-Майен"),
+    modern("SE", "Sweden", "Швеция"),
+    modern("CH", "Switzerland", "Швейцария"),
+    modern("SY", "Syria", "Сирия"),
+    modern("TW", "Taiwan", "Тайвань"),
+    modern("TJ", "Tajikistan", "Таджикистан"),
+    modern("TZ", "Tanzania", "Танзания"),
+    modern("TH", "Thailand", "Таиланд"),
+    modern("TL", "Timor-Leste", "Восточный Тимор"),
+    modern("TG", "Togo", "Того"),
+    modern("TK", "Tokelau", "Токелау"),
+    modern("TO", "Tonga", "Тонга"),
+    modern("TT", "Trinidad and Tobago", "Тринидад и Тобаго"),
+    modern("TN", "Tunisia", "Тунис"),
+    modern_aka("TR", "Türkiye", "Турция", &["Turkey"]),
+    modern("TM", "Turkmenistan", "Туркмения"),
+    modern("TC", "Turks and Caicos Islands", "Теркс и Кайкос"),
+    modern("TV", "Tuvalu", "Тувалу"),
+    modern("UG", "Uganda", "Уганда"),
+    modern("UA", "Ukraine", "Украина"),
+    modern_aka("AE", "United Arab Emirates", "Объединённые Арабские Эмираты", &["UAE", "ОАЭ"]),
+    modern_aka(
+        "GB",
+        "United Kingdom",
+        "Великобритания",
+        &["UK", "Great Britain", "Britain", "England", "Англия", "Соединённое Королевство"],
+    ),
+    modern_aka(
+        "US",
+        "United States",
+        "США",
+        &["USA", "United States of America", "America", "Соединённые Штаты Америки", "Америка"],
+    ),
+    modern("UM", "United States Minor Outlying Islands", "Внешние малые острова США"),
+    modern("UY", "Uruguay", "Уругвай"),
+    modern("UZ", "Uzbekistan", "Узбекистан"),
+    modern("VU", "Vanuatu", "Вануату"),
+    modern("VE", "Venezuela", "Венесуэла"),
+    modern("VN", "Viet Nam", "Вьетнам"),
+    modern("VG", "Virgin Islands (British)", "Британские Виргинские острова"),
+    modern("VI", "Virgin Islands (U.S.)", "Виргинские Острова США"),
+    modern("WF", "Wallis and Futuna", "Уоллис и Футуна"),
+    modern("EH", "Western Sahara", "Западная Сахара"),
+    modern("YE", "Yemen", "Йемен"),
+    modern("ZM", "Zambia", "Замбия"),
+    modern("ZW", "Zimbabwe", "Зимбабве"),
+    // Historical entities. ISO 3166-3 codes where the entity had an ISO
+    // code; `X-` codes for older states that never had one.
+    historical("SUHH", "Soviet Union", "СССР", &["USSR", "Union of Soviet Socialist Republics", "Советский Союз", "SU"]),
+    historical("DDDE", "East Germany", "ГДР", &["German Democratic Republic", "GDR", "DDR", "Германская Демократическая Республика"]),
+    historical("YUCS", "Yugoslavia", "Югославия", &["SFRY", "СФРЮ"]),
+    historical("CSHH", "Czechoslovakia", "Чехословакия", &["ЧССР"]),
+    historical("VDVN", "North Vietnam", "Северный Вьетнам", &["Democratic Republic of Vietnam", "ДРВ"]),
+    historical("YDYE", "South Yemen", "Южный Йемен", &["People's Democratic Republic of Yemen", "НДРЙ"]),
+    historical("ZRCD", "Zaire", "Заир", &[]),
+    historical("BUMM", "Burma", "Бирма", &[]),
+    historical("X-RUE", "Russian Empire", "Российская империя", &["Imperial Russia"]),
+    historical("X-PRU", "Prussia", "Пруссия", &["Kingdom of Prussia", "Королевство Пруссия"]),
+    historical("X-DEK", "German Empire", "Германская империя", &["Imperial Germany", "Kaiserreich"]),
+    historical("X-DER", "Nazi Germany", "Третий рейх", &["Third Reich", "Германский рейх"]),
+    historical("X-AUH", "Austria-Hungary", "Австро-Венгрия", &["Austro-Hungarian Empire"]),
+    historical("X-OTT", "Ottoman Empire", "Османская империя", &["Ottoman Turkey"]),
+    historical("X-CSA", "Confederate States", "Конфедеративные Штаты Америки", &["Confederacy", "CSA", "Конфедерация"]),
+    historical("X-RVN", "South Vietnam", "Южный Вьетнам", &["Republic of Vietnam"]),
+    historical("X-JPE", "Empire of Japan", "Японская империя", &["Imperial Japan"]),
+    historical("X-ROC", "Republic of China (1912–1949)", "Китайская Республика (1912–1949)", &[]),
+];
@@ -0,0 +1,99 @@
+//! Which of the Russian and English names of branches, categories and
+//! formation levels to show
+//!
+//! A library prefers one of the two (see `Library::display_language`) or
+//! follows the UI language; an empty name gives way to the other one.
+
+/// Name language a library prefers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayLang {
+    /// Follow the UI language
+    #[default]
+    Ui,
+    Ru,
+    En,
+}
+
+impl DisplayLang {
+    /// Preference stored as `code`: "ru", "en", or "auto" (also "ui") to
+    /// follow the UI; anything else follows the UI
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "ru" => DisplayLang::Ru,
+            "en" => DisplayLang::En,
+            _ => DisplayLang::Ui,
+        }
+    }
+
+    /// Code stored in the library
+    pub fn code(self) -> &'static str {
+        match self {
+            DisplayLang::Ui => "auto",
+            DisplayLang::Ru => "ru",
+            DisplayLang::En => "en",
+        }
+    }
+
+    /// Language whose names are shown, "ru" or "en", in the UI language
+    /// `ui_lang`; UI languages other than Russian show English names
+    pub fn resolve(self, ui_lang: &str) -> &'static str {
+        match self {
+            DisplayLang::Ru => "ru",
+            DisplayLang::En => "en",
+            DisplayLang::Ui if ui_lang == "ru" => "ru",
+            DisplayLang::Ui => "en",
+        }
+    }
+}
+
+/// Name to show of one with Russian name `ru` and English name `en`: the one
+/// in the library's preferred language, or the other when that is empty
+pub fn display_name<'a>(ru: &'a str, en: &'a str, lib_pref: DisplayLang, ui_lang: &str) -> &'a str {
+    let (preferred, other) = if lib_pref.resolve(ui_lang) == "ru" { (ru, en) } else { (en, ru) };
+    if preferred.trim().is_empty() {
+        other
+    } else {
+        preferred
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_name_follows_ui_language() {
+        assert_eq!(display_name("Пехота", "Infantry", DisplayLang::Ui, "ru"), "Пехота");
+        assert_eq!(display_name("Пехота", "Infantry", DisplayLang::Ui, "en"), "Infantry");
+        assert_eq!(display_name("Пехота", "Infantry", DisplayLang::Ui, "de"), "Infantry");
+    }
+
+    #[test]
+    fn test_display_name_library_preference_wins() {
+        for ui in ["ru", "en"] {
+            assert_eq!(display_name("Пехота", "Infantry", DisplayLang::Ru, ui), "Пехота");
+            assert_eq!(display_name("Пехота", "Infantry", DisplayLang::En, ui), "Infantry");
+        }
+    }
+
+    #[test]
+    fn test_display_name_empty_side_gives_way() {
+        for pref in [DisplayLang::Ui, DisplayLang::Ru, DisplayLang::En] {
+            for ui in ["ru", "en"] {
+                assert_eq!(display_name("", "Infantry", pref, ui), "Infantry");
+                assert_eq!(display_name("Пехота", "  ", pref, ui), "Пехота");
+                assert_eq!(display_name("", "", pref, ui), "");
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_lang_codes() {
+        assert_eq!(DisplayLang::from_code("auto"), DisplayLang::Ui);
+        assert_eq!(DisplayLang::from_code("ui"), DisplayLang::Ui);
+        assert_eq!(DisplayLang::from_code(""), DisplayLang::Ui);
+        for lang in [DisplayLang::Ui, DisplayLang::Ru, DisplayLang::En] {
+            assert_eq!(DisplayLang::from_code(lang.code()), lang);
+        }
+    }
+}
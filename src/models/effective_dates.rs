@@ -0,0 +1,190 @@
+//! Periods in which units are part of the order of battle, and filtering a
+//! unit tree to the units valid at a given date.
+//!
+//! Dates are stored as `YYYY-MM-DD` text. Both ends of a period are
+//! inclusive and either may be open; a unit without dates is always valid.
+
+use chrono::NaiveDate;
+
+use super::library::Unit;
+use super::tree::{assemble_forest, walk_units};
+use super::validation::ValidationError;
+
+/// Format of stored and entered dates
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Parse an entered date; blank text means no date
+pub fn parse_date(text: &str) -> Result<Option<NaiveDate>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(text, DATE_FORMAT)
+        .map(Some)
+        .map_err(|_| format!("\"{}\" is not a date in the form YYYY-MM-DD", text))
+}
+
+/// Stored form of a date
+pub fn format_date(date: NaiveDate) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+/// Check an entered period and return it in stored form
+pub fn parse_effective_dates(
+    from: &str,
+    to: &str,
+) -> Result<(Option<String>, Option<String>), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    let mut parse = |field: &str, text: &str| {
+        parse_date(text).unwrap_or_else(|message| {
+            errors.push(ValidationError {
+                field: field.to_string(),
+                message,
+            });
+            None
+        })
+    };
+    let from = parse("effective_from", from);
+    let to = parse("effective_to", to);
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            errors.push(ValidationError {
+                field: "effective_to".to_string(),
+                message: "The end date cannot be before the start date".to_string(),
+            });
+        }
+    }
+    if errors.is_empty() {
+        Ok((from.map(format_date), to.map(format_date)))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Whether a period includes `as_of`. Stored dates that do not parse leave
+/// that end of the period open.
+pub fn is_effective_at(from: Option<&str>, to: Option<&str>, as_of: NaiveDate) -> bool {
+    let bound = |date: Option<&str>| date.and_then(|d| parse_date(d).ok().flatten());
+    bound(from).is_none_or(|from| from <= as_of) && bound(to).is_none_or(|to| as_of <= to)
+}
+
+impl Unit {
+    /// Whether the unit is part of the order of battle at `as_of`
+    pub fn is_effective_at(&self, as_of: NaiveDate) -> bool {
+        is_effective_at(self.effective_from.as_deref(), self.effective_to.as_deref(), as_of)
+    }
+}
+
+/// Units valid at `as_of`; a unit that is left out takes its subordinates with it
+pub fn units_as_of(units: &[Unit], as_of: NaiveDate) -> Vec<Unit> {
+    // Position in `nodes` of every unit kept so far, by its place in the walk
+    let mut kept: Vec<Option<usize>> = Vec::new();
+    let mut nodes = Vec::new();
+    for visit in walk_units(units) {
+        let parent = match visit.parent.map(|p| kept[p]) {
+            Some(None) => {
+                kept.push(None);
+                continue;
+            }
+            Some(parent) => parent,
+            None => None,
+        };
+        if visit.unit.is_effective_at(as_of) {
+            kept.push(Some(nodes.len()));
+            nodes.push((visit.unit.without_children(), parent));
+        } else {
+            kept.push(None);
+        }
+    }
+    assemble_forest(nodes, |unit, children| unit.children = children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tree::unit_chain_with;
+
+    fn date(text: &str) -> NaiveDate {
+        parse_date(text).unwrap().unwrap()
+    }
+
+    fn unit(name: &str, from: Option<&str>, to: Option<&str>, children: Vec<Unit>) -> Unit {
+        let mut unit = Unit::new(name.to_string(), String::new());
+        unit.effective_from = from.map(str::to_string);
+        unit.effective_to = to.map(str::to_string);
+        unit.children = children;
+        unit
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("  "), Ok(None));
+        assert_eq!(parse_date(" 1943-07-05 ").unwrap(), NaiveDate::from_ymd_opt(1943, 7, 5));
+        assert!(parse_date("1943-02-30").is_err());
+        assert!(parse_date("05.07.1943").is_err());
+    }
+
+    #[test]
+    fn test_parse_effective_dates() {
+        assert_eq!(parse_effective_dates("", ""), Ok((None, None)));
+        assert_eq!(
+            parse_effective_dates("1943-7-5", ""),
+            Ok((Some("1943-07-05".to_string()), None))
+        );
+        assert!(parse_effective_dates("1943-07-05", "1943-07-05").is_ok());
+
+        let errors = parse_effective_dates("1944-01-01", "1943-12-31").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "effective_to");
+        let errors = parse_effective_dates("soon", "later").unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_is_effective_at_boundaries_and_open_ends() {
+        let (from, to) = (Some("1943-01-01"), Some("1943-12-31"));
+        assert!(is_effective_at(from, to, date("1943-01-01")));
+        assert!(is_effective_at(from, to, date("1943-12-31")));
+        assert!(!is_effective_at(from, to, date("1942-12-31")));
+        assert!(!is_effective_at(from, to, date("1944-01-01")));
+
+        assert!(is_effective_at(None, to, date("1900-01-01")));
+        assert!(is_effective_at(from, None, date("2100-01-01")));
+        assert!(is_effective_at(None, None, date("1943-06-01")));
+        assert!(is_effective_at(Some("garbage"), None, date("1943-06-01")));
+    }
+
+    #[test]
+    fn test_units_as_of_drops_subtrees() {
+        let units = vec![
+            unit(
+                "Division",
+                None,
+                None,
+                vec![
+                    unit("1st Regiment", None, Some("1942-12-31"), vec![unit("Battalion", None, None, vec![])]),
+                    unit("2nd Regiment", Some("1943-01-01"), None, vec![]),
+                ],
+            ),
+            unit("Reserve", Some("1944-06-01"), None, vec![]),
+        ];
+        let names = |units: &[Unit]| -> Vec<String> {
+            units
+                .iter()
+                .flat_map(|u| std::iter::once(u.name.clone()).chain(u.children.iter().map(|c| c.name.clone())))
+                .collect()
+        };
+        assert_eq!(names(&units_as_of(&units, date("1943-01-01"))), vec!["Division", "2nd Regiment"]);
+        let early = units_as_of(&units, date("1942-12-31"));
+        assert_eq!(names(&early), vec!["Division", "1st Regiment"]);
+        assert_eq!(early[0].children[0].children.len(), 1);
+    }
+
+    #[test]
+    fn test_units_as_of_deep_chain() {
+        let units = unit_chain_with(1000, |i| unit(&format!("U{}", i), None, (i == 500).then_some("1942-12-31"), vec![]));
+        assert_eq!(walk_units(&units_as_of(&units, date("1942-12-31"))).count(), 1000);
+        let later = units_as_of(&units, date("1943-01-01"));
+        assert_eq!(walk_units(&later).last().map(|v| (v.depth, v.unit.name.as_str())), Some((499, "U499")));
+    }
+}
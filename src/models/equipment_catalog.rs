@@ -0,0 +1,338 @@
+//! Library-scoped catalog of equipment and vehicle names.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Kind of catalog equipment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EquipmentCategory {
+    SmallArms,
+    Vehicles,
+    #[default]
+    Other,
+}
+
+impl EquipmentCategory {
+    /// All categories in display order
+    pub const ALL: [EquipmentCategory; 3] = [
+        EquipmentCategory::SmallArms,
+        EquipmentCategory::Vehicles,
+        EquipmentCategory::Other,
+    ];
+
+    /// Code stored in the database and in exported files
+    pub fn code(self) -> &'static str {
+        match self {
+            EquipmentCategory::SmallArms => "small_arms",
+            EquipmentCategory::Vehicles => "vehicles",
+            EquipmentCategory::Other => "other",
+        }
+    }
+
+    /// Category for a stored code; unknown codes are `Other`
+    pub fn from_code(code: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.code() == code.trim())
+            .unwrap_or_default()
+    }
+
+    /// English name, also the translation key
+    pub fn label(self) -> &'static str {
+        match self {
+            EquipmentCategory::SmallArms => "Small arms",
+            EquipmentCategory::Vehicles => "Vehicles",
+            EquipmentCategory::Other => "Other",
+        }
+    }
+}
+
+/// An equipment or vehicle type a library's units can carry (e.g. AK-74, BMP-2).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EquipmentCatalogEntry {
+    pub id: Option<i64>,
+    pub library_id: i64,
+    pub name_ru: String,
+    pub name_en: String,
+    #[serde(default)]
+    pub category: EquipmentCategory,
+    /// Position in the library's list (ties fall back to id)
+    #[serde(default)]
+    pub sort_order: i64,
+    /// Other names the same item goes by (e.g. "Калашников", "AK74")
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+impl EquipmentCatalogEntry {
+    pub fn new(library_id: i64, name_ru: String, name_en: String, category: EquipmentCategory) -> Self {
+        Self {
+            id: None,
+            library_id,
+            name_ru,
+            name_en,
+            category,
+            sort_order: 0,
+            aliases: Vec::new(),
+        }
+    }
+
+    /// The Russian and English names, then the aliases, without blank ones
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        [self.name_ru.as_str(), self.name_en.as_str()]
+            .into_iter()
+            .chain(self.aliases.iter().map(String::as_str))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Name in `lang` ("ru" or anything else for English), or the other one when blank
+    pub fn display_name(&self, lang: &str) -> &str {
+        let (first, second) = if lang == "ru" {
+            (&self.name_ru, &self.name_en)
+        } else {
+            (&self.name_en, &self.name_ru)
+        };
+        if first.trim().is_empty() {
+            second.trim()
+        } else {
+            first.trim()
+        }
+    }
+
+    /// Add `name` as an alias unless the entry already goes by it
+    pub fn add_alias(&mut self, name: &str) {
+        let key = equipment_name_key(name);
+        if !key.is_empty() && !self.names().any(|n| equipment_name_key(n) == key) {
+            self.aliases.push(name.trim().to_string());
+        }
+    }
+
+    /// Trim the aliases and drop blank ones and those repeating a name of the entry
+    pub fn normalize_aliases(&mut self) {
+        let aliases = std::mem::take(&mut self.aliases);
+        for alias in aliases {
+            self.add_alias(&alias);
+        }
+    }
+}
+
+/// Aliases as typed in the editor: separated by semicolons, since names
+/// like "7,62-мм ПКМ" contain commas
+pub fn parse_aliases(text: &str) -> Vec<String> {
+    text.split(';')
+        .map(str::trim)
+        .filter(|alias| !alias.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Aliases as shown in the editor
+pub fn format_aliases(aliases: &[String]) -> String {
+    aliases.join("; ")
+}
+
+/// Fold full-width forms (as typed with CJK input methods) to their ASCII
+/// counterparts
+fn fold_width(c: char) -> char {
+    match c {
+        '\u{3000}' => ' ',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Key equipment names are matched by: trimmed, inner whitespace collapsed,
+/// full-width forms folded and lowercased, so " ак-74" matches "АК-74" and
+/// "ＡＫ－７４" matches "AK-74"
+pub fn equipment_name_key(name: &str) -> String {
+    let folded: String = name.chars().map(fold_width).collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// A name two or more catalog entries go by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasCollision {
+    /// The name as the first entry claiming it spells it
+    pub name: String,
+    /// Indexes of the claiming entries in the catalog, in catalog order
+    pub entries: Vec<usize>,
+}
+
+/// Looks up catalog entries by any of their names or aliases. A name claimed
+/// by several entries resolves to the first of them and is reported as a
+/// collision.
+pub struct EquipmentAliases<'a> {
+    entries: &'a [EquipmentCatalogEntry],
+    by_key: HashMap<String, usize>,
+    collisions: Vec<AliasCollision>,
+}
+
+impl<'a> EquipmentAliases<'a> {
+    pub fn new(entries: &'a [EquipmentCatalogEntry]) -> Self {
+        // Each key's first claimant, with the name as that entry spells it
+        let mut claims: HashMap<String, (usize, &str)> = HashMap::new();
+        let mut collisions: BTreeMap<String, AliasCollision> = BTreeMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            for name in entry.names() {
+                let key = equipment_name_key(name);
+                match claims.get(&key) {
+                    None => {
+                        claims.insert(key, (index, name));
+                    }
+                    Some(&(owner, first_name)) if owner != index => {
+                        let collision = collisions.entry(key).or_insert_with(|| AliasCollision {
+                            name: first_name.to_string(),
+                            entries: vec![owner],
+                        });
+                        if !collision.entries.contains(&index) {
+                            collision.entries.push(index);
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        let mut collisions: Vec<AliasCollision> = collisions.into_values().collect();
+        collisions.sort_by_key(|c| c.entries.clone());
+        let by_key = claims.into_iter().map(|(key, (index, _))| (key, index)).collect();
+        Self { entries, by_key, collisions }
+    }
+
+    /// Index of the entry going by `name`
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.by_key.get(&equipment_name_key(name)).copied()
+    }
+
+    /// The entry going by `name`
+    pub fn resolve(&self, name: &str) -> Option<&'a EquipmentCatalogEntry> {
+        self.position(name).map(|i| &self.entries[i])
+    }
+
+    /// Name of the entry going by `name` in `lang`; names not in the catalog
+    /// come back trimmed
+    pub fn canonical_name(&self, name: &str, lang: &str) -> String {
+        self.resolve(name)
+            .map(|entry| entry.display_name(lang))
+            .filter(|canonical| !canonical.is_empty())
+            .unwrap_or(name.trim())
+            .to_string()
+    }
+
+    /// Names claimed by more than one entry
+    pub fn collisions(&self) -> &[AliasCollision] {
+        &self.collisions
+    }
+}
+
+/// `imported` entries merged into `existing`: one going by a name of an
+/// entry already in the list adds its names to that entry's aliases, the
+/// others are appended
+pub fn merge_catalog_entries(
+    existing: Vec<EquipmentCatalogEntry>,
+    imported: Vec<EquipmentCatalogEntry>,
+) -> Vec<EquipmentCatalogEntry> {
+    let mut merged = existing;
+    for entry in imported {
+        let target = {
+            let aliases = EquipmentAliases::new(&merged);
+            entry.names().find_map(|name| aliases.position(name))
+        };
+        match target {
+            Some(index) => {
+                for name in entry.names() {
+                    merged[index].add_alias(name);
+                }
+            }
+            None => merged.push(entry),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_codes_round_trip() {
+        for category in EquipmentCategory::ALL {
+            assert_eq!(EquipmentCategory::from_code(category.code()), category);
+        }
+        assert_eq!(EquipmentCategory::from_code("aircraft"), EquipmentCategory::Other);
+        assert_eq!(EquipmentCategory::from_code(""), EquipmentCategory::Other);
+    }
+
+    fn entry(ru: &str, en: &str, aliases: &[&str]) -> EquipmentCatalogEntry {
+        let mut e = EquipmentCatalogEntry::new(1, ru.to_string(), en.to_string(), EquipmentCategory::Other);
+        e.aliases = aliases.iter().map(|a| a.to_string()).collect();
+        e
+    }
+
+    #[test]
+    fn test_name_key_ignores_case_width_and_spacing() {
+        assert_eq!(equipment_name_key("  АК-74 "), equipment_name_key("ак-74"));
+        assert_eq!(equipment_name_key("ＡＫ－７４"), equipment_name_key("ak-74"));
+        assert_eq!(equipment_name_key("T-72\u{3000} B"), "t-72 b");
+        assert_ne!(equipment_name_key("T-72B"), equipment_name_key("T-72 B"));
+    }
+
+    #[test]
+    fn test_aliases_resolve_to_canonical_entry() {
+        let catalog = vec![entry("АК-74", "AK-74", &["Калашников", "AK74"]), entry("БМП-2", "BMP-2", &[])];
+        let aliases = EquipmentAliases::new(&catalog);
+        assert_eq!(aliases.position("ak74"), Some(0));
+        assert_eq!(aliases.position("КАЛАШНИКОВ"), Some(0));
+        assert_eq!(aliases.position("bmp-2"), Some(1));
+        assert_eq!(aliases.position("T-72"), None);
+        assert_eq!(aliases.canonical_name("AK74", "ru"), "АК-74");
+        assert_eq!(aliases.canonical_name("AK74", "en"), "AK-74");
+        assert_eq!(aliases.canonical_name(" T-72 ", "en"), "T-72");
+        assert!(aliases.collisions().is_empty());
+    }
+
+    #[test]
+    fn test_collisions_report_every_claimant() {
+        let catalog = vec![
+            entry("АК-74", "AK-74", &["Автомат"]),
+            entry("АКМ", "AKM", &["автомат", "AKM"]),
+            entry("РПК", "RPK", &["ＡＫＭ"]),
+        ];
+        let aliases = EquipmentAliases::new(&catalog);
+        assert_eq!(
+            aliases.collisions(),
+            [
+                AliasCollision { name: "Автомат".to_string(), entries: vec![0, 1] },
+                AliasCollision { name: "AKM".to_string(), entries: vec![1, 2] },
+            ]
+        );
+        // The first claimant keeps the name
+        assert_eq!(aliases.position("автомат"), Some(0));
+        assert_eq!(aliases.position("akm"), Some(1));
+    }
+
+    #[test]
+    fn test_alias_text_and_normalization() {
+        assert_eq!(parse_aliases(" 7,62-мм ПКМ ; ;PKM"), vec!["7,62-мм ПКМ", "PKM"]);
+        assert_eq!(format_aliases(&parse_aliases("a;b")), "a; b");
+        let mut e = entry("ПКМ", "PKM", &[" pkm ", "Печенег", "печенег", " "]);
+        e.normalize_aliases();
+        assert_eq!(e.aliases, vec!["Печенег"]);
+    }
+
+    #[test]
+    fn test_merge_folds_imported_names_into_existing_entries() {
+        let existing = vec![entry("АК-74", "AK-74", &[]), entry("БМП-2", "BMP-2", &[])];
+        let imported = vec![
+            entry("", "ak-74", &["AK74"]),
+            entry("Т-72", "T-72", &[]),
+            entry("", "T-72", &["Т-72Б"]),
+        ];
+        let merged = merge_catalog_entries(existing, imported);
+        let names: Vec<&str> = merged.iter().map(|e| e.name_en.as_str()).collect();
+        assert_eq!(names, vec!["AK-74", "BMP-2", "T-72"]);
+        assert_eq!(merged[0].aliases, vec!["AK74"]);
+        assert_eq!(merged[2].aliases, vec!["Т-72Б"]);
+    }
+}
@@ -114,6 +114,15 @@ pub struct CustomFormationLevel {
     pub name_en: String,
     /// Which standard level this custom level corresponds to (0..=11).
     pub standard_level_ordinal: i32,
+    /// Position in the library's list (ties fall back to the standard level)
+    #[serde(default)]
+    pub sort_order: i64,
+    /// Smallest expected aggregated personnel of a unit at this level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_personnel: Option<u32>,
+    /// Largest expected aggregated personnel of a unit at this level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_personnel: Option<u32>,
 }
 
 impl CustomFormationLevel {
@@ -124,6 +133,32 @@ impl CustomFormationLevel {
             name_ru,
             name_en,
             standard_level_ordinal,
+            sort_order: 0,
+            min_personnel: None,
+            max_personnel: None,
+        }
+    }
+
+    /// Whether `name` (a unit type) names this level in either language
+    pub fn matches_name(&self, name: &str) -> bool {
+        let name = name.trim();
+        !name.is_empty()
+            && (self.name_en.trim().eq_ignore_ascii_case(name)
+                || self.name_ru.trim().to_lowercase() == name.to_lowercase())
+    }
+
+    /// Whether a personnel range is set
+    pub fn has_personnel_range(&self) -> bool {
+        self.min_personnel.is_some() || self.max_personnel.is_some()
+    }
+
+    /// Expected personnel range for display: "30–45", "≥ 30", "≤ 45", or None
+    pub fn personnel_range_label(&self) -> Option<String> {
+        match (self.min_personnel, self.max_personnel) {
+            (Some(min), Some(max)) => Some(format!("{}–{}", min, max)),
+            (Some(min), None) => Some(format!("≥ {}", min)),
+            (None, Some(max)) => Some(format!("≤ {}", max)),
+            (None, None) => None,
         }
     }
 }
@@ -189,8 +224,8 @@ mod tests {
     #[test]
     fn test_standard_level_all_ordered() {
         let all = StandardFormationLevel::all();
-        for i in 0..all.len() {
-            assert_eq!(all[i].ordinal(), i as i32);
+        for (i, level) in all.iter().enumerate() {
+            assert_eq!(level.ordinal(), i as i32);
         }
     }
 
@@ -204,6 +239,36 @@ mod tests {
         assert_eq!(level.standard_level_ordinal, 3);
     }
 
+    #[test]
+    fn test_personnel_range_label() {
+        let mut level = CustomFormationLevel::new(1, "взвод".to_string(), "platoon".to_string(), 3);
+        assert!(!level.has_personnel_range());
+        assert_eq!(level.personnel_range_label(), None);
+        level.min_personnel = Some(30);
+        assert_eq!(level.personnel_range_label().as_deref(), Some("≥ 30"));
+        level.max_personnel = Some(45);
+        assert_eq!(level.personnel_range_label().as_deref(), Some("30–45"));
+        level.min_personnel = None;
+        assert_eq!(level.personnel_range_label().as_deref(), Some("≤ 45"));
+    }
+
+    #[test]
+    fn test_matches_name() {
+        let level = CustomFormationLevel::new(1, "Взвод".to_string(), "Platoon".to_string(), 3);
+        assert!(level.matches_name("platoon"));
+        assert!(level.matches_name(" взвод "));
+        assert!(!level.matches_name("company"));
+        assert!(!level.matches_name(""));
+    }
+
+    #[test]
+    fn test_custom_formation_level_without_range_deserializes() {
+        let json = r#"{"id":null,"library_id":1,"name_ru":"рота","name_en":"company","standard_level_ordinal":4}"#;
+        let level: CustomFormationLevel = serde_json::from_str(json).unwrap();
+        assert_eq!(level.min_personnel, None);
+        assert_eq!(level.max_personnel, None);
+    }
+
     #[test]
     fn test_custom_formation_level_serialization() {
         let level = CustomFormationLevel::new(1, "рота".to_string(), "company".to_string(), 4);
@@ -3,6 +3,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use super::version::Versioned;
+use super::equipment_catalog::EquipmentAliases;
+use super::tree::walk_units;
+use super::numbering::NumberingOptions;
 
 /// A library contains multiple units and metadata
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -11,18 +14,38 @@ pub struct Library {
     pub id: Option<i64>,
     /// Library name (e.g., "US ARMY 2003")
     pub name: String,
-    /// Country code (e.g., "US", "RU")
+    /// Country as entered (e.g., "US", "RU", "Prussia")
     pub country: String,
+    /// Code of the country, when the text names a known one (see `models::country`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
     /// Era/period (e.g., "2003", "Cold War")
     pub era: String,
-    /// Author of the library
+    /// Author of the library (missing from files of early versions)
+    #[serde(default)]
     pub author: String,
-    /// Current version number
+    /// Current version number (missing from files of early versions)
+    #[serde(default = "default_version")]
     pub version: i64,
     /// Tags for categorization
     pub tags: Vec<String>,
     /// Units in this library
     pub units: Vec<Unit>,
+    /// Preferred language for bilingual names: "auto" (follow the UI), "ru" or "en"
+    #[serde(default = "default_display_language")]
+    pub display_language: String,
+    /// How formations are numbered in views and exports; overrides are keyed
+    /// by the ids of the library's formation levels
+    #[serde(default, skip_serializing_if = "NumberingOptions::is_default")]
+    pub numbering: NumberingOptions,
+}
+
+fn default_display_language() -> String {
+    "auto".to_string()
+}
+
+fn default_version() -> i64 {
+    1
 }
 
 impl Library {
@@ -31,12 +54,15 @@ impl Library {
         Self {
             id: None,
             name,
+            country_code: super::country::resolve_country_code(&country).map(str::to_string),
             country,
             era,
             author,
             version: 1,
             tags: Vec::new(),
             units: Vec::new(),
+            display_language: default_display_language(),
+            numbering: NumberingOptions::default(),
         }
     }
 
@@ -88,6 +114,18 @@ pub struct Unit {
     pub equipment: Vec<Equipment>,
     /// Child units
     pub children: Vec<Unit>,
+    /// First day the unit is part of the order of battle, `YYYY-MM-DD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_from: Option<String>,
+    /// Last day the unit is part of the order of battle, `YYYY-MM-DD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_to: Option<String>,
+    /// Number of the official TOE document the unit follows (e.g. "TOE 07015L000")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toe_number: Option<String>,
+    /// Formation level of the unit's library, by id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formation_level_id: Option<i64>,
 }
 
 impl Unit {
@@ -101,6 +139,10 @@ impl Unit {
             personnel: Vec::new(),
             equipment: Vec::new(),
             children: Vec::new(),
+            effective_from: None,
+            effective_to: None,
+            toe_number: None,
+            formation_level_id: None,
         }
     }
 
@@ -121,28 +163,27 @@ impl Unit {
 
     /// Get total personnel count (including children)
     pub fn total_personnel(&self) -> usize {
-        let direct = self.personnel.len();
-        let children_total: usize = self.children.iter().map(|c| c.total_personnel()).sum();
-        direct + children_total
+        walk_units(std::slice::from_ref(self)).map(|v| v.unit.personnel.len()).sum()
     }
 
     /// Get total equipment count (including children)
     pub fn total_equipment(&self) -> HashMap<String, usize> {
         let mut totals = HashMap::new();
-        
-        // Add direct equipment
-        for eq in &self.equipment {
-            *totals.entry(eq.name.clone()).or_insert(0) += eq.quantity;
-        }
-        
-        // Add child equipment
-        for child in &self.children {
-            let child_totals = child.total_equipment();
-            for (name, qty) in child_totals {
-                *totals.entry(name).or_insert(0) += qty;
+        for visit in walk_units(std::slice::from_ref(self)) {
+            for eq in &visit.unit.equipment {
+                *totals.entry(eq.name.clone()).or_insert(0) += eq.quantity;
             }
         }
-        
+        totals
+    }
+
+    /// Total equipment count (including children) by catalog name in `lang`:
+    /// items entered under an alias of a catalog entry count as that entry
+    pub fn total_equipment_resolved(&self, aliases: &EquipmentAliases, lang: &str) -> HashMap<String, usize> {
+        let mut totals = HashMap::new();
+        for (name, qty) in self.total_equipment() {
+            *totals.entry(aliases.canonical_name(&name, lang)).or_insert(0) += qty;
+        }
         totals
     }
 }
@@ -255,6 +296,27 @@ mod tests {
         assert_eq!(platoon.total_personnel(), 2);
     }
 
+    #[test]
+    fn test_equipment_totals_merge_aliases() {
+        use crate::models::{EquipmentCatalogEntry, EquipmentCategory};
+        let mut ak = EquipmentCatalogEntry::new(1, "АК-74".to_string(), "AK-74".to_string(), EquipmentCategory::SmallArms);
+        ak.aliases = vec!["AK74".to_string()];
+        let catalog = vec![ak];
+        let aliases = EquipmentAliases::new(&catalog);
+
+        let mut platoon = Unit::new("Platoon".to_string(), "Platoon".to_string());
+        platoon.add_equipment(Equipment::new("AK-74".to_string(), 2));
+        let mut squad = Unit::new("Squad".to_string(), "Squad".to_string());
+        squad.add_equipment(Equipment::new("ak74".to_string(), 9));
+        squad.add_equipment(Equipment::new("RPG-7".to_string(), 1));
+        platoon.add_child(squad);
+
+        let totals = platoon.total_equipment_resolved(&aliases, "ru");
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["АК-74"], 11);
+        assert_eq!(totals["RPG-7"], 1);
+    }
+
     #[test]
     fn test_versioned_trait() {
         use crate::models::Versioned;
@@ -4,10 +4,29 @@ pub mod library;
 pub mod version;
 pub mod formation_level;
 pub mod branch;
+pub mod equipment_catalog;
+pub mod provenance;
 pub mod validation;
+pub mod effective_dates;
+pub mod numbering;
+pub mod country;
+pub mod tags;
+pub mod tree;
+pub mod toe_number;
+pub mod display_name;
+mod country_data;
 
 pub use library::{Library, Unit, Equipment, Personnel};
 pub use version::{Versioned, Snapshot};
 pub use formation_level::{StandardFormationLevel, CustomFormationLevel, STANDARD_LEVEL_COUNT};
 pub use branch::{Branch, BranchCategory, default_branches, default_branch_categories};
-pub use validation::{ValidationError, validate_library, validate_branch, validate_formation_level};
\ No newline at end of file
+pub use equipment_catalog::{EquipmentCatalogEntry, EquipmentCategory, EquipmentAliases, AliasCollision, equipment_name_key, merge_catalog_entries, parse_aliases, format_aliases};
+pub use provenance::{CopiedData, CopyRecord};
+pub use validation::{ValidationError, LibraryFieldErrors, validate_library, validate_library_fields, validate_branch, validate_formation_level, validate_formation_level_set, FormationLevelSetIssues, validate_personnel_range};
+pub use country::{Country, countries, country_by_code, resolve_country_code, suggest_countries};
+pub use effective_dates::{parse_date, format_date, parse_effective_dates, is_effective_at, units_as_of};
+pub use numbering::{NumberingStyle, NumberingOptions, NumberingNode, compute_prefixes, format_ordinal, number_units, unit_prefixes};
+pub use tags::{TagState, tag_states, tag_changes, apply_tag_changes, normalize_tag, normalize_tags};
+pub use toe_number::{normalize_toe_number, toe_number_key, exact_toe_match, find_toe_collision, ToeNumberCollision};
+pub use display_name::{display_name, DisplayLang};
+pub use tree::{Visit, TreeWalk, walk_units, for_each_unit_mut, assemble_forest, TreeShape, tree_shape, TreeLimits, TreeLimitIssue, DEFAULT_MAX_TREE_DEPTH, MAX_TREE_DEPTH};
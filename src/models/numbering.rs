@@ -0,0 +1,393 @@
+//! Automatic formation numbering (ordinal of a formation among its siblings)
+//!
+//! The ordinal is never stored in the unit name; it is derived from the sibling
+//! order and formatted according to the selected style. The options are kept
+//! with each library ([`super::Library::numbering`]).
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+
+use super::Unit;
+
+/// Style used to render an ordinal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberingStyle {
+    /// 1, 2, 3 …
+    Arabic,
+    /// I, II, III …
+    Roman,
+    /// A, B, C … Z, AA, AB …
+    Letters,
+}
+
+impl NumberingStyle {
+    /// Style code used in settings and UI callbacks
+    pub fn code(&self) -> &'static str {
+        match self {
+            NumberingStyle::Arabic => "arabic",
+            NumberingStyle::Roman => "roman",
+            NumberingStyle::Letters => "letters",
+        }
+    }
+
+    /// Parse style from code
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.trim().to_lowercase().as_str() {
+            "arabic" => Some(NumberingStyle::Arabic),
+            "roman" => Some(NumberingStyle::Roman),
+            "letters" => Some(NumberingStyle::Letters),
+            _ => None,
+        }
+    }
+}
+
+/// Numbering display options of a library
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NumberingOptions {
+    /// Whether prefixes are shown at all
+    pub enabled: bool,
+    /// Default style for all levels
+    pub style: NumberingStyle,
+    /// Per-level overrides keyed by formation level id, e.g. battalions -> Roman
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub level_overrides: BTreeMap<i64, NumberingStyle>,
+}
+
+impl Default for NumberingOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            style: NumberingStyle::Arabic,
+            level_overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl NumberingOptions {
+    /// Enabled options with the given default style
+    pub fn with_style(style: NumberingStyle) -> Self {
+        Self {
+            enabled: true,
+            style,
+            level_overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Whether these are the options of a library that never set any
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Move the overrides to the new ids of their formation levels, dropping
+    /// those of levels `level_ids` does not map
+    pub fn remap_levels(&mut self, level_ids: &HashMap<i64, i64>) {
+        self.level_overrides = std::mem::take(&mut self.level_overrides)
+            .into_iter()
+            .filter_map(|(old, style)| level_ids.get(&old).map(|&new| (new, style)))
+            .collect();
+    }
+
+    /// Add a per-level override
+    pub fn set_override(&mut self, level_id: i64, style: NumberingStyle) {
+        self.level_overrides.insert(level_id, style);
+    }
+
+    /// Style to use for a unit of the given formation level
+    pub fn style_for(&self, level_id: Option<i64>) -> NumberingStyle {
+        level_id
+            .and_then(|id| self.level_overrides.get(&id))
+            .copied()
+            .unwrap_or(self.style)
+    }
+
+    /// Display prefix ("1. ", "II. ", "B. ") of the `ordinal`-th unit of a level
+    pub fn prefix(&self, ordinal: usize, level_id: Option<i64>) -> String {
+        format!("{}. ", format_ordinal(ordinal, self.style_for(level_id)))
+    }
+}
+
+/// Minimal view of a unit needed to derive its ordinal
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberingNode {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    /// Explicit position among siblings; gaps are allowed
+    pub sort_order: Option<i64>,
+    /// Formation level, for per-level overrides
+    pub level_id: Option<i64>,
+}
+
+/// Convert a number to Roman numerals (1..=3999), None outside that range.
+pub fn to_roman(n: usize) -> Option<String> {
+    if n == 0 || n > 3999 {
+        return None;
+    }
+    const TABLE: [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"),
+        (100, "C"), (90, "XC"), (50, "L"), (40, "XL"),
+        (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut rest = n;
+    let mut out = String::new();
+    for (value, symbol) in TABLE {
+        while rest >= value {
+            out.push_str(symbol);
+            rest -= value;
+        }
+    }
+    Some(out)
+}
+
+/// Convert a number to spreadsheet-style letters (1 = A, 26 = Z, 27 = AA), None for 0.
+pub fn to_letters(n: usize) -> Option<String> {
+    if n == 0 {
+        return None;
+    }
+    let mut rest = n;
+    let mut out = Vec::new();
+    while rest > 0 {
+        rest -= 1;
+        out.push((b'A' + (rest % 26) as u8) as char);
+        rest /= 26;
+    }
+    Some(out.into_iter().rev().collect())
+}
+
+/// Format an ordinal in the given style, falling back to arabic when the style cannot express it.
+pub fn format_ordinal(n: usize, style: NumberingStyle) -> String {
+    let formatted = match style {
+        NumberingStyle::Arabic => None,
+        NumberingStyle::Roman => to_roman(n),
+        NumberingStyle::Letters => to_letters(n),
+    };
+    formatted.unwrap_or_else(|| n.to_string())
+}
+
+/// Derive the 1-based ordinal of each node among its siblings.
+///
+/// Siblings are ordered by `sort_order` (gaps are ignored), nodes without a
+/// `sort_order` come after those with one, and ties keep the input order.
+pub fn sibling_ordinals(nodes: &[NumberingNode]) -> HashMap<i64, usize> {
+    let mut groups: HashMap<Option<i64>, Vec<(usize, &NumberingNode)>> = HashMap::new();
+    for (pos, node) in nodes.iter().enumerate() {
+        groups.entry(node.parent_id).or_default().push((pos, node));
+    }
+    let mut ordinals = HashMap::new();
+    for siblings in groups.values_mut() {
+        siblings.sort_by_key(|(pos, n)| (n.sort_order.is_none(), n.sort_order, *pos));
+        for (i, (_, node)) in siblings.iter().enumerate() {
+            ordinals.insert(node.id, i + 1);
+        }
+    }
+    ordinals
+}
+
+/// Compute display prefixes ("1. ", "II. ", "B. ") for every node; empty map when disabled.
+pub fn compute_prefixes(nodes: &[NumberingNode], options: &NumberingOptions) -> HashMap<i64, String> {
+    if !options.enabled {
+        return HashMap::new();
+    }
+    let ordinals = sibling_ordinals(nodes);
+    nodes
+        .iter()
+        .filter_map(|node| {
+            ordinals
+                .get(&node.id)
+                .map(|&ordinal| (node.id, options.prefix(ordinal, node.level_id)))
+        })
+        .collect()
+}
+
+/// Prefixes of the units of `units` and below keyed by unit id, each unit
+/// counted among its siblings in tree order; empty map when disabled.
+/// Units without an id get none.
+pub fn unit_prefixes(units: &[Unit], options: &NumberingOptions) -> HashMap<i64, String> {
+    let mut prefixes = HashMap::new();
+    if !options.enabled {
+        return prefixes;
+    }
+    let mut pending = vec![units];
+    while let Some(siblings) = pending.pop() {
+        for (i, unit) in siblings.iter().enumerate() {
+            if let Some(id) = unit.id {
+                prefixes.insert(id, options.prefix(i + 1, unit.formation_level_id));
+            }
+            pending.push(&unit.children);
+        }
+    }
+    prefixes
+}
+
+/// Prefix the name of every unit of `units` and below with its ordinal among
+/// its siblings in tree order, for views and exports; no-op when disabled
+pub fn number_units(units: &mut [Unit], options: &NumberingOptions) {
+    if !options.enabled {
+        return;
+    }
+    let mut pending = vec![units];
+    while let Some(siblings) = pending.pop() {
+        for (i, unit) in siblings.iter_mut().enumerate() {
+            unit.name = format!("{}{}", options.prefix(i + 1, unit.formation_level_id), unit.name);
+            pending.push(&mut unit.children);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BRIGADE: i64 = 10;
+    const BATTALION: i64 = 11;
+    const COMPANY: i64 = 12;
+
+    fn node(id: i64, parent_id: Option<i64>, sort_order: Option<i64>, level_id: i64) -> NumberingNode {
+        NumberingNode {
+            id,
+            parent_id,
+            sort_order,
+            level_id: Some(level_id),
+        }
+    }
+
+    fn unit(id: i64, level_id: Option<i64>, children: Vec<Unit>) -> Unit {
+        let mut unit = Unit::new(format!("U{}", id), String::new());
+        unit.id = Some(id);
+        unit.formation_level_id = level_id;
+        unit.children = children;
+        unit
+    }
+
+    #[test]
+    fn test_to_roman() {
+        assert_eq!(to_roman(1).as_deref(), Some("I"));
+        assert_eq!(to_roman(4).as_deref(), Some("IV"));
+        assert_eq!(to_roman(14).as_deref(), Some("XIV"));
+        assert_eq!(to_roman(1994).as_deref(), Some("MCMXCIV"));
+        assert_eq!(to_roman(0), None);
+        assert_eq!(to_roman(4000), None);
+    }
+
+    #[test]
+    fn test_to_letters() {
+        assert_eq!(to_letters(1).as_deref(), Some("A"));
+        assert_eq!(to_letters(26).as_deref(), Some("Z"));
+        assert_eq!(to_letters(27).as_deref(), Some("AA"));
+        assert_eq!(to_letters(52).as_deref(), Some("AZ"));
+        assert_eq!(to_letters(0), None);
+    }
+
+    #[test]
+    fn test_format_ordinal_style_fallback() {
+        assert_eq!(format_ordinal(3, NumberingStyle::Arabic), "3");
+        assert_eq!(format_ordinal(3, NumberingStyle::Roman), "III");
+        assert_eq!(format_ordinal(5000, NumberingStyle::Roman), "5000");
+        assert_eq!(format_ordinal(0, NumberingStyle::Letters), "0");
+    }
+
+    #[test]
+    fn test_sibling_ordinals_with_gaps() {
+        let nodes = vec![
+            node(1, None, Some(0), BRIGADE),
+            node(2, Some(1), Some(50), BATTALION),
+            node(3, Some(1), Some(10), BATTALION),
+            node(4, Some(1), Some(20), BATTALION),
+        ];
+        let ord = sibling_ordinals(&nodes);
+        assert_eq!(ord[&1], 1);
+        assert_eq!(ord[&3], 1);
+        assert_eq!(ord[&4], 2);
+        assert_eq!(ord[&2], 3);
+    }
+
+    #[test]
+    fn test_sibling_ordinals_missing_sort_order_goes_last() {
+        let nodes = vec![
+            node(1, Some(9), None, COMPANY),
+            node(2, Some(9), Some(5), COMPANY),
+            node(3, Some(9), None, COMPANY),
+        ];
+        let ord = sibling_ordinals(&nodes);
+        assert_eq!(ord[&2], 1);
+        assert_eq!(ord[&1], 2);
+        assert_eq!(ord[&3], 3);
+    }
+
+    #[test]
+    fn test_compute_prefixes_with_level_overrides() {
+        let nodes = vec![
+            node(1, None, Some(1), BRIGADE),
+            node(2, Some(1), Some(1), BATTALION),
+            node(3, Some(1), Some(2), BATTALION),
+            node(4, Some(3), Some(1), COMPANY),
+            node(5, Some(3), Some(2), COMPANY),
+        ];
+        let mut opts = NumberingOptions::with_style(NumberingStyle::Arabic);
+        opts.set_override(BATTALION, NumberingStyle::Roman);
+        opts.set_override(COMPANY, NumberingStyle::Letters);
+        let prefixes = compute_prefixes(&nodes, &opts);
+        assert_eq!(prefixes[&1], "1. ");
+        assert_eq!(prefixes[&2], "I. ");
+        assert_eq!(prefixes[&3], "II. ");
+        assert_eq!(prefixes[&4], "A. ");
+        assert_eq!(prefixes[&5], "B. ");
+    }
+
+    #[test]
+    fn test_number_units_follows_tree_order_and_levels() {
+        let mut units = vec![
+            unit(1, Some(BRIGADE), vec![
+                unit(2, Some(BATTALION), vec![unit(4, Some(COMPANY), vec![]), unit(5, None, vec![])]),
+                unit(3, Some(BATTALION), vec![]),
+            ]),
+        ];
+        let mut opts = NumberingOptions::with_style(NumberingStyle::Arabic);
+        opts.set_override(BATTALION, NumberingStyle::Roman);
+        opts.set_override(COMPANY, NumberingStyle::Letters);
+        let prefixes = unit_prefixes(&units, &opts);
+        assert_eq!(prefixes[&3], "II. ");
+        assert_eq!(prefixes[&5], "2. ");
+
+        number_units(&mut units, &opts);
+        let battalions = &units[0].children;
+        assert_eq!(units[0].name, "1. U1");
+        assert_eq!(battalions[0].name, "I. U2");
+        assert_eq!(battalions[1].name, "II. U3");
+        assert_eq!(battalions[0].children[0].name, "A. U4");
+        assert_eq!(battalions[0].children[1].name, "2. U5");
+
+        let mut plain = vec![unit(1, None, vec![])];
+        number_units(&mut plain, &NumberingOptions::default());
+        assert_eq!(plain[0].name, "U1");
+    }
+
+    #[test]
+    fn test_compute_prefixes_disabled() {
+        let nodes = vec![node(1, None, Some(1), BRIGADE)];
+        assert!(compute_prefixes(&nodes, &NumberingOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_options_round_trip_and_remap_levels() {
+        let mut opts = NumberingOptions::with_style(NumberingStyle::Letters);
+        opts.set_override(BATTALION, NumberingStyle::Roman);
+        opts.set_override(COMPANY, NumberingStyle::Arabic);
+        let json = serde_json::to_value(&opts).unwrap();
+        assert_eq!(json["style"], "letters");
+        assert_eq!(json["level_overrides"]["11"], "roman");
+        assert_eq!(serde_json::from_value::<NumberingOptions>(json).unwrap(), opts);
+        assert_eq!(serde_json::from_str::<NumberingOptions>("{}").unwrap(), NumberingOptions::default());
+
+        opts.remap_levels(&HashMap::from([(BATTALION, 21)]));
+        assert_eq!(opts.level_overrides, BTreeMap::from([(21, NumberingStyle::Roman)]));
+    }
+
+    #[test]
+    fn test_style_from_code() {
+        assert_eq!(NumberingStyle::from_code("Roman"), Some(NumberingStyle::Roman));
+        assert_eq!(NumberingStyle::from_code("letters"), Some(NumberingStyle::Letters));
+        assert_eq!(NumberingStyle::from_code("off"), None);
+    }
+}
@@ -0,0 +1,70 @@
+//! Where a library's lists were copied from (copy-from-library provenance).
+
+use serde::{Deserialize, Serialize};
+
+/// List copied from one library into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopiedData {
+    Branches,
+    BranchCategories,
+    FormationLevels,
+    EquipmentCatalog,
+}
+
+impl CopiedData {
+    pub const ALL: [CopiedData; 4] = [
+        CopiedData::Branches,
+        CopiedData::BranchCategories,
+        CopiedData::FormationLevels,
+        CopiedData::EquipmentCatalog,
+    ];
+
+    /// Code stored in the database
+    pub fn code(self) -> &'static str {
+        match self {
+            CopiedData::Branches => "branches",
+            CopiedData::BranchCategories => "branch_categories",
+            CopiedData::FormationLevels => "formation_levels",
+            CopiedData::EquipmentCatalog => "equipment_catalog",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.code() == code)
+    }
+
+    /// English name in lower case for use inside a sentence, also the translation key
+    pub fn label(self) -> &'static str {
+        match self {
+            CopiedData::Branches => "branches",
+            CopiedData::BranchCategories => "branch categories",
+            CopiedData::FormationLevels => "formation levels",
+            CopiedData::EquipmentCatalog => "equipment catalog",
+        }
+    }
+}
+
+/// One copy of a list from `source_library_id` into `target_library_id`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyRecord {
+    pub id: Option<i64>,
+    pub target_library_id: i64,
+    pub source_library_id: i64,
+    pub what: CopiedData,
+    /// Unix time of the copy
+    pub copied_at: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copied_data_codes_round_trip() {
+        for what in CopiedData::ALL {
+            assert_eq!(CopiedData::from_code(what.code()), Some(what));
+        }
+        assert_eq!(CopiedData::from_code("units"), None);
+    }
+}
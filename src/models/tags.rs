@@ -0,0 +1,166 @@
+//! Tag normalization and tag edits across several libraries at once
+
+/// How a tag is set on a group of libraries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagState {
+    /// On none of them
+    None,
+    /// On some of them
+    Some,
+    /// On all of them
+    All,
+}
+
+impl TagState {
+    /// Next state when the user clicks a tag. "Some" is only offered for tags
+    /// that started out mixed, so it can be restored after a mis-click.
+    pub fn cycle(self, initial: TagState) -> TagState {
+        match (self, initial) {
+            (TagState::None, _) => TagState::All,
+            (TagState::All, TagState::Some) => TagState::Some,
+            (TagState::All, _) => TagState::None,
+            (TagState::Some, _) => TagState::All,
+        }
+    }
+}
+
+/// Every tag used by the given libraries with how many of them carry it,
+/// sorted case-insensitively. Tags on none of them are not listed.
+pub fn tag_states(tag_sets: &[Vec<String>]) -> Vec<(String, TagState)> {
+    let mut tags: Vec<&String> = tag_sets.iter().flatten().collect();
+    tags.sort_by_key(|t| (t.to_lowercase(), t.as_str()));
+    tags.dedup();
+    tags.into_iter()
+        .map(|tag| {
+            let count = tag_sets.iter().filter(|set| set.contains(tag)).count();
+            let state = if count == tag_sets.len() { TagState::All } else { TagState::Some };
+            (tag.clone(), state)
+        })
+        .collect()
+}
+
+/// Tags to add to and remove from every library, from the states shown when
+/// the dialog opened and the states chosen. Tags left mixed are untouched.
+pub fn tag_changes(initial: &[(String, TagState)], chosen: &[(String, TagState)]) -> (Vec<String>, Vec<String>) {
+    let initial_state = |tag: &str| {
+        initial
+            .iter()
+            .find(|(t, _)| t == tag)
+            .map(|(_, s)| *s)
+            .unwrap_or(TagState::None)
+    };
+    let mut add = Vec::new();
+    let mut remove = Vec::new();
+    for (tag, state) in chosen {
+        if *state == initial_state(tag) {
+            continue;
+        }
+        match state {
+            TagState::All => add.push(tag.clone()),
+            TagState::None => remove.push(tag.clone()),
+            TagState::Some => {}
+        }
+    }
+    (add, remove)
+}
+
+/// `tags` with `remove` dropped and missing `add` tags appended; existing
+/// order is kept. `None` when nothing would change.
+pub fn apply_tag_changes(tags: &[String], add: &[String], remove: &[String]) -> Option<Vec<String>> {
+    let mut updated: Vec<String> = tags.iter().filter(|t| !remove.contains(t)).cloned().collect();
+    for tag in add {
+        if !updated.contains(tag) {
+            updated.push(tag.clone());
+        }
+    }
+    (updated != tags).then_some(updated)
+}
+
+/// A tag with surrounding whitespace trimmed and inner runs of whitespace
+/// collapsed to a single space
+pub fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Normalized tags without empty ones and without duplicates that differ only
+/// in case; the first spelling seen is kept, in the original order
+pub fn normalize_tags<I, S>(tags: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut seen = std::collections::HashSet::new();
+    tags.into_iter()
+        .map(|t| normalize_tag(t.as_ref()))
+        .filter(|t| !t.is_empty() && seen.insert(t.to_lowercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tag_states_from_selection() {
+        let states = tag_states(&[set(&["ww2", "infantry"]), set(&["ww2", "Armor"]), set(&["ww2"])]);
+        assert_eq!(
+            states,
+            [
+                ("Armor".to_string(), TagState::Some),
+                ("infantry".to_string(), TagState::Some),
+                ("ww2".to_string(), TagState::All),
+            ]
+        );
+        assert!(tag_states(&[set(&[]), set(&[])]).is_empty());
+    }
+
+    #[test]
+    fn test_cycle_offers_some_only_for_mixed_tags() {
+        assert_eq!(TagState::All.cycle(TagState::All), TagState::None);
+        assert_eq!(TagState::None.cycle(TagState::All), TagState::All);
+        assert_eq!(TagState::Some.cycle(TagState::Some), TagState::All);
+        assert_eq!(TagState::All.cycle(TagState::Some), TagState::Some);
+    }
+
+    #[test]
+    fn test_changes_only_for_tags_that_moved() {
+        let initial = vec![
+            ("a".to_string(), TagState::All),
+            ("b".to_string(), TagState::Some),
+            ("c".to_string(), TagState::Some),
+        ];
+        let chosen = vec![
+            ("a".to_string(), TagState::None),
+            ("b".to_string(), TagState::Some),
+            ("c".to_string(), TagState::All),
+            ("new".to_string(), TagState::All),
+            ("unused".to_string(), TagState::None),
+        ];
+        let (add, remove) = tag_changes(&initial, &chosen);
+        assert_eq!(add, set(&["c", "new"]));
+        assert_eq!(remove, set(&["a"]));
+    }
+
+    #[test]
+    fn test_apply_keeps_order_and_reports_no_op() {
+        let tags = set(&["x", "y", "z"]);
+        assert_eq!(apply_tag_changes(&tags, &set(&["w", "x"]), &set(&["y"])), Some(set(&["x", "z", "w"])));
+        assert_eq!(apply_tag_changes(&tags, &set(&["x"]), &set(&["missing"])), None);
+    }
+
+    #[test]
+    fn test_normalize_tag_trims_and_collapses_spaces() {
+        assert_eq!(normalize_tag("  cold   war\t era "), "cold war era");
+        assert_eq!(normalize_tag("   "), "");
+    }
+
+    #[test]
+    fn test_normalize_tags_dedups_case_insensitively_keeping_first_spelling() {
+        let tags = normalize_tags(["WW2", " infantry", "ww2", "", "Infantry ", "Армия", "армия", "cold  war", "Cold War"]);
+        assert_eq!(tags, set(&["WW2", "infantry", "Армия", "cold war"]));
+    }
+}
@@ -0,0 +1,107 @@
+//! TOE numbers: document numbers of official tables of organization and
+//! equipment, such as "TOE 07015L000" or "MTOE 07245H600".
+//!
+//! Numbers are stored as entered (trimmed) but compared by [`toe_number_key`],
+//! so case and spacing never make two numbers differ.
+
+use std::fmt;
+
+/// Stored form of an entered TOE number: trimmed, inner runs of whitespace
+/// collapsed to one space; `None` when blank
+pub fn normalize_toe_number(text: &str) -> Option<String> {
+    let number = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!number.is_empty()).then_some(number)
+}
+
+/// Key TOE numbers are compared by: uppercased with all whitespace removed,
+/// so "toe 07015l000" matches "TOE07015L000"
+pub fn toe_number_key(number: &str) -> String {
+    number.split_whitespace().collect::<String>().to_uppercase()
+}
+
+/// The one entry of `numbers` whose TOE number matches `query` exactly once
+/// both are normalized. `None` when the query is blank, nothing matches or
+/// several entries share the number.
+pub fn exact_toe_match<'a, T>(numbers: impl IntoIterator<Item = (T, Option<&'a str>)>, query: &str) -> Option<T> {
+    let key = toe_number_key(query);
+    if key.is_empty() {
+        return None;
+    }
+    let mut matches = numbers
+        .into_iter()
+        .filter(|(_, number)| number.is_some_and(|n| toe_number_key(n) == key));
+    let (found, _) = matches.next()?;
+    matches.next().is_none().then_some(found)
+}
+
+/// A TOE number already used by another unit of the same library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToeNumberCollision {
+    /// The number as the other unit spells it
+    pub number: String,
+    pub unit_id: i64,
+    pub unit_name: String,
+}
+
+impl fmt::Display for ToeNumberCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TOE number {} is already used by \"{}\"", self.number, self.unit_name)
+    }
+}
+
+impl std::error::Error for ToeNumberCollision {}
+
+/// First unit other than `unit_id` in `existing` (id, name, TOE number) whose
+/// number matches `number`
+pub fn find_toe_collision(
+    existing: &[(i64, String, String)],
+    unit_id: Option<i64>,
+    number: &str,
+) -> Option<ToeNumberCollision> {
+    let key = toe_number_key(number);
+    if key.is_empty() {
+        return None;
+    }
+    existing
+        .iter()
+        .find(|(id, _, other)| Some(*id) != unit_id && toe_number_key(other) == key)
+        .map(|(id, name, other)| ToeNumberCollision {
+            number: other.clone(),
+            unit_id: *id,
+            unit_name: name.clone(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_key() {
+        assert_eq!(normalize_toe_number("  TOE   07015L000 "), Some("TOE 07015L000".to_string()));
+        assert_eq!(normalize_toe_number(" \t"), None);
+        assert_eq!(toe_number_key(" toe 07015l000"), "TOE07015L000");
+        assert_eq!(toe_number_key("TOE07015L000"), toe_number_key("Toe 0701 5L000"));
+    }
+
+    #[test]
+    fn test_exact_match_needs_one_unit() {
+        let numbers = [(1, Some("TOE 07015L000")), (2, None), (3, Some("MTOE 07245H600")), (4, Some("mtoe07245h600"))];
+        assert_eq!(exact_toe_match(numbers, " toe07015l000 "), Some(1));
+        // A prefix is not an exact match
+        assert_eq!(exact_toe_match(numbers, "TOE 07015"), None);
+        // Shared numbers do not jump anywhere
+        assert_eq!(exact_toe_match(numbers, "MTOE 07245H600"), None);
+        assert_eq!(exact_toe_match(numbers, "  "), None);
+    }
+
+    #[test]
+    fn test_collision_ignores_the_unit_itself() {
+        let existing = vec![(1, "1st Battalion".to_string(), "TOE 07015L000".to_string())];
+        let collision = find_toe_collision(&existing, Some(2), "toe  07015l000").unwrap();
+        assert_eq!(collision.unit_id, 1);
+        assert_eq!(collision.to_string(), "TOE number TOE 07015L000 is already used by \"1st Battalion\"");
+        assert_eq!(find_toe_collision(&existing, Some(1), "TOE 07015L000"), None);
+        assert_eq!(find_toe_collision(&existing, None, "TOE 07015L001"), None);
+    }
+}
@@ -0,0 +1,392 @@
+//! Walking unit trees without recursion, and limits on their shape
+//!
+//! Trees come from files and spreadsheets as well as the editor, so their
+//! depth is not bounded by anything but [`TreeLimits`]. Code that visits every
+//! unit uses [`walk_units`] (or [`TreeWalk`] for other tree types) and builds
+//! trees with [`assemble_forest`], keeping the call stack flat however deep
+//! the tree is.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use super::library::Unit;
+
+/// A unit reached by a [`TreeWalk`]
+pub struct Visit<'a, T> {
+    pub unit: &'a T,
+    /// 0 for the units the walk started from
+    pub depth: usize,
+    /// Position of the parent's visit in the walk; `None` for the starting units
+    pub parent: Option<usize>,
+}
+
+/// Depth-first walk visiting every unit before its children, siblings in
+/// order, with an explicit stack
+pub struct TreeWalk<'a, T> {
+    stack: Vec<(&'a T, usize, Option<usize>)>,
+    next: usize,
+    children: fn(&T) -> &[T],
+}
+
+impl<'a, T> TreeWalk<'a, T> {
+    /// Walk `roots` and everything below them; `children` gives a unit's subordinates
+    pub fn new(roots: &'a [T], children: fn(&T) -> &[T]) -> Self {
+        Self {
+            stack: roots.iter().rev().map(|unit| (unit, 0, None)).collect(),
+            next: 0,
+            children,
+        }
+    }
+}
+
+impl<'a, T> Iterator for TreeWalk<'a, T> {
+    type Item = Visit<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (unit, depth, parent) = self.stack.pop()?;
+        let index = self.next;
+        self.next += 1;
+        let children: &'a [T] = (self.children)(unit);
+        self.stack.extend(children.iter().rev().map(|child| (child, depth + 1, Some(index))));
+        Some(Visit { unit, depth, parent })
+    }
+}
+
+fn unit_children(unit: &Unit) -> &[Unit] {
+    &unit.children
+}
+
+/// Every unit of `units` and below, parents before their children
+pub fn walk_units(units: &[Unit]) -> TreeWalk<'_, Unit> {
+    TreeWalk::new(units, unit_children)
+}
+
+/// Run `f` on every unit of `units` and below, parents before their children
+pub fn for_each_unit_mut(units: &mut [Unit], mut f: impl FnMut(&mut Unit)) {
+    let mut pending: Vec<&mut Unit> = units.iter_mut().rev().collect();
+    while let Some(unit) = pending.pop() {
+        f(unit);
+        pending.extend(unit.children.iter_mut().rev());
+    }
+}
+
+/// Build a forest from `nodes`, each with the position of its parent in
+/// `nodes` (`None` for the roots). Children keep their order in `nodes`;
+/// `attach` hands a node its children. Nodes whose parent chain never
+/// reaches a root (a parent cycle) are left out.
+pub fn assemble_forest<T>(nodes: Vec<(T, Option<usize>)>, mut attach: impl FnMut(&mut T, Vec<T>)) -> Vec<T> {
+    let count = nodes.len();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut roots = Vec::new();
+    for (i, (_, parent)) in nodes.iter().enumerate() {
+        match *parent {
+            Some(p) if p < count && p != i => children[p].push(i),
+            _ => roots.push(i),
+        }
+    }
+
+    // Parents before children, so going backwards finishes every subtree
+    // before the unit it hangs from
+    let mut order = Vec::with_capacity(count);
+    let mut visited = vec![false; count];
+    let mut stack: Vec<usize> = roots.iter().rev().copied().collect();
+    while let Some(i) = stack.pop() {
+        if std::mem::replace(&mut visited[i], true) {
+            continue;
+        }
+        order.push(i);
+        stack.extend(children[i].iter().rev());
+    }
+
+    let mut pending: Vec<Option<T>> = nodes.into_iter().map(|(node, _)| Some(node)).collect();
+    let mut built: Vec<Option<T>> = (0..count).map(|_| None).collect();
+    for &i in order.iter().rev() {
+        let Some(mut node) = pending[i].take() else {
+            continue;
+        };
+        attach(&mut node, children[i].iter().filter_map(|&c| built[c].take()).collect());
+        built[i] = Some(node);
+    }
+    roots.iter().filter_map(|&r| built[r].take()).collect()
+}
+
+impl Unit {
+    /// Copy of the unit without its subordinates
+    pub fn without_children(&self) -> Unit {
+        Unit {
+            id: self.id,
+            name: self.name.clone(),
+            unit_type: self.unit_type.clone(),
+            parent_id: self.parent_id,
+            personnel: self.personnel.clone(),
+            equipment: self.equipment.clone(),
+            children: Vec::new(),
+            effective_from: self.effective_from.clone(),
+            effective_to: self.effective_to.clone(),
+            toe_number: self.toe_number.clone(),
+            formation_level_id: self.formation_level_id,
+        }
+    }
+}
+
+/// How deep and how wide a unit tree is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TreeShape {
+    /// Levels, counting the top one (0 for no units)
+    pub depth: usize,
+    /// Most units directly under one parent, top-level units counted as one group
+    pub widest: usize,
+}
+
+impl TreeShape {
+    /// One unit without subordinates
+    pub const SINGLE: TreeShape = TreeShape { depth: 1, widest: 1 };
+
+    /// Shape of this tree hung under a unit `levels_above` levels deep that
+    /// then has `siblings` direct subordinates, this tree's top included
+    pub fn placed_under(self, levels_above: usize, siblings: usize) -> TreeShape {
+        TreeShape {
+            depth: levels_above + self.depth.max(1),
+            widest: self.widest.max(siblings),
+        }
+    }
+}
+
+/// Shape of `units` and everything below them
+pub fn tree_shape(units: &[Unit]) -> TreeShape {
+    walk_units(units).fold(
+        TreeShape { depth: 0, widest: units.len() },
+        |shape, visit| TreeShape {
+            depth: shape.depth.max(visit.depth + 1),
+            widest: shape.widest.max(visit.unit.children.len()),
+        },
+    )
+}
+
+/// A unit tree going past one of the [`TreeLimits`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeLimitIssue {
+    TooDeep { depth: usize, limit: usize },
+    TooWide { children: usize, limit: usize },
+}
+
+impl fmt::Display for TreeLimitIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeLimitIssue::TooDeep { depth, limit } => {
+                write!(f, "The unit tree would be {} levels deep; the limit is {}", depth, limit)
+            }
+            TreeLimitIssue::TooWide { children, limit } => {
+                write!(f, "A unit would have {} direct subordinates; the limit is {}", children, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TreeLimitIssue {}
+
+/// Deepest unit tree any [`TreeLimits`] allow: a library file holding a
+/// deeper one nests past what the JSON and YAML parsers read back (see
+/// [`crate::import::limits::PARSER_MAX_DEPTH`])
+pub const MAX_TREE_DEPTH: usize = 60;
+
+/// Default hard limit on unit tree depth
+pub const DEFAULT_MAX_TREE_DEPTH: usize = MAX_TREE_DEPTH;
+
+/// Soft limits warn about a unit tree, hard ones refuse it when units are
+/// created, imported or moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeLimits {
+    pub warn_depth: usize,
+    pub max_depth: usize,
+    pub warn_children: usize,
+    pub max_children: usize,
+    /// Direct subordinates the formations sidebar lists before folding the
+    /// rest behind a "show all" row
+    pub sidebar_children: usize,
+}
+
+impl Default for TreeLimits {
+    fn default() -> Self {
+        Self {
+            warn_depth: 12,
+            max_depth: DEFAULT_MAX_TREE_DEPTH,
+            warn_children: 200,
+            max_children: 2000,
+            sidebar_children: 50,
+        }
+    }
+}
+
+impl TreeLimits {
+    /// The hard depth limit in effect: `max_depth`, but never past
+    /// [`MAX_TREE_DEPTH`], so every tree allowed can be exported and read back
+    pub fn depth_limit(&self) -> usize {
+        self.max_depth.min(MAX_TREE_DEPTH)
+    }
+
+    /// The soft limits a tree of `shape` goes past, or the first hard limit
+    /// it goes past as the error
+    pub fn check(&self, shape: TreeShape) -> Result<Vec<TreeLimitIssue>, TreeLimitIssue> {
+        if shape.depth > self.depth_limit() {
+            return Err(TreeLimitIssue::TooDeep { depth: shape.depth, limit: self.depth_limit() });
+        }
+        if shape.widest > self.max_children {
+            return Err(TreeLimitIssue::TooWide { children: shape.widest, limit: self.max_children });
+        }
+        let mut warnings = Vec::new();
+        if shape.depth > self.warn_depth {
+            warnings.push(TreeLimitIssue::TooDeep { depth: shape.depth, limit: self.warn_depth });
+        }
+        if shape.widest > self.warn_children {
+            warnings.push(TreeLimitIssue::TooWide { children: shape.widest, limit: self.warn_children });
+        }
+        Ok(warnings)
+    }
+}
+
+/// Test fixture: `depth` units named U0, U1, …, each the only subordinate of
+/// the one before
+#[cfg(test)]
+pub(crate) fn unit_chain(depth: usize) -> Vec<Unit> {
+    unit_chain_with(depth, |i| Unit::new(format!("U{}", i), "unit".to_string()))
+}
+
+/// Test fixture: like [`unit_chain`], with the unit at each depth made by `unit`
+#[cfg(test)]
+pub(crate) fn unit_chain_with(depth: usize, unit: impl FnMut(usize) -> Unit) -> Vec<Unit> {
+    let nodes = (0..depth).map(unit).enumerate().map(|(i, u)| (u, i.checked_sub(1))).collect();
+    assemble_forest(nodes, |unit, children| unit.children = children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One unit with `width` direct subordinates
+    fn fan(width: usize) -> Vec<Unit> {
+        let mut root = Unit::new("Root".to_string(), "unit".to_string());
+        root.children = (0..width).map(|i| Unit::new(format!("C{}", i), "unit".to_string())).collect();
+        vec![root]
+    }
+
+    #[test]
+    fn test_walk_is_preorder_with_parents() {
+        let mut a = Unit::new("A".to_string(), String::new());
+        let mut b = Unit::new("B".to_string(), String::new());
+        b.children.push(Unit::new("B1".to_string(), String::new()));
+        a.children = vec![b, Unit::new("C".to_string(), String::new())];
+        let forest = vec![a, Unit::new("D".to_string(), String::new())];
+
+        let visits: Vec<(&str, usize, Option<usize>)> =
+            walk_units(&forest).map(|v| (v.unit.name.as_str(), v.depth, v.parent)).collect();
+        assert_eq!(
+            visits,
+            [("A", 0, None), ("B", 1, Some(0)), ("B1", 2, Some(1)), ("C", 1, Some(0)), ("D", 0, None)]
+        );
+    }
+
+    #[test]
+    fn test_deep_chain_walks_without_recursion() {
+        let forest = unit_chain(5000);
+        assert_eq!(walk_units(&forest).count(), 5000);
+        assert_eq!(walk_units(&forest).last().map(|v| v.depth), Some(4999));
+        assert_eq!(tree_shape(&forest), TreeShape { depth: 5000, widest: 1 });
+        // Taken apart level by level: dropping it whole would recurse
+        let mut next = forest;
+        while let Some(mut unit) = next.pop() {
+            next = std::mem::take(&mut unit.children);
+        }
+    }
+
+    #[test]
+    fn test_assemble_keeps_order_and_drops_cycles() {
+        let unit = |name: &str| Unit::new(name.to_string(), String::new());
+        let nodes = vec![
+            (unit("Child 1"), Some(2)),
+            (unit("Loop A"), Some(4)),
+            (unit("Root"), None),
+            (unit("Child 2"), Some(2)),
+            (unit("Loop B"), Some(1)),
+            (unit("Grandchild"), Some(3)),
+            (unit("Self"), Some(6)),
+        ];
+        let forest = assemble_forest(nodes, |unit, children| unit.children = children);
+        let names: Vec<(&str, usize)> = walk_units(&forest).map(|v| (v.unit.name.as_str(), v.depth)).collect();
+        assert_eq!(names, [("Root", 0), ("Child 1", 1), ("Child 2", 1), ("Grandchild", 2), ("Self", 0)]);
+    }
+
+    #[test]
+    fn test_for_each_unit_mut_reaches_deep_chains() {
+        let mut forest = unit_chain(5000);
+        let mut visited = 0;
+        for_each_unit_mut(&mut forest, |unit| {
+            unit.formation_level_id = Some(visited);
+            visited += 1;
+        });
+        assert_eq!(visited, 5000);
+        assert_eq!(walk_units(&forest).last().and_then(|v| v.unit.formation_level_id), Some(4999));
+        let mut next = forest;
+        while let Some(mut unit) = next.pop() {
+            next = std::mem::take(&mut unit.children);
+        }
+    }
+
+    #[test]
+    fn test_shape_of_wide_tree() {
+        let forest = fan(5000);
+        assert_eq!(tree_shape(&forest), TreeShape { depth: 2, widest: 5000 });
+        assert_eq!(tree_shape(&[]), TreeShape::default());
+        let roots: Vec<Unit> = (0..3).map(|i| Unit::new(i.to_string(), String::new())).collect();
+        assert_eq!(tree_shape(&roots), TreeShape { depth: 1, widest: 3 });
+    }
+
+    #[test]
+    fn test_limits_warn_then_refuse() {
+        let limits = TreeLimits::default();
+        assert_eq!(limits.check(tree_shape(&unit_chain(10))), Ok(vec![]));
+        assert_eq!(
+            limits.check(tree_shape(&unit_chain(20))),
+            Ok(vec![TreeLimitIssue::TooDeep { depth: 20, limit: 12 }])
+        );
+        assert_eq!(
+            limits.check(tree_shape(&unit_chain(200))),
+            Err(TreeLimitIssue::TooDeep { depth: 200, limit: MAX_TREE_DEPTH })
+        );
+        // Raising the hard depth limit stops at what files can hold
+        let raised = TreeLimits { max_depth: 1000, ..limits };
+        assert_eq!(raised.depth_limit(), MAX_TREE_DEPTH);
+        assert!(raised.check(tree_shape(&unit_chain(MAX_TREE_DEPTH))).is_ok());
+        assert!(raised.check(tree_shape(&unit_chain(MAX_TREE_DEPTH + 1))).is_err());
+        assert_eq!(
+            limits.check(tree_shape(&fan(500))),
+            Ok(vec![TreeLimitIssue::TooWide { children: 500, limit: 200 }])
+        );
+        assert_eq!(
+            limits.check(tree_shape(&fan(5000))),
+            Err(TreeLimitIssue::TooWide { children: 5000, limit: 2000 })
+        );
+    }
+
+    #[test]
+    fn test_placing_a_subtree() {
+        let limits = TreeLimits::default();
+        // A new unit under a parent 60 levels deep would be the 61st level
+        let shape = TreeShape::SINGLE.placed_under(MAX_TREE_DEPTH, 1);
+        assert_eq!(shape, TreeShape { depth: MAX_TREE_DEPTH + 1, widest: 1 });
+        assert!(limits.check(shape).is_err());
+        assert_eq!(tree_shape(&unit_chain(3)).placed_under(2, 2001).widest, 2001);
+        assert!(limits.check(TreeShape::SINGLE.placed_under(MAX_TREE_DEPTH - 1, 2000)).is_ok());
+    }
+
+    #[test]
+    fn test_without_children_copies_the_rest() {
+        let mut unit = Unit::new("Company".to_string(), "company".to_string());
+        unit.id = Some(7);
+        unit.effective_from = Some("1943-01-01".to_string());
+        unit.children = fan(3);
+        let copy = unit.without_children();
+        assert!(copy.children.is_empty());
+        assert_eq!(Unit { children: unit.children.clone(), ..copy }, unit);
+    }
+}
@@ -1,5 +1,7 @@
 //! Input validation for domain models
 
+use super::CustomFormationLevel;
+
 /// Validation error with field name and message
 #[derive(Debug, Clone, PartialEq)]
 pub struct ValidationError {
@@ -45,11 +47,44 @@ pub fn validate_library(name: &str, country: &str, era: &str) -> Vec<ValidationE
             field: "era".to_string(),
             message: "Era cannot be empty".to_string(),
         });
+    } else if era.chars().count() > 100 {
+        errors.push(ValidationError {
+            field: "era".to_string(),
+            message: "Era cannot exceed 100 characters".to_string(),
+        });
     }
 
     errors
 }
 
+/// Library validation errors by field, for showing each under its input
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryFieldErrors {
+    pub name: Option<String>,
+    pub country: Option<String>,
+    pub era: Option<String>,
+}
+
+impl LibraryFieldErrors {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.country.is_none() && self.era.is_none()
+    }
+}
+
+/// [`validate_library`] with the first message of each field
+pub fn validate_library_fields(name: &str, country: &str, era: &str) -> LibraryFieldErrors {
+    let mut fields = LibraryFieldErrors::default();
+    for error in validate_library(name, country, era) {
+        let slot = match error.field.as_str() {
+            "name" => &mut fields.name,
+            "country" => &mut fields.country,
+            _ => &mut fields.era,
+        };
+        slot.get_or_insert(error.message);
+    }
+    fields
+}
+
 /// Validate a branch name pair.
 pub fn validate_branch(name_ru: &str, name_en: &str) -> Vec<ValidationError> {
     let mut errors = Vec::new();
@@ -92,6 +127,67 @@ pub fn validate_formation_level(name_ru: &str, name_en: &str, ordinal: i32) -> V
     errors
 }
 
+/// Validate an expected personnel range (both bounds optional).
+pub fn validate_personnel_range(min: Option<u32>, max: Option<u32>) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            errors.push(ValidationError {
+                field: "personnel_range".to_string(),
+                message: "Minimum personnel cannot exceed maximum personnel".to_string(),
+            });
+        }
+    }
+
+    errors
+}
+
+/// Problems across a library's formation levels as a whole
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormationLevelSetIssues {
+    /// English names used by more than one level, as first spelled
+    pub duplicate_names: Vec<String>,
+    /// Standard ordinals shared by several levels, with those levels' names
+    pub shared_ordinals: Vec<(i32, Vec<String>)>,
+}
+
+/// Check a library's formation levels together. Duplicate English names
+/// (trimmed, ignoring case; empty ones aside) are errors; levels sharing a
+/// standard ordinal are allowed but worth a warning.
+pub fn validate_formation_level_set(levels: &[CustomFormationLevel]) -> FormationLevelSetIssues {
+    let mut issues = FormationLevelSetIssues::default();
+    let mut seen: Vec<String> = Vec::new();
+    for level in levels {
+        let key = level.name_en.trim().to_lowercase();
+        if key.is_empty() {
+            continue;
+        }
+        if seen.contains(&key) {
+            if !issues.duplicate_names.iter().any(|n| n.trim().to_lowercase() == key) {
+                issues.duplicate_names.push(level.name_en.trim().to_string());
+            }
+        } else {
+            seen.push(key);
+        }
+    }
+    for level in levels {
+        let ordinal = level.standard_level_ordinal;
+        if issues.shared_ordinals.iter().any(|(o, _)| *o == ordinal) {
+            continue;
+        }
+        let names: Vec<String> = levels
+            .iter()
+            .filter(|l| l.standard_level_ordinal == ordinal)
+            .map(|l| if l.name_en.trim().is_empty() { l.name_ru.trim() } else { l.name_en.trim() }.to_string())
+            .collect();
+        if names.len() > 1 {
+            issues.shared_ordinals.push((ordinal, names));
+        }
+    }
+    issues
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,6 +240,35 @@ mod tests {
         assert_eq!(errors[0].field, "name");
     }
 
+    #[test]
+    fn test_validate_library_fields_valid() {
+        assert!(validate_library_fields("US Army 2003", "US", "2003").is_empty());
+    }
+
+    #[test]
+    fn test_validate_library_fields_empty_name() {
+        let fields = validate_library_fields("", "US", "2003");
+        assert_eq!(fields.name.as_deref(), Some("Library name cannot be empty"));
+        assert_eq!(fields.country, None);
+        assert_eq!(fields.era, None);
+    }
+
+    #[test]
+    fn test_validate_library_fields_long_era() {
+        let fields = validate_library_fields("Test", "US", &"1".repeat(101));
+        assert_eq!(fields.era.as_deref(), Some("Era cannot exceed 100 characters"));
+        assert!(fields.name.is_none() && fields.country.is_none());
+        // Counted in characters, not bytes
+        assert!(validate_library_fields("Test", "US", &"я".repeat(100)).is_empty());
+    }
+
+    #[test]
+    fn test_validate_library_fields_whitespace_country() {
+        let fields = validate_library_fields("Test", "  \t ", "2003");
+        assert_eq!(fields.country.as_deref(), Some("Country cannot be empty"));
+        assert!(!fields.is_empty());
+    }
+
     #[test]
     fn test_validate_branch_valid() {
         let errors = validate_branch("Пехота", "Infantry");
@@ -183,6 +308,16 @@ mod tests {
         assert_eq!(errors[0].field, "standard_level_ordinal");
     }
 
+    #[test]
+    fn test_validate_personnel_range() {
+        assert!(validate_personnel_range(None, None).is_empty());
+        assert!(validate_personnel_range(Some(30), None).is_empty());
+        assert!(validate_personnel_range(Some(30), Some(30)).is_empty());
+        let errors = validate_personnel_range(Some(45), Some(30));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "personnel_range");
+    }
+
     #[test]
     fn test_validation_error_display() {
         let err = ValidationError {
@@ -191,4 +326,48 @@ mod tests {
         };
         assert_eq!(format!("{}", err), "name: cannot be empty");
     }
+
+    fn level(name_ru: &str, name_en: &str, ordinal: i32) -> CustomFormationLevel {
+        CustomFormationLevel::new(1, name_ru.to_string(), name_en.to_string(), ordinal)
+    }
+
+    #[test]
+    fn test_formation_level_set_valid() {
+        let levels = [level("отделение", "squad", 1), level("взвод", "platoon", 2)];
+        assert_eq!(validate_formation_level_set(&levels), FormationLevelSetIssues::default());
+    }
+
+    #[test]
+    fn test_formation_level_set_duplicate_names_ignore_case_and_spaces() {
+        let levels = [
+            level("взвод", "Platoon", 2),
+            level("рота", "company", 3),
+            level("взвод 2", " platoon ", 2),
+            level("взвод 3", "PLATOON", 2),
+            level("", "", 4),
+            level("батальон", "", 5),
+        ];
+        let issues = validate_formation_level_set(&levels);
+        assert_eq!(issues.duplicate_names, vec!["platoon".to_string()]);
+    }
+
+    #[test]
+    fn test_formation_level_set_shared_ordinals() {
+        let levels = [
+            level("взвод", "platoon", 2),
+            level("рота", "company", 3),
+            level("группа", "", 2),
+            level("батарея", "battery", 3),
+            level("батальон", "battalion", 4),
+        ];
+        let issues = validate_formation_level_set(&levels);
+        assert!(issues.duplicate_names.is_empty());
+        assert_eq!(
+            issues.shared_ordinals,
+            vec![
+                (2, vec!["platoon".to_string(), "группа".to_string()]),
+                (3, vec!["company".to_string(), "battery".to_string()]),
+            ]
+        );
+    }
 }
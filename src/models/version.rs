@@ -26,6 +26,10 @@ pub struct Snapshot {
     pub data: String,
     /// Optional description/comment
     pub description: Option<String>,
+    /// Taken by autosave: the state at `version` with unsaved changes, not
+    /// a version of its own
+    #[serde(default)]
+    pub is_autosave: bool,
 }
 
 impl Snapshot {
@@ -38,6 +42,7 @@ impl Snapshot {
             timestamp: chrono::Utc::now().timestamp(),
             data,
             description: None,
+            is_autosave: false,
         }
     }
 
@@ -55,6 +60,7 @@ impl Snapshot {
             timestamp: chrono::Utc::now().timestamp(),
             data,
             description: Some(description),
+            is_autosave: false,
         }
     }
 }
@@ -83,7 +89,7 @@ pub fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> String {
 
     match (old_val, new_val) {
         (Ok(old_v), Ok(new_v)) => {
-            diff_json_values("", &old_v, &new_v, &mut changes);
+            diff_json_values("", &flatten_snapshot(old_v), &flatten_snapshot(new_v), &mut changes);
             if changes.len() == 1 {
                 changes.push("Data changed but no structural differences detected.".to_string());
             }
@@ -96,6 +102,24 @@ pub fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> String {
     changes.join("\n")
 }
 
+/// Snapshot JSON with the library's fields at the top: full-state snapshots
+/// nest them under `library`, next to the per-library tables
+fn flatten_snapshot(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    let Value::Object(mut map) = value else {
+        return value;
+    };
+    if map.remove("format_version").is_some() {
+        if let Some(Value::Object(library)) = map.remove("library") {
+            for (key, field) in library {
+                map.entry(key).or_insert(field);
+            }
+        }
+    }
+    Value::Object(map)
+}
+
 /// Recursively compare two JSON values and report differences.
 fn diff_json_values(
     path: &str,
@@ -221,6 +245,20 @@ mod tests {
         assert!(diff.contains("array length 2 -> 3"));
     }
 
+    #[test]
+    fn test_diff_snapshots_full_state_against_plain_library() {
+        let s1 = Snapshot::new(1, 1, "{\"name\":\"A\"}".to_string());
+        let s2 = Snapshot::new(
+            1,
+            2,
+            "{\"format_version\":1,\"library\":{\"name\":\"B\"},\"branches\":[]}".to_string(),
+        );
+        let diff = diff_snapshots(&s1, &s2);
+        assert!(diff.contains("Changed: name"));
+        assert!(diff.contains("Added: branches"));
+        assert!(!diff.contains("library"));
+    }
+
     #[test]
     fn test_diff_snapshots_invalid_json() {
         let s1 = Snapshot::new(1, 1, "not json".to_string());
@@ -0,0 +1,141 @@
+//! Rows copied from one editor window with "Copy all" and appended to another
+//! with "Paste".
+
+use std::collections::HashSet;
+use serde_json::json;
+
+use crate::export::{BranchCategoryExport, BranchExport, FormationLevelExport};
+
+/// The rows on the application clipboard
+#[derive(Debug, Clone)]
+pub enum ClipboardContent {
+    Branches(Vec<BranchExport>),
+    Categories(Vec<BranchCategoryExport>),
+    FormationLevels(Vec<FormationLevelExport>),
+}
+
+impl ClipboardContent {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Branches(rows) => rows.len(),
+            Self::Categories(rows) => rows.len(),
+            Self::FormationLevels(rows) => rows.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Text for the system clipboard: the JSON of an export file with the
+    /// same rows, so it can be saved to a file and imported
+    pub fn to_json(&self) -> String {
+        let value = match self {
+            Self::Branches(rows) => json!({ "branches": rows }),
+            Self::Categories(rows) => json!({ "categories": rows }),
+            Self::FormationLevels(rows) => json!({ "formation_levels": rows }),
+        };
+        serde_json::to_string_pretty(&value).unwrap_or_default()
+    }
+}
+
+/// Rows to append on paste, and how many were left out as duplicates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pasted<T> {
+    pub rows: Vec<T>,
+    pub skipped: usize,
+}
+
+fn name_key(name: &str) -> Option<String> {
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_lowercase())
+}
+
+/// The `incoming` rows that can be appended after rows named `existing`.
+///
+/// A row is skipped when its English name (trimmed, case-insensitive) is
+/// already in the list or was pasted earlier in `incoming`. Rows without an
+/// English name are never duplicates.
+pub fn paste_rows<T: Clone, S: AsRef<str>>(
+    existing: impl IntoIterator<Item = S>,
+    incoming: &[T],
+    name_en: impl Fn(&T) -> &str,
+) -> Pasted<T> {
+    let mut names: HashSet<String> = existing.into_iter().filter_map(|n| name_key(n.as_ref())).collect();
+    let mut pasted = Pasted { rows: Vec::new(), skipped: 0 };
+    for row in incoming {
+        let duplicate = name_key(name_en(row)).is_some_and(|key| !names.insert(key));
+        if duplicate {
+            pasted.skipped += 1;
+        } else {
+            pasted.rows.push(row.clone());
+        }
+    }
+    pasted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(name_en: &str) -> BranchCategoryExport {
+        BranchCategoryExport {
+            name_ru: String::new(),
+            name_en: name_en.to_string(),
+        }
+    }
+
+    fn names(pasted: &Pasted<BranchCategoryExport>) -> Vec<&str> {
+        pasted.rows.iter().map(|c| c.name_en.as_str()).collect()
+    }
+
+    #[test]
+    fn test_paste_appends_new_rows_in_order() {
+        let incoming = vec![category("Navy"), category("Air Force")];
+        let pasted = paste_rows(["Army"], &incoming, |c| &c.name_en);
+        assert_eq!(names(&pasted), ["Navy", "Air Force"]);
+        assert_eq!(pasted.skipped, 0);
+    }
+
+    #[test]
+    fn test_paste_skips_existing_names_ignoring_case_and_spaces() {
+        let incoming = vec![category("ARMY"), category(" navy "), category("Marines")];
+        let pasted = paste_rows(["Army", "Navy"], &incoming, |c| &c.name_en);
+        assert_eq!(names(&pasted), ["Marines"]);
+        assert_eq!(pasted.skipped, 2);
+    }
+
+    #[test]
+    fn test_paste_skips_repeats_within_pasted_rows() {
+        let incoming = vec![category("Navy"), category("navy")];
+        let pasted = paste_rows(Vec::<String>::new(), &incoming, |c| &c.name_en);
+        assert_eq!(names(&pasted), ["Navy"]);
+        assert_eq!(pasted.skipped, 1);
+    }
+
+    #[test]
+    fn test_paste_keeps_rows_without_english_name() {
+        let incoming = vec![category(""), category("  ")];
+        let pasted = paste_rows(["", "Army"], &incoming, |c| &c.name_en);
+        assert_eq!(pasted.rows.len(), 2);
+        assert_eq!(pasted.skipped, 0);
+    }
+
+    #[test]
+    fn test_to_json_reads_back_as_export_file() {
+        let content = ClipboardContent::Branches(vec![BranchExport {
+            name_ru: "Пехота".into(),
+            name_en: "Infantry".into(),
+            category_name_ru: None,
+            category_name_en: Some("Ground".into()),
+        }]);
+        let file: crate::export::BranchesFile = serde_json::from_str(&content.to_json()).unwrap();
+        assert_eq!(file.branches.len(), 1);
+        assert_eq!(file.branches[0].category_name_en.as_deref(), Some("Ground"));
+        assert!(file.categories.is_empty());
+
+        let levels = ClipboardContent::FormationLevels(Vec::new());
+        assert!(levels.is_empty());
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&levels.to_json()).unwrap()["formation_levels"], json!([]));
+    }
+}
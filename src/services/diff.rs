@@ -0,0 +1,718 @@
+//! Structured comparison of two libraries
+//!
+//! [`diff_libraries`] is pure: it only looks at the two values, so it can be
+//! used on snapshots, imports or anything else that yields a [`Library`].
+//! Units are matched by id where both sides have one, then by name under the
+//! matched parent, and finally by a name that is unique on both sides, which
+//! is how an id-less unit that changed parent is reported as moved.
+//!
+//! [`compare_formation`] uses the same matching to line up the rows of one
+//! formation's table with the rows of the same formation in an older version.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use crate::models::{walk_units, Library, Unit};
+
+/// One library field that differs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// One change to the unit tree. Paths are unit names joined with " / ";
+/// the top level is an empty path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnitChange {
+    Added { path: String },
+    Removed { path: String },
+    Renamed { path: String, new_name: String },
+    Moved { path: String, from: String, to: String },
+    /// Number of personnel in one position changed
+    Personnel { path: String, position: String, old: usize, new: usize },
+    /// Quantity of one equipment item changed
+    Equipment { path: String, name: String, old: usize, new: usize },
+}
+
+/// Everything that differs between two libraries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryDiff {
+    /// Name, country, era and author
+    pub fields: Vec<FieldChange>,
+    pub tags_added: Vec<String>,
+    pub tags_removed: Vec<String>,
+    /// Removed, renamed, moved and changed units in old tree order, then added units
+    pub units: Vec<UnitChange>,
+}
+
+impl LibraryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.tags_added.is_empty() && self.tags_removed.is_empty() && self.units.is_empty()
+    }
+
+    /// One line per change
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.fields.iter().map(|f| f.to_string()).collect();
+        lines.extend(self.tags_removed.iter().map(|t| format!("Tag removed: {}", t)));
+        lines.extend(self.tags_added.iter().map(|t| format!("Tag added: {}", t)));
+        lines.extend(self.units.iter().map(|u| u.to_string()));
+        lines
+    }
+
+    /// Change lines joined for display, or a note when nothing changed
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            "No changes.".to_string()
+        } else {
+            self.lines().join("\n")
+        }
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Changed: {} ({:?} -> {:?})", self.field, self.old, self.new)
+    }
+}
+
+fn place(path: &str) -> &str {
+    if path.is_empty() {
+        "(top level)"
+    } else {
+        path
+    }
+}
+
+impl fmt::Display for UnitChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnitChange::Added { path } => write!(f, "Unit added: {}", path),
+            UnitChange::Removed { path } => write!(f, "Unit removed: {}", path),
+            UnitChange::Renamed { path, new_name } => write!(f, "Unit renamed: {} -> {}", path, new_name),
+            UnitChange::Moved { path, from, to } => {
+                write!(f, "Unit moved: {} ({} -> {})", path, place(from), place(to))
+            }
+            UnitChange::Personnel { path, position, old, new } => {
+                write!(f, "Personnel: {} — {} ({} -> {})", path, position, old, new)
+            }
+            UnitChange::Equipment { path, name, old, new } => {
+                write!(f, "Equipment: {} — {} ({} -> {})", path, name, old, new)
+            }
+        }
+    }
+}
+
+/// Compare two libraries
+pub fn diff_libraries(a: &Library, b: &Library) -> LibraryDiff {
+    let mut diff = LibraryDiff::default();
+    for (field, old, new) in [
+        ("name", &a.name, &b.name),
+        ("country", &a.country, &b.country),
+        ("era", &a.era, &b.era),
+        ("author", &a.author, &b.author),
+    ] {
+        if old != new {
+            diff.fields.push(FieldChange {
+                field,
+                old: old.clone(),
+                new: new.clone(),
+            });
+        }
+    }
+    // Tags are a set: reordering is not a change
+    diff.tags_removed = a.tags.iter().filter(|t| !b.tags.contains(t)).cloned().collect();
+    diff.tags_added = b.tags.iter().filter(|t| !a.tags.contains(t)).cloned().collect();
+    diff.units = diff_units(&a.units, &b.units);
+    diff
+}
+
+/// A unit with its place in the tree; `parent` indexes the same flat list
+struct Flat<'a> {
+    unit: &'a Unit,
+    path: String,
+    parent: Option<usize>,
+}
+
+/// Parents always come before their children
+fn flatten(units: &[Unit]) -> Vec<Flat<'_>> {
+    let mut out: Vec<Flat> = Vec::new();
+    for visit in walk_units(units) {
+        let path = match visit.parent {
+            Some(p) => format!("{} / {}", out[p].path, visit.unit.name),
+            None => visit.unit.name.clone(),
+        };
+        out.push(Flat { unit: visit.unit, path, parent: visit.parent });
+    }
+    out
+}
+
+fn parent_path(flat: &[Flat], parent: Option<usize>) -> String {
+    parent.map(|p| flat[p].path.clone()).unwrap_or_default()
+}
+
+/// Old index -> new index for every unit present on both sides
+fn match_units(old: &[Flat], new: &[Flat]) -> Vec<Option<usize>> {
+    let mut matched: Vec<Option<usize>> = vec![None; old.len()];
+    let mut taken = vec![false; new.len()];
+    let new_by_id: HashMap<i64, usize> = new
+        .iter()
+        .enumerate()
+        .filter_map(|(i, n)| n.unit.id.map(|id| (id, i)))
+        .collect();
+    for (i, o) in old.iter().enumerate() {
+        if let Some(&j) = o.unit.id.and_then(|id| new_by_id.get(&id)) {
+            matched[i] = Some(j);
+            taken[j] = true;
+        }
+    }
+    // Same name under the matched parent; parents are matched first
+    for (i, o) in old.iter().enumerate() {
+        if matched[i].is_some() {
+            continue;
+        }
+        let parent = o.parent.map(|p| matched[p]);
+        let candidate = new.iter().enumerate().find(|(j, n)| {
+            !taken[*j]
+                && n.unit.name == o.unit.name
+                && match parent {
+                    None => n.parent.is_none(),
+                    Some(mapped) => mapped.is_some() && n.parent == mapped,
+                }
+        });
+        if let Some((j, _)) = candidate {
+            matched[i] = Some(j);
+            taken[j] = true;
+        }
+    }
+    // A name left unmatched exactly once on each side: the unit moved
+    let mut old_names: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut new_names: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, o) in old.iter().enumerate().filter(|(i, _)| matched[*i].is_none()) {
+        old_names.entry(o.unit.name.as_str()).or_default().push(i);
+    }
+    for (j, n) in new.iter().enumerate().filter(|(j, _)| !taken[*j]) {
+        new_names.entry(n.unit.name.as_str()).or_default().push(j);
+    }
+    for (name, olds) in &old_names {
+        if let (&[i], Some(&[j])) = (olds.as_slice(), new_names.get(name).map(Vec::as_slice)) {
+            matched[i] = Some(j);
+        }
+    }
+    matched
+}
+
+fn diff_units(old_units: &[Unit], new_units: &[Unit]) -> Vec<UnitChange> {
+    let old = flatten(old_units);
+    let new = flatten(new_units);
+    let matched = match_units(&old, &new);
+
+    let mut changes = Vec::new();
+    let mut present = vec![false; new.len()];
+    for (i, o) in old.iter().enumerate() {
+        let Some(j) = matched[i] else {
+            changes.push(UnitChange::Removed { path: o.path.clone() });
+            continue;
+        };
+        present[j] = true;
+        let n = &new[j];
+        if o.unit.name != n.unit.name {
+            changes.push(UnitChange::Renamed {
+                path: o.path.clone(),
+                new_name: n.unit.name.clone(),
+            });
+        }
+        let same_parent = match (o.parent, n.parent) {
+            (None, None) => true,
+            (Some(p), Some(q)) => matched[p] == Some(q),
+            _ => false,
+        };
+        if !same_parent {
+            changes.push(UnitChange::Moved {
+                path: n.path.clone(),
+                from: parent_path(&old, o.parent),
+                to: parent_path(&new, n.parent),
+            });
+        }
+        let positions = |unit: &Unit| {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for p in &unit.personnel {
+                *counts.entry(p.position.clone()).or_default() += 1;
+            }
+            counts
+        };
+        for (position, old_count, new_count) in deltas(positions(o.unit), positions(n.unit)) {
+            changes.push(UnitChange::Personnel {
+                path: n.path.clone(),
+                position,
+                old: old_count,
+                new: new_count,
+            });
+        }
+        let equipment = |unit: &Unit| {
+            let mut totals: BTreeMap<String, usize> = BTreeMap::new();
+            for e in &unit.equipment {
+                *totals.entry(e.name.clone()).or_default() += e.quantity;
+            }
+            totals
+        };
+        for (name, old_qty, new_qty) in deltas(equipment(o.unit), equipment(n.unit)) {
+            changes.push(UnitChange::Equipment {
+                path: n.path.clone(),
+                name,
+                old: old_qty,
+                new: new_qty,
+            });
+        }
+    }
+    for (j, n) in new.iter().enumerate() {
+        if !present[j] {
+            changes.push(UnitChange::Added { path: n.path.clone() });
+        }
+    }
+    changes
+}
+
+/// Keys whose value differs, missing keys counting as zero
+fn deltas(old: BTreeMap<String, usize>, mut new: BTreeMap<String, usize>) -> Vec<(String, usize, usize)> {
+    let mut out = Vec::new();
+    for (key, a) in old {
+        let b = new.remove(&key).unwrap_or(0);
+        if a != b {
+            out.push((key, a, b));
+        }
+    }
+    out.extend(new.into_iter().map(|(key, b)| (key, 0, b)));
+    out.sort();
+    out
+}
+
+/// How a row of a formation table differs from the older version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowMark {
+    Unchanged,
+    Added,
+    Removed,
+    /// Renamed, moved or with a different count
+    Changed,
+}
+
+/// One count of a compared row; `None` on the side where the unit does not exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuantityCell {
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+}
+
+impl QuantityCell {
+    /// Present on both sides with different values
+    pub fn is_changed(&self) -> bool {
+        matches!((self.old, self.new), (Some(a), Some(b)) if a != b)
+    }
+
+    /// "old → new" when changed, otherwise the one value there is
+    pub fn text(&self) -> String {
+        match (self.old, self.new) {
+            (Some(a), Some(b)) if a != b => format!("{} → {}", a, b),
+            (_, Some(v)) | (Some(v), None) => v.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// A row of a formation table aligned with the older version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComparedRow {
+    pub mark: RowMark,
+    /// Unit id; the old unit's for removed rows
+    pub id: Option<i64>,
+    /// Current name; the old name for removed rows
+    pub name: String,
+    /// Name in the older version when the unit was renamed
+    pub old_name: Option<String>,
+    pub unit_type: String,
+    /// 0 for the formation itself
+    pub depth: usize,
+    /// Personnel positions of the unit itself
+    pub personnel: QuantityCell,
+    /// Equipment quantity of the unit itself
+    pub equipment: QuantityCell,
+}
+
+fn equipment_quantity(unit: &Unit) -> usize {
+    unit.equipment.iter().map(|e| e.quantity).sum()
+}
+
+fn compared_row(old: Option<&Unit>, new: Option<&Unit>, moved: bool, depth: usize) -> ComparedRow {
+    let shown = new.or(old).expect("a row has at least one side");
+    let personnel = QuantityCell {
+        old: old.map(|u| u.personnel.len()),
+        new: new.map(|u| u.personnel.len()),
+    };
+    let equipment = QuantityCell {
+        old: old.map(equipment_quantity),
+        new: new.map(equipment_quantity),
+    };
+    let old_name = match (old, new) {
+        (Some(o), Some(n)) if o.name != n.name => Some(o.name.clone()),
+        _ => None,
+    };
+    let mark = match (old, new) {
+        (None, _) => RowMark::Added,
+        (_, None) => RowMark::Removed,
+        _ if moved || old_name.is_some() || personnel.is_changed() || equipment.is_changed() => RowMark::Changed,
+        _ => RowMark::Unchanged,
+    };
+    ComparedRow {
+        mark,
+        id: shown.id,
+        name: shown.name.clone(),
+        old_name,
+        unit_type: shown.unit_type.clone(),
+        depth,
+        personnel,
+        equipment,
+    }
+}
+
+/// Rows of the formation `new` and its subunits in tree order, aligned with
+/// the same formation in an older version. Removed subunits follow the rows
+/// of their former parent; a subunit that moved elsewhere in the formation
+/// shows once, at its new place, as changed.
+pub fn compare_formation(old: &Unit, new: &Unit) -> Vec<ComparedRow> {
+    let old_flat = flatten(&old.children);
+    let new_flat = flatten(&new.children);
+    let matched = match_units(&old_flat, &new_flat);
+    let mut old_of_new: Vec<Option<usize>> = vec![None; new_flat.len()];
+    for (i, m) in matched.iter().enumerate() {
+        if let Some(j) = *m {
+            old_of_new[j] = Some(i);
+        }
+    }
+
+    let aligned = Alignment {
+        old: &old_flat,
+        new: &new_flat,
+        matched: &matched,
+        old_of_new: &old_of_new,
+    };
+    let mut rows = vec![compared_row(Some(old), Some(new), false, 0)];
+    aligned.push_rows(&mut rows);
+    rows
+}
+
+/// What is left to do while aligning, innermost last
+enum Step {
+    /// Rows under a new parent (`None`: the formation), then the old
+    /// children of its matched old parent that are gone
+    Children { new_parent: Option<usize>, old_parent: Option<usize>, depth: usize },
+    /// Old children of `old_parent` without a match, with their own removed children
+    Removed { old_parent: Option<usize>, depth: usize },
+    /// Row of a unit of the new tree
+    Present { index: usize, depth: usize },
+    /// Row of a unit only in the old tree
+    Gone { index: usize, depth: usize },
+}
+
+/// Both flattened trees with the matching between them
+struct Alignment<'a, 'u> {
+    old: &'a [Flat<'u>],
+    new: &'a [Flat<'u>],
+    matched: &'a [Option<usize>],
+    old_of_new: &'a [Option<usize>],
+}
+
+impl Alignment<'_, '_> {
+    /// Rows below the formation, each unit followed by its subunits
+    fn push_rows(&self, rows: &mut Vec<ComparedRow>) {
+        let mut steps = vec![Step::Children { new_parent: None, old_parent: None, depth: 1 }];
+        while let Some(step) = steps.pop() {
+            let mut next = Vec::new();
+            match step {
+                Step::Children { new_parent, old_parent, depth } => {
+                    for (j, _) in self.new.iter().enumerate().filter(|(_, n)| n.parent == new_parent) {
+                        next.push(Step::Present { index: j, depth });
+                        next.push(Step::Children { new_parent: Some(j), old_parent: self.old_of_new[j], depth: depth + 1 });
+                    }
+                    // An added unit has no old children
+                    if new_parent.is_none() || old_parent.is_some() {
+                        next.push(Step::Removed { old_parent, depth });
+                    }
+                }
+                Step::Removed { old_parent, depth } => {
+                    for (i, _) in self.old.iter().enumerate().filter(|(_, o)| o.parent == old_parent) {
+                        if self.matched[i].is_none() {
+                            next.push(Step::Gone { index: i, depth });
+                            next.push(Step::Removed { old_parent: Some(i), depth: depth + 1 });
+                        }
+                    }
+                }
+                Step::Present { index, depth } => {
+                    let n = &self.new[index];
+                    let old_index = self.old_of_new[index];
+                    let moved = old_index.is_some_and(|i| match self.old[i].parent {
+                        None => n.parent.is_some(),
+                        Some(p) => n.parent.is_none() || self.matched[p] != n.parent,
+                    });
+                    rows.push(compared_row(old_index.map(|i| self.old[i].unit), Some(n.unit), moved, depth));
+                }
+                Step::Gone { index, depth } => {
+                    rows.push(compared_row(Some(self.old[index].unit), None, false, depth));
+                }
+            }
+            steps.extend(next.into_iter().rev());
+        }
+    }
+}
+
+/// Names from the top level down to the unit with `id`
+pub fn unit_path(units: &[Unit], id: i64) -> Option<Vec<String>> {
+    let mut visits = Vec::new();
+    for visit in walk_units(units) {
+        let found = visit.unit.id == Some(id);
+        visits.push(visit);
+        if found {
+            let mut path = Vec::new();
+            let mut at = Some(visits.len() - 1);
+            while let Some(i) = at {
+                path.push(visits[i].unit.name.clone());
+                at = visits[i].parent;
+            }
+            path.reverse();
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// The unit with `id`, or else the one at `path` (ids change when a version is restored)
+pub fn find_unit<'a>(units: &'a [Unit], id: i64, path: &[String]) -> Option<&'a Unit> {
+    let by_id = walk_units(units).map(|v| v.unit).find(|u| u.id == Some(id));
+    by_id.or_else(|| {
+        let (first, rest) = path.split_first()?;
+        let mut unit = units.iter().find(|u| &u.name == first)?;
+        for name in rest {
+            unit = unit.children.iter().find(|u| &u.name == name)?;
+        }
+        Some(unit)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Equipment, Personnel};
+
+    fn library(units: Vec<Unit>) -> Library {
+        let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        library.units = units;
+        library
+    }
+
+    fn unit(id: Option<i64>, name: &str, children: Vec<Unit>) -> Unit {
+        let mut unit = Unit::new(name.to_string(), "company".to_string());
+        unit.id = id;
+        unit.children = children;
+        unit
+    }
+
+    #[test]
+    fn test_identical_libraries_have_no_changes() {
+        let a = library(vec![unit(Some(1), "HQ", vec![unit(Some(2), "1st", vec![])])]);
+        let diff = diff_libraries(&a, &a.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "No changes.");
+    }
+
+    #[test]
+    fn test_metadata_and_tags_as_a_set() {
+        let mut a = library(vec![]);
+        a.tags = vec!["x".to_string(), "y".to_string()];
+        let mut b = a.clone();
+        b.tags = vec!["y".to_string(), "x".to_string(), "z".to_string()];
+        b.era = "2005".to_string();
+
+        let diff = diff_libraries(&a, &b);
+        assert_eq!(
+            diff.fields,
+            [FieldChange {
+                field: "era",
+                old: "2003".to_string(),
+                new: "2005".to_string(),
+            }]
+        );
+        assert_eq!(diff.tags_added, ["z"]);
+        assert!(diff.tags_removed.is_empty());
+        assert!(diff.units.is_empty());
+    }
+
+    #[test]
+    fn test_units_added_removed_and_renamed_by_id() {
+        let a = library(vec![unit(Some(1), "HQ", vec![unit(Some(2), "1st", vec![]), unit(Some(3), "2nd", vec![])])]);
+        let b = library(vec![unit(Some(1), "Headquarters", vec![unit(Some(2), "1st", vec![]), unit(Some(4), "3rd", vec![])])]);
+        assert_eq!(
+            diff_libraries(&a, &b).units,
+            [
+                UnitChange::Renamed { path: "HQ".to_string(), new_name: "Headquarters".to_string() },
+                UnitChange::Removed { path: "HQ / 2nd".to_string() },
+                UnitChange::Added { path: "Headquarters / 3rd".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_moved_unit_is_not_delete_and_add() {
+        let platoon = |id| unit(id, "Mortar Platoon", vec![unit(id.map(|i| i + 1), "Section", vec![])]);
+        for ids in [(Some(1), Some(2), Some(10)), (None, None, None)] {
+            let a = library(vec![
+                unit(ids.0, "A Company", vec![platoon(ids.2)]),
+                unit(ids.1, "B Company", vec![]),
+            ]);
+            let b = library(vec![
+                unit(ids.0, "A Company", vec![]),
+                unit(ids.1, "B Company", vec![platoon(ids.2)]),
+            ]);
+            let diff = diff_libraries(&a, &b);
+            // The child moves with its parent and is not reported
+            assert_eq!(
+                diff.units,
+                [UnitChange::Moved {
+                    path: "B Company / Mortar Platoon".to_string(),
+                    from: "A Company".to_string(),
+                    to: "B Company".to_string(),
+                }],
+                "ids: {:?}",
+                ids
+            );
+        }
+    }
+
+    #[test]
+    fn test_personnel_and_equipment_deltas() {
+        let mut old = unit(Some(1), "Squad", vec![]);
+        old.personnel = vec![Personnel::new("Rifleman".to_string()); 2];
+        old.equipment = vec![Equipment::new("M4".to_string(), 8), Equipment::new("M249".to_string(), 2)];
+        let mut new = old.clone();
+        new.personnel.push(Personnel::new("Rifleman".to_string()));
+        new.personnel.push(Personnel::new("Medic".to_string()));
+        new.equipment = vec![Equipment::new("M4".to_string(), 9)];
+
+        let diff = diff_libraries(&library(vec![old]), &library(vec![new]));
+        let lines: Vec<String> = diff.units.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "Personnel: Squad — Medic (0 -> 1)",
+                "Personnel: Squad — Rifleman (2 -> 3)",
+                "Equipment: Squad — M249 (2 -> 0)",
+                "Equipment: Squad — M4 (8 -> 9)",
+            ]
+        );
+    }
+
+    fn row_summary(rows: &[ComparedRow]) -> Vec<(RowMark, usize, &str, String, String)> {
+        rows.iter()
+            .map(|r| (r.mark, r.depth, r.name.as_str(), r.personnel.text(), r.equipment.text()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_formation_marks_rows() {
+        let mut first = unit(Some(2), "1st Platoon", vec![]);
+        first.personnel = vec![Personnel::new("Rifleman".to_string()); 3];
+        let old = unit(
+            Some(1),
+            "A Company",
+            vec![first.clone(), unit(Some(3), "2nd Platoon", vec![unit(Some(4), "Weapons Squad", vec![])])],
+        );
+        let mut first_new = first;
+        first_new.personnel.push(Personnel::new("Medic".to_string()));
+        first_new.equipment = vec![Equipment::new("M4".to_string(), 4)];
+        let new = unit(Some(1), "A Company", vec![first_new, unit(None, "3rd Platoon", vec![])]);
+
+        let rows = compare_formation(&old, &new);
+        assert_eq!(
+            row_summary(&rows),
+            [
+                (RowMark::Unchanged, 0, "A Company", "0".to_string(), "0".to_string()),
+                (RowMark::Changed, 1, "1st Platoon", "3 → 4".to_string(), "0 → 4".to_string()),
+                (RowMark::Added, 1, "3rd Platoon", "0".to_string(), "0".to_string()),
+                (RowMark::Removed, 1, "2nd Platoon", "0".to_string(), "0".to_string()),
+                (RowMark::Removed, 2, "Weapons Squad", "0".to_string(), "0".to_string()),
+            ]
+        );
+        assert!(rows[1].personnel.is_changed());
+        assert!(!rows[2].personnel.is_changed());
+    }
+
+    #[test]
+    fn test_compare_formation_rename_and_move() {
+        let old = unit(
+            None,
+            "A Company",
+            vec![unit(None, "1st Platoon", vec![unit(None, "Sniper Team", vec![])]), unit(Some(5), "HQ", vec![])],
+        );
+        let new = unit(
+            None,
+            "A Company",
+            vec![unit(None, "1st Platoon", vec![]), unit(Some(5), "Headquarters", vec![unit(None, "Sniper Team", vec![])])],
+        );
+
+        let rows = compare_formation(&old, &new);
+        let marks: Vec<(RowMark, &str, Option<&str>)> =
+            rows.iter().map(|r| (r.mark, r.name.as_str(), r.old_name.as_deref())).collect();
+        assert_eq!(
+            marks,
+            [
+                (RowMark::Unchanged, "A Company", None),
+                (RowMark::Unchanged, "1st Platoon", None),
+                (RowMark::Changed, "Headquarters", Some("HQ")),
+                (RowMark::Changed, "Sniper Team", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_unit_by_id_then_path() {
+        let units = vec![unit(Some(1), "Battalion", vec![unit(Some(2), "A Company", vec![])])];
+        let path = unit_path(&units, 2).unwrap();
+        assert_eq!(path, ["Battalion", "A Company"]);
+        assert_eq!(find_unit(&units, 2, &[]).map(|u| u.name.as_str()), Some("A Company"));
+        // After a restore the ids no longer match, so the path is used
+        let restored = vec![unit(Some(10), "Battalion", vec![unit(Some(11), "A Company", vec![])])];
+        assert_eq!(find_unit(&restored, 2, &path).and_then(|u| u.id), Some(11));
+        assert!(find_unit(&restored, 2, &["Battalion".to_string(), "B Company".to_string()]).is_none());
+    }
+
+    /// Units 1..=`depth`, each the only subunit of the one before
+    fn chain(depth: i64) -> Unit {
+        let nodes = (1..=depth)
+            .map(|i| (unit(Some(i), &format!("U{}", i), vec![]), (i > 1).then(|| i as usize - 2)))
+            .collect();
+        crate::models::assemble_forest(nodes, |u, children| u.children = children).remove(0)
+    }
+
+    #[test]
+    fn test_deep_formations_compare_without_recursion() {
+        let old = chain(800);
+        let new = chain(300);
+        let rows = compare_formation(&old, &new);
+        assert_eq!(rows.len(), 800);
+        assert!(rows[..300].iter().all(|r| r.mark == RowMark::Unchanged));
+        assert!(rows[300..].iter().all(|r| r.mark == RowMark::Removed));
+        assert_eq!(rows.last().map(|r| (r.depth, r.name.as_str())), Some((799, "U800")));
+
+        let diff = diff_libraries(&library(vec![old.clone()]), &library(vec![new]));
+        assert_eq!(diff.units.len(), 500);
+        assert_eq!(unit_path(std::slice::from_ref(&old), 800).map(|p| p.len()), Some(800));
+        assert_eq!(find_unit(&[old], 800, &[]).map(|u| u.name.as_str()), Some("U800"));
+    }
+
+    #[test]
+    fn test_quantity_cell_text() {
+        assert_eq!(QuantityCell { old: Some(2), new: Some(3) }.text(), "2 → 3");
+        assert_eq!(QuantityCell { old: Some(2), new: Some(2) }.text(), "2");
+        assert_eq!(QuantityCell { old: None, new: Some(3) }.text(), "3");
+        assert_eq!(QuantityCell { old: Some(2), new: None }.text(), "2");
+    }
+}
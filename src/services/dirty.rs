@@ -0,0 +1,147 @@
+//! Unsaved changes of the open library
+//!
+//! Edits are written to the database as they are made, but only Save Library
+//! records them as a version. Changes since the library was opened or last
+//! saved are counted here, so exiting can offer to save them first.
+
+use super::events::ChangeEvent;
+use super::unit_rows::RowChanges;
+
+/// Changes to the open library since it was opened, saved or its changes
+/// were discarded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirtyState {
+    library_id: Option<i64>,
+    changes: u32,
+}
+
+impl DirtyState {
+    /// Start counting for a newly opened library (`None`: no library open).
+    /// Reopening the same library keeps its count.
+    pub fn open(&mut self, library_id: Option<i64>) {
+        if self.library_id != library_id {
+            *self = DirtyState { library_id, changes: 0 };
+        }
+    }
+
+    /// Count a change if it concerns the open library. [`ChangeEvent::UnitChanged`]
+    /// carries no library and is taken to be an edit of the open one.
+    pub fn record(&mut self, event: &ChangeEvent) {
+        let Some(open) = self.library_id else {
+            return;
+        };
+        let concerns_open = match *event {
+            ChangeEvent::UnitChanged(_) => true,
+            ChangeEvent::UnitTreeChanged(id)
+            | ChangeEvent::LibraryChanged(id)
+            | ChangeEvent::BranchSetChanged(id)
+            | ChangeEvent::FormationLevelsChanged(id)
+            | ChangeEvent::EquipmentCatalogChanged(id) => id == open,
+        };
+        if concerns_open {
+            self.changes = self.changes.saturating_add(1);
+        }
+    }
+
+    /// Count the rows a formation table save wrote to a unit of the open
+    /// library, one change per row inserted, updated or deleted
+    pub fn record_rows(&mut self, changes: RowChanges) {
+        if self.library_id.is_some() {
+            let rows = u32::try_from(changes.total()).unwrap_or(u32::MAX);
+            self.changes = self.changes.saturating_add(rows);
+        }
+    }
+
+    /// The open library was saved
+    pub fn saved(&mut self) {
+        self.changes = 0;
+    }
+
+    /// The user chose to leave the changes unsaved
+    pub fn discarded(&mut self) {
+        self.changes = 0;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.changes > 0
+    }
+
+    /// Changes counted since the library was opened or saved
+    pub fn changes(&self) -> u32 {
+        self.changes
+    }
+
+    /// Library the changes belong to
+    pub fn library_id(&self) -> Option<i64> {
+        self.library_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nothing_is_dirty_without_an_open_library() {
+        let mut dirty = DirtyState::default();
+        dirty.record(&ChangeEvent::UnitChanged(1));
+        dirty.record(&ChangeEvent::LibraryChanged(1));
+        assert!(!dirty.is_dirty());
+    }
+
+    #[test]
+    fn test_changes_to_the_open_library_mark_dirty() {
+        let mut dirty = DirtyState::default();
+        dirty.open(Some(3));
+        dirty.record(&ChangeEvent::LibraryChanged(4));
+        dirty.record(&ChangeEvent::BranchSetChanged(4));
+        dirty.record(&ChangeEvent::UnitTreeChanged(4));
+        assert!(!dirty.is_dirty());
+        dirty.record(&ChangeEvent::UnitChanged(10));
+        dirty.record(&ChangeEvent::UnitTreeChanged(3));
+        dirty.record(&ChangeEvent::FormationLevelsChanged(3));
+        assert!(dirty.is_dirty());
+        assert_eq!(dirty.changes(), 3);
+    }
+
+    #[test]
+    fn test_save_and_discard_clear() {
+        let mut dirty = DirtyState::default();
+        dirty.open(Some(3));
+        dirty.record(&ChangeEvent::LibraryChanged(3));
+        dirty.saved();
+        assert!(!dirty.is_dirty());
+        dirty.record(&ChangeEvent::EquipmentCatalogChanged(3));
+        assert!(dirty.is_dirty());
+        dirty.discarded();
+        assert!(!dirty.is_dirty());
+        assert_eq!(dirty.library_id(), Some(3));
+    }
+
+    #[test]
+    fn test_row_saves_count_every_row_written() {
+        let mut dirty = DirtyState::default();
+        dirty.record_rows(RowChanges { inserted: 1, updated: 0, deleted: 0 });
+        assert!(!dirty.is_dirty());
+        dirty.open(Some(3));
+        dirty.record_rows(RowChanges::default());
+        assert!(!dirty.is_dirty());
+        dirty.record_rows(RowChanges { inserted: 2, updated: 1, deleted: 1 });
+        assert_eq!(dirty.changes(), 4);
+    }
+
+    #[test]
+    fn test_opening_another_library_starts_over() {
+        let mut dirty = DirtyState::default();
+        dirty.open(Some(3));
+        dirty.record(&ChangeEvent::UnitChanged(1));
+        dirty.open(Some(3));
+        assert!(dirty.is_dirty());
+        dirty.open(Some(5));
+        assert!(!dirty.is_dirty());
+        assert_eq!(dirty.library_id(), Some(5));
+        dirty.open(None);
+        dirty.record(&ChangeEvent::UnitChanged(1));
+        assert!(!dirty.is_dirty());
+    }
+}
@@ -0,0 +1,247 @@
+//! Finding near-duplicate units within a library
+//!
+//! Imports and copy-paste leave units that differ only in spacing, case or
+//! word order ("1st Rifle Battalion" and "Rifle battalion 1st "). Only units
+//! under the same parent are compared; a pair is reported when the names are
+//! similar, any numbers in them are the same ("1st" and "2nd" battalions are
+//! not duplicates) and the personnel totals are close. Pairs the user marked
+//! as not duplicates are stored and not reported again.
+
+use std::collections::{HashMap, HashSet};
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::db::repositories::{pair_key, IgnoredDuplicateRepo, UnitRepo};
+use crate::models::{walk_units, Equipment, Unit};
+use super::level_mapping::similarity;
+
+/// How alike two units must be to be reported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DuplicateOptions {
+    /// Smallest [`name_similarity`]
+    pub name_threshold: f64,
+    /// Smallest [`personnel_similarity`]
+    pub personnel_threshold: f64,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self {
+            name_threshold: 0.85,
+            personnel_threshold: 0.75,
+        }
+    }
+}
+
+/// What the duplicates dialog shows of one unit
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitSummary {
+    pub id: i64,
+    pub name: String,
+    pub unit_type: String,
+    /// Personnel of the unit and its subordinates
+    pub personnel: usize,
+    /// Items of equipment of the unit and its subordinates
+    pub equipment: usize,
+    /// Direct subordinates
+    pub subunits: usize,
+}
+
+impl UnitSummary {
+    fn of(unit: &Unit, id: i64) -> Self {
+        Self {
+            id,
+            name: unit.name.clone(),
+            unit_type: unit.unit_type.clone(),
+            personnel: unit.total_personnel(),
+            equipment: unit.total_equipment().values().sum(),
+            subunits: unit.children.len(),
+        }
+    }
+}
+
+/// Two units under the same parent that look like the same unit
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePair {
+    /// The unit listed first under the parent
+    pub first: UnitSummary,
+    pub second: UnitSummary,
+    pub name_score: f64,
+    pub personnel_score: f64,
+}
+
+/// Lowercase words of a name in sorted order, so spacing, case, punctuation
+/// and word order do not count
+pub fn normalized_name(name: &str) -> String {
+    let lower = name.to_lowercase().replace('ё', "е");
+    let mut words: Vec<&str> = lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    words.sort_unstable();
+    words.join(" ")
+}
+
+/// Similarity of two names after [`normalized_name`], 0.0..=1.0
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    similarity(&normalized_name(a), &normalized_name(b))
+}
+
+/// The numbers in a name, in sorted order
+fn numbers(name: &str) -> Vec<&str> {
+    let mut numbers: Vec<&str> = name.split(|c: char| !c.is_ascii_digit()).filter(|n| !n.is_empty()).collect();
+    numbers.sort_unstable();
+    numbers
+}
+
+/// Ratio of the smaller personnel total to the larger; 1.0 when both are empty
+pub fn personnel_similarity(a: usize, b: usize) -> f64 {
+    match a.max(b) {
+        0 => 1.0,
+        larger => a.min(b) as f64 / larger as f64,
+    }
+}
+
+/// Near-duplicate pairs among `units` and their subordinates, in tree order.
+/// Only siblings are compared; pairs in `ignored` (as [`pair_key`]s) and units
+/// without an id are skipped.
+pub fn find_duplicates(units: &[Unit], ignored: &HashSet<(i64, i64)>, options: &DuplicateOptions) -> Vec<DuplicatePair> {
+    // Siblings grouped by their parent's position in the walk, in order
+    let mut groups: Vec<Vec<(i64, &Unit)>> = Vec::new();
+    let mut group_of: HashMap<Option<usize>, usize> = HashMap::new();
+    for visit in walk_units(units) {
+        let Some(id) = visit.unit.id else {
+            continue;
+        };
+        let group = *group_of.entry(visit.parent).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push((id, visit.unit));
+    }
+    let mut pairs = Vec::new();
+    for siblings in &groups {
+        for (i, (a_id, a)) in siblings.iter().enumerate() {
+            for (b_id, b) in &siblings[i + 1..] {
+                if ignored.contains(&pair_key(*a_id, *b_id)) || numbers(&a.name) != numbers(&b.name) {
+                    continue;
+                }
+                let name_score = name_similarity(&a.name, &b.name);
+                if name_score < options.name_threshold {
+                    continue;
+                }
+                let (first, second) = (UnitSummary::of(a, *a_id), UnitSummary::of(b, *b_id));
+                let personnel_score = personnel_similarity(first.personnel, second.personnel);
+                if personnel_score >= options.personnel_threshold {
+                    pairs.push(DuplicatePair { first, second, name_score, personnel_score });
+                }
+            }
+        }
+    }
+    pairs
+}
+
+/// Near-duplicate pairs of a library, leaving out the ignored ones
+pub fn find_library_duplicates(conn: &Connection, library_id: i64, options: &DuplicateOptions) -> Result<Vec<DuplicatePair>> {
+    let units = UnitRepo::new(conn).get_by_library_id(library_id)?;
+    let ignored = IgnoredDuplicateRepo::new(conn).list(library_id)?;
+    Ok(find_duplicates(&units, &ignored, options))
+}
+
+/// Equipment of a unit with `added` merged in: quantities of items with the
+/// same name (trimmed, case-insensitive) are summed under the kept name, and
+/// other items follow in their order
+pub fn merge_equipment(kept: &[Equipment], added: &[Equipment]) -> Vec<Equipment> {
+    let key = |name: &str| name.trim().to_lowercase();
+    let mut merged = kept.to_vec();
+    for item in added {
+        match merged.iter_mut().find(|e| key(&e.name) == key(&item.name)) {
+            Some(existing) => existing.quantity += item.quantity,
+            None => merged.push(item.clone()),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Personnel;
+
+    fn unit(id: i64, name: &str, personnel: usize) -> Unit {
+        let mut unit = Unit::new(name.to_string(), "Battalion".to_string());
+        unit.id = Some(id);
+        for _ in 0..personnel {
+            unit.add_personnel(Personnel::new("Rifleman".to_string()));
+        }
+        unit
+    }
+
+    fn ids(pairs: &[DuplicatePair]) -> Vec<(i64, i64)> {
+        pairs.iter().map(|p| (p.first.id, p.second.id)).collect()
+    }
+
+    #[test]
+    fn test_name_similarity_ignores_spacing_case_and_word_order() {
+        assert_eq!(name_similarity("1st Rifle Battalion", "1st Rifle Battalion "), 1.0);
+        assert_eq!(name_similarity("1st Rifle Battalion", "rifle battalion, 1st"), 1.0);
+        assert!(name_similarity("1st Rifle Battalion", "1st Rifle Batallion") > DuplicateOptions::default().name_threshold);
+        assert!(name_similarity("1st Rifle Battalion", "1st Tank Regiment") < 0.6);
+        assert_eq!(name_similarity("", ""), 0.0);
+    }
+
+    #[test]
+    fn test_personnel_similarity() {
+        assert_eq!(personnel_similarity(0, 0), 1.0);
+        assert_eq!(personnel_similarity(400, 500), 0.8);
+        assert_eq!(personnel_similarity(500, 400), 0.8);
+        assert_eq!(personnel_similarity(0, 10), 0.0);
+    }
+
+    #[test]
+    fn test_near_duplicate_siblings_are_paired() {
+        let mut regiment = unit(1, "10th Rifle Regiment", 0);
+        regiment.add_child(unit(2, "1st Rifle Battalion", 500));
+        regiment.add_child(unit(3, "2nd Rifle Battalion", 500));
+        regiment.add_child(unit(4, "1st Rifle Battalion ", 480));
+        regiment.add_child(unit(5, "1st rifle battalion", 100));
+        let pairs = find_duplicates(&[regiment], &HashSet::new(), &DuplicateOptions::default());
+        // 2 differs in number, 5 in personnel
+        assert_eq!(ids(&pairs), [(2, 4)]);
+        assert_eq!(pairs[0].name_score, 1.0);
+        assert_eq!(pairs[0].first.personnel, 500);
+        assert_eq!(pairs[0].personnel_score, 0.96);
+    }
+
+    #[test]
+    fn test_only_units_under_the_same_parent_are_compared() {
+        let mut first = unit(1, "1st Regiment", 0);
+        first.add_child(unit(3, "Signal Company", 50));
+        let mut second = unit(2, "2nd Regiment", 0);
+        second.add_child(unit(4, "Signal Company", 50));
+        second.add_child(unit(5, "Signal company", 50));
+        let roots = [first, second, unit(6, "Signal Company", 50)];
+        let pairs = find_duplicates(&roots, &HashSet::new(), &DuplicateOptions::default());
+        assert_eq!(ids(&pairs), [(4, 5)]);
+    }
+
+    #[test]
+    fn test_ignored_pairs_are_not_reported() {
+        let roots = [unit(1, "Signal Company", 50), unit(2, "Signal Company", 50), unit(3, "Signal Company", 50)];
+        let ignored = HashSet::from([pair_key(2, 1)]);
+        let pairs = find_duplicates(&roots, &ignored, &DuplicateOptions::default());
+        assert_eq!(ids(&pairs), [(1, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn test_merge_equipment_sums_same_names() {
+        let kept = [Equipment::new("AK-74".to_string(), 30), Equipment::new("BMP-2".to_string(), 3)];
+        let added = [Equipment::new("bmp-2 ".to_string(), 2), Equipment::new("PKM".to_string(), 4)];
+        let merged = merge_equipment(&kept, &added);
+        assert_eq!(
+            merged,
+            [
+                Equipment::new("AK-74".to_string(), 30),
+                Equipment::new("BMP-2".to_string(), 5),
+                Equipment::new("PKM".to_string(), 4),
+            ]
+        );
+    }
+}
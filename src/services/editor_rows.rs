@@ -0,0 +1,683 @@
+//! Row sets edited in the editor windows, and saving them
+
+use anyhow::{bail, Result};
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::db::repositories::{BranchCategoryRepo, BranchRepo, EquipmentCatalogRepo, FormationLevelRepo, LibraryRepo, UnitRepo};
+use crate::models::{Branch, BranchCategory, CustomFormationLevel, Equipment, EquipmentCatalogEntry, Personnel};
+use crate::services::{ChangeEvent, UnitService};
+
+/// Editor window that edits a whole list of a library at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditorKind {
+    Branches,
+    BranchCategories,
+    FormationLevels,
+    EquipmentCatalog,
+    /// Personnel and equipment rows of the formation with this unit id
+    FormationTable(i64),
+}
+
+impl EditorKind {
+    /// Window title in English, also the translation key
+    pub fn label(self) -> &'static str {
+        match self {
+            EditorKind::Branches => "Branches of service",
+            EditorKind::BranchCategories => "Branch categories",
+            EditorKind::FormationLevels => "Formation levels",
+            EditorKind::EquipmentCatalog => "Equipment and Vehicles",
+            EditorKind::FormationTable(_) => "Formation Table",
+        }
+    }
+
+    /// Event published once the editor's rows are saved
+    pub fn change_event(self, library_id: i64) -> ChangeEvent {
+        match self {
+            EditorKind::Branches | EditorKind::BranchCategories => ChangeEvent::BranchSetChanged(library_id),
+            EditorKind::FormationLevels => ChangeEvent::FormationLevelsChanged(library_id),
+            EditorKind::EquipmentCatalog => ChangeEvent::EquipmentCatalogChanged(library_id),
+            EditorKind::FormationTable(unit_id) => ChangeEvent::UnitChanged(unit_id),
+        }
+    }
+}
+
+/// Every row of one editor, in list order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "editor", content = "rows", rename_all = "snake_case")]
+pub enum EditorRows {
+    Branches(Vec<Branch>),
+    BranchCategories(Vec<BranchCategory>),
+    FormationLevels(Vec<CustomFormationLevel>),
+    EquipmentCatalog(Vec<EquipmentCatalogEntry>),
+    /// Rows with their stored ids, `None` for rows added in the editor
+    FormationTable {
+        unit_id: i64,
+        personnel: Vec<(Option<i64>, Personnel)>,
+        equipment: Vec<(Option<i64>, Equipment)>,
+    },
+}
+
+impl EditorRows {
+    pub fn kind(&self) -> EditorKind {
+        match self {
+            EditorRows::Branches(_) => EditorKind::Branches,
+            EditorRows::BranchCategories(_) => EditorKind::BranchCategories,
+            EditorRows::FormationLevels(_) => EditorKind::FormationLevels,
+            EditorRows::EquipmentCatalog(_) => EditorKind::EquipmentCatalog,
+            EditorRows::FormationTable { unit_id, .. } => EditorKind::FormationTable(*unit_id),
+        }
+    }
+
+    /// The library's stored rows of the given editor, in list order
+    pub fn load(conn: &Connection, library_id: i64, kind: EditorKind) -> Result<Self> {
+        Ok(match kind {
+            EditorKind::Branches => EditorRows::Branches(BranchRepo::new(conn).list_by_library(library_id)?),
+            EditorKind::BranchCategories => {
+                EditorRows::BranchCategories(BranchCategoryRepo::new(conn).list_by_library(library_id)?)
+            }
+            EditorKind::FormationLevels => {
+                EditorRows::FormationLevels(FormationLevelRepo::new(conn).list_by_library(library_id)?)
+            }
+            EditorKind::EquipmentCatalog => {
+                EditorRows::EquipmentCatalog(EquipmentCatalogRepo::new(conn).list_by_library(library_id)?)
+            }
+            EditorKind::FormationTable(unit_id) => {
+                let repo = UnitRepo::new(conn);
+                fn stored<T>((id, row): (i64, T)) -> (Option<i64>, T) {
+                    (Some(id), row)
+                }
+                EditorRows::FormationTable {
+                    unit_id,
+                    personnel: repo.list_personnel_rows(unit_id)?.into_iter().map(stored).collect(),
+                    equipment: repo.list_equipment_rows(unit_id)?.into_iter().map(stored).collect(),
+                }
+            }
+        })
+    }
+
+    pub fn row_count(&self) -> usize {
+        match self {
+            EditorRows::Branches(rows) => rows.len(),
+            EditorRows::BranchCategories(rows) => rows.len(),
+            EditorRows::FormationLevels(rows) => rows.len(),
+            EditorRows::EquipmentCatalog(rows) => rows.len(),
+            EditorRows::FormationTable { personnel, equipment, .. } => personnel.len() + equipment.len(),
+        }
+    }
+
+    /// Row count and a hash of every field, ids included
+    pub fn fingerprint(&self) -> RowsFingerprint {
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_vec(self).unwrap_or_default().hash(&mut hasher);
+        RowsFingerprint {
+            row_count: self.row_count(),
+            hash: hasher.finish(),
+        }
+    }
+}
+
+/// Summary of a library's stored list, taken when an editor loads it so that
+/// saving can tell whether another window changed the list meanwhile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowsFingerprint {
+    pub row_count: usize,
+    pub hash: u64,
+}
+
+/// Fingerprint of the library's stored list for the given editor
+pub fn stored_fingerprint(conn: &Connection, library_id: i64, kind: EditorKind) -> Result<RowsFingerprint> {
+    Ok(EditorRows::load(conn, library_id, kind)?.fingerprint())
+}
+
+/// Replace the library's list with `rows` in one transaction. Row ids and
+/// library ids are ignored, except that formation levels and branch
+/// categories keep the ids of stored rows (see [`save_formation_levels`] and
+/// [`save_branch_categories`]); positions become the sort order. Branch
+/// categories that no longer exist in the library are dropped, and catalog
+/// rows with neither name are skipped. A formation table writes only the
+/// rows that differ from the stored ones ([`UnitService::save_unit_rows`]),
+/// and only to a formation of the library.
+pub fn save_editor_rows(conn: &Connection, library_id: i64, rows: &EditorRows) -> Result<()> {
+    if let EditorRows::FormationTable { unit_id, personnel, equipment } = rows {
+        if UnitRepo::new(conn).get_placement(*unit_id)?.map(|(library, _)| library) != Some(library_id) {
+            bail!("Formation {} not found in library {}", unit_id, library_id);
+        }
+        UnitService::new(conn).save_unit_rows(*unit_id, personnel, equipment)?;
+        return Ok(());
+    }
+    let tx = begin_library_save(conn, library_id)?;
+    match rows {
+        EditorRows::Branches(branches) => write_branches(conn, library_id, branches)?,
+        EditorRows::BranchCategories(categories) => write_branch_categories(conn, library_id, categories)?,
+        EditorRows::FormationLevels(levels) => write_formation_levels(conn, library_id, levels, &LevelRemap::new())?,
+        EditorRows::EquipmentCatalog(entries) => {
+            let repo = EquipmentCatalogRepo::new(conn);
+            repo.delete_by_library(library_id)?;
+            for (i, e) in entries.iter().enumerate() {
+                let mut entry = EquipmentCatalogEntry::new(
+                    library_id,
+                    e.name_ru.trim().to_string(),
+                    e.name_en.trim().to_string(),
+                    e.category,
+                );
+                if entry.name_ru.is_empty() && entry.name_en.is_empty() {
+                    continue;
+                }
+                entry.aliases = e.aliases.clone();
+                entry.normalize_aliases();
+                entry.sort_order = i as i64;
+                repo.create(&mut entry)?;
+            }
+        }
+        // Saved above, in a transaction of their own
+        EditorRows::FormationTable { .. } => {}
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// A stored formation level missing from a new level list while units still use it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrphanedLevel {
+    pub level: CustomFormationLevel,
+    pub unit_count: usize,
+}
+
+/// Where the units of dropped formation levels go: old level id to a position
+/// in the new level list, or `None` to leave them without a level. Dropped
+/// levels missing from the map lose their units' references as well.
+pub type LevelRemap = HashMap<i64, Option<usize>>;
+
+/// Stored levels of the library that saving `levels` would delete although
+/// units still point at them, in the library's list order. A level is kept
+/// when a row of `levels` carries its id.
+pub fn orphaned_levels(conn: &Connection, library_id: i64, levels: &[CustomFormationLevel]) -> Result<Vec<OrphanedLevel>> {
+    let kept: HashSet<i64> = levels.iter().filter_map(|l| l.id).collect();
+    let counts = UnitRepo::new(conn).count_by_formation_level(library_id)?;
+    Ok(FormationLevelRepo::new(conn)
+        .list_by_library(library_id)?
+        .into_iter()
+        .filter_map(|level| {
+            let id = level.id?;
+            let unit_count = counts.get(&id).copied().unwrap_or(0);
+            (!kept.contains(&id) && unit_count > 0).then_some(OrphanedLevel { level, unit_count })
+        })
+        .collect())
+}
+
+/// Default mapping for orphaned levels: the first new level at the same
+/// standard level, otherwise none
+pub fn suggest_level_remap(orphans: &[OrphanedLevel], levels: &[CustomFormationLevel]) -> LevelRemap {
+    orphans
+        .iter()
+        .filter_map(|o| {
+            let target = levels
+                .iter()
+                .position(|l| l.standard_level_ordinal == o.level.standard_level_ordinal);
+            o.level.id.map(|id| (id, target))
+        })
+        .collect()
+}
+
+/// Replace the library's formation levels with `levels` and move the units of
+/// dropped levels as `remap` says, in one transaction. Rows carrying the id of
+/// a stored level update it in place, so its units keep their level.
+pub fn save_formation_levels(
+    conn: &Connection,
+    library_id: i64,
+    levels: &[CustomFormationLevel],
+    remap: &LevelRemap,
+) -> Result<()> {
+    let tx = begin_library_save(conn, library_id)?;
+    write_formation_levels(conn, library_id, levels, remap)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// A stored branch category missing from a new category list while branches
+/// still belong to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferencedCategory {
+    pub category: BranchCategory,
+    /// The category's branches, in the library's list order
+    pub branches: Vec<Branch>,
+}
+
+/// Stored categories of the library that saving `categories` would delete
+/// although branches still belong to them, in the library's list order. A
+/// category is kept when a row of `categories` carries its id.
+pub fn referenced_categories(
+    conn: &Connection,
+    library_id: i64,
+    categories: &[BranchCategory],
+) -> Result<Vec<ReferencedCategory>> {
+    let kept: HashSet<i64> = categories.iter().filter_map(|c| c.id).collect();
+    let branch_repo = BranchRepo::new(conn);
+    let mut referenced = Vec::new();
+    let mut branches = None;
+    for category in BranchCategoryRepo::new(conn).list_by_library(library_id)? {
+        let Some(id) = category.id.filter(|id| !kept.contains(id)) else {
+            continue;
+        };
+        if branch_repo.count_by_category(id)? == 0 {
+            continue;
+        }
+        let all: &Vec<Branch> = match &mut branches {
+            Some(all) => all,
+            None => branches.insert(branch_repo.list_by_library(library_id)?),
+        };
+        referenced.push(ReferencedCategory {
+            branches: all.iter().filter(|b| b.category_id == Some(id)).cloned().collect(),
+            category,
+        });
+    }
+    Ok(referenced)
+}
+
+/// Replace the library's branch categories with `categories` in one
+/// transaction. Rows carrying the id of a stored category update it in
+/// place, so its branches stay in it; branches of removed categories are
+/// left without a category.
+pub fn save_branch_categories(conn: &Connection, library_id: i64, categories: &[BranchCategory]) -> Result<()> {
+    let tx = begin_library_save(conn, library_id)?;
+    write_branch_categories(conn, library_id, categories)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Replace the library's branches with `branches` in one transaction; nothing
+/// is written when any part fails. Branches keep only categories the library
+/// has, and their positions become the sort order.
+pub fn save_branches(conn: &Connection, library_id: i64, branches: &[Branch]) -> Result<()> {
+    let tx = begin_library_save(conn, library_id)?;
+    write_branches(conn, library_id, branches)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Save branches like [`save_editor_rows`], but first create `new_categories`
+/// in the same transaction. Their ids are placeholders that `branches` may
+/// refer to; returns the stored id for each placeholder.
+pub fn save_branches_with_categories(
+    conn: &Connection,
+    library_id: i64,
+    new_categories: &[BranchCategory],
+    branches: &[Branch],
+) -> Result<HashMap<i64, i64>> {
+    let tx = begin_library_save(conn, library_id)?;
+    let repo = BranchCategoryRepo::new(conn);
+    let mut stored_ids = HashMap::new();
+    for c in new_categories {
+        let mut category = c.clone();
+        category.id = None;
+        category.library_id = library_id;
+        repo.create(&mut category)?;
+        if let (Some(placeholder), Some(id)) = (c.id, category.id) {
+            stored_ids.insert(placeholder, id);
+        }
+    }
+    let branches: Vec<Branch> = branches
+        .iter()
+        .map(|b| Branch {
+            category_id: b.category_id.map(|id| stored_ids.get(&id).copied().unwrap_or(id)),
+            ..b.clone()
+        })
+        .collect();
+    write_branches(conn, library_id, &branches)?;
+    tx.commit()?;
+    Ok(stored_ids)
+}
+
+/// Start the transaction of a save, failing when the library does not exist
+fn begin_library_save(conn: &Connection, library_id: i64) -> Result<Transaction<'_>> {
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    if LibraryRepo::new(conn).get_by_id(library_id)?.is_none() {
+        anyhow::bail!("Library {} not found", library_id);
+    }
+    Ok(tx)
+}
+
+/// References to categories the library does not have are dropped
+fn write_branches(conn: &Connection, library_id: i64, branches: &[Branch]) -> Result<()> {
+    let categories: Vec<i64> = BranchCategoryRepo::new(conn)
+        .list_by_library(library_id)?
+        .into_iter()
+        .filter_map(|c| c.id)
+        .collect();
+    let repo = BranchRepo::new(conn);
+    repo.delete_by_library(library_id)?;
+    for (i, b) in branches.iter().enumerate() {
+        let category_id = b.category_id.filter(|id| categories.contains(id));
+        let mut branch = Branch::with_category(library_id, category_id, b.name_ru.clone(), b.name_en.clone());
+        branch.sort_order = i as i64;
+        repo.create(&mut branch)?;
+    }
+    Ok(())
+}
+
+fn write_branch_categories(conn: &Connection, library_id: i64, categories: &[BranchCategory]) -> Result<()> {
+    let repo = BranchCategoryRepo::new(conn);
+    let stored: Vec<i64> = repo.list_by_library(library_id)?.into_iter().filter_map(|c| c.id).collect();
+    let mut saved_ids = Vec::with_capacity(categories.len());
+    for (i, c) in categories.iter().enumerate() {
+        let mut category = BranchCategory::new(library_id, c.name_ru.clone(), c.name_en.clone());
+        category.sort_order = i as i64;
+        // A stored id is taken by its first row only; copies become new categories
+        match c.id.filter(|id| stored.contains(id) && !saved_ids.contains(id)) {
+            Some(id) => {
+                category.id = Some(id);
+                repo.update(&category)?;
+                repo.set_sort_order(id, category.sort_order)?;
+            }
+            None => repo.create(&mut category)?,
+        }
+        saved_ids.extend(category.id);
+    }
+
+    let branches = BranchRepo::new(conn);
+    for id in stored.into_iter().filter(|id| !saved_ids.contains(id)) {
+        branches.clear_category(id)?;
+        repo.delete(id)?;
+    }
+    Ok(())
+}
+
+fn write_formation_levels(
+    conn: &Connection,
+    library_id: i64,
+    levels: &[CustomFormationLevel],
+    remap: &LevelRemap,
+) -> Result<()> {
+    let repo = FormationLevelRepo::new(conn);
+    let stored: Vec<i64> = repo.list_by_library(library_id)?.into_iter().filter_map(|l| l.id).collect();
+    let mut saved_ids = Vec::with_capacity(levels.len());
+    for (i, l) in levels.iter().enumerate() {
+        let mut level = l.clone();
+        level.library_id = library_id;
+        level.sort_order = i as i64;
+        // A stored id is taken by its first row only; copies become new levels
+        match level.id.filter(|id| stored.contains(id) && !saved_ids.contains(id)) {
+            Some(id) => {
+                repo.update(&level)?;
+                repo.set_sort_order(id, level.sort_order)?;
+            }
+            None => {
+                level.id = None;
+                repo.create(&mut level)?;
+            }
+        }
+        saved_ids.extend(level.id);
+    }
+
+    let units = UnitRepo::new(conn);
+    for id in stored.into_iter().filter(|id| !saved_ids.contains(id)) {
+        if let Some(target) = remap.get(&id) {
+            // `saved_ids` lines up with `levels`: every row ends up with an id
+            units.reassign_formation_level(library_id, id, target.and_then(|i| saved_ids.get(i).copied()))?;
+        }
+        repo.delete(id)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::models::{EquipmentCategory, Library, Unit};
+
+    fn library(db: &Database) -> i64 {
+        let mut library = Library::new("Test".to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        library.id.unwrap()
+    }
+
+    #[test]
+    fn test_save_replaces_rows_in_order() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let mut category = BranchCategory::new(lib_id, "Боевые".to_string(), "Combat".to_string());
+        BranchCategoryRepo::new(db.conn()).create(&mut category).unwrap();
+        let mut old = Branch::new(lib_id, "Старый".to_string(), "Old".to_string());
+        BranchRepo::new(db.conn()).create(&mut old).unwrap();
+
+        let rows = EditorRows::Branches(vec![
+            Branch::with_category(99, category.id, "Танки".to_string(), "Armor".to_string()),
+            Branch::with_category(99, Some(12345), "Пехота".to_string(), "Infantry".to_string()),
+        ]);
+        save_editor_rows(db.conn(), lib_id, &rows).unwrap();
+
+        let saved = BranchRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        let names: Vec<&str> = saved.iter().map(|b| b.name_en.as_str()).collect();
+        assert_eq!(names, ["Armor", "Infantry"]);
+        assert_eq!(saved[0].category_id, category.id);
+        assert_eq!(saved[1].category_id, None);
+    }
+
+    #[test]
+    fn test_save_skips_blank_catalog_rows() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let mut ak = EquipmentCatalogEntry::new(lib_id, " АК-74 ".to_string(), "AK-74".to_string(), EquipmentCategory::SmallArms);
+        ak.aliases = vec![" ak-74".to_string(), "Калашников ".to_string()];
+        let rows = EditorRows::EquipmentCatalog(vec![
+            ak,
+            EquipmentCatalogEntry::new(lib_id, " ".to_string(), "".to_string(), EquipmentCategory::Other),
+        ]);
+        save_editor_rows(db.conn(), lib_id, &rows).unwrap();
+        let saved = EquipmentCatalogRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].name_ru, "АК-74");
+        assert_eq!(saved[0].aliases, vec!["Калашников"]);
+    }
+
+    #[test]
+    fn test_save_for_missing_library_fails() {
+        let db = Database::open_in_memory().unwrap();
+        let rows = EditorRows::BranchCategories(vec![BranchCategory::new(1, "ПВО".to_string(), "Air defense".to_string())]);
+        assert!(save_editor_rows(db.conn(), 42, &rows).is_err());
+        let count: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM branch_categories", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    /// Stored levels with one unit at each
+    fn levels_with_units(db: &Database, lib_id: i64, levels: &[(&str, i32)]) -> Vec<(i64, i64)> {
+        let level_repo = FormationLevelRepo::new(db.conn());
+        let unit_repo = UnitRepo::new(db.conn());
+        levels
+            .iter()
+            .enumerate()
+            .map(|(i, (name, ordinal))| {
+                let mut level = CustomFormationLevel::new(lib_id, name.to_string(), String::new(), *ordinal);
+                level.sort_order = i as i64;
+                level_repo.create(&mut level).unwrap();
+                let mut unit = Unit::new(format!("Unit {}", name), name.to_string());
+                unit_repo.create(lib_id, &mut unit).unwrap();
+                unit_repo.set_formation_level(unit.id.unwrap(), level.id).unwrap();
+                (level.id.unwrap(), unit.id.unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_replacing_level_set_remaps_units() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let old = levels_with_units(&db, lib_id, &[("отделение", 1), ("взвод", 3), ("батальон", 5)]);
+        let unit_repo = UnitRepo::new(db.conn());
+        let mut second = Unit::new("2-й взвод".to_string(), "взвод".to_string());
+        unit_repo.create(lib_id, &mut second).unwrap();
+        unit_repo.set_formation_level(second.id.unwrap(), Some(old[1].0)).unwrap();
+
+        let nato = vec![
+            CustomFormationLevel::new(lib_id, String::new(), "Section".to_string(), 1),
+            CustomFormationLevel::new(lib_id, String::new(), "Platoon".to_string(), 3),
+            CustomFormationLevel::new(lib_id, String::new(), "Company".to_string(), 4),
+        ];
+        let orphans = orphaned_levels(db.conn(), lib_id, &nato).unwrap();
+        let counts: Vec<(&str, usize)> = orphans.iter().map(|o| (o.level.name_ru.as_str(), o.unit_count)).collect();
+        assert_eq!(counts, [("отделение", 1), ("взвод", 2), ("батальон", 1)]);
+
+        let mut remap = suggest_level_remap(&orphans, &nato);
+        assert_eq!(remap[&old[0].0], Some(0));
+        assert_eq!(remap[&old[1].0], Some(1));
+        assert_eq!(remap[&old[2].0], None);
+        // The battalion is mapped onto the company by hand
+        remap.insert(old[2].0, Some(2));
+        save_formation_levels(db.conn(), lib_id, &nato, &remap).unwrap();
+
+        let saved = FormationLevelRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        let names: Vec<&str> = saved.iter().map(|l| l.name_en.as_str()).collect();
+        assert_eq!(names, ["Section", "Platoon", "Company"]);
+        let level_of = |unit_id: i64| unit_repo.formation_level(unit_id).unwrap();
+        assert_eq!(level_of(old[0].1), saved[0].id);
+        assert_eq!(level_of(old[1].1), saved[1].id);
+        assert_eq!(level_of(second.id.unwrap()), saved[1].id);
+        assert_eq!(level_of(old[2].1), saved[2].id);
+    }
+
+    #[test]
+    fn test_unassigned_and_unmapped_levels_leave_units_without_level() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let old = levels_with_units(&db, lib_id, &[("взвод", 3), ("рота", 4)]);
+        let new = vec![CustomFormationLevel::new(lib_id, String::new(), "Platoon".to_string(), 3)];
+        let remap = LevelRemap::from([(old[0].0, None)]);
+        save_formation_levels(db.conn(), lib_id, &new, &remap).unwrap();
+
+        let unit_repo = UnitRepo::new(db.conn());
+        assert_eq!(unit_repo.formation_level(old[0].1).unwrap(), None);
+        assert_eq!(unit_repo.formation_level(old[1].1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_save_keeps_stored_levels_in_place() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let old = levels_with_units(&db, lib_id, &[("взвод", 3), ("рота", 4)]);
+        let mut stored = FormationLevelRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        stored[0].name_en = "Platoon".to_string();
+        stored.swap(0, 1);
+        assert!(orphaned_levels(db.conn(), lib_id, &stored).unwrap().is_empty());
+
+        save_editor_rows(db.conn(), lib_id, &EditorRows::FormationLevels(stored)).unwrap();
+        let saved = FormationLevelRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        assert_eq!(saved.iter().map(|l| l.id.unwrap()).collect::<Vec<_>>(), [old[1].0, old[0].0]);
+        assert_eq!(saved[1].name_en, "Platoon");
+        assert_eq!(UnitRepo::new(db.conn()).formation_level(old[0].1).unwrap(), Some(old[0].0));
+    }
+
+    #[test]
+    fn test_fingerprint_notices_saves_by_other_windows() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let rows = |names: &[&str]| {
+            EditorRows::BranchCategories(
+                names.iter().map(|n| BranchCategory::new(lib_id, n.to_string(), String::new())).collect(),
+            )
+        };
+        save_editor_rows(db.conn(), lib_id, &rows(&["Боевые", "Тыл"])).unwrap();
+        let loaded = stored_fingerprint(db.conn(), lib_id, EditorKind::BranchCategories).unwrap();
+        assert_eq!(loaded.row_count, 2);
+        assert_eq!(stored_fingerprint(db.conn(), lib_id, EditorKind::BranchCategories).unwrap(), loaded);
+        // Other lists of the library do not count
+        let branches = EditorRows::Branches(vec![Branch::new(lib_id, "Пехота".to_string(), String::new())]);
+        save_editor_rows(db.conn(), lib_id, &branches).unwrap();
+        assert_eq!(stored_fingerprint(db.conn(), lib_id, EditorKind::BranchCategories).unwrap(), loaded);
+
+        // Same number of rows, one renamed
+        let mut stored = EditorRows::load(db.conn(), lib_id, EditorKind::BranchCategories).unwrap();
+        if let EditorRows::BranchCategories(ref mut categories) = stored {
+            categories[1].name_ru = "Тыловые".to_string();
+        }
+        save_editor_rows(db.conn(), lib_id, &stored).unwrap();
+        let renamed = stored_fingerprint(db.conn(), lib_id, EditorKind::BranchCategories).unwrap();
+        assert_eq!(renamed.row_count, 2);
+        assert_ne!(renamed, loaded);
+
+        save_editor_rows(db.conn(), lib_id, &rows(&["Боевые"])).unwrap();
+        assert_eq!(stored_fingerprint(db.conn(), lib_id, EditorKind::BranchCategories).unwrap().row_count, 1);
+    }
+
+    #[test]
+    fn test_rows_serialize_with_editor_tag() {
+        let rows = EditorRows::BranchCategories(vec![BranchCategory::new(1, "ПВО".to_string(), "Air defense".to_string())]);
+        let json = serde_json::to_value(&rows).unwrap();
+        assert_eq!(json["editor"], "branch_categories");
+        assert_eq!(serde_json::from_value::<EditorRows>(json).unwrap(), rows);
+    }
+
+    #[test]
+    fn test_formation_table_rows_journal_and_save() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let other_id = library(&db);
+        let repo = UnitRepo::new(db.conn());
+        let mut unit = Unit::new("A Company".to_string(), "Company".to_string());
+        unit.personnel = vec![Personnel::new("Commander".to_string())];
+        unit.equipment = vec![Equipment::new("M4".to_string(), 10), Equipment::new("HMMWV".to_string(), 2)];
+        repo.create(lib_id, &mut unit).unwrap();
+        let unit_id = unit.id.unwrap();
+
+        let kind = EditorKind::FormationTable(unit_id);
+        let loaded = EditorRows::load(db.conn(), lib_id, kind).unwrap();
+        assert_eq!(loaded.kind(), kind);
+        assert_eq!(loaded.row_count(), 3);
+        let json = serde_json::to_value(&loaded).unwrap();
+        assert_eq!(json["editor"], "formation_table");
+        assert_eq!(serde_json::from_value::<EditorRows>(json).unwrap(), loaded);
+
+        let EditorRows::FormationTable { personnel, mut equipment, .. } = loaded else {
+            panic!("not a formation table");
+        };
+        equipment[0].1.quantity = 12;
+        equipment.push((None, Equipment::new("M249".to_string(), 1)));
+        let edited = EditorRows::FormationTable { unit_id, personnel, equipment };
+        assert!(save_editor_rows(db.conn(), other_id, &edited).is_err());
+        save_editor_rows(db.conn(), lib_id, &edited).unwrap();
+        let saved: Vec<(String, usize)> =
+            repo.list_equipment_rows(unit_id).unwrap().into_iter().map(|(_, e)| (e.name, e.quantity)).collect();
+        assert_eq!(saved, [("M4".to_string(), 12), ("HMMWV".to_string(), 2), ("M249".to_string(), 1)]);
+        assert_eq!(stored_fingerprint(db.conn(), lib_id, kind).unwrap().row_count, 4);
+    }
+
+    #[test]
+    fn test_save_branches_with_categories() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = library(&db);
+        let mut pending = BranchCategory::new(lib_id, "ПВО".to_string(), "Air defense".to_string());
+        pending.id = Some(-2);
+        let branches = vec![
+            Branch::with_category(lib_id, Some(-2), "ПВО".to_string(), "Air defense".to_string()),
+            Branch::new(lib_id, "Пехота".to_string(), "Infantry".to_string()),
+        ];
+        let stored_ids = save_branches_with_categories(db.conn(), lib_id, &[pending.clone()], &branches).unwrap();
+        let categories = BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        assert_eq!(categories.len(), 1);
+        assert_eq!(stored_ids.get(&-2).copied(), categories[0].id);
+        let saved = BranchRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        assert_eq!(saved[0].category_id, categories[0].id);
+        assert_eq!(saved[1].category_id, None);
+
+        // A failed branch write leaves no category behind
+        db.conn()
+            .execute_batch(
+                "CREATE TRIGGER fail_branch BEFORE INSERT ON branches WHEN NEW.name_en = 'Broken'
+                 BEGIN SELECT RAISE(ABORT, 'forced failure'); END",
+            )
+            .unwrap();
+        let mut other = BranchCategory::new(lib_id, "Связь".to_string(), "Signals".to_string());
+        other.id = Some(-2);
+        let broken = vec![Branch::with_category(lib_id, Some(-2), "Сломано".to_string(), "Broken".to_string())];
+        assert!(save_branches_with_categories(db.conn(), lib_id, &[other], &broken).is_err());
+        assert_eq!(BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! Change-notification bus
+//!
+//! Mutating operations publish typed events; views and caches subscribe and
+//! refresh only what the event affects instead of reloading everything.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// A change to persisted data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeEvent {
+    /// Personnel or equipment of a unit changed; carries the unit id
+    UnitChanged(i64),
+    /// Units of a library were added, deleted, moved or merged, or had their
+    /// name, type or level changed; carries the library id
+    UnitTreeChanged(i64),
+    /// Library metadata changed, or the library was created/deleted
+    LibraryChanged(i64),
+    /// Branches or branch categories of a library changed
+    BranchSetChanged(i64),
+    /// Formation levels of a library (names, expected sizes) changed
+    FormationLevelsChanged(i64),
+    /// Equipment catalog of a library changed
+    EquipmentCatalogChanged(i64),
+}
+
+/// View or cache that may need refreshing after a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RefreshTarget {
+    /// Libraries sidebar (names, counts)
+    LibrariesList,
+    /// Formations tree of the current library (names, totals)
+    FormationsTree,
+    /// Personnel and equipment totals of the formations tree, updated in
+    /// place without rebuilding the tree
+    FormationCounts,
+}
+
+impl ChangeEvent {
+    /// Views/caches invalidated by this event, given the currently open library.
+    pub fn refresh_targets(&self, current_library_id: Option<i64>) -> Vec<RefreshTarget> {
+        match *self {
+            ChangeEvent::UnitChanged(_) => vec![RefreshTarget::FormationCounts],
+            ChangeEvent::UnitTreeChanged(id) | ChangeEvent::LibraryChanged(id) => {
+                let mut targets = vec![RefreshTarget::LibrariesList];
+                if current_library_id == Some(id) {
+                    targets.push(RefreshTarget::FormationsTree);
+                }
+                targets
+            }
+            ChangeEvent::BranchSetChanged(id) | ChangeEvent::FormationLevelsChanged(id) => {
+                if current_library_id == Some(id) {
+                    vec![RefreshTarget::FormationsTree]
+                } else {
+                    Vec::new()
+                }
+            }
+            // Unit equipment does not reference the catalog yet
+            ChangeEvent::EquipmentCatalogChanged(_) => Vec::new(),
+        }
+    }
+}
+
+type Handler = dyn Fn(&ChangeEvent);
+
+/// Keeps a subscription alive; dropping it unsubscribes.
+pub struct Subscription {
+    _handler: Rc<Handler>,
+}
+
+/// Single-threaded publish/subscribe bus holding weak references to handlers
+#[derive(Default)]
+pub struct ChangeBus {
+    subscribers: RefCell<Vec<Weak<Handler>>>,
+}
+
+impl ChangeBus {
+    /// Create an empty bus
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler; it stays registered while the returned subscription lives.
+    pub fn subscribe<F>(&self, handler: F) -> Subscription
+    where
+        F: Fn(&ChangeEvent) + 'static,
+    {
+        let handler: Rc<Handler> = Rc::new(handler);
+        self.subscribers.borrow_mut().push(Rc::downgrade(&handler));
+        Subscription { _handler: handler }
+    }
+
+    /// Deliver an event to all live subscribers. Returns the number of handlers called.
+    ///
+    /// Handlers may subscribe or publish re-entrantly.
+    pub fn publish(&self, event: ChangeEvent) -> usize {
+        let live: Vec<Rc<Handler>> = {
+            let mut subs = self.subscribers.borrow_mut();
+            subs.retain(|w| w.strong_count() > 0);
+            subs.iter().filter_map(|w| w.upgrade()).collect()
+        };
+        for handler in &live {
+            handler(&event);
+        }
+        live.len()
+    }
+
+    /// Number of live subscribers (dead ones are pruned)
+    pub fn subscriber_count(&self) -> usize {
+        let mut subs = self.subscribers.borrow_mut();
+        subs.retain(|w| w.strong_count() > 0);
+        subs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_publish_reaches_subscribers() {
+        let bus = ChangeBus::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let seen_c = seen.clone();
+        let _sub = bus.subscribe(move |e| seen_c.borrow_mut().push(*e));
+        assert_eq!(bus.publish(ChangeEvent::UnitChanged(7)), 1);
+        assert_eq!(bus.publish(ChangeEvent::LibraryChanged(1)), 1);
+        assert_eq!(
+            *seen.borrow(),
+            vec![ChangeEvent::UnitChanged(7), ChangeEvent::LibraryChanged(1)]
+        );
+    }
+
+    #[test]
+    fn test_dropped_subscription_is_cleaned_up() {
+        let bus = ChangeBus::new();
+        let count = Rc::new(Cell::new(0));
+        let c1 = count.clone();
+        let sub1 = bus.subscribe(move |_| c1.set(c1.get() + 1));
+        let c2 = count.clone();
+        let _sub2 = bus.subscribe(move |_| c2.set(c2.get() + 1));
+        assert_eq!(bus.subscriber_count(), 2);
+        drop(sub1);
+        assert_eq!(bus.publish(ChangeEvent::BranchSetChanged(1)), 1);
+        assert_eq!(count.get(), 1);
+        assert_eq!(bus.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn test_reentrant_publish() {
+        let bus = Rc::new(ChangeBus::new());
+        let count = Rc::new(Cell::new(0));
+        let bus_c = Rc::downgrade(&bus);
+        let count_c = count.clone();
+        let _sub = bus.subscribe(move |e| {
+            count_c.set(count_c.get() + 1);
+            if let (ChangeEvent::UnitChanged(_), Some(b)) = (e, bus_c.upgrade()) {
+                b.publish(ChangeEvent::LibraryChanged(1));
+            }
+        });
+        bus.publish(ChangeEvent::UnitChanged(3));
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn test_refresh_targets() {
+        assert_eq!(
+            ChangeEvent::UnitChanged(1).refresh_targets(Some(5)),
+            vec![RefreshTarget::FormationCounts]
+        );
+        assert_eq!(
+            ChangeEvent::UnitTreeChanged(5).refresh_targets(Some(5)),
+            vec![RefreshTarget::LibrariesList, RefreshTarget::FormationsTree]
+        );
+        assert_eq!(
+            ChangeEvent::UnitTreeChanged(4).refresh_targets(Some(5)),
+            vec![RefreshTarget::LibrariesList]
+        );
+        assert_eq!(
+            ChangeEvent::LibraryChanged(5).refresh_targets(Some(5)),
+            vec![RefreshTarget::LibrariesList, RefreshTarget::FormationsTree]
+        );
+        assert_eq!(
+            ChangeEvent::LibraryChanged(4).refresh_targets(Some(5)),
+            vec![RefreshTarget::LibrariesList]
+        );
+        assert!(ChangeEvent::BranchSetChanged(4).refresh_targets(Some(5)).is_empty());
+        assert_eq!(
+            ChangeEvent::BranchSetChanged(5).refresh_targets(Some(5)),
+            vec![RefreshTarget::FormationsTree]
+        );
+        assert!(ChangeEvent::FormationLevelsChanged(4).refresh_targets(Some(5)).is_empty());
+        assert!(ChangeEvent::EquipmentCatalogChanged(5).refresh_targets(Some(5)).is_empty());
+        assert_eq!(
+            ChangeEvent::FormationLevelsChanged(5).refresh_targets(Some(5)),
+            vec![RefreshTarget::FormationsTree]
+        );
+    }
+}
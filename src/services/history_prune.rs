@@ -0,0 +1,200 @@
+//! Pruning of old library versions, and how it coordinates with windows that
+//! read the history meanwhile.
+//!
+//! A prune registers itself for its library in the [`OperationRegistry`], so a
+//! History window can show that maintenance is running and reload when it
+//! ends. Flows that read versions (restore, compare, open as a new library)
+//! hold them in the registry; the prune reads the holds inside its write
+//! transaction and leaves held versions alone.
+
+use anyhow::Result;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::collections::BTreeSet;
+
+use super::operations::OperationRegistry;
+use crate::db::repositories::VersionRepo;
+use crate::models::Snapshot;
+
+/// Name a prune is registered under
+pub const PRUNE_OPERATION: &str = "Prune History";
+
+/// Which versions a prune keeps besides held ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrunePolicy {
+    /// Newest versions always kept (at least one)
+    pub keep_latest: usize,
+}
+
+impl Default for PrunePolicy {
+    fn default() -> Self {
+        Self { keep_latest: 20 }
+    }
+}
+
+/// Ids of the snapshots a prune deletes: all but the newest
+/// `policy.keep_latest`, those with a description and the `held` versions
+pub fn plan_prune(snapshots: &[Snapshot], policy: PrunePolicy, held: &BTreeSet<i64>) -> Vec<i64> {
+    let mut newest_first: Vec<&Snapshot> = snapshots.iter().collect();
+    newest_first.sort_by_key(|s| std::cmp::Reverse(s.version));
+    newest_first
+        .into_iter()
+        .skip(policy.keep_latest.max(1))
+        .filter(|s| s.description.as_deref().is_none_or(|d| d.trim().is_empty()))
+        .filter(|s| !held.contains(&s.version))
+        .filter_map(|s| s.id)
+        .collect()
+}
+
+/// Delete the versions of a library that `policy` does not keep, in one
+/// transaction; returns how many were deleted. Meant for a worker thread with
+/// its own connection: readers on other connections see the history before
+/// or after the prune, never halfway.
+pub fn prune_history(
+    conn: &Connection,
+    registry: &OperationRegistry,
+    library_id: i64,
+    policy: PrunePolicy,
+) -> Result<usize> {
+    let _op = registry.register_for_library(PRUNE_OPERATION, library_id, false);
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let repo = VersionRepo::new(conn);
+    // Holds are read once the write lock is taken: a flow that holds a
+    // version before this point keeps it
+    let planned = plan_prune(&repo.list_by_library(library_id)?, policy, &registry.held_versions(library_id));
+    for &id in &planned {
+        repo.delete(id)?;
+    }
+    tx.commit()?;
+    log::info!("Pruned {} versions of library {}", planned.len(), library_id);
+    Ok(planned.len())
+}
+
+/// Whether a prune of `library_id` is running
+pub fn prune_running(registry: &OperationRegistry, library_id: i64) -> bool {
+    registry.running_for_library(library_id).iter().any(|op| op.name == PRUNE_OPERATION)
+}
+
+/// What a History window polling the registry has to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceChange {
+    None,
+    /// Show the maintenance banner
+    Started,
+    /// Hide the banner and reload the versions
+    Finished,
+}
+
+/// Tracks maintenance of one library between polls of the registry
+#[derive(Debug, Default)]
+pub struct MaintenanceWatch {
+    running: bool,
+}
+
+impl MaintenanceWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Compare with the registry's current state
+    pub fn poll(&mut self, registry: &OperationRegistry, library_id: i64) -> MaintenanceChange {
+        let running = prune_running(registry, library_id);
+        let change = match (self.running, running) {
+            (false, true) => MaintenanceChange::Started,
+            (true, false) => MaintenanceChange::Finished,
+            _ => MaintenanceChange::None,
+        };
+        self.running = running;
+        change
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::models::Library;
+    use crate::services::LibraryService;
+    use std::sync::mpsc;
+
+    fn snapshot(id: i64, version: i64, description: Option<&str>) -> Snapshot {
+        Snapshot {
+            id: Some(id),
+            library_id: 1,
+            version,
+            timestamp: 0,
+            data: String::new(),
+            description: description.map(str::to_string),
+            is_autosave: false,
+        }
+    }
+
+    #[test]
+    fn test_plan_keeps_newest_described_and_held() {
+        let snapshots: Vec<Snapshot> = (1..=6)
+            .map(|v| snapshot(v * 10, v, (v == 2).then_some("Before the reorganization")))
+            .collect();
+        let policy = PrunePolicy { keep_latest: 2 };
+        assert_eq!(plan_prune(&snapshots, policy, &BTreeSet::new()), [40, 30, 10]);
+        assert_eq!(plan_prune(&snapshots, policy, &BTreeSet::from([3])), [40, 10]);
+        // The newest version is kept whatever the policy says
+        assert_eq!(plan_prune(&snapshots[..1], PrunePolicy { keep_latest: 0 }, &BTreeSet::new()), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_prune_respects_holds_taken_before_it() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = service
+            .create_library(Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string()))
+            .unwrap();
+        let lib_id = library.id.unwrap();
+        for _ in 0..5 {
+            service.create_snapshot(lib_id, "").unwrap();
+        }
+        let registry = OperationRegistry::new();
+        let oldest = service.get_library_versions(lib_id).unwrap().iter().map(|s| s.version).min().unwrap();
+        let hold = registry.hold_versions(lib_id, &[oldest]);
+
+        let deleted = prune_history(db.conn(), &registry, lib_id, PrunePolicy { keep_latest: 2 }).unwrap();
+        let left: Vec<i64> = service.get_library_versions(lib_id).unwrap().iter().map(|s| s.version).collect();
+        assert!(left.contains(&oldest));
+        assert_eq!(left.len(), 3, "{:?}", left);
+        assert_eq!(deleted, 3);
+        assert!(registry.is_idle());
+        drop(hold);
+    }
+
+    #[test]
+    fn test_watch_follows_a_long_running_prune() {
+        let registry = OperationRegistry::new();
+        let mut watch = MaintenanceWatch::new();
+        assert_eq!(watch.poll(&registry, 1), MaintenanceChange::None);
+
+        // A fake prune that runs until told to stop
+        let (started_tx, started_rx) = mpsc::channel();
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let worker_registry = registry.clone();
+        let worker = std::thread::spawn(move || {
+            let _op = worker_registry.register_for_library(PRUNE_OPERATION, 1, false);
+            started_tx.send(()).unwrap();
+            stop_rx.recv().unwrap();
+        });
+        started_rx.recv().unwrap();
+
+        let mut other = MaintenanceWatch::new();
+        assert_eq!(other.poll(&registry, 2), MaintenanceChange::None);
+        assert_eq!(watch.poll(&registry, 1), MaintenanceChange::Started);
+        assert!(watch.is_running());
+        assert_eq!(watch.poll(&registry, 1), MaintenanceChange::None);
+
+        stop_tx.send(()).unwrap();
+        worker.join().unwrap();
+        assert_eq!(watch.poll(&registry, 1), MaintenanceChange::Finished);
+        assert!(!watch.is_running());
+        assert_eq!(watch.poll(&registry, 1), MaintenanceChange::None);
+    }
+}
@@ -0,0 +1,384 @@
+//! Lazy mapping of free-text unit types to the library's formation levels
+//!
+//! Unit types were typed by hand long before formation levels existed, so many
+//! of them ("Abteilung", "сводный отряд", "1st Bn") name no level exactly.
+//! [`LevelMappingService::scan_library`] sets the level only where the type
+//! names one outright and queues every other unit with the best suggestion
+//! from [`suggest_level`]; the user confirms or overrides the queue in
+//! Library → Review Level Mappings.
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::db::repositories::{FormationLevelRepo, LevelMappingEntry, LevelMappingRepo, UnitRepo};
+use crate::models::CustomFormationLevel;
+
+/// Scores at or above this are applied without asking
+pub const AUTO_APPLY_SCORE: f64 = 1.0;
+/// Suggestions below this are not offered
+pub const SUGGESTION_MIN_SCORE: f64 = 0.5;
+
+/// Short forms seen in orders of battle, by standard level ordinal
+const ABBREVIATIONS: &[(&str, i32)] = &[
+    ("ft", 0),
+    ("sqd", 1),
+    ("sec", 2),
+    ("plt", 3),
+    ("pl", 3),
+    ("coy", 4),
+    ("co", 4),
+    ("bn", 5),
+    ("btn", 5),
+    ("regt", 6),
+    ("rgt", 6),
+    ("bde", 7),
+    ("div", 8),
+    ("отд", 1),
+    ("взв", 3),
+    ("батр", 4),
+    ("бр", 7),
+    ("див", 8),
+];
+
+/// Lowercase words of a unit type; numbers, ordinals ("1st", "2-й") and
+/// single letters are dropped
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace('ё', "е")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 1 && !w.chars().any(|c| c.is_ascii_digit()))
+        .map(str::to_string)
+        .collect()
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// 1.0 for equal strings down to 0.0 for nothing in common
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/// How well a unit type names a level, 0.0..=1.0. The whole type is compared
+/// with both names of the level; a single word of it ("Rifle Company") or a
+/// known abbreviation ("Bn") counts slightly less than the whole.
+pub fn score_level(unit_type: &str, level: &CustomFormationLevel) -> f64 {
+    let unit_words = words(unit_type);
+    if unit_words.is_empty() {
+        return 0.0;
+    }
+    let whole = unit_words.join(" ");
+    let mut best: f64 = 0.0;
+    for name in [&level.name_en, &level.name_ru] {
+        let name = words(name).join(" ");
+        if name.is_empty() {
+            continue;
+        }
+        best = best.max(similarity(&whole, &name));
+        if unit_words.len() > 1 {
+            for word in &unit_words {
+                best = best.max(similarity(word, &name) * 0.9);
+            }
+        }
+    }
+    for word in &unit_words {
+        if ABBREVIATIONS
+            .iter()
+            .any(|&(abbr, ordinal)| abbr == word && ordinal == level.standard_level_ordinal)
+        {
+            best = best.max(0.85);
+        }
+    }
+    best
+}
+
+/// The best scoring level for a unit type with its score, if any reaches
+/// [`SUGGESTION_MIN_SCORE`]. Ties go to the level listed first.
+pub fn suggest_level<'a>(unit_type: &str, levels: &'a [CustomFormationLevel]) -> Option<(&'a CustomFormationLevel, f64)> {
+    let mut best: Option<(&CustomFormationLevel, f64)> = None;
+    for level in levels {
+        let score = score_level(unit_type, level);
+        if score >= SUGGESTION_MIN_SCORE && best.is_none_or(|(_, s)| score > s) {
+            best = Some((level, score));
+        }
+    }
+    best
+}
+
+/// Outcome of scanning a library
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanOutcome {
+    /// Units whose level was set because their type names it
+    pub mapped: usize,
+    /// Units waiting for review
+    pub pending: usize,
+}
+
+/// Formation level mapping for the units of a library
+pub struct LevelMappingService<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> LevelMappingService<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Map the units without a level: set it where the type names a level,
+    /// queue the rest with a suggestion. Libraries without formation levels are
+    /// left alone. Safe to run again; dismissed entries stay dismissed.
+    pub fn scan_library(&self, library_id: i64) -> Result<ScanOutcome> {
+        let levels = FormationLevelRepo::new(self.conn).list_by_library(library_id)?;
+        let queue = LevelMappingRepo::new(self.conn);
+        if levels.is_empty() {
+            return Ok(ScanOutcome {
+                mapped: 0,
+                pending: queue.count_pending(library_id)?,
+            });
+        }
+        let units = UnitRepo::new(self.conn);
+        let tx = self.conn.unchecked_transaction()?;
+        let mut mapped = 0;
+        for (unit_id, unit_type) in units.list_without_level(library_id)? {
+            match suggest_level(&unit_type, &levels) {
+                Some((level, score)) if score >= AUTO_APPLY_SCORE => {
+                    units.set_formation_level(unit_id, level.id)?;
+                    queue.remove(unit_id)?;
+                    mapped += 1;
+                }
+                suggestion => {
+                    let (level_id, score) = suggestion.map_or((None, 0.0), |(l, s)| (l.id, s));
+                    queue.upsert(library_id, unit_id, &unit_type, level_id, score)?;
+                }
+            }
+        }
+        let pending = queue.count_pending(library_id)?;
+        tx.commit()?;
+        Ok(ScanOutcome { mapped, pending })
+    }
+
+    /// Queued units of a library awaiting review
+    pub fn pending(&self, library_id: i64) -> Result<Vec<LevelMappingEntry>> {
+        LevelMappingRepo::new(self.conn).list_pending(library_id)
+    }
+
+    /// Number of queued units awaiting review
+    pub fn pending_count(&self, library_id: i64) -> Result<usize> {
+        LevelMappingRepo::new(self.conn).count_pending(library_id)
+    }
+
+    /// Apply reviewed decisions in one transaction: `Some(level)` sets the
+    /// unit's level, `None` keeps it without one. Either way the unit no longer
+    /// awaits review. Levels must belong to the library.
+    pub fn apply(&self, library_id: i64, decisions: &[(i64, Option<i64>)]) -> Result<usize> {
+        let levels = FormationLevelRepo::new(self.conn).list_by_library(library_id)?;
+        let units = UnitRepo::new(self.conn);
+        let queue = LevelMappingRepo::new(self.conn);
+        let tx = self.conn.unchecked_transaction()?;
+        for &(unit_id, level_id) in decisions {
+            match units.get_placement(unit_id)? {
+                Some((lib, _)) if lib == library_id => {}
+                _ => anyhow::bail!("Unit {} is not in library {}", unit_id, library_id),
+            }
+            match level_id {
+                Some(level_id) => {
+                    if !levels.iter().any(|l| l.id == Some(level_id)) {
+                        anyhow::bail!("Formation level {} is not in library {}", level_id, library_id);
+                    }
+                    units.set_formation_level(unit_id, Some(level_id))?;
+                    queue.remove(unit_id)?;
+                }
+                None => queue.dismiss(unit_id)?,
+            }
+        }
+        tx.commit()?;
+        Ok(decisions.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::models::{Library, Unit};
+    use crate::services::LibraryService;
+
+    fn level(id: i64, name_ru: &str, name_en: &str, ordinal: i32) -> CustomFormationLevel {
+        let mut level = CustomFormationLevel::new(1, name_ru.to_string(), name_en.to_string(), ordinal);
+        level.id = Some(id);
+        level
+    }
+
+    fn levels() -> Vec<CustomFormationLevel> {
+        vec![
+            level(1, "отделение", "squad", 1),
+            level(2, "взвод", "platoon", 3),
+            level(3, "рота", "company", 4),
+            level(4, "батальон", "battalion", 5),
+            level(5, "полк", "regiment", 6),
+        ]
+    }
+
+    /// English name of the suggested level and whether it would be applied directly
+    fn suggested(unit_type: &str) -> Option<(String, bool)> {
+        suggest_level(unit_type, &levels()).map(|(l, s)| (l.name_en.clone(), s >= AUTO_APPLY_SCORE))
+    }
+
+    #[test]
+    fn test_exact_names_in_either_language_apply_directly() {
+        for (unit_type, expected) in [
+            ("Battalion", "battalion"),
+            ("  PLATOON ", "platoon"),
+            ("Рота", "company"),
+            ("1st Battalion", "battalion"),
+        ] {
+            assert_eq!(suggested(unit_type), Some((expected.to_string(), true)), "{}", unit_type);
+        }
+    }
+
+    #[test]
+    fn test_messy_historical_types_are_only_suggested() {
+        for (unit_type, expected) in [
+            ("батальона", "battalion"),
+            ("роты", "company"),
+            ("1st Bn", "battalion"),
+            ("Rifle Coy", "company"),
+            ("Rifle Company", "company"),
+            ("2-й взв.", "platoon"),
+            ("Regt HQ", "regiment"),
+            ("Squadron", "squad"),
+        ] {
+            assert_eq!(suggested(unit_type), Some((expected.to_string(), false)), "{}", unit_type);
+        }
+    }
+
+    #[test]
+    fn test_unrelated_types_get_no_suggestion() {
+        for unit_type in ["Abteilung", "сводный отряд", "Kampfgruppe", "", "12"] {
+            assert_eq!(suggested(unit_type), None, "{}", unit_type);
+        }
+    }
+
+    #[test]
+    fn test_similarity_bounds() {
+        assert_eq!(similarity("abc", "abc"), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+        assert_eq!(similarity("", ""), 0.0);
+        assert!((similarity("батальона", "батальон") - 8.0 / 9.0).abs() < 1e-9);
+    }
+
+    /// Library with the test levels and one root unit per type
+    fn library_with_units(db: &Database, types: &[&str]) -> (i64, Vec<i64>) {
+        let library = LibraryService::new(db.conn())
+            .import_library(Library {
+                units: types.iter().map(|t| Unit::new(format!("{} unit", t), t.to_string())).collect(),
+                ..Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string())
+            })
+            .unwrap();
+        let lib_id = library.id.unwrap();
+        let repo = FormationLevelRepo::new(db.conn());
+        for mut level in levels() {
+            level.id = None;
+            level.library_id = lib_id;
+            repo.create(&mut level).unwrap();
+        }
+        let ids = UnitRepo::new(db.conn())
+            .get_by_library_id(lib_id)
+            .unwrap()
+            .into_iter()
+            .map(|u| u.id.unwrap())
+            .collect();
+        (lib_id, ids)
+    }
+
+    #[test]
+    fn test_scan_maps_exact_types_and_queues_the_rest() {
+        let db = Database::open_in_memory().unwrap();
+        let (lib_id, ids) = library_with_units(&db, &["Battalion", "Abteilung", "роты", ""]);
+        let service = LevelMappingService::new(db.conn());
+
+        let outcome = service.scan_library(lib_id).unwrap();
+        assert_eq!(outcome, ScanOutcome { mapped: 1, pending: 2 });
+        let units = UnitRepo::new(db.conn());
+        assert!(units.formation_level(ids[0]).unwrap().is_some());
+        let pending = service.pending(lib_id).unwrap();
+        let types: Vec<(&str, bool)> = pending
+            .iter()
+            .map(|e| (e.unit_type.as_str(), e.suggested_level_id.is_some()))
+            .collect();
+        assert_eq!(types, [("Abteilung", false), ("роты", true)]);
+
+        // Scanning again queues nothing twice
+        assert_eq!(service.scan_library(lib_id).unwrap(), ScanOutcome { mapped: 0, pending: 2 });
+    }
+
+    #[test]
+    fn test_apply_confirms_overrides_and_clears_the_queue() {
+        let db = Database::open_in_memory().unwrap();
+        let (lib_id, ids) = library_with_units(&db, &["Abteilung", "роты"]);
+        let service = LevelMappingService::new(db.conn());
+        service.scan_library(lib_id).unwrap();
+        let pending = service.pending(lib_id).unwrap();
+        let battalion = FormationLevelRepo::new(db.conn())
+            .list_by_library(lib_id)
+            .unwrap()
+            .into_iter()
+            .find(|l| l.name_en == "battalion")
+            .and_then(|l| l.id);
+
+        // Override "Abteilung" to battalion, keep "роты" without a level
+        let decisions: Vec<(i64, Option<i64>)> = pending
+            .iter()
+            .map(|e| (e.unit_id, if e.unit_type == "Abteilung" { battalion } else { None }))
+            .collect();
+        assert_eq!(service.apply(lib_id, &decisions).unwrap(), 2);
+        assert_eq!(service.pending_count(lib_id).unwrap(), 0);
+        let units = UnitRepo::new(db.conn());
+        assert_eq!(units.formation_level(ids[0]).unwrap(), battalion);
+        assert_eq!(units.formation_level(ids[1]).unwrap(), None);
+        // A dismissed unit is not queued again by the next scan
+        assert_eq!(service.scan_library(lib_id).unwrap().pending, 0);
+    }
+
+    #[test]
+    fn test_apply_rejects_foreign_level_without_partial_writes() {
+        let db = Database::open_in_memory().unwrap();
+        let (lib_id, ids) = library_with_units(&db, &["Abteilung", "Kampfgruppe"]);
+        let service = LevelMappingService::new(db.conn());
+        service.scan_library(lib_id).unwrap();
+        let level_id = FormationLevelRepo::new(db.conn()).list_by_library(lib_id).unwrap()[0].id;
+
+        assert!(service.apply(lib_id, &[(ids[0], level_id), (ids[1], Some(9999))]).is_err());
+        assert_eq!(UnitRepo::new(db.conn()).formation_level(ids[0]).unwrap(), None);
+        assert_eq!(service.pending_count(lib_id).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_library_without_levels_is_not_scanned() {
+        let db = Database::open_in_memory().unwrap();
+        let library = LibraryService::new(db.conn())
+            .import_library(Library {
+                units: vec![Unit::new("HQ".to_string(), "Abteilung".to_string())],
+                ..Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string())
+            })
+            .unwrap();
+        let outcome = LevelMappingService::new(db.conn()).scan_library(library.id.unwrap()).unwrap();
+        assert_eq!(outcome, ScanOutcome::default());
+    }
+}
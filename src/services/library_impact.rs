@@ -0,0 +1,111 @@
+//! What else changes when a library's name, country or era is edited, so the
+//! properties dialog can list it before the change is saved
+
+use crate::export::{default_file_name, html::report_meta};
+use crate::models::Library;
+
+/// Something shown outside the library that is derived from its name,
+/// country or era
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerivedArtifact {
+    /// File name offered when saving or exporting the library
+    ExportFileName,
+    /// Title and subtitle of the interactive HTML report
+    ReportHeader,
+    /// Title of exported diagrams
+    DiagramTitle,
+    /// Entry under File > Recent Libraries
+    RecentLibraries,
+}
+
+impl DerivedArtifact {
+    pub const ALL: [DerivedArtifact; 4] = [
+        DerivedArtifact::ExportFileName,
+        DerivedArtifact::ReportHeader,
+        DerivedArtifact::DiagramTitle,
+        DerivedArtifact::RecentLibraries,
+    ];
+
+    /// English label; the UI translates it
+    pub fn label(self) -> &'static str {
+        match self {
+            DerivedArtifact::ExportFileName => "Default export file name",
+            DerivedArtifact::ReportHeader => "Report header",
+            DerivedArtifact::DiagramTitle => "Diagram title",
+            DerivedArtifact::RecentLibraries => "Recent Libraries entry",
+        }
+    }
+
+    /// The artifact as it is for `library`
+    fn render(self, library: &Library) -> String {
+        match self {
+            DerivedArtifact::ExportFileName => default_file_name(&library.name, "json"),
+            DerivedArtifact::ReportHeader => format!("{} — {}", library.name, report_meta(library)),
+            DerivedArtifact::DiagramTitle | DerivedArtifact::RecentLibraries => library.name.clone(),
+        }
+    }
+}
+
+/// A derived artifact that differs after an edit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Impact {
+    pub artifact: DerivedArtifact,
+    pub before: String,
+    pub after: String,
+}
+
+/// Artifacts that change when `before` is saved as `after`. The recent list
+/// only counts when the library is on it.
+pub fn identity_change_impact(before: &Library, after: &Library, in_recent_list: bool) -> Vec<Impact> {
+    DerivedArtifact::ALL
+        .into_iter()
+        .filter(|&artifact| artifact != DerivedArtifact::RecentLibraries || in_recent_list)
+        .map(|artifact| Impact { artifact, before: artifact.render(before), after: artifact.render(after) })
+        .filter(|impact| impact.before != impact.after)
+        .collect()
+}
+
+/// Whether saving `after` gives the library another name. Versions in the
+/// history keep the old one, so restoring them brings it back.
+pub fn renames(before: &Library, after: &Library) -> bool {
+    before.name.trim() != after.name.trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str, era: &str) -> Library {
+        Library::new(name.to_string(), "US".to_string(), era.to_string(), "A".to_string())
+    }
+
+    #[test]
+    fn test_era_change_only_touches_the_report() {
+        let before = library("US Army", "2003");
+        let after = library("US Army", "OIF 2003–2004");
+        let impact = identity_change_impact(&before, &after, true);
+        assert_eq!(impact.len(), 1);
+        assert_eq!(impact[0].artifact, DerivedArtifact::ReportHeader);
+        assert_eq!(impact[0].before, "US Army — US · 2003 · A · v1");
+        assert_eq!(impact[0].after, "US Army — US · OIF 2003–2004 · A · v1");
+        assert!(!renames(&before, &after));
+    }
+
+    #[test]
+    fn test_rename_changes_file_names_and_titles() {
+        let before = library("US Army", "2003");
+        let after = library("US/Army 2003", "2003");
+        let impact = identity_change_impact(&before, &after, false);
+        let artifacts: Vec<DerivedArtifact> = impact.iter().map(|i| i.artifact).collect();
+        assert_eq!(
+            artifacts,
+            [DerivedArtifact::ExportFileName, DerivedArtifact::ReportHeader, DerivedArtifact::DiagramTitle]
+        );
+        assert_eq!((impact[0].before.as_str(), impact[0].after.as_str()), ("US Army.json", "US_Army 2003.json"));
+        assert!(renames(&before, &after));
+
+        let listed = identity_change_impact(&before, &after, true);
+        assert_eq!(listed.last().unwrap().artifact, DerivedArtifact::RecentLibraries);
+        assert!(identity_change_impact(&before, &before.clone(), true).is_empty());
+    }
+}
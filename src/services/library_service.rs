@@ -1,27 +1,110 @@
 //! Library service for managing libraries with version control
 
 use anyhow::Result;
-use rusqlite::Connection;
-use crate::models::{Library, Snapshot, default_branches, default_branch_categories};
-use crate::db::repositories::{LibraryRepo, VersionRepo, BranchRepo, BranchCategoryRepo};
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::models::{Branch, BranchCategory, CustomFormationLevel, Library, Snapshot, TreeLimits, apply_tag_changes, for_each_unit_mut, default_branches, default_branch_categories, resolve_country_code, tree_shape};
+use crate::db::repositories::{LibraryRepo, LibrarySummary, VersionRepo, BranchRepo, BranchCategoryRepo, FormationLevelRepo, UnitRepo};
+use crate::export::full::{FullLibraryExport, SnapshotState, FULL_EXPORT_FORMAT_VERSION};
+use crate::export::{export_json_anonymized, export_json_with_options, AnonymizeOptions, ExportOptions};
+use crate::import::LibraryImport;
+use super::metrics;
+
+/// Errors of [`LibraryService`] that callers handle rather than just report
+#[derive(Debug, Clone, PartialEq)]
+pub enum LibraryServiceError {
+    /// A library with the same name, country and era already exists
+    Duplicate { existing_id: i64 },
+}
+
+impl std::fmt::Display for LibraryServiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Duplicate { existing_id } => {
+                write!(f, "A library with this name, country and era already exists (ID: {})", existing_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LibraryServiceError {}
+
+/// Autosave snapshots kept per library unless told otherwise
+pub const DEFAULT_AUTOSAVE_KEEP: usize = 5;
+
+/// Description of autosave snapshots
+pub const AUTOSAVE_DESCRIPTION: &str = "autosave";
 
 /// Service for library operations with automatic version management
 pub struct LibraryService<'a> {
+    conn: &'a Connection,
     library_repo: LibraryRepo<'a>,
     version_repo: VersionRepo<'a>,
     branch_repo: BranchRepo<'a>,
     branch_category_repo: BranchCategoryRepo<'a>,
+    unit_repo: UnitRepo<'a>,
+    formation_level_repo: FormationLevelRepo<'a>,
+    tree_limits: TreeLimits,
+    autosave_keep: usize,
 }
 
 impl<'a> LibraryService<'a> {
     /// Create new library service
     pub fn new(conn: &'a Connection) -> Self {
         Self {
+            conn,
             library_repo: LibraryRepo::new(conn),
             version_repo: VersionRepo::new(conn),
             branch_repo: BranchRepo::new(conn),
             branch_category_repo: BranchCategoryRepo::new(conn),
+            unit_repo: UnitRepo::new(conn),
+            formation_level_repo: FormationLevelRepo::new(conn),
+            tree_limits: TreeLimits::default(),
+            autosave_keep: DEFAULT_AUTOSAVE_KEEP,
+        }
+    }
+
+    /// Refuse imported unit trees past `limits` instead of the default ones
+    pub fn with_tree_limits(mut self, limits: TreeLimits) -> Self {
+        self.tree_limits = limits;
+        self
+    }
+
+    /// Keep the newest `keep` autosaves of a library (at least one) instead
+    /// of [`DEFAULT_AUTOSAVE_KEEP`]
+    pub fn with_autosave_keep(mut self, keep: usize) -> Self {
+        self.autosave_keep = keep.max(1);
+        self
+    }
+
+    /// Fail before anything is written when an imported unit tree goes past
+    /// a hard limit; soft limits are for the caller to warn about
+    fn check_imported_tree(&self, library: &Library) -> Result<()> {
+        self.tree_limits.check(tree_shape(&library.units))?;
+        Ok(())
+    }
+
+    /// Run `f` in a transaction, or in the caller's when one is already open,
+    /// so nothing `f` wrote is kept if it fails
+    fn in_transaction<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.in_transaction_with(TransactionBehavior::Deferred, f)
+    }
+
+    /// Like [`Self::in_transaction`], taking the write lock up front when it
+    /// opens the transaction, so reads in `f` see no concurrent writer
+    fn in_immediate_transaction<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.in_transaction_with(TransactionBehavior::Immediate, f)
+    }
+
+    fn in_transaction_with<T>(&self, behavior: TransactionBehavior, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        if !self.conn.is_autocommit() {
+            return f();
         }
+        let tx = Transaction::new_unchecked(self.conn, behavior)?;
+        let value = f()?;
+        tx.commit()?;
+        Ok(value)
     }
 
     /// Validate library fields before create/update.
@@ -42,56 +125,481 @@ impl<'a> LibraryService<'a> {
         Ok(())
     }
 
-    /// Create a new library, initial snapshot, default branch categories, and default branches
-    pub fn create_library(&self, mut library: Library) -> Result<Library> {
+    /// Create a new library, default branch categories, default branches and
+    /// the initial snapshot of all that, all or nothing.
+    ///
+    /// Fails with [`LibraryServiceError::Duplicate`] when a library with the
+    /// same identity exists; see [`Self::create_library_with`] to allow it.
+    pub fn create_library(&self, library: Library) -> Result<Library> {
+        self.create_library_with(library, false)
+    }
+
+    /// Like [`Self::create_library`], storing the library's unit tree too.
+    /// Formation levels are the new library's own, so units lose theirs.
+    pub fn create_library_with_units(&self, mut library: Library) -> Result<Library> {
+        self.check_imported_tree(&library)?;
+        for_each_unit_mut(&mut library.units, |unit| unit.formation_level_id = None);
+        self.create_library_rows(library, false, true)
+    }
+
+    /// Like [`Self::create_library`]; with `allow_duplicate` a library with the
+    /// same name, country and era as an existing one is created anyway
+    pub fn create_library_with(&self, library: Library, allow_duplicate: bool) -> Result<Library> {
+        self.create_library_rows(library, allow_duplicate, false)
+    }
+
+    /// Create the library row, its default categories and branches, with
+    /// `with_units` its unit tree, and snapshot the result
+    fn create_library_rows(&self, mut library: Library, allow_duplicate: bool, with_units: bool) -> Result<Library> {
         Self::validate_library(&library)?;
-        self.library_repo.create(&mut library)?;
-        
-        if let Some(lib_id) = library.id {
-            let data = serde_json::to_string(&library)?;
-            let mut snapshot = Snapshot::new(lib_id, library.version, data);
-            self.version_repo.create(&mut snapshot)?;
-
-            let mut category_ids = Vec::new();
-            for mut cat in default_branch_categories(lib_id) {
-                self.branch_category_repo.create(&mut cat)?;
-                if let Some(id) = cat.id {
-                    category_ids.push(id);
+        if !allow_duplicate {
+            if let Some(existing_id) = self
+                .library_repo
+                .find_by_identity(&library.name, &library.country, &library.era)?
+                .and_then(|existing| existing.id)
+            {
+                return Err(LibraryServiceError::Duplicate { existing_id }.into());
+            }
+        }
+        if library.country_code.is_none() {
+            library.country_code = resolve_country_code(&library.country).map(str::to_string);
+        }
+        // The library gets formation levels of its own, so no override names one
+        library.numbering.level_overrides.clear();
+        self.in_transaction(|| {
+            self.library_repo.create(&mut library)?;
+            if let Some(lib_id) = library.id {
+                let mut category_ids = Vec::new();
+                for mut cat in default_branch_categories(lib_id) {
+                    self.branch_category_repo.create(&mut cat)?;
+                    if let Some(id) = cat.id {
+                        category_ids.push(id);
+                    }
+                }
+                for (mut branch, cat_idx) in default_branches(lib_id) {
+                    branch.category_id = category_ids.get(cat_idx).copied();
+                    self.branch_repo.create(&mut branch)?;
+                }
+                if with_units {
+                    for unit in &mut library.units {
+                        unit.parent_id = None;
+                        self.unit_repo.create_tree(lib_id, unit)?;
+                    }
                 }
+                self.store_snapshot(lib_id)?;
+            }
+            Ok(library)
+        })
+    }
+
+    /// Create a library read from a file, storing its unit tree as well.
+    /// Any incoming id is discarded so existing rows are never touched; the
+    /// file carries no formation levels, so units come without one.
+    pub fn import_library(&self, mut library: Library) -> Result<Library> {
+        self.check_imported_tree(&library)?;
+        library.id = None;
+        for_each_unit_mut(&mut library.units, |unit| unit.formation_level_id = None);
+        // Callers ask about duplicates of imported files themselves
+        self.create_library_rows(library, true, true)
+    }
+
+    /// Like [`Self::import_library`], also storing the exported version history
+    /// (entries of the `versions` array written by `export_json_with_mode`).
+    /// Versions that already exist after the import are skipped.
+    pub fn import_library_with_versions(&self, library: Library, versions: &[serde_json::Value]) -> Result<Library> {
+        self.in_transaction(|| {
+            let created = self.import_library(library)?;
+            let Some(lib_id) = created.id else {
+                return Ok(created);
+            };
+            let existing: HashSet<i64> = self
+                .version_repo
+                .list_by_library(lib_id)?
+                .iter()
+                .map(|s| s.version)
+                .collect();
+            for entry in versions {
+                let mut snapshot = snapshot_from_export(lib_id, entry)?;
+                if existing.contains(&snapshot.version) {
+                    log::debug!("Skipping imported version {}: already present", snapshot.version);
+                    continue;
+                }
+                self.version_repo.create(&mut snapshot)?;
+            }
+            Ok(created)
+        })
+    }
+
+    /// Gather the library, its unit tree and its formation levels, branch
+    /// categories and branches for a full export
+    pub fn export_full(&self, library_id: i64) -> Result<FullLibraryExport> {
+        metrics::timed("export_full", || self.full_state(library_id))
+    }
+
+    /// Everything stored for the library, as exported and snapshotted
+    fn full_state(&self, library_id: i64) -> Result<FullLibraryExport> {
+        Ok(FullLibraryExport {
+            format_version: FULL_EXPORT_FORMAT_VERSION,
+            library: self.library_with_units(library_id)?,
+            formation_levels: self.formation_level_repo.list_by_library(library_id)?,
+            branch_categories: self.branch_category_repo.list_by_library(library_id)?,
+            branches: self.branch_repo.list_by_library(library_id)?,
+        })
+    }
+
+    /// Write the library as an enveloped JSON export, with its version
+    /// history when `options` ask for it
+    pub fn export_json(&self, library_id: i64, path: &Path, options: &ExportOptions) -> Result<()> {
+        let library = self.library_with_units(library_id)?;
+        export_json_with_options(&library, path, options, Some(&self.version_repo))
+    }
+
+    /// Write the library's full state with the author and notes stripped,
+    /// with its version history unless `options` drop it
+    pub fn export_anonymized(&self, library_id: i64, path: &Path, options: &AnonymizeOptions) -> Result<()> {
+        metrics::timed("export_anonymized", || {
+            let versions = if options.include_versions {
+                self.version_repo.list_by_library(library_id)?
+            } else {
+                Vec::new()
+            };
+            export_json_anonymized(self.full_state(library_id)?, versions, path, options)
+        })
+    }
+
+    /// Recreate a full export as a new library in a single transaction.
+    /// Nothing is written if any part fails.
+    pub fn import_full(&self, export: FullLibraryExport) -> Result<Library> {
+        if export.format_version > FULL_EXPORT_FORMAT_VERSION {
+            anyhow::bail!("Unsupported library file format version {}", export.format_version);
+        }
+        self.check_imported_tree(&export.library)?;
+        let mut library = export.library;
+        Self::validate_library(&library)?;
+        library.id = None;
+        metrics::timed("import_full", || {
+            self.in_transaction(|| {
+                self.library_repo.create(&mut library)?;
+                let lib_id = library.id.ok_or_else(|| anyhow::anyhow!("Library was not assigned an id"))?;
+                self.create_tables(
+                    lib_id,
+                    export.branch_categories,
+                    export.branches,
+                    export.formation_levels,
+                    &mut library,
+                )?;
+                self.store_snapshot(lib_id)?;
+                Ok(library)
+            })
+        })
+    }
+
+    /// Create the categories, branches, formation levels and unit tree of a
+    /// full export or snapshot under library `lib_id`. Their ids are the ones
+    /// they were stored with; branches, units and the library's numbering
+    /// overrides are remapped to the new category and level ids.
+    fn create_tables(
+        &self,
+        lib_id: i64,
+        categories: Vec<BranchCategory>,
+        branches: Vec<Branch>,
+        levels: Vec<CustomFormationLevel>,
+        library: &mut Library,
+    ) -> Result<()> {
+        let mut category_ids = HashMap::new();
+        for mut category in categories {
+            let old_id = category.id.take();
+            category.library_id = lib_id;
+            self.branch_category_repo.create(&mut category)?;
+            if let (Some(old), Some(new)) = (old_id, category.id) {
+                category_ids.insert(old, new);
             }
-            for (mut branch, cat_idx) in default_branches(lib_id) {
-                branch.category_id = category_ids.get(cat_idx).copied();
-                self.branch_repo.create(&mut branch)?;
+        }
+        for mut branch in branches {
+            branch.id = None;
+            branch.library_id = lib_id;
+            branch.category_id = branch.category_id.and_then(|old| category_ids.get(&old).copied());
+            self.branch_repo.create(&mut branch)?;
+        }
+        let mut level_ids = HashMap::new();
+        for mut level in levels {
+            let old_id = level.id.take();
+            level.library_id = lib_id;
+            self.formation_level_repo.create(&mut level)?;
+            if let (Some(old), Some(new)) = (old_id, level.id) {
+                level_ids.insert(old, new);
             }
         }
-        
-        Ok(library)
+        library.numbering.remap_levels(&level_ids);
+        self.library_repo.update_numbering(lib_id, &library.numbering)?;
+        for_each_unit_mut(&mut library.units, |unit| {
+            unit.formation_level_id = unit.formation_level_id.and_then(|old| level_ids.get(&old).copied());
+        });
+        for unit in &mut library.units {
+            unit.parent_id = None;
+            self.unit_repo.create_tree(lib_id, unit)?;
+        }
+        Ok(())
+    }
+
+    /// Store a library read from a file, full export or plain
+    pub fn import(&self, import: LibraryImport) -> Result<Library> {
+        match import {
+            LibraryImport::Plain { library, versions } => self.import_library_with_versions(library, &versions),
+            LibraryImport::Full(export) => self.import_full(export),
+        }
+    }
+
+    /// Library with the same name, country and era, if any, ignoring case and
+    /// surrounding whitespace
+    pub fn find_duplicate(&self, library: &Library) -> Result<Option<Library>> {
+        self.library_repo
+            .find_by_identity(&library.name, &library.country, &library.era)
     }
 
     /// Save library (update if exists, create if new) and create snapshot
     pub fn save_library(&self, mut library: Library, create_snapshot: bool) -> Result<Library> {
-        Self::validate_library(&library)?;
-        if library.id.is_none() {
-            // New library
-            self.create_library(library)
-        } else {
-            // Update existing library
-            if create_snapshot {
-                library.increment_version();
+        metrics::timed("save_library", || {
+            Self::validate_library(&library)?;
+            if library.id.is_none() {
+                // New library
+                self.create_library(library)
+            } else {
+                // Update existing library; the row and its snapshot are written together
+                if create_snapshot {
+                    library.increment_version();
+                }
+                self.in_transaction(|| {
+                    self.library_repo.update(&library)?;
+                    if create_snapshot {
+                        if let Some(lib_id) = library.id {
+                            self.store_snapshot(lib_id)?;
+                        }
+                    }
+                    Ok(library)
+                })
             }
-            self.library_repo.update(&library)?;
-            
-            // Create snapshot if requested
-            if create_snapshot {
-                if let Some(lib_id) = library.id {
-                    let data = serde_json::to_string(&library)?;
-                    let mut snapshot = Snapshot::new(lib_id, library.version, data);
-                    self.version_repo.create(&mut snapshot)?;
+        })
+    }
+
+    /// Store the library's stored state, tables included, as a snapshot of
+    /// its version
+    fn store_snapshot(&self, lib_id: i64) -> Result<()> {
+        metrics::timed("create_snapshot", || {
+            let state = self.full_state(lib_id)?;
+            let data = serde_json::to_string(&state)?;
+            let mut snapshot = Snapshot::new(lib_id, state.library.version, data);
+            self.version_repo.create(&mut snapshot)
+        })
+    }
+
+    /// Add and remove tags on several libraries in one transaction, without
+    /// snapshots. Returns how many libraries actually changed.
+    pub fn apply_bulk_tags(&self, library_ids: &[i64], add: &[String], remove: &[String]) -> Result<usize> {
+        self.in_transaction(|| {
+            let mut changed = 0;
+            for &id in library_ids {
+                let library = self
+                    .library_repo
+                    .get_by_id(id)?
+                    .ok_or_else(|| anyhow::anyhow!("Library {} not found", id))?;
+                if let Some(tags) = apply_tag_changes(&library.tags, add, remove) {
+                    self.library_repo.update_tags(id, &tags)?;
+                    changed += 1;
                 }
             }
-            
-            Ok(library)
+            Ok(changed)
+        })
+    }
+
+    /// Snapshot the library with its unit tree, formation levels, branch
+    /// categories and branches as a new version with a description.
+    /// The version is taken past both the library's and the newest snapshot's
+    /// inside an immediate transaction, so back-to-back snapshots never collide.
+    pub fn create_snapshot(&self, library_id: i64, description: &str) -> Result<Snapshot> {
+        metrics::timed("create_snapshot", || {
+            self.in_immediate_transaction(|| self.snapshot_next_version(library_id, description))
+        })
+    }
+
+    /// Snapshot the library's full state as an autosave of its current
+    /// version, which is not bumped: the user's versions stay those they
+    /// saved. Autosaves past the newest kept are deleted in the same
+    /// transaction.
+    pub fn create_autosave_snapshot(&self, library_id: i64) -> Result<Snapshot> {
+        metrics::timed("create_autosave_snapshot", || {
+            let (snapshot, pruned) = self.in_immediate_transaction(|| {
+                let state = self.full_state(library_id)?;
+                let data = serde_json::to_string(&state)?;
+                let mut snapshot =
+                    Snapshot::with_description(library_id, state.library.version, data, AUTOSAVE_DESCRIPTION.to_string());
+                snapshot.is_autosave = true;
+                self.version_repo.create(&mut snapshot)?;
+                let pruned = self.version_repo.prune_autosaves(library_id, self.autosave_keep)?;
+                Ok((snapshot, pruned))
+            })?;
+            if pruned > 0 {
+                log::debug!("Deleted {} old autosaves of library {}", pruned, library_id);
+            }
+            Ok(snapshot)
+        })
+    }
+
+    /// The stored library with its unit tree; fails when there is none
+    pub fn library_with_units(&self, library_id: i64) -> Result<Library> {
+        let mut library = self
+            .library_repo
+            .get_by_id(library_id)?
+            .ok_or_else(|| anyhow::anyhow!("Library {} not found", library_id))?;
+        library.units = self.unit_repo.get_by_library_id(library_id)?;
+        Ok(library)
+    }
+
+    /// Bump the library past its newest snapshot and store its current state;
+    /// callers hold the transaction
+    fn snapshot_next_version(&self, library_id: i64, description: &str) -> Result<Snapshot> {
+        let mut library = self
+            .library_repo
+            .get_by_id(library_id)?
+            .ok_or_else(|| anyhow::anyhow!("Library {} not found", library_id))?;
+        let latest = self.version_repo.latest_version(library_id)?.unwrap_or(0);
+        library.set_version(library.version.max(latest) + 1);
+        self.library_repo.update(&library)?;
+
+        let data = serde_json::to_string(&self.full_state(library_id)?)?;
+        let mut snapshot = Snapshot::new(library_id, library.version, data);
+        let description = description.trim();
+        if !description.is_empty() {
+            snapshot.description = Some(description.to_string());
+        }
+        self.version_repo.create(&mut snapshot)?;
+        Ok(snapshot)
+    }
+
+    /// Make an earlier version the current state and record that as a new
+    /// snapshot "Restored from vN", so no history is lost. The unit tree,
+    /// formation levels, branch categories and branches are all replaced by
+    /// the snapshot's. Snapshots taken before they held those tables only
+    /// carry the library with its units, and with units only when they
+    /// recorded any: they leave the tables, and the units when they have
+    /// none, as they are.
+    pub fn restore_version(&self, library_id: i64, version: i64) -> Result<Snapshot> {
+        metrics::timed("restore_version", || {
+            self.apply_version(library_id, version, &format!("Restored from v{}", version))
+        })
+    }
+
+    /// Like [`Self::restore_version`] for autosave `snapshot_id`, recording
+    /// the new snapshot as "Restored from autosave of vN"
+    pub fn restore_autosave(&self, library_id: i64, snapshot_id: i64) -> Result<Snapshot> {
+        metrics::timed("restore_version", || {
+            let snapshot = self
+                .version_repo
+                .get_by_id(snapshot_id)?
+                .filter(|s| s.is_autosave && s.library_id == library_id)
+                .ok_or_else(|| anyhow::anyhow!("Autosave {} of library {} not found", snapshot_id, library_id))?;
+            let description = format!("Restored from autosave of v{}", snapshot.version);
+            self.apply_snapshot(library_id, &description, || SnapshotState::from_data(&snapshot.data))
+        })
+    }
+
+    /// Like [`Self::restore_version`], recording the new snapshot as "Reverted to vN"
+    pub fn revert_to_version(&self, library_id: i64, version: i64) -> Result<Snapshot> {
+        metrics::timed("revert_to_version", || {
+            self.apply_version(library_id, version, &format!("Reverted to v{}", version))
+        })
+    }
+
+    /// Write a snapshot back over the library and snapshot the result, all in
+    /// one immediate transaction
+    fn apply_version(&self, library_id: i64, version: i64, description: &str) -> Result<Snapshot> {
+        self.apply_snapshot(library_id, description, || {
+            self.snapshot_state(library_id, version)?
+                .ok_or_else(|| anyhow::anyhow!("Version {} of library {} not found", version, library_id))
+        })
+    }
+
+    /// Write the state `load` reads back over the library and snapshot the
+    /// result, all in one immediate transaction or in the caller's
+    fn apply_snapshot(&self, library_id: i64, description: &str, load: impl FnOnce() -> Result<SnapshotState>) -> Result<Snapshot> {
+        self.in_immediate_transaction(|| self.write_snapshot(library_id, description, load()?))
+    }
+
+    /// Body of [`Self::apply_snapshot`]; callers hold the transaction
+    fn write_snapshot(&self, library_id: i64, description: &str, state: SnapshotState) -> Result<Snapshot> {
+        let current = self
+            .library_repo
+            .get_by_id(library_id)?
+            .ok_or_else(|| anyhow::anyhow!("Library {} not found", library_id))?;
+        let write_library = |restored: &mut Library| -> Result<()> {
+            restored.id = Some(library_id);
+            restored.version = current.version;
+            if restored.country_code.is_none() {
+                restored.country_code = resolve_country_code(&restored.country).map(str::to_string);
+            }
+            self.library_repo.update(restored)
+        };
+        match state {
+            SnapshotState::Full(export) => {
+                let mut restored = export.library;
+                write_library(&mut restored)?;
+                self.unit_repo.delete_by_library(library_id)?;
+                self.branch_repo.delete_by_library(library_id)?;
+                self.branch_category_repo.delete_by_library(library_id)?;
+                self.formation_level_repo.delete_by_library(library_id)?;
+                self.create_tables(
+                    library_id,
+                    export.branch_categories,
+                    export.branches,
+                    export.formation_levels,
+                    &mut restored,
+                )?;
+            }
+            SnapshotState::Library(mut restored) => {
+                write_library(&mut restored)?;
+                // Levels removed since the snapshot leave their units without one
+                let levels: HashSet<i64> = self
+                    .formation_level_repo
+                    .list_by_library(library_id)?
+                    .into_iter()
+                    .filter_map(|l| l.id)
+                    .collect();
+                for_each_unit_mut(&mut restored.units, |unit| {
+                    unit.formation_level_id = unit.formation_level_id.filter(|id| levels.contains(id));
+                });
+                restored.numbering.level_overrides.retain(|id, _| levels.contains(id));
+                self.library_repo.update_numbering(library_id, &restored.numbering)?;
+                // Metadata saves stored these without units
+                if !restored.units.is_empty() {
+                    self.unit_repo.delete_by_library(library_id)?;
+                    for unit in &mut restored.units {
+                        unit.parent_id = None;
+                        self.unit_repo.create_tree(library_id, unit)?;
+                    }
+                }
+            }
+        }
+        self.snapshot_next_version(library_id, description)
+    }
+
+    /// Delete one snapshot of a library; the last remaining version is kept,
+    /// autosaves can always go
+    pub fn delete_snapshot(&self, library_id: i64, snapshot_id: i64) -> Result<()> {
+        let autosave = self.version_repo.get_by_id(snapshot_id)?.filter(|s| s.is_autosave);
+        if let Some(autosave) = autosave {
+            if autosave.library_id != library_id {
+                anyhow::bail!("Snapshot {} does not belong to library {}", snapshot_id, library_id);
+            }
+            return self.version_repo.delete(snapshot_id);
+        }
+        let snapshots = self.version_repo.list_by_library(library_id)?;
+        if !snapshots.iter().any(|s| s.id == Some(snapshot_id)) {
+            anyhow::bail!("Snapshot {} does not belong to library {}", snapshot_id, library_id);
         }
+        if snapshots.len() < 2 {
+            anyhow::bail!("Cannot delete the only version of a library");
+        }
+        self.version_repo.delete(snapshot_id)
     }
 
     /// Get library by ID
@@ -101,7 +609,21 @@ impl<'a> LibraryService<'a> {
 
     /// List all libraries
     pub fn list_libraries(&self) -> Result<Vec<Library>> {
-        self.library_repo.list_all()
+        metrics::timed_counted(
+            "list_libraries",
+            || self.library_repo.list_all(),
+            |r| r.as_ref().ok().map(Vec::len),
+        )
+    }
+
+    /// Names, unit and tag counts of all libraries, for lists that do not
+    /// need the libraries themselves
+    pub fn list_library_summaries(&self) -> Result<Vec<LibrarySummary>> {
+        metrics::timed_counted(
+            "list_library_summaries",
+            || self.library_repo.list_summaries(),
+            |r| r.as_ref().ok().map(Vec::len),
+        )
     }
 
     /// Search libraries
@@ -119,23 +641,87 @@ impl<'a> LibraryService<'a> {
         self.version_repo.list_by_library(library_id)
     }
 
+    /// Versions and autosaves of a library together, newest first
+    pub fn get_history(&self, library_id: i64) -> Result<Vec<Snapshot>> {
+        let mut history = self.version_repo.list_by_library(library_id)?;
+        history.extend(self.version_repo.list_autosaves(library_id)?);
+        history.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(b.id.cmp(&a.id)));
+        Ok(history)
+    }
+
     /// Get latest version snapshot for a library
     pub fn get_latest_version(&self, library_id: i64) -> Result<Option<Snapshot>> {
         self.version_repo.get_latest(library_id)
     }
 
+    /// Create a new library from one version of another, leaving the original
+    /// untouched. The copy is named "<name> (vN)" (disambiguated if taken),
+    /// starts at version 1 with a single snapshot and gets the version's
+    /// formation levels, branch categories and branches; versions stored
+    /// before snapshots held those get the default branches.
+    pub fn fork_from_version(&self, library_id: i64, version: i64) -> Result<Library> {
+        let mut state = self
+            .snapshot_state(library_id, version)?
+            .ok_or_else(|| anyhow::anyhow!("Version {} of library {} not found", version, library_id))?;
+        let existing = self.library_repo.list_names()?;
+        let library = match &mut state {
+            SnapshotState::Full(export) => &mut export.library,
+            SnapshotState::Library(library) => library,
+        };
+        library.name = unique_library_name(&format!("{} (v{})", library.name, version), &existing);
+        library.set_version(1);
+        match state {
+            SnapshotState::Full(export) => self.import_full(export),
+            SnapshotState::Library(library) => self.import_library(library),
+        }
+    }
+
     /// Restore library from a specific version snapshot
     pub fn restore_from_version(&self, library_id: i64, version: i64) -> Result<Option<Library>> {
-        let snapshots = self.version_repo.list_by_library(library_id)?;
-        let snapshot = snapshots.iter().find(|s| s.version == version);
-        
-        if let Some(snap) = snapshot {
-            let library: Library = serde_json::from_str(&snap.data)?;
-            Ok(Some(library))
-        } else {
-            Ok(None)
-        }
+        Ok(self.snapshot_state(library_id, version)?.map(SnapshotState::into_library))
     }
+
+    /// What the snapshot of `version` holds, if there is one
+    fn snapshot_state(&self, library_id: i64, version: i64) -> Result<Option<SnapshotState>> {
+        self.version_repo
+            .get_by_version(library_id, version)?
+            .map(|s| SnapshotState::from_data(&s.data))
+            .transpose()
+    }
+}
+
+/// `base`, or `base (2)`, `base (3)`, … — the first name not in `existing`
+pub fn unique_library_name(base: &str, existing: &[String]) -> String {
+    if !existing.iter().any(|n| n == base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{} ({})", base, n))
+        .find(|candidate| !existing.contains(candidate))
+        .expect("unbounded range always yields a free name")
+}
+
+/// Snapshot from one entry of an exported `versions` array.
+/// `data` may be the serialized library string or an inline object.
+fn snapshot_from_export(library_id: i64, entry: &serde_json::Value) -> Result<Snapshot> {
+    let version = entry
+        .get("version")
+        .and_then(|v| v.as_i64())
+        .ok_or_else(|| anyhow::anyhow!("Version entry without a version number"))?;
+    let data = match entry.get("data") {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) if !other.is_null() => serde_json::to_string(other)?,
+        _ => anyhow::bail!("Version {} has no data", version),
+    };
+    let mut snapshot = Snapshot::new(library_id, version, data);
+    if let Some(ts) = entry.get("timestamp").and_then(|t| t.as_i64()) {
+        snapshot.timestamp = ts;
+    }
+    snapshot.description = entry
+        .get("description")
+        .and_then(|d| d.as_str())
+        .map(str::to_string);
+    Ok(snapshot)
 }
 
 #[cfg(test)]
@@ -143,6 +729,72 @@ mod tests {
     use super::*;
     use crate::db::Database;
     use crate::db::repositories::BranchRepo;
+    use crate::models::{TreeLimitIssue, Unit, MAX_TREE_DEPTH};
+
+    #[test]
+    fn test_unique_library_name() {
+        let existing = vec!["Sample".to_string(), "Sample (2)".to_string()];
+        assert_eq!(unique_library_name("Other", &existing), "Other");
+        assert_eq!(unique_library_name("Sample", &existing), "Sample (3)");
+        assert_eq!(unique_library_name("Sample", &existing[1..]), "Sample");
+    }
+
+    #[test]
+    fn test_fork_from_version() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let mut library = Library::new("Army".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        library.units.push(Unit::new("1st Battalion".to_string(), "battalion".to_string()));
+        let created = service.import_library(library).unwrap();
+        let lib_id = created.id.unwrap();
+        let mut edited = created.clone();
+        edited.name = "Army renamed".to_string();
+        edited.units.clear();
+        service.save_library(edited, true).unwrap();
+
+        let fork = service.fork_from_version(lib_id, 1).unwrap();
+        assert_ne!(fork.id, Some(lib_id));
+        assert_eq!(fork.name, "Army (v1)");
+        assert_eq!(fork.version, 1);
+        assert_eq!(service.get_library_versions(fork.id.unwrap()).unwrap().len(), 1);
+        let units = UnitRepo::new(db.conn()).get_by_library_id(fork.id.unwrap()).unwrap();
+        assert_eq!(units[0].name, "1st Battalion");
+        // The original is untouched
+        let original = service.get_library(lib_id).unwrap().unwrap();
+        assert_eq!(original.name, "Army renamed");
+        assert_eq!(original.version, 2);
+
+        // Forking the same version again picks a free name
+        assert_eq!(service.fork_from_version(lib_id, 1).unwrap().name, "Army (v1) (2)");
+    }
+
+    #[test]
+    fn test_fork_from_version_copies_levels_and_branches() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let mut level = CustomFormationLevel::new(lib_id, "Рота".to_string(), "Company".to_string(), 5);
+        FormationLevelRepo::new(db.conn()).create(&mut level).unwrap();
+        let mut branch = Branch::new(lib_id, "Связь".to_string(), "Signals".to_string());
+        BranchRepo::new(db.conn()).create(&mut branch).unwrap();
+        let version = service.create_snapshot(lib_id, "").unwrap().version;
+
+        let fork = service.fork_from_version(lib_id, version).unwrap().id.unwrap();
+        assert_eq!(FormationLevelRepo::new(db.conn()).list_by_library(fork).unwrap()[0].name_en, "Company");
+        assert_eq!(branch_names(&db, fork), branch_names(&db, lib_id));
+        assert_eq!(unit_names(&db, fork), ["A", "B"]);
+    }
+
+    #[test]
+    fn test_fork_from_missing_version() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = Library::new("Army".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        let lib_id = service.create_library(library).unwrap().id.unwrap();
+        let err = service.fork_from_version(lib_id, 5).unwrap_err();
+        assert_eq!(err.to_string(), format!("Version 5 of library {} not found", lib_id));
+        assert_eq!(service.list_libraries().unwrap().len(), 1);
+    }
 
     #[test]
     fn test_create_library_with_snapshot() {
@@ -166,6 +818,235 @@ mod tests {
         assert_eq!(snapshots[0].version, 1);
     }
 
+    #[test]
+    fn test_import_library_stores_unit_tree() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+
+        let mut library = Library::new(
+            "Imported".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        library.id = Some(999);
+        let mut battalion = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+        battalion.add_child(Unit::new("A Company".to_string(), "Company".to_string()));
+        battalion.add_child(Unit::new("B Company".to_string(), "Company".to_string()));
+        library.add_unit(battalion);
+
+        let created = service.import_library(library).unwrap();
+        assert_ne!(created.id, Some(999));
+        let tree = UnitRepo::new(db.conn())
+            .list_tree_by_library(created.id.unwrap())
+            .unwrap();
+        let got: Vec<(&str, i32)> = tree.iter().map(|n| (n.name.as_str(), n.depth)).collect();
+        assert_eq!(got, vec![("1st Battalion", 0), ("A Company", 1), ("B Company", 1)]);
+    }
+
+    #[test]
+    fn test_snapshots_hold_the_full_state() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let mut library = Library::new("Full".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+        library.add_unit(Unit::new("1st Battalion".to_string(), "Battalion".to_string()));
+        let lib_id = service.import_library(library).unwrap().id.unwrap();
+
+        let snapshots = service.get_library_versions(lib_id).unwrap();
+        let SnapshotState::Full(state) = SnapshotState::from_data(&snapshots[0].data).unwrap() else {
+            panic!("snapshot is not a full state");
+        };
+        assert_eq!(state.library.units.len(), 1);
+        assert_eq!(state.branches, BranchRepo::new(db.conn()).list_by_library(lib_id).unwrap());
+        assert!(!state.branch_categories.is_empty());
+
+        FormationLevelRepo::new(db.conn())
+            .create(&mut CustomFormationLevel::new(lib_id, "Полк".to_string(), "Regiment".to_string(), 6))
+            .unwrap();
+        let snapshot = service.create_snapshot(lib_id, "levels").unwrap();
+        let SnapshotState::Full(state) = SnapshotState::from_data(&snapshot.data).unwrap() else {
+            panic!("snapshot is not a full state");
+        };
+        assert_eq!(state.formation_levels.len(), 1);
+    }
+
+    #[test]
+    fn test_import_library_refuses_tree_past_hard_limits() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Deep".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        library.units = crate::models::tree::unit_chain(100);
+
+        let err = LibraryService::new(db.conn()).import_library(library.clone()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TreeLimitIssue>(),
+            Some(&TreeLimitIssue::TooDeep { depth: 100, limit: MAX_TREE_DEPTH })
+        );
+        for table in ["libraries", "snapshots", "units"] {
+            assert_eq!(count(&db, table), 0, "{}", table);
+        }
+
+        // A raised limit stops at the deepest tree files can hold
+        let limits = TreeLimits { max_depth: 100, ..TreeLimits::default() };
+        let err = LibraryService::new(db.conn()).with_tree_limits(limits).import_library(library).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TreeLimitIssue>(),
+            Some(&TreeLimitIssue::TooDeep { depth: 100, limit: MAX_TREE_DEPTH })
+        );
+
+        library = Library::new("Deep".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        library.units = crate::models::tree::unit_chain(MAX_TREE_DEPTH);
+        let created = LibraryService::new(db.conn()).import_library(library).unwrap();
+        let tree = UnitRepo::new(db.conn()).list_tree_by_library(created.id.unwrap()).unwrap();
+        let last = format!("U{}", MAX_TREE_DEPTH - 1);
+        assert_eq!(tree.last().map(|n| (n.name.as_str(), n.depth as usize)), Some((last.as_str(), MAX_TREE_DEPTH - 1)));
+        let loaded = UnitRepo::new(db.conn()).get_by_library_id(created.id.unwrap()).unwrap();
+        assert_eq!(crate::models::tree_shape(&loaded).depth, MAX_TREE_DEPTH);
+    }
+
+    #[test]
+    fn test_import_library_with_versions() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let mut library = Library::new(
+            "Imported".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        library.id = Some(42);
+        library.set_version(3);
+        let versions = vec![
+            serde_json::json!({"version": 1, "timestamp": 1000, "description": "First", "data": "{}"}),
+            serde_json::json!({"version": 2, "timestamp": 2000, "description": null, "data": {"name": "x"}}),
+            // Same as the current version: the fresh snapshot is kept
+            serde_json::json!({"version": 3, "timestamp": 3000, "data": "{}"}),
+        ];
+
+        let created = service.import_library_with_versions(library, &versions).unwrap();
+        let snapshots = service.get_library_versions(created.id.unwrap()).unwrap();
+        let mut got: Vec<(i64, Option<String>)> =
+            snapshots.iter().map(|s| (s.version, s.description.clone())).collect();
+        got.sort();
+        assert_eq!(got, vec![(1, Some("First".to_string())), (2, None), (3, None)]);
+        assert_eq!(snapshots.iter().find(|s| s.version == 1).unwrap().timestamp, 1000);
+
+        let bad = vec![serde_json::json!({"timestamp": 1})];
+        let lib = Library::new("Bad".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        assert!(service.import_library_with_versions(lib, &bad).is_err());
+    }
+
+    /// Make every insert into `table` fail
+    fn fail_inserts(db: &Database, table: &str) {
+        db.conn()
+            .execute_batch(&format!(
+                "CREATE TEMP TRIGGER fail_{table}_insert BEFORE INSERT ON {table}
+                 BEGIN SELECT RAISE(ABORT, 'insert failed'); END;"
+            ))
+            .unwrap();
+    }
+
+    fn count(db: &Database, table: &str) -> i64 {
+        db.conn()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_create_library_failure_leaves_nothing_behind() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        // Fails after the library, its snapshot and its categories are inserted
+        fail_inserts(&db, "branches");
+        let library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+
+        assert!(service.create_library(library).is_err());
+        for table in ["libraries", "snapshots", "branch_categories", "branches"] {
+            assert_eq!(count(&db, table), 0, "{}", table);
+        }
+        assert!(db.conn().is_autocommit());
+    }
+
+    #[test]
+    fn test_import_library_with_bad_version_leaves_nothing_behind() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        library.add_unit(Unit::new("HQ".to_string(), "Company".to_string()));
+        let bad = vec![serde_json::json!({"timestamp": 1})];
+
+        assert!(service.import_library_with_versions(library, &bad).is_err());
+        for table in ["libraries", "snapshots", "units"] {
+            assert_eq!(count(&db, table), 0, "{}", table);
+        }
+    }
+
+    #[test]
+    fn test_save_library_snapshot_failure_keeps_previous_row() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let created = service
+            .create_library(Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string()))
+            .unwrap();
+        fail_inserts(&db, "snapshots");
+        let renamed = Library { name: "Renamed".to_string(), ..created.clone() };
+
+        assert!(service.save_library(renamed, true).is_err());
+        let stored = service.get_library(created.id.unwrap()).unwrap().unwrap();
+        assert_eq!(stored.name, "Test");
+        assert_eq!(stored.version, created.version);
+    }
+
+    #[test]
+    fn test_find_duplicate() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = Library::new(
+            "Test".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        assert!(service.find_duplicate(&library).unwrap().is_none());
+        let created = service.create_library(library.clone()).unwrap();
+        assert_eq!(service.find_duplicate(&library).unwrap().unwrap().id, created.id);
+    }
+
+    #[test]
+    fn test_create_library_rejects_duplicate_identity() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let created = service
+            .create_library(Library::new("US Army".to_string(), "US".to_string(), "2003".to_string(), "A".to_string()))
+            .unwrap();
+
+        let err = service
+            .create_library(Library::new(" us army".to_string(), "us".to_string(), "2003 ".to_string(), "B".to_string()))
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LibraryServiceError>(),
+            Some(&LibraryServiceError::Duplicate { existing_id: created.id.unwrap() })
+        );
+        assert_eq!(service.list_libraries().unwrap().len(), 1);
+
+        // A different era is a different library
+        service
+            .create_library(Library::new("US Army".to_string(), "US".to_string(), "2020".to_string(), "A".to_string()))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_create_library_with_allow_duplicate() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = Library::new("US Army".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        let first = service.create_library(library.clone()).unwrap();
+        let second = service.create_library_with(library.clone(), true).unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(service.list_libraries().unwrap().len(), 2);
+        // Importing the same file twice is decided by the caller, not refused here
+        service.import_library(library).unwrap();
+    }
+
     #[test]
     fn test_save_library_with_snapshot() {
         let db = Database::open_in_memory().unwrap();
@@ -192,6 +1073,411 @@ mod tests {
         assert_eq!(snapshots.len(), 2);
     }
 
+    #[test]
+    fn test_create_snapshot_stores_description_and_units() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = service
+            .import_library(Library {
+                units: vec![Unit::new("HQ".to_string(), "Staff".to_string())],
+                ..Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string())
+            })
+            .unwrap();
+        let lib_id = library.id.unwrap();
+
+        let snapshot = service.create_snapshot(lib_id, "  Before reorganisation  ").unwrap();
+        assert_eq!(snapshot.version, 2);
+        assert_eq!(service.get_library(lib_id).unwrap().unwrap().version, 2);
+        let stored = service.get_latest_version(lib_id).unwrap().unwrap();
+        assert_eq!(stored.description.as_deref(), Some("Before reorganisation"));
+        let restored = service.restore_from_version(lib_id, 2).unwrap().unwrap();
+        assert_eq!(restored.units.len(), 1);
+        assert_eq!(restored.units[0].name, "HQ");
+
+        // A blank description is stored as none
+        let blank = service.create_snapshot(lib_id, "   ").unwrap();
+        assert_eq!(service.get_latest_version(lib_id).unwrap().unwrap().description, None);
+        assert_eq!(blank.version, 3);
+    }
+
+    #[test]
+    fn test_snapshots_join_the_callers_transaction() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let version = service.get_library(lib_id).unwrap().unwrap().version;
+        let versions = service.get_library_versions(lib_id).unwrap().len();
+
+        let tx = db.conn().unchecked_transaction().unwrap();
+        service.create_snapshot(lib_id, "Inside").unwrap();
+        service.create_autosave_snapshot(lib_id).unwrap();
+        tx.rollback().unwrap();
+        assert_eq!(service.get_library_versions(lib_id).unwrap().len(), versions);
+        assert!(VersionRepo::new(db.conn()).list_autosaves(lib_id).unwrap().is_empty());
+        assert_eq!(service.get_library(lib_id).unwrap().unwrap().version, version);
+    }
+
+    #[test]
+    fn test_apply_bulk_tags_without_snapshots() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let ids: Vec<i64> = [vec!["ww2".to_string()], vec!["ww2".to_string(), "draft".to_string()], vec![]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, tags)| {
+                let mut library = Library::new(format!("L{}", i), "US".to_string(), "1944".to_string(), String::new());
+                library.tags = tags;
+                service.create_library(library).unwrap().id.unwrap()
+            })
+            .collect();
+
+        let changed = service
+            .apply_bulk_tags(&ids[..2], &["europe".to_string()], &["draft".to_string()])
+            .unwrap();
+        assert_eq!(changed, 2);
+        let tags = |id| service.get_library(id).unwrap().unwrap().tags;
+        assert_eq!(tags(ids[0]), ["ww2", "europe"]);
+        assert_eq!(tags(ids[1]), ["ww2", "europe"]);
+        assert!(tags(ids[2]).is_empty());
+        assert_eq!(service.get_library(ids[0]).unwrap().unwrap().version, 1);
+        assert_eq!(service.get_library_versions(ids[0]).unwrap().len(), 1);
+        // Nothing left to change
+        assert_eq!(service.apply_bulk_tags(&ids[..2], &["europe".to_string()], &[]).unwrap(), 0);
+        assert!(service.apply_bulk_tags(&[ids[2] + 100], &[], &[]).is_err());
+    }
+
+    #[test]
+    fn test_apply_bulk_tags_joins_the_callers_transaction() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = Library::new("L".to_string(), "US".to_string(), "1944".to_string(), String::new());
+        let id = service.create_library(library).unwrap().id.unwrap();
+
+        let tx = db.conn().unchecked_transaction().unwrap();
+        assert_eq!(service.apply_bulk_tags(&[id], &["europe".to_string()], &[]).unwrap(), 1);
+        tx.rollback().unwrap();
+        assert!(service.get_library(id).unwrap().unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn test_restore_version_records_a_new_snapshot() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = service
+            .import_library(Library {
+                units: vec![Unit::new("HQ".to_string(), "Staff".to_string())],
+                ..Library::new("Original".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string())
+            })
+            .unwrap();
+        let lib_id = library.id.unwrap();
+        service.create_snapshot(lib_id, "with HQ").unwrap();
+        let mut renamed = service.get_library(lib_id).unwrap().unwrap();
+        renamed.name = "Renamed".to_string();
+        service.save_library(renamed, true).unwrap();
+        UnitRepo::new(db.conn()).delete_by_library(lib_id).unwrap();
+
+        let snapshot = service.restore_version(lib_id, 2).unwrap();
+        assert_eq!(snapshot.version, 4);
+        assert_eq!(snapshot.description.as_deref(), Some("Restored from v2"));
+        let current = service.get_library(lib_id).unwrap().unwrap();
+        assert_eq!((current.name.as_str(), current.version), ("Original", 4));
+        let units = UnitRepo::new(db.conn()).get_by_library_id(lib_id).unwrap();
+        assert_eq!(units.len(), 1);
+        // Nothing was dropped from the history
+        assert_eq!(service.get_library_versions(lib_id).unwrap().len(), 4);
+        assert!(service.restore_version(lib_id, 99).is_err());
+    }
+
+    /// Library at v1 with an "HQ" unit, then saved as v2 named "Renamed" with units "A", "B"
+    fn library_with_two_versions(db: &Database) -> i64 {
+        let service = LibraryService::new(db.conn());
+        let lib_id = service
+            .import_library(Library {
+                units: vec![Unit::new("HQ".to_string(), "Staff".to_string())],
+                ..Library::new("Original".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string())
+            })
+            .unwrap()
+            .id
+            .unwrap();
+        service.create_snapshot(lib_id, "with HQ").unwrap();
+        let unit_repo = UnitRepo::new(db.conn());
+        unit_repo.delete_by_library(lib_id).unwrap();
+        for name in ["A", "B"] {
+            unit_repo.create_tree(lib_id, &mut Unit::new(name.to_string(), "Company".to_string())).unwrap();
+        }
+        let mut renamed = service.get_library(lib_id).unwrap().unwrap();
+        renamed.name = "Renamed".to_string();
+        service.save_library(renamed, true).unwrap();
+        lib_id
+    }
+
+    fn unit_names(db: &Database, lib_id: i64) -> Vec<String> {
+        UnitRepo::new(db.conn())
+            .get_by_library_id(lib_id)
+            .unwrap()
+            .into_iter()
+            .map(|u| u.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_revert_to_version_twice_is_idempotent() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+
+        let first = service.revert_to_version(lib_id, 2).unwrap();
+        assert_eq!(first.description.as_deref(), Some("Reverted to v2"));
+        let after_first = service.get_library(lib_id).unwrap().unwrap();
+        let units_first = unit_names(&db, lib_id);
+        assert_eq!(after_first.name, "Original");
+        assert_eq!(units_first, ["HQ"]);
+
+        let second = service.revert_to_version(lib_id, 2).unwrap();
+        assert_eq!(second.version, first.version + 1);
+        let after_second = service.get_library(lib_id).unwrap().unwrap();
+        assert_eq!(
+            Library { version: 0, ..after_second },
+            Library { version: 0, ..after_first }
+        );
+        assert_eq!(unit_names(&db, lib_id), units_first);
+    }
+
+    #[test]
+    fn test_revert_to_version_failure_leaves_database_untouched() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let before = service.get_library(lib_id).unwrap().unwrap();
+        let versions_before = service.get_library_versions(lib_id).unwrap().len();
+        // Fail the revert after the old units are deleted, while the snapshot's are inserted
+        db.conn()
+            .execute_batch(
+                "CREATE TEMP TRIGGER fail_unit_insert BEFORE INSERT ON units
+                 BEGIN SELECT RAISE(ABORT, 'insert failed'); END;",
+            )
+            .unwrap();
+
+        assert!(service.revert_to_version(lib_id, 2).is_err());
+        assert_eq!(service.get_library(lib_id).unwrap().unwrap(), before);
+        assert_eq!(unit_names(&db, lib_id), ["A", "B"]);
+        assert_eq!(service.get_library_versions(lib_id).unwrap().len(), versions_before);
+    }
+
+    #[test]
+    fn test_revert_to_version_joins_the_callers_transaction() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let versions_before = service.get_library_versions(lib_id).unwrap().len();
+
+        let tx = db.conn().unchecked_transaction().unwrap();
+        service.revert_to_version(lib_id, 2).unwrap();
+        assert_eq!(unit_names(&db, lib_id), ["HQ"]);
+        tx.rollback().unwrap();
+        assert_eq!(unit_names(&db, lib_id), ["A", "B"]);
+        assert_eq!(service.get_library_versions(lib_id).unwrap().len(), versions_before);
+    }
+
+    fn branch_names(db: &Database, lib_id: i64) -> Vec<String> {
+        BranchRepo::new(db.conn())
+            .list_by_library(lib_id)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name_en)
+            .collect()
+    }
+
+    #[test]
+    fn test_revert_to_version_restores_levels_categories_and_branches() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let level_repo = FormationLevelRepo::new(db.conn());
+        let mut level = CustomFormationLevel::new(lib_id, "Рота".to_string(), "Company".to_string(), 5);
+        level_repo.create(&mut level).unwrap();
+        let unit_repo = UnitRepo::new(db.conn());
+        let a = unit_repo.get_by_library_id(lib_id).unwrap()[0].id.unwrap();
+        unit_repo.set_formation_level(a, level.id).unwrap();
+        let branches_before = branch_names(&db, lib_id);
+        let categories_before = BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap().len();
+        let version = service.create_snapshot(lib_id, "with levels").unwrap().version;
+
+        BranchRepo::new(db.conn()).delete_by_library(lib_id).unwrap();
+        BranchCategoryRepo::new(db.conn()).delete_by_library(lib_id).unwrap();
+        level_repo.delete_by_library(lib_id).unwrap();
+        service.revert_to_version(lib_id, version).unwrap();
+
+        assert_eq!(branch_names(&db, lib_id), branches_before);
+        let categories = BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        assert_eq!(categories.len(), categories_before);
+        let category_ids: HashSet<i64> = categories.iter().filter_map(|c| c.id).collect();
+        assert!(BranchRepo::new(db.conn())
+            .list_by_library(lib_id)
+            .unwrap()
+            .iter()
+            .all(|b| b.category_id.is_some_and(|id| category_ids.contains(&id))));
+        let levels = level_repo.list_by_library(lib_id).unwrap();
+        assert_eq!(levels.len(), 1);
+        let units = unit_repo.get_by_library_id(lib_id).unwrap();
+        assert_eq!(units[0].name, "A");
+        assert_eq!(units[0].formation_level_id, levels[0].id);
+    }
+
+    #[test]
+    fn test_revert_to_version_without_units_empties_the_tree() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = service
+            .create_library(Library::new("Empty".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string()))
+            .unwrap()
+            .id
+            .unwrap();
+        UnitRepo::new(db.conn())
+            .create_tree(lib_id, &mut Unit::new("HQ".to_string(), "Staff".to_string()))
+            .unwrap();
+
+        service.revert_to_version(lib_id, 1).unwrap();
+        assert!(unit_names(&db, lib_id).is_empty());
+    }
+
+    #[test]
+    fn test_revert_to_plain_library_snapshot_keeps_tables() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let mut legacy = service.library_with_units(lib_id).unwrap();
+        legacy.name = "Legacy".to_string();
+        legacy.units.clear();
+        let mut snapshot = Snapshot::new(lib_id, 50, serde_json::to_string(&legacy).unwrap());
+        VersionRepo::new(db.conn()).create(&mut snapshot).unwrap();
+        let branches_before = branch_names(&db, lib_id);
+
+        service.revert_to_version(lib_id, 50).unwrap();
+        assert_eq!(service.get_library(lib_id).unwrap().unwrap().name, "Legacy");
+        assert_eq!(unit_names(&db, lib_id), ["A", "B"]);
+        assert_eq!(branch_names(&db, lib_id), branches_before);
+    }
+
+    #[test]
+    fn test_delete_snapshot_keeps_the_last_one() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = service
+            .create_library(Library::new(
+                "Test".to_string(),
+                "US".to_string(),
+                "2003".to_string(),
+                "Author".to_string(),
+            ))
+            .unwrap();
+        let lib_id = library.id.unwrap();
+        let second = service.create_snapshot(lib_id, "").unwrap();
+        let first_id = service.get_library_versions(lib_id).unwrap()[1].id.unwrap();
+
+        service.delete_snapshot(lib_id, first_id).unwrap();
+        let versions = service.get_library_versions(lib_id).unwrap();
+        assert_eq!(versions.iter().map(|s| s.version).collect::<Vec<_>>(), [2]);
+        assert!(service.delete_snapshot(lib_id, second.id.unwrap()).is_err());
+        assert!(service.delete_snapshot(lib_id + 1, second.id.unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_autosave_does_not_bump_the_version() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let version = service.get_library(lib_id).unwrap().unwrap().version;
+        UnitRepo::new(db.conn()).create_tree(lib_id, &mut Unit::new("C".to_string(), "Company".to_string())).unwrap();
+
+        let autosave = service.create_autosave_snapshot(lib_id).unwrap();
+        assert!(autosave.is_autosave);
+        assert_eq!(autosave.version, version);
+        assert_eq!(autosave.description.as_deref(), Some(AUTOSAVE_DESCRIPTION));
+        assert_eq!(service.get_library(lib_id).unwrap().unwrap().version, version);
+        assert_eq!(service.get_latest_version(lib_id).unwrap().unwrap().version, version);
+        assert!(service.get_library_versions(lib_id).unwrap().iter().all(|s| !s.is_autosave));
+        // The next saved version follows on from the last one
+        assert_eq!(service.create_snapshot(lib_id, "").unwrap().version, version + 1);
+
+        let saved = SnapshotState::from_data(&autosave.data).unwrap().into_library();
+        assert_eq!(saved.units.len(), 3);
+        assert!(service.create_autosave_snapshot(lib_id + 100).is_err());
+    }
+
+    #[test]
+    fn test_autosaves_are_pruned_to_the_newest() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn()).with_autosave_keep(2);
+        let lib_id = library_with_two_versions(&db);
+        let ids: Vec<Option<i64>> = (0..4).map(|_| service.create_autosave_snapshot(lib_id).unwrap().id).collect();
+
+        let kept: Vec<Option<i64>> = VersionRepo::new(db.conn()).list_autosaves(lib_id).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(kept, [ids[3], ids[2]]);
+        let versions = service.get_library_versions(lib_id).unwrap().len();
+        let history = service.get_history(lib_id).unwrap();
+        assert_eq!(history.len(), versions + 2);
+        assert_eq!(history[0].id, ids[3]);
+
+        // At least one is always kept
+        let service = LibraryService::new(db.conn()).with_autosave_keep(0);
+        let last = service.create_autosave_snapshot(lib_id).unwrap();
+        assert_eq!(VersionRepo::new(db.conn()).list_autosaves(lib_id).unwrap(), [last]);
+    }
+
+    #[test]
+    fn test_restore_and_delete_autosave() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let lib_id = library_with_two_versions(&db);
+        let autosave = service.create_autosave_snapshot(lib_id).unwrap();
+        UnitRepo::new(db.conn()).delete_by_library(lib_id).unwrap();
+        UnitRepo::new(db.conn()).create_tree(lib_id, &mut Unit::new("Z".to_string(), "Company".to_string())).unwrap();
+
+        let restored = service.restore_autosave(lib_id, autosave.id.unwrap()).unwrap();
+        assert_eq!(restored.description, Some(format!("Restored from autosave of v{}", autosave.version)));
+        assert!(!restored.is_autosave);
+        assert_eq!(unit_names(&db, lib_id), ["A", "B"]);
+        assert!(service.restore_autosave(lib_id + 1, autosave.id.unwrap()).is_err());
+        let first_version = service.get_library_versions(lib_id).unwrap().last().unwrap().id.unwrap();
+        assert!(service.restore_autosave(lib_id, first_version).is_err());
+
+        assert!(service.delete_snapshot(lib_id + 1, autosave.id.unwrap()).is_err());
+        service.delete_snapshot(lib_id, autosave.id.unwrap()).unwrap();
+        assert!(VersionRepo::new(db.conn()).list_autosaves(lib_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_create_snapshot_never_reuses_a_version() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let library = service
+            .create_library(Library::new(
+                "Test".to_string(),
+                "US".to_string(),
+                "2003".to_string(),
+                "Author".to_string(),
+            ))
+            .unwrap();
+        let lib_id = library.id.unwrap();
+        // A snapshot ahead of the library's own version, e.g. from an imported history
+        VersionRepo::new(db.conn())
+            .create(&mut Snapshot::new(lib_id, 2, "{}".to_string()))
+            .unwrap();
+
+        let first = service.create_snapshot(lib_id, "first").unwrap();
+        let second = service.create_snapshot(lib_id, "second").unwrap();
+        assert_eq!((first.version, second.version), (3, 4));
+        let versions: Vec<i64> = service
+            .get_library_versions(lib_id)
+            .unwrap()
+            .iter()
+            .map(|s| s.version)
+            .collect();
+        assert_eq!(versions, [4, 3, 2, 1]);
+        assert!(service.create_snapshot(lib_id + 1, "missing").is_err());
+    }
+
     #[test]
     fn test_search_libraries() {
         let db = Database::open_in_memory().unwrap();
@@ -0,0 +1,137 @@
+//! Library lint rules: problems found by checking units against the library's
+//! own settings (formation levels and their expected sizes).
+//!
+//! Every rule is a plain function over the unit tree; [`lint_units`] runs them
+//! all and returns problems in depth-first unit order.
+
+use crate::models::{walk_units, CustomFormationLevel, Unit};
+
+/// What a problem is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// Aggregated personnel below the level's minimum
+    Understrength,
+    /// Aggregated personnel above the level's maximum
+    Overstrength,
+}
+
+/// One problem found on a unit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub unit_id: Option<i64>,
+    pub unit_name: String,
+    pub message: String,
+}
+
+/// Formation level of a unit, matched by unit type against the level names
+pub fn level_for_unit<'a>(unit: &Unit, levels: &'a [CustomFormationLevel]) -> Option<&'a CustomFormationLevel> {
+    levels.iter().find(|l| l.matches_name(&unit.unit_type))
+}
+
+/// Units whose aggregated personnel (own plus all subordinates) falls outside
+/// the expected range of their formation level. Levels without a range and
+/// units with no matching level are skipped.
+pub fn lint_personnel_ranges(units: &[Unit], levels: &[CustomFormationLevel]) -> Vec<Problem> {
+    let mut out = Vec::new();
+    for unit in walk_units(units).map(|v| v.unit) {
+        if let Some(level) = level_for_unit(unit, levels).filter(|l| l.has_personnel_range()) {
+            let total = unit.total_personnel();
+            let range = level.personnel_range_label().unwrap_or_default();
+            let kind = match (level.min_personnel, level.max_personnel) {
+                (Some(min), _) if total < min as usize => Some(ProblemKind::Understrength),
+                (_, Some(max)) if total > max as usize => Some(ProblemKind::Overstrength),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let what = match kind {
+                    ProblemKind::Understrength => "understrength",
+                    ProblemKind::Overstrength => "overstrength",
+                };
+                out.push(Problem {
+                    kind,
+                    unit_id: unit.id,
+                    unit_name: unit.name.clone(),
+                    message: format!(
+                        "{} is {}: {} personnel, expected {} for a {}",
+                        unit.name, what, total, range, level.name_en
+                    ),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Run every lint rule over a library's units
+pub fn lint_units(units: &[Unit], levels: &[CustomFormationLevel]) -> Vec<Problem> {
+    lint_personnel_ranges(units, levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Personnel;
+
+    fn platoon_level(min: Option<u32>, max: Option<u32>) -> CustomFormationLevel {
+        let mut level = CustomFormationLevel::new(1, "взвод".to_string(), "platoon".to_string(), 3);
+        level.min_personnel = min;
+        level.max_personnel = max;
+        level
+    }
+
+    /// Platoon with `own` personnel and two squads of `per_squad` each
+    fn platoon(own: usize, per_squad: usize) -> Unit {
+        let mut platoon = Unit::new("1st Platoon".to_string(), "Platoon".to_string());
+        platoon.id = Some(7);
+        for _ in 0..own {
+            platoon.add_personnel(Personnel::new("HQ".to_string()));
+        }
+        for name in ["1st Squad", "2nd Squad"] {
+            let mut squad = Unit::new(name.to_string(), "squad".to_string());
+            for _ in 0..per_squad {
+                squad.add_personnel(Personnel::new("Rifleman".to_string()));
+            }
+            platoon.add_child(squad);
+        }
+        platoon
+    }
+
+    #[test]
+    fn test_inside_range_is_clean() {
+        let levels = [platoon_level(Some(20), Some(45))];
+        assert!(lint_units(&[platoon(2, 9)], &levels).is_empty());
+        // Bounds are inclusive
+        assert!(lint_units(&[platoon(2, 9)], &[platoon_level(Some(20), Some(20))]).is_empty());
+    }
+
+    #[test]
+    fn test_below_range_counts_subordinates() {
+        let levels = [platoon_level(Some(30), Some(45))];
+        let problems = lint_units(&[platoon(2, 9)], &levels);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::Understrength);
+        assert_eq!(problems[0].unit_id, Some(7));
+        assert_eq!(
+            problems[0].message,
+            "1st Platoon is understrength: 20 personnel, expected 30–45 for a platoon"
+        );
+    }
+
+    #[test]
+    fn test_above_range() {
+        let levels = [platoon_level(None, Some(45))];
+        let problems = lint_units(&[platoon(100, 200)], &levels);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::Overstrength);
+        assert!(problems[0].message.contains("500 personnel, expected ≤ 45"));
+    }
+
+    #[test]
+    fn test_level_without_range_is_skipped() {
+        let levels = [platoon_level(None, None)];
+        assert!(lint_units(&[platoon(100, 200)], &levels).is_empty());
+        // Units whose type matches no level are skipped too
+        assert!(lint_units(&[platoon(100, 200)], &[]).is_empty());
+    }
+}
@@ -0,0 +1,288 @@
+//! Per-operation timing metrics for the current session
+//!
+//! Wrap service entry points with [`timed`] (or [`timed_counted`] when the
+//! operation handles a known number of rows). Durations are kept in a small
+//! in-memory histogram per operation; operations slower than the threshold are
+//! reported to the slow-operation listener (the status bar in the GUI).
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Default duration above which an operation is reported as slow
+pub const DEFAULT_SLOW_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// Upper bounds (inclusive, in milliseconds) of the histogram buckets; the last bucket is open-ended
+pub const BUCKET_BOUNDS_MS: [u64; 6] = [10, 50, 100, 250, 500, 1000];
+
+/// Aggregated timings of one operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationStats {
+    pub count: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// Total rows processed (operations that report a row count)
+    pub rows: u64,
+    /// Counts per bucket of [`BUCKET_BOUNDS_MS`] plus one overflow bucket
+    pub buckets: [u64; BUCKET_BOUNDS_MS.len() + 1],
+}
+
+impl Default for OperationStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            rows: 0,
+            buckets: [0; BUCKET_BOUNDS_MS.len() + 1],
+        }
+    }
+}
+
+impl OperationStats {
+    fn add(&mut self, duration: Duration, rows: Option<usize>) {
+        self.count += 1;
+        self.total += duration;
+        self.min = self.min.min(duration);
+        self.max = self.max.max(duration);
+        self.rows += rows.unwrap_or(0) as u64;
+        self.buckets[bucket_index(duration)] += 1;
+    }
+
+    /// Mean duration (zero when nothing was recorded)
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count as u32
+        }
+    }
+}
+
+/// Index of the histogram bucket for a duration
+pub fn bucket_index(duration: Duration) -> usize {
+    let ms = duration.as_millis();
+    BUCKET_BOUNDS_MS
+        .iter()
+        .position(|&bound| ms <= bound as u128)
+        .unwrap_or(BUCKET_BOUNDS_MS.len())
+}
+
+/// An operation that took longer than the threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowOperation {
+    pub name: String,
+    pub duration: Duration,
+    pub rows: Option<usize>,
+}
+
+impl SlowOperation {
+    /// Short text for the status bar, e.g. "Slow operation: save_library took 1.2 s (40 rows)"
+    pub fn message(&self) -> String {
+        let mut msg = format!(
+            "Slow operation: {} took {:.1} s",
+            self.name,
+            self.duration.as_secs_f64()
+        );
+        if let Some(rows) = self.rows {
+            msg.push_str(&format!(" ({} rows)", rows));
+        }
+        msg
+    }
+}
+
+/// Collects timings per operation name
+#[derive(Debug, Clone)]
+pub struct MetricsRecorder {
+    threshold: Duration,
+    stats: BTreeMap<String, OperationStats>,
+}
+
+impl Default for MetricsRecorder {
+    fn default() -> Self {
+        Self::new(DEFAULT_SLOW_THRESHOLD)
+    }
+}
+
+impl MetricsRecorder {
+    /// Create an empty recorder with the given slow-operation threshold
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            stats: BTreeMap::new(),
+        }
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub fn set_threshold(&mut self, threshold: Duration) {
+        self.threshold = threshold;
+    }
+
+    /// Record one run. Returns the slow-operation report if it exceeded the threshold.
+    pub fn record(&mut self, name: &str, duration: Duration, rows: Option<usize>) -> Option<SlowOperation> {
+        self.stats.entry(name.to_string()).or_default().add(duration, rows);
+        (duration > self.threshold).then(|| SlowOperation {
+            name: name.to_string(),
+            duration,
+            rows,
+        })
+    }
+
+    /// Stats of one operation
+    pub fn get(&self, name: &str) -> Option<&OperationStats> {
+        self.stats.get(name)
+    }
+
+    /// All operations, sorted by name
+    pub fn snapshot(&self) -> Vec<(String, OperationStats)> {
+        self.stats.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.stats.clear();
+    }
+}
+
+type SlowListener = Box<dyn Fn(&SlowOperation) + Send + Sync>;
+
+struct Global {
+    recorder: Mutex<MetricsRecorder>,
+    listener: Mutex<Option<SlowListener>>,
+}
+
+fn global() -> &'static Global {
+    static GLOBAL: OnceLock<Global> = OnceLock::new();
+    GLOBAL.get_or_init(|| Global {
+        recorder: Mutex::new(MetricsRecorder::default()),
+        listener: Mutex::new(None),
+    })
+}
+
+/// Set the callback invoked for slow operations (replaces the previous one).
+pub fn set_slow_listener<F>(listener: F)
+where
+    F: Fn(&SlowOperation) + Send + Sync + 'static,
+{
+    *global().listener.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(listener));
+}
+
+/// Record a run in the session recorder and notify the listener if it was slow.
+pub fn record(name: &str, duration: Duration, rows: Option<usize>) {
+    let slow = global()
+        .recorder
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .record(name, duration, rows);
+    if let Some(slow) = slow {
+        log::warn!("{}", slow.message());
+        if let Some(listener) = global().listener.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            listener(&slow);
+        }
+    }
+}
+
+/// Run `f` and record how long it took under `name`.
+pub fn timed<T, F: FnOnce() -> T>(name: &str, f: F) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(name, start.elapsed(), None);
+    result
+}
+
+/// Like [`timed`], also recording the number of rows reported by `count` for the result.
+pub fn timed_counted<T, F, C>(name: &str, f: F, count: C) -> T
+where
+    F: FnOnce() -> T,
+    C: FnOnce(&T) -> Option<usize>,
+{
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    record(name, elapsed, count(&result));
+    result
+}
+
+/// Stats collected during this session, sorted by operation name
+pub fn session_stats() -> Vec<(String, OperationStats)> {
+    global().recorder.lock().unwrap_or_else(|e| e.into_inner()).snapshot()
+}
+
+/// Change the session slow-operation threshold
+pub fn set_slow_threshold(threshold: Duration) {
+    global().recorder.lock().unwrap_or_else(|e| e.into_inner()).set_threshold(threshold);
+}
+
+/// Plain-text table of the session stats (for the debug dialog)
+pub fn format_stats(stats: &[(String, OperationStats)]) -> String {
+    let mut out = String::from("operation                 count    mean     max   rows\n");
+    for (name, s) in stats {
+        out.push_str(&format!(
+            "{:<24} {:>6} {:>5}ms {:>5}ms {:>6}\n",
+            name,
+            s.count,
+            s.mean().as_millis(),
+            s.max.as_millis(),
+            s.rows
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_index() {
+        assert_eq!(bucket_index(Duration::from_millis(0)), 0);
+        assert_eq!(bucket_index(Duration::from_millis(10)), 0);
+        assert_eq!(bucket_index(Duration::from_millis(11)), 1);
+        assert_eq!(bucket_index(Duration::from_millis(600)), 5);
+        assert_eq!(bucket_index(Duration::from_secs(5)), BUCKET_BOUNDS_MS.len());
+    }
+
+    #[test]
+    fn test_recorder_aggregates_per_operation() {
+        let mut rec = MetricsRecorder::new(Duration::from_secs(10));
+        rec.record("save_library", Duration::from_millis(20), Some(5));
+        rec.record("save_library", Duration::from_millis(40), Some(7));
+        rec.record("export_json", Duration::from_millis(3), None);
+        let save = rec.get("save_library").unwrap();
+        assert_eq!(save.count, 2);
+        assert_eq!(save.min, Duration::from_millis(20));
+        assert_eq!(save.max, Duration::from_millis(40));
+        assert_eq!(save.mean(), Duration::from_millis(30));
+        assert_eq!(save.rows, 12);
+        assert_eq!(save.buckets[1], 2);
+        let names: Vec<String> = rec.snapshot().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["export_json", "save_library"]);
+    }
+
+    #[test]
+    fn test_threshold_reports_slow_operations() {
+        let mut rec = MetricsRecorder::new(Duration::from_millis(100));
+        assert!(rec.record("refresh", Duration::from_millis(100), None).is_none());
+        let slow = rec.record("import", Duration::from_millis(1500), Some(300)).unwrap();
+        assert_eq!(slow.name, "import");
+        assert_eq!(slow.message(), "Slow operation: import took 1.5 s (300 rows)");
+        rec.set_threshold(Duration::from_secs(2));
+        assert!(rec.record("import", Duration::from_millis(1500), None).is_none());
+    }
+
+    #[test]
+    fn test_timed_records_into_session() {
+        let value = timed("metrics_test_op", || 42);
+        assert_eq!(value, 42);
+        let rows = timed_counted("metrics_test_rows", || vec![1, 2, 3], |v| Some(v.len()));
+        assert_eq!(rows.len(), 3);
+        let stats = session_stats();
+        assert!(stats.iter().any(|(n, s)| n == "metrics_test_op" && s.count >= 1));
+        assert!(stats.iter().any(|(n, s)| n == "metrics_test_rows" && s.rows >= 3));
+        assert!(format_stats(&stats).contains("metrics_test_op"));
+    }
+}
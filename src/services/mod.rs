@@ -1,5 +1,43 @@
 //! Service layer for business logic
 
 pub mod library_service;
+pub mod clipboard;
+pub mod diff;
+pub mod dirty;
+pub mod duplicates;
+pub mod editor_rows;
+pub mod events;
+pub mod history_prune;
+pub mod metrics;
+pub mod operations;
+pub mod recovery;
+pub mod level_mapping;
+pub mod library_impact;
+pub mod lint;
+pub mod sample;
+pub mod snapshot_diff;
+pub mod summary;
+pub mod unit_rows;
+pub mod unit_service;
 
-pub use library_service::LibraryService;
+pub use library_service::{unique_library_name, LibraryService, LibraryServiceError, AUTOSAVE_DESCRIPTION, DEFAULT_AUTOSAVE_KEEP};
+pub use clipboard::{paste_rows, ClipboardContent, Pasted};
+pub use dirty::DirtyState;
+pub use diff::{compare_formation, diff_libraries, find_unit, unit_path, ComparedRow, FieldChange, LibraryDiff, QuantityCell, RowMark, UnitChange};
+pub use editor_rows::{
+    orphaned_levels, referenced_categories, save_branch_categories, save_branches, save_branches_with_categories, save_editor_rows, save_formation_levels, stored_fingerprint, suggest_level_remap, EditorKind, EditorRows,
+    LevelRemap, OrphanedLevel, ReferencedCategory, RowsFingerprint,
+};
+pub use duplicates::{
+    find_duplicates, find_library_duplicates, merge_equipment, name_similarity, DuplicateOptions, DuplicatePair, UnitSummary,
+};
+pub use events::{ChangeBus, ChangeEvent, RefreshTarget, Subscription};
+pub use history_prune::{plan_prune, prune_history, prune_running, MaintenanceChange, MaintenanceWatch, PrunePolicy};
+pub use library_impact::{identity_change_impact, renames, DerivedArtifact, Impact};
+pub use level_mapping::{LevelMappingService, ScanOutcome};
+pub use lint::{lint_units, Problem, ProblemKind};
+pub use operations::{CancellationToken, OperationGuard, OperationRegistry, VersionHold};
+pub use recovery::{apply_entry, leftover_journals, pending_entries, remove_journals, Debounce, Journal, JournalEntry};
+pub use snapshot_diff::{diff_snapshots_staged, DiffGranularity, StagedDiff};
+pub use unit_rows::{diff_rows, RowChanges, RowOp};
+pub use unit_service::UnitService;
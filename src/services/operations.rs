@@ -0,0 +1,350 @@
+//! Registry of in-flight background operations
+//!
+//! Workers register when they start and hold the returned [`OperationGuard`];
+//! dropping the guard deregisters the operation, also when the worker panics.
+//! The main window consults the registry before closing so exports and imports
+//! are not cut off halfway.
+//!
+//! Operations on one library's data can say so, letting its windows show that
+//! maintenance is running, and flows that read library versions can hold them
+//! so maintenance leaves them alone.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Shared flag a worker polls to stop early
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Snapshot of one running operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationInfo {
+    pub id: u64,
+    pub name: String,
+    /// Whether the worker checks its cancellation token
+    pub cancellable: bool,
+    /// Fraction done (0.0..=1.0) if the worker reports it
+    pub progress: Option<f32>,
+    pub elapsed: Duration,
+    /// Library whose data the operation changes, if only one
+    pub library_id: Option<i64>,
+}
+
+struct Entry {
+    name: String,
+    library_id: Option<i64>,
+    cancellable: bool,
+    progress: Option<f32>,
+    started: Instant,
+    token: CancellationToken,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    entries: BTreeMap<u64, Entry>,
+    /// Held versions by hold id: library id and version numbers
+    holds: BTreeMap<u64, (i64, Vec<i64>)>,
+}
+
+/// Thread-safe list of running operations; clones share the same registry
+#[derive(Clone, Default)]
+pub struct OperationRegistry {
+    inner: Arc<(Mutex<Inner>, Condvar)>,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a starting operation. It stays listed until the guard is dropped.
+    pub fn register(&self, name: &str, cancellable: bool) -> OperationGuard {
+        self.register_entry(name, None, cancellable)
+    }
+
+    /// Register a starting operation that changes the data of one library
+    pub fn register_for_library(&self, name: &str, library_id: i64, cancellable: bool) -> OperationGuard {
+        self.register_entry(name, Some(library_id), cancellable)
+    }
+
+    fn register_entry(&self, name: &str, library_id: Option<i64>, cancellable: bool) -> OperationGuard {
+        let token = CancellationToken::new();
+        let mut inner = self.lock();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.entries.insert(
+            id,
+            Entry {
+                name: name.to_string(),
+                library_id,
+                cancellable,
+                progress: None,
+                started: Instant::now(),
+                token: token.clone(),
+            },
+        );
+        log::debug!("Operation started: {} (#{})", name, id);
+        OperationGuard {
+            registry: self.clone(),
+            id,
+            token,
+        }
+    }
+
+    /// Running operations in start order
+    pub fn running(&self) -> Vec<OperationInfo> {
+        self.lock()
+            .entries
+            .iter()
+            .map(|(&id, e)| OperationInfo {
+                id,
+                name: e.name.clone(),
+                cancellable: e.cancellable,
+                progress: e.progress,
+                elapsed: e.started.elapsed(),
+                library_id: e.library_id,
+            })
+            .collect()
+    }
+
+    /// Running operations that change the data of `library_id`, in start order
+    pub fn running_for_library(&self, library_id: i64) -> Vec<OperationInfo> {
+        self.running().into_iter().filter(|op| op.library_id == Some(library_id)).collect()
+    }
+
+    /// Keep `versions` of a library from being deleted by maintenance until
+    /// the returned hold is dropped
+    pub fn hold_versions(&self, library_id: i64, versions: &[i64]) -> VersionHold {
+        let mut inner = self.lock();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.holds.insert(id, (library_id, versions.to_vec()));
+        VersionHold { registry: self.clone(), id }
+    }
+
+    /// Versions of a library some flow is holding
+    pub fn held_versions(&self, library_id: i64) -> BTreeSet<i64> {
+        self.lock()
+            .holds
+            .values()
+            .filter(|(lib, _)| *lib == library_id)
+            .flat_map(|(_, versions)| versions.iter().copied())
+            .collect()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    /// Signal cancellation to every cancellable operation; returns how many were signalled
+    pub fn cancel_all(&self) -> usize {
+        let inner = self.lock();
+        let mut count = 0;
+        for entry in inner.entries.values().filter(|e| e.cancellable) {
+            entry.token.cancel();
+            count += 1;
+        }
+        count
+    }
+
+    /// Block until no operation is running or the timeout passes; true when idle
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let (_, condvar) = &*self.inner;
+        let guard = self.lock();
+        let (guard, _) = condvar
+            .wait_timeout_while(guard, timeout, |inner| !inner.entries.is_empty())
+            .unwrap_or_else(|e| e.into_inner());
+        guard.entries.is_empty()
+    }
+
+    fn finish(&self, id: u64) {
+        let removed = self.lock().entries.remove(&id);
+        if let Some(entry) = removed {
+            log::debug!(
+                "Operation finished: {} (#{}) after {:?}",
+                entry.name,
+                id,
+                entry.started.elapsed()
+            );
+        }
+        self.inner.1.notify_all();
+    }
+}
+
+/// Registration of one running operation; deregisters on drop
+pub struct OperationGuard {
+    registry: OperationRegistry,
+    id: u64,
+    token: CancellationToken,
+}
+
+impl OperationGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Token to poll in the worker
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Report progress as a fraction (clamped to 0.0..=1.0)
+    pub fn set_progress(&self, fraction: f32) {
+        if let Some(entry) = self.registry.lock().entries.get_mut(&self.id) {
+            entry.progress = Some(fraction.clamp(0.0, 1.0));
+        }
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.registry.finish(self.id);
+    }
+}
+
+/// Versions held by a flow that reads them; released on drop
+pub struct VersionHold {
+    registry: OperationRegistry,
+    id: u64,
+}
+
+impl Drop for VersionHold {
+    fn drop(&mut self) {
+        self.registry.lock().holds.remove(&self.id);
+    }
+}
+
+/// Multi-line description of running operations for the close dialog
+pub fn describe_running(operations: &[OperationInfo]) -> String {
+    operations
+        .iter()
+        .map(|op| match op.progress {
+            Some(p) => format!("{} — {:.0}% ({} s)", op.name, p * 100.0, op.elapsed.as_secs()),
+            None => format!("{} ({} s)", op.name, op.elapsed.as_secs()),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_deregisters_on_drop() {
+        let registry = OperationRegistry::new();
+        assert!(registry.is_idle());
+        let export = registry.register("Export HTML", false);
+        let import = registry.register("Import workbook", true);
+        let names: Vec<String> = registry.running().into_iter().map(|o| o.name).collect();
+        assert_eq!(names, vec!["Export HTML", "Import workbook"]);
+        drop(export);
+        assert_eq!(registry.running().len(), 1);
+        assert_eq!(registry.running()[0].id, import.id());
+        drop(import);
+        assert!(registry.is_idle());
+    }
+
+    #[test]
+    fn test_cancel_all_only_signals_cancellable() {
+        let registry = OperationRegistry::new();
+        let fixed = registry.register("Snapshot", false);
+        let stoppable = registry.register("Export", true);
+        let token = stoppable.token();
+        assert_eq!(registry.cancel_all(), 1);
+        assert!(token.is_cancelled());
+        assert!(stoppable.is_cancelled());
+        assert!(!fixed.is_cancelled());
+    }
+
+    #[test]
+    fn test_progress_is_reported_and_clamped() {
+        let registry = OperationRegistry::new();
+        let op = registry.register("Export", true);
+        assert_eq!(registry.running()[0].progress, None);
+        op.set_progress(0.25);
+        assert_eq!(registry.running()[0].progress, Some(0.25));
+        op.set_progress(3.0);
+        assert_eq!(registry.running()[0].progress, Some(1.0));
+        assert!(describe_running(&registry.running()).starts_with("Export — 100%"));
+    }
+
+    #[test]
+    fn test_worker_cancellation_and_wait_idle() {
+        let registry = OperationRegistry::new();
+        let guard = registry.register("Worker", true);
+        let worker = std::thread::spawn(move || {
+            let mut steps = 0;
+            while !guard.is_cancelled() {
+                steps += 1;
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            steps
+        });
+        assert!(!registry.wait_idle(Duration::from_millis(10)));
+        registry.cancel_all();
+        assert!(registry.wait_idle(Duration::from_secs(5)));
+        assert!(worker.join().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_library_operations_and_version_holds() {
+        let registry = OperationRegistry::new();
+        let _other = registry.register("Export HTML", false);
+        let prune = registry.register_for_library("Prune History", 7, false);
+        assert_eq!(registry.running_for_library(7).len(), 1);
+        assert_eq!(registry.running_for_library(7)[0].id, prune.id());
+        assert!(registry.running_for_library(8).is_empty());
+        drop(prune);
+        assert!(registry.running_for_library(7).is_empty());
+
+        let restore = registry.hold_versions(7, &[3]);
+        let compare = registry.hold_versions(7, &[3, 5]);
+        let _elsewhere = registry.hold_versions(8, &[1]);
+        assert_eq!(registry.held_versions(7), BTreeSet::from([3, 5]));
+        drop(compare);
+        assert_eq!(registry.held_versions(7), BTreeSet::from([3]));
+        drop(restore);
+        assert!(registry.held_versions(7).is_empty());
+        // Holds are not operations: closing the window does not wait for them
+        assert_eq!(registry.running().len(), 1);
+    }
+
+    #[test]
+    fn test_panicking_worker_deregisters() {
+        let registry = OperationRegistry::new();
+        let guard = registry.register("Doomed", false);
+        let result = std::thread::spawn(move || {
+            let _guard = guard;
+            panic!("worker failed");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(registry.is_idle());
+    }
+}
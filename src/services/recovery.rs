@@ -0,0 +1,451 @@
+//! Crash recovery journal for edits still open in editor windows.
+//!
+//! Each session appends the pending rows of its editors to its own journal
+//! file, one JSON entry per line. A clean shutdown deletes the file, so a
+//! journal found at startup was left by a session that did not exit cleanly
+//! unless its session still holds the lock on the journal's lock file.
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::services::editor_rows::{save_editor_rows, EditorKind, EditorRows};
+
+const JOURNAL_PREFIX: &str = "session-";
+const JOURNAL_EXTENSION: &str = "jsonl";
+const LOCK_EXTENSION: &str = "lock";
+
+/// One journaled state of an editor window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Database file the library is in; `None` for an in-memory database
+    pub database: Option<PathBuf>,
+    pub library_id: i64,
+    /// Library name at the time of the change, for the recovery dialog
+    pub library_name: String,
+    pub editor: EditorKind,
+    /// Unix time of the change
+    pub saved_at: i64,
+    /// Editor rows; `None` once the editor saved or dropped them
+    pub rows: Option<EditorRows>,
+}
+
+impl JournalEntry {
+    /// Pending rows of an editor
+    pub fn pending(database: Option<PathBuf>, library_id: i64, library_name: &str, rows: EditorRows) -> Self {
+        Self {
+            database,
+            library_id,
+            library_name: library_name.to_string(),
+            editor: rows.kind(),
+            saved_at: chrono::Utc::now().timestamp(),
+            rows: Some(rows),
+        }
+    }
+
+    /// Marker that the editor has nothing left to recover
+    pub fn finished(database: Option<PathBuf>, library_id: i64, library_name: &str, editor: EditorKind) -> Self {
+        Self {
+            database,
+            library_id,
+            library_name: library_name.to_string(),
+            editor,
+            saved_at: chrono::Utc::now().timestamp(),
+            rows: None,
+        }
+    }
+}
+
+/// Journal file of one session
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+    /// Lock file held for as long as the session runs; the system releases
+    /// it when the process ends, however it ends
+    _lock: Option<Arc<fs::File>>,
+}
+
+impl Journal {
+    /// Journal of the running process in `dir`. The start time keeps it apart
+    /// from a leftover journal of an earlier process with the same id.
+    pub fn for_session(dir: &Path) -> Self {
+        let started = chrono::Utc::now().timestamp_millis();
+        let name = format!("{}{}-{}.{}", JOURNAL_PREFIX, std::process::id(), started, JOURNAL_EXTENSION);
+        let path = dir.join(name);
+        let lock = match hold_lock(&lock_path(&path)) {
+            Ok(file) => Some(Arc::new(file)),
+            Err(e) => {
+                log::warn!("Failed to lock the recovery journal {}: {}", path.display(), e);
+                None
+            }
+        };
+        Self { path, _lock: lock }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry; the directory and file are created on first use
+    pub fn append(&self, entry: &JournalEntry) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Delete the journal on clean shutdown
+    pub fn remove(&self) -> Result<()> {
+        remove_journals(std::slice::from_ref(&self.path))
+    }
+}
+
+/// Lock file of the journal at `journal`
+fn lock_path(journal: &Path) -> PathBuf {
+    journal.with_extension(LOCK_EXTENSION)
+}
+
+/// Create the lock file at `path` and lock it
+fn hold_lock(path: &Path) -> Result<fs::File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+    file.try_lock()?;
+    Ok(file)
+}
+
+/// Whether a running session holds the lock of the journal at `journal`.
+/// Journals without a lock file come from builds that wrote none.
+fn held_by_running_session(journal: &Path) -> bool {
+    let Ok(file) = OpenOptions::new().write(true).open(lock_path(journal)) else {
+        return false;
+    };
+    matches!(file.try_lock(), Err(fs::TryLockError::WouldBlock))
+}
+
+/// Journals in `dir` left by sessions that ended without a clean shutdown;
+/// journals of sessions still running are left out
+pub fn leftover_journals(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_journal = path.extension().is_some_and(|e| e == JOURNAL_EXTENSION)
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(JOURNAL_PREFIX));
+        if is_journal && path.is_file() && !held_by_running_session(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Entries of a journal file. Lines that do not parse, such as a write cut
+/// short by the crash, are skipped.
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("Skipping unreadable line in {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Latest entry per database, library and editor across the journals,
+/// oldest first, leaving out editors that saved or dropped their rows
+pub fn pending_entries(paths: &[PathBuf]) -> Result<Vec<JournalEntry>> {
+    let mut latest: HashMap<(Option<PathBuf>, i64, EditorKind), JournalEntry> = HashMap::new();
+    for path in paths {
+        for entry in read_journal(path)? {
+            let key = (entry.database.as_deref().map(resolve_database), entry.library_id, entry.editor);
+            if latest.get(&key).is_none_or(|old| old.saved_at <= entry.saved_at) {
+                latest.insert(key, entry);
+            }
+        }
+    }
+    let mut pending: Vec<JournalEntry> = latest.into_values().filter(|e| e.rows.is_some()).collect();
+    pending.sort_by_key(|e| (e.saved_at, e.library_id));
+    Ok(pending)
+}
+
+/// Delete journal files with their lock files; ones already gone are not an error
+pub fn remove_journals(paths: &[PathBuf]) -> Result<()> {
+    for path in paths {
+        for file in [path.clone(), lock_path(path)] {
+            match fs::remove_file(&file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Absolute form of a database path, links resolved, for comparing paths;
+/// a file that is gone keeps the path as written
+fn resolve_database(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The same file, even when one of the paths is relative or goes through a link
+fn same_database(a: Option<&Path>, b: Option<&Path>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => resolve_database(a) == resolve_database(b),
+        (a, b) => a == b,
+    }
+}
+
+/// Save the recovered rows the same way the editor saves them on close.
+/// Fails without writing anything when the entry was journaled against
+/// another database file than the one of `conn`.
+pub fn apply_entry(conn: &Connection, entry: &JournalEntry) -> Result<()> {
+    let rows = entry
+        .rows
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Nothing to recover for library {}", entry.library_id))?;
+    let open = conn.path().filter(|p| !p.is_empty()).map(Path::new);
+    if !same_database(entry.database.as_deref(), open) {
+        let journaled = entry
+            .database
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "an unknown database".to_string());
+        anyhow::bail!("These changes were made in {}, not in the open database", journaled);
+    }
+    save_editor_rows(conn, entry.library_id, rows)
+}
+
+/// When to write the journal: `delay` after the last change, but no later
+/// than `max_wait` after the first change not yet written, so constant
+/// typing still gets journaled.
+#[derive(Debug, Clone)]
+pub struct Debounce {
+    delay: Duration,
+    max_wait: Duration,
+    first_change: Option<Instant>,
+    last_change: Option<Instant>,
+}
+
+impl Debounce {
+    pub fn new(delay: Duration, max_wait: Duration) -> Self {
+        Self {
+            delay,
+            max_wait,
+            first_change: None,
+            last_change: None,
+        }
+    }
+
+    /// Record a change; returns how long to wait before checking `due`
+    pub fn change(&mut self, now: Instant) -> Duration {
+        let first = *self.first_change.get_or_insert(now);
+        self.last_change = Some(now);
+        let deadline = (now + self.delay).min(first + self.max_wait);
+        deadline.saturating_duration_since(now)
+    }
+
+    /// Whether the pending changes should be written now; resets when they should
+    pub fn due(&mut self, now: Instant) -> bool {
+        let (Some(first), Some(last)) = (self.first_change, self.last_change) else {
+            return false;
+        };
+        let due = now >= last + self.delay || now >= first + self.max_wait;
+        if due {
+            self.first_change = None;
+            self.last_change = None;
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::{FormationLevelRepo, LibraryRepo};
+    use crate::db::Database;
+    use crate::models::{BranchCategory, CustomFormationLevel, Library};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("toeditor_recovery_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn categories(names: &[&str]) -> EditorRows {
+        EditorRows::BranchCategories(
+            names
+                .iter()
+                .map(|n| BranchCategory::new(1, n.to_string(), n.to_string()))
+                .collect(),
+        )
+    }
+
+    fn entry(library_id: i64, saved_at: i64, rows: Option<EditorRows>) -> JournalEntry {
+        JournalEntry {
+            database: None,
+            library_id,
+            library_name: "Test".to_string(),
+            editor: EditorKind::BranchCategories,
+            saved_at,
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_journal_round_trip_skips_cut_off_line() {
+        let dir = temp_dir("round_trip");
+        let journal = Journal::for_session(&dir);
+        let first = entry(1, 10, Some(categories(&["ПВО"])));
+        journal.append(&first).unwrap();
+        let mut file = OpenOptions::new().append(true).open(journal.path()).unwrap();
+        file.write_all(b"{\"library_id\":1,\"libr").unwrap();
+
+        assert_eq!(read_journal(journal.path()).unwrap(), vec![first]);
+        journal.remove().unwrap();
+        assert!(leftover_journals(&dir).unwrap().is_empty());
+        journal.remove().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_journals_of_running_sessions_are_not_leftovers() {
+        let dir = temp_dir("running");
+        let journal = Journal::for_session(&dir);
+        journal.append(&entry(1, 10, Some(categories(&["A"])))).unwrap();
+        assert!(leftover_journals(&dir).unwrap().is_empty());
+
+        // The session ends without removing its journal
+        let path = journal.path().to_path_buf();
+        drop(journal);
+        assert_eq!(leftover_journals(&dir).unwrap(), vec![path.clone()]);
+        remove_journals(std::slice::from_ref(&path)).unwrap();
+        assert!(!lock_path(&path).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pending_keeps_latest_and_drops_finished() {
+        let dir = temp_dir("pending");
+        let journal = Journal::for_session(&dir);
+        journal.append(&entry(1, 10, Some(categories(&["A"])))).unwrap();
+        journal.append(&entry(1, 20, Some(categories(&["A", "B"])))).unwrap();
+        journal.append(&entry(2, 15, Some(categories(&["C"])))).unwrap();
+        journal.append(&entry(2, 25, None)).unwrap();
+
+        let pending = pending_entries(&[journal.path().to_path_buf()]).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].library_id, 1);
+        assert_eq!(pending[0].rows, Some(categories(&["A", "B"])));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pending_keeps_libraries_of_other_databases_apart() {
+        let dir = temp_dir("pending_databases");
+        let journal = Journal::for_session(&dir);
+        let in_database = |name: &str, saved_at: i64, rows: EditorRows| JournalEntry {
+            database: Some(dir.join(name)),
+            ..entry(1, saved_at, Some(rows))
+        };
+        journal.append(&in_database("first.db", 10, categories(&["A"]))).unwrap();
+        journal.append(&in_database("second.db", 20, categories(&["B"]))).unwrap();
+        journal.append(&in_database("first.db", 30, categories(&["A", "C"]))).unwrap();
+
+        let pending = pending_entries(&[journal.path().to_path_buf()]).unwrap();
+        let databases: Vec<_> = pending.iter().map(|e| e.database.clone()).collect();
+        assert_eq!(databases, [Some(dir.join("second.db")), Some(dir.join("first.db"))]);
+        assert_eq!(pending[1].rows, Some(categories(&["A", "C"])));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_leftovers_of_missing_dir_are_empty() {
+        assert!(leftover_journals(&temp_dir("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_debounce_waits_for_pause_but_not_forever() {
+        let delay = Duration::from_millis(500);
+        let mut debounce = Debounce::new(delay, Duration::from_secs(2));
+        let start = Instant::now();
+        assert!(!debounce.due(start));
+        assert_eq!(debounce.change(start), delay);
+        assert!(!debounce.due(start + Duration::from_millis(300)));
+
+        // Changes every 400 ms never pause long enough; max_wait caps the wait
+        let mut now = start;
+        for _ in 0..4 {
+            now += Duration::from_millis(400);
+            assert!(!debounce.due(now));
+            debounce.change(now);
+        }
+        assert_eq!(debounce.change(now), Duration::from_millis(400));
+        assert!(debounce.due(start + Duration::from_secs(2)));
+        assert!(!debounce.due(start + Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_apply_entry_saves_rows() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Test".to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+        let mut level = CustomFormationLevel::new(lib_id, "взвод".to_string(), "platoon".to_string(), 3);
+        level.min_personnel = Some(25);
+
+        let recovered = JournalEntry::pending(None, lib_id, "Test", EditorRows::FormationLevels(vec![level]));
+        apply_entry(db.conn(), &recovered).unwrap();
+        let saved = FormationLevelRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].min_personnel, Some(25));
+
+        let finished = JournalEntry::finished(None, lib_id, "Test", EditorKind::FormationLevels);
+        assert!(apply_entry(db.conn(), &finished).is_err());
+    }
+
+    #[test]
+    fn test_apply_entry_refuses_another_database() {
+        let dir = temp_dir("other_database");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("toeditor.db");
+        let db = Database::open(&path).unwrap();
+        let mut library = Library::new("Test".to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let lib_id = library.id.unwrap();
+        let rows = categories(&["ПВО"]);
+
+        let elsewhere = JournalEntry::pending(Some(dir.join("other.db")), lib_id, "Test", rows.clone());
+        let err = apply_entry(db.conn(), &elsewhere).unwrap_err();
+        assert!(err.to_string().contains("other.db"), "{}", err);
+        let unknown = JournalEntry::pending(None, lib_id, "Test", rows.clone());
+        assert!(apply_entry(db.conn(), &unknown).is_err());
+
+        let here = JournalEntry::pending(Some(dir.join(".").join("toeditor.db")), lib_id, "Test", rows);
+        apply_entry(db.conn(), &here).unwrap();
+        drop(db);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,67 @@
+//! Built-in sample library for first-run onboarding
+//!
+//! A motor rifle battalion with companies, platoons, squads, personnel and
+//! equipment, plus formation levels (with expected sizes), branch categories
+//! and branches, stored as a full library export.
+
+use anyhow::{Context, Result};
+use crate::export::full::{parse_full_export, FullLibraryExport};
+use super::library_service::unique_library_name;
+
+/// Tag carried by libraries created from the sample
+pub const SAMPLE_TAG: &str = "sample";
+
+const SAMPLE_LIBRARY_JSON: &str = include_str!("../../samples/sample_library.json");
+
+/// The sample as a full export, renamed so it does not clash with
+/// `existing_names` and tagged with [`SAMPLE_TAG`]
+pub fn sample_library(existing_names: &[String]) -> Result<FullLibraryExport> {
+    let data: serde_json::Value =
+        serde_json::from_str(SAMPLE_LIBRARY_JSON).context("Embedded sample library is not valid JSON")?;
+    let mut export = parse_full_export(&data)?
+        .ok_or_else(|| anyhow::anyhow!("Embedded sample library is not a full export"))?;
+    export.library.name = unique_library_name(&export.library.name, existing_names);
+    if !export.library.tags.iter().any(|t| t == SAMPLE_TAG) {
+        export.library.tags.push(SAMPLE_TAG.to_string());
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::models::validate_library;
+    use crate::services::{lint_units, LibraryService};
+
+    #[test]
+    fn test_sample_passes_validation_and_lint() {
+        let export = sample_library(&[]).unwrap();
+        let library = &export.library;
+        assert!(validate_library(&library.name, &library.country, &library.era).is_empty());
+        assert_eq!(library.tags.iter().filter(|t| *t == SAMPLE_TAG).count(), 1);
+        assert!(library.units[0].total_personnel() > 300);
+        assert_eq!(export.formation_levels.len(), 4);
+        assert!(export.branches.iter().all(|b| b.category_id.is_some()));
+        // The sample is a well-formed example: every unit fits its level's range
+        assert_eq!(lint_units(&library.units, &export.formation_levels), Vec::new());
+    }
+
+    #[test]
+    fn test_sample_imports_twice_with_distinct_names() {
+        let db = Database::open_in_memory().unwrap();
+        let service = LibraryService::new(db.conn());
+        let names = || -> Vec<String> {
+            service.list_libraries().unwrap().into_iter().map(|l| l.name).collect()
+        };
+        let first = service.import_full(sample_library(&names()).unwrap()).unwrap();
+        let second = service.import_full(sample_library(&names()).unwrap()).unwrap();
+        assert_ne!(first.name, second.name);
+        assert_eq!(second.name, format!("{} (2)", first.name));
+
+        let exported = service.export_full(second.id.unwrap()).unwrap();
+        assert_eq!(exported.library.units[0].children.len(), 9);
+        assert_eq!(exported.branches.len(), 6);
+        assert!(exported.library.tags.contains(&SAMPLE_TAG.to_string()));
+    }
+}
@@ -0,0 +1,532 @@
+//! Staged comparison of two library snapshots.
+//!
+//! The diff runs in passes of increasing cost and stops after the requested
+//! [`DiffGranularity`]: metadata (library fields only; the unit trees are
+//! skipped by the parser), structure (units added, removed, moved, renamed)
+//! and full (personnel per position and equipment quantities per unit, as
+//! [`diff_libraries`] reports them). Every pass only appends to the output of
+//! the previous one.
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use crate::models::{Equipment, Library, Personnel, Snapshot, TreeWalk, Unit};
+use super::diff::{diff_libraries, UnitChange};
+
+/// How deep a snapshot comparison goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiffGranularity {
+    /// Name, country, era, author, tags and version
+    Metadata,
+    /// Plus units added, removed, moved or renamed
+    Structure,
+    /// Plus personnel and equipment changes per unit
+    Full,
+}
+
+impl DiffGranularity {
+    pub const ALL: [DiffGranularity; 3] = [Self::Metadata, Self::Structure, Self::Full];
+
+    /// English label for the compare dialog
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Metadata => "Metadata only",
+            Self::Structure => "Structure",
+            Self::Full => "Full",
+        }
+    }
+
+    /// From a dialog dropdown index; out-of-range values fall back to structure
+    pub fn from_index(index: i32) -> Self {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| Self::ALL.get(i).copied())
+            .unwrap_or(Self::Structure)
+    }
+}
+
+/// Result of a staged comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedDiff {
+    /// Human-readable change lines, metadata first
+    pub changes: Vec<String>,
+    /// Last pass that ran (lower than requested if the data was identical or unreadable)
+    pub completed: Option<DiffGranularity>,
+    pub elapsed: Duration,
+}
+
+impl StagedDiff {
+    /// Change lines joined for display, or a note when nothing changed
+    pub fn summary(&self) -> String {
+        if self.changes.is_empty() {
+            "No changes.".to_string()
+        } else {
+            self.changes.join("\n")
+        }
+    }
+}
+
+/// Library fields compared by the metadata pass; `units` is skipped without building trees
+#[derive(Deserialize)]
+struct MetadataView {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    country: String,
+    #[serde(default)]
+    era: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    version: i64,
+}
+
+/// Unit tree without personnel and equipment (structure pass)
+#[derive(Deserialize)]
+struct UnitShape {
+    #[serde(default)]
+    id: Option<i64>,
+    name: String,
+    #[serde(default)]
+    unit_type: String,
+    #[serde(default)]
+    children: Vec<UnitShape>,
+}
+
+#[derive(Deserialize)]
+struct PersonnelView {
+    #[serde(default)]
+    position: String,
+    #[serde(default)]
+    rank: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EquipmentView {
+    name: String,
+    #[serde(default)]
+    quantity: usize,
+}
+
+/// Complete unit tree (full pass)
+#[derive(Deserialize)]
+struct UnitDetail {
+    #[serde(default)]
+    id: Option<i64>,
+    name: String,
+    #[serde(default)]
+    unit_type: String,
+    #[serde(default)]
+    children: Vec<UnitDetail>,
+    #[serde(default)]
+    personnel: Vec<PersonnelView>,
+    #[serde(default)]
+    equipment: Vec<EquipmentView>,
+}
+
+impl UnitDetail {
+    /// The unit and its subunits as library units, for [`diff_libraries`]
+    fn into_unit(self) -> Unit {
+        let mut unit = Unit::new(self.name, self.unit_type);
+        unit.id = self.id;
+        unit.personnel = self
+            .personnel
+            .into_iter()
+            .map(|p| Personnel { position: p.position, rank: p.rank })
+            .collect();
+        unit.equipment = self.equipment.into_iter().map(|e| Equipment::new(e.name, e.quantity)).collect();
+        unit.children = self.children.into_iter().map(UnitDetail::into_unit).collect();
+        unit
+    }
+}
+
+#[derive(Deserialize)]
+struct UnitsView<U> {
+    #[serde(default = "Vec::new")]
+    units: Vec<U>,
+}
+
+/// Common view of both unit tree shapes for the structural pass
+trait TreeUnit: Sized {
+    fn id(&self) -> Option<i64>;
+    fn name(&self) -> &str;
+    fn unit_type(&self) -> &str;
+    fn children(&self) -> &[Self];
+}
+
+impl TreeUnit for UnitShape {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn unit_type(&self) -> &str {
+        &self.unit_type
+    }
+    fn children(&self) -> &[Self] {
+        &self.children
+    }
+}
+
+impl TreeUnit for UnitDetail {
+    fn id(&self) -> Option<i64> {
+        self.id
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn unit_type(&self) -> &str {
+        &self.unit_type
+    }
+    fn children(&self) -> &[Self] {
+        &self.children
+    }
+}
+
+/// Units are matched by id when they have one, otherwise by their name path
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum UnitKey {
+    Id(i64),
+    Path(String),
+}
+
+struct FlatUnit<'a, U> {
+    unit: &'a U,
+    path: String,
+    parent_path: String,
+}
+
+/// Every unit by key. Subunits go in before their parent, so a parent wins
+/// over a subunit with the same key.
+fn flatten<'a, U: TreeUnit>(units: &'a [U], out: &mut BTreeMap<UnitKey, FlatUnit<'a, U>>) {
+    let mut preorder: Vec<(usize, FlatUnit<'a, U>)> = Vec::new();
+    for visit in TreeWalk::new(units, U::children) {
+        let parent_path = visit.parent.map(|p| preorder[p].1.path.clone()).unwrap_or_default();
+        let path = if parent_path.is_empty() {
+            visit.unit.name().to_string()
+        } else {
+            format!("{} / {}", parent_path, visit.unit.name())
+        };
+        preorder.push((visit.depth, FlatUnit { unit: visit.unit, path, parent_path }));
+    }
+
+    let mut insert = |flat: FlatUnit<'a, U>| {
+        let key = match flat.unit.id() {
+            Some(id) => UnitKey::Id(id),
+            None => UnitKey::Path(flat.path.clone()),
+        };
+        out.insert(key, flat);
+    };
+    // A unit is done once the walk comes back up to its depth
+    let mut open: Vec<(usize, FlatUnit<'a, U>)> = Vec::new();
+    for (depth, flat) in preorder {
+        while open.last().is_some_and(|(d, _)| *d >= depth) {
+            if let Some((_, done)) = open.pop() {
+                insert(done);
+            }
+        }
+        open.push((depth, flat));
+    }
+    while let Some((_, done)) = open.pop() {
+        insert(done);
+    }
+}
+
+/// Compare two snapshots up to the given granularity
+pub fn diff_snapshots_staged(old: &Snapshot, new: &Snapshot, granularity: DiffGranularity) -> StagedDiff {
+    let start = Instant::now();
+    let mut changes = Vec::new();
+    let completed = run_passes(&old.data, &new.data, granularity, &mut changes);
+    StagedDiff {
+        changes,
+        completed,
+        elapsed: start.elapsed(),
+    }
+}
+
+fn run_passes(old: &str, new: &str, granularity: DiffGranularity, changes: &mut Vec<String>) -> Option<DiffGranularity> {
+    if old == new {
+        return Some(granularity);
+    }
+    if let Err(e) = diff_metadata(old, new, changes) {
+        changes.push(format!("Could not read snapshot data: {}", e));
+        return None;
+    }
+    if granularity == DiffGranularity::Metadata {
+        return Some(DiffGranularity::Metadata);
+    }
+    if granularity == DiffGranularity::Structure {
+        let result = parse_units::<UnitShape>(old, new).map(|(o, n)| diff_structure(&o, &n, changes));
+        return match result {
+            Ok(()) => Some(DiffGranularity::Structure),
+            Err(e) => {
+                changes.push(format!("Could not read units: {}", e));
+                Some(DiffGranularity::Metadata)
+            }
+        };
+    }
+    match parse_units::<UnitDetail>(old, new) {
+        Ok((o, n)) => {
+            diff_structure(&o, &n, changes);
+            diff_details(o, n, changes);
+            Some(DiffGranularity::Full)
+        }
+        Err(e) => {
+            changes.push(format!("Could not read units: {}", e));
+            Some(DiffGranularity::Metadata)
+        }
+    }
+}
+
+/// Only tells whether snapshot data is a full-state snapshot
+#[derive(Deserialize)]
+struct LayoutProbe {
+    format_version: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct FullStateView<T> {
+    library: T,
+}
+
+/// Read the library of snapshot data as `T`, nested under `library` in
+/// full-state snapshots
+fn read_library<T: DeserializeOwned>(data: &str) -> serde_json::Result<T> {
+    let probe: LayoutProbe = serde_json::from_str(data)?;
+    if probe.format_version.is_some() {
+        Ok(serde_json::from_str::<FullStateView<T>>(data)?.library)
+    } else {
+        serde_json::from_str(data)
+    }
+}
+
+fn diff_metadata(old: &str, new: &str, changes: &mut Vec<String>) -> serde_json::Result<()> {
+    let old: MetadataView = read_library(old)?;
+    let new: MetadataView = read_library(new)?;
+    let fields = [
+        ("name", &old.name, &new.name),
+        ("country", &old.country, &new.country),
+        ("era", &old.era, &new.era),
+        ("author", &old.author, &new.author),
+    ];
+    for (field, a, b) in fields {
+        if a != b {
+            changes.push(format!("Changed: {} ({:?} -> {:?})", field, a, b));
+        }
+    }
+    if old.version != new.version {
+        changes.push(format!("Changed: version ({} -> {})", old.version, new.version));
+    }
+    for tag in old.tags.iter().filter(|t| !new.tags.contains(t)) {
+        changes.push(format!("Tag removed: {}", tag));
+    }
+    for tag in new.tags.iter().filter(|t| !old.tags.contains(t)) {
+        changes.push(format!("Tag added: {}", tag));
+    }
+    Ok(())
+}
+
+fn parse_units<U: DeserializeOwned>(old: &str, new: &str) -> serde_json::Result<(Vec<U>, Vec<U>)> {
+    let old: UnitsView<U> = read_library(old)?;
+    let new: UnitsView<U> = read_library(new)?;
+    Ok((old.units, new.units))
+}
+
+fn diff_structure<U: TreeUnit>(old: &[U], new: &[U], changes: &mut Vec<String>) {
+    let mut old_flat = BTreeMap::new();
+    let mut new_flat = BTreeMap::new();
+    flatten(old, &mut old_flat);
+    flatten(new, &mut new_flat);
+
+    for (key, o) in &old_flat {
+        let Some(n) = new_flat.get(key) else {
+            changes.push(format!("Unit removed: {}", o.path));
+            continue;
+        };
+        if o.unit.name() != n.unit.name() {
+            changes.push(format!("Unit renamed: {} -> {}", o.path, n.unit.name()));
+        }
+        if o.parent_path != n.parent_path {
+            let parent = |p: &str| if p.is_empty() { "(top level)".to_string() } else { p.to_string() };
+            changes.push(format!(
+                "Unit moved: {} ({} -> {})",
+                n.unit.name(),
+                parent(&o.parent_path),
+                parent(&n.parent_path)
+            ));
+        }
+        if o.unit.unit_type() != n.unit.unit_type() {
+            changes.push(format!(
+                "Unit type changed: {} ({} -> {})",
+                n.path,
+                o.unit.unit_type(),
+                n.unit.unit_type()
+            ));
+        }
+    }
+    for (key, n) in &new_flat {
+        if !old_flat.contains_key(key) {
+            changes.push(format!("Unit added: {}", n.path));
+        }
+    }
+}
+
+/// Personnel and equipment changes of the units on both sides; the
+/// structural changes [`diff_libraries`] also finds are already listed
+fn diff_details(old: Vec<UnitDetail>, new: Vec<UnitDetail>, changes: &mut Vec<String>) {
+    let library = |units: Vec<UnitDetail>| Library {
+        units: units.into_iter().map(UnitDetail::into_unit).collect(),
+        ..Library::new(String::new(), String::new(), String::new(), String::new())
+    };
+    let diff = diff_libraries(&library(old), &library(new));
+    changes.extend(
+        diff.units
+            .iter()
+            .filter(|c| matches!(c, UnitChange::Personnel { .. } | UnitChange::Equipment { .. }))
+            .map(UnitChange::to_string),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(version: i64, data: serde_json::Value) -> Snapshot {
+        Snapshot::new(1, version, data.to_string())
+    }
+
+    #[test]
+    fn test_granularity_from_index() {
+        assert_eq!(DiffGranularity::from_index(0), DiffGranularity::Metadata);
+        assert_eq!(DiffGranularity::from_index(2), DiffGranularity::Full);
+        assert_eq!(DiffGranularity::from_index(-1), DiffGranularity::Structure);
+        assert_eq!(DiffGranularity::from_index(7), DiffGranularity::Structure);
+    }
+
+    #[test]
+    fn test_identical_snapshots_stop_immediately() {
+        let data = serde_json::json!({"name": "A", "units": []});
+        let diff = diff_snapshots_staged(&snapshot(1, data.clone()), &snapshot(2, data), DiffGranularity::Full);
+        assert!(diff.changes.is_empty());
+        assert_eq!(diff.summary(), "No changes.");
+        assert_eq!(diff.completed, Some(DiffGranularity::Full));
+    }
+
+    #[test]
+    fn test_metadata_tags_compared_as_sets() {
+        let old = snapshot(1, serde_json::json!({"name": "A", "tags": ["x", "y"]}));
+        let new = snapshot(2, serde_json::json!({"name": "A", "tags": ["y", "x", "z"]}));
+        let diff = diff_snapshots_staged(&old, &new, DiffGranularity::Metadata);
+        assert_eq!(diff.changes, vec!["Tag added: z".to_string()]);
+    }
+
+    fn library(name: &str, units: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({"name": name, "country": "US", "era": "2003", "author": "A",
+                           "version": 1, "tags": [], "units": units})
+    }
+
+    #[test]
+    fn test_each_stage_extends_the_previous() {
+        let old = snapshot(1, library("A", serde_json::json!([
+            {"id": 1, "name": "1st Bn", "unit_type": "Battalion", "personnel": [], "equipment": [], "children": [
+                {"id": 2, "name": "A Co", "unit_type": "Company", "children": [],
+                 "personnel": [{"position": "CO", "rank": "CPT"}],
+                 "equipment": [{"name": "M4", "quantity": 10}]},
+                {"id": 3, "name": "B Co", "unit_type": "Company", "children": [], "personnel": [], "equipment": []}
+            ]},
+            {"id": 4, "name": "2nd Bn", "unit_type": "Battalion", "children": [], "personnel": [], "equipment": []}
+        ])));
+        let new = snapshot(2, library("B", serde_json::json!([
+            {"id": 1, "name": "1st Bn", "unit_type": "Battalion", "personnel": [], "equipment": [], "children": [
+                {"id": 2, "name": "A Co", "unit_type": "Company", "children": [], "personnel": [],
+                 "equipment": [{"name": "M4", "quantity": 12}]}
+            ]},
+            {"id": 4, "name": "2nd Bn", "unit_type": "Battalion", "personnel": [], "equipment": [], "children": [
+                {"id": 3, "name": "B Co", "unit_type": "Company", "children": [], "personnel": [], "equipment": []}
+            ]},
+            {"id": 5, "name": "3rd Bn", "unit_type": "Battalion", "children": [], "personnel": [], "equipment": []}
+        ])));
+
+        let stages: Vec<StagedDiff> = DiffGranularity::ALL
+            .iter()
+            .map(|&g| diff_snapshots_staged(&old, &new, g))
+            .collect();
+        for pair in stages.windows(2) {
+            assert!(pair[0].changes.len() < pair[1].changes.len());
+            assert!(pair[1].changes.starts_with(&pair[0].changes));
+        }
+        assert_eq!(stages[0].changes, vec!["Changed: name (\"A\" -> \"B\")".to_string()]);
+        // Moved, not removed and added
+        assert!(stages[1].changes.contains(&"Unit moved: B Co (1st Bn -> 2nd Bn)".to_string()));
+        assert!(stages[1].changes.contains(&"Unit added: 3rd Bn".to_string()));
+        assert!(!stages[1].changes.iter().any(|c| c.starts_with("Unit removed")));
+        assert!(stages[2].changes.contains(&"Personnel: 1st Bn / A Co — CO (1 -> 0)".to_string()));
+        assert!(stages[2].changes.contains(&"Equipment: 1st Bn / A Co — M4 (10 -> 12)".to_string()));
+        assert_eq!(stages[2].completed, Some(DiffGranularity::Full));
+    }
+
+    #[test]
+    fn test_full_state_snapshots_compare_their_library() {
+        let units = serde_json::json!([
+            {"id": 1, "name": "1st Bn", "unit_type": "Battalion", "children": [], "personnel": [], "equipment": []}
+        ]);
+        let old = snapshot(1, library("A", units.clone()));
+        let new = snapshot(2, serde_json::json!({"format_version": 1, "library": library("B", units), "branches": []}));
+        let diff = diff_snapshots_staged(&old, &new, DiffGranularity::Full);
+        assert_eq!(diff.changes, vec!["Changed: name (\"A\" -> \"B\")".to_string()]);
+        assert_eq!(diff.completed, Some(DiffGranularity::Full));
+    }
+
+    #[test]
+    fn test_metadata_pass_does_not_parse_units() {
+        // Units that are not valid unit objects: only the later passes notice
+        let old = snapshot(1, library("A", serde_json::json!([{"bogus": true}])));
+        let new = snapshot(2, library("A", serde_json::json!([42, "x"])));
+        let meta = diff_snapshots_staged(&old, &new, DiffGranularity::Metadata);
+        assert!(meta.changes.is_empty());
+        assert_eq!(meta.completed, Some(DiffGranularity::Metadata));
+
+        let structure = diff_snapshots_staged(&old, &new, DiffGranularity::Structure);
+        assert_eq!(structure.completed, Some(DiffGranularity::Metadata));
+        assert!(structure.changes[0].starts_with("Could not read units"));
+    }
+
+    #[test]
+    fn test_units_without_ids_match_by_path() {
+        let old = snapshot(1, library("A", serde_json::json!([{"name": "HQ", "children": []}])));
+        let new = snapshot(2, library("A", serde_json::json!([{"name": "HQ", "unit_type": "Staff", "children": []}])));
+        let diff = diff_snapshots_staged(&old, &new, DiffGranularity::Structure);
+        assert_eq!(diff.changes, vec!["Unit type changed: HQ ( -> Staff)".to_string()]);
+    }
+
+    #[test]
+    fn test_deep_unit_chain() {
+        // As deep as snapshot JSON can nest: each level is an object in a list
+        let chain = |leaf_type: &str| {
+            let mut units = serde_json::json!([{"name": "U59", "unit_type": leaf_type, "children": []}]);
+            for i in (0..59).rev() {
+                units = serde_json::json!([{"name": format!("U{}", i), "children": units}]);
+            }
+            units
+        };
+        let old = snapshot(1, library("A", chain("")));
+        let new = snapshot(2, library("A", chain("Staff")));
+        let diff = diff_snapshots_staged(&old, &new, DiffGranularity::Full);
+        let path: Vec<String> = (0..60).map(|i| format!("U{}", i)).collect();
+        assert_eq!(diff.changes, vec![format!("Unit type changed: {} ( -> Staff)", path.join(" / "))]);
+        assert_eq!(diff.completed, Some(DiffGranularity::Full));
+    }
+
+    #[test]
+    fn test_unreadable_data_reports_error() {
+        let old = Snapshot::new(1, 1, "not json".to_string());
+        let new = Snapshot::new(1, 2, "{}".to_string());
+        let diff = diff_snapshots_staged(&old, &new, DiffGranularity::Structure);
+        assert_eq!(diff.completed, None);
+        assert!(diff.changes[0].starts_with("Could not read snapshot data"));
+    }
+}
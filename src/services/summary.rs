@@ -0,0 +1,191 @@
+//! Summary table of a formation: personnel by rank and by position and
+//! equipment by name over its whole subtree, with subtotals per direct
+//! subordinate unit
+//!
+//! Names are merged across units after trimming and ignoring case, so
+//! "Rifle" in one platoon and "rifle " in another make one row, shown as
+//! first spelled.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use crate::db::repositories::UnitRepo;
+use crate::models::{walk_units, Unit};
+
+/// One row of a summary: a rank, position or equipment name and its total
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryRow {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Totals of a direct subordinate unit, its own subtree included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildSubtotal {
+    pub unit_id: Option<i64>,
+    pub name: String,
+    pub personnel: usize,
+    pub equipment: usize,
+}
+
+/// Roll-up of a formation and everything under it. Rows are in the order
+/// their names first occur, depth-first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnitSummary {
+    pub unit_name: String,
+    pub personnel: usize,
+    /// Personnel without a rank are counted under an empty name
+    pub by_rank: Vec<SummaryRow>,
+    pub by_position: Vec<SummaryRow>,
+    pub equipment: Vec<SummaryRow>,
+    pub children: Vec<ChildSubtotal>,
+}
+
+impl UnitSummary {
+    /// Items of all equipment rows
+    pub fn equipment_total(&self) -> usize {
+        self.equipment.iter().map(|r| r.count).sum()
+    }
+}
+
+/// Summary of the unit `unit_id` as stored, with its subtree
+pub fn compute_summary(unit_id: i64, conn: &Connection) -> Result<UnitSummary> {
+    let unit = UnitRepo::new(conn)
+        .get_by_id(unit_id)?
+        .with_context(|| format!("Unit {} not found", unit_id))?;
+    Ok(summarize(&unit))
+}
+
+/// Summary of `unit` and its subtree
+pub fn summarize(unit: &Unit) -> UnitSummary {
+    let (mut by_rank, mut by_position, mut equipment) = (Tally::default(), Tally::default(), Tally::default());
+    for visit in walk_units(std::slice::from_ref(unit)) {
+        for p in &visit.unit.personnel {
+            by_rank.add(p.rank.as_deref().unwrap_or(""), 1);
+            by_position.add(&p.position, 1);
+        }
+        for e in &visit.unit.equipment {
+            equipment.add(&e.name, e.quantity);
+        }
+    }
+    let children = unit
+        .children
+        .iter()
+        .map(|child| ChildSubtotal {
+            unit_id: child.id,
+            name: child.name.clone(),
+            personnel: child.total_personnel(),
+            equipment: child.total_equipment().values().sum(),
+        })
+        .collect();
+    UnitSummary {
+        unit_name: unit.name.clone(),
+        personnel: unit.total_personnel(),
+        by_rank: by_rank.rows,
+        by_position: by_position.rows,
+        equipment: equipment.rows,
+        children,
+    }
+}
+
+/// Key two names are merged under
+fn merge_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Counts by name, merged per [`merge_key`], in first-seen order
+#[derive(Default)]
+struct Tally {
+    rows: Vec<SummaryRow>,
+    index: HashMap<String, usize>,
+}
+
+impl Tally {
+    fn add(&mut self, name: &str, count: usize) {
+        let key = merge_key(name);
+        match self.index.get(&key) {
+            Some(&i) => self.rows[i].count += count,
+            None => {
+                self.index.insert(key, self.rows.len());
+                self.rows.push(SummaryRow { name: name.trim().to_string(), count });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{repositories::LibraryRepo, Database};
+    use crate::models::{Equipment, Library, Personnel};
+
+    fn row(name: &str, count: usize) -> SummaryRow {
+        SummaryRow { name: name.to_string(), count }
+    }
+
+    /// Company with two platoons whose equipment and ranks overlap
+    fn company() -> Unit {
+        let mut company = Unit::new("A Company".to_string(), "company".to_string());
+        company.personnel.push(Personnel::with_rank("Company Commander".to_string(), "CPT".to_string()));
+        company.equipment.push(Equipment::new("Radio".to_string(), 1));
+
+        let mut first = Unit::new("1st Platoon".to_string(), "platoon".to_string());
+        first.personnel.push(Personnel::with_rank("Platoon Leader".to_string(), "LT".to_string()));
+        first.personnel.push(Personnel::with_rank("Rifleman".to_string(), "PVT".to_string()));
+        first.personnel.push(Personnel::new("Rifleman".to_string()));
+        first.equipment.push(Equipment::new("Rifle".to_string(), 20));
+        first.equipment.push(Equipment::new("Radio".to_string(), 2));
+
+        let mut second = Unit::new("2nd Platoon".to_string(), "platoon".to_string());
+        second.personnel.push(Personnel::with_rank("Platoon Leader".to_string(), " lt ".to_string()));
+        second.personnel.push(Personnel::with_rank("rifleman".to_string(), "Pvt".to_string()));
+        second.equipment.push(Equipment::new("rifle ".to_string(), 18));
+        second.equipment.push(Equipment::new("Mortar".to_string(), 1));
+
+        company.children.push(first);
+        company.children.push(second);
+        company
+    }
+
+    #[test]
+    fn test_summary_merges_names_across_units() {
+        let summary = summarize(&company());
+        assert_eq!(summary.unit_name, "A Company");
+        assert_eq!(summary.personnel, 6);
+        assert_eq!(summary.by_rank, vec![row("CPT", 1), row("LT", 2), row("PVT", 2), row("", 1)]);
+        assert_eq!(
+            summary.by_position,
+            vec![row("Company Commander", 1), row("Platoon Leader", 2), row("Rifleman", 3)]
+        );
+        assert_eq!(summary.equipment, vec![row("Radio", 3), row("Rifle", 38), row("Mortar", 1)]);
+        assert_eq!(summary.equipment_total(), 42);
+    }
+
+    #[test]
+    fn test_summary_subtotals_per_child() {
+        let summary = summarize(&company());
+        let subtotals: Vec<(&str, usize, usize)> =
+            summary.children.iter().map(|c| (c.name.as_str(), c.personnel, c.equipment)).collect();
+        assert_eq!(subtotals, vec![("1st Platoon", 3, 22), ("2nd Platoon", 2, 19)]);
+    }
+
+    #[test]
+    fn test_summary_of_a_leaf_unit() {
+        let summary = summarize(&Unit::new("HQ".to_string(), "section".to_string()));
+        assert_eq!(summary, UnitSummary { unit_name: "HQ".to_string(), ..UnitSummary::default() });
+    }
+
+    #[test]
+    fn test_compute_summary_from_database() {
+        let db = Database::open_in_memory().unwrap();
+        let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        let mut unit = company();
+        UnitRepo::new(db.conn()).create_tree(library.id.unwrap(), &mut unit).unwrap();
+
+        let summary = compute_summary(unit.id.unwrap(), db.conn()).unwrap();
+        assert_eq!(summary, summarize(&unit));
+        assert_eq!(summary.children[0].unit_id, unit.children[0].id);
+        assert!(compute_summary(-1, db.conn()).is_err());
+    }
+}
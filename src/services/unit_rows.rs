@@ -0,0 +1,139 @@
+//! Saving a unit's personnel and equipment rows by their differences from
+//! the stored rows, so editing one quantity writes one row instead of all of
+//! them.
+
+/// One write needed to turn the stored rows into the edited ones
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowOp<T> {
+    Insert(T),
+    /// New value for the stored row with this id
+    Update(i64, T),
+    Delete(i64),
+}
+
+/// How many rows a save inserted, updated and deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RowChanges {
+    pub inserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl RowChanges {
+    /// Count the operations of `ops`
+    pub fn of<T>(ops: &[RowOp<T>]) -> Self {
+        let mut changes = Self::default();
+        for op in ops {
+            match op {
+                RowOp::Insert(_) => changes.inserted += 1,
+                RowOp::Update(..) => changes.updated += 1,
+                RowOp::Delete(_) => changes.deleted += 1,
+            }
+        }
+        changes
+    }
+
+    pub fn total(&self) -> usize {
+        self.inserted + self.updated + self.deleted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+}
+
+impl std::ops::Add for RowChanges {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            inserted: self.inserted + other.inserted,
+            updated: self.updated + other.updated,
+            deleted: self.deleted + other.deleted,
+        }
+    }
+}
+
+/// Fewest writes turning `stored` (id, value) into `edited` (stored id or
+/// `None` for a new row, value). Rows are matched by id: edited rows with an
+/// id not among the stored ones are inserted, stored rows missing from
+/// `edited` are deleted, and matched rows are updated only when their value
+/// changed. Deletes come first, then updates and inserts in edited order.
+pub fn diff_rows<T: Clone + PartialEq>(stored: &[(i64, T)], edited: &[(Option<i64>, T)]) -> Vec<RowOp<T>> {
+    let kept: std::collections::HashSet<i64> = edited.iter().filter_map(|(id, _)| *id).collect();
+    let mut ops: Vec<RowOp<T>> = stored
+        .iter()
+        .filter(|(id, _)| !kept.contains(id))
+        .map(|(id, _)| RowOp::Delete(*id))
+        .collect();
+    let mut matched = std::collections::HashSet::new();
+    for (id, value) in edited {
+        let old = id.and_then(|id| stored.iter().find(|(stored_id, _)| *stored_id == id));
+        match old {
+            // A row listed twice is stored once; the copy becomes a new row
+            Some((id, old)) if matched.insert(*id) => {
+                if old != value {
+                    ops.push(RowOp::Update(*id, value.clone()));
+                }
+            }
+            _ => ops.push(RowOp::Insert(value.clone())),
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Equipment;
+
+    fn stored() -> Vec<(i64, Equipment)> {
+        vec![
+            (1, Equipment::new("M4 Carbine".to_string(), 120)),
+            (2, Equipment::new("M249 SAW".to_string(), 9)),
+            (3, Equipment::new("HMMWV".to_string(), 4)),
+        ]
+    }
+
+    fn unchanged() -> Vec<(Option<i64>, Equipment)> {
+        stored().into_iter().map(|(id, e)| (Some(id), e)).collect()
+    }
+
+    #[test]
+    fn test_unchanged_rows_need_no_writes() {
+        assert_eq!(diff_rows(&stored(), &unchanged()), Vec::new());
+        assert!(RowChanges::of(&diff_rows(&stored(), &unchanged())).is_empty());
+    }
+
+    #[test]
+    fn test_one_quantity_is_one_update() {
+        let mut edited = unchanged();
+        edited[1].1.quantity = 6;
+        let ops = diff_rows(&stored(), &edited);
+        assert_eq!(ops, [RowOp::Update(2, Equipment::new("M249 SAW".to_string(), 6))]);
+        assert_eq!(RowChanges::of(&ops), RowChanges { inserted: 0, updated: 1, deleted: 0 });
+    }
+
+    #[test]
+    fn test_added_removed_and_duplicated_rows() {
+        let mut edited = unchanged();
+        edited.remove(0);
+        edited.push((None, Equipment::new("Javelin".to_string(), 2)));
+        // A copied row keeps the id it was copied from
+        edited.push(edited[0].clone());
+        let ops = diff_rows(&stored(), &edited);
+        assert_eq!(
+            ops,
+            [
+                RowOp::Delete(1),
+                RowOp::Insert(Equipment::new("Javelin".to_string(), 2)),
+                RowOp::Insert(Equipment::new("M249 SAW".to_string(), 9)),
+            ]
+        );
+        // Ids from elsewhere are new rows here
+        assert_eq!(
+            diff_rows(&[], &[(Some(7), Equipment::new("HMMWV".to_string(), 1))]),
+            [RowOp::Insert(Equipment::new("HMMWV".to_string(), 1))]
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Unit service: structural edits to the formation tree
+
+use anyhow::{bail, Result};
+use rusqlite::Connection;
+use crate::db::repositories::UnitRepo;
+use crate::models::{find_toe_collision, normalize_toe_number, Equipment, Personnel, TreeLimitIssue, TreeLimits, TreeShape, Unit};
+use super::duplicates::merge_equipment;
+use super::unit_rows::{diff_rows, RowChanges, RowOp};
+
+/// Service for adding, deleting and reordering units
+pub struct UnitService<'a> {
+    conn: &'a Connection,
+    unit_repo: UnitRepo<'a>,
+    tree_limits: TreeLimits,
+    /// Refuse a TOE number another unit of the same library already has
+    unique_toe_numbers: bool,
+}
+
+impl<'a> UnitService<'a> {
+    /// Create new unit service
+    pub fn new(conn: &'a Connection) -> Self {
+        Self {
+            conn,
+            unit_repo: UnitRepo::new(conn),
+            tree_limits: TreeLimits::default(),
+            unique_toe_numbers: false,
+        }
+    }
+
+    /// Refuse new units past `limits` instead of the default ones
+    pub fn with_tree_limits(mut self, limits: TreeLimits) -> Self {
+        self.tree_limits = limits;
+        self
+    }
+
+    /// Refuse TOE numbers already used in the same library
+    pub fn with_unique_toe_numbers(mut self, unique: bool) -> Self {
+        self.unique_toe_numbers = unique;
+        self
+    }
+
+    /// Soft limits a new unit under `parent_id` (top level when `None`) would
+    /// go past. Fails when it would go past a hard limit.
+    pub fn check_new_unit(&self, library_id: i64, parent_id: Option<i64>) -> Result<Vec<TreeLimitIssue>> {
+        let levels_above = match parent_id {
+            Some(id) => self.unit_repo.count_levels(id)?,
+            None => 0,
+        };
+        let siblings = self.unit_repo.list_sibling_ids(library_id, parent_id)?.len() + 1;
+        Ok(self.tree_limits.check(TreeShape::SINGLE.placed_under(levels_above, siblings))?)
+    }
+
+    /// Add a top-level unit to a library, after the existing ones
+    pub fn add_root(&self, library_id: i64, name: &str, unit_type: &str) -> Result<Unit> {
+        self.check_new_unit(library_id, None)?;
+        let mut unit = Unit::new(name.to_string(), unit_type.to_string());
+        self.unit_repo.create(library_id, &mut unit)?;
+        Ok(unit)
+    }
+
+    /// Add a unit as the last child of `parent_id`
+    pub fn add_child(&self, parent_id: i64, name: &str, unit_type: &str) -> Result<Unit> {
+        let Some((library_id, _)) = self.unit_repo.get_placement(parent_id)? else {
+            bail!("Unit {} not found", parent_id);
+        };
+        self.check_new_unit(library_id, Some(parent_id))?;
+        let mut unit = Unit::new(name.to_string(), unit_type.to_string());
+        unit.parent_id = Some(parent_id);
+        self.unit_repo.create(library_id, &mut unit)?;
+        Ok(unit)
+    }
+
+    /// Save a unit's name, type, effective period and TOE number. The TOE
+    /// number is stored normalized; with unique TOE numbers on, one that
+    /// another unit of the library already has fails with a
+    /// [`ToeNumberCollision`](crate::models::ToeNumberCollision).
+    pub fn update_properties(&self, unit: &Unit) -> Result<()> {
+        let id = unit.id.ok_or_else(|| anyhow::anyhow!("Unit has no id"))?;
+        let Some((library_id, _)) = self.unit_repo.get_placement(id)? else {
+            bail!("Unit {} not found", id);
+        };
+        let toe_number = unit.toe_number.as_deref().and_then(normalize_toe_number);
+        if let (true, Some(number)) = (self.unique_toe_numbers, &toe_number) {
+            let existing = self.unit_repo.list_toe_numbers(library_id)?;
+            if let Some(collision) = find_toe_collision(&existing, Some(id), number) {
+                return Err(collision.into());
+            }
+        }
+        self.unit_repo.update_properties(&Unit { toe_number, ..unit.clone() })
+    }
+
+    /// Save a unit's edited personnel and equipment rows (stored row id, or
+    /// `None` for a new row) in one transaction, writing only the rows that
+    /// differ from the stored ones. Returns what was written; nothing is
+    /// written, not even the unit's modification time, for an unchanged table.
+    pub fn save_unit_rows(
+        &self,
+        unit_id: i64,
+        personnel: &[(Option<i64>, Personnel)],
+        equipment: &[(Option<i64>, Equipment)],
+    ) -> Result<RowChanges> {
+        if self.unit_repo.get_placement(unit_id)?.is_none() {
+            bail!("Unit {} not found", unit_id);
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let personnel_ops = diff_rows(&self.unit_repo.list_personnel_rows(unit_id)?, personnel);
+        let equipment_ops = diff_rows(&self.unit_repo.list_equipment_rows(unit_id)?, equipment);
+        for op in &personnel_ops {
+            match op {
+                RowOp::Insert(p) => self.unit_repo.create_personnel(unit_id, &mut p.clone())?,
+                RowOp::Update(id, p) => self.unit_repo.update_personnel(*id, p)?,
+                RowOp::Delete(id) => self.unit_repo.delete_personnel(*id)?,
+            }
+        }
+        for op in &equipment_ops {
+            match op {
+                RowOp::Insert(e) => self.unit_repo.create_equipment(unit_id, e)?,
+                RowOp::Update(id, e) => self.unit_repo.update_equipment(*id, e)?,
+                RowOp::Delete(id) => self.unit_repo.delete_equipment(*id)?,
+            }
+        }
+        let changes = RowChanges::of(&personnel_ops) + RowChanges::of(&equipment_ops);
+        if !changes.is_empty() {
+            self.unit_repo.touch(unit_id)?;
+        }
+        tx.commit()?;
+        log::info!(
+            "Saved rows of unit {}: {} inserted, {} updated, {} deleted",
+            unit_id,
+            changes.inserted,
+            changes.updated,
+            changes.deleted
+        );
+        Ok(changes)
+    }
+
+    /// Delete a unit with all of its subordinates. Returns how many units were removed.
+    pub fn delete_subtree(&self, unit_id: i64) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let expected = self.unit_repo.count_subtree(unit_id)?;
+        if expected == 0 {
+            bail!("Unit {} not found", unit_id);
+        }
+        let before = self.count_units()?;
+        self.unit_repo.delete(unit_id)?;
+        let removed = before - self.count_units()?;
+        if removed != expected {
+            // Foreign keys off or a broken parent link: leave the tree untouched
+            bail!(
+                "Deleting unit {} removed {} units, expected {}",
+                unit_id,
+                removed,
+                expected
+            );
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Merge unit `other_id` into `keep_id`: its personnel are added, its
+    /// equipment merged ([`merge_equipment`]), its subordinates moved after
+    /// those of `keep_id`, and the unit itself deleted. Both units must be in
+    /// the same library, and `keep_id` must not be below `other_id`.
+    pub fn merge_units(&self, keep_id: i64, other_id: i64) -> Result<()> {
+        let (Some((library_id, _)), Some((other_library_id, _))) =
+            (self.unit_repo.get_placement(keep_id)?, self.unit_repo.get_placement(other_id)?)
+        else {
+            bail!("Unit {} or {} not found", keep_id, other_id);
+        };
+        if library_id != other_library_id {
+            bail!("Units {} and {} are in different libraries", keep_id, other_id);
+        }
+        let mut ancestor = Some(keep_id);
+        while let Some(id) = ancestor {
+            if id == other_id {
+                bail!("Cannot merge unit {} into itself or a unit below it", other_id);
+            }
+            ancestor = self.unit_repo.get_placement(id)?.and_then(|(_, parent)| parent);
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        for (_, mut p) in self.unit_repo.list_personnel_rows(other_id)? {
+            self.unit_repo.create_personnel(keep_id, &mut p)?;
+        }
+        let stored = self.unit_repo.list_equipment_rows(keep_id)?;
+        let kept: Vec<Equipment> = stored.iter().map(|(_, e)| e.clone()).collect();
+        let added: Vec<Equipment> = self.unit_repo.list_equipment_rows(other_id)?.into_iter().map(|(_, e)| e).collect();
+        // Merged items keep the order of the kept ones, so the first rows are theirs
+        let edited: Vec<(Option<i64>, Equipment)> = merge_equipment(&kept, &added)
+            .into_iter()
+            .enumerate()
+            .map(|(i, e)| (stored.get(i).map(|(id, _)| *id), e))
+            .collect();
+        for op in diff_rows(&stored, &edited) {
+            match op {
+                RowOp::Insert(e) => self.unit_repo.create_equipment(keep_id, &e)?,
+                RowOp::Update(id, e) => self.unit_repo.update_equipment(id, &e)?,
+                RowOp::Delete(id) => self.unit_repo.delete_equipment(id)?,
+            }
+        }
+        let first = self.unit_repo.list_sibling_ids(library_id, Some(keep_id))?.len() as i64 + 1;
+        for (i, child) in self.unit_repo.list_sibling_ids(library_id, Some(other_id))?.into_iter().enumerate() {
+            self.unit_repo.set_parent(child, Some(keep_id), first + i as i64)?;
+        }
+        self.unit_repo.delete(other_id)?;
+        self.unit_repo.touch(keep_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Swap a unit with its previous sibling. Returns false if it is already first.
+    pub fn move_up(&self, unit_id: i64) -> Result<bool> {
+        self.move_by(unit_id, -1)
+    }
+
+    /// Swap a unit with its next sibling. Returns false if it is already last.
+    pub fn move_down(&self, unit_id: i64) -> Result<bool> {
+        self.move_by(unit_id, 1)
+    }
+
+    fn move_by(&self, unit_id: i64, offset: isize) -> Result<bool> {
+        let Some((library_id, parent_id)) = self.unit_repo.get_placement(unit_id)? else {
+            bail!("Unit {} not found", unit_id);
+        };
+        let mut siblings = self.unit_repo.list_sibling_ids(library_id, parent_id)?;
+        let Some(index) = siblings.iter().position(|&id| id == unit_id) else {
+            bail!("Unit {} not found among its siblings", unit_id);
+        };
+        let Some(target) = index.checked_add_signed(offset).filter(|&t| t < siblings.len()) else {
+            return Ok(false);
+        };
+        siblings.swap(index, target);
+        // Renumber the whole sibling group so duplicate sort orders cannot stick
+        let tx = self.conn.unchecked_transaction()?;
+        for (position, id) in siblings.iter().enumerate() {
+            self.unit_repo.set_sort_order(*id, position as i64 + 1)?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    fn count_units(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM units", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::db::repositories::LibraryRepo;
+    use crate::models::{Library, ToeNumberCollision};
+
+    fn setup(db: &Database) -> i64 {
+        let mut library = Library::new(
+            "Test".to_string(),
+            "US".to_string(),
+            "2003".to_string(),
+            "Author".to_string(),
+        );
+        LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+        library.id.unwrap()
+    }
+
+    fn child_names(db: &Database, parent_id: i64) -> Vec<String> {
+        UnitRepo::new(db.conn())
+            .get_by_id(parent_id)
+            .unwrap()
+            .unwrap()
+            .children
+            .into_iter()
+            .map(|c| c.name)
+            .collect()
+    }
+
+    /// Asserts the children of `parent_id` are numbered 1, 2, … with no gaps or repeats
+    fn assert_dense_sort_order(db: &Database, parent_id: i64) {
+        let mut stmt = db.conn().prepare("SELECT sort_order FROM units WHERE parent_id = ?1 ORDER BY sort_order").unwrap();
+        let orders: Vec<i64> = stmt.query_map([parent_id], |row| row.get(0)).unwrap().map(|o| o.unwrap()).collect();
+        assert_eq!(orders, (1..=orders.len() as i64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_save_unit_rows_writes_only_changes() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = setup(&db);
+        let service = UnitService::new(db.conn());
+        let unit_id = service.add_root(lib_id, "1st Platoon", "Platoon").unwrap().id.unwrap();
+        let repo = UnitRepo::new(db.conn());
+        let personnel = [(None, Personnel::with_rank("Platoon Leader".to_string(), "LT".to_string()))];
+        let equipment = [
+            (None, Equipment::new("M4 Carbine".to_string(), 40)),
+            (None, Equipment::new("M249 SAW".to_string(), 6)),
+        ];
+        let first = service.save_unit_rows(unit_id, &personnel, &equipment).unwrap();
+        assert_eq!(first, RowChanges { inserted: 3, updated: 0, deleted: 0 });
+
+        let stored_personnel: Vec<_> =
+            repo.list_personnel_rows(unit_id).unwrap().into_iter().map(|(id, p)| (Some(id), p)).collect();
+        let mut stored_equipment: Vec<_> =
+            repo.list_equipment_rows(unit_id).unwrap().into_iter().map(|(id, e)| (Some(id), e)).collect();
+        assert!(service.save_unit_rows(unit_id, &stored_personnel, &stored_equipment).unwrap().is_empty());
+
+        let saw_id = stored_equipment[1].0.unwrap();
+        stored_equipment[1].1.quantity = 9;
+        let changes = service.save_unit_rows(unit_id, &stored_personnel, &stored_equipment).unwrap();
+        assert_eq!(changes, RowChanges { inserted: 0, updated: 1, deleted: 0 });
+        // The row keeps its id
+        assert_eq!(repo.list_equipment_rows(unit_id).unwrap()[1], (saw_id, Equipment::new("M249 SAW".to_string(), 9)));
+
+        let changes = service.save_unit_rows(unit_id, &[], &stored_equipment[..1]).unwrap();
+        assert_eq!(changes, RowChanges { inserted: 0, updated: 0, deleted: 2 });
+        assert!(service.save_unit_rows(unit_id + 100, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn test_merge_units_combines_rows_and_children() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = setup(&db);
+        let service = UnitService::new(db.conn());
+        let repo = UnitRepo::new(db.conn());
+        let regiment = service.add_root(lib_id, "Regiment", "Regiment").unwrap().id.unwrap();
+        let keep = service.add_child(regiment, "1st Battalion", "Battalion").unwrap().id.unwrap();
+        let other = service.add_child(regiment, "1st Battalion ", "Battalion").unwrap().id.unwrap();
+        service.add_child(keep, "1st Company", "Company").unwrap();
+        service.add_child(other, "2nd Company", "Company").unwrap();
+        service.add_child(other, "3rd Company", "Company").unwrap();
+        service
+            .save_unit_rows(keep, &[(None, Personnel::new("Commander".to_string()))], &[(None, Equipment::new("BMP-2".to_string(), 3))])
+            .unwrap();
+        service
+            .save_unit_rows(
+                other,
+                &[(None, Personnel::new("Chief of Staff".to_string()))],
+                &[(None, Equipment::new("bmp-2".to_string(), 2)), (None, Equipment::new("PKM".to_string(), 4))],
+            )
+            .unwrap();
+
+        service.merge_units(keep, other).unwrap();
+
+        assert!(repo.get_by_id(other).unwrap().is_none());
+        let merged = repo.get_by_id(keep).unwrap().unwrap();
+        let positions: Vec<&str> = merged.personnel.iter().map(|p| p.position.as_str()).collect();
+        assert_eq!(positions, ["Commander", "Chief of Staff"]);
+        assert_eq!(merged.equipment, [Equipment::new("BMP-2".to_string(), 5), Equipment::new("PKM".to_string(), 4)]);
+        assert_eq!(child_names(&db, keep), ["1st Company", "2nd Company", "3rd Company"]);
+        assert_dense_sort_order(&db, keep);
+        assert_eq!(child_names(&db, regiment), ["1st Battalion"]);
+    }
+
+    #[test]
+    fn test_merge_units_refuses_a_unit_into_its_subordinate() {
+        let db = Database::open_in_memory().unwrap();
+        let lib_id = setup(&db);
+        let service = UnitService::new(db.conn());
+        let parent = service.add_root(lib_id, "Battalion", "Battalion").unwrap().id.unwrap();
+        let child = service.add_child(parent, "Battalion HQ", "Company").unwrap().id.unwrap();
+        assert!(service.merge_units(child, parent).is_err());
+        assert!(service.merge_units(parent, parent).is_err());
+        assert_eq!(child_names(&db, parent), ["Battalion HQ"]);
+    }
+
+    #[test]
+    fn test_ordering_stable_after_multiple_moves() {
+        let db = Database::open_in_memory().unwrap();
+        let service = UnitService::new(db.conn());
+        let company = service.add_root(setup(&db), "Company", "company").unwrap().id.unwrap();
+        let ids: Vec<i64> = ["A", "B", "C", "D"]
+            .iter()
+            .map(|n| service.add_child(company, n, "platoon").unwrap().id.unwrap())
+            .collect();
+        assert_eq!(child_names(&db, company), ["A", "B", "C", "D"]);
+        assert_dense_sort_order(&db, company);
+
+        let steps = [
+            (ids[2], true, ["A", "C", "B", "D"]),
+            (ids[2], true, ["C", "A", "B", "D"]),
+            (ids[0], false, ["C", "B", "A", "D"]),
+            (ids[0], false, ["C", "B", "D", "A"]),
+        ];
+        for (id, up, expected) in steps {
+            let moved = if up { service.move_up(id) } else { service.move_down(id) };
+            assert!(moved.unwrap());
+            assert_eq!(child_names(&db, company), expected);
+            assert_dense_sort_order(&db, company);
+        }
+        // New children go last, and the order survives reloading
+        service.add_child(company, "E", "platoon").unwrap();
+        assert_eq!(child_names(&db, company), ["C", "B", "D", "A", "E"]);
+        assert_dense_sort_order(&db, company);
+    }
+
+    #[test]
+    fn test_move_first_up_and_last_down_are_noops() {
+        let db = Database::open_in_memory().unwrap();
+        let service = UnitService::new(db.conn());
+        let company = service.add_root(setup(&db), "Company", "company").unwrap().id.unwrap();
+        let first = service.add_child(company, "A", "platoon").unwrap().id.unwrap();
+        let last = service.add_child(company, "B", "platoon").unwrap().id.unwrap();
+
+        assert!(!service.move_up(first).unwrap());
+        assert!(!service.move_down(last).unwrap());
+        assert_eq!(child_names(&db, company), ["A", "B"]);
+    }
+
+    #[test]
+    fn test_delete_parent_removes_grandchildren() {
+        let db = Database::open_in_memory().unwrap();
+        let library_id = setup(&db);
+        let service = UnitService::new(db.conn());
+        let battalion = service.add_root(library_id, "Battalion", "battalion").unwrap().id.unwrap();
+        let company = service.add_child(battalion, "Company", "company").unwrap().id.unwrap();
+        let platoon = service.add_child(company, "Platoon", "platoon").unwrap().id.unwrap();
+        service.add_child(platoon, "Squad", "squad").unwrap();
+        let other = service.add_root(library_id, "Other", "company").unwrap().id.unwrap();
+
+        assert_eq!(service.delete_subtree(company).unwrap(), 3);
+        let repo = UnitRepo::new(db.conn());
+        assert!(repo.get_by_id(platoon).unwrap().is_none());
+        assert!(child_names(&db, battalion).is_empty());
+        assert!(repo.get_by_id(other).unwrap().is_some());
+        assert!(service.delete_subtree(company).is_err());
+    }
+
+    #[test]
+    fn test_unique_toe_numbers_per_library() {
+        let db = Database::open_in_memory().unwrap();
+        let service = UnitService::new(db.conn()).with_unique_toe_numbers(true);
+        let library_id = setup(&db);
+        let first = service.add_root(library_id, "1st Battalion", "battalion").unwrap();
+        let second = service.add_root(library_id, "2nd Battalion", "battalion").unwrap();
+        let other = service.add_root(setup(&db), "Other library", "battalion").unwrap();
+
+        let numbered = |unit: &Unit, number: &str| Unit { toe_number: Some(number.to_string()), ..unit.clone() };
+        service.update_properties(&numbered(&first, "  TOE  07015L000 ")).unwrap();
+        let repo = UnitRepo::new(db.conn());
+        assert_eq!(repo.get_by_id(first.id.unwrap()).unwrap().unwrap().toe_number.as_deref(), Some("TOE 07015L000"));
+
+        // Case and spacing do not make a different number
+        let err = service.update_properties(&numbered(&second, "toe07015l000")).unwrap_err();
+        let collision = err.downcast_ref::<ToeNumberCollision>().unwrap();
+        assert_eq!((collision.unit_id, collision.unit_name.as_str()), (first.id.unwrap(), "1st Battalion"));
+        assert_eq!(repo.get_by_id(second.id.unwrap()).unwrap().unwrap().toe_number, None);
+
+        // Saving a unit with its own number, another library, or the check off are fine
+        service.update_properties(&numbered(&first, "TOE 07015L000")).unwrap();
+        service.update_properties(&numbered(&other, "TOE 07015L000")).unwrap();
+        UnitService::new(db.conn()).update_properties(&numbered(&second, "TOE 07015L000")).unwrap();
+        // Blank numbers are cleared
+        service.update_properties(&numbered(&first, "  ")).unwrap();
+        assert_eq!(repo.get_by_id(first.id.unwrap()).unwrap().unwrap().toe_number, None);
+    }
+
+    #[test]
+    fn test_new_units_warn_then_refuse_past_tree_limits() {
+        let db = Database::open_in_memory().unwrap();
+        let library_id = setup(&db);
+        let limits = TreeLimits { warn_depth: 3, max_depth: 4, warn_children: 2, max_children: 3, ..TreeLimits::default() };
+        let service = UnitService::new(db.conn()).with_tree_limits(limits);
+
+        let mut parent = service.add_root(library_id, "L1", "").unwrap().id.unwrap();
+        for level in 2..=4 {
+            parent = service.add_child(parent, &format!("L{}", level), "").unwrap().id.unwrap();
+        }
+        assert_eq!(UnitRepo::new(db.conn()).count_levels(parent).unwrap(), 4);
+        let err = service.add_child(parent, "L5", "").unwrap_err();
+        assert_eq!(err.downcast_ref::<TreeLimitIssue>(), Some(&TreeLimitIssue::TooDeep { depth: 5, limit: 4 }));
+
+        let top = UnitRepo::new(db.conn()).get_by_library_id(library_id).unwrap()[0].id.unwrap();
+        assert_eq!(service.check_new_unit(library_id, Some(top)).unwrap(), vec![]);
+        service.add_child(top, "Second", "").unwrap();
+        assert_eq!(
+            service.check_new_unit(library_id, Some(top)).unwrap(),
+            vec![TreeLimitIssue::TooWide { children: 3, limit: 2 }]
+        );
+        service.add_child(top, "Third", "").unwrap();
+        assert!(service.add_child(top, "Fourth", "").is_err());
+        assert_eq!(child_names(&db, top), ["L2", "Second", "Third"]);
+    }
+}
@@ -0,0 +1,3 @@
+//! View state of the formation tabs that does not depend on the UI toolkit
+
+pub mod zoom;
@@ -0,0 +1,101 @@
+//! Zoom of a formation view
+//!
+//! Zoom levels are powers of [`ZOOM_STEP`] clamped to
+//! [`MIN_ZOOM`]..=[`MAX_ZOOM`]. Each step snaps to the nearest power first,
+//! so zooming in and back out lands on exactly the level it started from
+//! instead of drifting by float error.
+
+/// Zoom of a new view
+pub const DEFAULT_ZOOM: f32 = 1.0;
+pub const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 4.0;
+/// Factor of one Zoom In or Zoom Out
+pub const ZOOM_STEP: f32 = 1.2;
+
+/// `zoom` within the limits; anything not a positive number is the default
+pub fn clamp_zoom(zoom: f32) -> f32 {
+    if zoom.is_finite() && zoom > 0.0 {
+        zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+    } else {
+        DEFAULT_ZOOM
+    }
+}
+
+/// Number of steps from the default zoom to the level nearest `zoom`
+fn steps(zoom: f32) -> i32 {
+    (clamp_zoom(zoom).ln() / ZOOM_STEP.ln()).round() as i32
+}
+
+/// Zoom `steps` steps from the default, clamped
+fn at_steps(steps: i32) -> f32 {
+    clamp_zoom(ZOOM_STEP.powi(steps))
+}
+
+/// One step closer
+pub fn zoom_in(zoom: f32) -> f32 {
+    at_steps(steps(zoom) + 1)
+}
+
+/// One step further
+pub fn zoom_out(zoom: f32) -> f32 {
+    at_steps(steps(zoom) - 1)
+}
+
+/// Zoom as a percentage for display, e.g. 120
+pub fn zoom_percent(zoom: f32) -> i32 {
+    (clamp_zoom(zoom) * 100.0).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat(zoom: f32, times: usize, step: fn(f32) -> f32) -> f32 {
+        (0..times).fold(zoom, |z, _| step(z))
+    }
+
+    #[test]
+    fn test_steps_multiply_by_the_factor() {
+        assert!((zoom_in(DEFAULT_ZOOM) - 1.2).abs() < 1e-6);
+        assert!((zoom_out(DEFAULT_ZOOM) - 1.0 / 1.2).abs() < 1e-6);
+        assert_eq!(zoom_percent(zoom_in(zoom_in(DEFAULT_ZOOM))), 144);
+    }
+
+    #[test]
+    fn test_repeated_zoom_stops_at_the_limits() {
+        assert_eq!(repeat(DEFAULT_ZOOM, 10, zoom_in), MAX_ZOOM);
+        assert_eq!(repeat(DEFAULT_ZOOM, 20, zoom_out), MIN_ZOOM);
+        // Leaving a limit goes back onto the regular levels
+        assert!((zoom_out(MAX_ZOOM) - ZOOM_STEP.powi(7)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_zooming_in_and_out_does_not_drift() {
+        // Ten steps in and ten back out, within the limits
+        let zoomed_out = repeat(DEFAULT_ZOOM, 5, zoom_out);
+        let zoomed_in = repeat(zoomed_out, 10, zoom_in);
+        assert_eq!(repeat(zoomed_in, 5, zoom_out), 1.0);
+        let mut zoom = DEFAULT_ZOOM;
+        for _ in 0..10 {
+            zoom = zoom_out(zoom_in(zoom));
+        }
+        assert_eq!(zoom, 1.0);
+    }
+
+    #[test]
+    fn test_off_grid_zoom_snaps_to_the_nearest_level() {
+        // A saved 1.19 is taken as 1.2
+        assert_eq!(zoom_in(1.19), zoom_in(zoom_in(DEFAULT_ZOOM)));
+        assert_eq!(zoom_out(1.19), 1.0);
+    }
+
+    #[test]
+    fn test_clamp_zoom() {
+        assert_eq!(clamp_zoom(0.1), MIN_ZOOM);
+        assert_eq!(clamp_zoom(10.0), MAX_ZOOM);
+        assert_eq!(clamp_zoom(1.5), 1.5);
+        assert_eq!(clamp_zoom(f32::NAN), DEFAULT_ZOOM);
+        assert_eq!(clamp_zoom(-2.0), DEFAULT_ZOOM);
+        assert_eq!(clamp_zoom(0.0), DEFAULT_ZOOM);
+    }
+}
@@ -0,0 +1,144 @@
+//! Tests for the read-only `toeditor::api` handle against a database file
+
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+use toeditor::api::{self, ReadOnlyHandle};
+use toeditor::db::repositories::EquipmentCatalogRepo;
+use toeditor::db::Database;
+use toeditor::models::{Equipment, EquipmentCatalogEntry, EquipmentCategory, Library, Personnel, Unit};
+use toeditor::services::LibraryService;
+
+/// Write a database file with one library: a battalion of two companies
+fn fixture_database(path: &Path) -> i64 {
+    let db = Database::open(path).unwrap();
+    let mut library = Library::new(
+        "US Army 2003".to_string(),
+        "US".to_string(),
+        "2003".to_string(),
+        "Author".to_string(),
+    );
+    let mut battalion = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+    battalion.add_personnel(Personnel { position: "Commander".to_string(), rank: Some("LTC".to_string()) });
+    battalion.add_equipment(Equipment { name: "M4 Carbine".to_string(), quantity: 2 });
+    let mut alpha = Unit::new("A Company".to_string(), "Company".to_string());
+    alpha.add_personnel(Personnel { position: "Rifleman".to_string(), rank: None });
+    alpha.add_personnel(Personnel { position: "Rifleman".to_string(), rank: None });
+    alpha.add_equipment(Equipment { name: "M4 Carbine".to_string(), quantity: 120 });
+    battalion.add_child(alpha);
+    let mut bravo = Unit::new("B Company".to_string(), "Company".to_string());
+    bravo.add_equipment(Equipment { name: "M2 Bradley".to_string(), quantity: 14 });
+    battalion.add_child(bravo);
+    library.add_unit(battalion);
+    LibraryService::new(db.conn()).import_library(library).unwrap().id.unwrap()
+}
+
+fn open_fixture() -> (TempDir, ReadOnlyHandle, i64) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("toeditor.db");
+    let id = fixture_database(&path);
+    let handle = api::read_only(&path).unwrap();
+    (dir, handle, id)
+}
+
+#[test]
+fn test_libraries_lists_metadata() {
+    let (_dir, handle, id) = open_fixture();
+    let libraries = handle.libraries().unwrap();
+    assert_eq!(libraries.len(), 1);
+    assert_eq!(libraries[0].id, Some(id));
+    assert_eq!(libraries[0].name, "US Army 2003");
+}
+
+#[test]
+fn test_unit_tree_is_nested() {
+    let (_dir, handle, id) = open_fixture();
+    let tree = handle.unit_tree(id).unwrap();
+    assert_eq!(tree.len(), 1);
+    assert_eq!(tree[0].name, "1st Battalion");
+    let children: Vec<&str> = tree[0].children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(children, ["A Company", "B Company"]);
+}
+
+#[test]
+fn test_summary_totals() {
+    let (_dir, handle, id) = open_fixture();
+    let summary = handle.summary(id).unwrap();
+    assert_eq!(summary.name, "US Army 2003");
+    assert_eq!(summary.unit_count, 3);
+    assert_eq!(summary.personnel, 3);
+    assert_eq!(summary.equipment.get("M4 Carbine"), Some(&122));
+    assert_eq!(summary.equipment.get("M2 Bradley"), Some(&14));
+}
+
+#[test]
+fn test_summary_counts_aliases_under_catalog_name() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("toeditor.db");
+    let id = fixture_database(&path);
+    {
+        let db = Database::open(&path).unwrap();
+        let mut m4 = EquipmentCatalogEntry::new(id, "М4".to_string(), "M4".to_string(), EquipmentCategory::SmallArms);
+        m4.aliases = vec!["m4 carbine".to_string()];
+        EquipmentCatalogRepo::new(db.conn()).create(&mut m4).unwrap();
+    }
+    let summary = api::read_only(&path).unwrap().summary(id).unwrap();
+    assert_eq!(summary.equipment.get("M4"), Some(&122));
+    assert_eq!(summary.equipment.get("M4 Carbine"), None);
+    assert_eq!(summary.equipment.get("M2 Bradley"), Some(&14));
+}
+
+#[test]
+fn test_library_json_is_full_export() {
+    let (_dir, handle, id) = open_fixture();
+    let json: serde_json::Value = serde_json::from_str(&handle.library_json(id).unwrap()).unwrap();
+    assert!(json["format_version"].is_number());
+    assert_eq!(json["library"]["name"], "US Army 2003");
+    assert_eq!(json["library"]["units"][0]["children"].as_array().unwrap().len(), 2);
+    assert!(!json["branches"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_missing_library_is_an_error() {
+    let (_dir, handle, id) = open_fixture();
+    assert!(handle.summary(id + 100).is_err());
+    assert!(handle.unit_tree(id + 100).is_err());
+    assert!(handle.library_json(id + 100).is_err());
+}
+
+#[test]
+fn test_missing_file_is_not_created() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("absent.db");
+    assert!(api::read_only(&path).is_err());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_handle_does_not_write() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("toeditor.db");
+    let id = fixture_database(&path);
+    let before = std::fs::read(&path).unwrap();
+    let handle = api::read_only(&path).unwrap();
+    handle.libraries().unwrap();
+    handle.summary(id).unwrap();
+    drop(handle);
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+}
+
+#[test]
+fn test_handle_shared_between_threads() {
+    let (_dir, handle, id) = open_fixture();
+    let handle = Arc::new(handle);
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let handle = Arc::clone(&handle);
+            thread::spawn(move || handle.summary(id).unwrap().unit_count)
+        })
+        .collect();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), 3);
+    }
+}
@@ -0,0 +1,171 @@
+//! Tests for saving a library's branch categories and other list rows from
+//! the editors
+
+use toeditor::db::repositories::{BranchCategoryRepo, BranchRepo, FormationLevelRepo, LibraryRepo};
+use toeditor::db::Database;
+use toeditor::models::{Branch, BranchCategory, CustomFormationLevel, Library};
+use toeditor::services::{
+    referenced_categories, save_branch_categories, save_branches, save_editor_rows, save_formation_levels, EditorRows,
+    LevelRemap,
+};
+
+/// Library with categories "Combat" and "Support" and a branch in each,
+/// plus one without a category
+fn library_with_branches(db: &Database) -> (i64, Vec<BranchCategory>) {
+    let mut library = Library::new("Test".to_string(), "RU".to_string(), "2020".to_string(), "A".to_string());
+    LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+    let lib_id = library.id.unwrap();
+
+    let cat_repo = BranchCategoryRepo::new(db.conn());
+    let mut categories = Vec::new();
+    for (i, (ru, en)) in [("Боевые", "Combat"), ("Обеспечения", "Support")].into_iter().enumerate() {
+        let mut category = BranchCategory::new(lib_id, ru.to_string(), en.to_string());
+        category.sort_order = i as i64;
+        cat_repo.create(&mut category).unwrap();
+        categories.push(category);
+    }
+    let branch_repo = BranchRepo::new(db.conn());
+    for (category, ru, en) in [
+        (categories[0].id, "Пехота", "Infantry"),
+        (categories[1].id, "Связь", "Signals"),
+        (None, "Прочие", "Other"),
+    ] {
+        let mut branch = Branch::with_category(lib_id, category, ru.to_string(), en.to_string());
+        branch_repo.create(&mut branch).unwrap();
+    }
+    (lib_id, categories)
+}
+
+/// Category names of the library's branches, by branch English name
+fn branch_categories(db: &Database, lib_id: i64) -> Vec<(String, Option<String>)> {
+    let categories = BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+    BranchRepo::new(db.conn())
+        .list_by_library(lib_id)
+        .unwrap()
+        .into_iter()
+        .map(|b| {
+            let category = b
+                .category_id
+                .and_then(|id| categories.iter().find(|c| c.id == Some(id)))
+                .map(|c| c.name_en.clone());
+            (b.name_en, category)
+        })
+        .collect()
+}
+
+#[test]
+fn test_renaming_category_keeps_branch_assignments() {
+    let db = Database::open_in_memory().unwrap();
+    let (lib_id, mut categories) = library_with_branches(&db);
+    categories[0].name_en = "Combat arms".to_string();
+    categories.swap(0, 1);
+    categories.push(BranchCategory::new(lib_id, "Специальные".to_string(), "Special".to_string()));
+
+    assert!(referenced_categories(db.conn(), lib_id, &categories).unwrap().is_empty());
+    save_branch_categories(db.conn(), lib_id, &categories).unwrap();
+
+    assert_eq!(
+        branch_categories(&db, lib_id),
+        vec![
+            ("Infantry".to_string(), Some("Combat arms".to_string())),
+            ("Signals".to_string(), Some("Support".to_string())),
+            ("Other".to_string(), None),
+        ]
+    );
+    let stored = BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+    let names: Vec<&str> = stored.iter().map(|c| c.name_en.as_str()).collect();
+    assert_eq!(names, ["Support", "Combat arms", "Special"]);
+    assert_eq!(stored[0].id, categories[0].id);
+}
+
+#[test]
+fn test_removed_category_is_reported_and_its_branches_cleared() {
+    let db = Database::open_in_memory().unwrap();
+    let (lib_id, categories) = library_with_branches(&db);
+    let remaining = vec![categories[0].clone()];
+
+    let referenced = referenced_categories(db.conn(), lib_id, &remaining).unwrap();
+    assert_eq!(referenced.len(), 1);
+    assert_eq!(referenced[0].category.name_en, "Support");
+    let branches: Vec<&str> = referenced[0].branches.iter().map(|b| b.name_en.as_str()).collect();
+    assert_eq!(branches, ["Signals"]);
+
+    save_branch_categories(db.conn(), lib_id, &remaining).unwrap();
+    assert_eq!(BranchRepo::new(db.conn()).count_by_category(categories[1].id.unwrap()).unwrap(), 0);
+    assert_eq!(
+        branch_categories(&db, lib_id),
+        vec![
+            ("Infantry".to_string(), Some("Combat".to_string())),
+            ("Signals".to_string(), None),
+            ("Other".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn test_editor_rows_save_keeps_category_ids() {
+    let db = Database::open_in_memory().unwrap();
+    let (lib_id, categories) = library_with_branches(&db);
+    save_editor_rows(db.conn(), lib_id, &EditorRows::BranchCategories(categories.clone())).unwrap();
+    let stored = BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+    assert_eq!(
+        stored.iter().map(|c| c.id).collect::<Vec<_>>(),
+        categories.iter().map(|c| c.id).collect::<Vec<_>>()
+    );
+    assert_eq!(branch_categories(&db, lib_id)[0].1.as_deref(), Some("Combat"));
+}
+
+/// Make every insert into `table` fail, as a full disk or a locked database would
+fn fail_inserts(db: &Database, table: &str) {
+    db.conn()
+        .execute_batch(&format!(
+            "CREATE TRIGGER fail_{table} BEFORE INSERT ON {table} BEGIN SELECT RAISE(ABORT, 'disk full'); END;"
+        ))
+        .unwrap();
+}
+
+#[test]
+fn test_save_branches_stores_list_order() {
+    let db = Database::open_in_memory().unwrap();
+    let (lib_id, categories) = library_with_branches(&db);
+    let mut branches = BranchRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+    branches.reverse();
+    branches.push(Branch::with_category(lib_id, categories[0].id, "Сапёры".to_string(), "Sappers".to_string()));
+
+    save_branches(db.conn(), lib_id, &branches).unwrap();
+    assert_eq!(
+        branch_categories(&db, lib_id),
+        vec![
+            ("Other".to_string(), None),
+            ("Signals".to_string(), Some("Support".to_string())),
+            ("Infantry".to_string(), Some("Combat".to_string())),
+            ("Sappers".to_string(), Some("Combat".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn test_failed_saves_report_error_and_keep_stored_rows() {
+    let db = Database::open_in_memory().unwrap();
+    let (lib_id, mut categories) = library_with_branches(&db);
+    let before = branch_categories(&db, lib_id);
+
+    fail_inserts(&db, "branches");
+    let mut branches = BranchRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+    branches.push(Branch::new(lib_id, "Сапёры".to_string(), "Sappers".to_string()));
+    assert!(save_branches(db.conn(), lib_id, &branches).is_err());
+    assert_eq!(branch_categories(&db, lib_id), before);
+
+    fail_inserts(&db, "branch_categories");
+    categories.push(BranchCategory::new(lib_id, "Специальные".to_string(), "Special".to_string()));
+    assert!(save_branch_categories(db.conn(), lib_id, &categories).is_err());
+    assert_eq!(BranchCategoryRepo::new(db.conn()).list_by_library(lib_id).unwrap().len(), 2);
+    assert_eq!(branch_categories(&db, lib_id), before);
+
+    let stored = FormationLevelRepo::new(db.conn()).list_by_library(lib_id).unwrap();
+    fail_inserts(&db, "formation_levels");
+    let mut levels = stored.clone();
+    levels.push(CustomFormationLevel::new(lib_id, "Звено".to_string(), "Flight".to_string(), 2));
+    assert!(save_formation_levels(db.conn(), lib_id, &levels, &LevelRemap::new()).is_err());
+    assert_eq!(FormationLevelRepo::new(db.conn()).list_by_library(lib_id).unwrap(), stored);
+}
@@ -0,0 +1,151 @@
+//! The headless subcommands, run through the `toeditor` binary against a
+//! database in a temporary directory
+
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+use toeditor::api::LibraryManager;
+use toeditor::cli::{EXIT_LIBRARY_NOT_FOUND, EXIT_OK, EXIT_USAGE};
+use toeditor::models::{Equipment, Library};
+
+fn toeditor(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_toeditor")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// A database file with "US Army 2003": a battalion with one company
+fn fixture_database(path: &Path) {
+    let manager = LibraryManager::open(path).unwrap();
+    let library = Library::new("US Army 2003".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+    let id = manager.create_library(library).unwrap().id.unwrap();
+    let battalion = manager.add_unit(id, None, "1st Battalion", "Battalion").unwrap();
+    let company = manager.add_unit(id, battalion.id, "A Company", "Company").unwrap();
+    manager
+        .set_unit_rows(company.id.unwrap(), &[], &[Equipment::new("M2 Bradley".to_string(), 14)])
+        .unwrap();
+}
+
+#[test]
+fn test_export_import_round_trip_without_a_window() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path().join("source.db");
+    fixture_database(&db);
+    let db = db.to_str().unwrap();
+
+    let csv = dir.path().join("out.csv");
+    let output = toeditor(&["export", "--library", "US Army 2003", "--format", "csv", "--out", csv.to_str().unwrap(), "--db", db]);
+    assert_eq!(output.status.code(), Some(EXIT_OK), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(std::fs::read_to_string(&csv).unwrap().contains("M2 Bradley"));
+
+    let json = dir.path().join("army.json");
+    let output = toeditor(&["export", "--library", "US Army 2003", "--out", json.to_str().unwrap(), "--db", db]);
+    assert_eq!(output.status.code(), Some(EXIT_OK));
+
+    let target = dir.path().join("target.db");
+    let target = target.to_str().unwrap();
+    let output = toeditor(&["import", json.to_str().unwrap(), "--db", target]);
+    assert_eq!(output.status.code(), Some(EXIT_OK), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout(&output).contains("US Army 2003"));
+
+    let output = toeditor(&["list", "--db", target]);
+    assert_eq!(output.status.code(), Some(EXIT_OK));
+    assert!(stdout(&output).contains("US Army 2003\tUS\t2003"));
+
+    let imported = LibraryManager::open(target).unwrap();
+    let id = imported.libraries().unwrap()[0].id.unwrap();
+    let units = imported.library(id).unwrap().units;
+    assert_eq!(units[0].children[0].equipment, [Equipment::new("M2 Bradley".to_string(), 14)]);
+}
+
+#[test]
+fn test_snapshot_and_migrate() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path().join("source.db");
+    fixture_database(&db);
+    let db = db.to_str().unwrap();
+
+    let output = toeditor(&["snapshot", "--library", "US Army 2003", "--description", "Nightly", "--db", db]);
+    assert_eq!(output.status.code(), Some(EXIT_OK), "{}", String::from_utf8_lossy(&output.stderr));
+    let manager = LibraryManager::open(db).unwrap();
+    let id = manager.libraries().unwrap()[0].id.unwrap();
+    assert_eq!(manager.versions(id).unwrap()[0].description.as_deref(), Some("Nightly"));
+
+    let fresh = dir.path().join("fresh.db");
+    let output = toeditor(&["migrate", "--db", fresh.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(EXIT_OK));
+    assert!(stdout(&output).contains(&format!("schema version {}", toeditor::db::Database::CURRENT_SCHEMA_VERSION)));
+}
+
+#[test]
+fn test_stats_prints_counts_as_json() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path().join("source.db");
+    fixture_database(&db);
+
+    let output = toeditor(&["stats", "--library", "US Army 2003", "--db", db.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(EXIT_OK), "{}", String::from_utf8_lossy(&output.stderr));
+    let stats: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(stats["units"], 2);
+    assert_eq!(stats["equipment"], 14);
+    assert!(stats["created_at"].as_i64().unwrap() > 0);
+}
+
+#[test]
+fn test_exit_codes_for_failures() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path().join("source.db");
+    fixture_database(&db);
+    let db = db.to_str().unwrap();
+    let out = dir.path().join("out.csv");
+
+    let output = toeditor(&["export", "--library", "Missing", "--out", out.to_str().unwrap(), "--db", db]);
+    assert_eq!(output.status.code(), Some(EXIT_LIBRARY_NOT_FOUND));
+    assert!(!out.exists());
+
+    let output = toeditor(&["export", "--library", "US Army 2003", "--db", db]);
+    assert_eq!(output.status.code(), Some(EXIT_USAGE));
+
+    let missing = dir.path().join("missing.json");
+    let output = toeditor(&["import", missing.to_str().unwrap(), "--db", db]);
+    assert_ne!(output.status.code(), Some(EXIT_OK));
+    assert_ne!(output.status.code(), Some(EXIT_LIBRARY_NOT_FOUND));
+}
+
+#[test]
+fn test_commands_on_a_missing_database_create_nothing() {
+    let dir = TempDir::new().unwrap();
+    let typo = dir.path().join("typo.db");
+    let out = dir.path().join("out.json");
+    for args in [
+        vec!["list"],
+        vec!["export", "--library", "US Army 2003", "--out", out.to_str().unwrap()],
+        vec!["snapshot", "--library", "US Army 2003"],
+        vec!["stats", "--library", "US Army 2003"],
+    ] {
+        let output = toeditor(&[args.as_slice(), &["--db", typo.to_str().unwrap()]].concat());
+        assert_ne!(output.status.code(), Some(EXIT_OK));
+        assert_ne!(output.status.code(), Some(EXIT_LIBRARY_NOT_FOUND));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Database not found"), "{:?}", args);
+        assert!(!typo.exists());
+    }
+}
+
+/// Without --db neither the settings nor the default database are written
+#[cfg(unix)]
+#[test]
+fn test_commands_without_db_write_no_settings() {
+    let dir = TempDir::new().unwrap();
+    let home = dir.path().join("home");
+    let output = Command::new(env!("CARGO_BIN_EXE_toeditor"))
+        .arg("list")
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Database not found"));
+    assert!(!home.exists());
+}
@@ -16,6 +16,7 @@ fn test_settings_save_and_load() {
         color_scheme: "dark".to_string(),
         language: "ru".to_string(),
         database_path: Some(PathBuf::from("/tmp/test.db")),
+        ..Settings::default()
     };
     
     // Mock config_dir to return temp directory
@@ -76,6 +77,7 @@ fn test_settings_serialization_roundtrip() {
         color_scheme: "dark".to_string(),
         language: "ru".to_string(),
         database_path: Some(PathBuf::from("/custom/path.db")),
+        ..Settings::default()
     };
     
     let toml = toml::to_string(&original).unwrap();
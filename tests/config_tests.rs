@@ -19,6 +19,7 @@ fn test_settings_serialization() {
         color_scheme: "dark".to_string(),
         language: "ru".to_string(),
         database_path: Some(PathBuf::from("/tmp/test.db")),
+        ..Settings::default()
     };
     
     // Test that settings can be serialized to TOML
@@ -1,6 +1,6 @@
 //! Edge case tests for export functions
 
-use toeditor::export::{export_json, export_csv, export_svg};
+use toeditor::export::{export_json, export_csv, export_svg, SvgOptions, SvgOrientation};
 use toeditor::models::Library;
 use tempfile::NamedTempFile;
 
@@ -73,7 +73,62 @@ fn test_export_csv_library_with_units() {
     let content = std::fs::read_to_string(file.path()).unwrap();
     assert!(content.contains("Unit 1"), "CSV should contain unit name");
     assert!(content.contains("Company"), "CSV should contain unit type");
-    assert!(content.contains("Unit,Type,Parent,Personnel,Equipment"), "CSV should have header");
+    assert!(content.contains("Unit,Type,Parent,Depth,Personnel,Equipment"), "CSV should have header");
+}
+
+#[test]
+fn test_export_csv_round_trips_tricky_fields() {
+    use toeditor::models::{Equipment, Personnel, Unit};
+
+    let mut library = Library::new(
+        "Lib, \"quoted\"".to_string(),
+        "US".to_string(),
+        "2003".to_string(),
+        "Author".to_string(),
+    );
+    let mut battalion = Unit::new("1st Battalion, \"Black Lions\"".to_string(), "Battalion".to_string());
+    battalion.add_personnel(Personnel {
+        position: "Commander\nacting".to_string(),
+        rank: Some("LTC, \"Acting\"".to_string()),
+    });
+    let mut company = Unit::new("A Co\r\nHQ".to_string(), "Company".to_string());
+    company.add_equipment(Equipment::new("M2 \"Ma Deuce\", .50 cal".to_string(), 4));
+    company.add_equipment(Equipment::new("Radio".to_string(), 6));
+    battalion.add_child(company);
+    library.add_unit(battalion);
+
+    let file = NamedTempFile::new().unwrap();
+    export_csv(&library, file.path()).unwrap();
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(file.path())
+        .unwrap();
+    let records: Vec<Vec<String>> = reader
+        .records()
+        .map(|r| r.unwrap().iter().map(str::to_string).collect())
+        .collect();
+    let row = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect::<Vec<String>>();
+
+    assert_eq!(records[0], row(&["Library", "Lib, \"quoted\"", "US", "2003"]));
+    let section = |name: &str| records.iter().position(|r| r == &row(&[name])).unwrap();
+    let units = section("Units");
+    assert_eq!(records[units + 2], row(&["1st Battalion, \"Black Lions\"", "Battalion", "", "0", "1", ""]));
+    assert_eq!(
+        records[units + 3],
+        row(&["A Co\r\nHQ", "Company", "1st Battalion, \"Black Lions\"", "1", "0", "4xM2 \"Ma Deuce\", .50 cal; 6xRadio"])
+    );
+    let personnel = section("Personnel");
+    assert_eq!(
+        records[personnel + 2],
+        row(&["1st Battalion, \"Black Lions\"", "Commander\nacting", "LTC, \"Acting\""])
+    );
+    let equipment = section("Equipment");
+    assert_eq!(records[equipment + 2], row(&["A Co\r\nHQ", "M2 \"Ma Deuce\", .50 cal", "4"]));
+    assert_eq!(records[equipment + 3], row(&["A Co\r\nHQ", "Radio", "6"]));
+    assert_eq!(records[equipment + 4], row(&["A Co\r\nHQ", "Total", "10"]));
+    assert_eq!(records.len(), equipment + 5);
 }
 
 #[test]
@@ -85,7 +140,7 @@ fn test_export_svg_empty_library() {
         "Author".to_string(),
     );
     let file = NamedTempFile::new().unwrap();
-    export_svg(&library, file.path()).unwrap();
+    export_svg(&library, &library.units, file.path(), &SvgOptions::default()).unwrap();
     
     let content = std::fs::read_to_string(file.path()).unwrap();
     assert!(content.contains("Empty"));
@@ -101,10 +156,60 @@ fn test_export_svg_library_with_special_chars() {
         "Author".to_string(),
     );
     let file = NamedTempFile::new().unwrap();
-    export_svg(&library, file.path()).unwrap();
+    export_svg(&library, &library.units, file.path(), &SvgOptions::default()).unwrap();
     
     let content = std::fs::read_to_string(file.path()).unwrap();
     // Special characters should be XML-escaped in SVG
     assert!(content.contains("Test &amp; &lt;Library&gt;"), "Special chars should be XML-escaped");
     assert!(content.contains("svg"));
 }
+
+#[test]
+fn test_export_svg_ten_unit_tree_has_one_rect_per_unit() {
+    use toeditor::models::{Equipment, Personnel, Unit};
+
+    let library = Library::new(
+        "1-я мотострелковая дивизия".to_string(),
+        "RU".to_string(),
+        "1985".to_string(),
+        "Author".to_string(),
+    );
+    // Division -> 3 regiments -> 2 battalions each = 10 units
+    let mut division = Unit::new("Дивизия".to_string(), "division".to_string());
+    for r in 1..=3 {
+        let mut regiment = Unit::new(format!("{}-й мотострелковый полк", r), "regiment".to_string());
+        regiment.personnel.push(Personnel::new("Командир полка".to_string()));
+        for b in 1..=2 {
+            let mut battalion = Unit::new(format!("{}-й батальон", b), "battalion".to_string());
+            battalion.equipment.push(Equipment::new("БМП-2".to_string(), 31));
+            regiment.children.push(battalion);
+        }
+        division.children.push(regiment);
+    }
+    let units = vec![division];
+
+    for orientation in [SvgOrientation::TopDown, SvgOrientation::LeftRight] {
+        let file = NamedTempFile::new().unwrap();
+        export_svg(&library, &units, file.path(), &SvgOptions { orientation, ..SvgOptions::default() }).unwrap();
+        let content = std::fs::read_to_string(file.path()).unwrap();
+
+        let doc = roxmltree::Document::parse(&content).expect("SVG should be well-formed XML");
+        let root = doc.root_element();
+        assert_eq!(root.tag_name().name(), "svg");
+        let width: f64 = root.attribute("width").unwrap().parse().unwrap();
+        let height: f64 = root.attribute("height").unwrap().parse().unwrap();
+
+        let rects: Vec<_> = doc.descendants().filter(|n| n.has_tag_name("rect")).collect();
+        assert_eq!(rects.len(), 10);
+        let paths = doc.descendants().filter(|n| n.has_tag_name("path")).count();
+        assert_eq!(paths, 9, "one connector per parent-child edge");
+        for rect in rects {
+            let attr = |name: &str| -> f64 { rect.attribute(name).unwrap().parse().unwrap() };
+            assert!(attr("x") >= 0.0 && attr("x") + attr("width") <= width);
+            assert!(attr("y") >= 0.0 && attr("y") + attr("height") <= height);
+        }
+        assert!(content.contains("2-й мотострелковый полк"));
+        assert!(content.contains("P:1 E:0"));
+        assert!(content.contains("P:0 E:31"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Round-trip tests for exporting one formation to a file and importing it elsewhere
+
+use toeditor::db::Database;
+use toeditor::db::repositories::{FormationLevelRepo, UnitRepo};
+use toeditor::export::{export_formation_json, formation_export, import_formation_json};
+use toeditor::models::{walk_units, CustomFormationLevel, Equipment, Library, Personnel, TreeLimitIssue, TreeLimits, Unit, MAX_TREE_DEPTH};
+use toeditor::services::LibraryService;
+
+fn create_library(db: &Database, name: &str, units: Vec<Unit>) -> i64 {
+    let mut library = Library::new(name.to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+    library.units = units;
+    LibraryService::new(db.conn()).import_library(library).unwrap().id.unwrap()
+}
+
+fn create_level(db: &Database, lib_id: i64, name_ru: &str, name_en: &str, ordinal: i32) -> i64 {
+    let mut level = CustomFormationLevel::new(lib_id, name_ru.to_string(), name_en.to_string(), ordinal);
+    FormationLevelRepo::new(db.conn()).create(&mut level).unwrap();
+    level.id.unwrap()
+}
+
+/// Brigade > battalion > two companies, with personnel and equipment at every level
+fn brigade() -> Unit {
+    let mut brigade = Unit::new("1st Brigade".to_string(), "Brigade".to_string());
+    brigade.add_personnel(Personnel::with_rank("Commander".to_string(), "COL".to_string()));
+    let mut battalion = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+    battalion.add_equipment(Equipment::new("HMMWV".to_string(), 12));
+    battalion.toe_number = Some("TOE 07015L000".to_string());
+    for name in ["A Company", "B Company"] {
+        let mut company = Unit::new(name.to_string(), "Company".to_string());
+        company.add_personnel(Personnel::new("Rifleman".to_string()));
+        company.add_equipment(Equipment::new("M4 Carbine".to_string(), 100));
+        battalion.add_child(company);
+    }
+    brigade.add_child(battalion);
+    brigade
+}
+
+/// The unit tree without database ids
+fn stripped(unit: &Unit) -> Unit {
+    let mut unit = unit.clone();
+    fn strip(unit: &mut Unit) {
+        unit.id = None;
+        unit.parent_id = None;
+        unit.children.iter_mut().for_each(strip);
+    }
+    strip(&mut unit);
+    unit
+}
+
+fn unit_named(db: &Database, lib_id: i64, name: &str) -> Unit {
+    let units = UnitRepo::new(db.conn()).get_by_library_id(lib_id).unwrap();
+    walk_units(&units).map(|v| v.unit.clone()).find(|u| u.name == name).unwrap()
+}
+
+#[test]
+fn test_three_deep_subtree_round_trips_under_a_new_parent() {
+    let db = Database::open_in_memory().unwrap();
+    let lib_id = create_library(&db, "US Army 2003", vec![brigade(), Unit::new("Division HQ".to_string(), "HQ".to_string())]);
+    let source = unit_named(&db, lib_id, "1st Brigade");
+    let target_parent = unit_named(&db, lib_id, "Division HQ").id.unwrap();
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("brigade.json");
+    export_formation_json(source.id.unwrap(), db.conn(), &path).unwrap();
+    let imported = import_formation_json(&path, lib_id, Some(target_parent), TreeLimits::default(), db.conn()).unwrap();
+    assert_eq!(imported.unit_count, 4);
+    assert!(imported.warnings.is_empty());
+
+    let repo = UnitRepo::new(db.conn());
+    let copy = repo.get_by_id(imported.unit_id).unwrap().unwrap();
+    assert_eq!(copy.parent_id, Some(target_parent));
+    assert_eq!(stripped(&copy), stripped(&source));
+    // Every unit of the copy is new and hangs off a unit of the copy
+    let copy_ids: Vec<i64> = walk_units(std::slice::from_ref(&copy)).map(|v| v.unit.id.unwrap()).collect();
+    let source_ids: Vec<i64> = walk_units(std::slice::from_ref(&source)).map(|v| v.unit.id.unwrap()).collect();
+    assert!(copy_ids.iter().all(|id| !source_ids.contains(id)));
+    for visit in walk_units(std::slice::from_ref(&copy)).skip(1) {
+        assert!(copy_ids.contains(&visit.unit.parent_id.unwrap()));
+    }
+    // The original is untouched
+    assert_eq!(repo.get_by_id(source.id.unwrap()).unwrap().unwrap(), source);
+}
+
+#[test]
+fn test_cross_library_import_maps_levels_by_name() {
+    let db = Database::open_in_memory().unwrap();
+    let source_lib = create_library(&db, "Source", vec![brigade()]);
+    let brigade_level = create_level(&db, source_lib, "бригада", "brigade", 7);
+    let company_level = create_level(&db, source_lib, "рота", "company", 4);
+    let task_force = create_level(&db, source_lib, "тактическая группа", "task force", 5);
+    let repo = UnitRepo::new(db.conn());
+    let source = unit_named(&db, source_lib, "1st Brigade");
+    repo.set_formation_level(source.id.unwrap(), Some(brigade_level)).unwrap();
+    repo.set_formation_level(unit_named(&db, source_lib, "1st Battalion").id.unwrap(), Some(task_force)).unwrap();
+    repo.set_formation_level(unit_named(&db, source_lib, "A Company").id.unwrap(), Some(company_level)).unwrap();
+
+    // The target names the brigade level the same in English, the company
+    // level only in Russian, and has no task force
+    let target_lib = create_library(&db, "Target", Vec::new());
+    let target_brigade = create_level(&db, target_lib, "соединение", "Brigade", 7);
+    let target_company = create_level(&db, target_lib, "рота", "coy", 4);
+
+    let export = formation_export(db.conn(), source.id.unwrap()).unwrap();
+    assert_eq!(export.source_library, "Source");
+    assert_eq!(export.unit_levels.len(), 4);
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("brigade.json");
+    std::fs::write(&path, serde_json::to_string(&export).unwrap()).unwrap();
+
+    let imported = import_formation_json(&path, target_lib, None, TreeLimits::default(), db.conn()).unwrap();
+    assert_eq!(imported.warnings.len(), 1);
+    assert_eq!(imported.warnings[0].unit_name, "1st Battalion");
+    assert_eq!(imported.warnings[0].level, "task force");
+
+    let copy = repo.get_by_id(imported.unit_id).unwrap().unwrap();
+    let levels: Vec<Option<i64>> = walk_units(std::slice::from_ref(&copy))
+        .map(|v| repo.formation_level(v.unit.id.unwrap()).unwrap())
+        .collect();
+    assert_eq!(levels, [Some(target_brigade), None, Some(target_company), None]);
+    assert_eq!(repo.get_placement(imported.unit_id).unwrap(), Some((target_lib, None)));
+}
+
+#[test]
+fn test_import_refuses_a_parent_from_another_library() {
+    let db = Database::open_in_memory().unwrap();
+    let source_lib = create_library(&db, "Source", vec![brigade()]);
+    let target_lib = create_library(&db, "Target", Vec::new());
+    let source = unit_named(&db, source_lib, "1st Brigade");
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("brigade.json");
+    export_formation_json(source.id.unwrap(), db.conn(), &path).unwrap();
+
+    assert!(import_formation_json(&path, target_lib, source.id, TreeLimits::default(), db.conn()).is_err());
+    assert!(UnitRepo::new(db.conn()).get_by_library_id(target_lib).unwrap().is_empty());
+}
+
+#[test]
+fn test_import_refuses_a_tree_past_the_depth_limit() {
+    let db = Database::open_in_memory().unwrap();
+    let lib_id = create_library(&db, "US Army 2003", vec![brigade()]);
+    let source = unit_named(&db, lib_id, "1st Brigade");
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("brigade.json");
+    export_formation_json(source.id.unwrap(), db.conn(), &path).unwrap();
+
+    // Hang a chain down to the deepest level allowed
+    let repo = UnitRepo::new(db.conn());
+    let mut parent = source.id.unwrap();
+    for depth in 2..=MAX_TREE_DEPTH {
+        let mut unit = Unit::new(format!("Level {}", depth), "unit".to_string());
+        unit.parent_id = Some(parent);
+        repo.create(lib_id, &mut unit).unwrap();
+        parent = unit.id.unwrap();
+    }
+    assert_eq!(repo.count_levels(parent).unwrap(), MAX_TREE_DEPTH);
+    let before = walk_units(&repo.get_by_library_id(lib_id).unwrap()).count();
+
+    let err = import_formation_json(&path, lib_id, Some(parent), TreeLimits::default(), db.conn()).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<TreeLimitIssue>(),
+        Some(&TreeLimitIssue::TooDeep { depth: MAX_TREE_DEPTH + 3, limit: MAX_TREE_DEPTH })
+    );
+    assert_eq!(walk_units(&repo.get_by_library_id(lib_id).unwrap()).count(), before);
+
+    // Past the soft limit only, the import goes ahead and says so
+    let limits = TreeLimits { warn_depth: 3, ..TreeLimits::default() };
+    let imported = import_formation_json(&path, lib_id, source.id, limits, db.conn()).unwrap();
+    assert_eq!(imported.limit_warnings, [TreeLimitIssue::TooDeep { depth: 4, limit: 3 }]);
+}
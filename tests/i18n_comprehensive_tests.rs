@@ -1,6 +1,9 @@
 //! Comprehensive tests for i18n module
 
-use toeditor::i18n::{Language, TranslationManager};
+use toeditor::i18n::{
+    available_languages, format_number, language_for_locale, parse_translations, pick_name, plural_form, supported_code, time_ago, translate,
+    translation_table, Language, PluralForm, TranslationManager,
+};
 
 #[test]
 fn test_translation_manager_new() {
@@ -38,6 +41,55 @@ fn test_translation_manager_load_from_settings() {
     // We can't easily test the actual loading without mocking, but we can test it doesn't crash
 }
 
+#[test]
+fn test_translation_manager_translates_in_current_language() {
+    let mut tm = TranslationManager::new();
+    assert_eq!(tm.translate("File"), "File");
+    tm.set_language(Language::Russian);
+    assert_eq!(tm.translate("File"), "Файл");
+    tm.set_language_code("de");
+    assert_eq!(tm.language_code(), "en");
+    assert_eq!(tm.translate("File"), "File");
+}
+
+#[test]
+fn test_translate_falls_back_to_english() {
+    assert_eq!(translate("ru", "No such key anywhere"), "No such key anywhere");
+    assert_eq!(translate("de", "File"), "File");
+    assert_eq!(translate("en", "File"), "File");
+    assert!(translation_table("en").is_none());
+}
+
+#[test]
+fn test_key_present_only_in_ru() {
+    let ru = translation_table("ru").unwrap();
+    assert_eq!(ru.get("Delete Library…").map(String::as_str), Some("Удалить библиотеку…"));
+    assert_eq!(translate("ru", "Delete Library…"), "Удалить библиотеку…");
+    assert_eq!(translate("en", "Delete Library…"), "Delete Library…");
+}
+
+#[test]
+fn test_parse_translations_round_trip_with_comments() {
+    let source = "// Translations for tests\n\n{\n    // File menu\n    \"File\": \"Файл\",\n\n    \"Open…\": \"Открыть…\"\n}\n";
+    let table = parse_translations(source).unwrap();
+    assert_eq!(table.len(), 2);
+    assert_eq!(table["File"], "Файл");
+    let written = serde_json::to_string_pretty(&table).unwrap();
+    assert_eq!(parse_translations(&written).unwrap(), table);
+    assert!(parse_translations("{ \"File\": ").is_err());
+}
+
+#[test]
+fn test_available_languages() {
+    let codes: Vec<&str> = available_languages().iter().map(|l| l.code).collect();
+    assert_eq!(codes, ["en", "ru"]);
+    assert!(available_languages().iter().skip(1).all(|l| translation_table(l.code).is_some_and(|t| !t.is_empty())));
+    assert_eq!(supported_code("ru"), "ru");
+    assert_eq!(supported_code("xx"), "en");
+    assert_eq!(language_for_locale("ru_RU.UTF-8"), "ru");
+    assert_eq!(language_for_locale("de-DE"), "en");
+}
+
 #[test]
 fn test_language_code() {
     assert_eq!(Language::English.code(), "en");
@@ -59,9 +111,119 @@ fn test_language_from_code() {
     assert_eq!(Language::from_code("FR"), Language::English); // Default
 }
 
+#[test]
+fn test_language_from_locale() {
+    assert_eq!(Language::from_locale("ru-RU"), Language::Russian);
+    assert_eq!(Language::from_locale("ru_UA.UTF-8"), Language::Russian);
+    assert_eq!(Language::from_locale("RU"), Language::Russian);
+    assert_eq!(Language::from_locale("en-US"), Language::English);
+    assert_eq!(Language::from_locale("be-BY"), Language::English); // Fallback
+    assert_eq!(Language::from_locale("rus"), Language::English);
+    assert_eq!(Language::from_locale(""), Language::English);
+    assert_eq!(Language::from_locale("  @@garbage"), Language::English);
+}
+
+#[test]
+fn test_pick_name_preference_over_ui_language() {
+    for ui in ["ru", "en"] {
+        assert_eq!(pick_name("ru", ui, "Пехота", "Infantry"), "Пехота");
+        assert_eq!(pick_name("en", ui, "Пехота", "Infantry"), "Infantry");
+    }
+    assert_eq!(pick_name("auto", "ru", "Пехота", "Infantry"), "Пехота");
+    assert_eq!(pick_name("auto", "en", "Пехота", "Infantry"), "Infantry");
+    // Unknown preference behaves like "auto"
+    assert_eq!(pick_name("", "ru", "Пехота", "Infantry"), "Пехота");
+    assert_eq!(pick_name("de", "en", "Пехота", "Infantry"), "Infantry");
+}
+
+#[test]
+fn test_pick_name_falls_back_to_other_language() {
+    for pref in ["auto", "ru", "en"] {
+        for ui in ["ru", "en"] {
+            assert_eq!(pick_name(pref, ui, "", "Infantry"), "Infantry");
+            assert_eq!(pick_name(pref, ui, "Пехота", ""), "Пехота");
+            assert_eq!(pick_name(pref, ui, "  ", "Infantry"), "Infantry");
+            assert_eq!(pick_name(pref, ui, "", ""), "");
+        }
+    }
+}
+
 #[test]
 fn test_language_equality() {
     assert_eq!(Language::English, Language::English);
     assert_eq!(Language::Russian, Language::Russian);
     assert_ne!(Language::English, Language::Russian);
 }
+
+#[test]
+fn test_format_number_english() {
+    assert_eq!(format_number(0, "en"), "0");
+    assert_eq!(format_number(999, "en"), "999");
+    assert_eq!(format_number(1000, "en"), "1,000");
+    assert_eq!(format_number(1234567, "en"), "1,234,567");
+    assert_eq!(format_number(-4500, "en"), "-4,500");
+}
+
+#[test]
+fn test_format_number_russian() {
+    assert_eq!(format_number(12, "ru"), "12");
+    assert_eq!(format_number(1234567, "ru"), "1\u{a0}234\u{a0}567");
+}
+
+#[test]
+fn test_plural_form_english() {
+    assert_eq!(plural_form(1, "en"), PluralForm::One);
+    assert_eq!(plural_form(0, "en"), PluralForm::Many);
+    assert_eq!(plural_form(3, "en"), PluralForm::Many);
+    assert_eq!(plural_form(21, "en"), PluralForm::Many);
+}
+
+#[test]
+fn test_plural_form_russian() {
+    for n in [1, 21, 101] {
+        assert_eq!(plural_form(n, "ru"), PluralForm::One, "{}", n);
+    }
+    for n in [2, 4, 22, 104] {
+        assert_eq!(plural_form(n, "ru"), PluralForm::Few, "{}", n);
+    }
+    for n in [0, 5, 11, 12, 14, 111, 112] {
+        assert_eq!(plural_form(n, "ru"), PluralForm::Many, "{}", n);
+    }
+}
+
+#[test]
+fn test_time_ago_boundaries() {
+    let now = 1_717_755_000;
+    let minute = 60;
+    let hour = 60 * minute;
+    let day = 24 * hour;
+    let cases = [
+        (59, "just now", "только что"),
+        (61, "1 minute ago", "1 минуту назад"),
+        (5 * minute, "5 minutes ago", "5 минут назад"),
+        (23 * hour, "23 hours ago", "23 часа назад"),
+        (25 * hour, "1 day ago", "1 день назад"),
+        (2 * day, "2 days ago", "2 дня назад"),
+        (29 * day, "29 days ago", "29 дней назад"),
+    ];
+    for (elapsed, en, ru) in cases {
+        assert_eq!(time_ago(now - elapsed, now, "en"), en, "{}", elapsed);
+        assert_eq!(time_ago(now - elapsed, now, "ru"), ru, "{}", elapsed);
+    }
+}
+
+#[test]
+fn test_time_ago_falls_back_to_date() {
+    let now = 1_717_755_000;
+    let long_ago = now - 31 * 24 * 3600;
+    let date = chrono::DateTime::from_timestamp(long_ago, 0)
+        .unwrap()
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d")
+        .to_string();
+    assert_eq!(time_ago(long_ago, now, "en"), date);
+    assert_eq!(time_ago(long_ago, now, "ru"), date);
+    // A clock a little ahead is "just now"; far in the future is a date
+    assert_eq!(time_ago(now + 30, now, "en"), "just now");
+    assert_eq!(time_ago(now + 3600, now, "en").len(), "2024-06-07".len());
+}
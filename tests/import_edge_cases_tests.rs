@@ -1,9 +1,16 @@
 //! Edge case tests for import functions
 
-use toeditor::import::{import_json, import_json_with_versions};
+use std::path::PathBuf;
+use toeditor::db::Database;
+use toeditor::import::{import_json, import_json_with_versions, ImportError};
 use toeditor::models::Library;
+use toeditor::services::LibraryService;
 use tempfile::NamedTempFile;
 
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/json").join(name)
+}
+
 #[test]
 fn test_import_json_empty_library() {
     let library = Library::new(
@@ -106,3 +113,115 @@ fn test_import_json_nonexistent_file() {
     let result = import_json(std::path::Path::new("/nonexistent/file.json"));
     assert!(result.is_err());
 }
+
+#[test]
+fn test_import_json_rejects_oversize_file_before_reading() {
+    use toeditor::import::limits::DEFAULT_MAX_FILE_SIZE;
+
+    // Sparse file: nothing is written, the size limit trips on metadata alone
+    let file = NamedTempFile::new().unwrap();
+    file.as_file().set_len(DEFAULT_MAX_FILE_SIZE + 1).unwrap();
+    let err = import_json(file.path()).unwrap_err();
+    assert!(
+        err.to_string().contains("exceeds the file size limit of 64.0 MB"),
+        "unexpected error: {}",
+        err
+    );
+    assert!(import_json_with_versions(file.path()).is_err());
+}
+
+#[test]
+fn test_import_json_rejects_over_deep_document() {
+    let depth = 100_000;
+    let json = format!(
+        r#"{{"name": "Deep", "units": {}{}}}"#,
+        "[".repeat(depth),
+        "]".repeat(depth)
+    );
+    let file = NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), json).unwrap();
+    let err = import_json(file.path()).unwrap_err();
+    let expected = format!("nesting limit of {} levels", toeditor::import::limits::DEFAULT_MAX_DEPTH);
+    assert!(err.to_string().contains(&expected), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_import_limits_accept_file_just_under_limits() {
+    use toeditor::import::limits::ImportLimits;
+
+    let library = Library::new(
+        "Edge".to_string(),
+        "US".to_string(),
+        "2003".to_string(),
+        "Author".to_string(),
+    );
+    let json = serde_json::to_string(&library).unwrap();
+    let file = NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), &json).unwrap();
+
+    // A library with no units nests two levels deep ({"tags": []})
+    let exact = ImportLimits {
+        max_file_size: json.len() as u64,
+        max_depth: 2,
+    };
+    assert_eq!(exact.read_to_string(file.path()).unwrap(), json);
+
+    let smaller = ImportLimits {
+        max_file_size: json.len() as u64 - 1,
+        ..exact
+    };
+    assert!(smaller.read_to_string(file.path()).is_err());
+    let shallower = ImportLimits { max_depth: 1, ..exact };
+    assert!(shallower.read_to_string(file.path()).is_err());
+}
+
+#[test]
+fn test_import_json_old_format_without_author_and_version() {
+    let imported = import_json(&fixture("old_format_library.json")).unwrap();
+    assert_eq!(imported.name, "Old Export");
+    assert_eq!(imported.author, "");
+    assert_eq!(imported.version, 1);
+    assert_eq!(imported.units[0].children[0].equipment[0].quantity, 44);
+    // Ids from the exporting database are dropped
+    assert_eq!(imported.id, None);
+    assert_eq!(imported.units[0].id, None);
+    assert_eq!(imported.units[0].children[0].id, None);
+    assert_eq!(imported.units[0].children[0].parent_id, None);
+}
+
+#[test]
+fn test_import_json_with_clashing_id_does_not_overwrite() {
+    let db = Database::open_in_memory().unwrap();
+    let service = LibraryService::new(db.conn());
+    let existing = service
+        .create_library(Library::new("Mine".to_string(), "RU".to_string(), "1985".to_string(), "Me".to_string()))
+        .unwrap();
+    let mut other = Library::new("Theirs".to_string(), "US".to_string(), "2003".to_string(), "Them".to_string());
+    other.id = existing.id;
+    let file = NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), serde_json::to_string(&other).unwrap()).unwrap();
+
+    let imported = import_json(file.path()).unwrap();
+    assert_eq!(imported.id, None);
+    let saved = service.save_library(imported, false).unwrap();
+    assert_ne!(saved.id, existing.id);
+    assert_eq!(service.get_library(existing.id.unwrap()).unwrap().unwrap().name, "Mine");
+    assert_eq!(service.list_libraries().unwrap().len(), 2);
+}
+
+#[test]
+fn test_import_errors_are_told_apart() {
+    let file = NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), "name: not json").unwrap();
+    assert!(matches!(import_json(file.path()), Err(ImportError::NotJson(_))));
+
+    std::fs::write(file.path(), r#"{"country": "US", "era": "2003"}"#).unwrap();
+    let err = import_json(file.path()).unwrap_err();
+    assert!(matches!(err, ImportError::NotALibrary(_)), "{:?}", err);
+    assert!(err.to_string().contains("missing field `name`"), "{}", err);
+
+    std::fs::write(file.path(), "[1, 2]").unwrap();
+    assert!(matches!(import_json(file.path()), Err(ImportError::NotALibrary(_))));
+
+    assert!(matches!(import_json(std::path::Path::new("/nonexistent/file.json")), Err(ImportError::Io(_))));
+}
@@ -0,0 +1,92 @@
+//! Round trips of the JSON library export: the envelope, pretty and compact,
+//! and bare library files from before the envelope
+
+use tempfile::NamedTempFile;
+use toeditor::db::repositories::{LibraryRepo, VersionRepo};
+use toeditor::db::Database;
+use toeditor::export::{export_json, export_json_with_options, ExportOptions};
+use toeditor::import::{import_json, import_json_with_versions, json_export_info};
+use toeditor::models::{Library, Personnel, Snapshot, Unit};
+
+fn sample() -> Library {
+    let mut library = Library::new("Армия 1943".to_string(), "SU".to_string(), "1943".to_string(), "A".to_string());
+    let mut regiment = Unit::new("Стрелковый полк".to_string(), "regiment".to_string());
+    regiment.personnel.push(Personnel::with_rank("Командир".to_string(), "Майор".to_string()));
+    regiment.children.push(Unit::new("1-й батальон".to_string(), "battalion".to_string()));
+    library.add_unit(regiment);
+    library
+}
+
+fn assert_same_library(imported: &Library, library: &Library) {
+    assert_eq!(imported.name, library.name);
+    assert_eq!(imported.units.len(), 1);
+    assert_eq!(imported.units[0].name, library.units[0].name);
+    assert_eq!(imported.units[0].personnel, library.units[0].personnel);
+    assert_eq!(imported.units[0].children[0].name, library.units[0].children[0].name);
+}
+
+#[test]
+fn test_envelope_round_trip_pretty_and_compact() {
+    let library = sample();
+    for pretty in [true, false] {
+        let file = NamedTempFile::new().unwrap();
+        let options = ExportOptions { pretty, include_versions: false };
+        export_json_with_options(&library, file.path(), &options, None).unwrap();
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(content.contains('\n'), pretty);
+        let (imported, versions) = import_json_with_versions(file.path()).unwrap();
+        assert_same_library(&imported, &library);
+        assert!(versions.is_none());
+
+        let info = json_export_info(file.path()).unwrap().unwrap();
+        assert_eq!(info.toeditor_version, env!("CARGO_PKG_VERSION"));
+        assert!(info.exported_at > 0);
+    }
+}
+
+#[test]
+fn test_bare_library_round_trip_pretty_and_compact() {
+    let library = sample();
+    for json in [serde_json::to_string_pretty(&library).unwrap(), serde_json::to_string(&library).unwrap()] {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), json).unwrap();
+
+        assert_same_library(&import_json(file.path()).unwrap(), &library);
+        assert_eq!(json_export_info(file.path()).unwrap(), None);
+    }
+}
+
+#[test]
+fn test_envelope_round_trip_with_versions() {
+    let db = Database::open_in_memory().unwrap();
+    let mut library = sample();
+    LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+    let version_repo = VersionRepo::new(db.conn());
+    let mut snapshot = Snapshot::new(library.id.unwrap(), 1, serde_json::to_string(&library).unwrap());
+    version_repo.create(&mut snapshot).unwrap();
+
+    for pretty in [true, false] {
+        let file = NamedTempFile::new().unwrap();
+        let options = ExportOptions { pretty, include_versions: true };
+        export_json_with_options(&library, file.path(), &options, Some(&version_repo)).unwrap();
+
+        let (imported, versions) = import_json_with_versions(file.path()).unwrap();
+        assert_same_library(&imported, &library);
+        assert_eq!(imported.id, None);
+        let versions = versions.unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0]["version"], 1);
+    }
+}
+
+#[test]
+fn test_export_json_writes_envelope_by_default() {
+    let library = sample();
+    let file = NamedTempFile::new().unwrap();
+    export_json(&library, file.path()).unwrap();
+    let data: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap();
+    assert!(data.get("toeditor_version").is_some());
+    assert_eq!(data["library"]["name"], "Армия 1943");
+    assert_same_library(&import_json(file.path()).unwrap(), &library);
+}
@@ -0,0 +1,191 @@
+//! Round-trip tests for LibraryService::export_full / import_full
+
+use toeditor::db::Database;
+use toeditor::db::repositories::{BranchCategoryRepo, BranchRepo, FormationLevelRepo, LibraryRepo, UnitRepo};
+use toeditor::export::{export_full_to_path, FullLibraryExport};
+use toeditor::import::import_full_json;
+use toeditor::models::{Branch, BranchCategory, CustomFormationLevel, Equipment, Library, NumberingOptions, NumberingStyle, Personnel, Unit};
+use toeditor::services::LibraryService;
+
+/// Library with a battalion of two companies, a custom category, branch and
+/// formation level; A Company is at that level, which is numbered in letters
+fn populated_library(db: &Database) -> i64 {
+    let service = LibraryService::new(db.conn());
+    let mut library = Library::new(
+        "US Army 2003".to_string(),
+        "US".to_string(),
+        "2003".to_string(),
+        "Author".to_string(),
+    );
+    library.tags = vec!["army".to_string()];
+    let mut battalion = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+    battalion.add_personnel(Personnel { position: "Commander".to_string(), rank: Some("LTC".to_string()) });
+    let mut alpha = Unit::new("A Company".to_string(), "Company".to_string());
+    alpha.add_personnel(Personnel { position: "Rifleman".to_string(), rank: None });
+    alpha.add_equipment(Equipment { name: "M4 Carbine".to_string(), quantity: 120 });
+    battalion.add_child(alpha);
+    battalion.add_child(Unit::new("B Company".to_string(), "Company".to_string()));
+    library.add_unit(battalion);
+    let lib_id = service.import_library(library).unwrap().id.unwrap();
+
+    let mut category = BranchCategory::new(lib_id, "Спецназ".to_string(), "Special forces".to_string());
+    category.sort_order = 10;
+    BranchCategoryRepo::new(db.conn()).create(&mut category).unwrap();
+    let mut branch = Branch::with_category(lib_id, category.id, "Рейнджеры".to_string(), "Rangers".to_string());
+    branch.sort_order = 20;
+    BranchRepo::new(db.conn()).create(&mut branch).unwrap();
+    let mut level = CustomFormationLevel::new(lib_id, "тактическая группа".to_string(), "task force".to_string(), 5);
+    FormationLevelRepo::new(db.conn()).create(&mut level).unwrap();
+    let unit_repo = UnitRepo::new(db.conn());
+    let alpha = unit_repo.list_tree_by_library(lib_id).unwrap().into_iter().find(|n| n.name == "A Company").unwrap();
+    unit_repo.set_formation_level(alpha.id, level.id).unwrap();
+    let mut numbering = NumberingOptions::with_style(NumberingStyle::Roman);
+    numbering.set_override(level.id.unwrap(), NumberingStyle::Letters);
+    LibraryRepo::new(db.conn()).update_numbering(lib_id, &numbering).unwrap();
+    lib_id
+}
+
+/// Strip database ids so exports from different databases compare equal;
+/// branch categories are identified by their English name. Unit levels and
+/// numbering overrides are checked on their own.
+fn normalized(mut export: FullLibraryExport) -> (FullLibraryExport, Vec<Option<String>>) {
+    let branch_categories: Vec<Option<String>> = export
+        .branches
+        .iter()
+        .map(|b| {
+            b.category_id.and_then(|id| {
+                export
+                    .branch_categories
+                    .iter()
+                    .find(|c| c.id == Some(id))
+                    .map(|c| c.name_en.clone())
+            })
+        })
+        .collect();
+    fn strip(unit: &mut Unit) {
+        unit.id = None;
+        unit.parent_id = None;
+        unit.formation_level_id = None;
+        unit.children.iter_mut().for_each(strip);
+    }
+    export.library.id = None;
+    export.library.numbering.level_overrides.clear();
+    export.library.units.iter_mut().for_each(strip);
+    for c in &mut export.branch_categories {
+        c.id = None;
+        c.library_id = 0;
+    }
+    for b in &mut export.branches {
+        b.id = None;
+        b.library_id = 0;
+        b.category_id = None;
+    }
+    for l in &mut export.formation_levels {
+        l.id = None;
+        l.library_id = 0;
+    }
+    (export, branch_categories)
+}
+
+#[test]
+fn test_full_export_round_trip_into_new_database() {
+    let source = Database::open_in_memory().unwrap();
+    let lib_id = populated_library(&source);
+    let exported = LibraryService::new(source.conn()).export_full(lib_id).unwrap();
+    assert_eq!(exported.format_version, 1);
+    assert_eq!(exported.library.units.len(), 1);
+    assert_eq!(exported.library.units[0].children.len(), 2);
+    assert!(exported.branches.iter().any(|b| b.name_en == "Rangers"));
+    assert_eq!(exported.library.numbering.style, NumberingStyle::Roman);
+
+    let file = tempfile::NamedTempFile::new().unwrap();
+    export_full_to_path(&exported, file.path()).unwrap();
+    let read_back = import_full_json(file.path()).unwrap().expect("full export");
+
+    let target = Database::open_in_memory().unwrap();
+    let service = LibraryService::new(target.conn());
+    let imported = service.import_full(read_back).unwrap();
+    let new_id = imported.id.unwrap();
+
+    // Every child table matches, up to ids
+    let reexported = service.export_full(new_id).unwrap();
+    assert_eq!(normalized(reexported.clone()), normalized(exported.clone()));
+
+    let tree = UnitRepo::new(target.conn()).list_tree_by_library(new_id).unwrap();
+    let got: Vec<(&str, i32)> = tree.iter().map(|n| (n.name.as_str(), n.depth)).collect();
+    assert_eq!(got, vec![("1st Battalion", 0), ("A Company", 1), ("B Company", 1)]);
+
+    // Branches point at the target's categories, not the source ids
+    let rangers = reexported.branches.iter().find(|b| b.name_en == "Rangers").unwrap();
+    let category = BranchCategoryRepo::new(target.conn())
+        .get_by_id(rangers.category_id.unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(category.library_id, new_id);
+    assert_eq!(category.name_en, "Special forces");
+
+    // A Company keeps its level, now the target's copy of it
+    let level_id = reexported.library.units[0].children[0].formation_level_id.expect("level kept");
+    let level = reexported.formation_levels.iter().find(|l| l.id == Some(level_id)).unwrap();
+    assert_eq!((level.library_id, level.name_en.as_str()), (new_id, "task force"));
+    assert_eq!(reexported.library.units[0].children[1].formation_level_id, None);
+
+    // The library keeps its numbering, the override on the target's level
+    let numbering = service.get_library(new_id).unwrap().unwrap().numbering;
+    assert!(numbering.enabled);
+    assert_eq!(numbering.style, NumberingStyle::Roman);
+    assert_eq!(numbering.style_for(Some(level_id)), NumberingStyle::Letters);
+    assert_eq!(numbering.level_overrides.len(), 1);
+
+    // Only the initial snapshot is created, no default categories are added
+    assert_eq!(service.get_library_versions(new_id).unwrap().len(), 1);
+    assert_eq!(reexported.branch_categories.len(), exported.branch_categories.len());
+}
+
+#[test]
+fn test_import_full_is_atomic() {
+    let source = Database::open_in_memory().unwrap();
+    let lib_id = populated_library(&source);
+    let exported = LibraryService::new(source.conn()).export_full(lib_id).unwrap();
+
+    // Fail halfway through: after the library, categories and branches are written
+    let target = Database::open_in_memory().unwrap();
+    target
+        .conn()
+        .execute_batch(
+            "CREATE TRIGGER fail_levels BEFORE INSERT ON formation_levels
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+        )
+        .unwrap();
+    let service = LibraryService::new(target.conn());
+    let err = service.import_full(exported).unwrap_err();
+    assert!(err.to_string().contains("disk full"));
+    assert!(service.list_libraries().unwrap().is_empty());
+    let branches: i64 = target
+        .conn()
+        .query_row("SELECT COUNT(*) FROM branches", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(branches, 0);
+}
+
+#[test]
+fn test_import_full_joins_the_callers_transaction() {
+    let source = Database::open_in_memory().unwrap();
+    let lib_id = populated_library(&source);
+    let exported = LibraryService::new(source.conn()).export_full(lib_id).unwrap();
+
+    let target = Database::open_in_memory().unwrap();
+    let service = LibraryService::new(target.conn());
+    let tx = target.conn().unchecked_transaction().unwrap();
+    service.import_full(exported).unwrap();
+    tx.rollback().unwrap();
+    assert!(service.list_libraries().unwrap().is_empty());
+}
+
+#[test]
+fn test_import_full_json_ignores_plain_library_files() {
+    let library = Library::new("Plain".to_string(), "US".to_string(), "2003".to_string(), "A".to_string());
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), serde_json::to_string(&library).unwrap()).unwrap();
+    assert!(import_full_json(file.path()).unwrap().is_none());
+}
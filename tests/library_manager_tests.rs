@@ -0,0 +1,239 @@
+//! Scripted sessions with `toeditor::api::LibraryManager` against database
+//! and export files in a temporary directory, no windows involved
+
+use std::path::Path;
+use tempfile::TempDir;
+use toeditor::api::{ExportFormat, LibraryManager};
+use toeditor::export::{AnonymizeOptions, ExportOptions};
+use toeditor::models::{Branch, Equipment, Library, Personnel, Unit};
+
+fn new_library(name: &str) -> Library {
+    Library::new(name.to_string(), "SU".to_string(), "1943".to_string(), "Author".to_string())
+}
+
+/// Names of the tree, depth first, indented by level
+fn outline(units: &[Unit]) -> Vec<String> {
+    fn walk(units: &[Unit], depth: usize, out: &mut Vec<String>) {
+        for unit in units {
+            out.push(format!("{}{}", "  ".repeat(depth), unit.name));
+            walk(&unit.children, depth + 1, out);
+        }
+    }
+    let mut out = Vec::new();
+    walk(units, 0, &mut out);
+    out
+}
+
+/// A library with a regiment of two battalions; returns the manager, the
+/// library id and the first battalion's id
+fn scripted_library(path: &Path) -> (LibraryManager, i64, i64) {
+    let manager = LibraryManager::open(path).unwrap();
+    let library = manager.create_library(new_library("Rifle Division 1943")).unwrap();
+    let id = library.id.unwrap();
+
+    let regiment = manager.add_unit(id, None, "Rifle Regiment", "Regiment").unwrap();
+    let first = manager.add_unit(id, regiment.id, "1st Battalion", "Battalion").unwrap();
+    manager.add_unit(id, regiment.id, "2nd Battalion", "Battalion").unwrap();
+    manager
+        .set_unit_rows(
+            first.id.unwrap(),
+            &[Personnel::with_rank("Commander".to_string(), "Major".to_string()), Personnel::new("Rifleman".to_string())],
+            &[Equipment::new("PPSh-41".to_string(), 40)],
+        )
+        .unwrap();
+    (manager, id, first.id.unwrap())
+}
+
+#[test]
+fn test_scripted_session_round_trips_into_second_manager() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, battalion_id) = scripted_library(&dir.path().join("first.db"));
+
+    let mut battalion = manager.unit(battalion_id).unwrap();
+    battalion.name = "1st Rifle Battalion".to_string();
+    manager.update_unit(&battalion).unwrap();
+    let snapshot = manager.create_snapshot(id, "Battalions").unwrap();
+    assert_eq!(manager.versions(id).unwrap()[0].version, snapshot.version);
+
+    let library = manager.library(id).unwrap();
+    assert_eq!(
+        outline(&library.units),
+        ["Rifle Regiment", "  1st Rifle Battalion", "  2nd Battalion"]
+    );
+
+    let json = dir.path().join("division.json");
+    let yaml = dir.path().join("division.yaml");
+    let full = dir.path().join("division-full.json");
+    manager.export_library(id, &json).unwrap();
+    manager.export_library(id, &yaml).unwrap();
+    manager.export_full(id, &full).unwrap();
+
+    let second = LibraryManager::open(dir.path().join("second.db")).unwrap();
+    for path in [&json, &yaml, &full] {
+        let imported = second.import_library(path).unwrap();
+        let stored = second.library(imported.id.unwrap()).unwrap();
+        assert_eq!(stored.name, "Rifle Division 1943", "{}", path.display());
+        assert_eq!(outline(&stored.units), outline(&library.units), "{}", path.display());
+        let first = &stored.units[0].children[0];
+        assert_eq!(first.personnel, library.units[0].children[0].personnel);
+        assert_eq!(first.equipment, [Equipment::new("PPSh-41".to_string(), 40)]);
+    }
+    assert_eq!(second.libraries().unwrap().len(), 3);
+    assert_eq!(second.search("division").unwrap().len(), 3);
+}
+
+#[test]
+fn test_json_export_with_versions_carries_history() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, _) = scripted_library(&dir.path().join("first.db"));
+    manager.create_snapshot(id, "Draft").unwrap();
+    let versions = manager.versions(id).unwrap().len();
+
+    let path = dir.path().join("division.json");
+    let options = ExportOptions { pretty: false, include_versions: true };
+    manager.export_json(id, &path, &options).unwrap();
+
+    let second = LibraryManager::open_in_memory().unwrap();
+    let imported = second.import_library(&path).unwrap();
+    assert!(second.versions(imported.id.unwrap()).unwrap().len() >= versions);
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, battalion_id) = scripted_library(&dir.path().join("first.db"));
+    let before = manager.create_snapshot(id, "Before cuts").unwrap();
+
+    assert_eq!(manager.delete_unit(battalion_id).unwrap(), 1);
+    assert_eq!(outline(&manager.library(id).unwrap().units), ["Rifle Regiment", "  2nd Battalion"]);
+
+    let restored = manager.restore_version(id, before.version).unwrap();
+    assert!(restored.version > before.version);
+    let units = manager.library(id).unwrap().units;
+    assert_eq!(outline(&units), ["Rifle Regiment", "  1st Battalion", "  2nd Battalion"]);
+    assert_eq!(units[0].children[0].equipment, [Equipment::new("PPSh-41".to_string(), 40)]);
+}
+
+#[test]
+fn test_autosave_revert_and_fork() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, battalion_id) = scripted_library(&dir.path().join("first.db"));
+    let autosave = manager.create_autosave_snapshot(id, 2).unwrap();
+    let version = manager.create_snapshot(id, "Full strength").unwrap().version;
+    manager.delete_unit(battalion_id).unwrap();
+
+    let fork = manager.fork_from_version(id, version).unwrap();
+    assert_eq!(outline(&manager.library(fork.id.unwrap()).unwrap().units).len(), 3);
+    manager.revert_to_version(id, version).unwrap();
+    let units = manager.library(id).unwrap().units;
+    assert_eq!(outline(&units).len(), 3);
+
+    manager.delete_unit(units[0].children[0].id.unwrap()).unwrap();
+    manager.restore_autosave(id, autosave.id.unwrap()).unwrap();
+    assert_eq!(outline(&manager.library(id).unwrap().units).len(), 3);
+}
+
+#[test]
+fn test_export_as_of_leaves_out_later_units() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, battalion_id) = scripted_library(&dir.path().join("first.db"));
+    let mut battalion = manager.unit(battalion_id).unwrap();
+    battalion.effective_from = Some("1944-01-01".to_string());
+    manager.update_unit(&battalion).unwrap();
+    let date = chrono::NaiveDate::from_ymd_opt(1943, 6, 1);
+
+    let library = manager.library_as_of(id, date).unwrap();
+    assert_eq!(outline(&library.units), ["Rifle Regiment", "  2nd Battalion"]);
+    let csv = dir.path().join("division.csv");
+    manager.export_library_as_of(id, &csv, ExportFormat::Csv, date).unwrap();
+    assert!(!std::fs::read_to_string(&csv).unwrap().contains("1st Battalion"));
+}
+
+#[test]
+fn test_csv_and_xlsx_exports_are_written() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, _) = scripted_library(&dir.path().join("first.db"));
+    let csv = dir.path().join("division.csv");
+    let xlsx = dir.path().join("division.xlsx");
+    manager.export_library(id, &csv).unwrap();
+    manager.export_library(id, &xlsx).unwrap();
+    assert!(std::fs::read_to_string(&csv).unwrap().contains("1st Battalion"));
+    assert!(std::fs::metadata(&xlsx).unwrap().len() > 0);
+    assert!(manager.export_library(id, &dir.path().join("division.txt")).is_err());
+}
+
+#[test]
+fn test_branch_lists_round_trip_through_full_export() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, _) = scripted_library(&dir.path().join("first.db"));
+    let mut branches = manager.branches(id).unwrap();
+    assert!(!branches.is_empty(), "new libraries get the default branches");
+    branches.push(Branch::new(id, "Сапёры".to_string(), "Sappers".to_string()));
+    manager.save_branches(id, branches).unwrap();
+
+    let path = dir.path().join("division-full.json");
+    manager.export_full(id, &path).unwrap();
+    let second = LibraryManager::open(dir.path().join("second.db")).unwrap();
+    let imported = second.import_library(&path).unwrap().id.unwrap();
+    let names: Vec<String> = second.branches(imported).unwrap().into_iter().map(|b| b.name_en).collect();
+    let expected: Vec<String> = manager.branches(id).unwrap().into_iter().map(|b| b.name_en).collect();
+    assert_eq!(names, expected);
+    assert_eq!(names.last().map(String::as_str), Some("Sappers"));
+    assert_eq!(
+        second.branch_categories(imported).unwrap().len(),
+        manager.branch_categories(id).unwrap().len()
+    );
+}
+
+#[test]
+fn test_anonymized_export_keeps_units_and_history() {
+    let dir = TempDir::new().unwrap();
+    let (manager, id, _) = scripted_library(&dir.path().join("first.db"));
+    manager.create_snapshot(id, "Internal draft").unwrap();
+    let library = manager.library(id).unwrap();
+
+    let path = dir.path().join("division-shared.json");
+    let options = AnonymizeOptions { placeholder_author: "TOE Team".to_string(), ..AnonymizeOptions::default() };
+    manager.export_anonymized(id, &path, &options).unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!content.contains("\"Author\""));
+    assert!(!content.contains("Internal draft"));
+    let data: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(data["library"]["author"], "TOE Team");
+    assert_eq!(data["branches"].as_array().unwrap().len(), manager.branches(id).unwrap().len());
+    assert_eq!(data["versions"].as_array().unwrap().len(), manager.versions(id).unwrap().len());
+
+    let second = LibraryManager::open(dir.path().join("second.db")).unwrap();
+    let imported = second.import_library(&path).unwrap();
+    let stored = second.library(imported.id.unwrap()).unwrap();
+    assert_eq!(stored.author, "TOE Team");
+    assert_eq!(outline(&stored.units), outline(&library.units));
+    assert_eq!(stored.units[0].children[0].personnel, library.units[0].children[0].personnel);
+}
+
+#[test]
+fn test_add_unit_rejects_parent_of_another_library() {
+    let manager = LibraryManager::open_in_memory().unwrap();
+    let first = manager.create_library(new_library("First")).unwrap().id.unwrap();
+    let second = manager.create_library(new_library("Second")).unwrap().id.unwrap();
+    let regiment = manager.add_unit(first, None, "Regiment", "Regiment").unwrap();
+    assert!(manager.add_unit(second, regiment.id, "Battalion", "Battalion").is_err());
+    assert!(manager.library(second).unwrap().units.is_empty());
+}
+
+#[test]
+fn test_create_library_stores_its_units() {
+    let manager = LibraryManager::open_in_memory().unwrap();
+    let mut regiment = Unit::new("Rifle Regiment".to_string(), "Regiment".to_string());
+    regiment.children.push(Unit::new("1st Battalion".to_string(), "Battalion".to_string()));
+    let library = Library {
+        units: vec![regiment],
+        ..new_library("Rifle Division 1943")
+    };
+    let id = manager.create_library(library.clone()).unwrap().id.unwrap();
+
+    assert_eq!(outline(&manager.library(id).unwrap().units), ["Rifle Regiment", "  1st Battalion"]);
+    assert_eq!(manager.versions(id).unwrap().len(), 1);
+    // The same library again is a duplicate
+    assert!(manager.create_library(library).is_err());
+}
@@ -185,3 +185,156 @@ fn test_create_equipment() {
     assert_eq!(retrieved.equipment[0].name, "M1 Abrams");
     assert_eq!(retrieved.equipment[0].quantity, 4);
 }
+
+#[test]
+fn test_list_tree_by_library_three_levels() {
+    let db = Database::open_in_memory().unwrap();
+    let lib_repo = LibraryRepo::new(db.conn());
+    let mut library = Library::new(
+        "Test".to_string(),
+        "US".to_string(),
+        "2003".to_string(),
+        "Author".to_string(),
+    );
+    lib_repo.create(&mut library).unwrap();
+    let lib_id = library.id.unwrap();
+    let repo = UnitRepo::new(db.conn());
+
+    let mut battalion = Unit::new("1st Battalion".to_string(), "Battalion".to_string());
+    repo.create(lib_id, &mut battalion).unwrap();
+    let mut alpha = Unit::new("A Company".to_string(), "Company".to_string());
+    alpha.parent_id = battalion.id;
+    repo.create(lib_id, &mut alpha).unwrap();
+    let mut bravo = Unit::new("B Company".to_string(), "Company".to_string());
+    bravo.parent_id = battalion.id;
+    repo.create(lib_id, &mut bravo).unwrap();
+    let mut platoon = Unit::new("1st Platoon".to_string(), "Platoon".to_string());
+    platoon.parent_id = alpha.id;
+    repo.create(lib_id, &mut platoon).unwrap();
+
+    let tree = repo.list_tree_by_library(lib_id).unwrap();
+    let got: Vec<(&str, i32)> = tree.iter().map(|n| (n.name.as_str(), n.depth)).collect();
+    assert_eq!(
+        got,
+        vec![
+            ("1st Battalion", 0),
+            ("A Company", 1),
+            ("1st Platoon", 2),
+            ("B Company", 1),
+        ]
+    );
+    assert_eq!(tree[2].parent_id, alpha.id);
+}
+
+#[test]
+fn test_list_tree_by_library_orphan_and_cycle() {
+    let db = Database::open_in_memory().unwrap();
+    let lib_repo = LibraryRepo::new(db.conn());
+    let mut library = Library::new(
+        "Test".to_string(),
+        "US".to_string(),
+        "2003".to_string(),
+        "Author".to_string(),
+    );
+    lib_repo.create(&mut library).unwrap();
+    let lib_id = library.id.unwrap();
+
+    // Simulate a hand-edited database: a dangling parent_id and a two-unit cycle
+    db.conn().execute_batch("PRAGMA foreign_keys = OFF;").unwrap();
+    db.conn()
+        .execute(
+            "INSERT INTO units (id, library_id, name, unit_type, parent_id) VALUES
+             (1, ?1, 'Orphan', 'Company', 999),
+             (2, ?1, 'Loop A', 'Platoon', 3),
+             (3, ?1, 'Loop B', 'Platoon', 2),
+             (4, ?1, 'Under orphan', 'Platoon', 1)",
+            [lib_id],
+        )
+        .unwrap();
+
+    let tree = UnitRepo::new(db.conn()).list_tree_by_library(lib_id).unwrap();
+    let got: Vec<(i64, i32)> = tree.iter().map(|n| (n.id, n.depth)).collect();
+    assert_eq!(got, vec![(1, 0), (4, 1), (2, 0), (3, 1)]);
+}
+
+#[test]
+fn test_subtree_counts_roll_up_a_deep_tree() {
+    let db = Database::open_in_memory().unwrap();
+    let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+    LibraryRepo::new(db.conn()).create(&mut library).unwrap();
+    let lib_id = library.id.unwrap();
+    let repo = UnitRepo::new(db.conn());
+
+    // A chain 12 levels deep; each level has one rifleman, 3 rifles and a
+    // leaf sibling with one crewman and one vehicle
+    let mut chain = Vec::new();
+    let mut leaves = Vec::new();
+    let mut parent_id = None;
+    for level in 0..12 {
+        let mut unit = Unit::new(format!("Level {}", level), "Unit".to_string());
+        unit.parent_id = parent_id;
+        unit.add_personnel(Personnel::new("Rifleman".to_string()));
+        unit.add_equipment(Equipment::new("Rifle".to_string(), 3));
+        repo.create(lib_id, &mut unit).unwrap();
+        let mut leaf = Unit::new(format!("Leaf {}", level), "Crew".to_string());
+        leaf.parent_id = parent_id;
+        leaf.add_personnel(Personnel::new("Crewman".to_string()));
+        leaf.add_equipment(Equipment::new("Truck".to_string(), 1));
+        repo.create(lib_id, &mut leaf).unwrap();
+        parent_id = unit.id;
+        chain.push(unit.id.unwrap());
+        leaves.push(leaf.id.unwrap());
+    }
+    let mut empty = Unit::new("Empty".to_string(), "Unit".to_string());
+    empty.parent_id = parent_id;
+    repo.create(lib_id, &mut empty).unwrap();
+
+    let counts = repo.subtree_counts(lib_id).unwrap();
+    assert_eq!(counts.len(), 25);
+    for (level, id) in chain.iter().enumerate() {
+        let below = 12 - level as i64;
+        // The chain from here down, plus the leaves hanging under levels below this one
+        assert_eq!(counts[id], (below + (below - 1), 3 * below + (below - 1)), "level {}", level);
+    }
+    for id in &leaves {
+        assert_eq!(counts[id], (1, 1));
+    }
+    assert_eq!(counts[&empty.id.unwrap()], (0, 0));
+
+    // Matches what the loaded tree says
+    let roots = repo.get_by_library_id(lib_id).unwrap();
+    assert_eq!(counts[&chain[0]].0 as usize, roots[0].total_personnel());
+}
+
+#[test]
+fn test_subtree_counts_survive_cycles_and_other_libraries() {
+    let db = Database::open_in_memory().unwrap();
+    let lib_repo = LibraryRepo::new(db.conn());
+    let mut library = Library::new("Test".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+    lib_repo.create(&mut library).unwrap();
+    let mut other = Library::new("Other".to_string(), "US".to_string(), "2003".to_string(), "Author".to_string());
+    lib_repo.create(&mut other).unwrap();
+    let lib_id = library.id.unwrap();
+
+    db.conn().execute_batch("PRAGMA foreign_keys = OFF;").unwrap();
+    db.conn()
+        .execute(
+            "INSERT INTO units (id, library_id, name, unit_type, parent_id) VALUES
+             (2, ?1, 'Loop A', 'Platoon', 3),
+             (3, ?1, 'Loop B', 'Platoon', 2),
+             (5, ?2, 'Elsewhere', 'Platoon', 2)",
+            [lib_id, other.id.unwrap()],
+        )
+        .unwrap();
+    db.conn()
+        .execute_batch(
+            "INSERT INTO personnel (unit_id, position) VALUES (2, 'A'), (3, 'B'), (5, 'C');
+             INSERT INTO equipment (unit_id, name, quantity) VALUES (3, 'Rifle', 4), (5, 'Rifle', 100);",
+        )
+        .unwrap();
+
+    let counts = UnitRepo::new(db.conn()).subtree_counts(lib_id).unwrap();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&2], (2, 4));
+    assert_eq!(counts[&3], (2, 4));
+}
@@ -0,0 +1,101 @@
+//! Tests for Excel workbook import against the fixture workbooks in tests/data/xlsx
+
+use std::path::PathBuf;
+use toeditor::import::xlsx::import_xlsx;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join("xlsx")
+        .join(name)
+}
+
+#[test]
+fn test_import_indented_structure_with_merged_cells() {
+    let result = import_xlsx(&fixture("indented.xlsx")).unwrap();
+    let library = &result.library;
+    assert_eq!(library.name, "US Army 2003 (xlsx)");
+    assert_eq!(library.country, "US");
+    assert_eq!(library.era, "2003");
+    assert_eq!(library.author, "Tester");
+    assert_eq!(library.tags, vec!["army".to_string(), "test".to_string()]);
+
+    // A2:A6 is merged in the level column; it must not turn children into roots
+    assert_eq!(library.units.len(), 1);
+    let battalion = &library.units[0];
+    assert_eq!(battalion.name, "1st Battalion");
+    assert_eq!(battalion.unit_type, "Battalion");
+    let names: Vec<&str> = battalion.children.iter().map(|u| u.name.as_str()).collect();
+    assert_eq!(names, vec!["A Company", "HHC"]);
+
+    let company = &battalion.children[0];
+    assert_eq!(company.children.len(), 2);
+    // D4:D5 is merged, so the 2nd Platoon inherits the type
+    assert_eq!(company.children[1].name, "2nd Platoon");
+    assert_eq!(company.children[1].unit_type, "Platoon");
+
+    let platoon = &company.children[0];
+    assert_eq!(platoon.personnel.len(), 6);
+    assert_eq!(platoon.personnel.iter().filter(|p| p.position == "Rifleman").count(), 3);
+    // B6:B7 is merged, so the automatic rifleman shares the rank
+    let ar = platoon.personnel.iter().find(|p| p.position == "Automatic Rifleman").unwrap();
+    assert_eq!(ar.rank.as_deref(), Some("CPL"));
+
+    let hq = &battalion.children[1];
+    assert_eq!(hq.personnel[0].rank.as_deref(), Some("CPT"));
+    assert_eq!(hq.equipment[0].quantity, 2);
+}
+
+#[test]
+fn test_import_numeric_and_text_quantities() {
+    let result = import_xlsx(&fixture("indented.xlsx")).unwrap();
+    let platoon = &result.library.units[0].children[0].children[0];
+    let quantities: Vec<(&str, usize)> = platoon
+        .equipment
+        .iter()
+        .map(|e| (e.name.as_str(), e.quantity))
+        .collect();
+    assert_eq!(quantities, vec![("M4 Carbine", 9), ("M249 SAW", 2)]);
+
+    let cells: Vec<String> = result
+        .warnings
+        .iter()
+        .filter(|w| w.sheet == "1st Platoon")
+        .map(|w| w.cell.clone())
+        .collect();
+    assert_eq!(cells, vec!["B13".to_string(), "B14".to_string()]);
+    assert!(result.warnings[0].to_string().starts_with("1st Platoon!B13:"));
+
+    let parent = import_xlsx(&fixture("parent_column.xlsx")).unwrap();
+    let bn = &parent.library.units[0].children[0];
+    assert_eq!(bn.equipment[0].quantity, 58);
+    assert_eq!(bn.equipment[1].quantity, 14);
+}
+
+#[test]
+fn test_import_parent_column() {
+    let result = import_xlsx(&fixture("parent_column.xlsx")).unwrap();
+    let library = &result.library;
+    // No Library sheet: named after the file
+    assert_eq!(library.name, "parent_column");
+    let roots: Vec<&str> = library.units.iter().map(|u| u.name.as_str()).collect();
+    assert_eq!(roots, vec!["Brigade", "Lost Co"]);
+    assert_eq!(library.units[0].children.len(), 2);
+    assert!(result
+        .warnings
+        .iter()
+        .any(|w| w.sheet == "Structure" && w.cell == "C5" && w.message.contains("Nowhere")));
+    assert!(result.warnings.iter().any(|w| w.sheet == "Library"));
+}
+
+#[test]
+fn test_import_missing_structure_sheet() {
+    let err = import_xlsx(&fixture("no_structure.xlsx")).unwrap_err();
+    assert!(err.to_string().contains("Structure"));
+}
+
+#[test]
+fn test_import_missing_file() {
+    assert!(import_xlsx(&fixture("does_not_exist.xlsx")).is_err());
+}